@@ -0,0 +1,82 @@
+//! Times a timing run of each kernel with the SQLite perf log on and with
+//! `sim.fast`, and checks that fast mode reaches the same cycle count in
+//! less wall time. How much less depends on how much of the run goes to
+//! stats rather than to the memory model. Built with the `fast` feature,
+//! only the cycle counts are compared.
+//!
+//!     cargo bench --bench fast_mode
+//!     cargo bench --bench fast_mode --features fast
+
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use cyclotron::timeflow::STATS_COMPILED;
+use cyclotron::ui::{make_sim, read_toml, CyclotronArgs};
+
+const KERNELS: &[&str] = &["test/isa-tests/gemm_simt", "test/isa-tests/gemv"];
+const RUNS: usize = 5;
+
+/// Best wall time over `RUNS` runs, from building the sim to dropping it,
+/// and the cycle count they finished on.
+fn time_kernel(config: &str, elf: &str, fast: bool) -> (Duration, u64) {
+    let mut best = Duration::MAX;
+    let mut cycles = None;
+    for _ in 0..RUNS {
+        let args = CyclotronArgs {
+            config_path: PathBuf::from("config.toml"),
+            binary_path: Some(PathBuf::from(elf)),
+            timing: true,
+            fast,
+            ..CyclotronArgs::default()
+        };
+        // Setup and teardown count too: they open and flush the logs.
+        let start = Instant::now();
+        let mut sim = make_sim(Some(config), &Some(args));
+        sim.simulate().unwrap_or_else(|_| panic!("{elf} failed"));
+        let finished = sim.cycle();
+        drop(sim);
+        best = best.min(start.elapsed());
+        assert!(
+            cycles.replace(finished).is_none_or(|prev| prev == finished),
+            "{elf} is not deterministic"
+        );
+    }
+    (best, cycles.expect("RUNS is nonzero"))
+}
+
+fn main() {
+    // Keep the per-run perf logs out of the source tree.
+    std::env::set_var(
+        "CYCLOTRON_PERF_LOG_DIR",
+        std::env::temp_dir().join("cyclotron-bench-fast-mode"),
+    );
+    let config =
+        read_toml(PathBuf::from("config.toml").as_path()) + "\n[log.sqlite]\nenabled = true\n";
+
+    let mut results = Vec::new();
+    for &elf in KERNELS {
+        let (full, full_cycles) = time_kernel(&config, elf, false);
+        let (fast, fast_cycles) = time_kernel(&config, elf, true);
+        assert_eq!(
+            full_cycles, fast_cycles,
+            "{elf}: fast mode changed the cycle count"
+        );
+        results.push((elf, full_cycles, full, fast));
+    }
+
+    println!();
+    for (elf, cycles, full, fast) in &results {
+        println!(
+            "fast_mode: {elf}: {cycles} cycles, {:.3}s logged, {:.3}s fast ({:.2}x)",
+            full.as_secs_f64(),
+            fast.as_secs_f64(),
+            full.as_secs_f64() / fast.as_secs_f64()
+        );
+    }
+    // With the `fast` feature both runs skip the stats.
+    if STATS_COMPILED {
+        for (elf, _, full, fast) in &results {
+            assert!(fast < full, "{elf}: fast mode is not faster");
+        }
+    }
+}
@@ -1,12 +1,75 @@
 use anyhow::bail;
+use serde::Deserialize;
+
+/// Byte order of the values moved by `read_value` and `write_value`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Endianness {
+    #[default]
+    Little,
+    Big,
+}
+
+impl Endianness {
+    fn decode(self, bytes: &[u8]) -> u64 {
+        let fold = |value: u64, byte: &u8| value << 8 | u64::from(*byte);
+        match self {
+            Endianness::Little => bytes.iter().rev().fold(0, fold),
+            Endianness::Big => bytes.iter().fold(0, fold),
+        }
+    }
+
+    fn encode(self, value: u64, n: usize) -> Vec<u8> {
+        let bytes = value.to_le_bytes()[..n].to_vec();
+        match self {
+            Endianness::Little => bytes,
+            Endianness::Big => bytes.into_iter().rev().collect(),
+        }
+    }
+}
+
+/// Alignment a value access of `n` bytes needs: `n` rounded up to a power
+/// of two, so it never straddles a boundary of its own size.
+fn value_alignment(n: usize) -> usize {
+    assert!((1..=8).contains(&n), "value accesses are 1 to 8 bytes");
+    n.next_power_of_two()
+}
+
+/// Splits `[addr, addr + n)` into the largest naturally aligned pieces,
+/// each at most 8 bytes.
+fn aligned_pieces(addr: usize, n: usize) -> impl Iterator<Item = (usize, usize)> {
+    let end = addr + n;
+    let mut at = addr;
+    std::iter::from_fn(move || {
+        if at >= end {
+            return None;
+        }
+        let size = (1usize << at.trailing_zeros().min(3)).min(1 << (end - at).ilog2());
+        let piece = (at, size);
+        at += size;
+        Some(piece)
+    })
+}
 
 /// Trait for simulated memories in Cyclotron.
 pub trait HasMemory {
+    /// Width of the address space; accesses must fall below `1 << address_bits`.
+    fn address_bits(&self) -> u32 {
+        32
+    }
+    fn in_range(&self, addr: usize, n: usize) -> bool {
+        (addr + n - 1).checked_shr(self.address_bits()).unwrap_or(0) == 0
+    }
+
     fn read_impl(&self, addr: usize, n: usize) -> Result<&[u8], anyhow::Error>;
     fn read(&self, addr: usize, n: usize) -> Result<&[u8], anyhow::Error> {
         // cyclotron code itself must maintain these invariants
         assert!((n % 4 == 0) && n > 0, "word sized reads only");
-        assert!(addr + n - 1 < (1 << 32), "32-bit address range");
+        assert!(
+            self.in_range(addr, n),
+            "{}-bit address range",
+            self.address_bits()
+        );
 
         // program binary might try and do this, which we should flag as
         // correctness issue
@@ -25,7 +88,11 @@ pub trait HasMemory {
         let n: usize = data.len();
         let alignment: usize;
 
-        assert!(addr + n - 1 < (1 << 32), "32-bit address range");
+        assert!(
+            self.in_range(addr, n),
+            "{}-bit address range",
+            self.address_bits()
+        );
         if n < 4 {
             assert!(n == 1 || n == 2, "subword stores must be byte or half");
             alignment = n;
@@ -46,4 +113,74 @@ pub trait HasMemory {
     fn write_n<const N: usize>(&mut self, addr: usize, data: [u8; N]) -> Result<(), anyhow::Error> {
         self.write(addr, data.as_slice())
     }
+
+    /// Reads the `n`-byte value (1 to 8) at `addr` in `endianness` order.
+    /// `addr` must be aligned to `n` rounded up to a power of two.
+    fn read_value(
+        &self,
+        addr: usize,
+        n: usize,
+        endianness: Endianness,
+    ) -> Result<u64, anyhow::Error> {
+        if !addr.is_multiple_of(value_alignment(n)) {
+            bail!("unaligned memory read of size {} @ {:#08x}", n, addr);
+        }
+        self.read_value_split(addr, n, endianness)
+    }
+    /// Like `read_value`, but serves a misaligned read as several aligned
+    /// reads of its pieces.
+    fn read_value_split(
+        &self,
+        addr: usize,
+        n: usize,
+        endianness: Endianness,
+    ) -> Result<u64, anyhow::Error> {
+        value_alignment(n);
+        assert!(
+            self.in_range(addr, n),
+            "{}-bit address range",
+            self.address_bits()
+        );
+        let mut bytes = Vec::with_capacity(n);
+        for (at, size) in aligned_pieces(addr, n) {
+            bytes.extend_from_slice(self.read_impl(at, size)?);
+        }
+        Ok(endianness.decode(&bytes))
+    }
+
+    /// Writes the low `n` bytes (1 to 8) of `value` at `addr` in
+    /// `endianness` order, with the same alignment rule as `read_value`.
+    fn write_value(
+        &mut self,
+        addr: usize,
+        n: usize,
+        value: u64,
+        endianness: Endianness,
+    ) -> Result<(), anyhow::Error> {
+        if !addr.is_multiple_of(value_alignment(n)) {
+            bail!("unaligned memory write of size {} @ {:#08x}", n, addr);
+        }
+        self.write_value_split(addr, n, value, endianness)
+    }
+    /// Like `write_value`, but serves a misaligned write as several aligned
+    /// writes of its pieces.
+    fn write_value_split(
+        &mut self,
+        addr: usize,
+        n: usize,
+        value: u64,
+        endianness: Endianness,
+    ) -> Result<(), anyhow::Error> {
+        value_alignment(n);
+        assert!(
+            self.in_range(addr, n),
+            "{}-bit address range",
+            self.address_bits()
+        );
+        let bytes = endianness.encode(value, n);
+        for (at, size) in aligned_pieces(addr, n) {
+            self.write_impl(at, &bytes[at - addr..at - addr + size])?;
+        }
+        Ok(())
+    }
 }
@@ -1,7 +1,7 @@
 use crate::base::behavior::*;
 use std::sync::{Arc, OnceLock};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ModuleBase<T, C> {
     pub cycle: u64,
     pub frequency: u64,
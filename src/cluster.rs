@@ -1,18 +1,27 @@
 use crate::base::behavior::*;
 use crate::muon::core::MuonCore;
 use crate::neutrino::neutrino::Neutrino;
+use crate::sim::checkpoint::Shared;
 use crate::sim::flat_mem::FlatMemory;
+use crate::sim::gmem_race::{GmemEvent, GmemRaceChecker};
 use crate::sim::log::Logger;
 use crate::sim::perf_log::PerfLogSession;
+use crate::sim::smem_order::{SmemOrderCheck, SmemOrderChecker, SmemOrderStats};
 use crate::sim::top::ClusterConfig;
 use log::info;
-use std::sync::{Arc, RwLock};
+use std::sync::Arc;
 
+#[derive(Clone)]
 pub struct Cluster {
     id: usize,
     pub cores: Vec<MuonCore>,
     pub neutrino: Neutrino,
     scheduled_threadblocks: usize,
+    /// Checks the shared memory's store ordering; see `sim.smem_order`.
+    smem_order: Option<SmemOrderChecker>,
+    /// The race check over gmem, shared by every cluster; see
+    /// `sim.gmem_race`.
+    gmem_race: Option<Shared<GmemRaceChecker>>,
 }
 
 impl Cluster {
@@ -20,16 +29,16 @@ impl Cluster {
         config: Arc<ClusterConfig>,
         id: usize,
         logger: &Arc<Logger>,
-        gmem: Arc<RwLock<FlatMemory>>,
+        gmem: Shared<FlatMemory>,
     ) -> Self {
         let mut cores = Vec::new();
-        let shared_mem = Arc::new(RwLock::new(FlatMemory::new_with_size(
+        let shared_mem = Shared::new(FlatMemory::new_with_size(
             config.muon_config.smem_size,
             None,
-        )));
+        ));
         for cid in 0..config.muon_config.num_cores {
             cores.push(MuonCore::new(
-                Arc::new(config.muon_config),
+                Arc::new(config.core_muon_config(cid)),
                 id,
                 cid, // cluster-local
                 logger,
@@ -42,6 +51,8 @@ impl Cluster {
             cores,
             neutrino: Neutrino::new(Arc::new(config.neutrino_config)),
             scheduled_threadblocks: 0,
+            smem_order: None,
+            gmem_race: None,
         }
     }
 
@@ -49,25 +60,38 @@ impl Cluster {
         config: Arc<ClusterConfig>,
         id: usize,
         logger: &Arc<Logger>,
-        gmem: Arc<RwLock<FlatMemory>>,
-        gmem_timing: Arc<RwLock<crate::timeflow::ClusterGmemGraph>>,
+        gmem: Shared<FlatMemory>,
+        gmem_timing: Shared<crate::timeflow::ClusterGmemGraph>,
         perf_log_session: Option<Arc<PerfLogSession>>,
+        timed_cores: Option<&[usize]>,
     ) -> Self {
         let mut cores = Vec::new();
-        let shared_mem = Arc::new(RwLock::new(FlatMemory::new_with_size(
+        let shared_mem = Shared::new(FlatMemory::new_with_size(
             config.muon_config.smem_size,
             None,
-        )));
+        ));
         for cid in 0..config.muon_config.num_cores {
             let timing_core_id = id * config.muon_config.num_cores + cid;
+            if timed_cores.is_some_and(|timed| !timed.contains(&timing_core_id)) {
+                cores.push(MuonCore::new_functional(
+                    Arc::new(config.core_muon_config(cid)),
+                    id,
+                    cid,
+                    logger,
+                    gmem.clone(),
+                    shared_mem.clone(),
+                    timing_core_id,
+                ));
+                continue;
+            }
             cores.push(MuonCore::new_timed(
-                Arc::new(config.muon_config),
+                Arc::new(config.core_muon_config(cid)),
                 id,
                 cid,
                 logger,
                 gmem.clone(),
                 shared_mem.clone(),
-                config.timing_config.clone(),
+                config.core_timing_config(cid).clone(),
                 timing_core_id,
                 id,
                 gmem_timing.clone(),
@@ -79,9 +103,43 @@ impl Cluster {
             cores,
             neutrino: Neutrino::new(Arc::new(config.neutrino_config)),
             scheduled_threadblocks: 0,
+            smem_order: None,
+            gmem_race: None,
         }
     }
 
+    /// Starts checking that SMEM loads only see other warps' stores across
+    /// a barrier, or stops with `SmemOrderCheck::Off`.
+    pub fn set_smem_order_check(&mut self, action: SmemOrderCheck) {
+        let enabled = action != SmemOrderCheck::Off;
+        self.smem_order = enabled.then(|| {
+            let num_warps = self.cores.first().map_or(0, |core| core.conf().num_warps);
+            SmemOrderChecker::new(action, self.cores.len(), num_warps)
+        });
+        self.neutrino
+            .set_barrier_log(enabled || self.gmem_race.is_some());
+        for core in &mut self.cores {
+            core.set_smem_access_log(enabled);
+        }
+    }
+
+    /// Reports this cluster's gmem accesses, fences and barriers to
+    /// `checker`, or stops with `None`.
+    pub fn set_gmem_race_check(&mut self, checker: Option<Shared<GmemRaceChecker>>) {
+        let enabled = checker.is_some();
+        self.gmem_race = checker;
+        self.neutrino
+            .set_barrier_log(enabled || self.smem_order.is_some());
+        for core in &mut self.cores {
+            core.set_gmem_event_log(enabled);
+        }
+    }
+
+    /// What the SMEM ordering check found; `None` unless it is on.
+    pub fn smem_order_stats(&self) -> Option<&SmemOrderStats> {
+        self.smem_order.as_ref().map(SmemOrderChecker::stats)
+    }
+
     pub fn schedule_threadblock(&mut self) {
         assert_eq!(
             self.scheduled_threadblocks, 0,
@@ -91,6 +149,15 @@ impl Cluster {
         for core in &mut self.cores {
             core.spawn_single_warp();
         }
+        if let Some(checker) = &mut self.smem_order {
+            checker.reset();
+        }
+        if let Some(checker) = &self.gmem_race {
+            checker
+                .write()
+                .expect("gmem race checker lock poisoned")
+                .threadblock_start(self.id);
+        }
         self.scheduled_threadblocks += 1;
     }
 
@@ -98,6 +165,13 @@ impl Cluster {
         self.all_cores_retired() as usize
     }
 
+    /// Advances every core one cycle without issuing; see
+    /// `MuonCore::drain_step`.
+    pub fn drain_step(&mut self) {
+        self.cores.iter_mut().for_each(MuonCore::drain_step);
+        self.neutrino.tick_one();
+    }
+
     // TODO: This should differentiate between different threadblocks.
     pub fn all_cores_retired(&self) -> bool {
         self.cores
@@ -108,18 +182,58 @@ impl Cluster {
 
 impl ModuleBehaviors for Cluster {
     fn tick_one(&mut self) {
-        for core in &mut self.cores {
+        for (cid, core) in self.cores.iter_mut().enumerate() {
             core.tick_one();
             core.process(&mut self.neutrino).unwrap();
+            if let Some(checker) = &mut self.smem_order {
+                let cycle = core.time();
+                for access in core.take_smem_accesses() {
+                    checker.access(cycle, cid, &access);
+                }
+            }
+            if let Some(checker) = &self.gmem_race {
+                let cycle = core.time();
+                let mut checker = checker.write().expect("gmem race checker lock poisoned");
+                for event in core.take_gmem_events() {
+                    match event {
+                        GmemEvent::Access(line) => checker.access(cycle, self.id, cid, &line),
+                        GmemEvent::Fence { warp_id, order } => {
+                            checker.fence(self.id, cid, warp_id as usize, order)
+                        }
+                    }
+                }
+            }
         }
         self.neutrino.tick_one();
         self.neutrino
             .update(&mut self.cores.iter_mut().map(|c| &mut c.scheduler).collect());
+        if self.smem_order.is_some() || self.gmem_race.is_some() {
+            for arrived in self.neutrino.take_released_barriers() {
+                if let Some(checker) = &mut self.smem_order {
+                    checker.barrier(&arrived);
+                }
+                if let Some(checker) = &self.gmem_race {
+                    checker
+                        .write()
+                        .expect("gmem race checker lock poisoned")
+                        .barrier(self.id, &arrived);
+                }
+            }
+        }
     }
 
     fn reset(&mut self) {
         for core in &mut self.cores {
             core.reset();
         }
+        if let Some(checker) = &mut self.smem_order {
+            checker.reset();
+        }
+        if let Some(checker) = &self.gmem_race {
+            checker
+                .write()
+                .expect("gmem race checker lock poisoned")
+                .reset();
+        }
     }
 }
@@ -10,6 +10,7 @@ use std::sync::Arc;
 /// - Scheduling policy is round-robin across all available clusters, where
 ///   available clusters mean clusters that have free space in both the register file and the
 ///   shared memory that are larger than the static usage of a single threadblock
+#[derive(Clone)]
 pub struct CommandProcessor {
     base: ModuleBase<CommandProcessorState, MuonConfig>,
 }
@@ -19,7 +20,7 @@ const SMEM_USAGE_PER_BLOCK: isize = 32;
 const REGFILE_SIZE_PER_CLUSTER: isize = 64;
 const SMEM_SIZE_PER_CLUSTER: isize = 64;
 
-#[derive(Default)]
+#[derive(Clone, Default)]
 struct CommandProcessorState {
     remaining_threadblocks: isize,
     clusters: Vec<ClusterScheduleState>,
@@ -50,10 +51,10 @@ impl CommandProcessorState {
 }
 
 impl CommandProcessor {
-    pub fn new(_config: Arc<MuonConfig>, num_threadblocks: usize) -> Self {
+    pub fn new(_config: Arc<MuonConfig>, num_clusters: usize, num_threadblocks: usize) -> Self {
         CommandProcessor {
             base: ModuleBase::<CommandProcessorState, MuonConfig> {
-                state: CommandProcessorState::new(1 /*FIXME: num_clusters*/, num_threadblocks),
+                state: CommandProcessorState::new(num_clusters, num_threadblocks),
                 ..ModuleBase::default()
             },
         }
@@ -6,7 +6,9 @@ use std::{ffi::c_void, iter::zip, slice};
 
 use crate::{
     base::{behavior::Parameterizable, mem::HasMemory},
+    dpi::journaled,
     sim::flat_mem::FlatMemory,
+    sim::journal::{self, DpiEntry, JournalEvent},
 };
 
 struct LsuMemRequest {
@@ -118,6 +120,11 @@ impl LsuMemModel for BasicMemModel {
 pub extern "C" fn cyclotron_mem_init_rs(lsu_lanes: usize) -> *mut c_void {
     let mem_model = Box::new(BasicMemModel::new(lsu_lanes));
     let mem_model_ptr = Box::into_raw(mem_model);
+    journal::record(|| JournalEvent::Dpi {
+        entry: DpiEntry::MemInit,
+        scalars: vec![lsu_lanes as u64, mem_model_ptr as u64],
+        arrays: Vec::new(),
+    });
 
     mem_model_ptr as *mut c_void
 }
@@ -125,6 +132,11 @@ pub extern "C" fn cyclotron_mem_init_rs(lsu_lanes: usize) -> *mut c_void {
 #[no_mangle]
 // SAFETY: must be called at most once on a pointer returned from `cyclotron_mem_init_rs`
 pub extern "C" fn cyclotron_mem_free_rs(mem_model_ptr: *mut c_void) {
+    journal::record(|| JournalEvent::Dpi {
+        entry: DpiEntry::MemFree,
+        scalars: vec![mem_model_ptr as u64],
+        arrays: Vec::new(),
+    });
     let mem_model = unsafe { Box::from_raw(mem_model_ptr as *mut BasicMemModel) };
 
     drop(mem_model);
@@ -169,6 +181,22 @@ pub extern "C" fn cyclotron_mem_rs(
         )
     };
 
+    journal::record(|| JournalEvent::Dpi {
+        entry: DpiEntry::Mem,
+        scalars: vec![
+            mem_model_ptr as u64,
+            req_valid.into(),
+            req_store.into(),
+            req_tag.into(),
+            resp_ready.into(),
+        ],
+        arrays: vec![
+            journaled(req_address),
+            journaled(req_data),
+            journaled(req_mask),
+        ],
+    });
+
     mem_model.tick();
 
     if req_valid != 0 {
@@ -265,7 +293,7 @@ pub unsafe fn cyclotron_trace_mem_rs(
 
     *warp_id = line.warp_id as u8;
     for i in 0..config.num_lanes {
-        tmask_vec[i] = if (line.tmask & (1 << i)) != 0 { 1 } else { 0 };
+        tmask_vec[i] = line.tmask.bit(i) as u8;
         rs1_data[i] = line.rs1_data[i].unwrap_or(0);
         rs2_data[i] = line.rs2_data[i].unwrap_or(0);
         rd_addr[i] = line.rd_addr as u8;
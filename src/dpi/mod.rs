@@ -4,6 +4,8 @@ use crate::base::module::IsModule;
 use crate::dpi::tile::PipelineContext;
 use crate::muon::core::MuonCore;
 use crate::muon::decode::{DecodedInst, MicroOp};
+use crate::muon::lane_mask::LaneMask;
+use crate::sim::journal::{self, DpiEntry, JournalEvent};
 use crate::sim::top::Sim;
 use crate::sim::trace;
 use crate::sim::trace_db::{create_new_db_overwrite, default_trace_db_path};
@@ -44,6 +46,8 @@ struct Context {
     prev_rtl_finished: Vec<bool>,
     executed_insts: Vec<usize>,
     difftested_insts: usize,
+    /// Ibuffer heads the frontend exposed last cycle.
+    frontend_heads: Vec<IbufHead>,
 }
 
 impl Context {
@@ -89,6 +93,55 @@ struct MemQueueLine {
 }
 type MemQueue = VecDeque<MemQueueLine>;
 
+/// One warp's ibuffer head as exposed to the RTL, laid out for
+/// `cyclotron_frontend_batch_rs` to return one per warp per cycle.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct IbufHead {
+    pub valid: u8,
+    pub wid: u8,
+    pub op: u8,
+    pub opext: u8,
+    pub f3: u8,
+    pub rd_addr: u8,
+    pub rs1_addr: u8,
+    pub rs2_addr: u8,
+    pub rs3_addr: u8,
+    pub f7: u8,
+    pub csr_imm: u8,
+    pub pc: u32,
+    pub imm32: u32,
+    pub imm24: u32,
+    pub tmask: u32,
+    pub raw: u64,
+}
+
+impl IbufHead {
+    fn from_line(line: &Option<trace::Line>) -> Self {
+        let Some(line) = line else {
+            return Self::default();
+        };
+        Self {
+            valid: 1,
+            wid: line.warp_id as u8,
+            op: line.opcode,
+            opext: line.opext,
+            f3: line.f3,
+            rd_addr: line.rd_addr,
+            rs1_addr: line.rs1_addr,
+            rs2_addr: line.rs2_addr,
+            rs3_addr: line.rs3_addr,
+            f7: line.f7,
+            csr_imm: line.csr_imm,
+            pc: line.pc,
+            imm32: line.imm32,
+            imm24: line.imm24 as u32,
+            tmask: line.tmask.low_u32(),
+            raw: line.raw,
+        }
+    }
+}
+
 /// Global singleton to maintain simulator context across independent DPI calls.
 static CELL: RwLock<Option<Context>> = RwLock::new(None);
 thread_local! {
@@ -96,6 +149,11 @@ thread_local! {
     static TRACE_CONN: RefCell<Option<Connection>> = RefCell::new(None);
 }
 
+/// Widens a DPI input array for the journal.
+fn journaled<T: Copy + Into<u64>>(values: &[T]) -> Vec<u64> {
+    values.iter().map(|&value| value.into()).collect()
+}
+
 pub fn assert_single_core(sim: &Sim) {
     assert!(
         sim.top.clusters.len() == 1,
@@ -133,9 +191,29 @@ pub extern "C" fn cyclotron_init_rs(c_elfname: *const c_char, c_trace_db_path: *
         if c_trace_db_path.is_null() {
             String::new()
         } else {
-            CStr::from_ptr(c_trace_db_path).to_string_lossy().into_owned()
+            CStr::from_ptr(c_trace_db_path)
+                .to_string_lossy()
+                .into_owned()
         }
     };
+
+    if let Ok(path) = std::env::var("CYCLOTRON_RECORD") {
+        match journal::start_recording(std::path::Path::new(&path)) {
+            Ok(()) => println!("Cyclotron: recording DPI journal to {}", path),
+            Err(err) => eprintln!("Cyclotron: cannot record DPI journal to {}: {}", path, err),
+        }
+    }
+    journal::record(|| JournalEvent::DpiInit {
+        elf: elfname.clone(),
+        trace_db: trace_db_path_arg.clone(),
+        config: toml_string.clone(),
+    });
+    init_context(elfname, trace_db_path_arg, toml_string);
+}
+
+/// Builds the DPI context from the `cyclotron_init_rs` arguments and the
+/// config.toml contents.
+fn init_context(elfname: String, trace_db_path_arg: String, toml_string: Option<String>) {
     let mut cyclotron_args = CyclotronArgs::default();
     if !elfname.is_empty() {
         cyclotron_args.binary_path = Some(PathBuf::from(&elfname));
@@ -158,6 +236,11 @@ pub extern "C" fn cyclotron_init_rs(c_elfname: *const c_char, c_trace_db_path: *
         "Cyclotron: created sim object with config: [clusters={} cores={} warps={} lanes={}]",
         num_clusters, config.num_cores, config.num_warps, config.num_lanes
     );
+    // the RTL interface carries 32-bit thread masks
+    assert!(
+        config.num_lanes <= 32,
+        "co-simulation supports at most 32 lanes per warp"
+    );
 
     let final_elfname = sim_isa.config.elf.as_path();
     println!("Cyclotron: loading ELF file: {}", final_elfname.display());
@@ -174,6 +257,7 @@ pub extern "C" fn cyclotron_init_rs(c_elfname: *const c_char, c_trace_db_path: *
         prev_rtl_finished: vec![false; NUM_CLUSTERS * CORES_PER_CLUSTER],
         executed_insts: vec![0; NUM_CLUSTERS * CORES_PER_CLUSTER],
         difftested_insts: 0,
+        frontend_heads: vec![IbufHead::default(); config.num_warps],
     };
     c.sim_isa.top.reset();
     c.sim_be.top.reset();
@@ -207,6 +291,18 @@ pub unsafe fn cyclotron_imem_rs(
     imem_resp_bits_tag_ptr: *mut u8,
     imem_resp_bits_data_ptr: *mut u64,
 ) {
+    journal::record(|| JournalEvent::Dpi {
+        entry: DpiEntry::Imem,
+        scalars: vec![
+            imem_req_valid.into(),
+            imem_req_bits_store.into(),
+            imem_req_bits_address.into(),
+            imem_req_bits_size.into(),
+            imem_req_bits_tag.into(),
+            imem_resp_ready.into(),
+        ],
+        arrays: Vec::new(),
+    });
     let mut context_guard = CELL.write().unwrap();
     let context = context_guard
         .as_mut()
@@ -245,6 +341,11 @@ pub unsafe extern "C" fn cyclotron_fetch_rs(
     resp_bits_tag_ptr: *mut u64,
     resp_bits_inst_ptr: *mut u64,
 ) {
+    journal::record(|| JournalEvent::Dpi {
+        entry: DpiEntry::Fetch,
+        scalars: vec![req_valid.into(), req_bits_tag, req_bits_pc.into()],
+        arrays: Vec::new(),
+    });
     let mut context_guard = CELL.write().unwrap();
     let context = context_guard
         .as_mut()
@@ -304,6 +405,20 @@ pub unsafe extern "C" fn cyclotron_gmem_rs(
     let req_bits_mask = std::slice::from_raw_parts(req_bits_mask_ptr, num_lanes);
     // ignore upstream ready
     let _resp_ready = std::slice::from_raw_parts(resp_ready_ptr, num_lanes);
+    journal::record(|| JournalEvent::Dpi {
+        entry: DpiEntry::Gmem,
+        scalars: Vec::new(),
+        arrays: vec![
+            journaled(req_valid),
+            journaled(req_bits_store),
+            journaled(req_bits_address),
+            journaled(req_bits_size),
+            journaled(req_bits_tag),
+            journaled(req_bits_data),
+            journaled(req_bits_mask),
+            journaled(_resp_ready),
+        ],
+    });
     let resp_valid = std::slice::from_raw_parts_mut(resp_valid_ptr, num_lanes);
     let resp_bits_tag = std::slice::from_raw_parts_mut(resp_bits_tag_ptr, num_lanes);
     let resp_bits_data = std::slice::from_raw_parts_mut(resp_bits_data_ptr, num_lanes);
@@ -339,7 +454,7 @@ pub unsafe extern "C" fn cyclotron_gmem_rs(
         }
 
         if !is_store {
-            let data = top.gmem_load(address_aligned);
+            let data = top.gmem_load(address_aligned.into());
             // 1-cycle latency
             resp_valid[lane] = 1;
             resp_bits_tag[lane] = req_bits_tag[lane];
@@ -410,32 +525,110 @@ pub unsafe extern "C" fn cyclotron_frontend_rs(
     let context = context_guard
         .as_mut()
         .expect("DPI context not initialized!");
-    let sim = &mut context.sim_isa;
+    let num_warps = context.sim_isa.top.clusters[0].cores[0].conf().num_warps;
+    // SAFETY: precondition of function guarantees this is valid
+    let ready = unsafe { std::slice::from_raw_parts(ibuf_ready_vec, num_warps) };
+    let (heads, sim_finished) = frontend_cycle(context, ready);
+
+    let valid = unsafe { std::slice::from_raw_parts_mut(ibuf_valid_vec, num_warps) };
+    let tmask = unsafe { std::slice::from_raw_parts_mut(ibuf_tmask_vec, num_warps) };
+    let pc = unsafe { std::slice::from_raw_parts_mut(ibuf_pc_vec, num_warps) };
+    let wid = unsafe { std::slice::from_raw_parts_mut(ibuf_wid_vec, num_warps) };
+    let op = unsafe { std::slice::from_raw_parts_mut(ibuf_op_vec, num_warps) };
+    let opext = unsafe { std::slice::from_raw_parts_mut(ibuf_opext_vec, num_warps) };
+    let f3 = unsafe { std::slice::from_raw_parts_mut(ibuf_f3_vec, num_warps) };
+    let rd_addr = unsafe { std::slice::from_raw_parts_mut(ibuf_rd_addr_vec, num_warps) };
+    let rs1_addr = unsafe { std::slice::from_raw_parts_mut(ibuf_rs1_addr_vec, num_warps) };
+    let rs2_addr = unsafe { std::slice::from_raw_parts_mut(ibuf_rs2_addr_vec, num_warps) };
+    let rs3_addr = unsafe { std::slice::from_raw_parts_mut(ibuf_rs3_addr_vec, num_warps) };
+    let f7 = unsafe { std::slice::from_raw_parts_mut(ibuf_f7_vec, num_warps) };
+    let imm32 = unsafe { std::slice::from_raw_parts_mut(ibuf_imm32_vec, num_warps) };
+    let imm24 = unsafe { std::slice::from_raw_parts_mut(ibuf_imm24_vec, num_warps) };
+    let csr_imm = unsafe { std::slice::from_raw_parts_mut(ibuf_csr_imm_vec, num_warps) };
+    let raw = unsafe { std::slice::from_raw_parts_mut(ibuf_raw_vec, num_warps) };
+    let finished = unsafe { finished_ptr.as_mut().expect("pointer was null") };
+
+    for (w, head) in heads.iter().enumerate() {
+        valid[w] = head.valid;
+        if head.valid == 0 {
+            continue;
+        }
+        tmask[w] = head.tmask;
+        pc[w] = head.pc;
+        wid[w] = head.wid;
+        op[w] = head.op;
+        opext[w] = head.opext;
+        f3[w] = head.f3;
+        rd_addr[w] = head.rd_addr;
+        rs1_addr[w] = head.rs1_addr;
+        rs2_addr[w] = head.rs2_addr;
+        rs3_addr[w] = head.rs3_addr;
+        f7[w] = head.f7;
+        imm32[w] = head.imm32;
+        imm24[w] = head.imm24;
+        csr_imm[w] = head.csr_imm;
+        raw[w] = head.raw;
+    }
+    *finished = sim_finished as u8;
+}
 
+#[no_mangle]
+/// Batched `cyclotron_frontend_rs`: advances the frontend by up to `num_cycles` cycles under one
+/// DPI call, so frontend-only cosim doesn't pay an FFI crossing per cycle.  `ready_vec` holds one
+/// per-warp ready vector per cycle, back to back.  Cycle `i`'s ibuffer heads are written to
+/// `heads[i * num_warps..(i + 1) * num_warps]` and its finished flag to `finished_vec[i]`.
+/// With `stop_on_change` set, the call returns after the first cycle whose heads differ from the
+/// cycle before it, or that finishes, so the RTL can react before supplying more ready vectors.
+/// Returns the number of cycles run; entries past it are left untouched.
+///
+/// # Safety
+/// `ready_vec` and `heads` are arrays of size num_cycles * num_warps, and `finished_vec` of size
+/// num_cycles.
+pub unsafe extern "C" fn cyclotron_frontend_batch_rs(
+    num_cycles: u32,
+    stop_on_change: u8,
+    ready_vec: *const u8,
+    heads: *mut IbufHead,
+    finished_vec: *mut u8,
+) -> u32 {
+    let mut context_guard = CELL.write().unwrap();
+    let context = context_guard
+        .as_mut()
+        .expect("DPI context not initialized!");
+    let num_warps = context.sim_isa.top.clusters[0].cores[0].conf().num_warps;
+    let num_cycles = num_cycles as usize;
+    // SAFETY: precondition of function guarantees this is valid
+    let ready = unsafe { std::slice::from_raw_parts(ready_vec, num_cycles * num_warps) };
+    let heads = unsafe { std::slice::from_raw_parts_mut(heads, num_cycles * num_warps) };
+    let finished = unsafe { std::slice::from_raw_parts_mut(finished_vec, num_cycles) };
+
+    let per_cycle = zip(ready.chunks(num_warps), heads.chunks_mut(num_warps)).zip(finished);
+    for (cycle, ((ready, heads), finished)) in per_cycle.enumerate() {
+        let prev_heads = context.frontend_heads.clone();
+        let (cycle_heads, sim_finished) = frontend_cycle(context, ready);
+        heads.copy_from_slice(&cycle_heads);
+        *finished = sim_finished as u8;
+        if stop_on_change != 0 && (sim_finished || cycle_heads != prev_heads) {
+            return cycle as u32 + 1;
+        }
+    }
+    num_cycles as u32
+}
+
+/// One cycle of the frontend: hands the instructions the RTL took (`ready`) to the issue queue,
+/// advances the ISA model, and returns the new per-warp ibuffer heads and whether the run is
+/// finished.
+fn frontend_cycle(context: &mut Context, ready: &[u8]) -> (Vec<IbufHead>, bool) {
+    journal::record(|| JournalEvent::Dpi {
+        entry: DpiEntry::Frontend,
+        scalars: Vec::new(),
+        arrays: vec![journaled(ready)],
+    });
+    let sim = &mut context.sim_isa;
     assert_single_core(sim);
     let core_id = 0;
     let core = &mut sim.top.clusters[0].cores[core_id];
-    let config = core.conf().clone();
-
-    // SAFETY: precondition of function guarantees this is valid
-    let ready = unsafe { std::slice::from_raw_parts(ibuf_ready_vec, config.num_warps) };
-    let valid = unsafe { std::slice::from_raw_parts_mut(ibuf_valid_vec, config.num_warps) };
-    let tmask = unsafe { std::slice::from_raw_parts_mut(ibuf_tmask_vec, config.num_warps) };
-    let pc = unsafe { std::slice::from_raw_parts_mut(ibuf_pc_vec, config.num_warps) };
-    let wid = unsafe { std::slice::from_raw_parts_mut(ibuf_wid_vec, config.num_warps) };
-    let op = unsafe { std::slice::from_raw_parts_mut(ibuf_op_vec, config.num_warps) };
-    let opext = unsafe { std::slice::from_raw_parts_mut(ibuf_opext_vec, config.num_warps) };
-    let f3 = unsafe { std::slice::from_raw_parts_mut(ibuf_f3_vec, config.num_warps) };
-    let rd_addr = unsafe { std::slice::from_raw_parts_mut(ibuf_rd_addr_vec, config.num_warps) };
-    let rs1_addr = unsafe { std::slice::from_raw_parts_mut(ibuf_rs1_addr_vec, config.num_warps) };
-    let rs2_addr = unsafe { std::slice::from_raw_parts_mut(ibuf_rs2_addr_vec, config.num_warps) };
-    let rs3_addr = unsafe { std::slice::from_raw_parts_mut(ibuf_rs3_addr_vec, config.num_warps) };
-    let f7 = unsafe { std::slice::from_raw_parts_mut(ibuf_f7_vec, config.num_warps) };
-    let imm32 = unsafe { std::slice::from_raw_parts_mut(ibuf_imm32_vec, config.num_warps) };
-    let imm24 = unsafe { std::slice::from_raw_parts_mut(ibuf_imm24_vec, config.num_warps) };
-    let csr_imm = unsafe { std::slice::from_raw_parts_mut(ibuf_csr_imm_vec, config.num_warps) };
-    let raw = unsafe { std::slice::from_raw_parts_mut(ibuf_raw_vec, config.num_warps) };
-    let finished = unsafe { finished_ptr.as_mut().expect("pointer was null") };
+    let num_warps = core.conf().num_warps;
 
     // upon RTL fire, consume the instruction line in the tracer queue, and move it to the issue
     // queue.  This has to happen before the sim::tick() call below, so that it respects the
@@ -448,32 +641,7 @@ pub unsafe extern "C" fn cyclotron_frontend_rs(
 
     // peek again and expose the new head to the RTL
     let core = &mut sim.top.clusters[0].cores[0];
-    let new_heads = peek_heads(core, config.num_warps);
-    for (w, o_line) in new_heads.iter().enumerate() {
-        match o_line {
-            Some(line) => {
-                valid[w] = 1;
-                tmask[w] = line.tmask;
-                pc[w] = line.pc;
-                wid[w] = line.warp_id as u8;
-                op[w] = line.opcode;
-                opext[w] = line.opext;
-                f3[w] = line.f3;
-                rd_addr[w] = line.rd_addr;
-                rs1_addr[w] = line.rs1_addr;
-                rs2_addr[w] = line.rs2_addr;
-                rs3_addr[w] = line.rs3_addr;
-                f7[w] = line.f7;
-                imm32[w] = line.imm32;
-                imm24[w] = line.imm24 as u32;
-                csr_imm[w] = line.csr_imm;
-                raw[w] = line.raw;
-            }
-            None => {
-                valid[w] = 0;
-            }
-        }
-    }
+    let new_heads = peek_heads(core, num_warps);
 
     // debug
     if false {
@@ -487,8 +655,10 @@ pub unsafe extern "C" fn cyclotron_frontend_rs(
         }
     }
 
+    let heads: Vec<IbufHead> = new_heads.iter().map(IbufHead::from_line).collect();
+    context.frontend_heads = heads.clone();
     let sim_finished = sim.finished();
-    *finished = context.finish_after_timeout(sim_finished) as u8;
+    (heads, context.finish_after_timeout(sim_finished))
 }
 
 /// Issue a decoded instruction bundle to the backend model, and get the writeback bundle back.
@@ -533,6 +703,28 @@ pub unsafe fn cyclotron_backend_rs(
     writeback_ipdom_else_pc_ptr: *mut u32,
     finished_ptr: *mut u8,
 ) {
+    journal::record(|| JournalEvent::Dpi {
+        entry: DpiEntry::Backend,
+        scalars: vec![
+            issue_valid.into(),
+            issue_warp_id.into(),
+            issue_pc.into(),
+            issue_op.into(),
+            issue_opext.into(),
+            issue_f3.into(),
+            issue_rd_addr.into(),
+            issue_rs1_addr.into(),
+            issue_rs2_addr.into(),
+            issue_rs3_addr.into(),
+            issue_f7.into(),
+            issue_imm32.into(),
+            issue_imm24.into(),
+            issue_csr_imm.into(),
+            issue_tmask.into(),
+            issue_raw_inst,
+        ],
+        arrays: Vec::new(),
+    });
     let mut context_guard = CELL.write().unwrap();
     let context = context_guard
         .as_mut()
@@ -651,7 +843,7 @@ pub unsafe fn cyclotron_backend_rs(
         .backend(
             MicroOp {
                 inst: decoded,
-                tmask: issue_tmask,
+                tmask: LaneMask::from_u64(issue_tmask as u64),
             },
             &mut core.scheduler,
             neutrino,
@@ -665,12 +857,12 @@ pub unsafe fn cyclotron_backend_rs(
 
     *writeback_valid = 1u8;
     *writeback_pc = writeback.inst.pc;
-    *writeback_tmask = writeback.tmask;
+    *writeback_tmask = writeback.tmask.low_u32();
     *writeback_wid = issue_warp_id;
     *writeback_set_pc_valid = writeback.sched_wb.pc.is_some() as u8;
     *writeback_set_pc = writeback.sched_wb.pc.unwrap_or(0);
     *writeback_set_tmask_valid = writeback.sched_wb.tmask.is_some() as u8;
-    *writeback_set_tmask = writeback.sched_wb.tmask.unwrap_or(0);
+    *writeback_set_tmask = writeback.sched_wb.tmask.map_or(0, |m| m.low_u32());
     *writeback_wspawn_valid = writeback.sched_wb.wspawn_pc_count.is_some() as u8;
     *writeback_wspawn_count = writeback.sched_wb.wspawn_pc_count.unwrap_or((0, 0)).1;
     *writeback_wspawn_pc = writeback.sched_wb.wspawn_pc_count.unwrap_or((0, 0)).0;
@@ -678,13 +870,11 @@ pub unsafe fn cyclotron_backend_rs(
     *writeback_ipdom_restored_mask = writeback
         .sched_wb
         .ipdom_push
-        .map(|x| x.restored_mask)
-        .unwrap_or(0);
+        .map_or(0, |x| x.restored_mask.low_u32());
     *writeback_ipdom_else_mask = writeback
         .sched_wb
         .ipdom_push
-        .map(|x| x.else_mask)
-        .unwrap_or(0);
+        .map_or(0, |x| x.else_mask.low_u32());
     *writeback_ipdom_else_pc = writeback
         .sched_wb
         .ipdom_push
@@ -769,6 +959,42 @@ pub unsafe extern "C" fn cyclotron_trace_rs(
     let smem_resp_valid = unsafe { from_raw_parts(smem_resp_valid_vec, num_lanes) };
     let smem_resp_bits_tag = unsafe { from_raw_parts(smem_resp_bits_tag_vec, num_lanes) };
     let smem_resp_bits_data = unsafe { from_raw_parts(smem_resp_bits_data_vec, num_lanes) };
+    journal::record(|| JournalEvent::Dpi {
+        entry: DpiEntry::Trace,
+        scalars: vec![
+            cluster_id.into(),
+            core_id.into(),
+            cycle,
+            inst_valid.into(),
+            inst_pc.into(),
+            inst_warp_id.into(),
+        ],
+        arrays: vec![
+            journaled(inst_rs1_data),
+            journaled(inst_rs2_data),
+            journaled(_inst_rs3_data),
+            journaled(dmem_req_valid),
+            journaled(dmem_req_bits_store),
+            journaled(dmem_req_bits_address),
+            journaled(dmem_req_bits_size),
+            journaled(dmem_req_bits_tag),
+            journaled(dmem_req_bits_data),
+            journaled(_dmem_req_bits_mask),
+            journaled(dmem_resp_valid),
+            journaled(dmem_resp_bits_tag),
+            journaled(dmem_resp_bits_data),
+            journaled(smem_req_valid),
+            journaled(smem_req_bits_store),
+            journaled(smem_req_bits_address),
+            journaled(smem_req_bits_size),
+            journaled(smem_req_bits_tag),
+            journaled(smem_req_bits_data),
+            journaled(_smem_req_bits_mask),
+            journaled(smem_resp_valid),
+            journaled(smem_resp_bits_tag),
+            journaled(smem_resp_bits_data),
+        ],
+    });
 
     // print instruction progress
     if inst_valid != 0 {
@@ -1071,6 +1297,27 @@ pub unsafe extern "C" fn cyclotron_difftest_reg_rs(
     let cluster_id = cluster_id as usize;
     let core_id = core_id as usize;
     let config = sim.top.clusters[cluster_id].cores[core_id].conf().clone();
+    journal::record(|| JournalEvent::Dpi {
+        entry: DpiEntry::DifftestReg,
+        scalars: vec![
+            sim_tick.into(),
+            cluster_id as u64,
+            core_id as u64,
+            valid.into(),
+            pc.into(),
+            warp_id.into(),
+            tmask.into(),
+            rs1_enable.into(),
+            rs1_address.into(),
+            rs2_enable.into(),
+            rs2_address.into(),
+            rs3_enable.into(),
+            rs3_address.into(),
+        ],
+        arrays: [rs1_data_vec, rs2_data_vec, rs3_data_vec]
+            .map(|data| journaled(unsafe { std::slice::from_raw_parts(data, config.num_lanes) }))
+            .to_vec(),
+    });
 
     // runs regardless of valid == 0/1 to ensure model run-ahead of rtl
     if sim_tick == 1 {
@@ -1110,8 +1357,7 @@ pub unsafe extern "C" fn cyclotron_difftest_reg_rs(
         }
 
         // compare tmask
-        let num_lane_mask = !(!(0u32) << config.num_lanes);
-        let model_tmask = line.inst.tmask & num_lane_mask;
+        let model_tmask = (line.inst.tmask & LaneMask::all(config.num_lanes)).low_u32();
         if model_tmask != tmask {
             println!(
                 "DIFFTEST fail: tmask mismatch, cluster:{}, core:{}, warp:{}, pc:{:x}, rtl:{:x}, model:{:x}",
@@ -1273,6 +1519,21 @@ pub unsafe extern "C" fn profile_perf_counters_rs(
     let core = &mut sim.top.clusters[0].cores[0];
     let config = core.conf().clone();
     let global_core_id = cluster_id as usize * CORES_PER_CLUSTER + core_id as usize;
+    journal::record(|| JournalEvent::Dpi {
+        entry: DpiEntry::ProfilePerfCounters,
+        scalars: vec![
+            cluster_id.into(), core_id.into(), inst_retired, cycles, cycles_decoded,
+            cycles_dispatched, cycles_eligible, cycles_issued, finished.into(),
+        ],
+        arrays: [
+            per_warp_cycles_decoded_ptr, per_warp_cycles_dispatched_ptr,
+            per_warp_cycles_eligible_ptr, per_warp_cycles_issued_ptr, per_warp_stalls_waw_ptr,
+            per_warp_stalls_war_ptr, per_warp_stalls_scoreboard_ptr, per_warp_stalls_rs_full_ptr,
+            per_warp_stalls_busy_ptr, per_warp_stalls_busy_lsu_ptr,
+        ]
+        .map(|counts| unsafe { std::slice::from_raw_parts(counts, config.num_warps) }.to_vec())
+        .to_vec(),
+    });
 
     // only report at rising-edge
     let prev_rtl_finished = context.prev_rtl_finished[global_core_id];
@@ -1351,4 +1612,7 @@ pub unsafe extern "C" fn profile_perf_counters_rs(
 }
 
 mod mem_model;
+mod replay;
 mod tile;
+
+pub use replay::replay;
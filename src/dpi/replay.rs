@@ -0,0 +1,380 @@
+//! Replays a recorded DPI journal without the RTL simulator: each recorded
+//! call is made again with the inputs the RTL passed, into scratch output
+//! buffers, so a cosimulation failure reproduces in a standalone process.
+
+use std::collections::HashMap;
+use std::ffi::c_void;
+
+use crate::dpi::mem_model::{cyclotron_mem_free_rs, cyclotron_mem_init_rs, cyclotron_mem_rs};
+use crate::dpi::tile::cyclotron_tile_tick_rs;
+use crate::dpi::*;
+use crate::sim::journal::{self, DpiEntry, JournalEvent};
+
+/// Output buffers for one replayed call. Each is as wide as the widest
+/// array the entrypoint writes.
+struct Scratch {
+    width: usize,
+    u8s: Vec<Vec<u8>>,
+    u32s: Vec<Vec<u32>>,
+    u64s: Vec<Vec<u64>>,
+}
+
+impl Scratch {
+    fn new(width: usize) -> Self {
+        Self {
+            width: width.max(1),
+            u8s: Vec::new(),
+            u32s: Vec::new(),
+            u64s: Vec::new(),
+        }
+    }
+
+    fn u8(&mut self) -> *mut u8 {
+        self.u8s.push(vec![0; self.width]);
+        self.u8s.last_mut().unwrap().as_mut_ptr()
+    }
+
+    fn u32(&mut self) -> *mut u32 {
+        self.u32s.push(vec![0; self.width]);
+        self.u32s.last_mut().unwrap().as_mut_ptr()
+    }
+
+    fn u64(&mut self) -> *mut u64 {
+        self.u64s.push(vec![0; self.width]);
+        self.u64s.last_mut().unwrap().as_mut_ptr()
+    }
+}
+
+fn u8s(values: &[u64]) -> Vec<u8> {
+    values.iter().map(|&value| value as u8).collect()
+}
+
+fn u32s(values: &[u64]) -> Vec<u32> {
+    values.iter().map(|&value| value as u32).collect()
+}
+
+/// Widest lane or warp array of the context's core.
+fn context_width() -> usize {
+    let context_guard = CELL.read().unwrap();
+    let context = context_guard
+        .as_ref()
+        .expect("DPI context not initialized!");
+    let config = context.sim_isa.top.clusters[0].cores[0].conf();
+    config.num_lanes.max(config.num_warps)
+}
+
+/// Replays a journal that starts with `cyclotron_init_rs`.
+pub fn replay(events: Vec<JournalEvent>) -> Result<(), u32> {
+    let mut events = events.into_iter();
+    let Some(JournalEvent::DpiInit {
+        elf,
+        trace_db,
+        config,
+    }) = events.next()
+    else {
+        eprintln!("cyclotron: journal does not start with cyclotron_init_rs");
+        return Err(1);
+    };
+    journal::start_replay(events.collect());
+    init_context(elf, trace_db, config);
+
+    // recorded mem model handle -> replayed one
+    let mut mem_models = HashMap::new();
+    let mut calls = 0usize;
+    while let Some(event) = journal::next_replayed() {
+        let JournalEvent::Dpi {
+            entry,
+            scalars,
+            arrays,
+        } = event
+        else {
+            eprintln!("cyclotron: replay diverged at DPI call {calls}: unexpected {event:?}");
+            return Err(1);
+        };
+        unsafe { dispatch(entry, &scalars, &arrays, &mut mem_models) };
+        calls += 1;
+    }
+    println!("Cyclotron: replayed {} DPI calls", calls);
+    Ok(())
+}
+
+unsafe fn dispatch(
+    entry: DpiEntry,
+    s: &[u64],
+    a: &[Vec<u64>],
+    mem_models: &mut HashMap<u64, *mut c_void>,
+) {
+    match entry {
+        DpiEntry::Imem => {
+            let mut out = Scratch::new(1);
+            cyclotron_imem_rs(
+                out.u8(),
+                s[0] as u8,
+                s[1] as u8,
+                s[2] as u32,
+                s[3] as u8,
+                s[4] as u8,
+                0,
+                0,
+                s[5] as u8,
+                out.u8(),
+                out.u8(),
+                out.u64(),
+            );
+        }
+        DpiEntry::Fetch => {
+            let mut out = Scratch::new(1);
+            cyclotron_fetch_rs(
+                s[0] as u8,
+                s[1],
+                s[2] as u32,
+                out.u8(),
+                out.u64(),
+                out.u64(),
+            );
+        }
+        DpiEntry::Gmem => {
+            let mut out = Scratch::new(context_width());
+            let (valid, store, address) = (u8s(&a[0]), u8s(&a[1]), u32s(&a[2]));
+            let (size, tag, data) = (u8s(&a[3]), u32s(&a[4]), u32s(&a[5]));
+            let (mask, resp_ready) = (u8s(&a[6]), u8s(&a[7]));
+            cyclotron_gmem_rs(
+                valid.as_ptr(),
+                out.u8(),
+                store.as_ptr(),
+                address.as_ptr(),
+                size.as_ptr(),
+                tag.as_ptr(),
+                data.as_ptr(),
+                mask.as_ptr(),
+                resp_ready.as_ptr(),
+                out.u8(),
+                out.u32(),
+                out.u32(),
+            );
+        }
+        DpiEntry::Frontend => {
+            let mut out = Scratch::new(context_width());
+            let ready = u8s(&a[0]);
+            cyclotron_frontend_rs(
+                ready.as_ptr(),
+                out.u8(),
+                out.u32(),
+                out.u8(),
+                out.u8(),
+                out.u8(),
+                out.u8(),
+                out.u8(),
+                out.u8(),
+                out.u8(),
+                out.u8(),
+                out.u8(),
+                out.u32(),
+                out.u32(),
+                out.u8(),
+                out.u32(),
+                out.u64(),
+                out.u8(),
+            );
+        }
+        DpiEntry::Backend => {
+            let mut out = Scratch::new(context_width());
+            cyclotron_backend_rs(
+                s[0] as u8,
+                s[1] as u8,
+                s[2] as u32,
+                s[3] as u8,
+                s[4] as u8,
+                s[5] as u8,
+                s[6] as u8,
+                s[7] as u8,
+                s[8] as u8,
+                s[9] as u8,
+                out.u32(),
+                out.u32(),
+                out.u32(),
+                s[10] as u8,
+                s[11] as u32,
+                s[12] as u32,
+                s[13] as u8,
+                out.u32(),
+                s[14] as u32,
+                s[15],
+                out.u8(),
+                out.u32(),
+                out.u32(),
+                out.u8(),
+                out.u8(),
+                out.u32(),
+                out.u8(),
+                out.u32(),
+                out.u8(),
+                out.u32(),
+                out.u8(),
+                out.u32(),
+                out.u32(),
+                out.u8(),
+                out.u32(),
+                out.u32(),
+                out.u32(),
+                out.u8(),
+            );
+        }
+        DpiEntry::Trace => {
+            let rs = [u32s(&a[0]), u32s(&a[1]), u32s(&a[2])];
+            let port = |base: usize| {
+                (
+                    [u8s(&a[base]), u8s(&a[base + 1])],
+                    u32s(&a[base + 2]),
+                    u8s(&a[base + 3]),
+                    u32s(&a[base + 4]),
+                    u32s(&a[base + 5]),
+                    [u8s(&a[base + 6]), u8s(&a[base + 7])],
+                    [u32s(&a[base + 8]), u32s(&a[base + 9])],
+                )
+            };
+            let dmem = port(3);
+            let smem = port(13);
+            cyclotron_trace_rs(
+                s[0] as u32,
+                s[1] as u32,
+                s[2],
+                s[3] as u8,
+                s[4] as u32,
+                s[5] as u32,
+                0,
+                0,
+                0,
+                rs[0].as_ptr(),
+                0,
+                0,
+                rs[1].as_ptr(),
+                0,
+                0,
+                rs[2].as_ptr(),
+                dmem.0[0].as_ptr(),
+                dmem.0[1].as_ptr(),
+                dmem.1.as_ptr(),
+                dmem.2.as_ptr(),
+                dmem.3.as_ptr(),
+                dmem.4.as_ptr(),
+                dmem.5[0].as_ptr(),
+                dmem.5[1].as_ptr(),
+                dmem.6[0].as_ptr(),
+                dmem.6[1].as_ptr(),
+                smem.0[0].as_ptr(),
+                smem.0[1].as_ptr(),
+                smem.1.as_ptr(),
+                smem.2.as_ptr(),
+                smem.3.as_ptr(),
+                smem.4.as_ptr(),
+                smem.5[0].as_ptr(),
+                smem.5[1].as_ptr(),
+                smem.6[0].as_ptr(),
+                smem.6[1].as_ptr(),
+            );
+        }
+        DpiEntry::DifftestReg => {
+            let rs = [u32s(&a[0]), u32s(&a[1]), u32s(&a[2])];
+            cyclotron_difftest_reg_rs(
+                s[0] as u8,
+                s[1] as u32,
+                s[2] as u32,
+                s[3] as u8,
+                s[4] as u32,
+                s[5] as u32,
+                s[6] as u32,
+                s[7] as u8,
+                s[8] as u32,
+                rs[0].as_ptr(),
+                s[9] as u8,
+                s[10] as u32,
+                rs[1].as_ptr(),
+                s[11] as u8,
+                s[12] as u32,
+                rs[2].as_ptr(),
+            );
+        }
+        DpiEntry::ProfilePerfCounters => {
+            profile_perf_counters_rs(
+                s[0] as u32,
+                s[1] as u32,
+                s[2],
+                s[3],
+                s[4],
+                s[5],
+                s[6],
+                s[7],
+                a[0].as_ptr(),
+                a[1].as_ptr(),
+                a[2].as_ptr(),
+                a[3].as_ptr(),
+                a[4].as_ptr(),
+                a[5].as_ptr(),
+                a[6].as_ptr(),
+                a[7].as_ptr(),
+                a[8].as_ptr(),
+                a[9].as_ptr(),
+                s[8] as u8,
+            );
+        }
+        DpiEntry::TileTick => {
+            let mut out = Scratch::new(context_width());
+            let (req_ready, resp_valid) = (u8s(&a[0]), u8s(&a[1]));
+            let (resp_tag, resp_data) = (u32s(&a[2]), u32s(&a[3]));
+            cyclotron_tile_tick_rs(
+                out.u8(),
+                s[0] as u8,
+                out.u32(),
+                out.u64(),
+                out.u8(),
+                s[1] as u8,
+                s[2],
+                s[3],
+                out.u8(),
+                req_ready.as_ptr(),
+                out.u8(),
+                out.u32(),
+                out.u8(),
+                out.u32(),
+                out.u32(),
+                out.u8(),
+                out.u8(),
+                resp_valid.as_ptr(),
+                resp_tag.as_ptr(),
+                resp_data.as_ptr(),
+                out.u8(),
+            );
+        }
+        DpiEntry::MemInit => {
+            mem_models.insert(s[1], cyclotron_mem_init_rs(s[0] as usize));
+        }
+        DpiEntry::MemFree => {
+            if let Some(model) = mem_models.remove(&s[0]) {
+                cyclotron_mem_free_rs(model);
+            }
+        }
+        DpiEntry::Mem => {
+            let model = *mem_models
+                .get(&s[0])
+                .expect("journal uses a mem model it never created");
+            let mut out = Scratch::new(a[0].len());
+            let (address, data, mask) = (u32s(&a[0]), u32s(&a[1]), u8s(&a[2]));
+            cyclotron_mem_rs(
+                model,
+                out.u8(),
+                s[1] as u8,
+                s[2] as u8,
+                s[3] as u32,
+                address.as_ptr(),
+                data.as_ptr(),
+                mask.as_ptr(),
+                s[4] as u8,
+                out.u8(),
+                out.u32(),
+                out.u32(),
+                out.u8(),
+            );
+        }
+    }
+}
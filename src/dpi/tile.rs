@@ -1,7 +1,8 @@
 use crate::base::behavior::*;
-use crate::dpi::CELL;
+use crate::dpi::{journaled, CELL};
 use crate::muon::scheduler::Schedule;
 use crate::muon::warp::{ExWriteback, MemResponse};
+use crate::sim::journal::{self, DpiEntry, JournalEvent};
 use std::iter::zip;
 use std::slice::{from_raw_parts, from_raw_parts_mut};
 
@@ -91,6 +92,21 @@ pub unsafe extern "C" fn cyclotron_tile_tick_rs(
     let dmem_resp_valid = unsafe { from_raw_parts(dmem_resp_valid_vec, num_lanes) };
     let dmem_resp_bits_tag = unsafe { from_raw_parts(dmem_resp_bits_tag_vec, num_lanes) };
     let dmem_resp_bits_data = unsafe { from_raw_parts(dmem_resp_bits_data_vec, num_lanes) };
+    journal::record(|| JournalEvent::Dpi {
+        entry: DpiEntry::TileTick,
+        scalars: vec![
+            imem_req_ready.into(),
+            imem_resp_valid.into(),
+            imem_resp_bits_tag,
+            imem_resp_bits_data,
+        ],
+        arrays: vec![
+            journaled(dmem_req_ready),
+            journaled(dmem_resp_valid),
+            journaled(dmem_resp_bits_tag),
+            journaled(dmem_resp_bits_data),
+        ],
+    });
     let finished = unsafe { finished_ptr.as_mut().expect("pointer was null") };
 
     // pin to default
@@ -161,15 +177,18 @@ pub unsafe extern "C" fn cyclotron_tile_tick_rs(
                 if let Some(req) = req {
                     dmem_req_valid[i] = 1u8;
                     dmem_req_bits_store[i] = req.is_store as u8;
-                    dmem_req_bits_address[i] = req.addr;
+                    // The RTL dmem port is 32 bits wide; gmem windows are
+                    // not modelled in co-sim.
+                    let addr = req.addr as u32;
+                    dmem_req_bits_address[i] = addr;
                     dmem_req_bits_size[i] = logsize(req.size);
                     dmem_req_bits_tag[i] = 0; // 1 outstanding
                     dmem_req_bits_data[i] = if req.is_store {
-                        make_tilelink_data(req.addr, req.data.expect("no data for store req"))
+                        make_tilelink_data(addr, req.data.expect("no data for store req"))
                     } else {
                         0
                     };
-                    dmem_req_bits_mask[i] = make_tilelink_mask(req.addr, req.size);
+                    dmem_req_bits_mask[i] = make_tilelink_mask(addr, req.size);
                 }
             }
         } else {
@@ -199,7 +218,8 @@ pub unsafe extern "C" fn cyclotron_tile_tick_rs(
 
             let req = req.as_ref().expect("dmem response on an invalid req!");
             assert!(dmem_resp_bits_tag[i] == 0, "dmem req and resp tag mismatch");
-            let data = dmem_resp_bits_data[i].to_le_bytes();
+            // RTL returns the aligned word; responses carry the value itself.
+            let data = (dmem_resp_bits_data[i] >> ((req.addr & 3) * 8)).to_le_bytes();
             let resp = MemResponse {
                 data: (!req.is_store).then_some(data),
                 is_sext: req.is_sext,
@@ -6,6 +6,7 @@ pub mod dpi;
 pub mod memfuzz;
 pub mod muon;
 pub mod neutrino;
+pub mod prelude;
 pub mod sim;
 pub mod timeflow;
 pub mod timeq;
@@ -6,9 +6,20 @@ use cyclotron::ui::*;
 pub fn main() -> Result<(), u32> {
     env_logger::init();
 
+    if let Some(command) = parse_command() {
+        return run_command(command);
+    }
+
     let argv = CyclotronArgs::parse();
+    if let Some(events) = start_journal(&argv)? {
+        return cyclotron::dpi::replay(events);
+    }
     let toml_string = read_toml(argv.config_path.as_path());
+    cyclotron::sim::dump::install_signal_handler();
     let mut sim = make_sim(Some(&toml_string), &Some(argv));
     let result = sim.simulate();
+    if sim.interrupted_at().is_some() {
+        std::process::exit(cyclotron::sim::dump::INTERRUPTED_EXIT_CODE);
+    }
     result
 }
@@ -1,8 +1,21 @@
+use crate::base::mem::Endianness;
 use crate::sim::config::Config;
 use serde::Deserialize;
 
+/// What a load or store whose address is not a multiple of its size does.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MisalignedAccess {
+    /// Take a misaligned-access trap with `traps`, otherwise stop the run.
+    #[default]
+    Trap,
+    /// Serve the access as several aligned accesses.
+    Emulate,
+}
+
 #[derive(Debug, Deserialize, Clone, Copy)]
 #[serde(default)]
+#[non_exhaustive]
 pub struct MuonConfig {
     pub num_lanes: usize,
     pub num_warps: usize,
@@ -10,6 +23,21 @@ pub struct MuonConfig {
     pub num_regs: usize,
     pub start_pc: u32,
     pub smem_size: usize,
+    /// Base of a global address window mapped onto shared memory. Plain
+    /// loads and stores in `[smem_base_addr, smem_base_addr + smem_size)` then access
+    /// shared memory at their offset into the window, like `.shared` ones.
+    pub smem_base_addr: Option<u32>,
+    /// With timing enabled, hold the data of a shared-memory load until its
+    /// SMEM timing completion arrives instead of returning it at issue.
+    pub smem_data_coupled: bool,
+    /// Take machine-mode traps to `mtvec` on illegal instructions and
+    /// misaligned loads and stores, instead of stopping the simulation.
+    pub traps: bool,
+    /// See `MisalignedAccess`.
+    pub misaligned: MisalignedAccess,
+    /// Byte order of data loads and stores. Instruction fetch stays
+    /// little-endian.
+    pub endianness: Endianness,
     #[serde(skip)]
     pub lane_config: LaneConfig,
 }
@@ -25,6 +53,11 @@ impl Default for MuonConfig {
             num_regs: 256,
             start_pc: 0x10000000u32,
             smem_size: 0x10_0000, // includes MMIO space
+            smem_base_addr: None,
+            smem_data_coupled: false,
+            traps: false,
+            misaligned: MisalignedAccess::Trap,
+            endianness: Endianness::Little,
             lane_config: LaneConfig::default(),
         }
     }
@@ -1,38 +1,64 @@
 use crate::base::{behavior::*, module::*};
 use crate::info;
 use crate::muon::config::{LaneConfig, MuonConfig};
-use crate::muon::decode::{InstBuf, IssuedInst};
-use crate::muon::gmem::{CorePerfSummary, CoreTimingModel};
+use crate::muon::decode::{InstBuf, IssuedInst, MicroOp};
+use crate::muon::execute::Opcode;
+use crate::muon::gmem::{
+    CorePerfSummary, CoreTimingModel, CpiStack, InstLifecycle, SchedDecision, SharedIssuePolicy,
+};
+use crate::muon::lane_mask::{LaneMask, MAX_LANES};
 use crate::muon::scheduler::{Schedule, Scheduler};
 use crate::muon::warp::{ExecErr, Warp, Writeback};
 use crate::neutrino::neutrino::Neutrino;
+use crate::sim::checkpoint::Shared;
+use crate::sim::dump::{CoreStateDump, IbufferEntryDump, WarpStateDump};
 use crate::sim::flat_mem::FlatMemory;
+use crate::sim::gmem_race::GmemEvent;
 use crate::sim::log::Logger;
 use crate::sim::perf_log::PerfLogSession;
-use crate::sim::trace::{MemTracer, Tracer};
-use crate::timeflow::{ClusterGmemGraph, CoreGraphConfig};
+use crate::sim::trace::{MemTraceLine, MemTracer, Tracer};
+use crate::timeflow::fence::{decode_fence, FenceScope};
+use crate::timeflow::{ClusterGmemGraph, CoreGraph, CoreGraphConfig, GmemCompletion};
 use crate::timeq::module_now;
+use crate::utils::BitSlice;
 use std::iter::zip;
-use std::sync::{Arc, RwLock};
+use std::sync::Arc;
 
-#[derive(Debug, Default)]
+#[derive(Debug, Clone, Default)]
 pub struct MuonState {}
 
+#[derive(Clone)]
 pub struct MuonCore {
     base: ModuleBase<MuonState, MuonConfig>,
     pub scheduler: Scheduler,
     pub warps: Vec<Warp>,
-    pub(crate) shared_mem: Arc<RwLock<FlatMemory>>,
+    pub(crate) shared_mem: Shared<FlatMemory>,
 
     logger: Arc<Logger>,
     tracer: Arc<Tracer>,
     mem_tracer: Arc<MemTracer>,
+    /// SMEM accesses since the last `take_smem_accesses`; `None` unless
+    /// enabled.
+    smem_accesses: Option<Vec<MemTraceLine>>,
+    /// gmem accesses and fences since the last `take_gmem_events`; `None`
+    /// unless enabled.
+    gmem_events: Option<Vec<GmemEvent>>,
     timing_mode: TimingMode,
+    /// ibuffer heads from the last `process()` call, kept for state dumps
+    last_ibuf: Vec<Option<MicroOp>>,
 }
 
+#[derive(Clone)]
 enum TimingMode {
     Disabled,
     Enabled(CoreTimingModel),
+    /// Untimed core in a timed run: executes like `Disabled` but counts
+    /// issued instructions so it still shows up in the perf summary.
+    Functional {
+        timing_core_id: usize,
+        cluster_id: usize,
+        cpi_stack: CpiStack,
+    },
 }
 
 impl MuonCore {
@@ -41,10 +67,14 @@ impl MuonCore {
         cluster_id: usize,
         core_id: usize,
         logger: &Arc<Logger>,
-        gmem: Arc<RwLock<FlatMemory>>,
-        shared_mem: Arc<RwLock<FlatMemory>>,
+        gmem: Shared<FlatMemory>,
+        shared_mem: Shared<FlatMemory>,
         timing_mode: TimingMode,
     ) -> Self {
+        assert!(
+            (1..=MAX_LANES).contains(&config.num_lanes),
+            "num_lanes must be between 1 and {MAX_LANES}"
+        );
         let num_warps = config.num_warps;
         let mut core = MuonCore {
             base: Default::default(),
@@ -70,7 +100,10 @@ impl MuonCore {
             logger: logger.clone(),
             tracer: Arc::new(Tracer::new(&config)),
             mem_tracer: Arc::new(MemTracer::new()),
+            smem_accesses: None,
+            gmem_events: None,
             timing_mode,
+            last_ibuf: vec![None; num_warps],
         };
 
         info!(
@@ -87,8 +120,8 @@ impl MuonCore {
         cluster_id: usize,
         core_id: usize,
         logger: &Arc<Logger>,
-        gmem: Arc<RwLock<FlatMemory>>,
-        shared_mem: Arc<RwLock<FlatMemory>>,
+        gmem: Shared<FlatMemory>,
+        shared_mem: Shared<FlatMemory>,
     ) -> Self {
         Self::build_core(
             config,
@@ -106,12 +139,12 @@ impl MuonCore {
         cluster_id: usize,
         core_id: usize,
         logger: &Arc<Logger>,
-        gmem: Arc<RwLock<FlatMemory>>,
-        shared_mem: Arc<RwLock<FlatMemory>>,
+        gmem: Shared<FlatMemory>,
+        shared_mem: Shared<FlatMemory>,
         timing_config: CoreGraphConfig,
         timing_core_id: usize,
         timing_cluster_id: usize,
-        cluster_gmem: Arc<RwLock<ClusterGmemGraph>>,
+        cluster_gmem: Shared<ClusterGmemGraph>,
         perf_log_session: Option<Arc<PerfLogSession>>,
     ) -> Self {
         let num_warps = config.num_warps;
@@ -135,6 +168,32 @@ impl MuonCore {
         )
     }
 
+    /// A core that runs without a timing graph alongside timed ones;
+    /// `timing_core_id` is its global id in the perf summary.
+    pub fn new_functional(
+        config: Arc<MuonConfig>,
+        cluster_id: usize,
+        core_id: usize,
+        logger: &Arc<Logger>,
+        gmem: Shared<FlatMemory>,
+        shared_mem: Shared<FlatMemory>,
+        timing_core_id: usize,
+    ) -> Self {
+        Self::build_core(
+            config,
+            cluster_id,
+            core_id,
+            logger,
+            gmem,
+            shared_mem,
+            TimingMode::Functional {
+                timing_core_id,
+                cluster_id,
+                cpi_stack: CpiStack::default(),
+            },
+        )
+    }
+
     /// Spawn a single warp to this core.
     pub fn spawn_single_warp(&mut self) {
         self.scheduler.spawn_single_warp()
@@ -161,13 +220,53 @@ impl MuonCore {
 
     pub fn has_timing_inflight(&self) -> bool {
         match &self.timing_mode {
-            TimingMode::Disabled => false,
+            TimingMode::Disabled | TimingMode::Functional { .. } => false,
+            TimingMode::Enabled(timing_model) => {
+                timing_model.outstanding_gmem() > 0
+                    || timing_model.outstanding_smem() > 0
+                    || timing_model.outstanding_neutrino() > 0
+            }
+        }
+    }
+
+    /// Whether the timing model has nothing at all in flight; see
+    /// `CoreTimingModel::is_drained`. Always true without timing.
+    pub fn timing_drained(&self) -> bool {
+        match &self.timing_mode {
+            TimingMode::Disabled | TimingMode::Functional { .. } => true,
             TimingMode::Enabled(timing_model) => {
-                timing_model.outstanding_gmem() > 0 || timing_model.outstanding_smem() > 0
+                timing_model.is_drained(module_now(&self.scheduler))
             }
         }
     }
 
+    /// Advances the core one cycle without fetching or issuing: only the
+    /// timing model ticks, so work already in flight keeps completing.
+    pub fn drain_step(&mut self) {
+        self.tick_one();
+        if let TimingMode::Enabled(timing_model) = &mut self.timing_mode {
+            let now = module_now(&self.scheduler);
+            if !timing_model.is_drained(now) {
+                timing_model.tick(now, &mut self.scheduler);
+            }
+        }
+        self.scheduler.tick_one();
+        self.warps.iter_mut().for_each(Warp::tick_one);
+    }
+
+    /// Whether warp slot `wid` can take a new warp: its previous warp has
+    /// exited and, with timing enabled, every request it left in flight has
+    /// completed.
+    pub fn warp_slot_free(&self, wid: usize) -> bool {
+        if self.scheduler.active_warp_mask().bit(wid) {
+            return false;
+        }
+        match &self.timing_mode {
+            TimingMode::Disabled | TimingMode::Functional { .. } => true,
+            TimingMode::Enabled(timing_model) => !timing_model.warp_draining(wid),
+        }
+    }
+
     /// Schedule all warps and get per-warp PC/tmasks.
     pub fn schedule(&mut self) -> Vec<Option<Schedule>> {
         (0..self.conf().num_warps)
@@ -195,7 +294,7 @@ impl MuonCore {
                     ibuf_entries.push(entry);
                 }
             }
-            TimingMode::Disabled => {
+            TimingMode::Disabled | TimingMode::Functional { .. } => {
                 for (wid, sched_opt) in schedules.iter().enumerate() {
                     let entry = sched_opt.map(|sched| self.warps[wid].frontend(sched));
                     ibuf_entries.push(entry);
@@ -218,22 +317,51 @@ impl MuonCore {
             TimingMode::Enabled(timing_model) => {
                 timing_model.tick(now, &mut self.scheduler);
 
-                let eligible = ibuf
-                    .0
-                    .iter()
-                    .map(|entry| entry.is_some())
+                // Loads held for their SMEM completion finish first; their
+                // warps sit out issue until the cycle after.
+                let mut finished_loads = self
+                    .warps
+                    .iter_mut()
+                    .enumerate()
+                    .map(|(wid, warp)| {
+                        if !warp.has_deferred_load() || timing_model.has_pending_smem(wid) {
+                            return None;
+                        }
+                        let mut shared_mem =
+                            shared_mem.write().expect("shared memory lock poisoned");
+                        warp.finish_deferred_load(&mut shared_mem)
+                    })
+                    .collect::<Vec<_>>();
+
+                let eligible = zip(&ibuf.0, zip(&self.warps, &finished_loads))
+                    .map(|(entry, (warp, finished))| {
+                        entry.is_some() && !warp.has_deferred_load() && finished.is_none()
+                    })
                     .collect::<Vec<_>>();
-                let issue_mask = timing_model.select_issue_mask(now, &eligible);
+                let issue_mask = timing_model.select_issue_mask(now, &eligible, &self.scheduler);
                 let active_warps = self.scheduler.active_warp_mask().count_ones();
                 let eligible_warps = eligible.iter().filter(|v| **v).count() as u32;
                 let issued_warps = issue_mask.iter().filter(|v| **v).count() as u32;
-                timing_model.record_issue_stats(now, active_warps, eligible_warps, issued_warps);
+                timing_model.record_issue_stats(
+                    now,
+                    active_warps,
+                    eligible_warps,
+                    issued_warps,
+                    &self.scheduler,
+                );
 
                 let warps = self.warps.iter_mut();
                 let ibuf_entries = ibuf.0.iter().copied();
 
                 for (wid, (warp, ibuf_entry)) in zip(warps, ibuf_entries).enumerate() {
+                    let finished = finished_loads[wid].take();
                     let wb_opt = match ibuf_entry {
+                        Some(_) if finished.is_some() => {
+                            self.scheduler
+                                .set_resource_wait_until(wid, Some(now.saturating_add(1)));
+                            self.scheduler.replay_instruction(wid);
+                            finished
+                        }
                         Some(ib) => {
                             if !issue_mask.get(wid).copied().unwrap_or(false) {
                                 self.scheduler
@@ -253,7 +381,7 @@ impl MuonCore {
                                 )?
                             }
                         }
-                        None => None,
+                        None => finished,
                     };
                     if let Some((_, warp_mem_trace_lines)) = &wb_opt {
                         mem_trace_lines.extend(warp_mem_trace_lines.iter().cloned());
@@ -262,7 +390,7 @@ impl MuonCore {
                     writebacks.push(wb_opt);
                 }
             }
-            TimingMode::Disabled => {
+            TimingMode::Disabled | TimingMode::Functional { .. } => {
                 let warps = self.warps.iter_mut();
                 let ibuf_entries = ibuf.0.iter().copied();
 
@@ -286,13 +414,48 @@ impl MuonCore {
                     let wb_opt = wb_opt.map(|(wb, _)| wb);
                     writebacks.push(wb_opt);
                 }
+                if let TimingMode::Functional { cpi_stack, .. } = &mut self.timing_mode {
+                    let issued = writebacks.iter().filter(|wb| wb.is_some()).count() as u64;
+                    if issued > 0 {
+                        cpi_stack.record_issue(issued);
+                    } else if self.scheduler.active_warp_mask() == 0 {
+                        cpi_stack.record_idle();
+                    } else {
+                        cpi_stack.record_other();
+                    }
+                }
             }
         }
 
-        let tracer = Arc::get_mut(&mut self.tracer).expect("failed to get tracer");
+        let tracer = Arc::make_mut(&mut self.tracer);
         tracer.record(&writebacks);
-        let mem_tracer = Arc::get_mut(&mut self.mem_tracer).expect("failed to get mem tracer");
+        let mem_tracer = Arc::make_mut(&mut self.mem_tracer);
         mem_tracer.record(&mem_trace_lines);
+        if let Some(events) = &mut self.gmem_events {
+            events.extend(
+                mem_trace_lines
+                    .iter()
+                    .filter(|line| !line.is_smem)
+                    .cloned()
+                    .map(GmemEvent::Access),
+            );
+            for (wid, wb) in writebacks.iter().enumerate() {
+                let Some(wb) = wb else { continue };
+                if wb.inst.opcode != Opcode::MISC_MEM || wb.inst.f3 == 1 {
+                    continue;
+                }
+                let (scope, order) = decode_fence(wb.inst.f3, wb.inst.imm32);
+                if scope != FenceScope::Shared {
+                    events.push(GmemEvent::Fence {
+                        warp_id: wid as u32,
+                        order,
+                    });
+                }
+            }
+        }
+        if let Some(accesses) = &mut self.smem_accesses {
+            accesses.extend(mem_trace_lines.into_iter().filter(|line| line.is_smem));
+        }
 
         self.scheduler.tick_one();
         self.warps.iter_mut().for_each(Warp::tick_one);
@@ -305,7 +468,7 @@ impl MuonCore {
         &mut self,
         warp_id: usize,
         issued: IssuedInst,
-        tmask: u32,
+        tmask: LaneMask,
         neutrino: &mut Neutrino,
     ) -> Writeback {
         let shared_mem = Arc::clone(&self.shared_mem);
@@ -327,6 +490,7 @@ impl MuonCore {
     pub fn process(&mut self, neutrino: &mut Neutrino) -> Result<(), ExecErr> {
         let schedules = self.schedule();
         let ibuf = self.frontend(&schedules);
+        self.last_ibuf.clone_from(&ibuf.0);
         self.backend(&ibuf, neutrino)
     }
 
@@ -337,7 +501,7 @@ impl MuonCore {
     }
 
     pub fn get_tracer_mut(&mut self) -> &mut Tracer {
-        Arc::get_mut(&mut self.tracer).expect("failed to get tracer")
+        Arc::make_mut(&mut self.tracer)
     }
 
     pub fn get_tracer(&self) -> &Tracer {
@@ -345,19 +509,227 @@ impl MuonCore {
     }
 
     pub fn get_mem_tracer_mut(&mut self) -> &mut MemTracer {
-        Arc::get_mut(&mut self.mem_tracer).expect("failed to get mem tracer")
+        Arc::make_mut(&mut self.mem_tracer)
+    }
+
+    /// Starts or stops keeping the per-lane SMEM accesses
+    /// `take_smem_accesses` returns.
+    pub fn set_smem_access_log(&mut self, enabled: bool) {
+        match (enabled, self.smem_accesses.is_some()) {
+            (true, false) => self.smem_accesses = Some(Vec::new()),
+            (false, true) => self.smem_accesses = None,
+            _ => {}
+        }
+    }
+
+    /// SMEM accesses, one per lane, since the last call, in the order they
+    /// read or wrote memory.
+    pub fn take_smem_accesses(&mut self) -> Vec<MemTraceLine> {
+        self.smem_accesses
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    /// Starts or stops keeping the gmem accesses and fences
+    /// `take_gmem_events` returns.
+    pub fn set_gmem_event_log(&mut self, enabled: bool) {
+        match (enabled, self.gmem_events.is_some()) {
+            (true, false) => self.gmem_events = Some(Vec::new()),
+            (false, true) => self.gmem_events = None,
+            _ => {}
+        }
+    }
+
+    /// gmem accesses, one per lane, and gmem fences since the last call.
+    pub fn take_gmem_events(&mut self) -> Vec<GmemEvent> {
+        self.gmem_events
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
     }
 
     pub fn get_mem_tracer(&self) -> &MemTracer {
         &self.mem_tracer
     }
 
+    /// Gmem and smem requests the timing model has in flight; zero without
+    /// timing.
+    pub fn outstanding_timing_requests(&self) -> (usize, usize) {
+        match &self.timing_mode {
+            TimingMode::Disabled | TimingMode::Functional { .. } => (0, 0),
+            TimingMode::Enabled(timing_model) => (
+                timing_model.outstanding_gmem(),
+                timing_model.outstanding_smem(),
+            ),
+        }
+    }
+
+    /// Cycles charged to each CPI bucket so far; `None` without timing.
+    pub fn cpi_stack(&self) -> Option<CpiStack> {
+        match &self.timing_mode {
+            TimingMode::Disabled => None,
+            TimingMode::Enabled(timing_model) => Some(timing_model.cpi_stack()),
+            TimingMode::Functional { cpi_stack, .. } => Some(*cpi_stack),
+        }
+    }
+
+    /// Whether warp `wid` had an instruction at the ibuffer head on the last
+    /// `process()` call.
+    pub fn ibuffer_valid(&self, wid: usize) -> bool {
+        self.last_ibuf.get(wid).is_some_and(Option::is_some)
+    }
+
+    pub fn clear_timing_stats(&mut self) {
+        match &mut self.timing_mode {
+            TimingMode::Disabled => {}
+            TimingMode::Enabled(timing_model) => timing_model.clear_stats(),
+            TimingMode::Functional { cpi_stack, .. } => *cpi_stack = CpiStack::default(),
+        }
+    }
+
+    pub fn clear_shared_timing_stats(&mut self) {
+        if let TimingMode::Enabled(timing_model) = &mut self.timing_mode {
+            timing_model.clear_shared_stats();
+        }
+    }
+
+    /// See `CoreTimingModel::core_graph_mut`; `None` without timing.
+    pub fn core_graph_mut(&mut self) -> Option<&mut CoreGraph> {
+        match &mut self.timing_mode {
+            TimingMode::Disabled | TimingMode::Functional { .. } => None,
+            TimingMode::Enabled(timing_model) => Some(timing_model.core_graph_mut()),
+        }
+    }
+
+    /// See `CoreTimingModel::set_gmem_completion_log`; no-op without timing.
+    pub fn set_gmem_completion_log(&mut self, enabled: bool) {
+        if let TimingMode::Enabled(timing_model) = &mut self.timing_mode {
+            timing_model.set_gmem_completion_log(enabled);
+        }
+    }
+
+    pub fn take_gmem_completions(&mut self) -> Vec<GmemCompletion> {
+        match &mut self.timing_mode {
+            TimingMode::Disabled | TimingMode::Functional { .. } => Vec::new(),
+            TimingMode::Enabled(timing_model) => timing_model.take_gmem_completions(),
+        }
+    }
+
+    /// See `CoreTimingModel::set_context`; no-op without timing.
+    pub fn set_context(&mut self, context: usize) {
+        if let TimingMode::Enabled(timing_model) = &mut self.timing_mode {
+            timing_model.set_context(context);
+        }
+    }
+
+    /// See `CoreTimingModel::set_warp_context`; no-op without timing.
+    pub fn set_warp_context(&mut self, warp: usize, context: usize) {
+        if let TimingMode::Enabled(timing_model) = &mut self.timing_mode {
+            timing_model.set_warp_context(warp, context);
+        }
+    }
+
+    /// See `CoreTimingModel::set_stats`; no-op without timing.
+    pub fn set_stats(&mut self, enabled: bool) {
+        if let TimingMode::Enabled(timing_model) = &mut self.timing_mode {
+            timing_model.set_stats(enabled);
+        }
+    }
+
+    /// See `CoreTimingModel::set_pipeview`; no-op without timing.
+    pub fn set_pipeview(&mut self, enabled: bool) {
+        if let TimingMode::Enabled(timing_model) = &mut self.timing_mode {
+            timing_model.set_pipeview(enabled);
+        }
+    }
+
+    pub fn take_pipeview(&mut self) -> Vec<InstLifecycle> {
+        match &mut self.timing_mode {
+            TimingMode::Disabled | TimingMode::Functional { .. } => Vec::new(),
+            TimingMode::Enabled(timing_model) => timing_model.take_pipeview(),
+        }
+    }
+
+    /// See `CoreTimingModel::set_sched_log`; no-op without timing.
+    pub fn set_sched_log(&mut self, enabled: bool) {
+        if let TimingMode::Enabled(timing_model) = &mut self.timing_mode {
+            timing_model.set_sched_log(enabled);
+        }
+    }
+
+    pub fn take_sched_log(&mut self) -> Vec<SchedDecision> {
+        match &mut self.timing_mode {
+            TimingMode::Disabled | TimingMode::Functional { .. } => Vec::new(),
+            TimingMode::Enabled(timing_model) => timing_model.take_sched_log(),
+        }
+    }
+
+    /// See `CoreTimingModel::set_issue_policy`; no-op without timing.
+    pub fn set_issue_policy(&mut self, policy: Option<SharedIssuePolicy>) {
+        if let TimingMode::Enabled(timing_model) = &mut self.timing_mode {
+            timing_model.set_issue_policy(policy);
+        }
+    }
+
+    pub fn set_timing(&mut self, config: &CoreGraphConfig) {
+        if let TimingMode::Enabled(timing_model) = &mut self.timing_mode {
+            timing_model.set_timing(config);
+        }
+    }
+
+    pub fn set_shared_timing(&mut self, config: &CoreGraphConfig) {
+        if let TimingMode::Enabled(timing_model) = &mut self.timing_mode {
+            timing_model.set_shared_timing(config);
+        }
+    }
+
+    pub fn dump_state(&self, cluster_id: usize, core_id: usize) -> CoreStateDump {
+        let warps =
+            (0..self.warps.len())
+                .map(|wid| WarpStateDump {
+                    warp_id: wid,
+                    schedule: self.scheduler.warp_state(wid),
+                    ibuffer: self.last_ibuf.get(wid).copied().flatten().map(|uop| {
+                        IbufferEntryDump {
+                            pc: uop.inst.pc,
+                            raw: uop.inst.raw,
+                            tmask: uop.tmask,
+                        }
+                    }),
+                    timing: match &self.timing_mode {
+                        TimingMode::Disabled | TimingMode::Functional { .. } => None,
+                        TimingMode::Enabled(timing_model) => Some(timing_model.warp_state(wid)),
+                    },
+                })
+                .collect();
+        CoreStateDump {
+            cluster_id,
+            core_id,
+            cycle: module_now(&self.scheduler),
+            active_warps: self.scheduler.active_warp_mask(),
+            stalled_warps: self.scheduler.stalled_warp_mask(),
+            warps,
+        }
+    }
+
     pub fn timing_summary(&self) -> CorePerfSummary {
         match &self.timing_mode {
             TimingMode::Disabled => {
                 panic!("timing summary requested while timing mode is disabled")
             }
             TimingMode::Enabled(timing_model) => timing_model.perf_summary(),
+            TimingMode::Functional {
+                timing_core_id,
+                cluster_id,
+                cpi_stack,
+            } => CorePerfSummary {
+                core_id: *timing_core_id,
+                cluster_id: *cluster_id,
+                functional: true,
+                cpi_stack: *cpi_stack,
+                ..CorePerfSummary::default()
+            },
         }
     }
 }
@@ -2,11 +2,12 @@ use crate::base::behavior::*;
 use crate::base::module::*;
 use crate::muon::config::MuonConfig;
 use crate::muon::execute::CSRType;
+use crate::muon::trap::{self, Trap, TrapCause};
 use log::info;
 use std::collections::HashMap;
 use std::sync::{Arc, RwLock};
 
-#[derive(Debug, Default)]
+#[derive(Debug, Clone, Default)]
 pub struct CSRState {
     csr: HashMap<u32, u32>,
 }
@@ -34,6 +35,19 @@ impl ModuleBehaviors for CSRFile {
 
 module!(CSRFile, CSRState, MuonConfig,);
 
+impl Clone for CSRFile {
+    fn clone(&self) -> Self {
+        Self {
+            base: self.base.clone(),
+            lock: RwLock::new(()),
+            lane_id: self.lane_id,
+            block_idx: self.block_idx,
+            thread_idx: self.thread_idx,
+            bp: self.bp,
+        }
+    }
+}
+
 macro_rules! get_ref_rw_match {
     ($self:expr, $variable:expr, [$( $addr:expr, $init:expr );* $(;)?]) => {
         match $variable {
@@ -104,8 +118,6 @@ impl CSRFile {
             0x302, 0; // medeleg
             0x303, 0; // mideleg
             0x304, 0; // mie
-            0x305, 0; // mtvec
-            0x341, 0; // mepc
             0x3a0, 0; // pmpcf0
             0x3b0, 0; // pmpaddr0
             0xb01, 0; // mpm_reserved
@@ -130,7 +142,9 @@ impl CSRFile {
     }
 
     // these can only be read by the user,
-    // but the emulator can update them
+    // but the emulator can update them.
+    // the counters are filled in by the timing model before each read
+    // (see timeflow::perf_counters), and read 0 in functional runs
     fn csr_rw_ref_emu(&mut self, addr: u32) -> Option<&mut u32> {
         let _lock = self.lock.write().expect("lock poisoned");
         get_ref_rw_match!(self, addr, [
@@ -140,6 +154,66 @@ impl CSRFile {
             0xb80, 0; // mcycle_h
             0xb02, 0; // minstret
             0xb82, 0; // minstret_h
+            0xb03, 0; // mhpmcounter3: l0 hits
+            0xb83, 0; // mhpmcounter3h
+            0xb04, 0; // mhpmcounter4: l0 misses
+            0xb84, 0; // mhpmcounter4h
+            0xb05, 0; // mhpmcounter5: l1 hits
+            0xb85, 0; // mhpmcounter5h
+            0xb06, 0; // mhpmcounter6: l1 misses
+            0xb86, 0; // mhpmcounter6h
+            0xb07, 0; // mhpmcounter7: l2 hits
+            0xb87, 0; // mhpmcounter7h
+            0xb08, 0; // mhpmcounter8: l2 misses
+            0xb88, 0; // mhpmcounter8h
+            0xb09, 0; // mhpmcounter9: stall cycles
+            0xb89, 0; // mhpmcounter9h
+            0xb0a, 0; // mhpmcounter10: warp alu
+            0xb8a, 0; // mhpmcounter10h
+            0xb0b, 0; // mhpmcounter11: warp fpu
+            0xb8b, 0; // mhpmcounter11h
+            0xb0c, 0; // mhpmcounter12: warp load
+            0xb8c, 0; // mhpmcounter12h
+            0xb0d, 0; // mhpmcounter13: warp store
+            0xb8d, 0; // mhpmcounter13h
+            0xb0e, 0; // mhpmcounter14: warp branch
+            0xb8e, 0; // mhpmcounter14h
+            0xb0f, 0; // mhpmcounter15: warp sfu
+            0xb8f, 0; // mhpmcounter15h
+            0xb10, 0; // mhpmcounter16: warp active cycles
+            0xb90, 0; // mhpmcounter16h
+            0xc00, 0; // cycle
+            0xc80, 0; // cycleh
+            0xc02, 0; // instret
+            0xc82, 0; // instreth
+            0xc03, 0; // hpmcounter3
+            0xc83, 0; // hpmcounter3h
+            0xc04, 0; // hpmcounter4
+            0xc84, 0; // hpmcounter4h
+            0xc05, 0; // hpmcounter5
+            0xc85, 0; // hpmcounter5h
+            0xc06, 0; // hpmcounter6
+            0xc86, 0; // hpmcounter6h
+            0xc07, 0; // hpmcounter7
+            0xc87, 0; // hpmcounter7h
+            0xc08, 0; // hpmcounter8
+            0xc88, 0; // hpmcounter8h
+            0xc09, 0; // hpmcounter9
+            0xc89, 0; // hpmcounter9h
+            0xc0a, 0; // hpmcounter10
+            0xc8a, 0; // hpmcounter10h
+            0xc0b, 0; // hpmcounter11
+            0xc8b, 0; // hpmcounter11h
+            0xc0c, 0; // hpmcounter12
+            0xc8c, 0; // hpmcounter12h
+            0xc0d, 0; // hpmcounter13
+            0xc8d, 0; // hpmcounter13h
+            0xc0e, 0; // hpmcounter14
+            0xc8e, 0; // hpmcounter14h
+            0xc0f, 0; // hpmcounter15
+            0xc8f, 0; // hpmcounter15h
+            0xc10, 0; // hpmcounter16
+            0xc90, 0; // hpmcounter16h
         ])
     }
 
@@ -151,9 +225,35 @@ impl CSRFile {
             0x001, 0; // fflags
             0x002, 0; // frm
             0x003, 0; // fcsr
+            0x800, 0; // gmem_window: bits [63:32] of global addresses
+            0x305, 0; // mtvec
+            0x340, 0; // mscratch
+            0x341, 0; // mepc
+            0x342, 0; // mcause
+            0x343, 0; // mtval
         ])
     }
 
+    /// Records `trap` taken by the instruction at `pc` and returns the
+    /// handler address. Only direct-mode `mtvec` is supported.
+    pub fn take_trap(&mut self, trap: &Trap, pc: u32) -> u32 {
+        self.emu_access(0x341, pc);
+        self.emu_access(0x342, trap.cause as u32);
+        self.emu_access(0x343, trap.tval);
+        self.base.state.csr.get(&0x305).copied().unwrap_or(0) & !3
+    }
+
+    /// Where `mret` returns to.
+    pub fn mepc(&self) -> u32 {
+        self.base.state.csr.get(&0x341).copied().unwrap_or(0)
+    }
+
+    /// Upper half of this lane's global addresses. Only takes effect when
+    /// `[mem] address_bits` is above 32.
+    pub fn gmem_window(&self) -> u32 {
+        self.base.state.csr.get(&0x800).copied().unwrap_or(0)
+    }
+
     pub fn user_access(&mut self, addr: u32, value: u32, op: CSRType) -> u32 {
         if let Some(w) = self.csr_rw_ref_user(addr) {
             // writable
@@ -181,7 +281,7 @@ impl CSRFile {
             self.csr_rw_ref_emu(addr)
                 .map(|x| *x)
                 .or(self.csr_ro_ref(addr))
-                .expect(&format!("reading nonexistent csr {}", addr))
+                .unwrap_or_else(|| trap::raise(TrapCause::IllegalInstruction, 0))
         }
     }
 
@@ -4,6 +4,7 @@ use crate::base::behavior::*;
 use crate::base::module::{module, IsModule, ModuleBase};
 use crate::muon::config::MuonConfig;
 use crate::muon::execute::Opcode;
+use crate::muon::lane_mask::LaneMask;
 use crate::utils::*;
 use std::fmt::{Debug, Display, Formatter};
 use std::sync::Arc;
@@ -98,6 +99,13 @@ impl DecodedInst {
                 rs3: false,
                 rs4: false,
             },
+            Opcode::CUSTOM0 if self.f7 == Opcode::WARP_OP_F7 => HasRegs {
+                rs1: true,
+                // Shuffles take the lane offset or index in rs2.
+                rs2: self.f3 >= 0b100,
+                rs3: false,
+                rs4: false,
+            },
             Opcode::CUSTOM0 => HasRegs {
                 rs1: matches!(
                     self.sfu_key(),
@@ -174,7 +182,7 @@ impl Display for IssuedInst {
 #[derive(Debug, Clone, Copy)]
 pub struct MicroOp {
     pub inst: DecodedInst,
-    pub tmask: u32,
+    pub tmask: LaneMask,
 }
 pub struct InstBuf(pub Vec<Option<MicroOp>>);
 
@@ -204,7 +212,7 @@ pub fn sign_ext<const W: u8>(from: u32) -> i32 {
     ((from << (32 - W)) as i32) >> (32 - W)
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct RegFileState {
     gpr: [u32; 256],
 }
@@ -216,7 +224,7 @@ impl Default for RegFileState {
 }
 
 /// Register file for a single SIMT lane.
-#[derive(Debug, Default)]
+#[derive(Debug, Clone, Default)]
 pub struct RegFile {
     base: ModuleBase<RegFileState, MuonConfig>,
     lane_id: usize,
@@ -1,6 +1,8 @@
 use crate::muon::csr::CSRFile;
 use crate::muon::decode::{sign_ext, IssuedInst, MicroOp, RegFile};
+use crate::muon::lane_mask::LaneMask;
 use crate::muon::scheduler::{Scheduler, SchedulerWriteback};
+use crate::muon::trap::{self, TrapCause};
 use crate::muon::warp::{ExWriteback, MemRequest, MemResponse};
 use crate::neutrino::neutrino::Neutrino;
 use crate::utils::BitSlice;
@@ -46,6 +48,9 @@ impl Opcode {
     pub const NU_INVOKE_IMM: u16 = 0b001111011u16;
     pub const NU_PAYLOAD: u16 = 0b011011011u16;
     pub const NU_COMPLETE: u16 = 0b101011011u16;
+
+    /// `f7` of the CUSTOM0 vote and shuffle ops; `f3` picks the op.
+    pub const WARP_OP_F7: u8 = 0b0000010u8;
 }
 
 // TODO: use bitflags crate for this
@@ -98,6 +103,18 @@ pub enum SFUType {
     ECALL = 7,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WarpOpType {
+    VoteAll,
+    VoteAny,
+    VoteUni,
+    Ballot,
+    ShflUp,
+    ShflDown,
+    ShflBfly,
+    ShflIdx,
+}
+
 #[derive(Debug, FromPrimitive, Clone, Copy, PartialEq)]
 pub enum CSRType {
     RW = 1,
@@ -461,7 +478,7 @@ impl ExecuteUnit {
         let taken = INSTS
             .get(&issued_inst.f3)
             .and_then(|imp| Some(print_and_execute!(imp, [rs1, rs2])))
-            .expect("unimplemented branch instruction");
+            .unwrap_or_else(|| trap::raise(TrapCause::IllegalInstruction, 0));
         taken.then_some(branch_target)
     }
 
@@ -486,7 +503,7 @@ impl ExecuteUnit {
         let key = (issued_inst.f3, issued_inst.opext);
         let shared_load = issued_inst.opext == 1;
         let Some(&mnemonic) = INSTS.get(&key) else {
-            unimplemented!("unknown load instruction")
+            trap::raise(TrapCause::IllegalInstruction, 0)
         };
 
         let inst_imp = InstImp(mnemonic, |[a, b]| a.wrapping_add(b));
@@ -497,7 +514,7 @@ impl ExecuteUnit {
 
         let logsize = issued_inst.f3 & 3;
         let size = 1u32 << logsize;
-        let addr = alu_result;
+        let addr = alu_result.into();
         let sext = !issued_inst.f3.bit(2);
 
         Some(MemRequest {
@@ -523,7 +540,7 @@ impl ExecuteUnit {
         let key = (issued_inst.f3, issued_inst.opext);
         let shared_store = issued_inst.opext == 1;
         let Some(&mnemonic) = INSTS.get(&key) else {
-            unimplemented!("unknown store instruction")
+            trap::raise(TrapCause::IllegalInstruction, 0)
         };
 
         let inst_imp = InstImp(mnemonic, |[a, b]| a.wrapping_add(b));
@@ -532,7 +549,7 @@ impl ExecuteUnit {
             [issued_inst.rs1_data[lane].unwrap(), issued_inst.imm32]
         );
 
-        let addr = alu_result;
+        let addr = alu_result.into();
         let data = issued_inst.rs2_data[lane].unwrap();
         let logsize = issued_inst.f3 & 3;
         let size = 1u32 << logsize;
@@ -555,7 +572,7 @@ impl ExecuteUnit {
             5 => InstDef("csrrwi", CSRType::RWI),
             6 => InstDef("csrrsi", CSRType::RSI),
             7 => InstDef("csrrci", CSRType::RCI),
-            _ => panic!("unimplemented"),
+            _ => trap::raise(TrapCause::IllegalInstruction, 0),
         });
         let new_val = match csr_type {
             CSRType::RW | CSRType::RS | CSRType::RC => issued_inst.rs1_data[lane].unwrap(),
@@ -605,7 +622,7 @@ impl ExecuteUnit {
             insts
                 .get(&(f3_f7_mask!(issued_inst.f3, issued_inst.f7)))
                 .and_then(|imp| Some(print_and_unwrap!(imp)))
-                .expect("unimplemented sfu instruction")
+                .unwrap_or_else(|| trap::raise(TrapCause::IllegalInstruction, 0))
         };
 
         scheduler.sfu(
@@ -622,6 +639,74 @@ impl ExecuteUnit {
         )
     }
 
+    /// Votes and shuffles across the warp's active lanes. A vote writes the
+    /// same result to every active lane; a shuffle whose source lane is out
+    /// of range or inactive returns the lane's own value.
+    pub fn warp_op(issued_inst: &IssuedInst) -> Vec<Option<u32>> {
+        let insts = phf_map! {
+            // rd = 1 if rs1 != 0 in every active lane
+            0b000_0000010u16 => InstDef("vx_vote.all",  WarpOpType::VoteAll),
+            // rd = 1 if rs1 != 0 in any active lane
+            0b001_0000010u16 => InstDef("vx_vote.any",  WarpOpType::VoteAny),
+            // rd = 1 if rs1 is the same in every active lane
+            0b010_0000010u16 => InstDef("vx_vote.uni",  WarpOpType::VoteUni),
+            // rd = mask of the active lanes with rs1 != 0, lanes 0 to 31 only
+            0b011_0000010u16 => InstDef("vx_ballot",    WarpOpType::Ballot),
+            // rd = rs1 of lane - rs2
+            0b100_0000010u16 => InstDef("vx_shfl.up",   WarpOpType::ShflUp),
+            // rd = rs1 of lane + rs2
+            0b101_0000010u16 => InstDef("vx_shfl.down", WarpOpType::ShflDown),
+            // rd = rs1 of lane ^ rs2
+            0b110_0000010u16 => InstDef("vx_shfl.bfly", WarpOpType::ShflBfly),
+            // rd = rs1 of lane rs2
+            0b111_0000010u16 => InstDef("vx_shfl.idx",  WarpOpType::ShflIdx),
+        };
+        let op = insts
+            .get(&(f3_f7_mask!(issued_inst.f3, issued_inst.f7)))
+            .map(|imp| print_and_unwrap!(imp))
+            .unwrap_or_else(|| trap::raise(TrapCause::IllegalInstruction, 0));
+
+        // Only active lanes carry operands.
+        let values = &issued_inst.rs1_data;
+        let mut active = values.iter().flatten();
+        let vote = match op {
+            WarpOpType::VoteAll => Some(active.all(|&value| value != 0) as u32),
+            WarpOpType::VoteAny => Some(active.any(|&value| value != 0) as u32),
+            WarpOpType::VoteUni => {
+                let first = active.next();
+                Some(active.all(|value| Some(value) == first) as u32)
+            }
+            WarpOpType::Ballot => Some(
+                values
+                    .iter()
+                    .map(|value| value.is_some_and(|value| value != 0))
+                    .collect::<LaneMask>()
+                    .low_u32(),
+            ),
+            _ => None,
+        };
+
+        (0..values.len())
+            .map(|lane| {
+                let own = values[lane]?;
+                if vote.is_some() {
+                    return vote;
+                }
+                let delta = issued_inst.rs2_data[lane].unwrap() as usize;
+                let src = match op {
+                    WarpOpType::ShflUp => lane.checked_sub(delta),
+                    WarpOpType::ShflDown => lane.checked_add(delta),
+                    WarpOpType::ShflBfly => Some(lane ^ delta),
+                    _ => Some(delta),
+                };
+                Some(
+                    src.and_then(|src| values.get(src).copied().flatten())
+                        .unwrap_or(own),
+                )
+            })
+            .collect()
+    }
+
     pub fn custom3(issued_inst: &IssuedInst, lane: usize) -> Option<u32> {
         let insts = phf_map! {
             0b111_0101110u16 => InstImp("fexp.h", |[a]| {
@@ -643,7 +728,7 @@ impl ExecuteUnit {
                     [issued_inst.rs1_data[lane].unwrap()]
                 ))
             })
-            .or_else(|| panic!("unimplemented custom3 instruction"))
+            .or_else(|| trap::raise(TrapCause::IllegalInstruction, 0))
     }
 
     /// Collect source operand values from the regfile.
@@ -688,7 +773,7 @@ impl ExecuteUnit {
     }
 
     #[inline]
-    fn collect_lanes<F, T>(mut func: F, tmask: u32, rf: &mut [RegFile]) -> Vec<Option<T>>
+    fn collect_lanes<F, T>(mut func: F, tmask: LaneMask, rf: &mut [RegFile]) -> Vec<Option<T>>
     where
         F: FnMut(usize) -> Option<T>,
     {
@@ -705,7 +790,7 @@ impl ExecuteUnit {
         issued: IssuedInst,
         cid: usize,
         wid: usize,
-        tmask: u32,
+        tmask: LaneMask,
         rf: &mut [RegFile],
         csrf: &mut [CSRFile],
         scheduler: &mut Scheduler,
@@ -713,7 +798,7 @@ impl ExecuteUnit {
     ) -> ExWriteback {
         let num_lanes = rf.len();
         // lane id of first active thread
-        let first_lid = tmask.trailing_zeros() as usize;
+        let first_lid = tmask.first_lane().unwrap_or(num_lanes);
 
         debug!(
             "ISSUE: core:{}, warp:{}, {}, tmask: {:b}",
@@ -777,12 +862,17 @@ impl ExecuteUnit {
                     0 => InstDef("fence", 0),
                     1 => InstDef("fence.i", 1),
                     2 => InstDef("fence.s", 2),
-                    _ => panic!("unimplemented"),
+                    _ => trap::raise(TrapCause::IllegalInstruction, 0),
                 };
                 print_and_unwrap!(imp);
                 // TODO fence
                 (empty, empty_mem, empty_swb)
             }
+            Opcode::SYSTEM if issued.f3 == 0 && issued.imm32 == 0x302 => {
+                print_and_unwrap!(InstDef("mret", ()));
+                let mepc = csrf[first_lid].mepc();
+                (empty, empty_mem, scheduler.take_branch(wid, mepc))
+            }
             Opcode::SYSTEM => {
                 if issued.f3 == 0 {
                     // tohost
@@ -805,6 +895,9 @@ impl ExecuteUnit {
                     (rds, empty_mem, empty_swb)
                 }
             }
+            Opcode::CUSTOM0 if issued.f7 == Opcode::WARP_OP_F7 => {
+                (ExecuteUnit::warp_op(&issued), empty_mem, empty_swb)
+            }
             Opcode::CUSTOM0 => {
                 // FIXME: tmask?
                 (
@@ -828,9 +921,7 @@ impl ExecuteUnit {
                 empty_mem,
                 empty_swb,
             ),
-            _ => {
-                panic!("unimplemented opcode 0x{:x}", issued.opcode);
-            }
+            _ => trap::raise(TrapCause::IllegalInstruction, 0),
         };
 
         let issued_rd_addr = issued.rd_addr;
@@ -854,12 +945,7 @@ impl ExecuteUnit {
         if req.is_store {
             None
         } else {
-            let addr = req.addr;
             let size = req.size;
-            assert_eq!(addr >> 2, (addr + size - 1) >> 2, "misaligned load");
-
-            let bit_offset = ((addr & 3) * 8) as usize;
-
             let load_data_bytes = resp.data.expect("mem load response doesn't contain data");
             let raw_load = u32::from_le_bytes(load_data_bytes);
             let sext = req.is_sext;
@@ -871,9 +957,9 @@ impl ExecuteUnit {
                 }
             };
             let rd = match size {
-                1 => opt_sext(sign_ext::<8>, raw_load.sel(7 + bit_offset, bit_offset)), // load byte
-                2 => opt_sext(sign_ext::<16>, raw_load.sel(15 + bit_offset, bit_offset)), // load half
-                4 => raw_load, // load word
+                1 => opt_sext(sign_ext::<8>, raw_load.sel(7, 0)), // load byte
+                2 => opt_sext(sign_ext::<16>, raw_load.sel(15, 0)), // load half
+                4 => raw_load,                                    // load word
                 _ => panic!("unimplemented load type"),
             };
 
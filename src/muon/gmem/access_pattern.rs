@@ -0,0 +1,145 @@
+use std::ops::AddAssign;
+
+use serde::Serialize;
+
+/// PCs `AccessPatternSummary::format_lines` prints.
+const REPORTED_PCS: usize = 5;
+
+/// How a warp's gmem load or store spread its active lanes over memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AccessPattern {
+    /// Touches no more cache lines than a contiguous access of the same
+    /// size: consecutive elements, a broadcast, or a shuffle within them.
+    Coalesced,
+    /// Lanes a constant stride apart, wider than one element.
+    Strided,
+    Scattered,
+}
+
+impl AccessPattern {
+    /// Classifies an access from its active lanes' addresses, in lane order.
+    pub fn classify(lane_addrs: &[u64], bytes_per_lane: u32, line_bytes: u64) -> Self {
+        let line_bytes = line_bytes.max(1);
+        let (Some(&first), Some(&last)) = (lane_addrs.iter().min(), lane_addrs.iter().max()) else {
+            return Self::Coalesced;
+        };
+        let mut lines: Vec<u64> = lane_addrs.iter().map(|addr| addr / line_bytes).collect();
+        lines.sort_unstable();
+        lines.dedup();
+        let span = (lane_addrs.len() as u64 * bytes_per_lane.max(1) as u64).max(1);
+        let contiguous_lines = first.saturating_add(span - 1) / line_bytes - first / line_bytes + 1;
+        if lines.len() as u64 <= contiguous_lines || first == last {
+            return Self::Coalesced;
+        }
+        let stride = lane_addrs[1].wrapping_sub(lane_addrs[0]);
+        let strided = lane_addrs
+            .windows(2)
+            .all(|pair| pair[1].wrapping_sub(pair[0]) == stride);
+        if strided {
+            Self::Strided
+        } else {
+            Self::Scattered
+        }
+    }
+}
+
+/// Access patterns of every gmem load and store issued from one PC.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct PcAccessPatterns {
+    pub pc: u32,
+    pub coalesced: u64,
+    pub strided: u64,
+    pub scattered: u64,
+    /// First-level cache lines the issues touched, summed.
+    pub lines: u64,
+}
+
+impl PcAccessPatterns {
+    pub fn issues(&self) -> u64 {
+        self.coalesced + self.strided + self.scattered
+    }
+
+    pub fn uncoalesced(&self) -> u64 {
+        self.strided + self.scattered
+    }
+}
+
+/// Per-PC access patterns of issued gmem loads and stores, sorted by PC.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct AccessPatternSummary {
+    pub pcs: Vec<PcAccessPatterns>,
+}
+
+impl AccessPatternSummary {
+    pub fn record(&mut self, pc: u32, pattern: AccessPattern, lines: usize) {
+        let idx = match self.pcs.binary_search_by_key(&pc, |entry| entry.pc) {
+            Ok(idx) => idx,
+            Err(idx) => {
+                self.pcs.insert(
+                    idx,
+                    PcAccessPatterns {
+                        pc,
+                        ..PcAccessPatterns::default()
+                    },
+                );
+                idx
+            }
+        };
+        let entry = &mut self.pcs[idx];
+        match pattern {
+            AccessPattern::Coalesced => entry.coalesced += 1,
+            AccessPattern::Strided => entry.strided += 1,
+            AccessPattern::Scattered => entry.scattered += 1,
+        }
+        entry.lines = entry.lines.saturating_add(lines as u64);
+    }
+
+    pub fn get(&self, pc: u32) -> Option<&PcAccessPatterns> {
+        self.pcs
+            .binary_search_by_key(&pc, |entry| entry.pc)
+            .ok()
+            .map(|idx| &self.pcs[idx])
+    }
+
+    /// The PCs with the most strided or scattered issues, worst first.
+    pub fn format_lines(&self) -> Vec<String> {
+        let mut worst: Vec<&PcAccessPatterns> = self
+            .pcs
+            .iter()
+            .filter(|entry| entry.uncoalesced() > 0)
+            .collect();
+        worst.sort_by_key(|entry| std::cmp::Reverse((entry.uncoalesced(), entry.lines)));
+        worst
+            .iter()
+            .take(REPORTED_PCS)
+            .map(|entry| {
+                format!(
+                    "pc 0x{:08x} strided {} scattered {} of {} issues, {:.1} lines/issue",
+                    entry.pc,
+                    entry.strided,
+                    entry.scattered,
+                    entry.issues(),
+                    entry.lines as f64 / entry.issues() as f64
+                )
+            })
+            .collect()
+    }
+}
+
+impl AddAssign<&AccessPatternSummary> for AccessPatternSummary {
+    fn add_assign(&mut self, other: &AccessPatternSummary) {
+        for entry in &other.pcs {
+            match self.pcs.binary_search_by_key(&entry.pc, |own| own.pc) {
+                Ok(idx) => {
+                    let own = &mut self.pcs[idx];
+                    own.coalesced += entry.coalesced;
+                    own.strided += entry.strided;
+                    own.scattered += entry.scattered;
+                    own.lines = own.lines.saturating_add(entry.lines);
+                }
+                Err(idx) => self.pcs.insert(idx, *entry),
+            }
+        }
+    }
+}
@@ -1,59 +1,88 @@
 use crate::info;
 use crate::muon::scheduler::Scheduler;
 use crate::timeflow::lsu::LsuPayload;
+use crate::timeflow::{GmemRequest, RequestRef, RequestSpace, SmemRequest};
 use crate::timeq::Cycle;
 
-use super::{CoreTimingModel, SmemConflictSample};
+use super::{ContextMemStats, CoreTimingModel, GmemHitSummary, SmemConflictSample};
+
+/// The stats of `context`, growing `stats` to hold it.
+fn context_mem(stats: &mut Vec<ContextMemStats>, context: usize) -> &mut ContextMemStats {
+    if stats.len() <= context {
+        stats.resize_with(context + 1, Default::default);
+    }
+    &mut stats[context]
+}
+
+/// Counts the cache levels a completed gmem access looked up and hit.
+fn count_gmem_hits(hits: &mut GmemHitSummary, l0_enabled: bool, request: &GmemRequest) {
+    let mut l1_considered = true;
+    if l0_enabled {
+        hits.l0_accesses = hits.l0_accesses.saturating_add(1);
+        if request.l0_hit {
+            hits.l0_hits = hits.l0_hits.saturating_add(1);
+            l1_considered = false;
+        }
+    }
+
+    if l1_considered {
+        hits.l1_accesses = hits.l1_accesses.saturating_add(1);
+        if request.l1_hit {
+            hits.l1_hits = hits.l1_hits.saturating_add(1);
+        } else {
+            hits.l2_accesses = hits.l2_accesses.saturating_add(1);
+            if request.l2_hit {
+                hits.l2_hits = hits.l2_hits.saturating_add(1);
+            }
+        }
+    }
+    if request.rmw {
+        hits.rmw = hits.rmw.saturating_add(1);
+    }
+}
 
 impl CoreTimingModel {
     fn record_gmem_completion(&mut self, now: Cycle, completion: &crate::timeflow::GmemCompletion) {
+        let request = &completion.request;
         if let Some(range) = self.gmem_stats_range {
-            let addr = completion.request.addr;
-            if addr < range.start || addr >= range.end {
+            if request.addr < range.start || request.addr >= range.end {
                 return;
             }
         }
-        if completion.request.kind.is_mem() {
-            let mut l1_considered = true;
-            if self.gmem_policy.l0_enabled {
-                self.gmem_hits.l0_accesses = self.gmem_hits.l0_accesses.saturating_add(1);
-                if completion.request.l0_hit {
-                    self.gmem_hits.l0_hits = self.gmem_hits.l0_hits.saturating_add(1);
-                    l1_considered = false;
-                }
-            }
-
-            if l1_considered {
-                self.gmem_hits.l1_accesses = self.gmem_hits.l1_accesses.saturating_add(1);
-                if completion.request.l1_hit {
-                    self.gmem_hits.l1_hits = self.gmem_hits.l1_hits.saturating_add(1);
-                } else {
-                    self.gmem_hits.l2_accesses = self.gmem_hits.l2_accesses.saturating_add(1);
-                    if completion.request.l2_hit {
-                        self.gmem_hits.l2_hits = self.gmem_hits.l2_hits.saturating_add(1);
-                    }
-                }
-            }
+        let l0_enabled = self.gmem_policy.l0_enabled;
+        let context = context_mem(&mut self.context_mem, request.context);
+        if request.kind.is_mem() && !request.forwarded {
+            count_gmem_hits(&mut self.gmem_hits, l0_enabled, request);
+            count_gmem_hits(&mut context.gmem_hits, l0_enabled, request);
+            context.gmem_bytes = context.gmem_bytes.saturating_add(request.bytes as u64);
         }
 
-        if let Some(issue_at) = self.gmem_issue_cycle.get(&completion.request.id).copied() {
+        if let Some(issue_at) = self.gmem_issue_cycle.get(&request.id).copied() {
             let latency = now.saturating_sub(issue_at);
             self.gmem_latency_hist.record(latency);
-            self.latencies.gmem_count = self.latencies.gmem_count.saturating_add(1);
-            self.latencies.gmem_sum = self.latencies.gmem_sum.saturating_add(latency);
+            for latencies in [&mut self.latencies, &mut context.latencies] {
+                latencies.gmem_count = latencies.gmem_count.saturating_add(1);
+                latencies.gmem_sum = latencies.gmem_sum.saturating_add(latency);
+            }
         }
     }
 
     fn record_smem_completion(&mut self, now: Cycle, completion: &crate::timeflow::SmemCompletion) {
+        let context = context_mem(&mut self.context_mem, completion.request.context);
+        context.smem_bytes = context
+            .smem_bytes
+            .saturating_add(completion.request.bytes as u64);
         if let Some(issue_at) = self.smem_issue_cycle.get(&completion.request.id).copied() {
             let latency = now.saturating_sub(issue_at);
             self.smem_latency_hist.record(latency);
-            self.latencies.smem_count = self.latencies.smem_count.saturating_add(1);
-            self.latencies.smem_sum = self.latencies.smem_sum.saturating_add(latency);
+            for latencies in [&mut self.latencies, &mut context.latencies] {
+                latencies.smem_count = latencies.smem_count.saturating_add(1);
+                latencies.smem_sum = latencies.smem_sum.saturating_add(latency);
+            }
         }
     }
 
-    fn maybe_clear_gmem_issue_cycle(&mut self, request_id: u64) {
+    fn maybe_clear_gmem_issue_cycle(&mut self, now: Cycle, request_id: u64) {
         if self
             .pending_gmem
             .iter()
@@ -62,17 +91,29 @@ impl CoreTimingModel {
             return;
         }
         self.gmem_issue_cycle.remove(&request_id);
+        self.timeouts.forget(RequestSpace::Gmem, request_id);
+        self.release_request(now, RequestRef::gmem(request_id));
     }
 
-    fn maybe_clear_smem_issue_cycle(&mut self, request_id: u64) {
+    fn maybe_clear_smem_issue_cycle(&mut self, now: Cycle, warp: usize, request_id: u64) {
         if self
             .pending_smem
             .iter()
             .any(|queue| queue.iter().any(|(id, _)| *id == request_id))
         {
+            self.smem_first_completion.entry(request_id).or_insert(now);
             return;
         }
         self.smem_issue_cycle.remove(&request_id);
+        self.timeouts.forget(RequestSpace::Smem, request_id);
+        self.release_request(now, RequestRef::smem(request_id));
+        if let Some(first) = self.smem_first_completion.remove(&request_id) {
+            if let Some(stats) = self.smem_warps.per_warp.get_mut(warp) {
+                stats.serialization_cycles = stats
+                    .serialization_cycles
+                    .saturating_add(now.saturating_sub(first));
+            }
+        }
     }
 
     pub(super) fn record_smem_conflict(
@@ -101,7 +142,41 @@ impl CoreTimingModel {
             .unique_subbanks
             .saturating_add(sample.unique_subbanks as u64);
 
-        let _ = (now, warp, request_id);
+        if let Some(stats) = self.smem_warps.per_warp.get_mut(warp) {
+            stats.instructions = stats.instructions.saturating_add(1);
+            stats.active_lanes = stats
+                .active_lanes
+                .saturating_add(sample.active_lanes as u64);
+            stats.conflict_lanes = stats
+                .conflict_lanes
+                .saturating_add(sample.conflict_lanes as u64);
+        }
+
+        let _ = (now, request_id);
+    }
+
+    /// Charges the bank accesses `parts` of one smem request make to
+    /// `warp`: each part's lanes to its bank, and every lane past the first
+    /// in a bank as a conflict there.
+    pub(super) fn record_smem_banks(&mut self, warp: usize, parts: &[SmemRequest]) {
+        let Some(stats) = self.smem_warps.per_warp.get_mut(warp) else {
+            return;
+        };
+        stats.serialized_accesses = stats
+            .serialized_accesses
+            .saturating_add(parts.len().saturating_sub(1) as u64);
+        let accesses = &mut self.smem_warps.bank_accesses[warp];
+        let mut lanes = vec![0u64; accesses.len()];
+        for part in parts {
+            if let Some(count) = lanes.get_mut(part.bank) {
+                *count = count.saturating_add(part.active_lanes.max(1) as u64);
+            }
+        }
+        let conflicts = &mut self.smem_warps.bank_conflicts[warp];
+        for (bank, count) in lanes.into_iter().enumerate() {
+            accesses[bank] = accesses[bank].saturating_add(count);
+            conflicts[bank] = conflicts[bank].saturating_add(count.saturating_sub(1));
+        }
     }
 
     pub(super) fn handle_gmem_completion(
@@ -112,11 +187,21 @@ impl CoreTimingModel {
     ) {
         let warp = completion.request.warp;
         let completed_id = completion.request.id;
+        if self.timeouts.is_late(RequestSpace::Gmem, completed_id) {
+            self.graph
+                .lsu_release_load_data(&LsuPayload::Gmem(completion.request));
+            return;
+        }
+        self.release_gmem_miss(warp, completed_id);
         if !self.remove_gmem_pending(warp, completed_id, scheduler) {
             return;
         }
+        let (gmem_drained, smem_drained) =
+            (!self.has_pending_gmem(warp), !self.has_pending_smem(warp));
+        self.wakeups.notify(now, warp, gmem_drained, smem_drained);
         self.record_gmem_completion(now, &completion);
-        self.maybe_clear_gmem_issue_cycle(completed_id);
+        self.maybe_clear_gmem_issue_cycle(now, completed_id);
+        self.update_warp_drain(now, warp);
         self.graph
             .lsu_release_load_data(&LsuPayload::Gmem(completion.request.clone()));
         self.trace_event(
@@ -131,6 +216,20 @@ impl CoreTimingModel {
             self.logger,
             "[gmem] warp {} completed request {} done@{}", warp, completed_id, now
         );
+        if let (Some(session), Some(hops)) = (&self.perf_log_session, &completion.request.path) {
+            session.write_request_path(&crate::sim::perf_log::RequestPathRecord {
+                cycle: now,
+                cluster_id: self.cluster_id,
+                core_id: self.core_id,
+                warp,
+                request_id: completed_id,
+                addr: completion.request.addr,
+                hops: hops.clone(),
+            });
+        }
+        if let Some(log) = self.gmem_completion_log.as_mut() {
+            log.push(completion);
+        }
     }
 
     pub(super) fn handle_smem_completion(
@@ -141,11 +240,20 @@ impl CoreTimingModel {
     ) {
         let warp = completion.request.warp;
         let completed_id = completion.request.id;
+        if self.timeouts.is_late(RequestSpace::Smem, completed_id) {
+            self.graph
+                .lsu_release_load_data(&LsuPayload::Smem(completion.request));
+            return;
+        }
         if !self.remove_smem_pending(warp, completed_id, scheduler) {
             return;
         }
+        let (gmem_drained, smem_drained) =
+            (!self.has_pending_gmem(warp), !self.has_pending_smem(warp));
+        self.wakeups.notify(now, warp, gmem_drained, smem_drained);
         self.record_smem_completion(now, &completion);
-        self.maybe_clear_smem_issue_cycle(completed_id);
+        self.maybe_clear_smem_issue_cycle(now, warp, completed_id);
+        self.update_warp_drain(now, warp);
         self.graph
             .lsu_release_load_data(&LsuPayload::Smem(completion.request.clone()));
         self.trace_event(
@@ -1,15 +1,26 @@
-use std::collections::VecDeque;
+use std::collections::{HashMap, VecDeque};
 use std::env;
 use std::sync::Arc;
 
 use crate::info;
 use crate::muon::scheduler::Scheduler;
+use crate::sim::checkpoint::Shared;
 use crate::sim::log::Logger;
 use crate::sim::perf_log;
-use crate::timeflow::{ClusterGmemGraph, CoreGraph, CoreGraphConfig, WarpIssueScheduler};
+use crate::timeflow::StatsSwitch;
+use crate::timeflow::{
+    lsu::LsuPayload, ClusterGmemGraph, CoreGraph, CoreGraphConfig, FenceStats, GmemCompletion,
+    Ibuffers, PerfCounterFile, PowerGovernor, ReservationStations, SmemCompletion, StoreBuffer,
+    WarpContexts, WarpIssueScheduler,
+};
+use crate::timeflow::{DependencyTable, RequestSpace, RequestTimeouts};
 use crate::timeq::Cycle;
 
-use super::{CorePerfSummary, CoreStats, CoreTimingModel, GmemLevelSummary, StallSummary};
+use super::pipeview::{MemRequest, PipeviewTracker};
+use super::{
+    CorePerfSummary, CoreStats, CoreTimingModel, GmemLevelSummary, InstLifecycle, StallSummary,
+    WarpTimingState,
+};
 
 impl CoreTimingModel {
     pub fn new(
@@ -17,7 +28,7 @@ impl CoreTimingModel {
         num_warps: usize,
         core_id: usize,
         cluster_id: usize,
-        cluster_gmem: Arc<std::sync::RwLock<ClusterGmemGraph>>,
+        cluster_gmem: Shared<ClusterGmemGraph>,
         logger: Arc<Logger>,
     ) -> Self {
         let log_stats = env::var("CYCLOTRON_TIMING_LOG_STATS")
@@ -44,7 +55,7 @@ impl CoreTimingModel {
         num_warps: usize,
         core_id: usize,
         cluster_id: usize,
-        cluster_gmem: Arc<std::sync::RwLock<ClusterGmemGraph>>,
+        cluster_gmem: Shared<ClusterGmemGraph>,
         perf_log_session: Option<Arc<perf_log::PerfLogSession>>,
         logger: Arc<Logger>,
     ) -> Self {
@@ -72,15 +83,30 @@ impl CoreTimingModel {
         num_warps: usize,
         core_id: usize,
         cluster_id: usize,
-        cluster_gmem: Arc<std::sync::RwLock<ClusterGmemGraph>>,
+        cluster_gmem: Shared<ClusterGmemGraph>,
         logger: Arc<Logger>,
         log_stats: bool,
         perf_log_session: Option<Arc<perf_log::PerfLogSession>>,
     ) -> Self {
         let gmem_policy = config.memory.gmem.policy.clone();
         let gmem_stats_range = config.memory.gmem.stats_range;
+        let suppress_exited_writebacks = config.memory.writeback.suppress_exited;
+        let execute_writeback = config.memory.writeback.execute_results;
+        let tensor_writeback = config.memory.writeback.tensor_results;
+        let writeback_quotas = config.memory.writeback.quota.enabled();
+        let strict_inorder = config.memory.lsu.strict_inorder;
         let smem_config = config.memory.smem.clone();
+        let smem_warps = super::SmemWarpSummary::new(num_warps, smem_config.num_banks.max(1));
+        let timeouts = RequestTimeouts::new(config.memory.lsu.timeout);
         let issue_scheduler = WarpIssueScheduler::new(config.compute.scheduler.clone());
+        let power = PowerGovernor::new(config.compute.power.clone());
+        let contexts = WarpContexts::new(config.compute.context_switch, num_warps);
+        let ibuffers = Ibuffers::new(config.compute.ibuffer, num_warps);
+        let perf_counters = PerfCounterFile::new(config.compute.perf_counters, num_warps);
+        let store_buffer = StoreBuffer::new(config.memory.lsu.store_buffer.clone());
+        let reservation = ReservationStations::new(config.compute.execute.reservation_stations);
+        let forwarding =
+            crate::timeflow::Forwarding::new(config.compute.execute.forwarding.clone());
         let mut scheduler_stats = super::SchedulerSummary::default();
         scheduler_stats.issue_width = config.compute.scheduler.issue_width.max(1) as u64;
         let stats_log_period = env::var("CYCLOTRON_STATS_LOG_PERIOD")
@@ -100,16 +126,44 @@ impl CoreTimingModel {
             pending_dma: VecDeque::new(),
             pending_tensor: VecDeque::new(),
             issue_scheduler,
+            power,
+            contexts,
+            reservation,
+            reg_ready: vec![HashMap::new(); num_warps],
+            forwarding,
+            rs_dispatch: vec![None; num_warps],
             pending_fence: VecDeque::new(),
             fence_inflight: vec![None; num_warps],
+            flush_fences: HashMap::new(),
+            fence_drain_since: vec![None; num_warps],
+            fence_stats: FenceStats::default(),
             icache_inflight: vec![None; num_warps],
+            ibuffers,
+            perf_counters,
             pending_cluster_gmem: VecDeque::new(),
+            store_buffer,
             pending_cluster_smem: VecDeque::new(),
             pending_gmem: vec![VecDeque::new(); num_warps],
             pending_smem: vec![VecDeque::new(); num_warps],
+            wakeups: super::wakeups::CompletionWakeups::new(num_warps),
             pending_execute: vec![None; num_warps],
+            execute_results: Vec::new(),
+            execute_writeback,
+            tensor_writeback,
+            writeback_quotas,
+            pending_neutrino: vec![None; num_warps],
+            neutrino_complete_at: vec![0; num_warps],
+            pending_copy: vec![None; num_warps],
             gmem_issue_cycle: std::collections::HashMap::new(),
+            gmem_misses: std::collections::HashMap::new(),
+            gmem_completion_log: None,
+            pipeview: None,
+            sched_log: None,
+            issue_policy: None,
             smem_issue_cycle: std::collections::HashMap::new(),
+            smem_first_completion: std::collections::HashMap::new(),
+            timeouts,
+            dependencies: DependencyTable::new(),
             core_id,
             cluster_id,
             gmem_policy,
@@ -117,10 +171,13 @@ impl CoreTimingModel {
             gmem_stats_range,
             next_gmem_id: 0,
             next_smem_id: 0,
+            next_dma_id: 1,
+            next_tensor_id: 1,
             next_icache_id: 0,
             logger,
             perf_log_session,
             log_stats,
+            stats: StatsSwitch::default(),
             stats_log_period,
             last_stats_log_cycle: None,
             last_logged_gmem_completed: 0,
@@ -131,10 +188,29 @@ impl CoreTimingModel {
             smem_util: super::SmemUtilSummary::default(),
             execute_util: super::ExecuteUtilSummary::default(),
             smem_conflicts_summary: super::SmemConflictSummary::default(),
+            smem_warps,
+            access_patterns: super::AccessPatternSummary::default(),
             gmem_hits: super::GmemHitSummary::default(),
+            retry_aging: super::RetryAgingSummary::default(),
+            inflight_flushes: Vec::new(),
+            flush_ordering: super::FlushOrderingSummary::default(),
+            warp_exit: vec![None; num_warps],
+            suppress_exited_writebacks,
+            warp_exits: super::WarpExitSummary::default(),
+            strict_inorder,
+            strict_inorder_rejects: 0,
+            cpi_stack: super::CpiStack::default(),
             latencies: super::LatencySummary::default(),
+            warp_contexts: vec![0; num_warps],
+            context_mem: Vec::new(),
+            mlp: super::MlpSummary {
+                per_warp: vec![super::WarpMlpSummary::default(); num_warps],
+                ..super::MlpSummary::default()
+            },
             dma_util: super::BasicUtilSummary::default(),
             tensor_util: super::BasicUtilSummary::default(),
+            neutrino_util: super::BasicUtilSummary::default(),
+            copy_engine_util: super::BasicUtilSummary::default(),
             gmem_latency_hist: super::LatencyHistogram::default(),
             smem_latency_hist: super::LatencyHistogram::default(),
         }
@@ -144,6 +220,7 @@ impl CoreTimingModel {
         self.graph.tick_front(now);
 
         self.drive_lsu_issues(now);
+        self.drain_store_buffer(now);
         self.issue_pending_cluster_gmem(now);
         self.issue_pending_cluster_smem(now);
 
@@ -151,7 +228,14 @@ impl CoreTimingModel {
         let prev_smem = self.graph.smem_stats().completed;
         let mut gmem_completions = Vec::new();
         let mut gmem_stats_snapshot = None;
-        gmem_completions.extend(self.graph.collect_cluster_gmem_completions(self.core_id));
+        for completion in self.graph.collect_cluster_gmem_completions(self.core_id) {
+            // Buffered stores completed when they entered the store buffer.
+            if self.store_buffer.take_drained(&completion.request) {
+                self.release_gmem_miss(completion.request.warp, completion.request.id);
+                continue;
+            }
+            gmem_completions.push(completion);
+        }
         if self.log_stats {
             gmem_stats_snapshot = Some(self.graph.cluster_gmem_stats(self.core_id));
         }
@@ -161,30 +245,74 @@ impl CoreTimingModel {
         while let Some(completion) = self.graph.pop_smem_completion() {
             smem_completions.push(completion);
         }
+        while let Some(payload) = self.graph.lsu_take_forwarded(now) {
+            match payload {
+                LsuPayload::Gmem(request) => gmem_completions.push(GmemCompletion {
+                    request,
+                    ticket_ready_at: now,
+                    completed_at: now,
+                }),
+                LsuPayload::Smem(request) => smem_completions.push(SmemCompletion {
+                    request,
+                    ticket_ready_at: now,
+                    completed_at: now,
+                }),
+            }
+        }
+
+        for request in self.store_buffer.take_completed(now) {
+            gmem_completions.push(GmemCompletion {
+                request,
+                ticket_ready_at: now,
+                completed_at: now,
+            });
+        }
 
         self.drain_pending_writeback(now);
         self.drain_pending_fence(now);
+        self.collect_finished_jobs(now);
+        self.collect_execute_results(now);
         self.drain_pending_dma(now);
         self.drain_pending_tensor(now);
+        self.ibuffers.tick(scheduler.active_warp_mask());
 
         for completion in gmem_completions {
             let is_flush =
                 completion.request.kind.is_flush_l0() || completion.request.kind.is_flush_l1();
             if is_flush {
+                if self
+                    .timeouts
+                    .is_late(RequestSpace::Gmem, completion.request.id)
+                {
+                    continue;
+                }
+                self.inflight_flushes
+                    .retain(|&(id, _)| id != completion.request.id);
                 self.handle_gmem_completion(now, completion.clone(), scheduler);
-                self.enqueue_fence(
-                    now,
-                    crate::timeflow::FenceRequest {
-                        warp: completion.request.warp,
-                        request_id: completion.request.id,
-                    },
-                );
+                let fence = self
+                    .flush_fences
+                    .remove(&completion.request.id)
+                    .unwrap_or_else(|| {
+                        crate::timeflow::FenceRequest::new(
+                            completion.request.warp,
+                            completion.request.id,
+                        )
+                    });
+                self.enqueue_fence(now, fence);
+                continue;
+            }
+            if self.skips_writeback(completion.request.warp) {
+                self.handle_gmem_completion(now, completion, scheduler);
                 continue;
             }
             self.enqueue_writeback(now, crate::timeflow::WritebackPayload::Gmem(completion));
         }
 
         for completion in smem_completions {
+            if self.skips_writeback(completion.request.warp) {
+                self.handle_smem_completion(now, completion, scheduler);
+                continue;
+            }
             self.enqueue_writeback(now, crate::timeflow::WritebackPayload::Smem(completion));
         }
 
@@ -198,9 +326,19 @@ impl CoreTimingModel {
                 crate::timeflow::WritebackPayload::Smem(completion) => {
                     self.handle_smem_completion(now, completion, scheduler);
                 }
+                crate::timeflow::WritebackPayload::Execute(result) => {
+                    self.finish_execute_writeback(now, result.warp, scheduler);
+                }
+                crate::timeflow::WritebackPayload::Tensor(_) => {}
             }
         }
 
+        self.check_request_timeouts(now, scheduler);
+
+        for warp in self.wakeups.take_due(now) {
+            scheduler.clear_resource_wait(warp);
+        }
+
         while let Some(fence_req) = self.graph.fence_pop_ready() {
             if let Some(slot) = self.fence_inflight.get_mut(fence_req.warp) {
                 if slot.map(|id| id == fence_req.request_id).unwrap_or(false) {
@@ -210,7 +348,9 @@ impl CoreTimingModel {
             scheduler.clear_resource_wait(fence_req.warp);
         }
 
-        self.sample_metrics(now);
+        if self.stats.on() {
+            self.sample_metrics(now);
+        }
 
         if self.log_stats {
             if let Some(gmem_stats) = gmem_stats_snapshot {
@@ -266,10 +406,217 @@ impl CoreTimingModel {
             .unwrap_or(false)
     }
 
+    /// Whether `warp` is parked until a memory completion wakes it.
+    pub fn is_parked(&self, warp: usize) -> bool {
+        self.wakeups.is_parked(warp)
+    }
+
     pub fn outstanding_smem(&self) -> usize {
         self.pending_smem.iter().map(|queue| queue.len()).sum()
     }
 
+    pub fn cpi_stack(&self) -> super::CpiStack {
+        self.cpi_stack
+    }
+
+    /// Records that `warp` has exited. Its gmem and smem requests still in
+    /// flight keep occupying the memory system until they complete; the warp
+    /// stays draining until then.
+    pub fn notify_warp_exit(&mut self, now: Cycle, warp: usize) {
+        let residual = self.pending_gmem.get(warp).map_or(0, VecDeque::len)
+            + self.pending_smem.get(warp).map_or(0, VecDeque::len);
+        self.contexts.release(warp);
+        let stats = &mut self.warp_exits;
+        stats.exited_warps = stats.exited_warps.saturating_add(1);
+        if residual == 0 {
+            return;
+        }
+        stats.draining_exits = stats.draining_exits.saturating_add(1);
+        stats.residual_requests = stats.residual_requests.saturating_add(residual as u64);
+        if let Some(slot) = self.warp_exit.get_mut(warp) {
+            *slot = Some(now);
+        }
+        info!(
+            self.logger,
+            "warp {} exited with {} request(s) in flight", warp, residual
+        );
+    }
+
+    /// Whether `warp` has exited but still has requests in flight.
+    pub fn warp_draining(&self, warp: usize) -> bool {
+        self.warp_exit.get(warp).is_some_and(Option::is_some)
+    }
+
+    fn skips_writeback(&mut self, warp: usize) -> bool {
+        if !self.suppress_exited_writebacks || !self.warp_draining(warp) {
+            return false;
+        }
+        self.warp_exits.suppressed_writebacks =
+            self.warp_exits.suppressed_writebacks.saturating_add(1);
+        true
+    }
+
+    /// Ends the drain of an exited warp once its last request completes.
+    pub(super) fn update_warp_drain(&mut self, now: Cycle, warp: usize) {
+        let Some(exited_at) = self.warp_exit.get(warp).copied().flatten() else {
+            return;
+        };
+        if self.has_pending_gmem(warp) || self.has_pending_smem(warp) {
+            return;
+        }
+        self.warp_exit[warp] = None;
+        let drain = now.saturating_sub(exited_at);
+        let stats = &mut self.warp_exits;
+        stats.drain_cycles_sum = stats.drain_cycles_sum.saturating_add(drain);
+        stats.max_drain_cycles = stats.max_drain_cycles.max(drain);
+    }
+
+    pub fn outstanding_neutrino(&self) -> usize {
+        self.graph.neutrino_inflight()
+    }
+
+    /// Whether every subgraph is idle at `now`: no memory request, fetch,
+    /// execute op, fence or DMA/tensor job in flight, nothing queued to
+    /// issue or write back, and the store buffer empty.
+    pub fn is_drained(&self, now: Cycle) -> bool {
+        let settled =
+            |slots: &[Option<Cycle>]| slots.iter().flatten().all(|&ready_at| ready_at <= now);
+        self.outstanding_gmem() == 0
+            && self.outstanding_smem() == 0
+            && self.outstanding_neutrino() == 0
+            && self.pending_writeback.is_empty()
+            && self.pending_dma.is_empty()
+            && self.pending_tensor.is_empty()
+            && self.pending_fence.is_empty()
+            && self.pending_cluster_gmem.is_empty()
+            && self.pending_cluster_smem.is_empty()
+            && self.execute_results.is_empty()
+            && self.store_buffer.is_empty()
+            && self.fence_inflight.iter().all(Option::is_none)
+            && self
+                .icache_inflight
+                .iter()
+                .flatten()
+                .all(|fetch| fetch.ready_at <= now)
+            && settled(&self.pending_execute)
+            && settled(&self.pending_neutrino)
+            && settled(&self.pending_copy)
+            && !self.graph.dma_is_busy()
+            && !self.graph.tensor_is_busy()
+            && !self.graph.copy_engine_is_busy()
+    }
+
+    /// Ticks the model on its own from `now`, without issuing anything new,
+    /// until `is_drained`. Returns the cycle it stopped on: the first with
+    /// nothing in flight, or `now + budget` if work is still in flight then.
+    /// Cores sharing a cluster gmem graph have to drain in lockstep instead;
+    /// see `Sim::drain_all`.
+    pub fn drain(&mut self, now: Cycle, budget: Cycle, scheduler: &mut Scheduler) -> Cycle {
+        let deadline = now.saturating_add(budget);
+        let mut now = now;
+        while now < deadline && !self.is_drained(now) {
+            self.tick(now, scheduler);
+            now += 1;
+        }
+        now
+    }
+
+    /// The core's timing graph, e.g. to register completion hooks with
+    /// `CoreGraph::on_gmem_completion`.
+    pub fn core_graph_mut(&mut self) -> &mut CoreGraph {
+        &mut self.graph
+    }
+
+    /// Starts or stops recording completed gmem requests.
+    pub fn set_gmem_completion_log(&mut self, enabled: bool) {
+        match (enabled, self.gmem_completion_log.is_some()) {
+            (true, false) => self.gmem_completion_log = Some(Vec::new()),
+            (false, true) => self.gmem_completion_log = None,
+            _ => {}
+        }
+    }
+
+    /// Gmem requests completed since the last call, in completion order.
+    pub fn take_gmem_completions(&mut self) -> Vec<GmemCompletion> {
+        self.gmem_completion_log
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    /// Tags the requests of every warp with kernel context `context`.
+    pub fn set_context(&mut self, context: usize) {
+        self.warp_contexts.fill(context);
+    }
+
+    /// Tags the requests `warp` issues from now on with kernel context
+    /// `context`, e.g. for a block of a second kernel placed on the core.
+    pub fn set_warp_context(&mut self, warp: usize, context: usize) {
+        if let Some(slot) = self.warp_contexts.get_mut(warp) {
+            *slot = context;
+        }
+    }
+
+    /// Starts or stops the per-cycle utilization and occupancy samples,
+    /// `CYCLOTRON_TIMING_LOG_STATS` logging and the core graph's activity
+    /// sampling. Event counters and perf counter CSRs keep counting.
+    pub fn set_stats(&mut self, enabled: bool) {
+        self.stats = StatsSwitch::new(enabled);
+        self.log_stats &= self.stats.on();
+        self.graph.set_stats(enabled);
+    }
+
+    /// Starts or stops stamping instructions for the pipeline view.
+    pub fn set_pipeview(&mut self, enabled: bool) {
+        match (enabled, self.pipeview.is_some()) {
+            (true, false) => {
+                self.pipeview = Some(PipeviewTracker::new(self.pending_gmem.len()));
+            }
+            (false, true) => self.pipeview = None,
+            _ => {}
+        }
+    }
+
+    /// Instructions whose lifecycle finished since the last call.
+    pub fn take_pipeview(&mut self) -> Vec<InstLifecycle> {
+        self.pipeview
+            .as_mut()
+            .map(PipeviewTracker::take)
+            .unwrap_or_default()
+    }
+
+    pub fn warp_state(&self, warp: usize) -> WarpTimingState {
+        let ids = |slots: &Vec<VecDeque<(u64, Cycle)>>| -> Vec<u64> {
+            let mut ids: Vec<u64> = slots
+                .get(warp)
+                .map(|queue| queue.iter().map(|(id, _)| *id).collect())
+                .unwrap_or_default();
+            ids.dedup();
+            ids
+        };
+        WarpTimingState {
+            gmem_requests: ids(&self.pending_gmem),
+            smem_requests: ids(&self.pending_smem),
+            fence_request: self.fence_inflight.get(warp).copied().flatten(),
+            queued_fences: self
+                .pending_fence
+                .iter()
+                .filter(|fence| fence.warp == warp)
+                .map(|fence| fence.request_id)
+                .collect(),
+            execute_ready_at: self.pending_execute.get(warp).copied().flatten(),
+            neutrino_ready_at: self.pending_neutrino.get(warp).copied().flatten(),
+            copy_ready_at: self.pending_copy.get(warp).copied().flatten(),
+            icache_ready_at: self
+                .icache_inflight
+                .get(warp)
+                .copied()
+                .flatten()
+                .map(|inflight| inflight.ready_at),
+            exited_at: self.warp_exit.get(warp).copied().flatten(),
+        }
+    }
+
     pub fn stats(&self) -> CoreStats {
         let gmem_stats = self.graph.cluster_gmem_stats(self.core_id);
         CoreStats {
@@ -297,6 +644,7 @@ impl CoreTimingModel {
         CorePerfSummary {
             core_id: self.core_id,
             cluster_id: self.cluster_id,
+            functional: false,
             scheduler: self.scheduler_stats,
             smem_util: self.smem_util,
             execute_util: self.execute_util,
@@ -306,11 +654,35 @@ impl CoreTimingModel {
             tensor_bytes_issued: self.graph.tensor_bytes_issued(),
             tensor_bytes_completed: self.graph.tensor_bytes_completed(),
             tensor_util: self.tensor_util,
+            neutrino_stats: self.graph.neutrino_stats(),
+            neutrino_util: self.neutrino_util,
+            copy_engine_stats: self.graph.copy_engine_stats(),
+            copy_engine_util: self.copy_engine_util,
             smem_conflicts: self.smem_conflicts_summary,
+            smem_warps: self.smem_warps.clone(),
+            access_patterns: self.access_patterns.clone(),
             gmem_hits: self.gmem_hits,
             latencies: self.latencies,
+            context_mem: self.context_mem.clone(),
+            mlp: self.mlp.clone(),
             gmem_stats,
             gmem_level_stats,
+            memory_regions: self.graph.cluster_gmem_region_stats(self.core_id),
+            crossbar: self.graph.cluster_gmem_crossbar_stats(self.core_id),
+            dram_share: self.graph.cluster_gmem_dram_share_stats(),
+            dram_channels: self.graph.cluster_gmem_dram_channel_stats(),
+            dies: self.graph.cluster_gmem_die_traffic_stats(),
+            cache_banks: self.graph.cluster_gmem_cache_bank_activity(),
+            bottlenecks: self
+                .graph
+                .bottlenecks(&format!("cluster{}_core{}_", self.cluster_id, self.core_id)),
+            gmem_bottlenecks: self.graph.cluster_gmem_bottlenecks(),
+            fill_forward: self.graph.cluster_gmem_fill_forward_stats(),
+            cache_hints: self.graph.cluster_gmem_cache_hint_stats(),
+            injectors: self.graph.cluster_gmem_injector_stats(),
+            write_combining: self.graph.cluster_gmem_write_combining_stats(),
+            adaptive_routing: self.graph.cluster_gmem_adaptive_route_stats(),
+            refill_contention: self.graph.cluster_gmem_refill_contention(),
             smem_stats: smem_stats_snapshot.clone(),
             icache_stats: icache_stats_snapshot,
             lsu_stats: lsu_stats_snapshot,
@@ -323,7 +695,24 @@ impl CoreTimingModel {
                 gmem_busy: gmem_stats.busy_rejects(),
                 smem_queue_full: smem_stats_snapshot.queue_full_rejects,
                 smem_busy: smem_stats_snapshot.busy_rejects,
+                strict_inorder: self.strict_inorder_rejects,
             },
+            wakeups: self.wakeups.stats(),
+            retry_aging: self.retry_aging,
+            flush_ordering: self.flush_ordering,
+            request_timeouts: self.timeouts.stats(),
+            dependencies: self.dependencies.stats(),
+            warp_exits: self.warp_exits,
+            cpi_stack: self.cpi_stack,
+            warp_retire: self.perf_counters.warp_stats().to_vec(),
+            power: self.power.summary(),
+            context_switch: self.contexts.stats(),
+            reservation_stations: self.reservation.stats(),
+            forwarding: self.forwarding.stats(),
+            ibuffer: self.ibuffers.stats(),
+            store_buffer: self.store_buffer.stats(),
+            fences: self.fence_stats,
+            div_sqrt: self.graph.div_sqrt_stats(),
             gmem_latency_hist: self.gmem_latency_hist,
             smem_latency_hist: self.smem_latency_hist,
         }
@@ -335,11 +724,49 @@ impl CoreTimingModel {
         self.graph.clear_icache_stats();
         self.graph.clear_lsu_stats();
         self.graph.clear_writeback_stats();
+        self.graph.clear_neutrino_stats();
+        self.graph.clear_copy_engine_stats();
+        self.graph.clear_dma_stats();
+        self.graph.clear_tensor_stats();
         self.last_logged_gmem_completed = 0;
         self.last_logged_smem_completed = 0;
+        self.scheduler_stats = super::SchedulerSummary {
+            issue_width: self.scheduler_stats.issue_width,
+            ..super::SchedulerSummary::default()
+        };
+        self.smem_util = super::SmemUtilSummary::default();
+        self.smem_conflicts_summary = super::SmemConflictSummary::default();
+        self.smem_warps = self.smem_warps.cleared();
+        self.access_patterns = super::AccessPatternSummary::default();
+        self.gmem_hits = super::GmemHitSummary::default();
+        self.retry_aging = super::RetryAgingSummary::default();
+        self.flush_ordering = super::FlushOrderingSummary::default();
+        self.timeouts.clear_stats();
+        self.dependencies.clear_stats();
+        self.warp_exits = super::WarpExitSummary::default();
+        self.strict_inorder_rejects = 0;
+        self.wakeups.clear_stats();
+        self.cpi_stack = super::CpiStack::default();
+        self.power.clear_stats();
+        self.contexts.clear_stats();
+        self.reservation.clear_stats();
+        self.forwarding.clear_stats();
+        self.ibuffers.clear_stats();
+        self.perf_counters.clear_stats();
+        self.store_buffer.clear_stats();
+        self.graph.clear_execute_stats();
+        self.fence_stats = FenceStats::default();
+        self.latencies = super::LatencySummary::default();
+        self.context_mem.clear();
+        self.mlp = super::MlpSummary {
+            per_warp: vec![super::WarpMlpSummary::default(); self.mlp.per_warp.len()],
+            ..super::MlpSummary::default()
+        };
         self.execute_util = super::ExecuteUtilSummary::default();
         self.dma_util = super::BasicUtilSummary::default();
         self.tensor_util = super::BasicUtilSummary::default();
+        self.neutrino_util = super::BasicUtilSummary::default();
+        self.copy_engine_util = super::BasicUtilSummary::default();
         self.gmem_latency_hist = super::LatencyHistogram::default();
         self.smem_latency_hist = super::LatencyHistogram::default();
         self.pending_execute
@@ -347,6 +774,34 @@ impl CoreTimingModel {
             .for_each(|slot| *slot = None);
     }
 
+    /// Clears the gmem cache hierarchy counters shared by every core in the
+    /// cluster graph.
+    pub fn clear_shared_stats(&mut self) {
+        self.graph.cluster_gmem_clear_hierarchy_stats();
+    }
+
+    /// Applies the per-core parts of a mid-run timing change: the warp
+    /// scheduler, power governor, warp slots and execute unit timings. Work already in
+    /// flight finishes with the timing it was issued under.
+    pub fn set_timing(&mut self, config: &CoreGraphConfig) {
+        self.issue_scheduler = WarpIssueScheduler::new(config.compute.scheduler.clone());
+        self.power.set_config(config.compute.power.clone());
+        self.contexts.set_config(config.compute.context_switch);
+        self.ibuffers.set_config(config.compute.ibuffer);
+        self.perf_counters.set_config(config.compute.perf_counters);
+        self.reservation
+            .set_config(config.compute.execute.reservation_stations);
+        self.forwarding
+            .set_config(config.compute.execute.forwarding.clone());
+        self.graph.execute_set_config(&config.compute.execute);
+    }
+
+    /// Applies the cluster-shared gmem timings; call once per run, like
+    /// `clear_shared_stats`.
+    pub fn set_shared_timing(&mut self, config: &CoreGraphConfig) {
+        self.graph.cluster_gmem_set_timing(&config.memory.gmem);
+    }
+
     fn sample_metrics(&mut self, now: Cycle) {
         if self.last_metrics_cycle == Some(now) {
             return;
@@ -354,6 +809,9 @@ impl CoreTimingModel {
         self.last_metrics_cycle = Some(now);
         self.smem_util.cycles = self.smem_util.cycles.saturating_add(1);
         self.execute_util.cycles = self.execute_util.cycles.saturating_add(1);
+        if self.reservation.any_enabled() {
+            self.reservation.sample(now);
+        }
         self.dma_util.cycles = self.dma_util.cycles.saturating_add(1);
         if self.graph.dma_is_busy() {
             self.dma_util.busy_sum = self.dma_util.busy_sum.saturating_add(1);
@@ -362,6 +820,21 @@ impl CoreTimingModel {
         if self.graph.tensor_is_busy() {
             self.tensor_util.busy_sum = self.tensor_util.busy_sum.saturating_add(1);
         }
+        self.neutrino_util.cycles = self.neutrino_util.cycles.saturating_add(1);
+        if self.graph.neutrino_is_busy() {
+            self.neutrino_util.busy_sum = self.neutrino_util.busy_sum.saturating_add(1);
+        }
+        let mut core_misses = 0u64;
+        for (warp, summary) in self.mlp.per_warp.iter_mut().enumerate() {
+            let outstanding = self.graph.lsu_outstanding_misses(warp) as u64;
+            summary.sample(outstanding);
+            core_misses = core_misses.saturating_add(outstanding);
+        }
+        self.mlp.core.sample(core_misses);
+        self.copy_engine_util.cycles = self.copy_engine_util.cycles.saturating_add(1);
+        if self.graph.copy_engine_is_busy() {
+            self.copy_engine_util.busy_sum = self.copy_engine_util.busy_sum.saturating_add(1);
+        }
 
         use crate::timeflow::ExecUnitKind;
         if self.graph.execute_is_busy(ExecUnitKind::Int) {
@@ -381,6 +854,14 @@ impl CoreTimingModel {
         if self.graph.execute_is_busy(ExecUnitKind::Sfu) {
             self.execute_util.sfu_busy_sum = self.execute_util.sfu_busy_sum.saturating_add(1);
         }
+        if self.graph.execute_is_busy(ExecUnitKind::Shuffle) {
+            self.execute_util.shuffle_busy_sum =
+                self.execute_util.shuffle_busy_sum.saturating_add(1);
+        }
+        if self.graph.execute_is_busy(ExecUnitKind::DivSqrt) {
+            self.execute_util.div_sqrt_busy_sum =
+                self.execute_util.div_sqrt_busy_sum.saturating_add(1);
+        }
 
         let sample = self.graph.sample_smem_utilization();
         self.smem_util.lane_busy_sum = self
@@ -448,6 +929,7 @@ impl CoreTimingModel {
         if let Some(session) = &self.perf_log_session {
             let record = perf_log::StatsRecord {
                 cycle: now,
+                warmup: session.in_warmup(now),
                 summary: self.perf_summary(),
             };
             session.write_stats(&record);
@@ -471,6 +953,55 @@ impl CoreTimingModel {
         bytes: u32,
         reason: Option<&str>,
     ) {
-        let _ = (cycle, event, warp, request_id, bytes, reason);
+        let space = match event.split('_').next() {
+            Some("gmem") => Some(RequestSpace::Gmem),
+            Some("smem") => Some(RequestSpace::Smem),
+            _ => None,
+        };
+        if let (Some(space), Some(id)) = (space, request_id) {
+            self.note_request(cycle, space, id, || match reason {
+                Some(reason) => format!("{event} ({reason})"),
+                None => event.to_string(),
+            });
+        }
+        if let Some(session) = &self.perf_log_session {
+            if session.logs_request_events() {
+                session.write_request_event(crate::sim::sqlite_log::RequestEvent {
+                    cycle,
+                    cluster_id: self.cluster_id,
+                    core_id: self.core_id,
+                    warp,
+                    request_id,
+                    event: event.to_string(),
+                    bytes,
+                    reason: reason.map(str::to_string),
+                });
+            }
+        }
+        let Some(pipeview) = self.pipeview.as_mut() else {
+            return;
+        };
+        match event {
+            "gmem_issue" => pipeview.issue(cycle, warp, request_id.map(MemRequest::Gmem)),
+            "smem_issue" => pipeview.issue(cycle, warp, request_id.map(MemRequest::Smem)),
+            "exec_issue" | "rs_allocate" | "copy_issue" | "neutrino_issue" => {
+                pipeview.issue(cycle, warp, None)
+            }
+            "exec_complete" | "copy_complete" | "neutrino_complete" => pipeview.ready(cycle, warp),
+            "gmem_complete" | "smem_complete" => {
+                let (pending, request): (_, fn(u64) -> MemRequest) = if event == "gmem_complete" {
+                    (&self.pending_gmem, MemRequest::Gmem)
+                } else {
+                    (&self.pending_smem, MemRequest::Smem)
+                };
+                // A split request completes once per part; the last one
+                // leaves no pending entry behind.
+                let done = |id: &u64| pending[warp].iter().all(|(pending_id, _)| pending_id != id);
+                if let Some(id) = request_id.filter(done) {
+                    pipeview.request_done(cycle, request(id));
+                }
+            }
+            _ => {}
+        }
     }
 }
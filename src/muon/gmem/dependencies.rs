@@ -0,0 +1,96 @@
+use crate::muon::scheduler::Scheduler;
+use crate::timeflow::{DependencyError, RequestKind, RequestRef};
+use crate::timeq::Cycle;
+
+use super::wakeups::WakeOn;
+use super::CoreTimingModel;
+
+impl CoreTimingModel {
+    /// Hands out the id of a future request of `kind`, so that dependencies
+    /// can be declared on it before it issues. Gmem and smem requests carry
+    /// it in their `id`; DMA and tensor jobs pass it to `submit_dma` and
+    /// `submit_tensor`.
+    pub fn reserve_request(&mut self, kind: RequestKind) -> RequestRef {
+        let next = match kind {
+            RequestKind::Gmem => &mut self.next_gmem_id,
+            RequestKind::Smem => &mut self.next_smem_id,
+            RequestKind::Dma => &mut self.next_dma_id,
+            RequestKind::Tensor => &mut self.next_tensor_id,
+        };
+        let id = (*next).max(1);
+        *next = id.saturating_add(1);
+        let request = RequestRef::new(kind, id);
+        self.dependencies.open(request);
+        request
+    }
+
+    /// Holds `waiter` back from issuing until `on` completes, on top of
+    /// the ordering a warp already gets from its own requests. A
+    /// dependency on a request that already completed is satisfied at
+    /// once.
+    pub fn add_request_dependency(
+        &mut self,
+        waiter: RequestRef,
+        on: RequestRef,
+    ) -> Result<(), DependencyError> {
+        let in_flight = match on.kind {
+            RequestKind::Gmem => self.gmem_issue_cycle.contains_key(&on.id),
+            RequestKind::Smem => self.smem_issue_cycle.contains_key(&on.id),
+            RequestKind::Dma | RequestKind::Tensor => false,
+        };
+        self.dependencies.add(waiter, on, in_flight)
+    }
+
+    pub fn request_blocked(&self, request: RequestRef) -> bool {
+        self.dependencies.is_blocked(request)
+    }
+
+    /// Queues a DMA transfer under an id from `reserve_request`. It issues
+    /// once its dependencies have completed and the DMA queue has room.
+    pub fn submit_dma(&mut self, now: Cycle, id: u64, bytes: u32) {
+        self.enqueue_dma(now, id, bytes);
+    }
+
+    /// Like `submit_dma`, for the tensor queue.
+    pub fn submit_tensor(&mut self, now: Cycle, id: u64, bytes: u32) {
+        self.enqueue_tensor(now, id, bytes);
+    }
+
+    /// Parks `warp` if `request` still waits on a dependency; the warp
+    /// retries once the last one completes.
+    pub(super) fn hold_for_dependency(
+        &mut self,
+        now: Cycle,
+        warp: usize,
+        request: RequestRef,
+        bytes: u32,
+        scheduler: &mut Scheduler,
+    ) -> bool {
+        if !self.dependencies.is_blocked(request) {
+            return false;
+        }
+        self.park_warp(now, warp, WakeOn::Dependency(request), scheduler);
+        self.dependencies.record_blocked();
+        let event = match request.kind {
+            RequestKind::Smem => "smem_reject",
+            _ => "gmem_reject",
+        };
+        self.trace_event(
+            now,
+            event,
+            warp,
+            Some(request.id),
+            bytes,
+            Some("dependency"),
+        );
+        true
+    }
+
+    /// `request` completed or was dropped: releases whatever waited on it.
+    /// Released DMA and tensor jobs issue on the next drain of their queue.
+    pub(super) fn release_request(&mut self, now: Cycle, request: RequestRef) {
+        for waiter in self.dependencies.complete(request) {
+            self.wakeups.release(now, waiter);
+        }
+    }
+}
@@ -1,15 +1,20 @@
 use crate::info;
-use crate::muon::decode::IssuedInst;
+use crate::muon::decode::{DecodedInst, IssuedInst};
 use crate::muon::execute::Opcode;
 use crate::muon::scheduler::Scheduler;
 use crate::timeflow::lsu::LsuPayload;
 use crate::timeflow::{
-    execute::ExecUnitKind, GmemRequest, GmemRequestKind, IcacheIssue, IcacheReject, IcacheRequest,
-    LsuIssue, LsuReject, LsuRejectReason, SmemRequest,
+    contiguous_span, execute::ExecUnitKind, ByteMask, DivSqrtOp, ExecuteWriteback, FenceOrder,
+    FenceRequest, FenceScope, GmemRequest, GmemRequestKind, IcacheIssue, IcacheReject,
+    IcacheRequest, LsuIssue, LsuReject, LsuRejectReason, NeutrinoIssue, NeutrinoOpKind,
+    NeutrinoRejectReason, PerfCounters, RequestRef, RetireClass, SmemRequest,
 };
 use crate::timeq::{normalize_retry, Backpressure, Cycle, Ticket};
+use crate::utils::BitSlice;
 
-use super::{CoreTimingModel, IcacheInflight};
+use super::pipeview::MemRequest;
+use super::wakeups::WakeOn;
+use super::{AccessPattern, CoreTimingModel, CpiStall, IcacheInflight};
 
 impl CoreTimingModel {
     pub fn issue_gmem_request(
@@ -23,7 +28,28 @@ impl CoreTimingModel {
             return Err(now.saturating_add(1));
         }
 
+        if let Some(ready_at) = self.pending_copy[warp] {
+            if now >= ready_at {
+                self.pending_copy[warp] = None;
+                if request.id != 0 {
+                    self.release_request(now, RequestRef::gmem(request.id));
+                }
+                self.trace_event(now, "copy_complete", warp, None, request.bytes, None);
+                self.update_scheduler_state(warp, scheduler);
+                return Ok(Ticket::new(now, now, request.bytes));
+            }
+            scheduler.set_resource_wait_until(warp, Some(ready_at));
+            scheduler.replay_instruction(warp);
+            return Err(ready_at);
+        }
+        if let Some(wait_until) =
+            self.strict_inorder_wait(now, warp, "gmem_reject", request.bytes, scheduler)
+        {
+            return Err(wait_until);
+        }
+
         request.warp = warp;
+        request.context = self.warp_contexts[warp];
         request.core_id = self.core_id;
         request.cluster_id = self.cluster_id;
         if request.id == 0 {
@@ -33,9 +59,22 @@ impl CoreTimingModel {
                 self.next_gmem_id
             };
         }
+        let dependency = RequestRef::gmem(request.id);
+        if self.hold_for_dependency(now, warp, dependency, request.bytes, scheduler) {
+            return Err(Cycle::MAX);
+        }
         self.maybe_convert_mmio_flush(&mut request);
+        let mut copy_stream = None;
+        let mut access = None;
         if request.kind.is_mem() {
             if let Some(lane_addrs) = request.lane_addrs.as_ref() {
+                let bytes_per_lane = request.bytes / request.active_lanes.max(1);
+                let span = contiguous_span(lane_addrs, bytes_per_lane);
+                copy_stream = self
+                    .graph
+                    .copy_engine_observe(warp, request.is_load, span)
+                    .and(span);
+
                 // coalesce at the first cache level's line size.
                 let line_bytes = if self.gmem_policy.l0_enabled {
                     self.gmem_policy.l0_line_bytes.max(1)
@@ -48,26 +87,32 @@ impl CoreTimingModel {
                     .collect();
                 lines.sort_unstable();
                 lines.dedup();
+                let pattern = AccessPattern::classify(lane_addrs, bytes_per_lane, line_bytes);
+                access = Some((request.pc, pattern, lines.len()));
+                if !request.is_load && !lines.is_empty() {
+                    let mut masks = vec![ByteMask::new(line_bytes as u32); lines.len()];
+                    for &addr in lane_addrs {
+                        let line = (addr / line_bytes) * line_bytes;
+                        if let Ok(idx) = lines.binary_search(&line) {
+                            masks[idx].set_range((addr - line) as u32, bytes_per_lane.max(1));
+                        }
+                    }
+                    request.line_byte_enables = Some(masks);
+                }
                 if !lines.is_empty() {
                     request.coalesced_lines = Some(lines);
                 }
             }
         }
         request.lane_addrs = None;
-        if request.stall_on_completion {
-            if let Some(slot) = self.pending_gmem.get(warp) {
-                if !slot.is_empty() {
-                    let wait_until = slot
-                        .iter()
-                        .map(|(_, ready_at)| *ready_at)
-                        .min()
-                        .unwrap_or(now.saturating_add(1))
-                        .max(now.saturating_add(1));
-                    scheduler.set_resource_wait_until(warp, Some(wait_until));
-                    scheduler.replay_instruction(warp);
-                    return Err(wait_until);
-                }
-            }
+        // A load the STQ can forward does not have to wait for the store.
+        let forwardable = request.is_load
+            && self
+                .graph
+                .lsu_can_forward(&LsuPayload::Gmem(request.clone()));
+        if request.stall_on_completion && !forwardable && self.has_pending_gmem(warp) {
+            self.park_warp(now, warp, WakeOn::Drained(FenceScope::Global), scheduler);
+            return Err(Cycle::MAX);
         }
         let issue_bytes = request.bytes;
         let request_id = request.id;
@@ -82,26 +127,71 @@ impl CoreTimingModel {
             .map(|lines| lines.len().max(1))
             .unwrap_or(1);
         let is_flush = request.kind.is_flush_l0() || request.kind.is_flush_l1();
+        let is_load = request.is_load;
         if let Err(reject) = self.graph.operand_fetch_try_issue(now, request.bytes) {
             let wait_until = reject.retry_at.max(now.saturating_add(1));
             scheduler.set_resource_wait_until(warp, Some(wait_until));
             scheduler.replay_instruction(warp);
             return Err(wait_until);
         }
+        if dma_trigger || tensor_trigger {
+            copy_stream = None;
+        }
+        if let Some((addr, bytes)) = copy_stream {
+            if let Some(ready_at) = self
+                .graph
+                .copy_engine_try_accelerate(now, warp, is_load, addr, bytes)
+            {
+                self.graph.copy_engine_record(issue_bytes, true);
+                if let Some((pc, pattern, lines)) = access {
+                    self.access_patterns.record(pc, pattern, lines);
+                }
+                self.trace_event(now, "copy_issue", warp, Some(request_id), issue_bytes, None);
+                // Accelerated stores retire immediately; loads hold the warp
+                // until their burst lands, like a synchronous offload.
+                if is_load && ready_at > now {
+                    self.pending_copy[warp] = Some(ready_at);
+                    scheduler.set_resource_wait_until(warp, Some(ready_at));
+                    scheduler.replay_instruction(warp);
+                    return Err(ready_at);
+                }
+                self.release_request(now, RequestRef::gmem(request_id));
+                return Ok(Ticket::new(now, ready_at, issue_bytes));
+            }
+        }
         let issue_result = self.graph.lsu_issue_gmem(now, request);
         match issue_result {
-            Ok(LsuIssue { ticket }) => {
+            Ok(LsuIssue { ticket, forwarded }) => {
                 let ready_at = ticket.ready_at();
+                // A forwarded load comes back as one completion from the LSU.
+                let split_count = if forwarded { 1 } else { split_count };
+                if copy_stream.is_some() {
+                    self.graph.copy_engine_record(issue_bytes, false);
+                }
+                if let Some((pc, pattern, lines)) = access {
+                    self.access_patterns.record(pc, pattern, lines);
+                }
                 self.gmem_issue_cycle.entry(request_id).or_insert(now);
                 self.add_gmem_pending(warp, request_id, ready_at, scheduler, split_count);
-                if is_flush {
+                let acquires = self
+                    .flush_fences
+                    .get(&request_id)
+                    .is_none_or(|fence| fence.order.acquires());
+                if is_flush && acquires {
                     self.register_fence(warp, request_id, scheduler);
                 }
+                if is_flush && self.gmem_policy.orders_flushes() {
+                    self.inflight_flushes.push((request_id, warp));
+                    self.flush_ordering.flushes = self.flush_ordering.flushes.saturating_add(1);
+                }
                 if dma_trigger {
-                    self.enqueue_dma(now, issue_bytes.max(1));
+                    self.enqueue_dma(now, 0, issue_bytes.max(1));
                 }
                 if tensor_trigger {
-                    self.enqueue_tensor(now, issue_bytes.max(1));
+                    self.enqueue_tensor(now, 0, issue_bytes.max(1));
+                }
+                if !forwarded {
+                    self.power.record_gmem();
                 }
                 self.trace_event(now, "gmem_issue", warp, Some(request_id), issue_bytes, None);
                 info!(
@@ -125,6 +215,7 @@ impl CoreTimingModel {
                 let reason_str = match reason {
                     LsuRejectReason::Busy => "busy",
                     LsuRejectReason::QueueFull => "queue_full",
+                    LsuRejectReason::FlushLimit => "flush_limit",
                 };
                 let (request_id, request_bytes) = match request {
                     LsuPayload::Gmem(req) => (req.id, req.bytes),
@@ -151,6 +242,74 @@ impl CoreTimingModel {
         }
     }
 
+    /// Issues a `fence` ordering `scope` for `warp`. A release fence first
+    /// waits for the warp's outstanding requests in `scope`; an acquire
+    /// fence holds the warp until the fence queue releases it. Fences that
+    /// order gmem flush the warp's L1, the others go straight to the fence
+    /// queue.
+    pub fn issue_fence(
+        &mut self,
+        now: Cycle,
+        warp: usize,
+        scope: FenceScope,
+        order: FenceOrder,
+        scheduler: &mut Scheduler,
+    ) -> Result<Ticket, Cycle> {
+        if warp >= self.pending_gmem.len() {
+            return Err(now.saturating_add(1));
+        }
+        if order.releases() {
+            let gmem = scope.orders_global() && self.has_pending_gmem(warp);
+            let smem = scope.orders_shared() && self.has_pending_smem(warp);
+            if gmem || smem {
+                self.fence_drain_since[warp].get_or_insert(now);
+                self.park_warp(now, warp, WakeOn::Drained(scope), scheduler);
+                return Err(Cycle::MAX);
+            }
+        }
+        let request_id = self.next_gmem_id.max(1);
+        let fence = FenceRequest {
+            warp,
+            request_id,
+            scope,
+            order,
+        };
+        let ticket = if scope.orders_global() {
+            let mut flush = GmemRequest::new_flush_l1(warp, 1);
+            flush.id = request_id;
+            flush.stall_on_completion = order.releases();
+            self.flush_fences.insert(request_id, fence);
+            match self.issue_gmem_request(now, warp, flush, scheduler) {
+                Ok(ticket) => ticket,
+                Err(retry_at) => {
+                    self.flush_fences.remove(&request_id);
+                    return Err(retry_at);
+                }
+            }
+        } else {
+            self.next_gmem_id = request_id.saturating_add(1);
+            if order.acquires() {
+                self.register_fence(warp, request_id, scheduler);
+            }
+            self.enqueue_fence(now, fence);
+            Ticket::new(now, now, 0)
+        };
+
+        let drain_since = self.fence_drain_since[warp].take();
+        let stats = self.fence_stats.scope_mut(scope);
+        stats.fences = stats.fences.saturating_add(1);
+        if let Some(since) = drain_since {
+            stats.drained = stats.drained.saturating_add(1);
+            stats.drain_cycles = stats.drain_cycles.saturating_add(now.saturating_sub(since));
+        }
+        match order {
+            FenceOrder::Acquire => stats.acquire_only = stats.acquire_only.saturating_add(1),
+            FenceOrder::Release => stats.release_only = stats.release_only.saturating_add(1),
+            FenceOrder::AcqRel => {}
+        }
+        Ok(ticket)
+    }
+
     pub fn issue_smem_request(
         &mut self,
         now: Cycle,
@@ -161,8 +320,14 @@ impl CoreTimingModel {
         if warp >= self.pending_smem.len() {
             return Err(now.saturating_add(1));
         }
+        if let Some(wait_until) =
+            self.strict_inorder_wait(now, warp, "smem_reject", request.bytes, scheduler)
+        {
+            return Err(wait_until);
+        }
 
         request.warp = warp;
+        request.context = self.warp_contexts[warp];
         if request.id == 0 {
             request.id = if self.next_smem_id == 0 {
                 1
@@ -172,6 +337,10 @@ impl CoreTimingModel {
         } else if request.id >= self.next_smem_id {
             self.next_smem_id = request.id.saturating_add(1);
         }
+        let dependency = RequestRef::smem(request.id);
+        if self.hold_for_dependency(now, warp, dependency, request.bytes, scheduler) {
+            return Err(Cycle::MAX);
+        }
         if request.id >= self.next_smem_id {
             self.next_smem_id = request.id.saturating_add(1);
         }
@@ -187,14 +356,16 @@ impl CoreTimingModel {
         }
         let issue_result = self.graph.lsu_issue_smem(now, request);
         match issue_result {
-            Ok(LsuIssue { ticket }) => {
+            Ok(LsuIssue { ticket, forwarded }) => {
                 let ready_at = ticket.ready_at();
+                let split_count = if forwarded { 1 } else { split_count };
                 self.smem_issue_cycle.entry(request_id).or_insert(now);
                 self.add_smem_pending(warp, request_id, ready_at, scheduler, split_count);
                 if let Some(sample) = conflict_sample {
                     self.record_smem_conflict(now, warp, request_id, sample);
                 }
-                self.trace_event(now, "smem_issue", warp, None, issue_bytes, None);
+                self.power.record_smem();
+                self.trace_event(now, "smem_issue", warp, Some(request_id), issue_bytes, None);
                 info!(
                     self.logger,
                     "[lsu] warp {} accepted smem request ready@{} bytes={}",
@@ -215,6 +386,7 @@ impl CoreTimingModel {
                 let reason_str = match reason {
                     LsuRejectReason::Busy => "busy",
                     LsuRejectReason::QueueFull => "queue_full",
+                    LsuRejectReason::FlushLimit => "flush_limit",
                 };
                 let (request_id, request_bytes) = match request {
                     LsuPayload::Smem(req) => (req.id, req.bytes),
@@ -241,6 +413,111 @@ impl CoreTimingModel {
         }
     }
 
+    /// Under `lsu.strict_inorder`, parks a warp's memory issue until its
+    /// outstanding access completes. Returns the retry cycle if it did, which
+    /// is left open: the completion sets it.
+    fn strict_inorder_wait(
+        &mut self,
+        now: Cycle,
+        warp: usize,
+        event: &str,
+        bytes: u32,
+        scheduler: &mut Scheduler,
+    ) -> Option<Cycle> {
+        if !self.strict_inorder {
+            return None;
+        }
+        if !self.has_pending_gmem(warp) && !self.has_pending_smem(warp) {
+            return None;
+        }
+        self.park_warp(now, warp, WakeOn::AnyCompletion, scheduler);
+        self.strict_inorder_rejects = self.strict_inorder_rejects.saturating_add(1);
+        self.trace_event(now, event, warp, None, bytes, Some("strict_inorder"));
+        Some(Cycle::MAX)
+    }
+
+    /// Checks the operands of `inst` against results still being produced
+    /// by execute issues. An instruction with a late operand takes a free
+    /// reservation-station entry of its unit and leaves the ibuffer,
+    /// dispatching once the operand is ready; without one it stalls its
+    /// warp. Only matters when some unit has reservation stations, or under
+    /// operand forwarding, which delays operands no bypass path reaches.
+    pub fn wait_operands(
+        &mut self,
+        now: Cycle,
+        warp: usize,
+        inst: &DecodedInst,
+        scheduler: &mut Scheduler,
+    ) -> Result<(), Cycle> {
+        if !(self.reservation.any_enabled() || self.forwarding.enabled())
+            || warp >= self.reg_ready.len()
+        {
+            return Ok(());
+        }
+        if self.pending_execute[warp].is_some() {
+            // Back to complete an execute issue that held the warp; its
+            // operands were checked when it issued.
+            return Ok(());
+        }
+        if let Some(dispatch_at) = self.rs_dispatch[warp] {
+            // Already placed in an entry on an earlier attempt that was then
+            // turned away by the unit.
+            if dispatch_at > now {
+                return Ok(());
+            }
+            self.rs_dispatch[warp] = None;
+        }
+        let consumer = self.exec_unit(inst.opcode, inst.f3, inst.f7);
+        let sources: Vec<_> = source_regs(inst)
+            .into_iter()
+            .filter(|&reg| reg != 0)
+            .filter_map(|reg| self.reg_ready[warp].get(&reg).copied())
+            .collect();
+        let mut result_at = sources.iter().map(|&(at, _)| at).max().unwrap_or(0);
+        let mut ready_at = sources
+            .iter()
+            .map(|&(at, producer)| self.forwarding.operand_ready(at, producer, consumer))
+            .max()
+            .unwrap_or(0);
+        if writes_rd(inst.opcode) && inst.rd_addr != 0 {
+            // A later write must not overtake an earlier one still in flight.
+            if let Some(&(at, _)) = self.reg_ready[warp].get(&inst.rd_addr) {
+                ready_at = ready_at.max(at);
+                result_at = result_at.max(at);
+            }
+        }
+        if ready_at <= now {
+            for &(at, _) in &sources {
+                self.forwarding.record_read(now, at);
+            }
+            return Ok(());
+        }
+        self.forwarding.record_stall(now, result_at, ready_at);
+
+        let kind = self
+            .exec_unit(inst.opcode, inst.f3, inst.f7)
+            .filter(|&kind| rs_eligible(inst.opcode) && self.reservation.enabled(kind));
+        if let Some(kind) = kind {
+            if self.reservation.has_free(now, kind) {
+                for &(at, _) in &sources {
+                    self.forwarding.record_read(ready_at, at);
+                }
+                self.reservation.allocate(now, kind, ready_at);
+                self.rs_dispatch[warp] = Some(ready_at);
+                self.trace_event(now, "rs_allocate", warp, None, 0, None);
+                return Ok(());
+            }
+        }
+        scheduler.set_resource_wait_until(warp, Some(ready_at));
+        scheduler.replay_instruction(warp);
+        if self.reservation.any_enabled() {
+            self.reservation
+                .record_operand_stall(ready_at - now, kind.is_some());
+        }
+        self.trace_event(now, "exec_reject", warp, None, 0, Some("operand_wait"));
+        Err(ready_at)
+    }
+
     pub fn issue_dma(&mut self, now: Cycle, bytes: u32) -> Result<Ticket, Cycle> {
         match self.graph.dma_try_issue(now, bytes) {
             Ok(ticket) => Ok(ticket),
@@ -267,13 +544,27 @@ impl CoreTimingModel {
             return Ok(Ticket::new(now, now, active_lanes.max(1)));
         }
         if active_lanes == 0 {
+            self.rs_dispatch[warp] = None;
             return Ok(Ticket::new(now, now, 0));
         }
+        if issued.opcode == Opcode::CUSTOM2 && self.graph.neutrino_is_enabled() {
+            return self.issue_neutrino(now, warp, issued, active_lanes, scheduler);
+        }
 
-        let kind = match exec_unit_for(issued) {
+        let kind = match self.exec_unit(issued.opcode, issued.f3, issued.f7) {
             Some(kind) => kind,
             None => return Ok(Ticket::new(now, now, active_lanes.max(1))),
         };
+        if self.reservation.enabled(kind) {
+            return self.issue_execute_nonblocking(
+                now,
+                warp,
+                kind,
+                issued,
+                active_lanes,
+                scheduler,
+            );
+        }
 
         if let Some(ready_at) = self.pending_execute[warp] {
             if now >= ready_at {
@@ -287,9 +578,22 @@ impl CoreTimingModel {
             return Err(ready_at);
         }
 
-        match self.graph.execute_issue(now, kind, active_lanes) {
+        match self.execute_issue(now, warp, kind, issued, active_lanes) {
             Ok(ticket) => {
                 let ready_at = ticket.ready_at();
+                if self.forwarding.enabled() && issued.rd_addr != 0 && writes_rd(issued.opcode) {
+                    self.reg_ready[warp].insert(issued.rd_addr, (ready_at, kind));
+                }
+                if self.execute_writeback {
+                    // Held until the result has been written back.
+                    let result = ExecuteWriteback { warp, active_lanes };
+                    self.execute_results.push((ready_at, result));
+                    self.pending_execute[warp] = Some(Cycle::MAX);
+                    scheduler.set_resource_wait_until(warp, Some(Cycle::MAX));
+                    scheduler.replay_instruction(warp);
+                    self.trace_event(now, "exec_issue", warp, None, active_lanes, None);
+                    return Err(Cycle::MAX);
+                }
                 if ready_at > now {
                     self.pending_execute[warp] = Some(ready_at);
                     scheduler.set_resource_wait_until(warp, Some(ready_at));
@@ -326,17 +630,244 @@ impl CoreTimingModel {
         }
     }
 
-    pub fn notify_csr_write(&mut self, now: Cycle, csr_addr: u32) {
+    /// `exec_unit_for`, with div/sqrt ops on the pipelined `int_div` and
+    /// `fp` units when the core has no dividers.
+    fn exec_unit(&self, opcode: u8, f3: u8, f7: u8) -> Option<ExecUnitKind> {
+        match exec_unit_for(opcode, f3, f7) {
+            Some(ExecUnitKind::DivSqrt) if !self.graph.div_sqrt_enabled() => {
+                match div_sqrt_op(opcode, f3, f7) {
+                    Some(DivSqrtOp::IntDiv) => Some(ExecUnitKind::IntDiv),
+                    _ => Some(ExecUnitKind::Fp),
+                }
+            }
+            kind => kind,
+        }
+    }
+
+    fn execute_issue(
+        &mut self,
+        now: Cycle,
+        warp: usize,
+        kind: ExecUnitKind,
+        issued: &IssuedInst,
+        active_lanes: u32,
+    ) -> Result<Ticket, Backpressure<()>> {
+        match div_sqrt_op(issued.opcode, issued.f3, issued.f7) {
+            Some(op) if kind == ExecUnitKind::DivSqrt => {
+                self.graph
+                    .execute_issue_div_sqrt(now, warp, op, active_lanes)
+            }
+            _ => self.graph.execute_issue(now, kind, active_lanes),
+        }
+    }
+
+    /// Issue path of units with reservation stations: the warp moves on as
+    /// soon as the unit accepts the instruction, and the result's ready
+    /// cycle goes to the register scoreboard for `wait_operands`.
+    fn issue_execute_nonblocking(
+        &mut self,
+        now: Cycle,
+        warp: usize,
+        kind: ExecUnitKind,
+        issued: &IssuedInst,
+        active_lanes: u32,
+        scheduler: &mut Scheduler,
+    ) -> Result<Ticket, Cycle> {
+        let dispatch_at = self.rs_dispatch[warp].unwrap_or(now).max(now);
+        match self.execute_issue(dispatch_at, warp, kind, issued, active_lanes) {
+            Ok(ticket) => {
+                self.rs_dispatch[warp] = None;
+                if issued.rd_addr != 0 && writes_rd(issued.opcode) {
+                    self.reg_ready[warp].insert(issued.rd_addr, (ticket.ready_at(), kind));
+                }
+                self.trace_event(now, "exec_issue", warp, None, active_lanes, None);
+                Ok(ticket)
+            }
+            Err(bp) => {
+                let wait_until = match bp {
+                    Backpressure::Busy { available_at, .. } => normalize_retry(now, available_at),
+                    Backpressure::QueueFull { .. } => {
+                        normalize_retry(now, self.graph.execute_suggest_retry(kind))
+                    }
+                }
+                .max(now.saturating_add(1));
+                scheduler.set_resource_wait_until(warp, Some(wait_until));
+                scheduler.replay_instruction(warp);
+                self.trace_event(now, "exec_reject", warp, None, active_lanes, Some("busy"));
+                Err(wait_until)
+            }
+        }
+    }
+
+    /// Route a Neutrino instruction through the offload command queue.
+    /// Invokes occupy a concurrency slot until `completion_latency` after
+    /// dispatch; synchronous invokes stall the warp until then. `nu.complete`
+    /// waits for every outstanding invoke of the warp before it is sent.
+    pub fn issue_neutrino(
+        &mut self,
+        now: Cycle,
+        warp: usize,
+        issued: &IssuedInst,
+        active_lanes: u32,
+        scheduler: &mut Scheduler,
+    ) -> Result<Ticket, Cycle> {
+        if warp >= self.pending_neutrino.len() {
+            return Ok(Ticket::new(now, now, active_lanes.max(1)));
+        }
+        let extended_opcode = issued.opcode as u16 | ((issued.opext as u16) << 7);
+        let Some(kind) = NeutrinoOpKind::from_extended_opcode(extended_opcode) else {
+            return Ok(Ticket::new(now, now, active_lanes.max(1)));
+        };
+
+        if let Some(ready_at) = self.pending_neutrino[warp] {
+            if now >= ready_at {
+                self.pending_neutrino[warp] = None;
+                self.trace_event(now, "neutrino_complete", warp, None, active_lanes, None);
+                self.update_scheduler_state(warp, scheduler);
+                return Ok(Ticket::new(now, now, active_lanes.max(1)));
+            }
+            scheduler.set_resource_wait_until(warp, Some(ready_at));
+            scheduler.replay_instruction(warp);
+            return Err(ready_at);
+        }
+
+        if kind == NeutrinoOpKind::Complete {
+            let outstanding_until = self.neutrino_complete_at[warp];
+            if outstanding_until > now {
+                scheduler.set_resource_wait_until(warp, Some(outstanding_until));
+                scheduler.replay_instruction(warp);
+                return Err(outstanding_until);
+            }
+        }
+
+        match self.graph.neutrino_try_issue(now, kind, active_lanes) {
+            Ok(NeutrinoIssue {
+                ticket,
+                complete_at,
+            }) => {
+                let sync = kind == NeutrinoOpKind::Invoke && issued.raw.bit(17);
+                if kind == NeutrinoOpKind::Invoke {
+                    self.neutrino_complete_at[warp] =
+                        self.neutrino_complete_at[warp].max(complete_at);
+                }
+                let ready_at = if sync { complete_at } else { ticket.ready_at() };
+                self.trace_event(now, "neutrino_issue", warp, None, active_lanes, None);
+                info!(
+                    self.logger,
+                    "[neutrino] warp {} accepted {:?} ready@{} complete@{}",
+                    warp,
+                    kind,
+                    ready_at,
+                    complete_at
+                );
+                if ready_at > now {
+                    self.pending_neutrino[warp] = Some(ready_at);
+                    scheduler.set_resource_wait_until(warp, Some(ready_at));
+                    scheduler.replay_instruction(warp);
+                    return Err(ready_at);
+                }
+                Ok(ticket)
+            }
+            Err(reject) => {
+                let wait_until = reject.retry_at.max(now.saturating_add(1));
+                scheduler.set_resource_wait_until(warp, Some(wait_until));
+                scheduler.replay_instruction(warp);
+                let reason_str = match reject.reason {
+                    NeutrinoRejectReason::Busy => "busy",
+                    NeutrinoRejectReason::QueueFull | NeutrinoRejectReason::FlushLimit => {
+                        "queue_full"
+                    }
+                };
+                self.trace_event(
+                    now,
+                    "neutrino_reject",
+                    warp,
+                    None,
+                    active_lanes,
+                    Some(reason_str),
+                );
+                Err(wait_until)
+            }
+        }
+    }
+
+    pub fn notify_csr_write(&mut self, now: Cycle, warp: usize, csr_addr: u32) {
         if self.graph.dma_matches_csr(csr_addr) {
-            self.enqueue_dma(now, 4);
+            self.enqueue_dma(now, 0, 4);
         }
         if self.graph.tensor_matches_csr(csr_addr) {
-            self.enqueue_tensor(now, 4);
+            self.enqueue_tensor(now, 0, 4);
+        }
+        if self.graph.copy_engine_matches_hint_csr(csr_addr) {
+            self.graph.copy_engine_hint(warp);
+        }
+    }
+
+    /// Value of the counter CSR `csr_addr` as `warp` reads it at `now`, or
+    /// `None` if the address is not a counter.
+    pub fn read_perf_counter(&mut self, now: Cycle, warp: usize, csr_addr: u32) -> Option<u32> {
+        if !PerfCounters::is_counter_csr(csr_addr) {
+            return None;
+        }
+        let hits = &self.gmem_hits;
+        let live = PerfCounters {
+            cycle: now,
+            instret: self.perf_counters.retired(),
+            l0_hits: hits.l0_hits,
+            l0_misses: hits.l0_accesses.saturating_sub(hits.l0_hits),
+            l1_hits: hits.l1_hits,
+            l1_misses: hits.l1_accesses.saturating_sub(hits.l1_hits),
+            l2_hits: hits.l2_hits,
+            l2_misses: hits.l2_accesses.saturating_sub(hits.l2_hits),
+            stall_cycles: self
+                .cpi_stack
+                .cycles
+                .saturating_sub(self.cpi_stack.base)
+                .saturating_sub(self.cpi_stack.idle),
+            warp: self.perf_counters.warp(warp),
+        };
+        self.perf_counters.read(warp, live).csr(csr_addr)
+    }
+
+    /// Counts an instruction of `warp` that issued to execute toward
+    /// `instret` and the warp's count for its class.
+    pub fn notify_retire(
+        &mut self,
+        now: Cycle,
+        warp: usize,
+        issued: &IssuedInst,
+        counter_read: bool,
+    ) {
+        let class = retire_class(issued.opcode, issued.f3, issued.f7);
+        self.perf_counters.retire(warp, class, counter_read);
+        if let Some(pipeview) = self.pipeview.as_mut() {
+            let (pending_gmem, pending_smem) = (&self.pending_gmem[warp], &self.pending_smem[warp]);
+            let pending = |request| match request {
+                MemRequest::Gmem(id) => pending_gmem.iter().any(|&(pending, _)| pending == id),
+                MemRequest::Smem(id) => pending_smem.iter().any(|&(pending, _)| pending == id),
+            };
+            let is_store = issued.opcode == Opcode::STORE || issued.opcode == Opcode::STORE_FP;
+            pipeview.retire(now, warp, issued.raw, is_store, pending);
         }
     }
 
-    pub fn select_issue_mask(&mut self, now: Cycle, eligible: &[bool]) -> Vec<bool> {
-        self.issue_scheduler.select(now, eligible)
+    pub fn select_issue_mask(
+        &mut self,
+        now: Cycle,
+        eligible: &[bool],
+        scheduler: &Scheduler,
+    ) -> Vec<bool> {
+        let eligible = self.contexts.admit(now, eligible);
+        let mut grants = self.issue_scheduler.select(now, &eligible);
+        self.apply_issue_policy(now, &eligible, &mut grants, scheduler);
+        self.power.throttle(now, &mut grants);
+        self.record_sched_decision(now, &eligible, &grants, scheduler);
+        if let Some(pipeview) = self.pipeview.as_mut() {
+            for (warp, _) in grants.iter().enumerate().filter(|(_, granted)| **granted) {
+                pipeview.dispatch(now, warp);
+            }
+        }
+        grants
     }
 
     pub fn record_issue_stats(
@@ -345,10 +876,21 @@ impl CoreTimingModel {
         active_warps: u32,
         eligible_warps: u32,
         issued_warps: u32,
+        scheduler: &Scheduler,
     ) {
         if self.last_issue_stats_cycle == Some(now) {
             return;
         }
+        self.record_cpi_cycle(now, active_warps, eligible_warps, issued_warps, scheduler);
+        // Sync stalls, as the CPI stack counts them, are not active cycles.
+        for warp in 0..self.perf_counters.warp_stats().len() {
+            let state = scheduler.warp_state(warp);
+            let neutrino_wait = self.pending_neutrino.get(warp).copied().flatten().is_some();
+            if state.active && !state.barrier_wait && !neutrino_wait {
+                self.perf_counters.count_active_cycle(warp);
+            }
+        }
+        self.power.end_cycle(issued_warps);
         self.last_issue_stats_cycle = Some(now);
         self.scheduler_stats.cycles = self.scheduler_stats.cycles.saturating_add(1);
         self.scheduler_stats.active_warps_sum = self
@@ -368,6 +910,62 @@ impl CoreTimingModel {
         }
     }
 
+    fn record_cpi_cycle(
+        &mut self,
+        now: Cycle,
+        active_warps: u32,
+        eligible_warps: u32,
+        issued_warps: u32,
+        scheduler: &Scheduler,
+    ) {
+        if issued_warps > 0 {
+            self.cpi_stack.record_issue(issued_warps as u64);
+            return;
+        }
+        if active_warps == 0 {
+            self.cpi_stack.record_idle();
+            return;
+        }
+        let stall = (0..self.pending_gmem.len())
+            .filter(|&warp| scheduler.warp_state(warp).active)
+            .filter_map(|warp| self.warp_cpi_stall(now, warp, scheduler))
+            .min();
+        match stall {
+            Some(stall) => self.cpi_stack.record_stall(stall),
+            None if self.contexts.denied_this_cycle() => self.cpi_stack.record_context_wait(),
+            None if self.power.denied_this_cycle() => self.cpi_stack.record_power_throttled(),
+            None if eligible_warps > 0 => self.cpi_stack.record_issue_limited(),
+            None => self.cpi_stack.record_other(),
+        }
+    }
+
+    pub(super) fn warp_cpi_stall(
+        &self,
+        now: Cycle,
+        warp: usize,
+        scheduler: &Scheduler,
+    ) -> Option<CpiStall> {
+        if !self.pending_gmem[warp].is_empty() {
+            Some(CpiStall::Gmem)
+        } else if !self.pending_smem[warp].is_empty() {
+            Some(CpiStall::Smem)
+        } else if self.fence_inflight[warp].is_some()
+            || self.pending_fence.iter().any(|fence| fence.warp == warp)
+        {
+            Some(CpiStall::Fence)
+        } else if self.pending_execute[warp].is_some_and(|ready_at| ready_at > now) {
+            Some(CpiStall::Execute)
+        } else if self.pending_neutrino[warp].is_some() || scheduler.warp_state(warp).barrier_wait {
+            Some(CpiStall::Sync)
+        } else if self.icache_inflight[warp].is_some() || self.ibuffers.starved(warp) {
+            Some(CpiStall::Icache)
+        } else if self.pending_copy[warp].is_some() {
+            Some(CpiStall::Copy)
+        } else {
+            None
+        }
+    }
+
     pub fn allow_fetch(
         &mut self,
         now: Cycle,
@@ -375,20 +973,36 @@ impl CoreTimingModel {
         pc: u32,
         scheduler: &mut Scheduler,
     ) -> bool {
+        let allowed = self.try_fetch(now, warp, pc, scheduler);
+        if let Some(pipeview) = self.pipeview.as_mut() {
+            pipeview.fetch(now, warp, pc, allowed);
+        }
+        allowed
+    }
+
+    fn try_fetch(&mut self, now: Cycle, warp: usize, pc: u32, scheduler: &mut Scheduler) -> bool {
         if warp >= self.icache_inflight.len() {
             return true;
         }
 
-        if let Some(entry) = self.icache_inflight[warp].as_ref() {
-            if now >= entry.ready_at {
-                self.icache_inflight[warp] = None;
-                return true;
-            }
-            scheduler.set_resource_wait_until(warp, Some(entry.ready_at));
+        if !self.ibuffers.try_issue(warp, pc) {
+            scheduler.set_resource_wait_until(warp, Some(now.saturating_add(1)));
             scheduler.replay_instruction(warp);
             return false;
         }
 
+        if let Some(entry) = self.icache_inflight[warp] {
+            if now < entry.ready_at {
+                scheduler.set_resource_wait_until(warp, Some(entry.ready_at));
+                scheduler.replay_instruction(warp);
+                return false;
+            }
+            self.icache_inflight[warp] = None;
+            if !entry.replay {
+                return true;
+            }
+        }
+
         let mut request = IcacheRequest::new(warp, pc, 8);
         request.core_id = self.core_id;
         if request.id == 0 {
@@ -403,12 +1017,15 @@ impl CoreTimingModel {
         }
 
         match self.graph.issue_icache(now, request) {
-            Ok(IcacheIssue { ticket }) => {
-                let ready_at = ticket.ready_at();
+            Ok(IcacheIssue { ticket, replay_at }) => {
+                let ready_at = replay_at.unwrap_or(ticket.ready_at());
                 if ready_at <= now {
                     true
                 } else {
-                    self.icache_inflight[warp] = Some(IcacheInflight { ready_at });
+                    self.icache_inflight[warp] = Some(IcacheInflight {
+                        ready_at,
+                        replay: replay_at.is_some(),
+                    });
                     scheduler.set_resource_wait_until(warp, Some(ready_at));
                     scheduler.replay_instruction(warp);
                     false
@@ -447,34 +1064,36 @@ impl CoreTimingModel {
     }
 }
 
-fn exec_unit_for(inst: &IssuedInst) -> Option<ExecUnitKind> {
-    if inst.opcode == Opcode::LOAD
-        || inst.opcode == Opcode::STORE
-        || inst.opcode == Opcode::MISC_MEM
-        || inst.opcode == Opcode::LOAD_FP
-        || inst.opcode == Opcode::STORE_FP
+fn exec_unit_for(opcode: u8, f3: u8, f7: u8) -> Option<ExecUnitKind> {
+    if opcode == Opcode::LOAD
+        || opcode == Opcode::STORE
+        || opcode == Opcode::MISC_MEM
+        || opcode == Opcode::LOAD_FP
+        || opcode == Opcode::STORE_FP
     {
         return None;
     }
 
-    match inst.opcode {
+    match opcode {
         Opcode::SYSTEM => {
-            if inst.f3 == 0 {
+            if f3 == 0 {
                 Some(ExecUnitKind::Sfu)
             } else {
                 Some(ExecUnitKind::Int)
             }
         }
+        Opcode::CUSTOM0 if f7 == Opcode::WARP_OP_F7 => Some(ExecUnitKind::Shuffle),
         Opcode::CUSTOM0 => Some(ExecUnitKind::Sfu),
         Opcode::CUSTOM2 => Some(ExecUnitKind::Sfu),
+        Opcode::OP_FP if div_sqrt_op(opcode, f3, f7).is_some() => Some(ExecUnitKind::DivSqrt),
         Opcode::OP_FP | Opcode::MADD | Opcode::MSUB | Opcode::NM_ADD | Opcode::NM_SUB => {
             Some(ExecUnitKind::Fp)
         }
         Opcode::OP => {
-            if inst.f7 == 0b0000001 {
-                match inst.f3 {
+            if f7 == 0b0000001 {
+                match f3 {
                     0b000 | 0b001 | 0b010 | 0b011 => Some(ExecUnitKind::IntMul),
-                    0b100 | 0b101 | 0b110 | 0b111 => Some(ExecUnitKind::IntDiv),
+                    0b100 | 0b101 | 0b110 | 0b111 => Some(ExecUnitKind::DivSqrt),
                     _ => Some(ExecUnitKind::Int),
                 }
             } else {
@@ -484,3 +1103,71 @@ fn exec_unit_for(inst: &IssuedInst) -> Option<ExecUnitKind> {
         _ => Some(ExecUnitKind::Int),
     }
 }
+
+/// The op an instruction runs on the div/sqrt unit, if it is one.
+fn div_sqrt_op(opcode: u8, f3: u8, f7: u8) -> Option<DivSqrtOp> {
+    match opcode {
+        Opcode::OP if f7 == 0b0000001 && f3 & 0b100 != 0 => Some(DivSqrtOp::IntDiv),
+        Opcode::OP_FP => match f7 & !0b11 {
+            0b0001100 => Some(DivSqrtOp::FpDiv),
+            0b0101100 => Some(DivSqrtOp::FpSqrt),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn retire_class(opcode: u8, f3: u8, f7: u8) -> RetireClass {
+    match opcode {
+        Opcode::LOAD | Opcode::LOAD_FP => RetireClass::Load,
+        Opcode::STORE | Opcode::STORE_FP | Opcode::MISC_MEM => RetireClass::Store,
+        Opcode::BRANCH | Opcode::JAL | Opcode::JALR => RetireClass::Branch,
+        _ => match exec_unit_for(opcode, f3, f7) {
+            Some(ExecUnitKind::Fp) => RetireClass::Fpu,
+            Some(ExecUnitKind::DivSqrt) if opcode == Opcode::OP_FP => RetireClass::Fpu,
+            Some(ExecUnitKind::Sfu | ExecUnitKind::Shuffle) => RetireClass::Sfu,
+            _ => RetireClass::Alu,
+        },
+    }
+}
+
+/// Registers an instruction reads.
+fn source_regs(inst: &DecodedInst) -> Vec<u8> {
+    match inst.opcode {
+        Opcode::LUI | Opcode::AUIPC | Opcode::JAL => vec![],
+        Opcode::OP_IMM | Opcode::JALR | Opcode::LOAD | Opcode::LOAD_FP => vec![inst.rs1_addr],
+        Opcode::SYSTEM => match inst.f3 {
+            1..=3 => vec![inst.rs1_addr],
+            _ => vec![],
+        },
+        Opcode::OP | Opcode::BRANCH | Opcode::STORE | Opcode::STORE_FP | Opcode::OP_FP => {
+            vec![inst.rs1_addr, inst.rs2_addr]
+        }
+        Opcode::MADD | Opcode::MSUB | Opcode::NM_ADD | Opcode::NM_SUB => {
+            vec![inst.rs1_addr, inst.rs2_addr, inst.rs3_addr]
+        }
+        _ => vec![inst.rs1_addr, inst.rs2_addr, inst.rs3_addr, inst.rs4_addr],
+    }
+}
+
+fn writes_rd(opcode: u8) -> bool {
+    !matches!(
+        opcode,
+        Opcode::BRANCH | Opcode::STORE | Opcode::STORE_FP | Opcode::MISC_MEM
+    )
+}
+
+/// Plain arithmetic may wait in a reservation station. Control flow, CSR
+/// and custom ops stall in the ibuffer so nothing after them runs ahead.
+fn rs_eligible(opcode: u8) -> bool {
+    matches!(
+        opcode,
+        Opcode::OP
+            | Opcode::OP_IMM
+            | Opcode::OP_FP
+            | Opcode::MADD
+            | Opcode::MSUB
+            | Opcode::NM_ADD
+            | Opcode::NM_SUB
+    )
+}
@@ -2,9 +2,16 @@ use serde::Serialize;
 use std::ops::AddAssign;
 
 use crate::timeflow::{
-    BarrierSummary, GmemStats, IcacheStats, LsuStats, SmemStats, WritebackStats,
+    AdaptiveRouteStats, BarrierSummary, BottleneckReport, CacheBankActivity, CacheHintStats,
+    ContextSwitchStats, CopyEngineStats, CrossbarPortStats, DependencyStats, DieTrafficStats,
+    DivSqrtStats, DramChannelStats, DramShareStats, FenceStats, FillForwardStats, ForwardingStats,
+    GmemStats, IbufferStats, IcacheStats, InjectorStats, LsuStats, MemoryRegionStats,
+    NeutrinoStats, PowerSummary, RefillContention, RequestTimeoutStats, ReservationStationStats,
+    SmemStats, StoreBufferStats, WarpRetireStats, WriteCombiningStats, WritebackStats,
 };
 
+use super::AccessPatternSummary;
+
 #[derive(Debug, Clone, Default)]
 pub struct CoreStats {
     pub gmem: GmemStats,
@@ -40,6 +47,45 @@ pub struct ExecuteUtilSummary {
     pub int_div_busy_sum: u64,
     pub fp_busy_sum: u64,
     pub sfu_busy_sum: u64,
+    pub shuffle_busy_sum: u64,
+    pub div_sqrt_busy_sum: u64,
+}
+
+impl ExecuteUtilSummary {
+    /// Busy cycles of each execution unit, named as in `compute.execute`.
+    pub fn units(&self) -> [(&'static str, u64); 7] {
+        [
+            ("alu", self.int_busy_sum),
+            ("int_mul", self.int_mul_busy_sum),
+            ("int_div", self.int_div_busy_sum),
+            ("fp", self.fp_busy_sum),
+            ("sfu", self.sfu_busy_sum),
+            ("shuffle", self.shuffle_busy_sum),
+            ("div_sqrt", self.div_sqrt_busy_sum),
+        ]
+    }
+
+    /// Cycles a unit with `busy` busy cycles sat idle, i.e. could have been
+    /// clock-gated.
+    pub fn idle_cycles(&self, busy: u64) -> u64 {
+        self.cycles.saturating_sub(busy)
+    }
+
+    /// One-line rendering, e.g. `duty alu 42.0% fp 3.1% (of 1200 cycles)`.
+    /// Units that were never busy are left out.
+    pub fn format_line(&self) -> String {
+        let cycles = self.cycles.max(1) as f64;
+        let terms: Vec<String> = self
+            .units()
+            .iter()
+            .filter(|(_, busy)| *busy > 0)
+            .map(|(name, busy)| format!("{name} {:.1}%", 100.0 * *busy as f64 / cycles))
+            .collect();
+        if terms.is_empty() {
+            return format!("duty idle (of {} cycles)", self.cycles);
+        }
+        format!("duty {} (of {} cycles)", terms.join(" "), self.cycles)
+    }
 }
 
 #[derive(Debug, Clone, Copy, Default, Serialize)]
@@ -48,12 +94,235 @@ pub struct BasicUtilSummary {
     pub busy_sum: u64,
 }
 
+/// Memory-level parallelism counters. `outstanding_sum / busy_cycles` is the
+/// average number of outstanding gmem misses over cycles with at least one.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct WarpMlpSummary {
+    pub misses: u64,
+    pub busy_cycles: u64,
+    pub outstanding_sum: u64,
+    pub max_outstanding: u64,
+}
+
+impl WarpMlpSummary {
+    pub fn sample(&mut self, outstanding: u64) {
+        if outstanding == 0 {
+            return;
+        }
+        self.busy_cycles = self.busy_cycles.saturating_add(1);
+        self.outstanding_sum = self.outstanding_sum.saturating_add(outstanding);
+        self.max_outstanding = self.max_outstanding.max(outstanding);
+    }
+
+    pub fn avg_outstanding(&self) -> f64 {
+        if self.busy_cycles == 0 {
+            0.0
+        } else {
+            self.outstanding_sum as f64 / self.busy_cycles as f64
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MlpSummary {
+    /// Whole-core counters, sampled on the sum over warps.
+    #[serde(flatten)]
+    pub core: WarpMlpSummary,
+    pub per_warp: Vec<WarpMlpSummary>,
+}
+
 #[derive(Debug, Clone, Copy, Default, Serialize)]
 pub struct StallSummary {
     pub gmem_queue_full: u64,
     pub gmem_busy: u64,
     pub smem_queue_full: u64,
     pub smem_busy: u64,
+    /// Memory issues held back by `lsu.strict_inorder` because the warp
+    /// still had an access outstanding.
+    pub strict_inorder: u64,
+}
+
+/// Warps parked until a memory completion instead of retrying their
+/// instruction: loads behind an outstanding load of the same warp, and
+/// `lsu.strict_inorder` holds.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct WakeupSummary {
+    pub parked: u64,
+    /// Wakeups delivered by a completion.
+    pub woken: u64,
+    /// Cycles from parking to the completion that woke the warp.
+    pub parked_cycles: u64,
+}
+
+impl AddAssign<&WakeupSummary> for WakeupSummary {
+    fn add_assign(&mut self, other: &WakeupSummary) {
+        self.parked = self.parked.saturating_add(other.parked);
+        self.woken = self.woken.saturating_add(other.woken);
+        self.parked_cycles = self.parked_cycles.saturating_add(other.parked_cycles);
+    }
+}
+
+/// Gmem requests that sat in the core's pending issue queue past
+/// `retry_age_threshold` and were let through ahead of younger traffic.
+/// A nonzero count means something downstream was starving them.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct RetryAgingSummary {
+    pub aged_issues: u64,
+    pub aged_flushes: u64,
+    pub max_wait: u64,
+}
+
+/// Gmem requests held in the core's pending issue queue behind an older
+/// flush under `flush_ordering`.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct FlushOrderingSummary {
+    /// Flushes accepted while `flush_ordering` was on.
+    pub flushes: u64,
+    /// Request-cycles spent waiting for an older flush to complete.
+    pub held_cycles: u64,
+}
+
+/// Warps that exited (KILL, ECALL or an all-zero thread mask) with requests
+/// still in flight, and how long those took to drain.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct WarpExitSummary {
+    pub exited_warps: u64,
+    /// Exits that left at least one gmem or smem request outstanding.
+    pub draining_exits: u64,
+    /// Requests still in flight at the moment of exit.
+    pub residual_requests: u64,
+    pub drain_cycles_sum: u64,
+    pub max_drain_cycles: u64,
+    /// Completions for exited warps that skipped the writeback queue.
+    pub suppressed_writebacks: u64,
+}
+
+/// Every scheduler cycle charged to exactly one bucket, so the buckets sum
+/// to `cycles`. A cycle that issues anything is `base`; otherwise it goes to
+/// the first reason, in field order, that holds back any active warp.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct CpiStack {
+    pub cycles: u64,
+    pub instructions: u64,
+    pub base: u64,
+    pub gmem: u64,
+    pub smem: u64,
+    pub fence: u64,
+    pub execute: u64,
+    pub sync: u64,
+    pub icache: u64,
+    pub copy: u64,
+    /// Every eligible warp was waiting for a warp slot or its context.
+    pub context: u64,
+    /// The power governor held back every eligible warp.
+    pub power: u64,
+    /// Warps were eligible but the issue scheduler picked none.
+    pub issue: u64,
+    /// No active warps.
+    pub idle: u64,
+    pub other: u64,
+}
+
+/// Why a warp could not issue this cycle, in `CpiStack` charging order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CpiStall {
+    Gmem,
+    Smem,
+    Fence,
+    Execute,
+    Sync,
+    Icache,
+    Copy,
+}
+
+impl CpiStack {
+    pub fn record_issue(&mut self, instructions: u64) {
+        self.cycles = self.cycles.saturating_add(1);
+        self.instructions = self.instructions.saturating_add(instructions);
+        self.base = self.base.saturating_add(1);
+    }
+
+    pub fn record_stall(&mut self, stall: CpiStall) {
+        self.cycles = self.cycles.saturating_add(1);
+        let bucket = match stall {
+            CpiStall::Gmem => &mut self.gmem,
+            CpiStall::Smem => &mut self.smem,
+            CpiStall::Fence => &mut self.fence,
+            CpiStall::Execute => &mut self.execute,
+            CpiStall::Sync => &mut self.sync,
+            CpiStall::Icache => &mut self.icache,
+            CpiStall::Copy => &mut self.copy,
+        };
+        *bucket = bucket.saturating_add(1);
+    }
+
+    pub fn record_context_wait(&mut self) {
+        self.cycles = self.cycles.saturating_add(1);
+        self.context = self.context.saturating_add(1);
+    }
+
+    pub fn record_power_throttled(&mut self) {
+        self.cycles = self.cycles.saturating_add(1);
+        self.power = self.power.saturating_add(1);
+    }
+
+    pub fn record_issue_limited(&mut self) {
+        self.cycles = self.cycles.saturating_add(1);
+        self.issue = self.issue.saturating_add(1);
+    }
+
+    pub fn record_idle(&mut self) {
+        self.cycles = self.cycles.saturating_add(1);
+        self.idle = self.idle.saturating_add(1);
+    }
+
+    pub fn record_other(&mut self) {
+        self.cycles = self.cycles.saturating_add(1);
+        self.other = self.other.saturating_add(1);
+    }
+
+    pub fn cpi(&self) -> f64 {
+        if self.instructions == 0 {
+            0.0
+        } else {
+            self.cycles as f64 / self.instructions as f64
+        }
+    }
+
+    /// `(name, cycles)` for each bucket, in charging order.
+    pub fn components(&self) -> [(&'static str, u64); 13] {
+        [
+            ("base", self.base),
+            ("gmem", self.gmem),
+            ("smem", self.smem),
+            ("fence", self.fence),
+            ("execute", self.execute),
+            ("sync", self.sync),
+            ("icache", self.icache),
+            ("copy", self.copy),
+            ("context", self.context),
+            ("power", self.power),
+            ("issue", self.issue),
+            ("idle", self.idle),
+            ("other", self.other),
+        ]
+    }
+
+    /// One-line rendering, e.g. `CPI 2.50 = base 1.00 + gmem 1.25 + idle 0.25`.
+    /// Zero buckets are left out.
+    pub fn format_line(&self) -> String {
+        if self.instructions == 0 {
+            return format!("CPI n/a ({} cycles, no instructions)", self.cycles);
+        }
+        let per_inst = |cycles: u64| cycles as f64 / self.instructions as f64;
+        let terms: Vec<String> = self
+            .components()
+            .iter()
+            .filter(|(_, cycles)| *cycles > 0)
+            .map(|(name, cycles)| format!("{name} {:.2}", per_inst(*cycles)))
+            .collect();
+        format!("CPI {:.2} = {}", self.cpi(), terms.join(" + "))
+    }
 }
 
 #[derive(Debug, Clone, Copy, Default, Serialize)]
@@ -65,6 +334,46 @@ pub struct SmemConflictSummary {
     pub unique_subbanks: u64,
 }
 
+/// Shared memory conflicts charged to the warp that issued them.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct SmemWarpStats {
+    pub instructions: u64,
+    pub active_lanes: u64,
+    pub conflict_lanes: u64,
+    /// Bank accesses an instruction was split into past the first.
+    pub serialized_accesses: u64,
+    /// Cycles from an instruction's first bank access completing to its
+    /// last.
+    pub serialization_cycles: u64,
+}
+
+/// Per-warp shared memory traffic. `bank_accesses[warp][bank]` counts the
+/// lanes each warp sent to each bank, and `bank_conflicts[warp][bank]` the
+/// ones that found another lane of the same instruction in that bank, so
+/// either matrix can be plotted as a warp-by-bank heat map.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SmemWarpSummary {
+    pub per_warp: Vec<SmemWarpStats>,
+    pub bank_accesses: Vec<Vec<u64>>,
+    pub bank_conflicts: Vec<Vec<u64>>,
+}
+
+impl SmemWarpSummary {
+    pub fn new(num_warps: usize, num_banks: usize) -> Self {
+        Self {
+            per_warp: vec![SmemWarpStats::default(); num_warps],
+            bank_accesses: vec![vec![0; num_banks]; num_warps],
+            bank_conflicts: vec![vec![0; num_banks]; num_warps],
+        }
+    }
+
+    /// A zeroed summary of the same shape.
+    pub fn cleared(&self) -> Self {
+        let num_banks = self.bank_accesses.first().map_or(0, Vec::len);
+        Self::new(self.per_warp.len(), num_banks)
+    }
+}
+
 #[derive(Debug, Clone, Copy, Default, Serialize)]
 pub struct GmemHitSummary {
     pub l0_accesses: u64,
@@ -73,9 +382,11 @@ pub struct GmemHitSummary {
     pub l1_hits: u64,
     pub l2_accesses: u64,
     pub l2_hits: u64,
+    /// Partial stores that read-modify-wrote a cached line.
+    pub rmw: u64,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct GmemLevelSummary {
     pub l0: GmemStats,
     pub l1: GmemStats,
@@ -90,6 +401,52 @@ pub struct LatencySummary {
     pub smem_sum: u64,
 }
 
+/// Gmem and smem traffic of one kernel on a core, so co-resident kernels
+/// can be compared level by level. DRAM accesses are the L2 misses.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct ContextMemStats {
+    pub gmem_bytes: u64,
+    pub smem_bytes: u64,
+    pub gmem_hits: GmemHitSummary,
+    pub latencies: LatencySummary,
+}
+
+impl ContextMemStats {
+    pub fn dram_accesses(&self) -> u64 {
+        self.gmem_hits
+            .l2_accesses
+            .saturating_sub(self.gmem_hits.l2_hits)
+    }
+
+    /// One-line rendering, e.g. `gmem 64 req 8192 B: L1 12/64 hits, L2
+    /// 40/52 hits, DRAM 12, avg latency 88.50; smem 16 req 2048 B, avg
+    /// latency 4.00`. Levels that saw no accesses are left out.
+    pub fn format_line(&self) -> String {
+        let hits = &self.gmem_hits;
+        let avg = |sum: u64, count: u64| sum as f64 / count.max(1) as f64;
+        let levels: Vec<String> = [
+            ("L0", hits.l0_hits, hits.l0_accesses),
+            ("L1", hits.l1_hits, hits.l1_accesses),
+            ("L2", hits.l2_hits, hits.l2_accesses),
+        ]
+        .iter()
+        .filter(|(_, _, accesses)| *accesses > 0)
+        .map(|(name, hits, accesses)| format!("{name} {hits}/{accesses} hits"))
+        .chain(std::iter::once(format!("DRAM {}", self.dram_accesses())))
+        .collect();
+        format!(
+            "gmem {} req {} B: {}, avg latency {:.2}; smem {} req {} B, avg latency {:.2}",
+            self.latencies.gmem_count,
+            self.gmem_bytes,
+            levels.join(", "),
+            avg(self.latencies.gmem_sum, self.latencies.gmem_count),
+            self.latencies.smem_count,
+            self.smem_bytes,
+            avg(self.latencies.smem_sum, self.latencies.smem_count)
+        )
+    }
+}
+
 #[derive(Debug, Clone, Copy, Default, Serialize)]
 pub struct LatencyHistogram {
     pub buckets: [u64; 6],
@@ -145,6 +502,10 @@ impl AddAssign<&ExecuteUtilSummary> for ExecuteUtilSummary {
         self.int_div_busy_sum = self.int_div_busy_sum.saturating_add(other.int_div_busy_sum);
         self.fp_busy_sum = self.fp_busy_sum.saturating_add(other.fp_busy_sum);
         self.sfu_busy_sum = self.sfu_busy_sum.saturating_add(other.sfu_busy_sum);
+        self.shuffle_busy_sum = self.shuffle_busy_sum.saturating_add(other.shuffle_busy_sum);
+        self.div_sqrt_busy_sum = self
+            .div_sqrt_busy_sum
+            .saturating_add(other.div_sqrt_busy_sum);
     }
 }
 
@@ -155,12 +516,85 @@ impl AddAssign<&BasicUtilSummary> for BasicUtilSummary {
     }
 }
 
+impl AddAssign<&WarpMlpSummary> for WarpMlpSummary {
+    fn add_assign(&mut self, other: &WarpMlpSummary) {
+        self.misses = self.misses.saturating_add(other.misses);
+        self.busy_cycles = self.busy_cycles.saturating_add(other.busy_cycles);
+        self.outstanding_sum = self.outstanding_sum.saturating_add(other.outstanding_sum);
+        self.max_outstanding = self.max_outstanding.max(other.max_outstanding);
+    }
+}
+
+impl AddAssign<&MlpSummary> for MlpSummary {
+    fn add_assign(&mut self, other: &MlpSummary) {
+        self.core += &other.core;
+        if self.per_warp.len() < other.per_warp.len() {
+            self.per_warp
+                .resize_with(other.per_warp.len(), Default::default);
+        }
+        for (total, warp) in self.per_warp.iter_mut().zip(&other.per_warp) {
+            *total += warp;
+        }
+    }
+}
+
 impl AddAssign<&StallSummary> for StallSummary {
     fn add_assign(&mut self, other: &StallSummary) {
         self.gmem_queue_full = self.gmem_queue_full.saturating_add(other.gmem_queue_full);
         self.gmem_busy = self.gmem_busy.saturating_add(other.gmem_busy);
         self.smem_queue_full = self.smem_queue_full.saturating_add(other.smem_queue_full);
         self.smem_busy = self.smem_busy.saturating_add(other.smem_busy);
+        self.strict_inorder = self.strict_inorder.saturating_add(other.strict_inorder);
+    }
+}
+
+impl AddAssign<&RetryAgingSummary> for RetryAgingSummary {
+    fn add_assign(&mut self, other: &RetryAgingSummary) {
+        self.aged_issues = self.aged_issues.saturating_add(other.aged_issues);
+        self.aged_flushes = self.aged_flushes.saturating_add(other.aged_flushes);
+        self.max_wait = self.max_wait.max(other.max_wait);
+    }
+}
+
+impl AddAssign<&FlushOrderingSummary> for FlushOrderingSummary {
+    fn add_assign(&mut self, other: &FlushOrderingSummary) {
+        self.flushes = self.flushes.saturating_add(other.flushes);
+        self.held_cycles = self.held_cycles.saturating_add(other.held_cycles);
+    }
+}
+
+impl AddAssign<&WarpExitSummary> for WarpExitSummary {
+    fn add_assign(&mut self, other: &WarpExitSummary) {
+        self.exited_warps = self.exited_warps.saturating_add(other.exited_warps);
+        self.draining_exits = self.draining_exits.saturating_add(other.draining_exits);
+        self.residual_requests = self
+            .residual_requests
+            .saturating_add(other.residual_requests);
+        self.drain_cycles_sum = self.drain_cycles_sum.saturating_add(other.drain_cycles_sum);
+        self.max_drain_cycles = self.max_drain_cycles.max(other.max_drain_cycles);
+        self.suppressed_writebacks = self
+            .suppressed_writebacks
+            .saturating_add(other.suppressed_writebacks);
+    }
+}
+
+impl AddAssign<&CpiStack> for CpiStack {
+    fn add_assign(&mut self, other: &CpiStack) {
+        self.cycles = self.cycles.saturating_add(other.cycles);
+        self.instructions = self.instructions.saturating_add(other.instructions);
+        self.base = self.base.saturating_add(other.base);
+        self.gmem = self.gmem.saturating_add(other.gmem);
+        self.smem = self.smem.saturating_add(other.smem);
+        self.fence = self.fence.saturating_add(other.fence);
+        self.execute = self.execute.saturating_add(other.execute);
+        self.sync = self.sync.saturating_add(other.sync);
+        self.icache = self.icache.saturating_add(other.icache);
+        self.copy = self.copy.saturating_add(other.copy);
+        self.context = self.context.saturating_add(other.context);
+        self.power = self.power.saturating_add(other.power);
+        self.issue = self.issue.saturating_add(other.issue);
+        self.idle = self.idle.saturating_add(other.idle);
+        self.other = self.other.saturating_add(other.other);
     }
 }
 
@@ -174,6 +608,48 @@ impl AddAssign<&SmemConflictSummary> for SmemConflictSummary {
     }
 }
 
+impl AddAssign<&SmemWarpStats> for SmemWarpStats {
+    fn add_assign(&mut self, other: &SmemWarpStats) {
+        self.instructions = self.instructions.saturating_add(other.instructions);
+        self.active_lanes = self.active_lanes.saturating_add(other.active_lanes);
+        self.conflict_lanes = self.conflict_lanes.saturating_add(other.conflict_lanes);
+        self.serialized_accesses = self
+            .serialized_accesses
+            .saturating_add(other.serialized_accesses);
+        self.serialization_cycles = self
+            .serialization_cycles
+            .saturating_add(other.serialization_cycles);
+    }
+}
+
+fn add_matrix(total: &mut Vec<Vec<u64>>, other: &[Vec<u64>]) {
+    if total.len() < other.len() {
+        total.resize_with(other.len(), Vec::new);
+    }
+    for (total_row, row) in total.iter_mut().zip(other) {
+        if total_row.len() < row.len() {
+            total_row.resize(row.len(), 0);
+        }
+        for (total, count) in total_row.iter_mut().zip(row) {
+            *total = total.saturating_add(*count);
+        }
+    }
+}
+
+impl AddAssign<&SmemWarpSummary> for SmemWarpSummary {
+    fn add_assign(&mut self, other: &SmemWarpSummary) {
+        if self.per_warp.len() < other.per_warp.len() {
+            self.per_warp
+                .resize_with(other.per_warp.len(), Default::default);
+        }
+        for (total, warp) in self.per_warp.iter_mut().zip(&other.per_warp) {
+            *total += warp;
+        }
+        add_matrix(&mut self.bank_accesses, &other.bank_accesses);
+        add_matrix(&mut self.bank_conflicts, &other.bank_conflicts);
+    }
+}
+
 impl AddAssign<&GmemHitSummary> for GmemHitSummary {
     fn add_assign(&mut self, other: &GmemHitSummary) {
         self.l0_accesses = self.l0_accesses.saturating_add(other.l0_accesses);
@@ -182,6 +658,7 @@ impl AddAssign<&GmemHitSummary> for GmemHitSummary {
         self.l1_hits = self.l1_hits.saturating_add(other.l1_hits);
         self.l2_accesses = self.l2_accesses.saturating_add(other.l2_accesses);
         self.l2_hits = self.l2_hits.saturating_add(other.l2_hits);
+        self.rmw = self.rmw.saturating_add(other.rmw);
     }
 }
 
@@ -194,16 +671,39 @@ impl AddAssign<&LatencySummary> for LatencySummary {
     }
 }
 
+impl AddAssign<&ContextMemStats> for ContextMemStats {
+    fn add_assign(&mut self, other: &ContextMemStats) {
+        self.gmem_bytes = self.gmem_bytes.saturating_add(other.gmem_bytes);
+        self.smem_bytes = self.smem_bytes.saturating_add(other.smem_bytes);
+        self.gmem_hits += &other.gmem_hits;
+        self.latencies += &other.latencies;
+    }
+}
+
+/// Adds `other` into `total` context by context.
+pub fn merge_context_mem(total: &mut Vec<ContextMemStats>, other: &[ContextMemStats]) {
+    if total.len() < other.len() {
+        total.resize_with(other.len(), Default::default);
+    }
+    for (total, context) in total.iter_mut().zip(other) {
+        *total += context;
+    }
+}
+
 impl AddAssign<&LatencyHistogram> for LatencyHistogram {
     fn add_assign(&mut self, other: &LatencyHistogram) {
         self.accumulate(other);
     }
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Default, Serialize)]
+#[non_exhaustive]
 pub struct CorePerfSummary {
     pub core_id: usize,
     pub cluster_id: usize,
+    /// The core ran without a timing graph (see `sim.timed_cores`); only
+    /// `cpi_stack` is filled in.
+    pub functional: bool,
     pub scheduler: SchedulerSummary,
     pub smem_util: SmemUtilSummary,
     pub execute_util: ExecuteUtilSummary,
@@ -213,11 +713,63 @@ pub struct CorePerfSummary {
     pub tensor_bytes_issued: u64,
     pub tensor_bytes_completed: u64,
     pub tensor_util: BasicUtilSummary,
+    pub neutrino_stats: NeutrinoStats,
+    pub neutrino_util: BasicUtilSummary,
+    pub copy_engine_stats: CopyEngineStats,
+    pub copy_engine_util: BasicUtilSummary,
     pub smem_conflicts: SmemConflictSummary,
+    /// `smem_conflicts` split by warp, with warp-by-bank access counts.
+    pub smem_warps: SmemWarpSummary,
+    /// Coalesced, strided and scattered gmem loads and stores per PC.
+    pub access_patterns: AccessPatternSummary,
     pub gmem_hits: GmemHitSummary,
     pub latencies: LatencySummary,
+    /// Completed gmem and smem traffic by the kernel context the issuing
+    /// warp belonged to, indexed by context.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub context_mem: Vec<ContextMemStats>,
+    pub mlp: MlpSummary,
     pub gmem_stats: GmemStats,
     pub gmem_level_stats: GmemLevelSummary,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub memory_regions: Vec<MemoryRegionStats>,
+    pub crossbar: CrossbarPortStats,
+    /// DRAM bytes split between demand misses and DMA/tensor transfers;
+    /// shared by every core, like `gmem_level_stats`.
+    pub dram_share: DramShareStats,
+    /// Per-channel DRAM traffic and utilization; shared by every core.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub dram_channels: Vec<DramChannelStats>,
+    /// Traffic to the local DRAM and each remote die; shared by every core.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub dies: Vec<DieTrafficStats>,
+    /// Contention in the core's own graph and LSU.
+    #[serde(skip_serializing_if = "BottleneckReport::is_empty")]
+    pub bottlenecks: BottleneckReport,
+    /// Contention in the shared gmem graph; shared by every core.
+    #[serde(skip_serializing_if = "BottleneckReport::is_empty")]
+    pub gmem_bottlenecks: BottleneckReport,
+    /// Active cycles of every cache bank; shared by every core.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub cache_banks: Vec<CacheBankActivity>,
+    /// Release delays of requests merged into MSHR entries; shared by
+    /// every core.
+    pub fill_forward: FillForwardStats,
+    /// Loads by cache hint and the level that served them; shared by every
+    /// core.
+    pub cache_hints: CacheHintStats,
+    /// Requests of the clusters `gmem.injectors` stands in for; shared by
+    /// every core.
+    pub injectors: InjectorStats,
+    /// L2 writebacks to DRAM and the write bursts they took after
+    /// combining; shared by every core.
+    pub write_combining: WriteCombiningStats,
+    /// Responses moved between duplicate return paths; shared by every
+    /// core.
+    pub adaptive_routing: AdaptiveRouteStats,
+    /// Refills that took L1 and L2 data array ports from hits; shared by
+    /// every core.
+    pub refill_contention: RefillContention,
     pub smem_stats: SmemStats,
     pub icache_stats: IcacheStats,
     pub lsu_stats: LsuStats,
@@ -226,6 +778,25 @@ pub struct CorePerfSummary {
     pub dma_completed: u64,
     pub tensor_completed: u64,
     pub stall_summary: StallSummary,
+    pub wakeups: WakeupSummary,
+    pub retry_aging: RetryAgingSummary,
+    pub flush_ordering: FlushOrderingSummary,
+    /// Requests that outlived `[lsu.timeout]`.
+    pub request_timeouts: RequestTimeoutStats,
+    /// Requests declared to wait on others; see `add_request_dependency`.
+    pub dependencies: DependencyStats,
+    pub warp_exits: WarpExitSummary,
+    pub cpi_stack: CpiStack,
+    /// Retired instructions by class and active cycles, one entry per warp.
+    pub warp_retire: Vec<WarpRetireStats>,
+    pub power: PowerSummary,
+    pub context_switch: ContextSwitchStats,
+    pub reservation_stations: ReservationStationStats,
+    pub forwarding: ForwardingStats,
+    pub ibuffer: IbufferStats,
+    pub store_buffer: StoreBufferStats,
+    pub fences: FenceStats,
+    pub div_sqrt: DivSqrtStats,
     pub gmem_latency_hist: LatencyHistogram,
     pub smem_latency_hist: LatencyHistogram,
 }
@@ -1,42 +1,109 @@
+use serde::Serialize;
 use std::collections::{HashMap, VecDeque};
 use std::sync::Arc;
 
 use crate::sim::log::Logger;
 use crate::sim::perf_log::PerfLogSession;
+use crate::timeflow::DependencyTable;
+use crate::timeflow::ExecUnitKind;
+use crate::timeflow::RequestTimeouts;
+use crate::timeflow::StatsSwitch;
 use crate::timeflow::{
-    CoreGraph, FenceRequest, GmemPolicyConfig, GmemRequest, SmemFlowConfig, SmemRequest,
-    WarpIssueScheduler, WritebackPayload,
+    CoreGraph, ExecuteWriteback, FenceRequest, FenceStats, GmemCompletion, GmemPolicyConfig,
+    GmemRequest, Ibuffers, PerfCounterFile, PowerGovernor, ReservationStations, SmemFlowConfig,
+    SmemRequest, StoreBuffer, WarpContexts, WarpIssueScheduler, WritebackPayload,
 };
 use crate::timeq::Cycle;
 
+mod access_pattern;
 mod completions;
 mod core;
+mod dependencies;
 mod issue;
 mod metrics;
 mod pending;
+mod pipeview;
+mod sched_log;
 mod split;
+mod timeouts;
+mod wakeups;
 
 #[cfg(test)]
 mod tests;
 
+pub use access_pattern::{AccessPattern, AccessPatternSummary, PcAccessPatterns};
 pub use metrics::*;
+pub use pipeview::InstLifecycle;
+pub use sched_log::{IssuePolicy, SchedDecision, SharedIssuePolicy, WarpSchedState};
 
+#[derive(Clone)]
 pub struct CoreTimingModel {
     graph: CoreGraph,
     pending_writeback: VecDeque<WritebackPayload>,
-    pending_dma: VecDeque<u32>,
-    pending_tensor: VecDeque<u32>,
+    /// DMA and tensor jobs waiting to issue, as (request id, bytes); id 0
+    /// for jobs started through MMIO or a CSR.
+    pending_dma: VecDeque<(u64, u32)>,
+    pending_tensor: VecDeque<(u64, u32)>,
     issue_scheduler: WarpIssueScheduler,
+    power: PowerGovernor,
+    contexts: WarpContexts,
+    reservation: ReservationStations,
+    /// Per warp, cycle each register written by an execute issue is ready,
+    /// and the unit producing it. Tracked for reservation-station issues,
+    /// and for every execute issue under operand forwarding; other
+    /// producers hold their warp until done.
+    reg_ready: Vec<HashMap<u8, (Cycle, ExecUnitKind)>>,
+    forwarding: crate::timeflow::Forwarding,
+    /// Dispatch cycle of an instruction that `wait_operands` sent to a
+    /// reservation station, consumed by `issue_execute`.
+    rs_dispatch: Vec<Option<Cycle>>,
     pending_fence: VecDeque<FenceRequest>,
     fence_inflight: Vec<Option<u64>>,
+    /// Fences waiting on the L1 flush they issued, by flush request id.
+    flush_fences: HashMap<u64, FenceRequest>,
+    /// Per warp, cycle a release fence started waiting for its scope to
+    /// drain.
+    fence_drain_since: Vec<Option<Cycle>>,
+    fence_stats: FenceStats,
     icache_inflight: Vec<Option<IcacheInflight>>,
+    ibuffers: Ibuffers,
+    perf_counters: PerfCounterFile,
     pending_cluster_gmem: VecDeque<PendingClusterIssue<GmemRequest>>,
+    store_buffer: StoreBuffer,
     pending_cluster_smem: VecDeque<PendingClusterIssue<SmemRequest>>,
     pending_gmem: Vec<VecDeque<(u64, Cycle)>>,
     pending_smem: Vec<VecDeque<(u64, Cycle)>>,
+    wakeups: wakeups::CompletionWakeups,
     pending_execute: Vec<Option<Cycle>>,
+    /// Under `writeback.execute_results`, execute results waiting for their
+    /// unit to produce them, as (ready cycle, result). Their warps hold
+    /// `pending_execute` at `Cycle::MAX` until the result leaves writeback.
+    execute_results: Vec<(Cycle, ExecuteWriteback)>,
+    execute_writeback: bool,
+    tensor_writeback: bool,
+    writeback_quotas: bool,
+    pending_neutrino: Vec<Option<Cycle>>,
+    neutrino_complete_at: Vec<Cycle>,
+    pending_copy: Vec<Option<Cycle>>,
     gmem_issue_cycle: HashMap<u64, Cycle>,
+    /// Request id -> outstanding child requests that missed.
+    gmem_misses: HashMap<u64, usize>,
+    /// Completed gmem requests kept for `take_gmem_completions`; `None`
+    /// unless an embedder asked for them.
+    gmem_completion_log: Option<Vec<GmemCompletion>>,
+    /// Instruction lifecycle stamps for `take_pipeview`; `None` unless
+    /// `[sim.pipeview]` records this core.
+    pipeview: Option<pipeview::PipeviewTracker>,
+    /// Decisions for `take_sched_log`; `None` unless `[sim.sched_log]`
+    /// records this core.
+    sched_log: Option<Vec<SchedDecision>>,
+    /// Overrides `issue_scheduler`'s choice when set.
+    issue_policy: Option<SharedIssuePolicy>,
     smem_issue_cycle: HashMap<u64, Cycle>,
+    /// Cycle the first bank access of each split smem request completed.
+    smem_first_completion: HashMap<u64, Cycle>,
+    timeouts: RequestTimeouts,
+    dependencies: DependencyTable,
     core_id: usize,
     cluster_id: usize,
     gmem_policy: GmemPolicyConfig,
@@ -45,9 +112,12 @@ pub struct CoreTimingModel {
     next_gmem_id: u64,
     next_smem_id: u64,
     next_icache_id: u64,
+    next_dma_id: u64,
+    next_tensor_id: u64,
     logger: Arc<Logger>,
     perf_log_session: Option<Arc<PerfLogSession>>,
     log_stats: bool,
+    stats: StatsSwitch,
     stats_log_period: Cycle,
     last_stats_log_cycle: Option<Cycle>,
     last_logged_gmem_completed: u64,
@@ -58,22 +128,63 @@ pub struct CoreTimingModel {
     smem_util: SmemUtilSummary,
     execute_util: ExecuteUtilSummary,
     smem_conflicts_summary: SmemConflictSummary,
+    smem_warps: SmemWarpSummary,
+    access_patterns: AccessPatternSummary,
     gmem_hits: GmemHitSummary,
+    retry_aging: RetryAgingSummary,
+    /// Flushes in flight as (request id, warp), oldest first, while
+    /// `flush_ordering` holds younger requests behind them.
+    inflight_flushes: Vec<(u64, usize)>,
+    flush_ordering: FlushOrderingSummary,
+    /// Cycle each exited warp left with requests still in flight; cleared
+    /// once they have all completed.
+    warp_exit: Vec<Option<Cycle>>,
+    suppress_exited_writebacks: bool,
+    warp_exits: WarpExitSummary,
+    strict_inorder: bool,
+    strict_inorder_rejects: u64,
+    cpi_stack: CpiStack,
     latencies: LatencySummary,
+    /// Kernel context of each warp, tagged onto the requests it issues.
+    warp_contexts: Vec<usize>,
+    context_mem: Vec<ContextMemStats>,
+    mlp: MlpSummary,
     dma_util: BasicUtilSummary,
     tensor_util: BasicUtilSummary,
+    neutrino_util: BasicUtilSummary,
+    copy_engine_util: BasicUtilSummary,
     gmem_latency_hist: LatencyHistogram,
     smem_latency_hist: LatencyHistogram,
 }
 
+#[derive(Clone)]
 struct PendingClusterIssue<T> {
     request: T,
     retry_at: Cycle,
+    /// Cycle the request first tried to issue, for retry aging.
+    enqueued_at: Cycle,
+}
+
+/// Outstanding timing-model work blocking one warp, as reported in state dumps.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct WarpTimingState {
+    pub gmem_requests: Vec<u64>,
+    pub smem_requests: Vec<u64>,
+    pub fence_request: Option<u64>,
+    pub queued_fences: Vec<u64>,
+    pub execute_ready_at: Option<Cycle>,
+    pub neutrino_ready_at: Option<Cycle>,
+    pub copy_ready_at: Option<Cycle>,
+    pub icache_ready_at: Option<Cycle>,
+    /// Set while the warp has exited but its requests are still draining.
+    pub exited_at: Option<Cycle>,
 }
 
 #[derive(Clone, Copy)]
 struct IcacheInflight {
     ready_at: Cycle,
+    /// The fetch was dropped by a replay and is sent again at `ready_at`.
+    replay: bool,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -2,10 +2,12 @@ use std::collections::VecDeque;
 
 use crate::muon::scheduler::Scheduler;
 use crate::timeflow::{
-    lsu::LsuPayload, FenceRequest, GmemReject, SmemIssue, SmemReject, WritebackPayload,
+    lsu::LsuPayload, FenceRequest, FlushOrdering, GmemReject, GmemRequest, RequestRef,
+    RequestSpace, SmemIssue, SmemReject, StoreBufferLookup, TensorWriteback, WritebackPayload,
 };
 use crate::timeq::Cycle;
 
+use super::wakeups::WakeOn;
 use super::{CoreTimingModel, PendingClusterIssue};
 
 impl CoreTimingModel {
@@ -17,21 +19,48 @@ impl CoreTimingModel {
             };
 
             match &payload {
+                LsuPayload::Gmem(req) if self.store_buffer.enabled() => {
+                    let split = self.split_gmem_request(req);
+                    let parts = split.len();
+                    self.note_request(now, RequestSpace::Gmem, req.id, || {
+                        format!("left LSU as {parts} line(s)")
+                    });
+                    let is_store = req.kind.is_mem() && !req.is_load;
+                    if is_store && !self.store_buffer.can_accept(&split) {
+                        // Leave the store in the STQ until an entry frees up.
+                        self.store_buffer.note_full(now);
+                        break;
+                    }
+                    for child in split {
+                        self.route_through_store_buffer(now, req, child);
+                    }
+                }
                 LsuPayload::Gmem(req) => {
                     let split = self.split_gmem_request(req);
+                    let parts = split.len();
+                    self.note_request(now, RequestSpace::Gmem, req.id, || {
+                        format!("left LSU as {parts} line(s)")
+                    });
                     for child in split {
                         self.pending_cluster_gmem.push_back(PendingClusterIssue {
                             request: child,
                             retry_at: now,
+                            enqueued_at: now,
                         });
                     }
                 }
                 LsuPayload::Smem(req) => {
                     let split = self.split_smem_request(req);
+                    self.record_smem_banks(req.warp, &split);
+                    let parts = split.len();
+                    self.note_request(now, RequestSpace::Smem, req.id, || {
+                        format!("left LSU as {parts} bank access(es)")
+                    });
                     for child in split {
                         self.pending_cluster_smem.push_back(PendingClusterIssue {
                             request: child,
                             retry_at: now,
+                            enqueued_at: now,
                         });
                     }
                 }
@@ -43,31 +72,106 @@ impl CoreTimingModel {
         }
     }
 
+    /// Buffers a store line, serves or holds a load line that overlaps a
+    /// buffered store, holds a flush behind its warp's buffered stores, and
+    /// sends anything else on to gmem. `parent` is the unsplit request.
+    fn route_through_store_buffer(&mut self, now: Cycle, parent: &GmemRequest, child: GmemRequest) {
+        if child.kind.is_mem() && !child.is_load {
+            self.store_buffer.insert(now, child);
+            return;
+        }
+        if child.kind.is_mem() {
+            let end = parent.addr.saturating_add(parent.bytes.max(1) as u64);
+            match self.store_buffer.lookup(&child, parent.addr, end) {
+                StoreBufferLookup::Forward => {
+                    let _ = self
+                        .graph
+                        .lsu_reserve_load_data(&LsuPayload::Gmem(child.clone()));
+                    self.store_buffer.forward(now, child);
+                    return;
+                }
+                StoreBufferLookup::Partial => {
+                    self.store_buffer.hold(child);
+                    return;
+                }
+                StoreBufferLookup::Miss => {}
+            }
+        } else if self.store_buffer.has_entries(child.warp) {
+            self.store_buffer.hold(child);
+            return;
+        }
+        self.pending_cluster_gmem.push_back(PendingClusterIssue {
+            request: child,
+            retry_at: now,
+            enqueued_at: now,
+        });
+    }
+
+    pub(super) fn drain_store_buffer(&mut self, now: Cycle) {
+        if !self.store_buffer.enabled() {
+            return;
+        }
+        for request in self.store_buffer.tick(now) {
+            self.pending_cluster_gmem.push_back(PendingClusterIssue {
+                request,
+                retry_at: now,
+                enqueued_at: now,
+            });
+        }
+    }
+
     pub(super) fn issue_pending_cluster_gmem(&mut self, now: Cycle) {
         if self.pending_cluster_gmem.is_empty() {
             return;
         }
 
+        // Requests retried past the age threshold go first, oldest first. If
+        // one of them is still turned away, younger requests wait a cycle so
+        // they cannot keep taking the space it needs.
+        let threshold = self.gmem_policy.retry_age_threshold;
+        let is_aged = |entry: &PendingClusterIssue<GmemRequest>| {
+            threshold > 0 && now.saturating_sub(entry.enqueued_at) >= threshold
+        };
+        if self.pending_cluster_gmem.iter().any(is_aged) {
+            let (mut aged, young): (VecDeque<_>, VecDeque<_>) =
+                self.pending_cluster_gmem.drain(..).partition(is_aged);
+            aged.make_contiguous()
+                .sort_by_key(|entry| entry.enqueued_at);
+            aged.extend(young);
+            self.pending_cluster_gmem = aged;
+        }
+
         let mut pending = VecDeque::with_capacity(self.pending_cluster_gmem.len());
         let mut remaining = self.pending_cluster_gmem.len();
+        let mut aged_blocked = false;
         while remaining > 0 {
             remaining -= 1;
             let entry = match self.pending_cluster_gmem.pop_front() {
                 Some(entry) => entry,
                 None => break,
             };
-            if entry.retry_at > now {
+            let aged = is_aged(&entry);
+            if entry.retry_at > now || (aged_blocked && !aged) {
                 pending.push_back(entry);
                 continue;
             }
+            if self.behind_inflight_flush(&entry.request) {
+                self.flush_ordering.held_cycles = self.flush_ordering.held_cycles.saturating_add(1);
+                pending.push_back(PendingClusterIssue {
+                    retry_at: now.saturating_add(1),
+                    ..entry
+                });
+                continue;
+            }
 
             if !self
                 .graph
                 .lsu_can_reserve_load_data(&LsuPayload::Gmem(entry.request.clone()))
             {
+                aged_blocked |= aged;
                 pending.push_back(PendingClusterIssue {
-                    request: entry.request,
                     retry_at: now.saturating_add(1),
+                    ..entry
                 });
                 continue;
             }
@@ -76,7 +180,19 @@ impl CoreTimingModel {
                 .graph
                 .cluster_gmem_issue(self.core_id, now, entry.request.clone());
             match issue {
-                Ok(_) => {
+                Ok(issue) => {
+                    if aged {
+                        self.record_aged_issue(now, &entry);
+                    }
+                    let (line, miss) = (entry.request.line_addr, issue.miss);
+                    let waited = now.saturating_sub(entry.enqueued_at);
+                    self.note_request(now, RequestSpace::Gmem, entry.request.id, || {
+                        let outcome = if miss { "miss" } else { "hit" };
+                        format!("entered cluster gmem for line {line:#x} ({outcome}) after {waited} cycle(s)")
+                    });
+                    if issue.miss && entry.request.kind.is_mem() {
+                        self.record_gmem_miss(entry.request.warp, issue.request_id);
+                    }
                     let _ = self
                         .graph
                         .lsu_reserve_load_data(&LsuPayload::Gmem(entry.request));
@@ -86,7 +202,12 @@ impl CoreTimingModel {
                     retry_at,
                     ..
                 }) => {
-                    pending.push_back(PendingClusterIssue { request, retry_at });
+                    aged_blocked |= aged;
+                    pending.push_back(PendingClusterIssue {
+                        request,
+                        retry_at,
+                        enqueued_at: entry.enqueued_at,
+                    });
                 }
             }
         }
@@ -94,6 +215,61 @@ impl CoreTimingModel {
         self.pending_cluster_gmem = pending;
     }
 
+    /// Whether `flush_ordering` holds `request` back until an older flush
+    /// from its warp, or from any warp on the core, completes.
+    fn behind_inflight_flush(&self, request: &GmemRequest) -> bool {
+        let same_core = match self.gmem_policy.flush_ordering {
+            FlushOrdering::Unordered => return false,
+            FlushOrdering::Warp => false,
+            FlushOrdering::Core => true,
+        };
+        request.kind.is_mem()
+            && self
+                .inflight_flushes
+                .iter()
+                .any(|&(id, warp)| id < request.id && (same_core || warp == request.warp))
+    }
+
+    fn record_aged_issue(&mut self, now: Cycle, entry: &PendingClusterIssue<GmemRequest>) {
+        let wait = now.saturating_sub(entry.enqueued_at);
+        let stats = &mut self.retry_aging;
+        if stats.aged_issues == 0 {
+            log::warn!(
+                "core {}: gmem request {} ({:?}) waited {} cycles to issue; \
+                 retry aging kicked in",
+                self.core_id,
+                entry.request.id,
+                entry.request.kind,
+                wait
+            );
+        }
+        stats.aged_issues = stats.aged_issues.saturating_add(1);
+        if !entry.request.kind.is_mem() {
+            stats.aged_flushes = stats.aged_flushes.saturating_add(1);
+        }
+        stats.max_wait = stats.max_wait.max(wait);
+    }
+
+    fn record_gmem_miss(&mut self, warp: usize, request_id: u64) {
+        self.graph.lsu_note_miss(warp);
+        *self.gmem_misses.entry(request_id).or_insert(0) += 1;
+        if let Some(summary) = self.mlp.per_warp.get_mut(warp) {
+            summary.misses = summary.misses.saturating_add(1);
+        }
+        self.mlp.core.misses = self.mlp.core.misses.saturating_add(1);
+    }
+
+    pub(super) fn release_gmem_miss(&mut self, warp: usize, request_id: u64) {
+        let Some(count) = self.gmem_misses.get_mut(&request_id) else {
+            return;
+        };
+        *count -= 1;
+        if *count == 0 {
+            self.gmem_misses.remove(&request_id);
+        }
+        self.graph.lsu_release_miss(warp);
+    }
+
     pub(super) fn issue_pending_cluster_smem(&mut self, now: Cycle) {
         if self.pending_cluster_smem.is_empty() {
             return;
@@ -117,14 +293,19 @@ impl CoreTimingModel {
                 .lsu_can_reserve_load_data(&LsuPayload::Smem(entry.request.clone()))
             {
                 pending.push_back(PendingClusterIssue {
-                    request: entry.request,
                     retry_at: now.saturating_add(1),
+                    ..entry
                 });
                 continue;
             }
 
             match self.graph.issue_smem(now, entry.request.clone()) {
                 Ok(SmemIssue { .. }) => {
+                    let (bank, subbank) = (entry.request.bank, entry.request.subbank);
+                    let waited = now.saturating_sub(entry.enqueued_at);
+                    self.note_request(now, RequestSpace::Smem, entry.request.id, || {
+                        format!("entered bank {bank} subbank {subbank} after {waited} cycle(s)")
+                    });
                     let _ = self
                         .graph
                         .lsu_reserve_load_data(&LsuPayload::Smem(entry.request));
@@ -134,7 +315,11 @@ impl CoreTimingModel {
                     retry_at,
                     ..
                 }) => {
-                    pending.push_back(PendingClusterIssue { request, retry_at });
+                    pending.push_back(PendingClusterIssue {
+                        request,
+                        retry_at,
+                        enqueued_at: entry.enqueued_at,
+                    });
                 }
             }
         }
@@ -142,17 +327,29 @@ impl CoreTimingModel {
         self.pending_cluster_smem = pending;
     }
 
+    /// Retries queued writebacks in order. Under `[writeback.quota]` a
+    /// rejected class keeps its place but the others still get their turn,
+    /// so one class over its share does not hold up the rest.
     pub(super) fn drain_pending_writeback(&mut self, now: Cycle) {
         let mut remaining = VecDeque::new();
+        let mut rejected = Vec::new();
         while let Some(payload) = self.pending_writeback.pop_front() {
+            let class = payload.class();
+            if rejected.contains(&class) {
+                remaining.push_back(payload);
+                continue;
+            }
             if self
                 .graph
                 .writeback_try_issue(now, payload.clone())
                 .is_err()
             {
                 remaining.push_back(payload);
-                remaining.extend(self.pending_writeback.drain(..));
-                break;
+                if !self.writeback_quotas {
+                    remaining.extend(self.pending_writeback.drain(..));
+                    break;
+                }
+                rejected.push(class);
             }
         }
         self.pending_writeback = remaining;
@@ -186,39 +383,98 @@ impl CoreTimingModel {
         }
     }
 
+    /// Releases what waited on finished DMA and tensor jobs, and queues
+    /// tensor results for writeback under `writeback.tensor_results`.
+    pub(super) fn collect_finished_jobs(&mut self, now: Cycle) {
+        for id in self.graph.dma_take_finished() {
+            self.release_request(now, RequestRef::dma(id));
+        }
+        for (id, bytes) in self.graph.tensor_take_finished() {
+            if id != 0 {
+                self.release_request(now, RequestRef::tensor(id));
+            }
+            if self.tensor_writeback {
+                self.enqueue_writeback(now, WritebackPayload::Tensor(TensorWriteback { bytes }));
+            }
+        }
+    }
+
+    /// Queues the execute results produced by `now` for writeback.
+    pub(super) fn collect_execute_results(&mut self, now: Cycle) {
+        if self.execute_results.is_empty() {
+            return;
+        }
+        let (ready, later) = std::mem::take(&mut self.execute_results)
+            .into_iter()
+            .partition(|&(ready_at, _)| ready_at <= now);
+        self.execute_results = later;
+        for (_, result) in ready {
+            self.enqueue_writeback(now, WritebackPayload::Execute(result));
+        }
+    }
+
+    /// An execute result left writeback: its warp may retire the
+    /// instruction.
+    pub(super) fn finish_execute_writeback(
+        &mut self,
+        now: Cycle,
+        warp: usize,
+        scheduler: &mut Scheduler,
+    ) {
+        if let Some(slot) = self.pending_execute.get_mut(warp) {
+            if *slot == Some(Cycle::MAX) {
+                *slot = Some(now);
+            }
+            scheduler.clear_resource_wait(warp);
+        }
+    }
+
+    /// Issues queued DMA jobs in order, leaving those still waiting on a
+    /// dependency queued; stops at the first the queue rejects.
     pub(super) fn drain_pending_dma(&mut self, now: Cycle) {
         let mut remaining = VecDeque::new();
-        while let Some(bytes) = self.pending_dma.pop_front() {
-            if self.graph.dma_try_issue(now, bytes).is_err() {
-                remaining.push_back(bytes);
-                remaining.extend(self.pending_dma.drain(..));
-                break;
+        let mut full = false;
+        while let Some((id, bytes)) = self.pending_dma.pop_front() {
+            let blocked = self.dependencies.is_blocked(RequestRef::dma(id));
+            if !full && !blocked && self.graph.dma_try_issue_with_id(now, id, bytes).is_ok() {
+                continue;
             }
+            full |= !blocked;
+            remaining.push_back((id, bytes));
         }
         self.pending_dma = remaining;
     }
 
-    pub(super) fn enqueue_dma(&mut self, now: Cycle, bytes: u32) {
-        if self.graph.dma_try_issue(now, bytes).is_err() {
-            self.pending_dma.push_back(bytes);
+    pub(super) fn enqueue_dma(&mut self, now: Cycle, id: u64, bytes: u32) {
+        if self.dependencies.is_blocked(RequestRef::dma(id)) {
+            self.dependencies.record_blocked();
+            self.pending_dma.push_back((id, bytes));
+        } else if self.graph.dma_try_issue_with_id(now, id, bytes).is_err() {
+            self.pending_dma.push_back((id, bytes));
         }
     }
 
+    /// Like `drain_pending_dma`, for the tensor queue.
     pub(super) fn drain_pending_tensor(&mut self, now: Cycle) {
         let mut remaining = VecDeque::new();
-        while let Some(bytes) = self.pending_tensor.pop_front() {
-            if self.graph.tensor_try_issue(now, bytes).is_err() {
-                remaining.push_back(bytes);
-                remaining.extend(self.pending_tensor.drain(..));
-                break;
+        let mut full = false;
+        while let Some((id, bytes)) = self.pending_tensor.pop_front() {
+            let blocked = self.dependencies.is_blocked(RequestRef::tensor(id));
+            if !full && !blocked && self.graph.tensor_try_issue_with_id(now, id, bytes).is_ok() {
+                continue;
             }
+            full |= !blocked;
+            remaining.push_back((id, bytes));
         }
         self.pending_tensor = remaining;
     }
 
-    pub(super) fn enqueue_tensor(&mut self, now: Cycle, bytes: u32) {
-        if self.graph.tensor_try_issue(now, bytes).is_err() {
-            self.pending_tensor.push_back(bytes);
+    pub(super) fn enqueue_tensor(&mut self, now: Cycle, id: u64, bytes: u32) {
+        if self.dependencies.is_blocked(RequestRef::tensor(id)) {
+            self.dependencies.record_blocked();
+            self.pending_tensor.push_back((id, bytes));
+        } else if self.graph.tensor_try_issue_with_id(now, id, bytes).is_err() {
+            self.pending_tensor.push_back((id, bytes));
         }
     }
 
@@ -303,6 +559,20 @@ impl CoreTimingModel {
         false
     }
 
+    /// Holds `warp` and its instruction until a completion it waits on
+    /// wakes it; see `CompletionWakeups`.
+    pub(super) fn park_warp(
+        &mut self,
+        now: Cycle,
+        warp: usize,
+        on: WakeOn,
+        scheduler: &mut Scheduler,
+    ) {
+        self.wakeups.park(now, warp, on);
+        scheduler.set_resource_wait_until(warp, Some(Cycle::MAX));
+        scheduler.replay_instruction(warp);
+    }
+
     pub(super) fn update_scheduler_state(&mut self, warp: usize, scheduler: &mut Scheduler) {
         let gmem_pending = self
             .pending_gmem
@@ -325,7 +595,17 @@ impl CoreTimingModel {
             .map(|entry| entry.is_some())
             .unwrap_or(false);
         let execute_pending = self.pending_execute.get(warp).copied().flatten().is_some();
-        if !gmem_pending && !smem_pending && !icache_pending && !fence_pending && !execute_pending {
+        let neutrino_pending = self.pending_neutrino.get(warp).copied().flatten().is_some();
+        let copy_pending = self.pending_copy.get(warp).copied().flatten().is_some();
+        if !gmem_pending
+            && !smem_pending
+            && !icache_pending
+            && !fence_pending
+            && !execute_pending
+            && !neutrino_pending
+            && !copy_pending
+            && !self.wakeups.is_parked(warp)
+        {
             scheduler.clear_resource_wait(warp);
         }
     }
@@ -0,0 +1,213 @@
+use std::collections::HashMap;
+
+use crate::timeq::Cycle;
+
+/// Cycle stamps of one instruction's trip through a core's timing path, as
+/// `sim::pipeview` writes them. A stage the instruction went through
+/// without waiting carries the stamp of the stage after it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct InstLifecycle {
+    pub warp: usize,
+    pub pc: u32,
+    pub raw: u64,
+    /// First cycle the warp tried to fetch the instruction.
+    pub fetch: Cycle,
+    /// Cycle it reached the head of the warp's instruction buffer.
+    pub decode: Cycle,
+    /// First cycle the issue scheduler picked it.
+    pub dispatch: Cycle,
+    /// Cycle it entered an execute unit, the LSU or another engine.
+    pub issue: Cycle,
+    /// Cycle its result was ready: when its execute unit finished or, for
+    /// loads and flushes, when its last memory request completed.
+    pub complete: Cycle,
+    /// Cycle it left the warp's issue slot, or completed if later.
+    pub retire: Cycle,
+    /// For stores, the cycle their last request completed.
+    pub store: Option<Cycle>,
+}
+
+/// A memory request an instruction waits on. Gmem and smem ids are
+/// allocated separately.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(super) enum MemRequest {
+    Gmem(u64),
+    Smem(u64),
+}
+
+#[derive(Debug, Clone, Default)]
+struct InFlight {
+    stamps: InstLifecycle,
+    decoded: bool,
+    dispatched: bool,
+    issued: bool,
+    ready: Option<Cycle>,
+    requests: Vec<MemRequest>,
+    last_completion: Option<Cycle>,
+    is_store: bool,
+}
+
+impl InFlight {
+    fn finish(mut self) -> InstLifecycle {
+        let stamps = &mut self.stamps;
+        let ready = self.ready.unwrap_or(stamps.issue).max(stamps.issue);
+        if self.is_store {
+            stamps.complete = ready;
+            stamps.retire = stamps.retire.max(ready);
+            stamps.store = Some(self.last_completion.unwrap_or(stamps.retire));
+        } else {
+            stamps.complete = ready.max(self.last_completion.unwrap_or(ready));
+            stamps.retire = stamps.retire.max(stamps.complete);
+        }
+        self.stamps
+    }
+}
+
+/// Lifecycle stamps of each warp's instruction in flight, and of retired
+/// instructions still waiting on memory.
+#[derive(Debug, Clone)]
+pub(super) struct PipeviewTracker {
+    warps: Vec<Option<InFlight>>,
+    waiting: HashMap<u64, InFlight>,
+    waiting_on: HashMap<MemRequest, u64>,
+    next_waiting: u64,
+    done: Vec<InstLifecycle>,
+}
+
+impl PipeviewTracker {
+    pub(super) fn new(num_warps: usize) -> Self {
+        Self {
+            warps: (0..num_warps).map(|_| None).collect(),
+            waiting: HashMap::new(),
+            waiting_on: HashMap::new(),
+            next_waiting: 0,
+            done: Vec::new(),
+        }
+    }
+
+    /// `warp` tried to fetch `pc`; `decoded` if the instruction is now at
+    /// the head of its instruction buffer.
+    pub(super) fn fetch(&mut self, now: Cycle, warp: usize, pc: u32, decoded: bool) {
+        let Some(slot) = self.warps.get_mut(warp) else {
+            return;
+        };
+        let inst = match slot {
+            Some(inst) if inst.stamps.pc == pc => inst,
+            _ => slot.insert(InFlight {
+                stamps: InstLifecycle {
+                    warp,
+                    pc,
+                    fetch: now,
+                    ..InstLifecycle::default()
+                },
+                ..InFlight::default()
+            }),
+        };
+        if decoded && !inst.decoded {
+            inst.decoded = true;
+            inst.stamps.decode = now;
+        }
+    }
+
+    pub(super) fn dispatch(&mut self, now: Cycle, warp: usize) {
+        if let Some(inst) = self.inflight(warp) {
+            if !inst.dispatched {
+                inst.dispatched = true;
+                inst.stamps.dispatch = now;
+            }
+        }
+    }
+
+    /// The instruction entered a unit, sending `request` to memory if any.
+    pub(super) fn issue(&mut self, now: Cycle, warp: usize, request: Option<MemRequest>) {
+        if let Some(inst) = self.inflight(warp) {
+            if !inst.issued {
+                inst.issued = true;
+                inst.stamps.issue = now;
+            }
+            inst.requests.extend(request);
+        }
+    }
+
+    pub(super) fn ready(&mut self, now: Cycle, warp: usize) {
+        if let Some(inst) = self.inflight(warp) {
+            inst.ready = Some(now);
+        }
+    }
+
+    /// The instruction left the warp's issue slot. It is done unless a
+    /// request `pending` still reports in flight has yet to complete.
+    pub(super) fn retire(
+        &mut self,
+        now: Cycle,
+        warp: usize,
+        raw: u64,
+        is_store: bool,
+        pending: impl Fn(MemRequest) -> bool,
+    ) {
+        let Some(mut inst) = self.warps.get_mut(warp).and_then(Option::take) else {
+            return;
+        };
+        let stamps = &mut inst.stamps;
+        stamps.raw = raw;
+        stamps.retire = now;
+        if !inst.issued {
+            stamps.issue = now;
+        }
+        if !inst.dispatched {
+            stamps.dispatch = stamps.issue;
+        }
+        if !inst.decoded {
+            stamps.decode = stamps.dispatch;
+        }
+        inst.is_store = is_store;
+        inst.requests.retain(|&request| pending(request));
+        if inst.requests.is_empty() {
+            self.done.push(inst.finish());
+            return;
+        }
+        let key = self.next_waiting;
+        self.next_waiting = self.next_waiting.wrapping_add(1);
+        for &request in &inst.requests {
+            self.waiting_on.insert(request, key);
+        }
+        self.waiting.insert(key, inst);
+    }
+
+    /// Every part of `request` has completed.
+    pub(super) fn request_done(&mut self, now: Cycle, request: MemRequest) {
+        let Some(key) = self.waiting_on.remove(&request) else {
+            // Completed before its instruction retired.
+            if let Some(inst) = self
+                .warps
+                .iter_mut()
+                .flatten()
+                .find(|inst| inst.requests.contains(&request))
+            {
+                inst.requests.retain(|&other| other != request);
+                inst.last_completion = Some(now);
+            }
+            return;
+        };
+        let Some(inst) = self.waiting.get_mut(&key) else {
+            return;
+        };
+        inst.requests.retain(|&other| other != request);
+        inst.last_completion = Some(now);
+        if inst.requests.is_empty() {
+            if let Some(inst) = self.waiting.remove(&key) {
+                self.done.push(inst.finish());
+            }
+        }
+    }
+
+    /// Instructions finished since the last call, in the order they
+    /// finished.
+    pub(super) fn take(&mut self) -> Vec<InstLifecycle> {
+        std::mem::take(&mut self.done)
+    }
+
+    fn inflight(&mut self, warp: usize) -> Option<&mut InFlight> {
+        self.warps.get_mut(warp).and_then(Option::as_mut)
+    }
+}
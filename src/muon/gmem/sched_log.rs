@@ -0,0 +1,152 @@
+use std::sync::{Arc, Mutex, PoisonError};
+
+use crate::muon::scheduler::Scheduler;
+use crate::timeq::Cycle;
+
+use super::{CoreTimingModel, CpiStall};
+
+/// One warp in a `SchedDecision`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WarpSchedState {
+    pub active: bool,
+    /// Had an instruction the issue scheduler could pick.
+    pub ready: bool,
+    /// What the warp was waiting on, if anything.
+    pub stall: Option<CpiStall>,
+    pub granted: bool,
+}
+
+/// The issue scheduler's view of one core in one cycle, and the warps it
+/// picked.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchedDecision {
+    pub cycle: Cycle,
+    /// Global id, `cluster * num_cores + core`.
+    pub core_id: usize,
+    pub warps: Vec<WarpSchedState>,
+}
+
+impl SchedDecision {
+    /// Warps that issued.
+    pub fn chosen(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.warps.len()).filter(|&warp| self.warps[warp].granted)
+    }
+
+    /// Warps that were ready but not picked.
+    pub fn alternatives(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.warps.len()).filter(|&warp| self.warps[warp].ready && !self.warps[warp].granted)
+    }
+
+    pub fn grants(&self) -> Vec<bool> {
+        self.warps.iter().map(|warp| warp.granted).collect()
+    }
+}
+
+/// Picks the warps to issue in place of the built-in issue scheduler, e.g.
+/// a learned policy.
+pub trait IssuePolicy: Send {
+    /// `decision` carries the built-in choice in `granted`. Returns the
+    /// warps to issue instead, by warp id, or `None` to keep the built-in
+    /// choice. Warps that are not ready never issue, whatever it returns.
+    fn select(&mut self, decision: &SchedDecision) -> Option<Vec<bool>>;
+}
+
+/// An `IssuePolicy` shared by every core it is installed on.
+pub type SharedIssuePolicy = Arc<Mutex<dyn IssuePolicy>>;
+
+impl CoreTimingModel {
+    /// Starts or stops recording a `SchedDecision` each cycle this core
+    /// has an active warp.
+    pub fn set_sched_log(&mut self, enabled: bool) {
+        match (enabled, self.sched_log.is_some()) {
+            (true, false) => self.sched_log = Some(Vec::new()),
+            (false, true) => self.sched_log = None,
+            _ => {}
+        }
+    }
+
+    /// Decisions recorded since the last call.
+    pub fn take_sched_log(&mut self) -> Vec<SchedDecision> {
+        self.sched_log
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    /// Lets `policy` override the issue scheduler's choice from now on;
+    /// `None` goes back to the built-in scheduler.
+    pub fn set_issue_policy(&mut self, policy: Option<SharedIssuePolicy>) {
+        self.issue_policy = policy;
+    }
+
+    /// Replaces `grants` with the installed policy's choice, if it makes
+    /// one.
+    pub(super) fn apply_issue_policy(
+        &mut self,
+        now: Cycle,
+        ready: &[bool],
+        grants: &mut [bool],
+        scheduler: &Scheduler,
+    ) {
+        let Some(policy) = self.issue_policy.clone() else {
+            return;
+        };
+        let decision = self.sched_decision(now, ready, grants, scheduler);
+        let choice = policy
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .select(&decision);
+        let Some(choice) = choice else {
+            return;
+        };
+        for (warp, grant) in grants.iter_mut().enumerate() {
+            *grant = ready[warp] && choice.get(warp).copied().unwrap_or(false);
+        }
+    }
+
+    pub(super) fn record_sched_decision(
+        &mut self,
+        now: Cycle,
+        ready: &[bool],
+        grants: &[bool],
+        scheduler: &Scheduler,
+    ) {
+        if self.sched_log.is_none() {
+            return;
+        }
+        let decision = self.sched_decision(now, ready, grants, scheduler);
+        if !decision.warps.iter().any(|warp| warp.active) {
+            return;
+        }
+        if let Some(log) = self.sched_log.as_mut() {
+            log.push(decision);
+        }
+    }
+
+    fn sched_decision(
+        &self,
+        now: Cycle,
+        ready: &[bool],
+        grants: &[bool],
+        scheduler: &Scheduler,
+    ) -> SchedDecision {
+        let warps = (0..ready.len())
+            .map(|warp| {
+                let active = scheduler.warp_state(warp).active;
+                WarpSchedState {
+                    active,
+                    ready: ready[warp],
+                    stall: active
+                        .then(|| self.warp_cpi_stall(now, warp, scheduler))
+                        .flatten(),
+                    granted: grants.get(warp).copied().unwrap_or(false),
+                }
+            })
+            .collect();
+        SchedDecision {
+            cycle: now,
+            core_id: self.core_id,
+            warps,
+        }
+    }
+}
@@ -1,5 +1,6 @@
 use super::{CoreTimingModel, SmemConflictSample};
 use crate::timeflow::{GmemRequest, SmemRequest};
+use bitvec::bitvec;
 
 impl CoreTimingModel {
     pub(super) fn split_gmem_request(&self, request: &GmemRequest) -> Vec<GmemRequest> {
@@ -18,15 +19,19 @@ impl CoreTimingModel {
         if lines.is_empty() {
             return vec![request.clone()];
         }
+        let masks = request.line_byte_enables.as_ref();
         lines
             .into_iter()
-            .map(|line| {
+            .enumerate()
+            .map(|(idx, line)| {
                 let mut child = request.clone();
                 child.addr = line;
                 child.line_addr = line;
                 child.bytes = line_bytes as u32;
                 child.coalesced_lines = None;
                 child.lane_addrs = None;
+                child.line_byte_enables = None;
+                child.byte_enable = masks.and_then(|masks| masks.get(idx)).cloned();
                 child
             })
             .collect()
@@ -90,47 +95,28 @@ impl CoreTimingModel {
             if lane_addrs.is_empty() {
                 return None;
             }
+            // one bit per bank and per (bank, subbank), so any bank count
+            // and any warp width is tracked without hashing
             let nb = num_banks as usize;
             let ns = num_subbanks as usize;
-            if nb <= 64 && ns <= 64 {
-                let mut bank_mask: u64 = 0;
-                let mut subbank_masks: Vec<u64> = vec![0u64; nb];
-                for &addr in lane_addrs {
-                    let word = addr / word_bytes;
-                    let bank = (word % num_banks) as usize;
-                    let subbank = ((word / num_banks) % num_subbanks) as usize;
-                    bank_mask |= 1u64 << bank;
-                    subbank_masks[bank] |= 1u64 << subbank;
-                }
-                let unique_banks = bank_mask.count_ones() as u32;
-                let unique_subbanks = subbank_masks.iter().map(|m| m.count_ones()).sum::<u32>();
-                let conflict_lanes = active.saturating_sub(unique_banks.max(1));
-                return Some(SmemConflictSample {
-                    active_lanes: active,
-                    unique_banks,
-                    unique_subbanks,
-                    conflict_lanes,
-                });
-            } else {
-                let mut banks = std::collections::HashSet::new();
-                let mut subbanks = std::collections::HashSet::new();
-                for &addr in lane_addrs {
-                    let word = addr / word_bytes;
-                    let bank = (word % num_banks) as usize;
-                    let subbank = ((word / num_banks) % num_subbanks) as usize;
-                    banks.insert(bank);
-                    subbanks.insert((bank, subbank));
-                }
-                let unique_banks = banks.len() as u32;
-                let unique_subbanks = subbanks.len() as u32;
-                let conflict_lanes = active.saturating_sub(unique_banks.max(1));
-                return Some(SmemConflictSample {
-                    active_lanes: active,
-                    unique_banks,
-                    unique_subbanks,
-                    conflict_lanes,
-                });
+            let mut bank_mask = bitvec![0; nb];
+            let mut subbank_mask = bitvec![0; nb * ns];
+            for &addr in lane_addrs {
+                let word = addr / word_bytes;
+                let bank = (word % num_banks) as usize;
+                let subbank = ((word / num_banks) % num_subbanks) as usize;
+                bank_mask.set(bank, true);
+                subbank_mask.set(bank * ns + subbank, true);
             }
+            let unique_banks = bank_mask.count_ones() as u32;
+            let unique_subbanks = subbank_mask.count_ones() as u32;
+            let conflict_lanes = active.saturating_sub(unique_banks.max(1));
+            return Some(SmemConflictSample {
+                active_lanes: active,
+                unique_banks,
+                unique_subbanks,
+                conflict_lanes,
+            });
         }
 
         let unique_banks = 1;
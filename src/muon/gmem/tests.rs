@@ -3,6 +3,7 @@ use crate::muon::config::{LaneConfig, MuonConfig};
 use crate::muon::decode::IssuedInst;
 use crate::muon::execute::Opcode;
 use crate::muon::scheduler::Scheduler;
+use crate::sim::checkpoint::{deep_copy, Shared};
 use crate::sim::log::Logger;
 use crate::timeflow::{ClusterGmemGraph, CoreGraphConfig, GmemFlowConfig, SmemFlowConfig};
 use crate::timeq::{module_now, ServerConfig};
@@ -59,11 +60,7 @@ fn make_model_with_lsu(num_warps: usize, lsu_depth: usize) -> CoreTimingModel {
     cfg.memory.lsu.queues.shared_ldq.queue_capacity = lsu_depth.max(1);
     cfg.memory.lsu.queues.shared_stq.queue_capacity = lsu_depth.max(1);
     let logger = Arc::new(Logger::silent());
-    let cluster_gmem = Arc::new(std::sync::RwLock::new(ClusterGmemGraph::new(
-        cfg.memory.gmem.clone(),
-        1,
-        1,
-    )));
+    let cluster_gmem = Shared::new(ClusterGmemGraph::new(cfg.memory.gmem.clone(), 1, 1));
     CoreTimingModel::new(cfg, num_warps, 0, 0, cluster_gmem, logger)
 }
 
@@ -79,11 +76,7 @@ fn make_model_with_execute(
     cfg.compute.execute = execute;
 
     let logger = Arc::new(Logger::silent());
-    let cluster_gmem = Arc::new(std::sync::RwLock::new(ClusterGmemGraph::new(
-        cfg.memory.gmem.clone(),
-        1,
-        1,
-    )));
+    let cluster_gmem = Shared::new(ClusterGmemGraph::new(cfg.memory.gmem.clone(), 1, 1));
     CoreTimingModel::new(cfg, num_warps, 0, 0, cluster_gmem, logger)
 }
 
@@ -147,6 +140,94 @@ fn accepted_request_marks_pending_until_completion() {
     assert!(model.stats().gmem.issued() >= 1);
 }
 
+#[test]
+fn completion_log_records_only_when_enabled() {
+    let mut scheduler = make_scheduler(1);
+    scheduler.spawn_single_warp();
+
+    let mut model = make_model(1);
+    let mut cycle = module_now(&scheduler);
+    model
+        .issue_gmem_request(cycle, 0, GmemRequest::new(0, 16, 0xF, true), &mut scheduler)
+        .expect("request should accept");
+    model.set_gmem_completion_log(true);
+    for _ in 0..500 {
+        model.tick(cycle, &mut scheduler);
+        if !model.has_pending_gmem(0) {
+            break;
+        }
+        cycle = cycle.saturating_add(1);
+    }
+
+    let completions = model.take_gmem_completions();
+    assert_eq!(completions.len(), 1);
+    assert_eq!(completions[0].request.warp, 0);
+    assert!(model.take_gmem_completions().is_empty());
+
+    model.set_gmem_completion_log(false);
+    model
+        .issue_gmem_request(
+            cycle,
+            0,
+            GmemRequest::new(64, 16, 0xF, true),
+            &mut scheduler,
+        )
+        .expect("request should accept");
+    for _ in 0..500 {
+        model.tick(cycle, &mut scheduler);
+        if !model.has_pending_gmem(0) {
+            break;
+        }
+        cycle = cycle.saturating_add(1);
+    }
+    assert_eq!(model.stats().gmem.completed(), 2);
+    assert!(model.take_gmem_completions().is_empty());
+}
+
+#[test]
+fn flush_ordering_holds_younger_requests_until_the_flush_completes() {
+    let mut scheduler = make_scheduler(2);
+    let threads = vec![vec![(0, 0, 0)], vec![(0, 0, 1)]];
+    scheduler.spawn_n_warps(0x8000_0000, &threads);
+
+    let mut cfg = CoreGraphConfig::default();
+    cfg.memory.gmem.policy.flush_ordering = crate::timeflow::FlushOrdering::Core;
+    let logger = Arc::new(Logger::silent());
+    let cluster_gmem = Shared::new(ClusterGmemGraph::new(cfg.memory.gmem.clone(), 1, 1));
+    let mut model = CoreTimingModel::new(cfg, 2, 0, 0, cluster_gmem, logger);
+    model.set_gmem_completion_log(true);
+    let mut cycle = module_now(&scheduler);
+    model
+        .issue_gmem_request(cycle, 0, GmemRequest::new_flush_l1(0, 1), &mut scheduler)
+        .expect("flush should accept");
+    model
+        .issue_gmem_request(cycle, 1, GmemRequest::new(1, 16, 0xF, true), &mut scheduler)
+        .expect("another warp's load should accept");
+    for _ in 0..1000 {
+        model.tick(cycle, &mut scheduler);
+        if !model.has_pending_gmem(0) && !model.has_pending_gmem(1) {
+            break;
+        }
+        cycle = cycle.saturating_add(1);
+    }
+
+    let kinds: Vec<_> = model
+        .take_gmem_completions()
+        .iter()
+        .map(|completion| completion.request.kind)
+        .collect();
+    assert_eq!(
+        kinds,
+        [
+            crate::timeflow::GmemRequestKind::FlushL1,
+            crate::timeflow::GmemRequestKind::Load
+        ]
+    );
+    let summary = model.perf_summary().flush_ordering;
+    assert_eq!(summary.flushes, 1);
+    assert!(summary.held_cycles > 0);
+}
+
 #[test]
 fn gmem_coalescing_adds_multiple_pending_entries() {
     let mut scheduler = make_scheduler(1);
@@ -155,11 +236,7 @@ fn gmem_coalescing_adds_multiple_pending_entries() {
     let mut cfg = CoreGraphConfig::default();
     cfg.memory.gmem.policy.l0_enabled = false;
     let logger = Arc::new(Logger::silent());
-    let cluster_gmem = Arc::new(std::sync::RwLock::new(ClusterGmemGraph::new(
-        cfg.memory.gmem.clone(),
-        1,
-        1,
-    )));
+    let cluster_gmem = Shared::new(ClusterGmemGraph::new(cfg.memory.gmem.clone(), 1, 1));
     let mut model = CoreTimingModel::new(cfg, 1, 0, 0, cluster_gmem, logger);
     let now = module_now(&scheduler);
     let mut request = GmemRequest::new(0, 16, 0x3, true);
@@ -201,11 +278,7 @@ fn gmem_coalescing_uses_l0_line_when_enabled() {
     cfg.memory.gmem.policy.l1_line_bytes = 32;
 
     let logger = Arc::new(Logger::silent());
-    let cluster_gmem = Arc::new(std::sync::RwLock::new(ClusterGmemGraph::new(
-        cfg.memory.gmem.clone(),
-        1,
-        1,
-    )));
+    let cluster_gmem = Shared::new(ClusterGmemGraph::new(cfg.memory.gmem.clone(), 1, 1));
     let mut model = CoreTimingModel::new(cfg, 1, 0, 0, cluster_gmem, logger);
 
     let now = module_now(&scheduler);
@@ -252,6 +325,7 @@ fn execute_pipeline_stalls_until_ready() {
         .is_ok());
 }
 
+#[cfg(not(feature = "fast"))]
 #[test]
 fn execute_utilization_counts_busy_cycles() {
     let mut scheduler = make_scheduler(1);
@@ -280,6 +354,16 @@ fn execute_utilization_counts_busy_cycles() {
     let summary = model.perf_summary();
     assert!(summary.execute_util.cycles > 0);
     assert!(summary.execute_util.int_busy_sum > 0);
+
+    let util = summary.execute_util;
+    assert_eq!(
+        util.idle_cycles(util.int_busy_sum),
+        util.cycles - util.int_busy_sum
+    );
+    assert_eq!(util.idle_cycles(util.fp_busy_sum), util.cycles);
+    let line = util.format_line();
+    assert!(line.starts_with("duty alu "), "{line}");
+    assert!(!line.contains(" fp "), "{line}");
 }
 
 #[test]
@@ -378,11 +462,7 @@ fn smem_crossbar_capacity_backpressures_new_requests() {
     cfg.memory.gmem = gmem;
     cfg.memory.smem = smem;
     let logger = Arc::new(Logger::silent());
-    let cluster_gmem = Arc::new(std::sync::RwLock::new(ClusterGmemGraph::new(
-        cfg.memory.gmem.clone(),
-        1,
-        1,
-    )));
+    let cluster_gmem = Shared::new(ClusterGmemGraph::new(cfg.memory.gmem.clone(), 1, 1));
     let mut model = CoreTimingModel::new(cfg, 2, 0, 0, cluster_gmem, logger);
 
     let now = module_now(&scheduler);
@@ -422,11 +502,7 @@ fn mmio_store_triggers_dma_queue() {
     cfg.memory.lsu.queues.global_ldq.queue_capacity = 8;
     cfg.memory.lsu.queues.global_stq.queue_capacity = 8;
     let logger = Arc::new(Logger::silent());
-    let cluster_gmem = Arc::new(std::sync::RwLock::new(ClusterGmemGraph::new(
-        cfg.memory.gmem.clone(),
-        1,
-        1,
-    )));
+    let cluster_gmem = Shared::new(ClusterGmemGraph::new(cfg.memory.gmem.clone(), 1, 1));
     let mut model = CoreTimingModel::new(cfg, 1, 0, 0, cluster_gmem, logger);
 
     let now = module_now(&scheduler);
@@ -454,11 +530,7 @@ fn perf_summary_tracks_dma_bytes() {
     cfg.io.dma.queue.bytes_per_cycle = 16;
     cfg.io.dma.queue.queue_capacity = 2;
     let logger = Arc::new(Logger::silent());
-    let cluster_gmem = Arc::new(std::sync::RwLock::new(ClusterGmemGraph::new(
-        cfg.memory.gmem.clone(),
-        1,
-        1,
-    )));
+    let cluster_gmem = Shared::new(ClusterGmemGraph::new(cfg.memory.gmem.clone(), 1, 1));
     let mut model = CoreTimingModel::new(cfg, 1, 0, 0, cluster_gmem, logger);
 
     let now = module_now(&scheduler);
@@ -483,11 +555,7 @@ fn perf_summary_tracks_tensor_bytes() {
     cfg.compute.tensor.queue.bytes_per_cycle = 16;
     cfg.compute.tensor.queue.queue_capacity = 2;
     let logger = Arc::new(Logger::silent());
-    let cluster_gmem = Arc::new(std::sync::RwLock::new(ClusterGmemGraph::new(
-        cfg.memory.gmem.clone(),
-        1,
-        1,
-    )));
+    let cluster_gmem = Shared::new(ClusterGmemGraph::new(cfg.memory.gmem.clone(), 1, 1));
     let mut model = CoreTimingModel::new(cfg, 1, 0, 0, cluster_gmem, logger);
 
     let now = module_now(&scheduler);
@@ -511,15 +579,11 @@ fn csr_write_triggers_tensor_queue() {
     cfg.compute.tensor.csr_addrs = vec![0x7c0];
     cfg.compute.tensor.queue.base_latency = 1;
     let logger = Arc::new(Logger::silent());
-    let cluster_gmem = Arc::new(std::sync::RwLock::new(ClusterGmemGraph::new(
-        cfg.memory.gmem.clone(),
-        1,
-        1,
-    )));
+    let cluster_gmem = Shared::new(ClusterGmemGraph::new(cfg.memory.gmem.clone(), 1, 1));
     let mut model = CoreTimingModel::new(cfg, 1, 0, 0, cluster_gmem, logger);
 
     let now = module_now(&scheduler);
-    model.notify_csr_write(now, 0x7c0);
+    model.notify_csr_write(now, 0, 0x7c0);
     for cycle in now..now + 5 {
         model.tick(cycle, &mut scheduler);
     }
@@ -527,6 +591,129 @@ fn csr_write_triggers_tensor_queue() {
     assert!(model.tensor_completed() >= 1);
 }
 
+#[test]
+fn neutrino_complete_waits_for_outstanding_invoke() {
+    let mut scheduler = make_scheduler(1);
+    scheduler.spawn_single_warp();
+
+    let mut cfg = CoreGraphConfig::default();
+    cfg.io.neutrino.enabled = true;
+    cfg.io.neutrino.queue.base_latency = 1;
+    cfg.io.neutrino.completion_latency = 10;
+    let logger = Arc::new(Logger::silent());
+    let cluster_gmem = Shared::new(ClusterGmemGraph::new(cfg.memory.gmem.clone(), 1, 1));
+    let mut model = CoreTimingModel::new(cfg, 1, 0, 0, cluster_gmem, logger);
+
+    let mut invoke = issued_int_op();
+    invoke.opcode = Opcode::CUSTOM2;
+    invoke.opext = (Opcode::NU_INVOKE >> 7) as u8;
+    let mut complete = invoke.clone();
+    complete.opext = (Opcode::NU_COMPLETE >> 7) as u8;
+
+    let now = module_now(&scheduler);
+    let dispatch_at = model
+        .issue_execute(now, 0, &invoke, 16, &mut scheduler)
+        .expect_err("invoke should stall until dispatched");
+    for cycle in now..=dispatch_at {
+        model.tick(cycle, &mut scheduler);
+    }
+    assert!(model
+        .issue_execute(dispatch_at, 0, &invoke, 16, &mut scheduler)
+        .is_ok());
+    assert_eq!(1, model.outstanding_neutrino());
+
+    let complete_wait = model
+        .issue_execute(dispatch_at, 0, &complete, 16, &mut scheduler)
+        .expect_err("complete should wait for the invoke");
+    assert!(complete_wait >= dispatch_at + 10);
+
+    for cycle in dispatch_at + 1..=complete_wait {
+        model.tick(cycle, &mut scheduler);
+    }
+    assert_eq!(0, model.outstanding_neutrino());
+    let summary = model.perf_summary();
+    assert_eq!(1, summary.neutrino_stats.invokes);
+    assert_eq!(1, summary.neutrino_stats.completed);
+    if crate::timeflow::STATS_COMPILED {
+        assert!(summary.neutrino_util.busy_sum > 0);
+    }
+}
+
+#[test]
+fn pending_neutrino_cycles_are_not_active_cycles() {
+    let mut scheduler = make_scheduler(1);
+    scheduler.spawn_single_warp();
+
+    let mut cfg = CoreGraphConfig::default();
+    cfg.io.neutrino.enabled = true;
+    cfg.io.neutrino.queue.base_latency = 4;
+    let logger = Arc::new(Logger::silent());
+    let cluster_gmem = Shared::new(ClusterGmemGraph::new(cfg.memory.gmem.clone(), 1, 1));
+    let mut model = CoreTimingModel::new(cfg, 1, 0, 0, cluster_gmem, logger);
+
+    let mut invoke = issued_int_op();
+    invoke.opcode = Opcode::CUSTOM2;
+    invoke.opext = (Opcode::NU_INVOKE >> 7) as u8;
+
+    let now = module_now(&scheduler);
+    model.record_issue_stats(now, 1, 1, 1, &scheduler);
+    let dispatch_at = model
+        .issue_execute(now + 1, 0, &invoke, 16, &mut scheduler)
+        .expect_err("invoke should stall until dispatched");
+    assert!(model.warp_state(0).neutrino_ready_at.is_some());
+    for cycle in now + 1..dispatch_at {
+        model.record_issue_stats(cycle, 1, 0, 0, &scheduler);
+        model.tick(cycle, &mut scheduler);
+    }
+    model.tick(dispatch_at, &mut scheduler);
+    assert!(model
+        .issue_execute(dispatch_at, 0, &invoke, 16, &mut scheduler)
+        .is_ok());
+    model.record_issue_stats(dispatch_at, 1, 1, 1, &scheduler);
+
+    let summary = model.perf_summary();
+    let waited = dispatch_at - (now + 1);
+    assert!(waited > 0);
+    assert_eq!(summary.cpi_stack.sync, waited);
+    assert_eq!(summary.warp_retire[0].active_cycles, 2);
+}
+
+#[test]
+fn memset_stream_is_served_by_copy_engine() {
+    let mut scheduler = make_scheduler(1);
+    scheduler.spawn_single_warp();
+
+    let mut cfg = CoreGraphConfig::default();
+    cfg.io.copy_engine.enabled = true;
+    cfg.io.copy_engine.detect_after = 2;
+    let logger = Arc::new(Logger::silent());
+    let cluster_gmem = Shared::new(ClusterGmemGraph::new(cfg.memory.gmem.clone(), 1, 1));
+    let mut model = CoreTimingModel::new(cfg, 1, 0, 0, cluster_gmem, logger);
+
+    let mut now = module_now(&scheduler);
+    for i in 0..4u64 {
+        let base = 0x4000 + i * 64;
+        let mut request = GmemRequest::new(0, 64, 16, false);
+        request.addr = base;
+        request.lane_addrs = Some((0..16).map(|lane| base + lane * 4).collect());
+        while model
+            .issue_gmem_request(now, 0, request.clone(), &mut scheduler)
+            .is_err()
+        {
+            now += 1;
+            model.tick(now, &mut scheduler);
+        }
+        now += 1;
+        model.tick(now, &mut scheduler);
+    }
+
+    let stats = model.perf_summary().copy_engine_stats;
+    assert_eq!(1, stats.set_streams);
+    assert_eq!(3 * 64, stats.eligible_bytes);
+    assert_eq!(stats.eligible_bytes, stats.accelerated_bytes);
+    assert_eq!(1, stats.bursts);
+}
+
 #[test]
 fn sequential_loads_benefit_from_cache() {
     let mut scheduler = make_scheduler(1);
@@ -535,11 +722,7 @@ fn sequential_loads_benefit_from_cache() {
     let mut cfg = CoreGraphConfig::default();
     cfg.memory.gmem.policy.l0_enabled = false;
     let logger = Arc::new(Logger::silent());
-    let cluster_gmem = Arc::new(std::sync::RwLock::new(ClusterGmemGraph::new(
-        cfg.memory.gmem.clone(),
-        1,
-        1,
-    )));
+    let cluster_gmem = Shared::new(ClusterGmemGraph::new(cfg.memory.gmem.clone(), 1, 1));
     let mut model = CoreTimingModel::new(cfg, 1, 0, 0, cluster_gmem, logger);
     let now = module_now(&scheduler);
 
@@ -572,3 +755,928 @@ fn sequential_loads_benefit_from_cache() {
     assert_eq!(summary.gmem_hits.l1_accesses, 2);
     assert_eq!(summary.gmem_hits.l1_hits, 1);
 }
+
+#[test]
+fn addresses_past_4gib_do_not_alias_low_lines() {
+    let mut scheduler = make_scheduler(1);
+    scheduler.spawn_single_warp();
+
+    let mut cfg = CoreGraphConfig::default();
+    cfg.memory.gmem.policy.l0_enabled = false;
+    let logger = Arc::new(Logger::silent());
+    let cluster_gmem = Shared::new(ClusterGmemGraph::new(cfg.memory.gmem.clone(), 1, 1));
+    let mut model = CoreTimingModel::new(cfg, 1, 0, 0, cluster_gmem, logger);
+    let mut cycle = module_now(&scheduler);
+
+    let addrs = [0x1000, 0x1_0000_1000, 0x1_0000_1004];
+    for (i, addr) in addrs.into_iter().enumerate() {
+        let mut req = GmemRequest::new(0, 16, 0xF, true);
+        req.addr = addr;
+        model
+            .issue_gmem_request(cycle, 0, req, &mut scheduler)
+            .expect("request should accept");
+        while model.stats().gmem.completed() <= i as u64 {
+            model.tick(cycle, &mut scheduler);
+            cycle = cycle.saturating_add(1);
+        }
+    }
+
+    // Only the third request, to the same line as the second, hits.
+    let summary = model.perf_summary();
+    assert_eq!(summary.gmem_hits.l1_accesses, 3);
+    assert_eq!(summary.gmem_hits.l1_hits, 1);
+}
+
+#[test]
+fn miss_cap_limits_per_warp_mlp() {
+    let mut scheduler = make_scheduler(1);
+    scheduler.spawn_single_warp();
+
+    let mut cfg = CoreGraphConfig::default();
+    cfg.memory.lsu.resources.max_outstanding_misses_per_warp = 1;
+    let logger = Arc::new(Logger::silent());
+    let cluster_gmem = Shared::new(ClusterGmemGraph::new(cfg.memory.gmem.clone(), 1, 1));
+    let mut model = CoreTimingModel::new(cfg, 1, 0, 0, cluster_gmem, logger);
+    let mut cycle = module_now(&scheduler);
+
+    let mut first = GmemRequest::new(0, 4, 1, false);
+    first.addr = 0x1000;
+    model
+        .issue_gmem_request(cycle, 0, first, &mut scheduler)
+        .expect("first store should accept");
+    while model.graph.lsu_outstanding_misses(0) == 0 {
+        cycle += 1;
+        model.tick(cycle, &mut scheduler);
+    }
+
+    let mut second = GmemRequest::new(0, 4, 1, false);
+    second.addr = 0x9000;
+    assert!(model
+        .issue_gmem_request(cycle, 0, second.clone(), &mut scheduler)
+        .is_err());
+    assert_eq!(1, model.stats().lsu.mlp_cap_rejects);
+
+    while model.outstanding_gmem() > 0 {
+        cycle += 1;
+        model.tick(cycle, &mut scheduler);
+    }
+    assert_eq!(0, model.graph.lsu_outstanding_misses(0));
+    model
+        .issue_gmem_request(cycle, 0, second, &mut scheduler)
+        .expect("cap frees up once the miss completes");
+
+    if crate::timeflow::STATS_COMPILED {
+        let mlp = model.perf_summary().mlp;
+        assert_eq!(1, mlp.per_warp[0].misses);
+        assert_eq!(1, mlp.core.max_outstanding);
+        assert!(mlp.core.busy_cycles > 0);
+        assert_eq!(1.0, mlp.core.avg_outstanding());
+    }
+}
+
+#[test]
+fn covered_load_forwards_from_pending_store() {
+    let mut scheduler = make_scheduler(1);
+    scheduler.spawn_single_warp();
+
+    let mut cfg = CoreGraphConfig::default();
+    cfg.memory.lsu.forwarding.enabled = true;
+    cfg.memory.lsu.forwarding.latency = 1;
+    let logger = Arc::new(Logger::silent());
+    let cluster_gmem = Shared::new(ClusterGmemGraph::new(cfg.memory.gmem.clone(), 1, 1));
+    let mut model = CoreTimingModel::new(cfg, 1, 0, 0, cluster_gmem, logger);
+    let mut cycle = module_now(&scheduler);
+
+    let mut store = GmemRequest::new(0, 16, 4, false);
+    store.addr = 0x1000;
+    model
+        .issue_gmem_request(cycle, 0, store, &mut scheduler)
+        .expect("store should accept");
+    let mut load = GmemRequest::new(0, 4, 1, true);
+    load.addr = 0x1008;
+    model
+        .issue_gmem_request(cycle, 0, load, &mut scheduler)
+        .expect("covered load should forward instead of waiting on the store");
+
+    while model.outstanding_gmem() > 0 && cycle < 500 {
+        cycle += 1;
+        model.tick(cycle, &mut scheduler);
+    }
+    assert_eq!(0, model.outstanding_gmem());
+    let summary = model.perf_summary();
+    assert_eq!(1, summary.lsu_stats.forwarded_loads);
+    assert_eq!(0, summary.gmem_hits.l1_hits);
+}
+
+#[test]
+fn clear_stats_drops_warmup_activity() {
+    let mut scheduler = make_scheduler(1);
+    scheduler.spawn_single_warp();
+
+    let mut model = make_model(1);
+    let now = module_now(&scheduler);
+    let mut request = GmemRequest::new(0, 16, 0xF, true);
+    request.addr = 0x2000;
+    model
+        .issue_gmem_request(now, 0, request, &mut scheduler)
+        .expect("request should accept");
+
+    let mut cycle = now;
+    for _ in 0..500 {
+        model.tick(cycle, &mut scheduler);
+        if model.stats().gmem.completed() >= 1 {
+            break;
+        }
+        cycle += 1;
+    }
+    let warm = model.perf_summary();
+    assert!(warm.gmem_stats.completed() >= 1);
+    assert!(warm.gmem_hits.l1_accesses + warm.gmem_hits.l0_accesses > 0);
+
+    model.clear_stats();
+    model.clear_shared_stats();
+    let cleared = model.perf_summary();
+    assert_eq!(0, cleared.gmem_stats.completed());
+    assert_eq!(
+        0,
+        cleared.gmem_hits.l0_accesses + cleared.gmem_hits.l1_accesses
+    );
+    assert_eq!(0, cleared.latencies.gmem_count);
+    assert_eq!(0, cleared.scheduler.cycles);
+    assert_eq!(0, cleared.gmem_level_stats.l1.accesses());
+    assert_eq!(warm.scheduler.issue_width, cleared.scheduler.issue_width);
+}
+
+#[test]
+fn warp_state_reports_outstanding_requests() {
+    let mut scheduler = make_scheduler(2);
+    scheduler.spawn_single_warp();
+
+    let mut model = make_model(2);
+    let now = module_now(&scheduler);
+    let request = GmemRequest::new(1, 16, 0xF, true);
+    model
+        .issue_gmem_request(now, 1, request, &mut scheduler)
+        .expect("request should accept");
+
+    let state = model.warp_state(1);
+    assert_eq!(state.gmem_requests.len(), 1);
+    assert!(state.smem_requests.is_empty());
+    assert!(model.warp_state(0).gmem_requests.is_empty());
+
+    let mut cycle = now;
+    for _ in 0..500 {
+        model.tick(cycle, &mut scheduler);
+        if !model.has_pending_gmem(1) {
+            break;
+        }
+        cycle = cycle.saturating_add(1);
+    }
+    assert!(model.warp_state(1).gmem_requests.is_empty());
+}
+
+#[test]
+fn cpi_stack_charges_outstanding_gmem_and_sums_to_cycles() {
+    let mut scheduler = make_scheduler(1);
+    let mut model = make_model(1);
+
+    model.record_issue_stats(0, 0, 0, 0, &scheduler);
+    scheduler.spawn_single_warp();
+    model.record_issue_stats(1, 1, 1, 1, &scheduler);
+
+    let request = GmemRequest::new(0, 16, 0xF, true);
+    model
+        .issue_gmem_request(2, 0, request, &mut scheduler)
+        .expect("request should accept");
+    model.record_issue_stats(2, 1, 0, 0, &scheduler);
+    model.record_issue_stats(2, 1, 0, 0, &scheduler);
+    model.record_issue_stats(3, 1, 0, 0, &scheduler);
+
+    let stack = model.perf_summary().cpi_stack;
+    assert_eq!(stack.cycles, 4);
+    assert_eq!(stack.instructions, 1);
+    assert_eq!((stack.idle, stack.base, stack.gmem), (1, 1, 2));
+    let total: u64 = stack.components().iter().map(|(_, cycles)| cycles).sum();
+    assert_eq!(total, stack.cycles);
+    assert_eq!(
+        stack.format_line(),
+        "CPI 4.00 = base 1.00 + gmem 2.00 + idle 1.00"
+    );
+}
+
+#[test]
+fn exited_warp_drains_before_its_slot_frees() {
+    let mut scheduler = make_scheduler(2);
+    scheduler.spawn_single_warp();
+    let mut model = make_model(2);
+    model.suppress_exited_writebacks = true;
+
+    let now = module_now(&scheduler);
+    let request = GmemRequest::new(1, 16, 0xF, true);
+    model
+        .issue_gmem_request(now, 1, request, &mut scheduler)
+        .expect("request should accept");
+    model.notify_warp_exit(now, 1);
+    model.notify_warp_exit(now, 0);
+    assert!(model.warp_draining(1));
+    assert!(!model.warp_draining(0));
+    assert_eq!(model.warp_state(1).exited_at, Some(now));
+
+    let mut cycle = now;
+    while model.warp_draining(1) && cycle < now + 500 {
+        cycle += 1;
+        model.tick(cycle, &mut scheduler);
+    }
+    assert!(!model.warp_draining(1));
+
+    let exits = model.perf_summary().warp_exits;
+    assert_eq!(exits.exited_warps, 2);
+    assert_eq!(exits.draining_exits, 1);
+    assert_eq!(exits.residual_requests, 1);
+    assert_eq!(exits.suppressed_writebacks, 1);
+    assert_eq!(exits.max_drain_cycles, cycle - now);
+    assert_eq!(model.stats().writeback.issued, 0);
+}
+
+#[test]
+fn deep_copied_model_continues_request_ids() {
+    let run_one = |model: &mut CoreTimingModel, scheduler: &mut Scheduler, start: Cycle| {
+        model
+            .issue_gmem_request(start, 0, GmemRequest::new(0, 16, 0xF, true), scheduler)
+            .expect("request should accept");
+        let id = model.warp_state(0).gmem_requests[0];
+        let mut cycle = start;
+        while model.has_pending_gmem(0) && cycle < start + 500 {
+            cycle += 1;
+            model.tick(cycle, scheduler);
+        }
+        (id, cycle + 1)
+    };
+
+    let mut scheduler = make_scheduler(1);
+    scheduler.spawn_single_warp();
+    let mut straight = make_model(1);
+    let (_, next) = run_one(&mut straight, &mut scheduler, 0);
+    let (_, next) = run_one(&mut straight, &mut scheduler, next);
+
+    // The copy gets its own cluster gmem graph, so running `straight` on
+    // leaves it where it was.
+    let (mut resumed, mut scheduler_b) = deep_copy(|| (straight.clone(), scheduler.clone()));
+    let straight_run = run_one(&mut straight, &mut scheduler, next);
+    let resumed_run = run_one(&mut resumed, &mut scheduler_b, next);
+    assert_eq!(straight_run, resumed_run);
+    assert!(straight_run.0 > 2);
+}
+
+#[test]
+fn strict_inorder_allows_one_outstanding_access_per_warp() {
+    let mut cfg = CoreGraphConfig::default();
+    cfg.memory.lsu.strict_inorder = true;
+    let cluster_gmem = Shared::new(ClusterGmemGraph::new(cfg.memory.gmem.clone(), 1, 1));
+    let mut model = CoreTimingModel::new(cfg, 2, 0, 0, cluster_gmem, Arc::new(Logger::silent()));
+    let mut scheduler = make_scheduler(2);
+    scheduler.spawn_single_warp();
+
+    model
+        .issue_gmem_request(0, 0, GmemRequest::new(0, 16, 0xF, true), &mut scheduler)
+        .expect("first access should accept");
+    let retry_at = model
+        .issue_gmem_request(0, 0, GmemRequest::new(0, 16, 0xF, true), &mut scheduler)
+        .expect_err("second gmem access should wait");
+    assert!(retry_at > 0);
+    model
+        .issue_smem_request(0, 0, SmemRequest::new(0, 32, 0xF, false, 0), &mut scheduler)
+        .expect_err("smem access should wait behind the gmem one");
+    // Other warps are unaffected.
+    model
+        .issue_gmem_request(0, 1, GmemRequest::new(0, 16, 0xF, true), &mut scheduler)
+        .expect("other warp should accept");
+    assert_eq!(model.perf_summary().stall_summary.strict_inorder, 2);
+
+    let mut cycle = 0;
+    while model.has_pending_gmem(0) && cycle < 500 {
+        cycle += 1;
+        model.tick(cycle, &mut scheduler);
+    }
+    assert!(!model.has_pending_gmem(0));
+    model
+        .issue_gmem_request(
+            cycle + 1,
+            0,
+            GmemRequest::new(0, 16, 0xF, true),
+            &mut scheduler,
+        )
+        .expect("access should accept once the previous one completed");
+}
+
+#[test]
+fn dependent_load_parks_until_completion_wakes_it() {
+    let mut scheduler = make_scheduler(1);
+    scheduler.spawn_single_warp();
+    let mut model = make_model(1);
+
+    model
+        .issue_gmem_request(0, 0, GmemRequest::new(0, 16, 0xF, true), &mut scheduler)
+        .expect("first load should accept");
+    let retry_at = model
+        .issue_gmem_request(0, 0, GmemRequest::new(64, 16, 0xF, true), &mut scheduler)
+        .expect_err("second load should wait for the first");
+    assert_eq!(retry_at, Cycle::MAX);
+    assert!(model.is_parked(0));
+    assert_eq!(
+        scheduler.warp_state(0).resource_wait_until,
+        Some(Cycle::MAX)
+    );
+
+    let mut cycle = 0;
+    while model.has_pending_gmem(0) && cycle < 500 {
+        cycle += 1;
+        model.tick(cycle, &mut scheduler);
+        if model.has_pending_gmem(0) {
+            assert!(model.is_parked(0), "woken before the load completed");
+        }
+    }
+    assert!(!model.has_pending_gmem(0));
+    assert!(!model.is_parked(0));
+    assert_eq!(scheduler.warp_state(0).resource_wait_until, None);
+    let wakeups = model.perf_summary().wakeups;
+    assert_eq!((wakeups.parked, wakeups.woken), (1, 1));
+    assert!(wakeups.parked_cycles > 0);
+}
+
+#[test]
+fn fence_waits_only_for_requests_in_its_scope() {
+    use crate::timeflow::{FenceOrder, FenceScope};
+
+    let mut scheduler = make_scheduler(1);
+    scheduler.spawn_single_warp();
+    let mut model = make_model(1);
+
+    model
+        .issue_gmem_request(0, 0, GmemRequest::new(0, 16, 0xF, true), &mut scheduler)
+        .expect("load should accept");
+    model
+        .issue_fence(0, 0, FenceScope::Shared, FenceOrder::AcqRel, &mut scheduler)
+        .expect("smem fence should not wait for the gmem load");
+    let retry_at = model
+        .issue_fence(
+            0,
+            0,
+            FenceScope::Global,
+            FenceOrder::Release,
+            &mut scheduler,
+        )
+        .expect_err("gmem fence should wait for the load");
+    assert_eq!(retry_at, Cycle::MAX);
+    assert!(model.is_parked(0));
+
+    let mut cycle = 0;
+    while model.is_parked(0) && cycle < 500 {
+        cycle += 1;
+        model.tick(cycle, &mut scheduler);
+    }
+    assert!(!model.has_pending_gmem(0));
+    model
+        .issue_fence(
+            cycle,
+            0,
+            FenceScope::Global,
+            FenceOrder::Release,
+            &mut scheduler,
+        )
+        .expect("gmem fence should issue once the load completed");
+
+    let fences = model.perf_summary().fences;
+    assert_eq!((fences.shared.fences, fences.shared.drained), (1, 0));
+    assert_eq!((fences.global.fences, fences.global.drained), (1, 1));
+    assert_eq!(fences.global.release_only, 1);
+    assert!(fences.global.drain_cycles > 0);
+    assert_eq!(fences.all.fences, 0);
+}
+
+#[test]
+fn reservation_station_holds_dependent_op_off_the_ibuffer() {
+    let mut scheduler = make_scheduler(1);
+    scheduler.spawn_single_warp();
+    let mut exec_cfg = crate::timeflow::ExecutePipelineConfig::default();
+    exec_cfg.reservation_stations.int_div = 1;
+    exec_cfg.div_sqrt.units = 0;
+    let mut model = make_model_with_execute(1, exec_cfg);
+
+    let div = |rd: u8, rs1: u8| crate::muon::decode::DecodedInst {
+        opcode: Opcode::OP,
+        f3: 0b100,
+        f7: 0b0000001,
+        rd_addr: rd,
+        rs1_addr: rs1,
+        rs2_addr: 0,
+        ..Default::default()
+    };
+    let issued = |inst: &crate::muon::decode::DecodedInst| IssuedInst {
+        opcode: inst.opcode,
+        rd_addr: inst.rd_addr,
+        f3: inst.f3,
+        f7: inst.f7,
+        rs1_addr: inst.rs1_addr,
+        ..issued_int_op()
+    };
+
+    // The producer no longer holds the warp until its result is ready.
+    let producer = div(5, 1);
+    model
+        .wait_operands(0, 0, &producer, &mut scheduler)
+        .expect("producer has no pending operands");
+    let ticket = model
+        .issue_execute(0, 0, &issued(&producer), 32, &mut scheduler)
+        .expect("producer should issue without blocking");
+    assert!(ticket.ready_at() > 1);
+
+    // Its consumer waits in the station instead of the ibuffer.
+    let consumer = div(6, 5);
+    model
+        .wait_operands(1, 0, &consumer, &mut scheduler)
+        .expect("consumer should take the free entry");
+    model
+        .issue_execute(1, 0, &issued(&consumer), 32, &mut scheduler)
+        .expect("consumer should be accepted for later dispatch");
+
+    // An independent op behind it proceeds.
+    let independent = div(7, 2);
+    model
+        .wait_operands(2, 0, &independent, &mut scheduler)
+        .expect("independent op is not blocked");
+
+    // A second consumer finds the only entry taken and stalls.
+    let blocked = div(8, 5);
+    let wait_until = model
+        .wait_operands(2, 0, &blocked, &mut scheduler)
+        .expect_err("station is full");
+    assert_eq!(wait_until, ticket.ready_at());
+
+    let stats = model.perf_summary().reservation_stations;
+    assert_eq!(stats.allocations, 1);
+    assert_eq!(stats.avoided_wait_cycles, ticket.ready_at() - 1);
+    assert_eq!(stats.operand_stalls, 1);
+    assert_eq!(stats.full_stalls, 1);
+}
+
+#[test]
+fn divides_share_the_divider_and_a_full_warp_queue_stalls() {
+    let mut scheduler = make_scheduler(2);
+    scheduler.spawn_single_warp();
+    let mut exec_cfg = crate::timeflow::ExecutePipelineConfig::default();
+    exec_cfg.div_sqrt = crate::timeflow::DivSqrtConfig {
+        units: 1,
+        fp_div_cycles: 10,
+        queue_per_warp: 1,
+        ..Default::default()
+    };
+    // A station lets a warp move on with its divide still running.
+    exec_cfg.reservation_stations.div_sqrt = 2;
+    let mut model = make_model_with_execute(2, exec_cfg);
+    let fdiv = IssuedInst {
+        opcode: Opcode::OP_FP,
+        f7: 0b0001100,
+        ..issued_int_op()
+    };
+
+    let first = model
+        .issue_execute(0, 0, &fdiv, 32, &mut scheduler)
+        .expect("divider is free");
+    assert_eq!(first.ready_at(), 10);
+    let retry = model
+        .issue_execute(1, 0, &fdiv, 32, &mut scheduler)
+        .expect_err("warp 0 already has its one divide in flight");
+    assert_eq!(retry, 10);
+
+    // Another warp still gets in, behind the divide that holds the divider.
+    let other = model
+        .issue_execute(1, 1, &fdiv, 32, &mut scheduler)
+        .expect("warp 1 has room in its queue");
+    assert_eq!(other.ready_at(), 20);
+
+    let stats = model.perf_summary().div_sqrt;
+    assert_eq!(stats.fp_divs, 2);
+    assert_eq!(stats.queue_full_rejects, 1);
+    assert_eq!(stats.wait_cycles, 9);
+}
+
+#[test]
+fn forwarding_times_back_to_back_dependent_alu_ops() {
+    let alu_op = |rd: u8, rs1: u8| crate::muon::decode::DecodedInst {
+        opcode: Opcode::OP,
+        rd_addr: rd,
+        rs1_addr: rs1,
+        rs2_addr: 0,
+        ..Default::default()
+    };
+    let issued = |inst: &crate::muon::decode::DecodedInst| IssuedInst {
+        opcode: inst.opcode,
+        rd_addr: inst.rd_addr,
+        rs1_addr: inst.rs1_addr,
+        ..issued_int_op()
+    };
+    // Cycle the producer's result is ready, and the consumer's verdict then.
+    let run = |forwarding: crate::timeflow::ForwardingConfig| {
+        let mut scheduler = make_scheduler(1);
+        scheduler.spawn_single_warp();
+        let mut exec_cfg = crate::timeflow::ExecutePipelineConfig::default();
+        exec_cfg.forwarding = forwarding;
+        let mut model = make_model_with_execute(1, exec_cfg);
+
+        let producer = alu_op(5, 1);
+        model
+            .wait_operands(0, 0, &producer, &mut scheduler)
+            .expect("producer has no pending operands");
+        let done = model
+            .issue_execute(0, 0, &issued(&producer), 32, &mut scheduler)
+            .expect_err("producer holds its warp");
+        model.tick(done, &mut scheduler);
+        model
+            .wait_operands(done, 0, &producer, &mut scheduler)
+            .expect("a completing producer is not held by its own result");
+        model
+            .issue_execute(done, 0, &issued(&producer), 32, &mut scheduler)
+            .expect("producer completes");
+
+        let consumer = model.wait_operands(done, 0, &alu_op(6, 5), &mut scheduler);
+        (done, consumer, model.perf_summary().forwarding)
+    };
+
+    let (_, consumer, _) = run(Default::default());
+    // Without a forwarding model, results are read the cycle they are ready.
+    assert_eq!(consumer, Ok(()));
+
+    let bypassed = crate::timeflow::ForwardingConfig {
+        enabled: true,
+        ..Default::default()
+    };
+    let (_, consumer, stats) = run(bypassed.clone());
+    assert_eq!(consumer, Ok(()));
+    assert_eq!(stats.bypassed, 1);
+
+    let no_paths = crate::timeflow::ForwardingConfig {
+        paths: Vec::new(),
+        ..bypassed
+    };
+    let (done, consumer, stats) = run(no_paths);
+    // Without a path, the consumer waits for the writeback round trip.
+    assert_eq!(consumer, Err(done + 2));
+    assert_eq!(stats.writeback_stalls, 1);
+    assert_eq!(stats.writeback_stall_cycles, 2);
+}
+
+#[test]
+fn access_pattern_classifies_lane_addresses() {
+    let unit: Vec<u64> = (0..16).map(|lane| 0x1000 + lane * 4).collect();
+    let broadcast = vec![0x1000; 16];
+    let shuffled: Vec<u64> = (0..16).rev().map(|lane| 0x1000 + lane * 4).collect();
+    let strided: Vec<u64> = (0..16).map(|lane| 0x1000 + lane * 128).collect();
+    let scattered: Vec<u64> = (0..16).map(|lane| 0x1000 + (lane * lane) * 64).collect();
+    let classify = |lane_addrs: &[u64]| AccessPattern::classify(lane_addrs, 4, 64);
+    assert_eq!(classify(&unit), AccessPattern::Coalesced);
+    assert_eq!(classify(&broadcast), AccessPattern::Coalesced);
+    assert_eq!(classify(&shuffled), AccessPattern::Coalesced);
+    assert_eq!(classify(&[0x1000]), AccessPattern::Coalesced);
+    assert_eq!(classify(&strided), AccessPattern::Strided);
+    assert_eq!(classify(&scattered), AccessPattern::Scattered);
+}
+
+#[test]
+fn access_patterns_are_counted_per_pc() {
+    let mut scheduler = make_scheduler(1);
+    scheduler.spawn_single_warp();
+    let mut model = make_model(1);
+
+    let mut now = module_now(&scheduler);
+    let issues: [(u32, Vec<u64>); 3] = [
+        (0x100, (0..16).map(|lane| 0x4000 + lane * 4).collect()),
+        (0x100, (0..16).map(|lane| 0x4040 + lane * 4).collect()),
+        (0x200, (0..16).map(|lane| 0x8000 + lane * 256).collect()),
+    ];
+    for (pc, lane_addrs) in issues {
+        let mut request = GmemRequest::new(0, 64, 16, false);
+        request.pc = pc;
+        request.lane_addrs = Some(lane_addrs);
+        while model
+            .issue_gmem_request(now, 0, request.clone(), &mut scheduler)
+            .is_err()
+        {
+            now += 1;
+            model.tick(now, &mut scheduler);
+        }
+        now += 1;
+        model.tick(now, &mut scheduler);
+    }
+
+    let summary = model.perf_summary().access_patterns;
+    let unit = summary.get(0x100).expect("pc 0x100 issued");
+    assert_eq!((unit.coalesced, unit.strided, unit.scattered), (2, 0, 0));
+    let wide = summary.get(0x200).expect("pc 0x200 issued");
+    assert_eq!((wide.coalesced, wide.strided, wide.scattered), (0, 1, 0));
+    assert_eq!(wide.lines, 16);
+    let lines = summary.format_lines();
+    assert_eq!(lines.len(), 1);
+    assert!(lines[0].starts_with("pc 0x00000200 strided 1"), "{lines:?}");
+}
+
+#[test]
+fn smem_warp_summary_attributes_bank_conflicts() {
+    let mut scheduler = make_scheduler(1);
+    scheduler.spawn_single_warp();
+
+    let mut cfg = CoreGraphConfig::default();
+    cfg.memory.smem.num_banks = 4;
+    let word = cfg.memory.smem.word_bytes as u64;
+    let logger = Arc::new(Logger::silent());
+    let cluster_gmem = Shared::new(ClusterGmemGraph::new(cfg.memory.gmem.clone(), 1, 1));
+    let mut model = CoreTimingModel::new(cfg, 1, 0, 0, cluster_gmem, logger);
+    let now = module_now(&scheduler);
+    // Lanes 0 and 2 share bank 0; lane 1 has bank 1 to itself.
+    let mut request = SmemRequest::new(0, 12, 3, false, 0);
+    request.lane_addrs = Some(vec![0, word, 4 * word]);
+    model
+        .issue_smem_request(now, 0, request, &mut scheduler)
+        .expect("smem request should accept");
+
+    let mut cycle = now;
+    for _ in 0..200 {
+        model.tick(cycle, &mut scheduler);
+        if model.outstanding_smem() == 0 {
+            break;
+        }
+        cycle = cycle.saturating_add(1);
+    }
+    assert_eq!(model.outstanding_smem(), 0);
+
+    let summary = model.perf_summary().smem_warps;
+    let warp = summary.per_warp[0];
+    assert_eq!(warp.instructions, 1);
+    assert_eq!(warp.active_lanes, 3);
+    assert_eq!(warp.conflict_lanes, 1);
+    assert_eq!(warp.serialized_accesses, 1);
+    assert_eq!(summary.bank_accesses[0][0], 2);
+    assert_eq!(summary.bank_accesses[0][1], 1);
+    assert_eq!(summary.bank_conflicts[0][0], 1);
+    assert_eq!(summary.bank_conflicts[0][1], 0);
+}
+
+#[test]
+fn smem_conflicts_scale_with_warp_width() {
+    for lanes in [8u32, 32, 64] {
+        let mut cfg = CoreGraphConfig::default();
+        // more banks than a u64 mask holds
+        cfg.memory.smem.num_banks = 128;
+        cfg.memory.smem.num_subbanks = 2;
+        let word = cfg.memory.smem.word_bytes as u64;
+        let logger = Arc::new(Logger::silent());
+        let cluster_gmem = Shared::new(ClusterGmemGraph::new(cfg.memory.gmem.clone(), 1, 1));
+        let model = CoreTimingModel::new(cfg, 1, 0, 0, cluster_gmem, logger);
+        // Lane pairs share a bank, one lane of each pair a row further down.
+        let mut request = SmemRequest::new(0, 4 * lanes, lanes, false, 0);
+        request.lane_addrs = Some(
+            (0..lanes as u64)
+                .map(|lane| (lane / 2 + (lane % 2) * 128) * word)
+                .collect(),
+        );
+        let sample = model
+            .compute_smem_conflict(&request)
+            .expect("lane addresses give a sample");
+        assert_eq!(sample.active_lanes, lanes, "{lanes} lanes");
+        assert_eq!(sample.unique_banks, lanes / 2);
+        assert_eq!(sample.unique_subbanks, lanes);
+        assert_eq!(sample.conflict_lanes, lanes / 2);
+    }
+}
+
+fn make_model_with_timeout(timeout: crate::timeflow::RequestTimeoutConfig) -> CoreTimingModel {
+    let mut cfg = CoreGraphConfig::default();
+    cfg.memory.lsu.timeout = timeout;
+    let logger = Arc::new(Logger::silent());
+    let cluster_gmem = Shared::new(ClusterGmemGraph::new(cfg.memory.gmem.clone(), 1, 1));
+    CoreTimingModel::new(cfg, 1, 0, 0, cluster_gmem, logger)
+}
+
+#[test]
+fn timed_out_request_is_dropped_and_its_late_completion_ignored() {
+    let mut scheduler = make_scheduler(1);
+    scheduler.spawn_single_warp();
+
+    // Shorter than any trip to DRAM.
+    let mut model = make_model_with_timeout(crate::timeflow::RequestTimeoutConfig {
+        cycles: 3,
+        history: true,
+        ..Default::default()
+    });
+    let now = module_now(&scheduler);
+    let request = GmemRequest::new(0, 16, 0xF, true);
+    model
+        .issue_gmem_request(now, 0, request, &mut scheduler)
+        .expect("request should accept");
+
+    let mut cycle = now;
+    for _ in 0..500 {
+        model.tick(cycle, &mut scheduler);
+        if model.perf_summary().request_timeouts.late_completions > 0 {
+            break;
+        }
+        cycle = cycle.saturating_add(1);
+    }
+
+    let stats = model.perf_summary().request_timeouts;
+    assert_eq!((stats.timed_out, stats.dropped), (1, 1));
+    assert_eq!(stats.late_completions, 1);
+    assert!(!model.has_pending_gmem(0));
+    assert_eq!(model.latencies.gmem_count, 0);
+}
+
+#[test]
+#[should_panic(expected = "request timed out")]
+fn timed_out_request_can_stop_the_run() {
+    let mut scheduler = make_scheduler(1);
+    scheduler.spawn_single_warp();
+
+    let mut model = make_model_with_timeout(crate::timeflow::RequestTimeoutConfig {
+        cycles: 3,
+        action: crate::timeflow::TimeoutAction::Error,
+        ..Default::default()
+    });
+    let now = module_now(&scheduler);
+    let request = SmemRequest::new(0, 32, 4, false, 0);
+    model
+        .issue_smem_request(now, 0, request, &mut scheduler)
+        .expect("smem request should accept");
+    for cycle in now..now + 500 {
+        model.tick(cycle, &mut scheduler);
+    }
+}
+
+#[test]
+fn dependent_request_waits_for_another_warps_request() {
+    use crate::timeflow::{RequestKind, RequestRef};
+
+    let mut scheduler = make_scheduler(2);
+    let threads = vec![vec![(0, 0, 0)], vec![(0, 0, 1)]];
+    scheduler.spawn_n_warps(0x8000_0000, &threads);
+    let mut model = make_model(2);
+
+    let now = module_now(&scheduler);
+    let fill = model.reserve_request(RequestKind::Gmem);
+    let consumer = model.reserve_request(RequestKind::Gmem);
+    model
+        .add_request_dependency(consumer, fill)
+        .expect("no cycle");
+    assert!(model.request_blocked(consumer));
+
+    let mut load = GmemRequest::new(0, 16, 0xF, true);
+    load.id = consumer.id;
+    let retry_at = model
+        .issue_gmem_request(now, 1, load.clone(), &mut scheduler)
+        .expect_err("consumer should wait for the fill");
+    assert_eq!(retry_at, Cycle::MAX);
+    assert!(model.is_parked(1));
+
+    let mut store = GmemRequest::new(0, 16, 0xF, false);
+    store.id = fill.id;
+    model
+        .issue_gmem_request(now, 0, store, &mut scheduler)
+        .expect("fill should accept");
+
+    let mut cycle = now;
+    while model.is_parked(1) {
+        assert!(model.gmem_issue_cycle.contains_key(&fill.id), "woken early");
+        cycle += 1;
+        assert!(cycle < now + 500, "consumer never released");
+        model.tick(cycle, &mut scheduler);
+    }
+    model
+        .issue_gmem_request(cycle, 1, load, &mut scheduler)
+        .expect("consumer should issue once the fill completed");
+
+    let stats = model.perf_summary().dependencies;
+    assert_eq!(
+        (stats.declared, stats.released, stats.blocked_issues),
+        (1, 1, 1)
+    );
+    assert_eq!(
+        model.add_request_dependency(RequestRef::gmem(99), fill),
+        Ok(())
+    );
+    assert!(!model.request_blocked(RequestRef::gmem(99)));
+}
+
+#[test]
+fn tensor_job_issues_after_the_dma_it_depends_on() {
+    use crate::timeflow::RequestKind;
+
+    let mut scheduler = make_scheduler(1);
+    scheduler.spawn_single_warp();
+
+    let mut cfg = CoreGraphConfig::default();
+    cfg.io.dma.enabled = true;
+    cfg.io.dma.queue.base_latency = 8;
+    cfg.compute.tensor.enabled = true;
+    cfg.compute.tensor.queue.base_latency = 1;
+    let logger = Arc::new(Logger::silent());
+    let cluster_gmem = Shared::new(ClusterGmemGraph::new(cfg.memory.gmem.clone(), 1, 1));
+    let mut model = CoreTimingModel::new(cfg, 1, 0, 0, cluster_gmem, logger);
+
+    let now = module_now(&scheduler);
+    let dma = model.reserve_request(RequestKind::Dma);
+    let tensor = model.reserve_request(RequestKind::Tensor);
+    model.add_request_dependency(tensor, dma).expect("no cycle");
+    model.submit_tensor(now, tensor.id, 64);
+    model.submit_dma(now, dma.id, 64);
+
+    let mut cycle = now;
+    while model.graph.dma_completed() == 0 {
+        assert_eq!(model.graph.tensor_bytes_issued(), 0, "tensor issued early");
+        model.tick(cycle, &mut scheduler);
+        cycle += 1;
+        assert!(cycle < now + 100, "dma never completed");
+    }
+    while model.graph.tensor_completed() == 0 {
+        model.tick(cycle, &mut scheduler);
+        cycle += 1;
+        assert!(cycle < now + 200, "tensor never completed");
+    }
+    assert!(!model.request_blocked(tensor));
+    assert_eq!(model.perf_summary().dependencies.blocked_issues, 1);
+}
+
+#[test]
+fn execute_result_holds_its_warp_until_written_back() {
+    let mut scheduler = make_scheduler(1);
+    scheduler.spawn_single_warp();
+
+    let mut cfg = CoreGraphConfig::default();
+    cfg.compute.execute.alu.base_latency = 2;
+    cfg.memory.writeback.enabled = true;
+    cfg.memory.writeback.execute_results = true;
+    cfg.memory.writeback.queue.base_latency = 3;
+    let logger = Arc::new(Logger::silent());
+    let cluster_gmem = Shared::new(ClusterGmemGraph::new(cfg.memory.gmem.clone(), 1, 1));
+    let mut model = CoreTimingModel::new(cfg, 1, 0, 0, cluster_gmem, logger);
+    let inst = issued_int_op();
+
+    let now = module_now(&scheduler);
+    let retry_at = model
+        .issue_execute(now, 0, &inst, 32, &mut scheduler)
+        .expect_err("result should go through writeback");
+    assert_eq!(retry_at, Cycle::MAX);
+
+    let mut cycle = now;
+    while scheduler.warp_state(0).resource_wait_until.is_some() {
+        cycle += 1;
+        assert!(cycle < now + 50, "result never left writeback");
+        model.tick(cycle, &mut scheduler);
+    }
+    // ALU latency, then the writeback queue's.
+    assert!(cycle >= now + 2 + 3);
+    model
+        .issue_execute(cycle, 0, &inst, 32, &mut scheduler)
+        .expect("instruction should retire once written back");
+
+    let execute = model.perf_summary().writeback_stats.classes.execute;
+    assert_eq!(execute.admitted, 1);
+}
+
+/// Issues only the highest-numbered ready warp, and asks for warp 2 as
+/// well whether or not it is ready.
+struct LastReadyWarp;
+
+impl IssuePolicy for LastReadyWarp {
+    fn select(&mut self, decision: &SchedDecision) -> Option<Vec<bool>> {
+        let last = decision.warps.iter().rposition(|warp| warp.ready)?;
+        let mut grants = vec![false; decision.warps.len()];
+        grants[last] = true;
+        grants[2] = true;
+        Some(grants)
+    }
+}
+
+#[test]
+fn issue_policy_overrides_the_scheduler_and_is_logged() {
+    let mut scheduler = make_scheduler(3);
+    scheduler.spawn_single_warp();
+    let mut model = make_model(3);
+    model.set_sched_log(true);
+    let eligible = [true, true, false];
+
+    model.set_issue_policy(Some(Arc::new(std::sync::Mutex::new(LastReadyWarp))));
+    assert_eq!(
+        model.select_issue_mask(0, &eligible, &scheduler),
+        [false, true, false]
+    );
+    model.set_issue_policy(None);
+    assert_eq!(model.select_issue_mask(1, &eligible, &scheduler), eligible);
+
+    let log = model.take_sched_log();
+    assert_eq!(log.len(), 2);
+    assert_eq!(log[0].cycle, 0);
+    assert_eq!(log[0].chosen().collect::<Vec<_>>(), [1]);
+    assert_eq!(log[0].alternatives().collect::<Vec<_>>(), [0]);
+    assert!(log[0].warps[0].active && !log[0].warps[1].active);
+    assert_eq!(log[1].chosen().collect::<Vec<_>>(), [0, 1]);
+    assert!(model.take_sched_log().is_empty());
+}
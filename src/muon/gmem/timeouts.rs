@@ -0,0 +1,140 @@
+use crate::muon::scheduler::Scheduler;
+use crate::timeflow::{FenceRequest, RequestRef, RequestSpace, TimedOutRequest, TimeoutAction};
+use crate::timeq::Cycle;
+
+use super::CoreTimingModel;
+
+impl CoreTimingModel {
+    /// Appends an event to a request's `[lsu.timeout]` history. `event` is
+    /// only formatted when history is on.
+    pub(super) fn note_request(
+        &mut self,
+        now: Cycle,
+        space: RequestSpace,
+        id: u64,
+        event: impl FnOnce() -> String,
+    ) {
+        if self.timeouts.tracks_history() {
+            self.timeouts.note(now, space, id, event());
+        }
+    }
+
+    /// Reports every request that has been in flight longer than
+    /// `[lsu.timeout]` allows, then drops it or stops the run.
+    pub(super) fn check_request_timeouts(&mut self, now: Cycle, scheduler: &mut Scheduler) {
+        if !self.timeouts.enabled() {
+            return;
+        }
+        let gmem = self
+            .gmem_issue_cycle
+            .iter()
+            .map(|(&id, &issued_at)| (issued_at, RequestSpace::Gmem, id));
+        let smem = self
+            .smem_issue_cycle
+            .iter()
+            .map(|(&id, &issued_at)| (issued_at, RequestSpace::Smem, id));
+        let mut expired: Vec<_> = gmem
+            .chain(smem)
+            .filter(|&(issued_at, _, _)| self.timeouts.expired(now, issued_at))
+            .collect();
+        if expired.is_empty() {
+            return;
+        }
+        expired.sort_unstable();
+
+        for (issued_at, space, id) in expired {
+            let pending = match space {
+                RequestSpace::Gmem => &self.pending_gmem,
+                RequestSpace::Smem => &self.pending_smem,
+            };
+            let Some((warp, parts_outstanding)) =
+                pending.iter().enumerate().find_map(|(warp, queue)| {
+                    let parts = queue.iter().filter(|(pending, _)| *pending == id).count();
+                    (parts > 0).then_some((warp, parts))
+                })
+            else {
+                // Nothing waits on it any more.
+                let request = match space {
+                    RequestSpace::Gmem => {
+                        self.gmem_issue_cycle.remove(&id);
+                        RequestRef::gmem(id)
+                    }
+                    RequestSpace::Smem => {
+                        self.smem_issue_cycle.remove(&id);
+                        RequestRef::smem(id)
+                    }
+                };
+                self.release_request(now, request);
+                continue;
+            };
+            let report = TimedOutRequest {
+                space,
+                id,
+                warp,
+                core_id: self.core_id,
+                issued_at,
+                detected_at: now,
+                parts_outstanding,
+                history: self.timeouts.expire(space, id),
+            };
+            match self.timeouts.action() {
+                TimeoutAction::Error => panic!("request timed out: {report}"),
+                TimeoutAction::Drop => {
+                    log::warn!("request timed out and dropped: {report}");
+                    self.drop_request(now, space, id, warp, scheduler);
+                }
+            }
+        }
+    }
+
+    /// Forgets a timed-out request as if it had completed, so its warp
+    /// stops waiting on it. LSU entries it still holds stay taken.
+    fn drop_request(
+        &mut self,
+        now: Cycle,
+        space: RequestSpace,
+        id: u64,
+        warp: usize,
+        scheduler: &mut Scheduler,
+    ) {
+        match space {
+            RequestSpace::Gmem => {
+                while self.remove_gmem_pending(warp, id, scheduler) {}
+                self.gmem_issue_cycle.remove(&id);
+                for _ in 0..self.gmem_misses.remove(&id).unwrap_or(0) {
+                    self.graph.lsu_release_miss(warp);
+                }
+                // A dropped flush still has to release the fence behind it.
+                self.inflight_flushes.retain(|&(flush, _)| flush != id);
+                let fence = self.flush_fences.remove(&id).or_else(|| {
+                    (self.fence_inflight.get(warp).copied().flatten() == Some(id))
+                        .then(|| FenceRequest::new(warp, id))
+                });
+                if let Some(fence) = fence {
+                    self.enqueue_fence(now, fence);
+                }
+            }
+            RequestSpace::Smem => {
+                while self.remove_smem_pending(warp, id, scheduler) {}
+                self.smem_issue_cycle.remove(&id);
+                self.smem_first_completion.remove(&id);
+            }
+        }
+        self.timeouts.record_drop(space, id);
+        let request = match space {
+            RequestSpace::Gmem => RequestRef::gmem(id),
+            RequestSpace::Smem => RequestRef::smem(id),
+        };
+        self.release_request(now, request);
+        let (gmem_drained, smem_drained) =
+            (!self.has_pending_gmem(warp), !self.has_pending_smem(warp));
+        self.wakeups.notify(now, warp, gmem_drained, smem_drained);
+        self.update_warp_drain(now, warp);
+        let event = match space {
+            RequestSpace::Gmem => "gmem_timeout",
+            RequestSpace::Smem => "smem_timeout",
+        };
+        self.trace_event(now, event, warp, Some(id), 0, Some("dropped"));
+        self.timeouts.forget(space, id);
+    }
+}
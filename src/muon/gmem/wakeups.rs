@@ -0,0 +1,118 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use crate::timeflow::{FenceScope, RequestRef};
+use crate::timeq::Cycle;
+
+use super::WakeupSummary;
+
+/// What a parked warp waits for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum WakeOn {
+    /// Its last outstanding request in the scope completing.
+    Drained(FenceScope),
+    /// Any of its gmem or smem requests completing.
+    AnyCompletion,
+    /// The request it is issuing having no dependencies left.
+    Dependency(RequestRef),
+}
+
+/// Warps parked until one of their memory requests completes, and the
+/// wakeups those completions scheduled, ordered by cycle. A parked warp
+/// holds a scheduler wait with no deadline; only a delivered wakeup lifts
+/// it, so the warp does not retry its instruction while it waits.
+#[derive(Clone)]
+pub(super) struct CompletionWakeups {
+    parked: Vec<Option<(WakeOn, Cycle)>>,
+    due: BinaryHeap<Reverse<(Cycle, usize)>>,
+    stats: WakeupSummary,
+}
+
+impl CompletionWakeups {
+    pub(super) fn new(num_warps: usize) -> Self {
+        Self {
+            parked: vec![None; num_warps],
+            due: BinaryHeap::new(),
+            stats: WakeupSummary::default(),
+        }
+    }
+
+    pub(super) fn park(&mut self, now: Cycle, warp: usize, on: WakeOn) {
+        let Some(slot) = self.parked.get_mut(warp) else {
+            return;
+        };
+        if slot.is_none() {
+            self.stats.parked = self.stats.parked.saturating_add(1);
+        }
+        *slot = Some((on, now));
+    }
+
+    pub(super) fn is_parked(&self, warp: usize) -> bool {
+        self.parked.get(warp).is_some_and(Option::is_some)
+    }
+
+    /// A request of `warp` completed at `now`; `gmem_drained` and
+    /// `smem_drained` say whether it has requests of either kind left.
+    /// Schedules its wakeup if that is what it was parked for.
+    pub(super) fn notify(
+        &mut self,
+        now: Cycle,
+        warp: usize,
+        gmem_drained: bool,
+        smem_drained: bool,
+    ) {
+        let Some(slot) = self.parked.get_mut(warp) else {
+            return;
+        };
+        let wake = match *slot {
+            Some((WakeOn::Drained(scope), _)) => {
+                (gmem_drained || !scope.orders_global()) && (smem_drained || !scope.orders_shared())
+            }
+            Some((WakeOn::AnyCompletion, _)) => true,
+            Some((WakeOn::Dependency(_), _)) | None => false,
+        };
+        if wake {
+            self.wake(now, warp);
+        }
+    }
+
+    /// `request` has no dependencies left; wakes the warps parked issuing it.
+    pub(super) fn release(&mut self, now: Cycle, request: RequestRef) {
+        for warp in 0..self.parked.len() {
+            if matches!(self.parked[warp], Some((WakeOn::Dependency(r), _)) if r == request) {
+                self.wake(now, warp);
+            }
+        }
+    }
+
+    fn wake(&mut self, now: Cycle, warp: usize) {
+        let (_, parked_at) = self.parked[warp].take().expect("parked warp");
+        self.stats.parked_cycles = self
+            .stats
+            .parked_cycles
+            .saturating_add(now.saturating_sub(parked_at));
+        self.due.push(Reverse((now, warp)));
+    }
+
+    /// Warps whose wakeups are due by `now`, earliest first.
+    pub(super) fn take_due(&mut self, now: Cycle) -> Vec<usize> {
+        let mut woken = Vec::new();
+        while let Some(Reverse((at, warp))) = self.due.peek().copied() {
+            if at > now {
+                break;
+            }
+            self.due.pop();
+            woken.push(warp);
+        }
+        self.stats.woken = self.stats.woken.saturating_add(woken.len() as u64);
+        woken
+    }
+
+    pub(super) fn stats(&self) -> WakeupSummary {
+        self.stats
+    }
+
+    pub(super) fn clear_stats(&mut self) {
+        self.stats = WakeupSummary::default();
+    }
+}
@@ -0,0 +1,195 @@
+use std::fmt;
+use std::ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, Not};
+
+use bitvec::prelude::*;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Widest warp the model supports.
+pub const MAX_LANES: usize = 64;
+
+/// One bit per lane of a warp, lane 0 in the least significant bit.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct LaneMask(BitArr!(for MAX_LANES, in u32, Lsb0));
+
+impl LaneMask {
+    pub const fn empty() -> Self {
+        Self(BitArray::ZERO)
+    }
+
+    /// Lanes `0..num_lanes` set.
+    pub fn all(num_lanes: usize) -> Self {
+        assert!(num_lanes <= MAX_LANES, "warp of {num_lanes} lanes too wide");
+        let mut mask = Self::empty();
+        mask.0[..num_lanes].fill(true);
+        mask
+    }
+
+    pub fn from_u64(bits: u64) -> Self {
+        let mut mask = Self::empty();
+        mask.0.store_le(bits);
+        mask
+    }
+
+    /// A mask read from a 32-bit register. It covers lanes 0 to 31, except
+    /// that `-1` turns on every lane, so a wide warp can still be fully
+    /// enabled.
+    pub fn from_reg(value: u32) -> Self {
+        if value == u32::MAX {
+            return Self::from_u64(u64::MAX);
+        }
+        Self::from_u64(value.into())
+    }
+
+    pub fn to_u64(&self) -> u64 {
+        self.0.load_le()
+    }
+
+    /// Lanes 0 to 31, as the tmask CSR and the RTL see them.
+    pub fn low_u32(&self) -> u32 {
+        self.0[..32].load_le()
+    }
+
+    pub fn bit(&self, lane: usize) -> bool {
+        self.0[lane]
+    }
+
+    pub fn set(&mut self, lane: usize, on: bool) {
+        self.0.set(lane, on);
+    }
+
+    pub fn count_ones(&self) -> u32 {
+        self.0.count_ones() as u32
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.not_any()
+    }
+
+    pub fn first_lane(&self) -> Option<usize> {
+        self.0.first_one()
+    }
+
+    pub fn lanes(&self) -> impl Iterator<Item = usize> + '_ {
+        self.0.iter_ones()
+    }
+}
+
+impl FromIterator<bool> for LaneMask {
+    fn from_iter<I: IntoIterator<Item = bool>>(iter: I) -> Self {
+        let mut mask = Self::empty();
+        for (lane, on) in iter.into_iter().enumerate() {
+            mask.set(lane, on);
+        }
+        mask
+    }
+}
+
+impl BitAnd for LaneMask {
+    type Output = Self;
+
+    fn bitand(self, rhs: Self) -> Self {
+        Self(self.0 & rhs.0)
+    }
+}
+
+impl BitOr for LaneMask {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl BitAndAssign for LaneMask {
+    fn bitand_assign(&mut self, rhs: Self) {
+        self.0 &= rhs.0;
+    }
+}
+
+impl BitOrAssign for LaneMask {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+impl Not for LaneMask {
+    type Output = Self;
+
+    fn not(self) -> Self {
+        Self(!self.0)
+    }
+}
+
+impl fmt::Debug for LaneMask {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#x}", self.to_u64())
+    }
+}
+
+impl fmt::LowerHex for LaneMask {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::LowerHex::fmt(&self.to_u64(), f)
+    }
+}
+
+impl fmt::Binary for LaneMask {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Binary::fmt(&self.to_u64(), f)
+    }
+}
+
+impl Serialize for LaneMask {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.to_u64().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for LaneMask {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        u64::deserialize(deserializer).map(Self::from_u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn all_lanes_at_8_32_64() {
+        for lanes in [8, 32, 64] {
+            let mask = LaneMask::all(lanes);
+            assert_eq!(mask.count_ones(), lanes as u32);
+            assert_eq!(mask.lanes().last(), Some(lanes - 1));
+            assert_eq!(mask.first_lane(), Some(0));
+        }
+        assert_eq!(LaneMask::all(8).to_u64(), 0xff);
+        assert_eq!(LaneMask::all(32).low_u32(), u32::MAX);
+        assert_eq!(LaneMask::all(64).to_u64(), u64::MAX);
+    }
+
+    #[test]
+    fn register_values_select_low_lanes_or_all() {
+        assert_eq!(LaneMask::from_reg(u32::MAX), LaneMask::all(64));
+        let lane_31 = LaneMask::from_reg(0x8000_0000);
+        assert_eq!(lane_31.lanes().collect::<Vec<_>>(), vec![31]);
+        assert_eq!(LaneMask::from_reg(0x7fff_ffff), LaneMask::all(31));
+        assert_eq!(LaneMask::from_reg(0xf0).to_u64(), 0xf0);
+        assert_eq!(
+            LaneMask::from_reg(u32::MAX) & LaneMask::all(8),
+            LaneMask::all(8)
+        );
+    }
+
+    #[test]
+    fn ops_reach_the_upper_lanes() {
+        let upper: LaneMask = (0..64).map(|lane| lane >= 40).collect();
+        assert_eq!(upper.first_lane(), Some(40));
+        assert_eq!(upper.low_u32(), 0);
+        assert_eq!((upper | !upper), LaneMask::all(64));
+        assert!((upper & !upper).is_empty());
+        let mut mask = LaneMask::empty();
+        mask.set(63, true);
+        mask |= LaneMask::all(1);
+        assert_eq!(mask.to_u64(), 0x8000_0000_0000_0001);
+    }
+}
@@ -5,5 +5,7 @@ pub mod execute;
 pub mod config;
 pub mod csr;
 pub mod gmem;
+pub mod lane_mask;
 pub mod scheduler;
+pub mod trap;
 pub mod warp;
@@ -3,34 +3,36 @@ use crate::base::module::{module, IsModule, ModuleBase};
 use crate::muon::config::MuonConfig;
 use crate::muon::decode::IssuedInst;
 use crate::muon::execute::SFUType;
+use crate::muon::lane_mask::LaneMask;
 use crate::timeq::Cycle;
 use crate::utils::{BitMask, BitSlice};
 use log::info;
+use serde::Serialize;
 use std::collections::VecDeque;
 use std::iter::{once, repeat};
 use std::sync::Arc;
 
-#[derive(Debug, Default)]
+#[derive(Debug, Clone, Default)]
 pub struct IpdomEntry {
     pub pc: u32,
-    pub tmask: u32,
+    pub tmask: LaneMask,
 }
 
 #[derive(Debug, Default, Clone, Copy)]
 pub struct IpdomPush {
-    pub restored_mask: u32,
-    pub else_mask: u32,
+    pub restored_mask: LaneMask,
+    pub else_mask: LaneMask,
     pub else_pc: u32,
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Clone, Default)]
 pub struct SchedulerState {
     /// differentiates between the initial and the final state, in both of which no active warps
     /// exist
     pub started: bool,
     pub active_warps: u32,
     pub stalled_warps: u32,
-    pub thread_masks: Vec<u32>,
+    pub thread_masks: Vec<LaneMask>,
     pub tohost: Option<u32>,
     pc: Vec<u32>,
     ipdom_stack: Vec<VecDeque<IpdomEntry>>,
@@ -43,21 +45,34 @@ pub struct SchedulerState {
 pub struct Schedule {
     pub pc: u32,
     pub warp: usize,
-    pub mask: u32,
+    pub mask: LaneMask,
     pub active_warps: u32,
 }
 
+/// Scheduler-side view of one warp, as reported in state dumps.
+#[derive(Debug, Clone, Serialize)]
+pub struct WarpScheduleState {
+    pub pc: u32,
+    pub tmask: LaneMask,
+    pub active: bool,
+    pub stalled: bool,
+    /// Held by Neutrino, e.g. waiting at a barrier.
+    pub barrier_wait: bool,
+    pub resource_wait_until: Option<Cycle>,
+    pub ipdom_depth: usize,
+}
+
 #[derive(Debug, Default, Clone, Copy)]
 pub struct SchedulerWriteback {
     pub pc: Option<u32>,
-    pub tmask: Option<u32>,
+    pub tmask: Option<LaneMask>,
     pub wspawn_pc_count: Option<(u32, u32)>,
     pub tohost: Option<u32>,
     pub ipdom_push: Option<IpdomPush>,
 }
 
 // instantiated per core
-#[derive(Debug, Default)]
+#[derive(Debug, Clone, Default)]
 pub struct Scheduler {
     base: ModuleBase<SchedulerState, MuonConfig>,
     cid: usize,
@@ -74,7 +89,7 @@ impl Scheduler {
                     started: false,
                     active_warps: 0,
                     stalled_warps: 0,
-                    thread_masks: vec![0u32; num_warps],
+                    thread_masks: vec![LaneMask::empty(); num_warps],
                     tohost: None,
                     pc: vec![config.start_pc; num_warps],
                     ipdom_stack: (0..num_warps).map(|_| VecDeque::new()).collect(),
@@ -89,13 +104,8 @@ impl Scheduler {
         me
     }
 
-    fn all_one_bitmask(&self) -> u32 {
-        // hack: left shifting by 32 undefined for u32. ideally use u64 everywhere
-        if self.conf().num_lanes >= 32 {
-            u32::MAX
-        } else {
-            (1u32 << self.conf().num_lanes) - 1
-        }
+    fn all_one_bitmask(&self) -> LaneMask {
+        LaneMask::all(self.conf().num_lanes)
     }
 
     pub fn spawn_single_warp(&mut self) {
@@ -109,8 +119,8 @@ impl Scheduler {
             .last()
             .expect("last warp is empty")
             .iter()
-            .enumerate()
-            .fold(0, |mask, (i, _)| mask | 1 << i);
+            .map(|_| true)
+            .collect::<LaneMask>();
         let num_warps = thread_idxs.len();
         let disabled_warps = self.conf().num_warps - num_warps;
         assert!(disabled_warps <= self.conf().num_warps);
@@ -119,7 +129,7 @@ impl Scheduler {
         self.state_mut().thread_masks = repeat(self.all_one_bitmask())
             .take(num_warps - 1)
             .chain(once(last_warp_mask))
-            .chain(repeat(0).take(disabled_warps))
+            .chain(repeat(LaneMask::empty()).take(disabled_warps))
             .collect();
         self.state_mut().pc = [pc].repeat(num_warps);
         self.base.state.active_warps = num_warps as u32;
@@ -129,7 +139,7 @@ impl Scheduler {
         self.base.state.pc[wid] = target_pc;
         if target_pc == 0 {
             // returned from main
-            self.base.state.thread_masks[wid] = 0;
+            self.base.state.thread_masks[wid] = LaneMask::empty();
             self.base.state.active_warps.mut_bit(wid, false);
         }
         SchedulerWriteback {
@@ -156,10 +166,10 @@ impl Scheduler {
         );
         match sfu {
             SFUType::TMC => {
-                let tmask = rs1[first_lid] & self.all_one_bitmask();
+                let tmask = LaneMask::from_reg(rs1[first_lid]) & self.all_one_bitmask();
                 info!("tmc value {:x}", tmask);
                 self.base.state.thread_masks[wid] = tmask;
-                if tmask == 0 {
+                if tmask.is_empty() {
                     self.base.state.active_warps.mut_bit(wid, false);
                 }
                 SchedulerWriteback {
@@ -190,9 +200,9 @@ impl Scheduler {
                 // deviation from vortex behavior: instead of checking for 1,
                 // check for nonzero. works with renaming front end
                 let invert = decoded_inst.rs2_addr != 0;
-                let then_mask = rs1.iter().map(|r| r.bit(0)).collect::<Vec<_>>().to_u32()
+                let then_mask = rs1.iter().map(|r| r.bit(0)).collect::<LaneMask>()
                     & self.state().thread_masks[wid];
-                let else_mask = rs1.iter().map(|r| !r.bit(0)).collect::<Vec<_>>().to_u32()
+                let else_mask = rs1.iter().map(|r| !r.bit(0)).collect::<LaneMask>()
                     & self.state().thread_masks[wid];
 
                 info!(
@@ -213,7 +223,7 @@ impl Scheduler {
                     tmask: then_mask | else_mask,
                 });
 
-                let diverges = (then_mask != 0) && (else_mask != 0);
+                let diverges = !then_mask.is_empty() && !else_mask.is_empty();
                 let now_mask = if invert { else_mask } else { then_mask };
                 let later_mask = if invert { then_mask } else { else_mask };
 
@@ -254,18 +264,14 @@ impl Scheduler {
             }
             SFUType::PRED => {
                 let invert = decoded_inst.rd_addr != 0;
-                let pred_mask = rs1
-                    .iter()
-                    .map(|r| r.bit(0) ^ invert)
-                    .collect::<Vec<_>>()
-                    .to_u32();
+                let pred_mask = rs1.iter().map(|r| r.bit(0) ^ invert).collect::<LaneMask>();
                 self.base.state.thread_masks[wid] &= pred_mask;
 
                 // if all threads are not active, set thread mask to rs2 of warp leader
-                if self.base.state.thread_masks[wid] == 0 {
-                    let tmask = rs2[first_lid];
+                if self.base.state.thread_masks[wid].is_empty() {
+                    let tmask = LaneMask::from_reg(rs2[first_lid]) & self.all_one_bitmask();
                     self.base.state.thread_masks[wid] = tmask;
-                    if tmask == 0 {
+                    if tmask.is_empty() {
                         self.base.state.active_warps.mut_bit(wid, false);
                     }
                 }
@@ -275,17 +281,17 @@ impl Scheduler {
                 }
             }
             SFUType::KILL => {
-                self.base.state.thread_masks[wid] = 0;
+                self.base.state.thread_masks[wid] = LaneMask::empty();
                 self.base.state.active_warps.mut_bit(wid, false);
                 SchedulerWriteback {
-                    tmask: Some(0),
+                    tmask: Some(LaneMask::empty()),
                     ..SchedulerWriteback::default()
                 }
             }
             SFUType::ECALL => {
                 let a0 = rs1[first_lid];
                 self.base.state.tohost = Some(a0);
-                self.base.state.thread_masks[wid] = 0;
+                self.base.state.thread_masks[wid] = LaneMask::empty();
                 self.base.state.active_warps.mut_bit(wid, false);
                 SchedulerWriteback {
                     tohost: Some(a0),
@@ -349,6 +355,28 @@ impl Scheduler {
         self.base.state.stalled_warps
     }
 
+    /// Warps held by Neutrino, e.g. waiting at a barrier.
+    pub fn barrier_wait_mask(&self) -> u32 {
+        self.base.state.neutrino_stalled_mask
+    }
+
+    pub fn pc(&self, wid: usize) -> u32 {
+        self.base.state.pc[wid]
+    }
+
+    pub fn warp_state(&self, wid: usize) -> WarpScheduleState {
+        let state = &self.base.state;
+        WarpScheduleState {
+            pc: state.pc[wid],
+            tmask: state.thread_masks[wid],
+            active: state.active_warps.bit(wid),
+            stalled: state.stalled_warps.bit(wid),
+            barrier_wait: state.neutrino_stalled_mask.bit(wid),
+            resource_wait_until: state.resource_wait_until[wid],
+            ipdom_depth: state.ipdom_stack[wid].len(),
+        }
+    }
+
     fn resource_wait_mask(&self) -> u32 {
         self.base
             .state
@@ -396,3 +424,106 @@ impl ModuleBehaviors for Scheduler {
         self.base.state.resource_wait_until = vec![None; self.conf().num_warps];
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::muon::execute::{ExecuteUnit, Opcode};
+
+    fn scheduler(num_lanes: usize) -> Scheduler {
+        let mut config = MuonConfig::default();
+        config.num_lanes = num_lanes;
+        config.num_warps = 2;
+        Scheduler::new(Arc::new(config), 0)
+    }
+
+    fn sfu_inst(num_lanes: usize) -> IssuedInst {
+        IssuedInst {
+            opcode: Opcode::CUSTOM0,
+            opext: 0,
+            rd_addr: 0,
+            f3: 0,
+            rs1_addr: 1,
+            rs2_addr: 0,
+            rs3_addr: 0,
+            rs4_addr: 0,
+            rs1_data: vec![None; num_lanes],
+            rs2_data: vec![None; num_lanes],
+            rs3_data: vec![None; num_lanes],
+            rs4_data: vec![None; num_lanes],
+            f7: 0,
+            imm32: 0,
+            imm24: 0,
+            csr_imm: 0,
+            pc: 0x100,
+            raw: 0,
+        }
+    }
+
+    #[test]
+    fn tmc_all_ones_enables_every_lane() {
+        for lanes in [8, 32, 64] {
+            let mut sched = scheduler(lanes);
+            sched.spawn_single_warp();
+            let inst = sfu_inst(lanes);
+            let ones = vec![u32::MAX; lanes];
+            let wb = sched.sfu(0, 0, SFUType::TMC, &inst, ones.clone(), ones);
+            assert_eq!(wb.tmask, Some(LaneMask::all(lanes)), "{lanes} lanes");
+            assert_eq!(sched.warp_state(0).tmask.count_ones(), lanes as u32);
+        }
+    }
+
+    #[test]
+    fn split_and_join_cover_the_upper_lanes() {
+        for lanes in [8, 32, 64] {
+            let mut sched = scheduler(lanes);
+            sched.spawn_single_warp();
+            let inst = sfu_inst(lanes);
+            // only the last lane takes the branch
+            let cond = (0..lanes).map(|lane| (lane == lanes - 1) as u32).collect();
+            let wb = sched.sfu(0, 0, SFUType::SPLIT, &inst, cond, vec![0; lanes]);
+            let then_mask: LaneMask = (0..lanes).map(|lane| lane == lanes - 1).collect();
+            assert_eq!(wb.tmask, Some(then_mask), "{lanes} lanes");
+            let push = wb.ipdom_push.expect("divergent split pushes");
+            assert_eq!(push.else_mask.count_ones(), lanes as u32 - 1);
+
+            sched.sfu(0, 0, SFUType::JOIN, &inst, vec![0; lanes], vec![0; lanes]);
+            assert_eq!(sched.warp_state(0).tmask, push.else_mask);
+            sched.sfu(0, 0, SFUType::JOIN, &inst, vec![0; lanes], vec![0; lanes]);
+            assert_eq!(sched.warp_state(0).tmask, LaneMask::all(lanes));
+        }
+    }
+
+    #[test]
+    fn tmc_reaches_upper_lanes_only_through_all_ones() {
+        let mut sched = scheduler(64);
+        sched.spawn_single_warp();
+        let inst = sfu_inst(64);
+        let lane_31 = vec![0x8000_0000; 64];
+        let wb = sched.sfu(0, 0, SFUType::TMC, &inst, lane_31.clone(), lane_31);
+        assert_eq!(wb.tmask.map(|mask| mask.to_u64()), Some(0x8000_0000));
+    }
+
+    #[test]
+    fn ballot_reports_the_low_32_lanes() {
+        for lanes in [8, 32, 64] {
+            let mut inst = sfu_inst(lanes);
+            inst.f3 = 0b011;
+            inst.f7 = 0b0000010;
+            inst.rs1_data = (0..lanes).map(|lane| Some(lane as u32 & 1)).collect();
+            let expected = LaneMask::from_u64(0xaaaa_aaaa_aaaa_aaaa) & LaneMask::all(lanes);
+            let rd = ExecuteUnit::warp_op(&inst);
+            assert_eq!(rd, vec![Some(expected.low_u32()); lanes], "{lanes} lanes");
+        }
+    }
+
+    #[test]
+    fn last_spawned_warp_gets_the_leftover_lanes() {
+        let mut sched = scheduler(64);
+        let full = vec![(0, 0, 0); 64];
+        let partial = vec![(0, 0, 0); 40];
+        sched.spawn_n_warps(0x100, &vec![full, partial]);
+        assert_eq!(sched.warp_state(0).tmask, LaneMask::all(64));
+        assert_eq!(sched.warp_state(1).tmask, LaneMask::all(40));
+    }
+}
@@ -0,0 +1,75 @@
+use std::fmt::{Display, Formatter};
+
+/// Exception codes written to `mcause`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrapCause {
+    IllegalInstruction = 2,
+    LoadAddressMisaligned = 4,
+    StoreAddressMisaligned = 6,
+}
+
+/// A machine-mode exception raised while executing an instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Trap {
+    pub cause: TrapCause,
+    /// Written to `mtval`: the faulting address of a misaligned access. 0
+    /// for illegal instructions, which are wider than `mtval`.
+    pub tval: u32,
+}
+
+/// Unwinds out of the execute stage with a `Trap`, which the warp's
+/// `catch_unwind` either takes or turns into an `ExecErr`. Unlike `panic!`,
+/// this does not run the panic hook, so a guest trap prints nothing.
+pub fn raise(cause: TrapCause, tval: u32) -> ! {
+    std::panic::resume_unwind(Box::new(Trap { cause, tval }))
+}
+
+impl Display for Trap {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.cause {
+            TrapCause::IllegalInstruction => write!(f, "illegal instruction"),
+            TrapCause::LoadAddressMisaligned => {
+                write!(f, "misaligned load from 0x{:08x}", self.tval)
+            }
+            TrapCause::StoreAddressMisaligned => {
+                write!(f, "misaligned store to 0x{:08x}", self.tval)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::muon::config::MuonConfig;
+    use crate::muon::csr::CSRFile;
+    use crate::muon::execute::CSRType;
+    use std::panic::catch_unwind;
+    use std::sync::Arc;
+
+    #[test]
+    fn take_trap_records_cause_and_returns_handler() {
+        let mut csr = CSRFile::new(Arc::new(MuonConfig::default()), 0);
+        csr.user_access(0x305, 0x8000_0101, CSRType::RW);
+        let trap = Trap {
+            cause: TrapCause::LoadAddressMisaligned,
+            tval: 0x1002,
+        };
+        assert_eq!(csr.take_trap(&trap, 0x8000_0040), 0x8000_0100);
+        assert_eq!(csr.mepc(), 0x8000_0040);
+        assert_eq!(csr.user_access(0x342, 0, CSRType::RS), 4);
+        assert_eq!(csr.user_access(0x343, 0, CSRType::RS), 0x1002);
+        assert_eq!(trap.to_string(), "misaligned load from 0x00001002");
+    }
+
+    #[test]
+    fn unknown_csr_raises_illegal_instruction() {
+        let payload = catch_unwind(|| {
+            let mut csr = CSRFile::new(Arc::new(MuonConfig::default()), 0);
+            csr.user_access(0x7ff, 0, CSRType::RS)
+        })
+        .unwrap_err();
+        let trap = payload.downcast::<Trap>().unwrap();
+        assert_eq!(trap.cause, TrapCause::IllegalInstruction);
+    }
+}
@@ -2,38 +2,46 @@ use crate::base::behavior::*;
 use crate::base::mem::HasMemory;
 use crate::base::module::{module, IsModule, ModuleBase};
 use crate::info;
-use crate::muon::config::MuonConfig;
+use crate::muon::config::{MisalignedAccess, MuonConfig};
 use crate::muon::csr::CSRFile;
 use crate::muon::decode::{DecodeUnit, DecodedInst, IssuedInst, MicroOp, RegFile};
 use crate::muon::execute::{ExecuteUnit, Opcode};
 use crate::muon::gmem::CoreTimingModel;
+use crate::muon::lane_mask::LaneMask;
 use crate::muon::scheduler::{Schedule, Scheduler, SchedulerWriteback};
+use crate::muon::trap::{self, Trap, TrapCause};
 use crate::neutrino::neutrino::Neutrino;
+use crate::sim::checkpoint::Shared;
 use crate::sim::flat_mem::FlatMemory;
 use crate::sim::log::Logger;
 use crate::sim::trace::MemTraceLine;
-use crate::timeflow::{GmemRequest, SmemRequest};
+use crate::timeflow::{decode_fence, GmemRequest, SmemRequest};
 use crate::timeq::Cycle;
 use crate::utils::BitSlice;
 use log::warn;
+use std::any::Any;
 use std::fmt::Debug;
 use std::fmt::{Display, Formatter};
 use std::iter::zip;
 use std::ops::DerefMut;
 use std::panic::{catch_unwind, AssertUnwindSafe};
-use std::sync::{Arc, RwLock};
+use std::sync::Arc;
 
-#[derive(Debug, Default)]
+#[derive(Debug, Clone, Default)]
 pub struct WarpState {
     pub reg_file: Vec<RegFile>,
     pub csr_file: Vec<CSRFile>,
 }
 
+#[derive(Clone)]
 pub struct Warp {
     pub base: ModuleBase<WarpState, MuonConfig>,
     pub wid: usize,
     logger: Arc<Logger>,
-    gmem: Arc<RwLock<FlatMemory>>,
+    gmem: Shared<FlatMemory>,
+    /// Shared-memory load waiting on its SMEM timing completion before its
+    /// data is read and written back (`smem_data_coupled`).
+    deferred_load: Option<ExWriteback>,
 }
 
 impl ModuleBehaviors for Warp {
@@ -51,10 +59,10 @@ module!(Warp, WarpState, MuonConfig,);
 
 /// Writeback result from the execute stage modulo memory load/stores,
 /// which will be handled in the mem stage.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ExWriteback {
     pub inst: IssuedInst,
-    pub tmask: u32,
+    pub tmask: LaneMask,
     pub rd_addr: u8,
     pub rd_data: Vec<Option<u32>>,
     pub mem_req: Vec<Option<MemRequest>>,
@@ -63,7 +71,8 @@ pub struct ExWriteback {
 
 #[derive(Clone, Debug)]
 pub struct MemRequest {
-    pub addr: u32,
+    /// Global requests carry the lane's `gmem_window` CSR in bits [63:32].
+    pub addr: u64,
     pub data: Option<u32>,
     pub size: u32,
     pub is_sext: bool,
@@ -73,6 +82,8 @@ pub struct MemRequest {
 
 #[derive(Clone, Debug)]
 pub struct MemResponse {
+    /// The loaded value, zero-extended from the access size, as
+    /// little-endian bytes.
     pub data: Option<[u8; 4]>,
     pub is_sext: bool,
 }
@@ -80,7 +91,7 @@ pub struct MemResponse {
 #[derive(Debug)]
 pub struct Writeback {
     pub inst: IssuedInst,
-    pub tmask: u32,
+    pub tmask: LaneMask,
     pub rd_addr: u8,
     pub rd_data: Vec<Option<u32>>,
     pub sched_wb: SchedulerWriteback,
@@ -95,6 +106,7 @@ pub struct ExecErr {
 
 #[derive(Debug, Clone)]
 struct TimedMemIssue {
+    pc: u32,
     opcode: u8,
     opext: u8,
     rs1_addr: u8,
@@ -133,11 +145,7 @@ impl Display for Writeback {
 }
 
 impl Warp {
-    pub fn new(
-        config: Arc<MuonConfig>,
-        logger: &Arc<Logger>,
-        gmem: Arc<RwLock<FlatMemory>>,
-    ) -> Warp {
+    pub fn new(config: Arc<MuonConfig>, logger: &Arc<Logger>, gmem: Shared<FlatMemory>) -> Warp {
         let num_lanes = config.num_lanes;
         let mut me = Warp {
             base: ModuleBase {
@@ -154,6 +162,7 @@ impl Warp {
             wid: config.lane_config.warp_id,
             logger: logger.clone(),
             gmem,
+            deferred_load: None,
         };
         me.init_conf(config);
         me
@@ -183,7 +192,7 @@ impl Warp {
         let inst = self.fetch(schedule.pc);
         for c in self.state_mut().csr_file.iter_mut() {
             c.emu_access(0xcc3, schedule.active_warps);
-            c.emu_access(0xcc4, schedule.mask);
+            c.emu_access(0xcc4, schedule.mask.low_u32());
         }
         self.frontend_nofetch(schedule, inst)
     }
@@ -239,12 +248,8 @@ impl Warp {
                 Ok((writeback, mem_trace_lines))
             }
             Err(payload) => {
-                let message = payload.downcast::<String>().ok().map(|s| *s);
-                Err(ExecErr {
-                    pc,
-                    warp_id: self.wid,
-                    message,
-                })
+                let ex_wb = self.unwound(payload, &uop, scheduler)?;
+                Ok(self.mem(&ex_wb, smem, None))
             }
         }
     }
@@ -264,16 +269,26 @@ impl Warp {
 
         scheduler.state_mut().thread_masks[self.wid] = tmask;
 
+        if timing_model
+            .wait_operands(now, self.wid, &decoded, scheduler)
+            .is_err()
+        {
+            return Ok(None);
+        }
+
+        let mut defer_load = false;
         match decoded.opcode {
             Opcode::LOAD | Opcode::STORE => {
-                if let Some(issue) = self.build_timed_mem_issue(&decoded, tmask) {
-                    if self.route_mem_to_smem(&decoded) {
+                if let Some(mut issue) = self.build_timed_mem_issue(&decoded, tmask) {
+                    if self.route_mem_to_smem(&mut issue) {
                         if self
                             .issue_smem_request(&issue, scheduler, timing_model, now)
                             .is_err()
                         {
                             return Ok(None);
                         }
+                        defer_load =
+                            decoded.opcode == Opcode::LOAD && self.conf().smem_data_coupled;
                     } else if self
                         .issue_gmem_request(&issue, scheduler, timing_model, now)
                         .is_err()
@@ -285,15 +300,14 @@ impl Warp {
             Opcode::MISC_MEM => {
                 let active_lanes = tmask.count_ones();
                 if active_lanes > 0 {
-                    let flush_req = if decoded.f3 == 1 {
-                        GmemRequest::new_flush_l0(self.wid, 1)
+                    let issued = if decoded.f3 == 1 {
+                        let flush_req = GmemRequest::new_flush_l0(self.wid, 1);
+                        timing_model.issue_gmem_request(now, self.wid, flush_req, scheduler)
                     } else {
-                        GmemRequest::new_flush_l1(self.wid, 1)
+                        let (scope, order) = decode_fence(decoded.f3, decoded.imm32);
+                        timing_model.issue_fence(now, self.wid, scope, order, scheduler)
                     };
-                    if timing_model
-                        .issue_gmem_request(now, self.wid, flush_req, scheduler)
-                        .is_err()
-                    {
+                    if issued.is_err() {
                         return Ok(None);
                     }
                 }
@@ -306,7 +320,7 @@ impl Warp {
                     _ => false,
                 };
                 if is_write {
-                    timing_model.notify_csr_write(now, decoded.imm32);
+                    timing_model.notify_csr_write(now, self.wid, decoded.imm32);
                 }
             }
             _ => {}
@@ -321,6 +335,35 @@ impl Warp {
             return Ok(None);
         }
 
+        let counter = match decoded.opcode {
+            Opcode::SYSTEM if decoded.f3 != 0 => {
+                timing_model.read_perf_counter(now, self.wid, decoded.imm32)
+            }
+            _ => None,
+        };
+        if let Some(value) = counter {
+            for csr in self.state_mut().csr_file.iter_mut() {
+                csr.emu_access(decoded.imm32, value);
+            }
+        }
+        timing_model.notify_retire(now, self.wid, &issued, counter.is_some());
+
+        if defer_load {
+            let ex_writeback = catch_unwind(AssertUnwindSafe(|| {
+                self.execute_nomem(issued, tmask, scheduler, neutrino)
+            }));
+            return match ex_writeback {
+                Ok(ex_writeback) => {
+                    self.deferred_load = Some(ex_writeback);
+                    Ok(None)
+                }
+                Err(payload) => {
+                    let ex_wb = self.unwound(payload, &uop, scheduler)?;
+                    Ok(Some(self.mem(&ex_wb, smem, None)))
+                }
+            };
+        }
+
         let writeback = catch_unwind(AssertUnwindSafe(|| {
             self.execute(issued, tmask, scheduler, neutrino, smem)
         }));
@@ -328,6 +371,9 @@ impl Warp {
         match writeback {
             Ok((writeback, mem_trace_lines)) => {
                 self.writeback(&writeback);
+                if !scheduler.state().active_warps.bit(self.wid) {
+                    timing_model.notify_warp_exit(now, self.wid);
+                }
 
                 info!(
                     self.logger,
@@ -342,12 +388,8 @@ impl Warp {
                 Ok(Some((writeback, mem_trace_lines)))
             }
             Err(payload) => {
-                let message = payload.downcast::<String>().ok().map(|s| *s);
-                Err(ExecErr {
-                    pc,
-                    warp_id: self.wid,
-                    message,
-                })
+                let ex_wb = self.unwound(payload, &uop, scheduler)?;
+                Ok(Some(self.mem(&ex_wb, smem, None)))
             }
         }
     }
@@ -360,7 +402,6 @@ impl Warp {
         scheduler: &mut Scheduler,
         neutrino: &mut Neutrino,
     ) -> Result<ExWriteback, ExecErr> {
-        let pc = uop.inst.pc;
         let tmask = uop.tmask;
 
         assert!(
@@ -379,14 +420,7 @@ impl Warp {
         // writeback
         match ex_writeback {
             Ok(wb) => Ok(wb),
-            Err(payload) => {
-                let message = payload.downcast::<String>().ok().map(|s| *s);
-                Err(ExecErr {
-                    pc,
-                    warp_id: self.wid,
-                    message,
-                })
-            }
+            Err(payload) => self.unwound(payload, &uop, scheduler),
         }
     }
 
@@ -396,11 +430,58 @@ impl Warp {
         ExecuteUnit::collect(&uop, rf)
     }
 
+    /// Handles a panic out of the execute stage. With `traps` on, a `Trap`
+    /// is taken: the active lanes record it in their CSRs and the warp jumps
+    /// to `mtvec`, writing back nothing. Anything else, or a trap with no
+    /// handler installed, stops the run.
+    fn unwound(
+        &mut self,
+        payload: Box<dyn Any + Send>,
+        uop: &MicroOp,
+        scheduler: &mut Scheduler,
+    ) -> Result<ExWriteback, ExecErr> {
+        let pc = uop.inst.pc;
+        let payload = match payload.downcast::<Trap>() {
+            Ok(trap) if self.conf().traps => {
+                let mut handler = 0;
+                for (lane, csr) in self.base.state.csr_file.iter_mut().enumerate() {
+                    if uop.tmask.bit(lane) {
+                        handler = csr.take_trap(&trap, pc);
+                    }
+                }
+                if handler == 0 {
+                    return Err(self.exec_err(pc, Some(trap.to_string())));
+                }
+                let num_lanes = self.base.state.csr_file.len();
+                return Ok(ExWriteback {
+                    inst: self.collect(uop),
+                    tmask: uop.tmask,
+                    rd_addr: 0,
+                    rd_data: vec![None; num_lanes],
+                    mem_req: vec![None; num_lanes],
+                    sched_wb: scheduler.take_branch(self.wid, handler),
+                });
+            }
+            Ok(trap) => return Err(self.exec_err(pc, Some(trap.to_string()))),
+            Err(payload) => payload,
+        };
+        let message = payload.downcast::<String>().ok().map(|s| *s);
+        Err(self.exec_err(pc, message))
+    }
+
+    fn exec_err(&self, pc: u32, message: Option<String>) -> ExecErr {
+        ExecErr {
+            pc,
+            warp_id: self.wid,
+            message,
+        }
+    }
+
     /// Execute a single-warp issued instruction and return a writeback bundle.
     pub fn execute(
         &mut self,
         issued: IssuedInst,
-        tmask: u32,
+        tmask: LaneMask,
         scheduler: &mut Scheduler,
         neutrino: &mut Neutrino,
         smem: &mut FlatMemory,
@@ -415,7 +496,7 @@ impl Warp {
     pub fn execute_nomem(
         &mut self,
         issued: IssuedInst,
-        tmask: u32,
+        tmask: LaneMask,
         scheduler: &mut Scheduler,
         neutrino: &mut Neutrino,
     ) -> ExWriteback {
@@ -424,9 +505,60 @@ impl Warp {
         let csrf = self.base.state.csr_file.as_mut_slice();
 
         // ALU/FPU/AddrGen stage
-        ExecuteUnit::execute(
+        let mut ex_writeback = ExecuteUnit::execute(
             issued, core_id, self.wid, tmask, rf, csrf, scheduler, neutrino,
-        )
+        );
+        for (lane, oreq) in ex_writeback.mem_req.iter_mut().enumerate() {
+            let Some(req) = oreq else {
+                continue;
+            };
+            if self.conf().traps
+                && self.conf().misaligned == MisalignedAccess::Trap
+                && req.addr % req.size as u64 != 0
+            {
+                let cause = if req.is_store {
+                    TrapCause::StoreAddressMisaligned
+                } else {
+                    TrapCause::LoadAddressMisaligned
+                };
+                trap::raise(cause, req.addr as u32);
+            }
+            if req.is_smem {
+                continue;
+            }
+            if let Some(offset) = self.smem_window_offset(req.addr as u32) {
+                req.is_smem = true;
+                req.addr = offset.into();
+            } else {
+                let window = self.base.state.csr_file[lane].gmem_window();
+                req.addr = windowed_addr(window, req.addr as u32);
+            }
+        }
+        ex_writeback
+    }
+
+    /// Offset into shared memory of a global address inside the
+    /// `smem_base_addr` window, if one is configured.
+    fn smem_window_offset(&self, addr: u32) -> Option<u32> {
+        let base = self.conf().smem_base_addr?;
+        addr.checked_sub(base)
+            .filter(|offset| (*offset as usize) < self.conf().smem_size)
+    }
+
+    pub fn has_deferred_load(&self) -> bool {
+        self.deferred_load.is_some()
+    }
+
+    /// Serves a load held by `smem_data_coupled` once its timing completion
+    /// has arrived: reads shared memory now and writes the result back.
+    pub fn finish_deferred_load(
+        &mut self,
+        smem: &mut FlatMemory,
+    ) -> Option<(Writeback, Vec<MemTraceLine>)> {
+        let ex_writeback = self.deferred_load.take()?;
+        let (writeback, mem_trace_lines) = self.mem(&ex_writeback, smem, None);
+        self.writeback(&writeback);
+        Some((writeback, mem_trace_lines))
     }
 
     /// Memory stage; serve the memory requests produced in EX.
@@ -469,6 +601,7 @@ impl Warp {
                 Some(MemTraceLine {
                     warp_id: self.wid as u32,
                     lane_id: lane_id as u32,
+                    pc: ex_writeback.inst.pc,
                     is_smem: req.is_smem,
                     store: req.is_store,
                     address: req.addr,
@@ -524,19 +657,17 @@ impl Warp {
             gmem.deref_mut()
         };
 
-        let addr = mem_req.addr;
-        let addr_aligned = addr >> 2 << 2;
-        let size = mem_req.size;
+        let addr = mem_req.addr as usize;
+        let size = mem_req.size as usize;
+        let endianness = self.conf().endianness;
+        let emulate = self.conf().misaligned == MisalignedAccess::Emulate;
 
         if mem_req.is_store {
             let store_data = mem_req.data.expect("store req missing a data field");
-            let store_data_bytes = store_data.to_le_bytes();
-
-            match size {
-                1 => mem.write(addr as usize, &store_data_bytes[0..1]), // store byte
-                2 => mem.write(addr as usize, &store_data_bytes[0..2]), // store half
-                4 => mem.write(addr as usize, &store_data_bytes[0..4]), // store word
-                _ => panic!("unimplemented store size"),
+            if emulate {
+                mem.write_value_split(addr, size, store_data.into(), endianness)
+            } else {
+                mem.write_value(addr, size, store_data.into(), endianness)
             }
             .expect("store failed");
 
@@ -545,11 +676,16 @@ impl Warp {
                 is_sext: mem_req.is_sext, // pass-through
             }
         } else {
-            let load_data = mem.read_n::<4>(addr_aligned as usize).expect("load failed");
+            let load_data = if emulate {
+                mem.read_value_split(addr, size, endianness)
+            } else {
+                mem.read_value(addr, size, endianness)
+            }
+            .expect("load failed");
 
-            // byte-masking will be handled in Execute::mem
+            // sign extension will be handled in Execute::mem
             MemResponse {
-                data: Some(load_data),
+                data: Some((load_data as u32).to_le_bytes()),
                 is_sext: mem_req.is_sext, // pass-through
             }
         }
@@ -578,7 +714,11 @@ impl Warp {
         }
     }
 
-    fn build_timed_mem_issue(&self, decoded: &DecodedInst, tmask: u32) -> Option<TimedMemIssue> {
+    fn build_timed_mem_issue(
+        &self,
+        decoded: &DecodedInst,
+        tmask: LaneMask,
+    ) -> Option<TimedMemIssue> {
         if decoded.opcode != Opcode::LOAD && decoded.opcode != Opcode::STORE {
             warn!(
                 "warp {} mem issue requested for non-memory opcode=0x{:02x}",
@@ -593,9 +733,10 @@ impl Warp {
         }
 
         let bytes_per_lane = 1u32 << (decoded.f3 & 3);
-        let lane_addrs = self.collect_lane_addrs(decoded.rs1_addr, decoded.imm32, tmask);
+        let lane_addrs = self.collect_lane_addrs(decoded, tmask);
 
         Some(TimedMemIssue {
+            pc: decoded.pc,
             opcode: decoded.opcode,
             opext: decoded.opext,
             rs1_addr: decoded.rs1_addr,
@@ -628,6 +769,7 @@ impl Warp {
             issue.active_lanes,
             issue.opcode == Opcode::LOAD,
         );
+        request.pc = issue.pc;
         request.addr = issue.lane_addrs.iter().copied().min().unwrap_or(0);
         request.lane_addrs = Some(issue.lane_addrs.clone());
 
@@ -637,18 +779,44 @@ impl Warp {
             .map_err(|_| ())
     }
 
-    fn route_mem_to_smem(&self, decoded: &DecodedInst) -> bool {
-        (decoded.opcode == Opcode::LOAD || decoded.opcode == Opcode::STORE) && decoded.opext == 1
+    /// Whether a timed access goes to SMEM: `.shared` accesses, and plain
+    /// ones whose every lane falls in the `smem_base_addr` window. The latter are
+    /// rewritten to their window offsets so banking sees SMEM addresses; an
+    /// access straddling the window edge is timed as gmem.
+    fn route_mem_to_smem(&self, issue: &mut TimedMemIssue) -> bool {
+        if issue.opext == 1 {
+            return true;
+        }
+        let offsets = issue
+            .lane_addrs
+            .iter()
+            .map(|&addr| self.smem_window_offset(addr as u32).map(u64::from))
+            .collect::<Option<Vec<_>>>();
+        match offsets {
+            Some(offsets) if !offsets.is_empty() => {
+                issue.lane_addrs = offsets;
+                issue.opext = 1;
+                true
+            }
+            _ => false,
+        }
     }
 
-    fn collect_lane_addrs(&self, rs1_addr: u8, imm32: u32, tmask: u32) -> Vec<u64> {
+    /// Per-lane addresses of a timed access. Global ones include the lane's
+    /// `gmem_window`; `route_mem_to_smem` only looks at the low 32 bits.
+    fn collect_lane_addrs(&self, decoded: &DecodedInst, tmask: LaneMask) -> Vec<u64> {
+        let state = &self.base.state;
         let mut addrs = Vec::new();
-        for (lane, lrf) in self.base.state.reg_file.iter().enumerate() {
+        for (lane, lrf) in state.reg_file.iter().enumerate() {
             if !tmask.bit(lane) {
                 continue;
             }
-            let addr = lrf.read_gpr(rs1_addr).wrapping_add(imm32) as u64;
-            addrs.push(addr);
+            let addr = lrf.read_gpr(decoded.rs1_addr).wrapping_add(decoded.imm32);
+            if decoded.opext == 1 {
+                addrs.push(addr.into());
+            } else {
+                addrs.push(windowed_addr(state.csr_file[lane].gmem_window(), addr));
+            }
         }
         addrs
     }
@@ -687,15 +855,19 @@ impl Warp {
     }
 }
 
+/// A 32-bit global address placed in the 4GiB window selected by the
+/// `gmem_window` CSR.
+fn windowed_addr(window: u32, addr: u32) -> u64 {
+    (u64::from(window) << 32) | u64::from(addr)
+}
+
 fn trace_mem_data(mem_req: &MemRequest, mem_resp: &MemResponse) -> u32 {
     if mem_req.is_store {
         let store_data = mem_req.data.expect("store req missing a data field");
         mask_mem_data(store_data, mem_req.size)
     } else {
         let raw_load = u32::from_le_bytes(mem_resp.data.expect("load response missing data"));
-        let bit_offset = ((mem_req.addr & 3) * 8) as usize;
-        let shifted = raw_load >> bit_offset;
-        mask_mem_data(shifted, mem_req.size)
+        mask_mem_data(raw_load, mem_req.size)
     }
 }
 
@@ -16,11 +16,12 @@ pub fn counter_cmp(a: u32, b: u32, n: usize) -> Ordering {
     dist_ab.cmp(&dist_ba)
 }
 
-#[derive(Default)]
+#[derive(Clone, Default)]
 pub struct CountersState {
     counters: Vec<u32>,
 }
 
+#[derive(Clone)]
 pub struct Counters {
     base: ModuleBase<CountersState, NeutrinoConfig>,
 }
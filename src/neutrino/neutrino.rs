@@ -3,6 +3,7 @@ use crate::base::module::IsModule;
 use crate::base::module::{module, ModuleBase};
 use crate::muon::decode::{IssuedInst, RegFile};
 use crate::muon::execute::{InstDef, Opcode};
+use crate::muon::lane_mask::LaneMask;
 use crate::muon::scheduler::Scheduler;
 use crate::neutrino::config::NeutrinoConfig;
 use crate::neutrino::scoreboard::{
@@ -13,9 +14,10 @@ use crate::utils::BitSlice;
 use log::{debug, info};
 use std::sync::Arc;
 
-#[derive(Default)]
+#[derive(Clone, Default)]
 pub struct NeutrinoState {}
 
+#[derive(Clone)]
 pub struct Neutrino {
     base: ModuleBase<NeutrinoState, NeutrinoConfig>,
     scoreboard: Scoreboard,
@@ -52,7 +54,7 @@ impl Neutrino {
         issued: &IssuedInst,
         cid: usize,
         wid: usize,
-        tmask: u32,
+        tmask: LaneMask,
         rf: &mut RegFile,
     ) {
         // decode neutrino instruction
@@ -125,6 +127,15 @@ impl Neutrino {
         );
     }
 
+    /// See `Scoreboard::set_barrier_log`.
+    pub fn set_barrier_log(&mut self, enabled: bool) {
+        self.scoreboard.set_barrier_log(enabled);
+    }
+
+    pub fn take_released_barriers(&mut self) -> Vec<Vec<Vec<LaneMask>>> {
+        self.scoreboard.take_released_barriers()
+    }
+
     pub fn update(&mut self, schedulers: &mut Vec<&mut Scheduler>) {
         // TODO: some steps can probably be skipped if no changes in state
         // find all ready jobs
@@ -139,7 +150,7 @@ impl Neutrino {
             .iter_mut()
             .zip(stalls.iter())
             .for_each(|(scheduler, stall)| {
-                scheduler.neutrino_stall(stall.iter().map(|x| *x > 0).collect());
+                scheduler.neutrino_stall(stall.iter().map(|x| !x.is_empty()).collect());
             });
     }
 }
@@ -1,6 +1,7 @@
 use crate::base::behavior::{ModuleBehaviors, Parameterizable};
 use crate::base::module::IsModule;
 use crate::base::module::{module, ModuleBase};
+use crate::muon::lane_mask::LaneMask;
 use crate::neutrino::config::NeutrinoConfig;
 use crate::neutrino::counters::Counters;
 use crate::neutrino::scoreboard::JobStatus::NotStarted;
@@ -37,17 +38,18 @@ pub enum JobStatus {
     Finished,
 }
 
+#[derive(Clone)]
 pub struct ScoreboardEntry {
     deps: Vec<JobID>,
     ret_mode: NeutrinoRetMode,
     part_mode: NeutrinoPartMode,
     sync: bool,
     num_elems: u32,
-    arrived_mask: Vec<Vec<u32>>, // mask[wid] = thread mask
+    arrived_mask: Vec<Vec<LaneMask>>, // mask[wid] = thread mask
     dispatched: bool,
 }
 
-#[derive(Default)]
+#[derive(Clone, Default)]
 pub struct ScoreboardState {
     entries: HashMap<JobID, ScoreboardEntry>,
 }
@@ -69,7 +71,7 @@ pub struct NeutrinoCmd {
     pub part_mode: NeutrinoPartMode,
     pub num_elems: u32,
     pub sync: bool,
-    pub tmask: u32,
+    pub tmask: LaneMask,
 }
 
 impl Display for NeutrinoCmd {
@@ -77,7 +79,7 @@ impl Display for NeutrinoCmd {
         write!(
             f,
             "neutrino [type: {}, job_id: {:?}, task: {}, deps: {:?}, \
-            ret_mode: {}, part_mode: {}, num_elems: {}, sync: {}, tmask: {:08x}]",
+            ret_mode: {}, part_mode: {}, num_elems: {}, sync: {}, tmask: {:016x}]",
             match self.cmd_type {
                 NeutrinoCmdType::Invoke => "invoke",
                 NeutrinoCmdType::Payload => "payload",
@@ -109,9 +111,13 @@ impl Display for JobID {
     }
 }
 
+#[derive(Clone)]
 pub struct Scoreboard {
     base: ModuleBase<ScoreboardState, NeutrinoConfig>,
     counters: Counters,
+    /// Arrived masks of the synchronizing barriers dispatched since the
+    /// last `take_released_barriers`; `None` unless enabled.
+    released_barriers: Option<Vec<Vec<Vec<LaneMask>>>>,
 }
 
 module!(Scoreboard, ScoreboardState, NeutrinoConfig,);
@@ -138,11 +144,30 @@ impl Scoreboard {
                 ..ModuleBase::default()
             },
             counters,
+            released_barriers: None,
         };
         me.init_conf(config);
         me
     }
 
+    /// Starts or stops keeping the barriers `take_released_barriers` returns.
+    pub fn set_barrier_log(&mut self, enabled: bool) {
+        match (enabled, self.released_barriers.is_some()) {
+            (true, false) => self.released_barriers = Some(Vec::new()),
+            (false, true) => self.released_barriers = None,
+            _ => {}
+        }
+    }
+
+    /// Synchronizing barriers released since the last call, in order, as
+    /// `mask[cid][wid]` thread masks of the warps that took part.
+    pub fn take_released_barriers(&mut self) -> Vec<Vec<Vec<LaneMask>>> {
+        self.released_barriers
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
     pub fn job_id_from_u32(&self, id: u32) -> JobID {
         let task_id = (id >> self.conf().counter_width) & ((1 << self.conf().task_id_width) - 1);
         let counter = id & ((1 << self.conf().counter_width) - 1);
@@ -180,7 +205,7 @@ impl Scoreboard {
                         part_mode: cmd.part_mode,
                         sync: cmd.sync,
                         num_elems: cmd.num_elems,
-                        arrived_mask: vec![vec![0u32; num_warps]; num_cores],
+                        arrived_mask: vec![vec![LaneMask::empty(); num_warps]; num_cores],
                         dispatched: false,
                     };
                     entry.arrived_mask[cid][wid] = cmd.tmask;
@@ -218,7 +243,7 @@ impl Scoreboard {
         // any thread in a warp means warp has arrived
         let num_warps: u32 = arrived
             .iter()
-            .flat_map(|x| x.iter().filter(|y| **y != 0u32))
+            .flat_map(|x| x.iter().filter(|y| !y.is_empty()))
             .collect::<Vec<_>>()
             .len() as u32;
 
@@ -268,8 +293,11 @@ impl Scoreboard {
         assert!(!entry.dispatched, "job already dispatched");
         entry.dispatched = true;
 
-        if job_id.task_id < 8 { // barriers
-             // do nothing
+        if job_id.task_id < 8 {
+            // barriers
+            if let Some(released) = self.released_barriers.as_mut().filter(|_| entry.sync) {
+                released.push(entry.arrived_mask.clone());
+            }
         } else {
             panic!("task id {} not implemented", job_id.task_id)
         }
@@ -286,11 +314,13 @@ impl Scoreboard {
         self.base.state.entries.remove_entry(&job_id);
     }
 
-    pub fn get_stalls(&mut self) -> Vec<Vec<u32>> {
+    pub fn get_stalls(&mut self) -> Vec<Vec<LaneMask>> {
         // find all sync, undispatched jobs and OR their masks
-        let mut stalls =
-            vec![vec![0u32; self.conf().muon_config.num_warps]; self.conf().muon_config.num_cores];
-        let arrived_masks: Vec<Vec<Vec<u32>>> = self
+        let mut stalls = vec![
+            vec![LaneMask::empty(); self.conf().muon_config.num_warps];
+            self.conf().muon_config.num_cores
+        ];
+        let arrived_masks: Vec<Vec<Vec<LaneMask>>> = self
             .base
             .state
             .entries
@@ -300,7 +330,7 @@ impl Scoreboard {
         arrived_masks.iter().for_each(|e| {
             e.iter().enumerate().for_each(|(cid, warp)| {
                 warp.iter().enumerate().for_each(|(wid, mask)| {
-                    stalls[cid][wid] |= mask;
+                    stalls[cid][wid] |= *mask;
                 });
             });
         });
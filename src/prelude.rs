@@ -0,0 +1,33 @@
+//! The types a program embedding Cyclotron needs, under paths that stay put
+//! when the modules behind them are reorganized:
+//!
+//! ```no_run
+//! use cyclotron::prelude::*;
+//!
+//! let config = std::fs::read_to_string("config.toml").unwrap();
+//! let mut sim: Sim = make_sim(Some(&config), &None);
+//! if let RunOutcome::Fired { cycle, .. } = sim.run_until(Event::NextKernelBoundary) {
+//!     println!("kernel finished at cycle {cycle}");
+//! }
+//! for summary in sim.timing_summaries() {
+//!     println!("core {} cpi {:.2}", summary.core_id, summary.cpi_stack.cpi());
+//! }
+//! ```
+//!
+//! Everything else under `cyclotron::` is reachable too, but may move
+//! between releases. The config and stats structs re-exported here are
+//! `#[non_exhaustive]`: new fields can be added to them, so build them from
+//! `Default` and set fields, rather than with struct literals.
+
+pub use crate::muon::config::MuonConfig;
+pub use crate::muon::gmem::CorePerfSummary;
+pub use crate::sim::config::{LogConfig, SimConfig, SqliteLogConfig};
+pub use crate::sim::hooks::{
+    CycleEvent, GmemCompletionEvent, KernelFinishEvent, RetireEvent, SimHooks,
+};
+pub use crate::sim::top::{DrainSnapshot, DrainTimeout, Event, EventSource, RunOutcome, Sim};
+pub use crate::timeflow::{
+    ClusterGmemGraph, CoreGraph, CoreGraphConfig, EdgeStats, FlowGraph, GmemStats, Link, TimedNode,
+};
+pub use crate::timeq::Cycle;
+pub use crate::ui::{make_sim, CyclotronArgs};
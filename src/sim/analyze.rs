@@ -0,0 +1,269 @@
+use crate::timeflow::gmem::CacheLevelConfig;
+use crate::timeflow::{ClusterGmemGraph, CoreGraphConfig};
+use serde::Serialize;
+use std::fmt;
+
+/// Derived sizes and bandwidths of one cache level. Bandwidths are in bytes
+/// per cycle.
+#[derive(Debug, Clone, Serialize)]
+pub struct CacheLevelReport {
+    pub name: &'static str,
+    /// Copies of this level in the machine: one per core for L0, one per
+    /// cluster for L1, one shared L2.
+    pub instances: usize,
+    pub sets: usize,
+    pub ways: usize,
+    pub line_bytes: u32,
+    pub size_bytes: u64,
+    pub banks: usize,
+    /// Tag plus data latency of a hit.
+    pub hit_latency: u64,
+    pub hit_bandwidth: u64,
+    pub aggregate_hit_bandwidth: u64,
+    pub refill_bandwidth: u64,
+    pub mshrs: usize,
+    /// Misses the level can have outstanding across all instances.
+    pub aggregate_mshrs: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ConfigAnalysis {
+    pub num_clusters: usize,
+    pub cores_per_cluster: usize,
+    pub levels: Vec<CacheLevelReport>,
+    /// Peak bandwidth summed over all `gmem.dram` channels.
+    pub dram_bandwidth: u64,
+    pub dram_latency: u64,
+    /// DRAM bandwidth the L2 MSHRs can sustain by Little's law: outstanding
+    /// lines times line size over DRAM latency.
+    pub mshr_limited_dram_bandwidth: f64,
+    /// Per-core return path into the LSU.
+    pub return_bandwidth: u64,
+    pub warnings: Vec<String>,
+}
+
+fn level_report(
+    name: &'static str,
+    level: &CacheLevelConfig,
+    instances: usize,
+    sets: usize,
+    ways: usize,
+    line_bytes: u32,
+) -> CacheLevelReport {
+    let sets = sets.max(1);
+    let ways = ways.max(1);
+    // The L0 is modeled with a single bank regardless of its config.
+    let banks = if name == "L0" { 1 } else { level.banks.max(1) };
+    let hit_bandwidth = level.data.bytes_per_cycle as u64 * banks as u64;
+    let mshrs = level.effective_mshr_capacity() * banks;
+    CacheLevelReport {
+        name,
+        instances,
+        sets,
+        ways,
+        line_bytes,
+        size_bytes: sets as u64 * ways as u64 * line_bytes as u64,
+        banks,
+        hit_latency: level.tag.base_latency + level.data.base_latency,
+        hit_bandwidth,
+        aggregate_hit_bandwidth: hit_bandwidth * instances as u64,
+        refill_bandwidth: level.refill.bytes_per_cycle as u64 * banks as u64,
+        mshrs,
+        aggregate_mshrs: mshrs * instances,
+    }
+}
+
+/// Works out cache sizes and bandwidths from a timing config and flags
+/// settings that are likely to bottleneck or surprise.
+pub fn analyze_config(
+    config: &CoreGraphConfig,
+    num_clusters: usize,
+    cores_per_cluster: usize,
+) -> ConfigAnalysis {
+    let gmem = &config.memory.gmem;
+    let policy = &gmem.policy;
+    let num_clusters = num_clusters.max(1);
+    let mut warnings = Vec::new();
+
+    if gmem.levels.len() != 3 {
+        warnings.push(format!(
+            "gmem.levels has {} entries; the hierarchy uses exactly three (L0, L1, L2)",
+            gmem.levels.len()
+        ));
+    }
+    let level = |idx: usize| gmem.levels.get(idx).cloned().unwrap_or_default();
+
+    let mut levels = Vec::new();
+    if policy.l0_enabled {
+        levels.push(level_report(
+            "L0",
+            &level(0),
+            num_clusters * cores_per_cluster,
+            policy.l0_sets,
+            policy.l0_ways,
+            policy.l0_line_bytes,
+        ));
+    }
+    levels.push(level_report(
+        "L1",
+        &level(1),
+        num_clusters,
+        policy.l1_sets,
+        policy.l1_ways,
+        policy.l1_line_bytes,
+    ));
+    levels.push(level_report(
+        "L2",
+        &level(2),
+        1,
+        policy.l2_sets,
+        policy.l2_ways,
+        policy.l2_line_bytes,
+    ));
+
+    let dram = &gmem.nodes.dram;
+    let dram_bandwidth = dram.bytes_per_cycle as u64 * gmem.dram.channel_count() as u64;
+    let dram_latency = dram.base_latency;
+    let l2 = levels.last().expect("L2 is always present");
+    let mshr_limited_dram_bandwidth =
+        l2.aggregate_mshrs as f64 * l2.line_bytes as f64 / dram_latency.max(1) as f64;
+    let return_bandwidth = gmem.nodes.return_path.bytes_per_cycle as u64;
+
+    let nearest = &levels[0];
+    if return_bandwidth < nearest.hit_bandwidth {
+        warnings.push(format!(
+            "return path ({return_bandwidth} B/cycle) is narrower than {} hit bandwidth ({} B/cycle)",
+            nearest.name, nearest.hit_bandwidth
+        ));
+    }
+    for pair in levels.windows(2) {
+        let (inner, outer) = (&pair[0], &pair[1]);
+        if inner.line_bytes > outer.line_bytes {
+            warnings.push(format!(
+                "{} line ({} B) is larger than {} line ({} B); each {} fill spans several {} lines",
+                inner.name, inner.line_bytes, outer.name, outer.line_bytes, inner.name, outer.name
+            ));
+        }
+        if inner.size_bytes * inner.instances as u64 > outer.size_bytes * outer.instances as u64 {
+            warnings.push(format!(
+                "total {} capacity exceeds total {} capacity",
+                inner.name, outer.name
+            ));
+        }
+    }
+    if l2.aggregate_hit_bandwidth < dram_bandwidth {
+        warnings.push(format!(
+            "L2 hit bandwidth ({} B/cycle) is below DRAM bandwidth ({dram_bandwidth} B/cycle)",
+            l2.aggregate_hit_bandwidth
+        ));
+    }
+    if mshr_limited_dram_bandwidth < dram_bandwidth as f64 {
+        warnings.push(format!(
+            "L2 MSHRs sustain {mshr_limited_dram_bandwidth:.1} B/cycle against a {dram_latency}-cycle DRAM latency, below its {dram_bandwidth} B/cycle peak"
+        ));
+    }
+
+    if gmem.levels.len() >= 3 {
+        let graph = ClusterGmemGraph::new_with_regions(
+            gmem.clone(),
+            config.memory.map.clone(),
+            num_clusters,
+            cores_per_cluster,
+        );
+        let validation = graph.validate();
+        for problem in validation.to_string().lines() {
+            warnings.push(format!("gmem graph: {problem}"));
+        }
+    }
+
+    ConfigAnalysis {
+        num_clusters,
+        cores_per_cluster,
+        levels,
+        dram_bandwidth,
+        dram_latency,
+        mshr_limited_dram_bandwidth,
+        return_bandwidth,
+        warnings,
+    }
+}
+
+impl fmt::Display for ConfigAnalysis {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} cluster(s) x {} core(s)",
+            self.num_clusters, self.cores_per_cluster
+        )?;
+        writeln!(
+            f,
+            "{:<5} {:>5} {:>10} {:>6} {:>5} {:>5} {:>8} {:>10} {:>10} {:>6}",
+            "level",
+            "count",
+            "size",
+            "line",
+            "ways",
+            "banks",
+            "hit lat",
+            "hit B/c",
+            "total B/c",
+            "MSHRs"
+        )?;
+        for level in &self.levels {
+            writeln!(
+                f,
+                "{:<5} {:>5} {:>9}K {:>6} {:>5} {:>5} {:>8} {:>10} {:>10} {:>6}",
+                level.name,
+                level.instances,
+                level.size_bytes / 1024,
+                level.line_bytes,
+                level.ways,
+                level.banks,
+                level.hit_latency,
+                level.hit_bandwidth,
+                level.aggregate_hit_bandwidth,
+                level.aggregate_mshrs
+            )?;
+        }
+        writeln!(
+            f,
+            "DRAM: {} B/cycle peak, {} cycles latency, {:.1} B/cycle MSHR-limited",
+            self.dram_bandwidth, self.dram_latency, self.mshr_limited_dram_bandwidth
+        )?;
+        writeln!(f, "return path: {} B/cycle per core", self.return_bandwidth)?;
+        for warning in &self.warnings {
+            writeln!(f, "warning: {warning}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_config_sizes_and_warnings() {
+        let config = CoreGraphConfig::default();
+        let analysis = analyze_config(&config, 2, 4);
+        let l1 = analysis
+            .levels
+            .iter()
+            .find(|level| level.name == "L1")
+            .unwrap();
+        let policy = &config.memory.gmem.policy;
+        assert_eq!(
+            l1.size_bytes,
+            (policy.l1_sets * policy.l1_ways) as u64 * policy.l1_line_bytes as u64
+        );
+        assert_eq!(l1.instances, 2);
+
+        let mut narrow = config.clone();
+        narrow.memory.gmem.nodes.return_path.bytes_per_cycle = 1;
+        let analysis = analyze_config(&narrow, 1, 1);
+        assert!(analysis
+            .warnings
+            .iter()
+            .any(|warning| warning.starts_with("return path")));
+    }
+}
@@ -0,0 +1,598 @@
+use crate::sim::latency_probe::probe_level;
+use crate::timeflow::smem::{SmemRequest, SmemSubgraph};
+use crate::timeflow::{ClusterGmemGraph, CoreFlowPayload, CoreGraphConfig, FlowGraph, GmemRequest};
+use crate::timeq::Cycle;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use toml::Value;
+
+/// Address the bandwidth stream starts at; clear of the latency chase.
+const STREAM_BASE: u64 = 0x5000_0000;
+/// Lines each core reads in the bandwidth stream.
+const STREAM_LINES: u64 = 512;
+/// Cycles a probe may run before the calibrator gives up on it.
+const PROBE_TIMEOUT: Cycle = 4_000_000;
+
+/// Measured hardware numbers to fit, read from a small TOML table. Latencies
+/// are load-to-use cycles of one dependent load; unset entries are left
+/// alone.
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CalibrationTargets {
+    pub l1_hit: Option<f64>,
+    pub l2_hit: Option<f64>,
+    pub dram: Option<f64>,
+    pub smem: Option<f64>,
+    /// Bytes per cycle the chip pulls from DRAM with every core streaming
+    /// through lines that miss every cache.
+    pub dram_bandwidth: Option<f64>,
+}
+
+/// How the probes are run.
+#[derive(Debug, Clone, Copy)]
+pub struct CalibrationOptions {
+    /// Timed loads per latency probe.
+    pub samples: u64,
+    /// Machine the bandwidth stream runs on; latencies are probed from a
+    /// single core.
+    pub num_clusters: usize,
+    pub cores_per_cluster: usize,
+}
+
+impl Default for CalibrationOptions {
+    fn default() -> Self {
+        Self {
+            samples: 64,
+            num_clusters: 1,
+            cores_per_cluster: 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Metric {
+    Smem,
+    L1Hit,
+    L2Hit,
+    Dram,
+    DramBandwidth,
+}
+
+impl Metric {
+    /// Fitting order: each latency includes the levels inside it, and the
+    /// bandwidth a core sustains depends on the DRAM latency.
+    const ALL: [Metric; 5] = [
+        Metric::Smem,
+        Metric::L1Hit,
+        Metric::L2Hit,
+        Metric::Dram,
+        Metric::DramBandwidth,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Metric::Smem => "smem",
+            Metric::L1Hit => "l1_hit",
+            Metric::L2Hit => "l2_hit",
+            Metric::Dram => "dram",
+            Metric::DramBandwidth => "dram_bandwidth",
+        }
+    }
+
+    fn target(self, targets: &CalibrationTargets) -> Option<f64> {
+        match self {
+            Metric::Smem => targets.smem,
+            Metric::L1Hit => targets.l1_hit,
+            Metric::L2Hit => targets.l2_hit,
+            Metric::Dram => targets.dram,
+            Metric::DramBandwidth => targets.dram_bandwidth,
+        }
+    }
+
+    /// Parameters turned to fit this metric, in the order they are tried.
+    fn knobs(self) -> &'static [Knob] {
+        match self {
+            Metric::Smem => &[Knob::SmemBankLatency],
+            Metric::L1Hit => &[Knob::L1DataLatency],
+            Metric::L2Hit => &[Knob::L2DataLatency],
+            Metric::Dram => &[Knob::DramLatency],
+            // Outstanding misses usually cap a stream before DRAM does.
+            Metric::DramBandwidth => &[
+                Knob::DramBytesPerCycle,
+                Knob::L2Mshrs,
+                Knob::L1Mshrs,
+                Knob::L0Mshrs,
+            ],
+        }
+    }
+
+    fn measure(
+        self,
+        config: &CoreGraphConfig,
+        options: &CalibrationOptions,
+    ) -> Result<f64, String> {
+        let samples = options.samples;
+        let chase = |target| probe_level(config, target, samples).map(|c| c.avg_latency);
+        match self {
+            Metric::Smem => measure_smem_latency(config, samples),
+            Metric::L1Hit => chase("L1"),
+            Metric::L2Hit => chase("L2"),
+            Metric::Dram => chase("DRAM"),
+            Metric::DramBandwidth => measure_dram_bandwidth(
+                config,
+                options.num_clusters.max(1),
+                options.cores_per_cluster.max(1),
+            ),
+        }
+    }
+}
+
+/// A timing parameter the calibrator can turn. Every metric grows with its
+/// knobs, which is what the search relies on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Knob {
+    SmemBankLatency,
+    L1DataLatency,
+    L2DataLatency,
+    DramLatency,
+    DramBytesPerCycle,
+    L0Mshrs,
+    L1Mshrs,
+    L2Mshrs,
+}
+
+impl Knob {
+    fn path(self) -> &'static str {
+        match self {
+            Knob::SmemBankLatency => "smem.bank.base_latency",
+            Knob::L1DataLatency => "gmem.levels[1].data.base_latency",
+            Knob::L2DataLatency => "gmem.levels[2].data.base_latency",
+            Knob::DramLatency => "gmem.nodes.dram.base_latency",
+            Knob::DramBytesPerCycle => "gmem.nodes.dram.bytes_per_cycle",
+            Knob::L0Mshrs => "gmem.levels[0].mshr_capacity",
+            Knob::L1Mshrs => "gmem.levels[1].mshr_capacity",
+            Knob::L2Mshrs => "gmem.levels[2].mshr_capacity",
+        }
+    }
+
+    fn range(self) -> (u64, u64) {
+        match self {
+            Knob::SmemBankLatency
+            | Knob::L1DataLatency
+            | Knob::L2DataLatency
+            | Knob::DramLatency => (0, 4096),
+            Knob::DramBytesPerCycle | Knob::L0Mshrs | Knob::L1Mshrs | Knob::L2Mshrs => (1, 1024),
+        }
+    }
+
+    /// Index into `gmem.levels` of the cache level the knob belongs to.
+    fn level(self) -> Option<usize> {
+        match self {
+            Knob::L0Mshrs => Some(0),
+            Knob::L1DataLatency | Knob::L1Mshrs => Some(1),
+            Knob::L2DataLatency | Knob::L2Mshrs => Some(2),
+            _ => None,
+        }
+    }
+
+    fn get(self, config: &CoreGraphConfig) -> u64 {
+        let gmem = &config.memory.gmem;
+        if let Some(idx) = self.level() {
+            let level = gmem.levels.get(idx).cloned().unwrap_or_default();
+            return match self {
+                Knob::L1DataLatency | Knob::L2DataLatency => level.data.base_latency,
+                _ => level.effective_mshr_capacity() as u64,
+            };
+        }
+        match self {
+            Knob::SmemBankLatency => config.memory.smem.bank.base_latency,
+            Knob::DramLatency => gmem.nodes.dram.base_latency,
+            _ => gmem.nodes.dram.bytes_per_cycle as u64,
+        }
+    }
+
+    fn set(self, config: &mut CoreGraphConfig, value: u64) {
+        let gmem = &mut config.memory.gmem;
+        if let Some(idx) = self.level() {
+            // A level the config does not have leaves nothing to turn.
+            if let Some(level) = gmem.levels.get_mut(idx) {
+                match self {
+                    Knob::L1DataLatency | Knob::L2DataLatency => level.data.base_latency = value,
+                    _ => level.mshr_capacity = Some(value as usize),
+                }
+            }
+            return;
+        }
+        match self {
+            Knob::SmemBankLatency => config.memory.smem.bank.base_latency = value,
+            Knob::DramLatency => gmem.nodes.dram.base_latency = value,
+            _ => gmem.nodes.dram.bytes_per_cycle = value as u32,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ParameterChange {
+    pub path: &'static str,
+    pub before: u64,
+    pub after: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CalibrationFit {
+    pub metric: &'static str,
+    pub target: f64,
+    /// Measured with the config as given.
+    pub before: f64,
+    /// Measured with the calibrated config.
+    pub after: f64,
+    pub changes: Vec<ParameterChange>,
+}
+
+impl CalibrationFit {
+    pub fn error(&self) -> f64 {
+        if self.target == 0.0 {
+            self.after
+        } else {
+            (self.after - self.target) / self.target
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CalibrationReport {
+    pub fits: Vec<CalibrationFit>,
+    pub warnings: Vec<String>,
+}
+
+impl CalibrationReport {
+    /// Writes every changed parameter into `timing`, the merged `[timing]`
+    /// value the config was read from.
+    pub fn apply_to(&self, timing: &mut Value) -> Result<(), String> {
+        for change in self.fits.iter().flat_map(|fit| &fit.changes) {
+            set_path(timing, change.path, change.after as i64)?;
+        }
+        Ok(())
+    }
+}
+
+/// Searches the parameters behind each target in `targets` so the built-in
+/// probes measure as close to it as they can, and returns the tuned config.
+///
+/// Targets are fitted one after another, inner levels first, each by a
+/// binary search over its knobs with the earlier fits held. A target the
+/// knobs cannot reach is left at the closest value and reported as a
+/// warning.
+pub fn calibrate(
+    config: &CoreGraphConfig,
+    targets: &CalibrationTargets,
+    options: &CalibrationOptions,
+) -> (CoreGraphConfig, CalibrationReport) {
+    let mut config = config.clone();
+    let mut fits = Vec::new();
+    let mut warnings = Vec::new();
+    for metric in Metric::ALL {
+        let Some(target) = metric.target(targets) else {
+            continue;
+        };
+        let before = match metric.measure(&config, options) {
+            Ok(value) => value,
+            Err(err) => {
+                warnings.push(format!("{}: {err}", metric.name()));
+                continue;
+            }
+        };
+        let mut after = before;
+        let mut changes: Vec<ParameterChange> = metric
+            .knobs()
+            .iter()
+            .map(|knob| ParameterChange {
+                path: knob.path(),
+                before: knob.get(&config),
+                after: knob.get(&config),
+            })
+            .collect();
+        // A second pass lets an earlier knob settle once a later one has
+        // lifted the limit it was up against.
+        'passes: for _ in 0..2 {
+            for (knob, change) in metric.knobs().iter().zip(&mut changes) {
+                if (after - target).abs() <= 0.5 {
+                    break 'passes;
+                }
+                match fit_knob(&mut config, metric, *knob, target, options) {
+                    Ok(measured) => after = measured,
+                    Err(err) => {
+                        warnings.push(format!("{}: {err}", metric.name()));
+                        break 'passes;
+                    }
+                }
+                change.after = knob.get(&config);
+            }
+        }
+        changes.retain(|change| change.after != change.before);
+        let fit = CalibrationFit {
+            metric: metric.name(),
+            target,
+            before,
+            after,
+            changes,
+        };
+        if fit.error().abs() > 0.05 {
+            warnings.push(format!(
+                "{}: closest fit {:.1} is {:+.0}% off the target {:.1}",
+                fit.metric,
+                fit.after,
+                100.0 * fit.error(),
+                fit.target
+            ));
+        }
+        fits.push(fit);
+    }
+    (config, CalibrationReport { fits, warnings })
+}
+
+/// Sets `knob` to the value that brings `metric` closest to `target` and
+/// returns what `metric` measures there. The current value is kept unless
+/// another one lands strictly closer, so a knob the metric does not respond
+/// to is left alone.
+fn fit_knob(
+    config: &mut CoreGraphConfig,
+    metric: Metric,
+    knob: Knob,
+    target: f64,
+    options: &CalibrationOptions,
+) -> Result<f64, String> {
+    let mut measured = BTreeMap::new();
+    let mut measure = |value: u64| -> Result<f64, String> {
+        if let Some(&m) = measured.get(&value) {
+            return Ok(m);
+        }
+        let mut trial = config.clone();
+        knob.set(&mut trial, value);
+        let m = metric.measure(&trial, options)?;
+        measured.insert(value, m);
+        Ok(m)
+    };
+    // Smallest value that reaches the target; the one below it may land
+    // closer.
+    let (mut lo, mut hi) = knob.range();
+    while lo < hi {
+        let mid = lo + (hi - lo) / 2;
+        if measure(mid)? >= target {
+            hi = mid;
+        } else {
+            lo = mid + 1;
+        }
+    }
+    let current = knob.get(config);
+    let mut best = (current, measure(current)?);
+    for value in [Some(lo), lo.checked_sub(1)].into_iter().flatten() {
+        if value < knob.range().0 {
+            continue;
+        }
+        let m = measure(value)?;
+        if (m - target).abs() < (best.1 - target).abs() {
+            best = (value, m);
+        }
+    }
+    knob.set(config, best.0);
+    Ok(best.1)
+}
+
+/// Average cycles from issue to completion of `samples` back-to-back
+/// single-lane SMEM loads.
+fn measure_smem_latency(config: &CoreGraphConfig, samples: u64) -> Result<f64, String> {
+    let mut graph: FlowGraph<CoreFlowPayload> = FlowGraph::new();
+    let mut smem = SmemSubgraph::attach(&mut graph, &config.memory.smem);
+    let samples = samples.max(1);
+    let mut now: Cycle = 0;
+    let mut total = 0;
+    for _ in 0..samples {
+        let issued_at = now;
+        let mut request = SmemRequest::new(0, 4, 0x1, false, 0);
+        loop {
+            match smem.issue(&mut graph, now, request) {
+                Ok(_) => break,
+                Err(reject) => request = reject.payload,
+            }
+            graph.tick(now);
+            now += 1;
+            if now - issued_at > PROBE_TIMEOUT {
+                return Err("smem load was never accepted".to_string());
+            }
+        }
+        let completion = loop {
+            graph.tick(now);
+            smem.collect_completions(&mut graph, now);
+            if let Some(completion) = smem.completions.pop_front() {
+                break completion;
+            }
+            now += 1;
+            if now - issued_at > PROBE_TIMEOUT {
+                return Err("smem load never completed".to_string());
+            }
+        };
+        total += completion.completed_at.saturating_sub(issued_at);
+        now = now.max(completion.completed_at) + 1;
+    }
+    Ok(total as f64 / samples as f64)
+}
+
+/// Bytes per cycle the chip moves from DRAM with every core issuing a load
+/// to a new L2 line each cycle the hierarchy accepts one.
+fn measure_dram_bandwidth(
+    config: &CoreGraphConfig,
+    num_clusters: usize,
+    cores_per_cluster: usize,
+) -> Result<f64, String> {
+    let memory = &config.memory;
+    let line = memory.gmem.policy.l2_line_bytes.max(4) as u64;
+    let mut graph = ClusterGmemGraph::new_with_regions(
+        memory.gmem.clone(),
+        memory.map.clone(),
+        num_clusters,
+        cores_per_cluster,
+    );
+    let cores = num_clusters * cores_per_cluster;
+    let total = STREAM_LINES * cores as u64;
+    let mut issued = vec![0; cores];
+    let mut completed = 0;
+    let mut last_completion = 0;
+    let mut now: Cycle = 0;
+    while completed < total {
+        for (core, issued) in issued.iter_mut().enumerate() {
+            if *issued == STREAM_LINES {
+                continue;
+            }
+            let mut request = GmemRequest::new(0, 4, 0x1, true);
+            request.cluster_id = core / cores_per_cluster;
+            request.addr = STREAM_BASE + (core as u64 * STREAM_LINES + *issued) * line;
+            if graph.issue(core, now, request).is_ok() {
+                *issued += 1;
+            }
+        }
+        graph.tick(now);
+        for core in 0..cores {
+            while let Some(completion) = graph.pop_completion(core) {
+                completed += 1;
+                last_completion = last_completion.max(completion.completed_at);
+            }
+        }
+        now += 1;
+        if now > PROBE_TIMEOUT {
+            return Err(format!(
+                "bandwidth stream finished {completed} of {total} loads"
+            ));
+        }
+    }
+    Ok((total * line) as f64 / last_completion.max(1) as f64)
+}
+
+/// Sets a dotted `path` such as `gmem.levels[1].data.base_latency`,
+/// creating missing tables. Array elements must already exist.
+fn set_path(root: &mut Value, path: &str, value: i64) -> Result<(), String> {
+    let mut node = root;
+    let mut segments = path.split('.').peekable();
+    while let Some(segment) = segments.next() {
+        let (key, index) = match segment.split_once('[') {
+            Some((key, rest)) => {
+                let index = rest
+                    .trim_end_matches(']')
+                    .parse::<usize>()
+                    .map_err(|_| format!("bad index in {path}"))?;
+                (key, Some(index))
+            }
+            None => (segment, None),
+        };
+        let Value::Table(table) = node else {
+            return Err(format!("{path}: {key} is not inside a table"));
+        };
+        if segments.peek().is_none() && index.is_none() {
+            table.insert(key.to_string(), Value::Integer(value));
+            return Ok(());
+        }
+        node = table
+            .entry(key)
+            .or_insert_with(|| Value::Table(toml::Table::new()));
+        if let Some(index) = index {
+            node = match node {
+                Value::Array(items) => items.get_mut(index),
+                _ => None,
+            }
+            .ok_or_else(|| {
+                format!("{path}: the config does not list {key}[{index}]; spell out the whole array to calibrate it")
+            })?;
+        }
+    }
+    Err(format!("{path}: empty path"))
+}
+
+impl fmt::Display for CalibrationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<15} {:>8} {:>8} {:>8}  changes",
+            "metric", "target", "before", "after"
+        )?;
+        for fit in &self.fits {
+            let changes = fit
+                .changes
+                .iter()
+                .map(|c| format!("{} {} -> {}", c.path, c.before, c.after))
+                .collect::<Vec<_>>()
+                .join(", ");
+            writeln!(
+                f,
+                "{:<15} {:>8.1} {:>8.1} {:>8.1}  {}",
+                fit.metric, fit.target, fit.before, fit.after, changes
+            )?;
+        }
+        for warning in &self.warnings {
+            writeln!(f, "warning: {warning}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn small_config() -> CoreGraphConfig {
+        let mut config = CoreGraphConfig::default();
+        let policy = &mut config.memory.gmem.policy;
+        policy.l0_sets = 4;
+        policy.l1_sets = 8;
+        policy.l2_sets = 16;
+        config
+    }
+
+    #[test]
+    fn recovers_latencies_the_targets_came_from() {
+        let mut truth = small_config();
+        truth.memory.gmem.levels[2].data.base_latency += 5;
+        truth.memory.gmem.nodes.dram.base_latency += 40;
+        truth.memory.smem.bank.base_latency = 7;
+        let options = CalibrationOptions {
+            samples: 16,
+            ..CalibrationOptions::default()
+        };
+        let measure = |metric: Metric| metric.measure(&truth, &options).unwrap();
+        let targets = CalibrationTargets {
+            l2_hit: Some(measure(Metric::L2Hit)),
+            dram: Some(measure(Metric::Dram)),
+            smem: Some(measure(Metric::Smem)),
+            ..CalibrationTargets::default()
+        };
+
+        let (calibrated, report) = calibrate(&small_config(), &targets, &options);
+        assert!(report.warnings.is_empty(), "{:?}", report.warnings);
+        for knob in [
+            Knob::L2DataLatency,
+            Knob::DramLatency,
+            Knob::SmemBankLatency,
+        ] {
+            assert_eq!(knob.get(&calibrated), knob.get(&truth), "{}", knob.path());
+        }
+        assert!(report.fits.iter().all(|fit| fit.error().abs() < 0.01));
+    }
+
+    #[test]
+    fn writes_changes_into_the_timing_value() {
+        let mut timing: Value =
+            toml::from_str("[gmem]\nlevels = [{ banks = 1 }, { banks = 2 }, { banks = 1 }]\n")
+                .unwrap();
+        set_path(&mut timing, "gmem.levels[1].data.base_latency", 9).unwrap();
+        set_path(&mut timing, "gmem.nodes.dram.bytes_per_cycle", 24).unwrap();
+        assert_eq!(
+            timing["gmem"]["levels"][1]["data"]["base_latency"].as_integer(),
+            Some(9)
+        );
+        assert_eq!(
+            timing["gmem"]["nodes"]["dram"]["bytes_per_cycle"].as_integer(),
+            Some(24)
+        );
+        assert!(set_path(&mut timing, "gmem.levels[5].data.base_latency", 1).is_err());
+    }
+}
@@ -0,0 +1,161 @@
+//! In-memory checkpoints of a running simulation.
+//!
+//! `Sim::checkpoint` copies everything that decides how the run goes on:
+//! every cluster, core and warp, gmem and smem contents, the timing graphs
+//! with their queues, in-flight requests, request id allocators and stats
+//! window baselines, and the invariant counts. `Sim::restore` puts such a
+//! copy back, after which the run continues exactly as it did from the
+//! checkpoint. `sim.verify_checkpoint` checks that it does.
+//!
+//! Output sinks are not part of a checkpoint: perf logs, the trace db, VCD,
+//! pipeline view and scheduler log keep what they already wrote, and
+//! embedder hooks and issue policies stay registered.
+//!
+//! State shared between parts of the sim sits behind `Shared` handles. A
+//! plain `clone` of a handle shares the value; inside `deep_copy` every
+//! handle to one value maps to a single new copy of it, so a checkpoint
+//! shares state the way the original does.
+
+use std::any::Any;
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::ops::Deref;
+use std::sync::{Arc, PoisonError, RwLock};
+
+use crate::sim::gmem_race::GmemRaceChecker;
+use crate::sim::top::{CyclotronTop, TimingPhase};
+use crate::timeflow::invariants::CheckerState;
+
+/// Sim state saved by `Sim::checkpoint`. It can be restored any number of
+/// times.
+pub struct Checkpoint {
+    pub(crate) top: CyclotronTop,
+    pub(crate) gmem_race: Option<Shared<GmemRaceChecker>>,
+    pub(crate) cycle: u64,
+    pub(crate) kernel_finish_reported: bool,
+    pub(crate) kernel_finished_at: Vec<Option<u64>>,
+    pub(crate) timing_phases: VecDeque<TimingPhase>,
+    pub(crate) invariants: CheckerState,
+}
+
+impl Checkpoint {
+    /// Cycle the sim was at when the checkpoint was taken.
+    pub fn cycle(&self) -> u64 {
+        self.cycle
+    }
+}
+
+thread_local! {
+    /// Copies made so far by the `deep_copy` in progress, by the address of
+    /// the value they copy. `None` outside one.
+    static COPIES: RefCell<Option<HashMap<usize, Box<dyn Any>>>> = const { RefCell::new(None) };
+}
+
+/// Handle to state more than one part of the sim reads and writes, such as
+/// gmem or a cluster's gmem timing graph.
+pub struct Shared<T>(Arc<RwLock<T>>);
+
+impl<T> Shared<T> {
+    pub fn new(value: T) -> Self {
+        Self(Arc::new(RwLock::new(value)))
+    }
+}
+
+impl<T> From<Arc<RwLock<T>>> for Shared<T> {
+    fn from(handle: Arc<RwLock<T>>) -> Self {
+        Self(handle)
+    }
+}
+
+impl<T> Deref for Shared<T> {
+    type Target = Arc<RwLock<T>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Shared<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl<T: Clone + 'static> Clone for Shared<T> {
+    fn clone(&self) -> Self {
+        let key = Arc::as_ptr(&self.0) as *const () as usize;
+        let copied = COPIES.with_borrow(|copies| {
+            copies.as_ref().map(|copies| {
+                copies
+                    .get(&key)
+                    .and_then(|copy| copy.downcast_ref::<Arc<RwLock<T>>>())
+                    .cloned()
+            })
+        });
+        match copied {
+            None => Self(self.0.clone()),
+            Some(Some(copy)) => Self(copy),
+            Some(None) => {
+                let value = self
+                    .0
+                    .read()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .clone();
+                let copy = Arc::new(RwLock::new(value));
+                COPIES.with_borrow_mut(|copies| {
+                    if let Some(copies) = copies {
+                        copies.insert(key, Box::new(copy.clone()));
+                    }
+                });
+                Self(copy)
+            }
+        }
+    }
+}
+
+/// Clears the copy map even if a `clone` panics.
+struct CopyScope;
+
+impl Drop for CopyScope {
+    fn drop(&mut self) {
+        COPIES.set(None);
+    }
+}
+
+/// Runs `copy`, in which `Shared` handles clone to copies of their values,
+/// one per original, so what it clones shares nothing with the original.
+pub fn deep_copy<T>(copy: impl FnOnce() -> T) -> T {
+    COPIES.set(Some(HashMap::new()));
+    let _scope = CopyScope;
+    copy()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone)]
+    struct Pair {
+        a: Shared<u32>,
+        b: Shared<u32>,
+    }
+
+    #[test]
+    fn deep_copies_keep_sharing_but_not_with_the_original() {
+        let value = Shared::new(1);
+        let pair = Pair {
+            a: value.clone(),
+            b: value,
+        };
+        let copy = deep_copy(|| pair.clone());
+        *copy.a.write().unwrap() = 2;
+        assert_eq!(*copy.b.read().unwrap(), 2);
+        assert_eq!(*pair.a.read().unwrap(), 1);
+
+        // Outside `deep_copy`, a clone is another handle to the same value.
+        let shallow = pair.clone();
+        *shallow.a.write().unwrap() = 3;
+        assert_eq!(*pair.b.read().unwrap(), 3);
+    }
+}
@@ -5,14 +5,81 @@ use serde::de::DeserializeOwned;
 use serde::Deserialize;
 use toml::*;
 
+use crate::sim::gmem_race::GmemRaceConfig;
+use crate::sim::smem_order::SmemOrderCheck;
+use crate::timeflow::invariants::InvariantConfig;
+
 #[derive(Debug, Deserialize, Clone)]
 #[serde(default)]
+#[non_exhaustive]
 pub struct SimConfig {
     pub elf: PathBuf,
     pub log_level: u64,
     pub timeout: u64,
     pub trace: bool,
+    /// With `trace`, keep only the last this-many trace events in memory
+    /// and write them to the trace db on a panic, deadlock, timeout,
+    /// interrupt or kernel end, or when `Sim::dump_trace_ring` is called.
+    /// 0 writes every event as it happens.
+    pub trace_ring_events: u64,
     pub timing: bool,
+    /// Global ids (`cluster * num_cores + core`) of the cores that get a
+    /// timing graph under `timing`; the others run functionally. Every core
+    /// is timed when unset.
+    pub timed_cores: Option<Vec<usize>>,
+    /// Cycles at the start of the run whose stats go into a separate warmup
+    /// bucket instead of the reported totals.
+    pub warmup_cycles: u64,
+    /// Where to write a JSON dump of every warp's state if the run panics,
+    /// deadlocks or is interrupted. Disabled when unset.
+    pub state_dump: Option<PathBuf>,
+    /// Declare a deadlock after this many cycles without any warp changing
+    /// its PC or active mask. 0 disables the check.
+    pub deadlock_cycles: u64,
+    /// Cycles `Sim::drain_all` waits for the timing model to empty before
+    /// giving up.
+    pub drain_cycles: u64,
+    /// Number of clusters. Raised to fit every cluster named in `kernels`.
+    pub num_clusters: usize,
+    /// Kernels to run side by side on disjoint sets of clusters, sharing
+    /// the L2/DRAM path. Clusters no kernel claims run `elf`.
+    pub kernels: Vec<KernelConfig>,
+    /// Address (`host:port`) to serve Prometheus metrics on while the run
+    /// is going. Needs the `metrics-server` feature; disabled when unset.
+    pub metrics_addr: Option<String>,
+    /// Cycles between metrics snapshots.
+    pub metrics_interval: u64,
+    pub vcd: VcdConfig,
+    pub pipeview: PipeviewConfig,
+    pub sched_log: SchedLogConfig,
+    /// A scheduler log (see `sim::sched_log`) whose granted warps every
+    /// timed core issues in place of the built-in scheduler's choice, on
+    /// the cycles it has a record for. Disabled when unset.
+    pub sched_replay: Option<PathBuf>,
+    /// Check that SMEM loads see other warps' stores only across a barrier
+    /// (`count` or `panic`); see `sim::smem_order`. Not a stat, so fast
+    /// mode leaves it on.
+    pub smem_order: SmemOrderCheck,
+    /// Check gmem accesses for races between warps; see `sim::gmem_race`.
+    /// Not a stat, so fast mode leaves it on.
+    pub gmem_race: GmemRaceConfig,
+    /// What a failed timing-model invariant does, per category (`ignore`,
+    /// `log` or `abort`); see `timeflow::invariants`.
+    pub invariants: InvariantConfig,
+    /// Checkpoint the run at this cycle, and once it finishes, restore the
+    /// checkpoint and finish again. Panics unless both runs end on the same
+    /// cycle with the same timing summaries and gmem contents. Disabled
+    /// when unset.
+    pub verify_checkpoint: Option<u64>,
+    /// Skip every stat, log and trace sink that does not change timing:
+    /// the perf log, trace db, VCD, pipeline view, scheduler log, metrics
+    /// server and per-cycle stat sampling. The end-of-run timing summary is
+    /// skipped.
+    /// Always on when built with the `fast` feature.
+    pub fast: bool,
+    /// Taken from the top-level `[log]` section.
+    #[serde(skip)]
+    pub log: LogConfig,
 }
 
 pub trait Config: DeserializeOwned + Default {
@@ -36,15 +103,233 @@ impl Default for SimConfig {
             log_level: 0,
             timeout: 10000000,
             trace: false,
+            trace_ring_events: 0,
             timing: false,
+            timed_cores: None,
+            warmup_cycles: 0,
+            state_dump: None,
+            deadlock_cycles: 0,
+            drain_cycles: 100000,
+            num_clusters: 1,
+            kernels: Vec::new(),
+            metrics_addr: None,
+            metrics_interval: 1000,
+            vcd: VcdConfig::default(),
+            pipeview: PipeviewConfig::default(),
+            sched_log: SchedLogConfig::default(),
+            sched_replay: None,
+            smem_order: SmemOrderCheck::Off,
+            gmem_race: GmemRaceConfig::default(),
+            invariants: InvariantConfig::default(),
+            verify_checkpoint: None,
+            fast: false,
+            log: LogConfig::default(),
+        }
+    }
+}
+
+/// One `[[sim.kernels]]` entry: an ELF and the clusters that run it.
+#[derive(Debug, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct KernelConfig {
+    /// Label used in logs and the perf summary; defaults to the ELF's file
+    /// name.
+    pub name: String,
+    pub elf: PathBuf,
+    pub clusters: Vec<usize>,
+    /// Overrides `muon.start_pc` for this kernel's cores.
+    pub start_pc: Option<u32>,
+}
+
+/// `[sim.vcd]`: signals to record each cycle to a VCD waveform file.
+#[derive(Debug, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct VcdConfig {
+    /// Disabled when unset.
+    pub path: Option<PathBuf>,
+    /// Signal names, e.g. `warp.pc` or `gmem.dram*`; see `sim::vcd`.
+    pub signals: Vec<String>,
+    /// Global ids (`cluster * num_cores + core`) of the cores to record.
+    /// Every core is recorded when unset.
+    pub cores: Option<Vec<usize>>,
+}
+
+/// `[sim.pipeview]`: per-instruction pipeline stage log in gem5's
+/// O3PipeView format; see `sim::pipeview`. Needs timing.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct PipeviewConfig {
+    /// Disabled when unset.
+    pub path: Option<PathBuf>,
+    /// Global ids (`cluster * num_cores + core`) of the cores to record.
+    /// Every core is recorded when unset.
+    pub cores: Option<Vec<usize>>,
+    /// gem5 ticks written per cycle.
+    pub ticks_per_cycle: u64,
+}
+
+impl Default for PipeviewConfig {
+    fn default() -> Self {
+        Self {
+            path: None,
+            cores: None,
+            ticks_per_cycle: 1000,
         }
     }
 }
 
+/// `[sim.sched_log]`: binary (or with `log.format = "parquet"`, Parquet)
+/// per-cycle log of issue scheduler decisions; see `sim::sched_log`. Needs
+/// timing.
+#[derive(Debug, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct SchedLogConfig {
+    /// Disabled when unset.
+    pub path: Option<PathBuf>,
+    /// Global ids (`cluster * num_cores + core`) of the cores to record.
+    /// Every core is recorded when unset.
+    pub cores: Option<Vec<usize>>,
+}
+
+impl SimConfig {
+    /// Whether stats, logs and traces are collected; see `fast`.
+    pub fn stats_enabled(&self) -> bool {
+        crate::timeflow::STATS_COMPILED && !self.fast
+    }
+
+    pub fn cluster_count(&self) -> usize {
+        self.kernels
+            .iter()
+            .flat_map(|kernel| kernel.clusters.iter().map(|&id| id + 1))
+            .fold(self.num_clusters.max(1), usize::max)
+    }
+
+    /// Every kernel to launch, with names filled in. Clusters not claimed by
+    /// a `[[sim.kernels]]` entry are gathered into a kernel running `elf`.
+    /// Panics if two kernels claim the same cluster.
+    pub fn kernel_placement(&self) -> Vec<KernelConfig> {
+        let num_clusters = self.cluster_count();
+        let mut owner: Vec<Option<usize>> = vec![None; num_clusters];
+        let mut kernels = Vec::with_capacity(self.kernels.len() + 1);
+        for (idx, kernel) in self.kernels.iter().enumerate() {
+            for &cluster in &kernel.clusters {
+                if let Some(other) = owner[cluster].replace(idx) {
+                    assert_eq!(
+                        other, idx,
+                        "cluster {cluster} is assigned to more than one kernel"
+                    );
+                }
+            }
+            let mut kernel = kernel.clone();
+            kernel.clusters.sort_unstable();
+            kernel.clusters.dedup();
+            if kernel.name.is_empty() {
+                kernel.name = elf_name(&kernel.elf);
+            }
+            kernels.push(kernel);
+        }
+        let rest: Vec<usize> = (0..num_clusters)
+            .filter(|&cluster| owner[cluster].is_none())
+            .collect();
+        if !rest.is_empty() {
+            kernels.push(KernelConfig {
+                name: elf_name(&self.elf),
+                elf: self.elf.clone(),
+                clusters: rest,
+                start_pc: None,
+            });
+        }
+        kernels.retain(|kernel| !kernel.clusters.is_empty());
+        kernels
+    }
+}
+
+fn elf_name(elf: &std::path::Path) -> String {
+    elf.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "kernel".to_string())
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LogFormat {
+    /// One JSON object per line.
+    #[default]
+    Jsonl,
+    /// zstd-compressed Parquet with one column per (flattened) field.
+    /// Needs the `parquet` feature.
+    Parquet,
+}
+
+impl LogFormat {
+    /// The format this build writes in place of `self`: JSON lines for
+    /// Parquet without the `parquet` feature.
+    pub fn supported(self) -> Self {
+        if cfg!(feature = "parquet") {
+            self
+        } else {
+            Self::Jsonl
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self.supported() {
+            Self::Jsonl => "jsonl",
+            Self::Parquet => "parquet",
+        }
+    }
+}
+
+/// Output settings for the streaming perf logs.
+#[derive(Debug, Deserialize, Clone, Copy, Default)]
+#[serde(default)]
+#[non_exhaustive]
+pub struct LogConfig {
+    pub format: LogFormat,
+    pub sqlite: SqliteLogConfig,
+    /// Records the gmem graph nodes every `path_sample`-th request went
+    /// through, with the cycles it entered and left each, in
+    /// `request_paths`; 0 records none.
+    pub path_sample: u64,
+}
+
+/// `[log.sqlite]`: a `perf.sqlite` database in the run directory, next to
+/// the streaming logs, for querying with SQL.
 #[derive(Debug, Deserialize, Clone, Copy)]
+#[serde(default)]
+#[non_exhaustive]
+pub struct SqliteLogConfig {
+    pub enabled: bool,
+    /// Cycles between gmem graph node/edge occupancy snapshots; 0 turns
+    /// them off.
+    pub snapshot_interval: u64,
+    /// One row per request lifecycle event (issue, reject, complete, ...).
+    pub events: bool,
+    /// Rows buffered before they are written in one transaction.
+    pub batch_rows: usize,
+}
+
+impl Default for SqliteLogConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            snapshot_interval: 1000,
+            events: true,
+            batch_rows: 4096,
+        }
+    }
+}
+
+impl Config for LogConfig {}
+
+#[derive(Debug, Deserialize, Clone, Copy)]
+#[serde(default)]
 pub struct MemConfig {
     pub io_cout_addr: usize,
     pub io_cout_size: usize,
+    /// Physical gmem address width, 32..=64. Above 32, global loads and
+    /// stores take bits [63:32] from the lane's `gmem_window` CSR (0x800).
+    pub address_bits: u32,
 }
 
 impl Config for MemConfig {}
@@ -54,6 +339,51 @@ impl Default for MemConfig {
         Self {
             io_cout_addr: 0xFF080000,
             io_cout_size: 64,
+            address_bits: 32,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kernel(name: &str, clusters: &[usize]) -> KernelConfig {
+        KernelConfig {
+            name: name.to_string(),
+            elf: PathBuf::from(format!("{name}.elf")),
+            clusters: clusters.to_vec(),
+            start_pc: None,
         }
     }
+
+    #[test]
+    fn unclaimed_clusters_run_the_default_elf() {
+        let config = SimConfig {
+            elf: PathBuf::from("test/main.elf"),
+            num_clusters: 2,
+            kernels: vec![kernel("a", &[1]), kernel("b", &[3])],
+            ..SimConfig::default()
+        };
+        assert_eq!(config.cluster_count(), 4);
+        let placement = config.kernel_placement();
+        let layout: Vec<_> = placement
+            .iter()
+            .map(|kernel| (kernel.name.as_str(), kernel.clusters.clone()))
+            .collect();
+        assert_eq!(
+            layout,
+            [("a", vec![1]), ("b", vec![3]), ("main.elf", vec![0, 2])]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "more than one kernel")]
+    fn overlapping_kernels_are_rejected() {
+        let config = SimConfig {
+            kernels: vec![kernel("a", &[0]), kernel("b", &[0])],
+            ..SimConfig::default()
+        };
+        config.kernel_placement();
+    }
 }
@@ -0,0 +1,388 @@
+use crate::base::behavior::*;
+use crate::muon::lane_mask::LaneMask;
+use crate::sim::top::Sim;
+use crate::sim::trace::Line;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Identifies one warp's commit stream within a simulation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+pub struct WarpKey {
+    pub cluster: usize,
+    pub core: usize,
+    pub warp: usize,
+}
+
+/// Architecturally visible result of one committed instruction.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CommitRecord {
+    pub pc: u32,
+    pub raw: u64,
+    pub tmask: LaneMask,
+    pub rd_addr: u8,
+    pub rd_data: Vec<Option<u32>>,
+}
+
+impl From<&Line> for CommitRecord {
+    fn from(line: &Line) -> Self {
+        Self {
+            pc: line.pc,
+            raw: line.raw,
+            tmask: line.tmask,
+            rd_addr: line.rd_addr,
+            rd_data: line.rd_data.clone(),
+        }
+    }
+}
+
+impl CommitRecord {
+    fn fold_into(&self, hash: u64) -> u64 {
+        const FNV_PRIME: u64 = 0x100_0000_01b3;
+        let mut hash = hash;
+        let mut mix = |word: u64| {
+            for byte in word.to_le_bytes() {
+                hash ^= byte as u64;
+                hash = hash.wrapping_mul(FNV_PRIME);
+            }
+        };
+        mix(self.pc as u64);
+        mix(self.raw);
+        mix(self.tmask.to_u64());
+        mix(self.rd_addr as u64);
+        for lane in &self.rd_data {
+            mix(lane.map_or(u64::MAX, |v| v as u64));
+        }
+        hash
+    }
+}
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+
+/// Gmem word observed in both simulations at the divergence point.
+#[derive(Debug, Clone, Serialize)]
+pub struct MemoryWord {
+    pub addr: u64,
+    pub a: u32,
+    pub b: u32,
+}
+
+/// Minimized reproduction of the first functional mismatch between two runs.
+#[derive(Debug, Clone, Serialize)]
+pub struct Divergence {
+    pub warp: WarpKey,
+    /// Index (0-based) of the first mismatching instruction in the warp's
+    /// commit stream.
+    pub index: u64,
+    /// First instruction of the checkpoint window that contained the mismatch.
+    pub window_start: u64,
+    /// Lowest and highest PC committed by side A in `[window_start, index]`.
+    pub pc_range: (u32, u32),
+    /// Cycle each side committed the instruction on, as `Sim::cycle` and
+    /// the retire hook report it.
+    pub cycle_a: Option<u64>,
+    pub cycle_b: Option<u64>,
+    pub commit_a: Option<CommitRecord>,
+    pub commit_b: Option<CommitRecord>,
+    /// Gmem words touched by the warp's core during the window, as seen by
+    /// each side when it reached the divergent instruction.
+    pub memory: Vec<MemoryWord>,
+}
+
+#[derive(Debug, Clone)]
+pub struct DiffOptions {
+    /// Number of committed instructions per warp between state comparisons.
+    pub interval: u64,
+    /// Give up after this many cycles.
+    pub max_cycles: u64,
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        Self {
+            interval: 1000,
+            max_cycles: 10_000_000,
+        }
+    }
+}
+
+#[derive(Default)]
+struct WarpDigest {
+    count: u64,
+    hash: u64,
+    checkpoints: Vec<u64>,
+    compared: usize,
+}
+
+/// Per-warp rolling hash of committed instructions, checkpointed every
+/// `interval` commits.
+struct CommitLog {
+    interval: u64,
+    warps: BTreeMap<WarpKey, WarpDigest>,
+}
+
+impl CommitLog {
+    fn new(interval: u64) -> Self {
+        Self {
+            interval: interval.max(1),
+            warps: BTreeMap::new(),
+        }
+    }
+
+    fn push(&mut self, key: WarpKey, record: &CommitRecord) {
+        let interval = self.interval;
+        let digest = self.warps.entry(key).or_insert_with(|| WarpDigest {
+            hash: FNV_OFFSET,
+            ..WarpDigest::default()
+        });
+        digest.hash = record.fold_into(digest.hash);
+        digest.count += 1;
+        if digest.count.is_multiple_of(interval) {
+            digest.checkpoints.push(digest.hash);
+        }
+    }
+
+    fn digest(&self, key: &WarpKey) -> (u64, u64) {
+        self.warps
+            .get(key)
+            .map_or((0, FNV_OFFSET), |d| (d.count, d.hash))
+    }
+}
+
+/// Drains every core's instruction and memory tracers, handing each commit
+/// and gmem access to the callbacks.
+fn drain_commits(
+    sim: &mut Sim,
+    mut on_commit: impl FnMut(WarpKey, &Line),
+    mut on_mem: impl FnMut(WarpKey, u64),
+) {
+    for (cluster_id, cluster) in sim.top.clusters.iter_mut().enumerate() {
+        for (core_id, core) in cluster.cores.iter_mut().enumerate() {
+            let num_warps = core.conf().num_warps;
+            for warp in 0..num_warps {
+                while let Some(line) = core.get_tracer_mut().consume(warp) {
+                    let key = WarpKey {
+                        cluster: cluster_id,
+                        core: core_id,
+                        warp,
+                    };
+                    on_commit(key, &line);
+                }
+            }
+            while let Some(line) = core.get_mem_tracer_mut().consume() {
+                if line.is_smem {
+                    continue;
+                }
+                let key = WarpKey {
+                    cluster: cluster_id,
+                    core: core_id,
+                    warp: line.warp_id as usize,
+                };
+                on_mem(key, line.address & !0x3);
+            }
+        }
+    }
+}
+
+/// Runs both simulations lockstep and returns the first warp whose
+/// checkpoint hashes disagree, together with the bracketing commit counts
+/// `(lo, hi)`: the first `lo` commits are known to match and the first `hi`
+/// are known to differ.
+fn find_window(a: &mut Sim, b: &mut Sim, options: &DiffOptions) -> Option<(WarpKey, u64, u64)> {
+    let interval = options.interval.max(1);
+    let mut log_a = CommitLog::new(interval);
+    let mut log_b = CommitLog::new(interval);
+
+    for _ in 0..options.max_cycles {
+        if a.finished() && b.finished() {
+            break;
+        }
+        a.tick();
+        b.tick();
+        drain_commits(a, |key, line| log_a.push(key, &line.into()), |_, _| {});
+        drain_commits(b, |key, line| log_b.push(key, &line.into()), |_, _| {});
+
+        for (key, digest_a) in log_a.warps.iter_mut() {
+            let Some(digest_b) = log_b.warps.get(key) else {
+                continue;
+            };
+            let common = digest_a.checkpoints.len().min(digest_b.checkpoints.len());
+            for i in digest_a.compared..common {
+                if digest_a.checkpoints[i] != digest_b.checkpoints[i] {
+                    let lo = i as u64 * interval;
+                    return Some((*key, lo, lo + interval));
+                }
+            }
+            digest_a.compared = common;
+        }
+    }
+
+    // Compare the partial tail windows, including warps that only one side
+    // ever ran.
+    let keys: BTreeSet<WarpKey> = log_a
+        .warps
+        .keys()
+        .chain(log_b.warps.keys())
+        .copied()
+        .collect();
+    keys.into_iter().find_map(|key| {
+        let (count_a, hash_a) = log_a.digest(&key);
+        let (count_b, hash_b) = log_b.digest(&key);
+        if count_a == count_b && hash_a == hash_b {
+            return None;
+        }
+        let lo = (count_a.min(count_b) / interval) * interval;
+        Some((key, lo, count_a.max(count_b).max(lo + 1)))
+    })
+}
+
+/// What one side looked like when `key` committed its `target`-th
+/// instruction.
+struct ProbeSide {
+    hash: Option<u64>,
+    cycle: Option<u64>,
+    last: Option<CommitRecord>,
+    pc_range: Option<(u32, u32)>,
+    touched: BTreeSet<u64>,
+}
+
+/// Re-runs one simulation from reset until `key` has committed `target`
+/// instructions, or the run ends.
+fn probe(
+    sim: &mut Sim,
+    key: WarpKey,
+    target: u64,
+    window_start: u64,
+    max_cycles: u64,
+) -> ProbeSide {
+    let mut log = CommitLog::new(u64::MAX);
+    let mut side = ProbeSide {
+        hash: None,
+        cycle: None,
+        last: None,
+        pc_range: None,
+        touched: BTreeSet::new(),
+    };
+    for _ in 0..max_cycles {
+        if sim.finished() {
+            break;
+        }
+        sim.tick();
+        let mut commits = Vec::new();
+        let mut accesses = Vec::new();
+        drain_commits(
+            sim,
+            |k, line| {
+                if k == key {
+                    commits.push(CommitRecord::from(line));
+                }
+            },
+            |k, addr| {
+                if k.cluster == key.cluster && k.core == key.core {
+                    accesses.push(addr);
+                }
+            },
+        );
+        if log.digest(&key).0 >= window_start {
+            side.touched.extend(accesses);
+        }
+        for record in commits {
+            let index = log.digest(&key).0;
+            log.push(key, &record);
+            if index >= window_start {
+                side.pc_range = Some(match side.pc_range {
+                    Some((lo, hi)) => (lo.min(record.pc), hi.max(record.pc)),
+                    None => (record.pc, record.pc),
+                });
+            }
+            if index + 1 == target {
+                side.hash = Some(log.digest(&key).1);
+                side.cycle = Some(sim.cycle());
+                side.last = Some(record);
+                return side;
+            }
+        }
+    }
+    side
+}
+
+/// Finds the smallest `n` in `(lo, hi]` for which `differs(n)` holds,
+/// assuming `differs` is monotonic and `differs(hi)` is true.
+pub fn bisect(mut lo: u64, mut hi: u64, mut differs: impl FnMut(u64) -> bool) -> u64 {
+    while hi - lo > 1 {
+        let mid = lo + (hi - lo) / 2;
+        if differs(mid) {
+            hi = mid;
+        } else {
+            lo = mid;
+        }
+    }
+    hi
+}
+
+/// Runs the simulations built by `make_a` and `make_b` lockstep, comparing
+/// per-warp committed state every `options.interval` instructions. On a
+/// mismatch, re-runs both from reset to bisect down to the first divergent
+/// instruction and returns a reproduction of it.
+pub fn find_divergence(
+    make_a: impl Fn() -> Sim,
+    make_b: impl Fn() -> Sim,
+    options: &DiffOptions,
+) -> Option<Divergence> {
+    let (key, lo, hi) = {
+        let mut a = make_a();
+        let mut b = make_b();
+        find_window(&mut a, &mut b, options)?
+    };
+    log::info!("diff: {key:?} diverges within commits ({lo}, {hi}]; bisecting");
+
+    let max_cycles = options.max_cycles;
+    let first = bisect(lo, hi, |n| {
+        let side_a = probe(&mut make_a(), key, n, lo, max_cycles);
+        let side_b = probe(&mut make_b(), key, n, lo, max_cycles);
+        side_a.hash.is_none() || side_a.hash != side_b.hash
+    });
+
+    let mut a = make_a();
+    let mut b = make_b();
+    let side_a = probe(&mut a, key, first, lo, max_cycles);
+    let side_b = probe(&mut b, key, first, lo, max_cycles);
+    let memory = side_a
+        .touched
+        .union(&side_b.touched)
+        .map(|&addr| MemoryWord {
+            addr,
+            a: u32::from_le_bytes(a.top.gmem_load(addr)),
+            b: u32::from_le_bytes(b.top.gmem_load(addr)),
+        })
+        .collect();
+
+    Some(Divergence {
+        warp: key,
+        index: first - 1,
+        window_start: lo,
+        pc_range: side_a.pc_range.or(side_b.pc_range).unwrap_or((0, 0)),
+        cycle_a: side_a.cycle,
+        cycle_b: side_b.cycle,
+        commit_a: side_a.last,
+        commit_b: side_b.last,
+        memory,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::bisect;
+
+    #[test]
+    fn bisect_finds_first_differing_prefix() {
+        for first in 1..=16 {
+            let mut probes = 0;
+            let found = bisect(0, 16, |n| {
+                probes += 1;
+                n >= first
+            });
+            assert_eq!(found, first);
+            assert!(probes <= 4);
+        }
+    }
+}
@@ -0,0 +1,110 @@
+use crate::muon::gmem::WarpTimingState;
+use crate::muon::lane_mask::LaneMask;
+use crate::muon::scheduler::WarpScheduleState;
+use crate::sim::metadata::RunMetadata;
+use serde::Serialize;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, Once};
+
+/// Instruction a warp had fetched into the instruction buffer on the last
+/// processed cycle.
+#[derive(Debug, Clone, Serialize)]
+pub struct IbufferEntryDump {
+    pub pc: u32,
+    pub raw: u64,
+    pub tmask: LaneMask,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WarpStateDump {
+    pub warp_id: usize,
+    #[serde(flatten)]
+    pub schedule: WarpScheduleState,
+    pub ibuffer: Option<IbufferEntryDump>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timing: Option<WarpTimingState>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CoreStateDump {
+    pub cluster_id: usize,
+    pub core_id: usize,
+    pub cycle: u64,
+    pub active_warps: u32,
+    pub stalled_warps: u32,
+    pub warps: Vec<WarpStateDump>,
+}
+
+/// Snapshot of every warp in the simulation, written when a run terminates
+/// abnormally.
+#[derive(Debug, Clone, Serialize)]
+pub struct SimStateDump {
+    pub reason: String,
+    pub cycle: u64,
+    pub finished: bool,
+    pub cores: Vec<CoreStateDump>,
+    pub metadata: RunMetadata,
+}
+
+impl SimStateDump {
+    pub fn write(&self, path: &Path) -> std::io::Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json)
+    }
+}
+
+static PANIC_HOOK: Once = Once::new();
+static LAST_PANIC: Mutex<Option<String>> = Mutex::new(None);
+static SIGNAL_HANDLER: Once = Once::new();
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Exit status of a run stopped by SIGINT or SIGTERM, as a shell reports a
+/// process killed by SIGINT.
+pub const INTERRUPTED_EXIT_CODE: i32 = 130;
+
+/// Chains a panic hook that remembers the panic message so it can be put in
+/// the state dump, and installs the signal handler. Safe to call more than
+/// once.
+pub fn install_handlers() {
+    PANIC_HOOK.call_once(|| {
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            if let Ok(mut last) = LAST_PANIC.lock() {
+                *last = Some(info.to_string());
+            }
+            previous(info);
+        }));
+    });
+    install_signal_handler();
+}
+
+/// Installs a SIGINT/SIGTERM handler that asks the simulate loop to stop
+/// and write its reports. A second signal exits right away. Safe to call
+/// more than once.
+pub fn install_signal_handler() {
+    SIGNAL_HANDLER.call_once(|| {
+        let handler = || {
+            if INTERRUPTED.swap(true, Ordering::SeqCst) {
+                std::process::exit(INTERRUPTED_EXIT_CODE);
+            }
+        };
+        if let Err(err) = ctrlc::set_handler(handler) {
+            log::warn!("failed to install SIGINT/SIGTERM handler: {err}");
+        }
+    });
+}
+
+pub fn take_panic_message() -> Option<String> {
+    LAST_PANIC.lock().ok().and_then(|mut last| last.take())
+}
+
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+/// Forgets a signal that already stopped a run, so the next run in the
+/// same process is not stopped by it too.
+pub fn clear_interrupted() {
+    INTERRUPTED.store(false, Ordering::SeqCst);
+}
@@ -1,20 +1,72 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
 use std::io::Write;
 
+use anyhow::bail;
+
 use crate::{
     base::mem::HasMemory,
     sim::{config::MemConfig, elf::ElfBackedMem},
 };
 
+/// Granularity at which memory above the flat vector is allocated, and at
+/// which a clone copies the flat vector.
+const HIGH_PAGE_BYTES: usize = 1 << 16;
+
+static ZERO_PAGE: [u8; HIGH_PAGE_BYTES] = [0; HIGH_PAGE_BYTES];
+
 /// Gigantic 4 GB vector to model memory space; relies on lazy allocation within OS to avoid actually
 /// causing memory pressure. Avoids hash-table lookup for every memory access
-#[derive(Debug, Clone)]
+///
+/// With `address_bits` above 32, addresses past the vector live in `high`, a
+/// sparse map of pages allocated on first write. Unwritten pages read as zero.
+#[derive(Debug)]
 pub struct FlatMemory {
     bytes: Vec<u8>,
+    high: HashMap<usize, Box<[u8]>>,
+    /// Pages of `bytes` ever written. A clone copies only these, so it
+    /// stays as lazily allocated as the original.
+    written: Vec<bool>,
     config: Option<MemConfig>,
 }
 
+impl Clone for FlatMemory {
+    fn clone(&self) -> Self {
+        let mut bytes = vec![0u8; self.bytes.len()];
+        for (page, _) in self
+            .written
+            .iter()
+            .enumerate()
+            .filter(|(_, &written)| written)
+        {
+            let start = page * HIGH_PAGE_BYTES;
+            let end = (start + HIGH_PAGE_BYTES).min(bytes.len());
+            bytes[start..end].copy_from_slice(&self.bytes[start..end]);
+        }
+        Self {
+            bytes,
+            high: self.high.clone(),
+            written: self.written.clone(),
+            config: self.config,
+        }
+    }
+}
+
 impl HasMemory for FlatMemory {
+    fn address_bits(&self) -> u32 {
+        self.config.map_or(32, |config| config.address_bits)
+    }
+
     fn read_impl(&self, addr: usize, n: usize) -> Result<&[u8], anyhow::Error> {
+        if addr >= self.bytes.len() && self.address_bits() > 32 {
+            let (page, offset) = Self::high_page(addr, n)?;
+            let page = self
+                .high
+                .get(&page)
+                .map_or(&ZERO_PAGE[..], |page| &page[..]);
+            return Ok(&page[offset..offset + n]);
+        }
         Ok(self.bytes[addr..addr + n].try_into().unwrap())
     }
 
@@ -36,8 +88,19 @@ impl HasMemory for FlatMemory {
             }
         }
 
+        if addr >= self.bytes.len() && self.address_bits() > 32 {
+            let (page, offset) = Self::high_page(addr, data.len())?;
+            let page = self
+                .high
+                .entry(page)
+                .or_insert_with(|| vec![0u8; HIGH_PAGE_BYTES].into_boxed_slice());
+            page[offset..offset + data.len()].copy_from_slice(data);
+            return Ok(());
+        }
+
         let bytes = &mut self.bytes[addr..addr + data.len()];
         bytes.copy_from_slice(data);
+        self.mark_written(addr, data.len());
 
         Ok(())
     }
@@ -45,13 +108,41 @@ impl HasMemory for FlatMemory {
 
 impl FlatMemory {
     pub fn new(config: Option<MemConfig>) -> Self {
-        let bytes = vec![0u8; 1 << 32];
-        Self { bytes, config }
+        Self::new_with_size(1 << 32, config)
     }
 
     pub fn new_with_size(size: usize, config: Option<MemConfig>) -> Self {
+        if let Some(config) = config {
+            assert!(
+                (32..=64).contains(&config.address_bits),
+                "mem.address_bits must be between 32 and 64, got {}",
+                config.address_bits
+            );
+        }
         let bytes = vec![0u8; size];
-        Self { bytes, config }
+        Self {
+            bytes,
+            high: HashMap::new(),
+            written: vec![false; size.div_ceil(HIGH_PAGE_BYTES)],
+            config,
+        }
+    }
+
+    fn mark_written(&mut self, addr: usize, n: usize) {
+        if n == 0 {
+            return;
+        }
+        let pages = addr / HIGH_PAGE_BYTES..=(addr + n - 1) / HIGH_PAGE_BYTES;
+        self.written[pages].fill(true);
+    }
+
+    /// Page number and in-page offset of a high-memory access.
+    fn high_page(addr: usize, n: usize) -> Result<(usize, usize), anyhow::Error> {
+        let offset = addr % HIGH_PAGE_BYTES;
+        if offset + n > HIGH_PAGE_BYTES {
+            bail!("memory access of size {} @ {:#x} crosses a page", n, addr);
+        }
+        Ok((addr / HIGH_PAGE_BYTES, offset))
     }
 
     pub fn copy_elf(&mut self, elf: &ElfBackedMem) {
@@ -78,10 +169,121 @@ impl FlatMemory {
                 "copy_elf: copy dest ({gpu_start:x}..{gpu_end:x}) out-of-range of FlatMemory (0..{len:x})"
             ));
             bytes.copy_from_slice(&data);
+            self.mark_written(gpu_start, gpu_end - gpu_start);
         }
     }
 
     pub fn size_bytes(&self) -> usize {
         self.bytes.len()
     }
+
+    /// Hash of everything ever written; equal for memories that saw the
+    /// same writes.
+    pub fn digest(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        for (page, _) in self
+            .written
+            .iter()
+            .enumerate()
+            .filter(|(_, &written)| written)
+        {
+            let start = page * HIGH_PAGE_BYTES;
+            let end = (start + HIGH_PAGE_BYTES).min(self.bytes.len());
+            page.hash(&mut hasher);
+            self.bytes[start..end].hash(&mut hasher);
+        }
+        let mut high: Vec<_> = self.high.iter().collect();
+        high.sort_unstable_by_key(|&(page, _)| *page);
+        high.hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::base::mem::Endianness;
+
+    fn wide_memory(address_bits: u32) -> FlatMemory {
+        let config = MemConfig {
+            address_bits,
+            ..MemConfig::default()
+        };
+        FlatMemory::new_with_size(0x1000, Some(config))
+    }
+
+    #[test]
+    fn high_addresses_are_backed_sparsely() {
+        let mut mem = wide_memory(40);
+        let addr = 0x12_3456_7890;
+        assert_eq!(mem.read_n::<4>(addr).unwrap(), [0; 4]);
+        mem.write_n(addr, [1, 2, 3, 4]).unwrap();
+        assert_eq!(mem.read_n::<4>(addr).unwrap(), [1, 2, 3, 4]);
+        assert_eq!(mem.read_n::<4>(addr & 0xffff_ffff).unwrap(), [0; 4]);
+        assert_eq!(mem.high.len(), 1);
+    }
+
+    #[test]
+    fn clones_copy_only_written_pages() {
+        let mut mem = FlatMemory::new_with_size(4 * HIGH_PAGE_BYTES, None);
+        let addr = HIGH_PAGE_BYTES + 8;
+        mem.write_n(addr, [1, 2, 3, 4]).unwrap();
+        let copy = mem.clone();
+        mem.write_n(addr, [5; 4]).unwrap();
+        assert_eq!(copy.read_n::<4>(addr).unwrap(), [1, 2, 3, 4]);
+        assert_eq!(copy.written, [false, true, false, false]);
+    }
+
+    #[test]
+    #[should_panic(expected = "40-bit address range")]
+    fn accesses_past_address_bits_panic() {
+        let mem = wide_memory(40);
+        let _ = mem.read_n::<4>(1 << 40);
+    }
+
+    #[test]
+    fn values_of_any_width_round_trip_in_either_byte_order() {
+        let mut mem = wide_memory(32);
+        for n in 1..=8 {
+            let value = 0x8877_6655_4433_2211u64 >> (64 - 8 * n);
+            for endianness in [Endianness::Little, Endianness::Big] {
+                mem.write_value(0x100, n, value, endianness).unwrap();
+                assert_eq!(mem.read_value(0x100, n, endianness).unwrap(), value);
+            }
+        }
+        mem.write_value(0x200, 4, 0x1122_3344, Endianness::Big)
+            .unwrap();
+        assert_eq!(mem.read_n::<4>(0x200).unwrap(), [0x11, 0x22, 0x33, 0x44]);
+        assert_eq!(
+            mem.read_value(0x200, 2, Endianness::Little).unwrap(),
+            0x2211
+        );
+    }
+
+    #[test]
+    fn misaligned_values_fail_unless_split() {
+        let mut mem = wide_memory(32);
+        mem.write_n(0x100, [1, 2, 3, 4, 5, 6, 7, 8]).unwrap();
+        assert!(mem.read_value(0x101, 2, Endianness::Little).is_err());
+        assert!(mem.read_value(0x101, 3, Endianness::Little).is_err());
+        assert!(mem.write_value(0x106, 4, 0, Endianness::Little).is_err());
+        // Three bytes only need to stay inside one aligned word.
+        assert_eq!(
+            mem.read_value(0x104, 3, Endianness::Little).unwrap(),
+            0x07_0605
+        );
+
+        assert_eq!(
+            mem.read_value_split(0x103, 4, Endianness::Little).unwrap(),
+            0x0706_0504
+        );
+        assert_eq!(
+            mem.read_value_split(0x101, 7, Endianness::Big).unwrap(),
+            0x0203_0405_0607_08
+        );
+        mem.write_value_split(0x107, 2, 0xaabb, Endianness::Little)
+            .unwrap();
+        assert_eq!(mem.read_n::<4>(0x104).unwrap(), [5, 6, 7, 0xbb]);
+        assert_eq!(mem.read_n::<4>(0x108).unwrap(), [0xaa, 0, 0, 0]);
+    }
 }
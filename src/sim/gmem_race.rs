@@ -0,0 +1,436 @@
+//! Flags gmem accesses that race with another warp's store. Every gmem
+//! word remembers the warp, PC and cycle of its last store; a load or store
+//! of the word by a different warp is a race unless a barrier both warps
+//! took part in, or a release fence by the writer followed by an acquire
+//! fence by the other warp, came between them. Words on the atomics list
+//! are not checked. One checker covers the gmem of every cluster; see
+//! `sim.gmem_race`.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::muon::lane_mask::LaneMask;
+use crate::sim::trace::MemTraceLine;
+use crate::timeflow::fence::FenceOrder;
+
+/// What the checker does about a race.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GmemRaceCheck {
+    #[default]
+    Off,
+    /// Count it, log the first one and keep going.
+    Count,
+    Panic,
+}
+
+/// `[sim.gmem_race]`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct GmemRaceConfig {
+    pub check: GmemRaceCheck,
+    /// Addresses of words kernels synchronize through on purpose, e.g.
+    /// flags and counters; accesses to them are never races.
+    pub atomics: Vec<u64>,
+}
+
+/// What a core reports to the checker.
+#[derive(Debug, Clone)]
+pub enum GmemEvent {
+    /// One lane's gmem access.
+    Access(MemTraceLine),
+    /// A fence over gmem by a warp.
+    Fence { warp_id: u32, order: FenceOrder },
+}
+
+/// A warp by cluster, cluster-local core and warp id, and the PC it was at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct GmemRaceWarp {
+    pub cluster: usize,
+    pub core: usize,
+    pub warp: usize,
+    pub pc: u32,
+}
+
+impl fmt::Display for GmemRaceWarp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "cluster {} core {} warp {} (pc 0x{:x})",
+            self.cluster, self.core, self.warp, self.pc
+        )
+    }
+}
+
+/// An access to a word another warp stored with nothing ordering the two.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct GmemRace {
+    pub cycle: u64,
+    /// The word's address.
+    pub address: u64,
+    pub store: bool,
+    pub accessor: GmemRaceWarp,
+    pub writer: GmemRaceWarp,
+    /// Cycle of the writer's store.
+    pub written_at: u64,
+}
+
+impl fmt::Display for GmemRace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "cycle {}: {} {} gmem 0x{:x}, stored at cycle {} by {} with no barrier or fence between them",
+            self.cycle,
+            self.accessor,
+            if self.store { "stored" } else { "loaded" },
+            self.address,
+            self.written_at,
+            self.writer
+        )
+    }
+}
+
+/// Races kept for the report; later ones are only counted.
+const KEPT_RACES: usize = 8;
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct GmemRaceStats {
+    pub loads: u64,
+    pub stores: u64,
+    pub fences: u64,
+    pub barriers: u64,
+    /// Accesses, one per lane, that raced with another warp's store.
+    pub races: u64,
+    /// The first few of them.
+    pub first_races: Vec<GmemRace>,
+}
+
+/// The last store to a word.
+#[derive(Debug, Clone, Copy)]
+struct LastStore {
+    /// Warp index; see `GmemRaceChecker::warp_index`.
+    warp: usize,
+    pc: u32,
+    cycle: u64,
+    /// Event count when it was stored.
+    epoch: u64,
+    /// The writer's threadblock count when it was stored.
+    threadblock: u64,
+}
+
+#[derive(Clone)]
+pub struct GmemRaceChecker {
+    action: GmemRaceCheck,
+    atomics: HashSet<u64>,
+    num_cores: usize,
+    num_warps: usize,
+    /// Warps per cluster.
+    cluster_warps: usize,
+    /// Counts stores, fences and barriers, so they can be put in order.
+    epoch: u64,
+    /// Word address -> its last store.
+    stores: HashMap<u64, LastStore>,
+    /// `[cluster][a * cluster_warps + b]`: epoch of the last barrier warps
+    /// `a` and `b` of the cluster both took part in; 0 for none.
+    synced: Vec<Vec<u64>>,
+    /// Per warp: epoch of its last release and acquire fence; 0 for none.
+    released: Vec<u64>,
+    acquired: Vec<u64>,
+    /// Per cluster: threadblocks started. A threadblock that has retired
+    /// is ordered before everything after it.
+    threadblocks: Vec<u64>,
+    stats: GmemRaceStats,
+}
+
+impl GmemRaceChecker {
+    pub fn new(
+        config: &GmemRaceConfig,
+        num_clusters: usize,
+        num_cores: usize,
+        num_warps: usize,
+    ) -> Self {
+        let cluster_warps = num_cores * num_warps;
+        let warps = num_clusters * cluster_warps;
+        Self {
+            action: config.check,
+            atomics: config.atomics.iter().map(|&addr| addr & !3).collect(),
+            num_cores,
+            num_warps,
+            cluster_warps,
+            epoch: 0,
+            stores: HashMap::new(),
+            synced: vec![vec![0; cluster_warps * cluster_warps]; num_clusters],
+            released: vec![0; warps],
+            acquired: vec![0; warps],
+            threadblocks: vec![0; num_clusters],
+            stats: GmemRaceStats::default(),
+        }
+    }
+
+    fn warp_index(&self, cluster: usize, core: usize, warp: usize) -> usize {
+        (cluster * self.num_cores + core) * self.num_warps + warp
+    }
+
+    fn warp_at(&self, index: usize, pc: u32) -> GmemRaceWarp {
+        GmemRaceWarp {
+            cluster: index / self.cluster_warps,
+            core: index % self.cluster_warps / self.num_warps,
+            warp: index % self.num_warps,
+            pc,
+        }
+    }
+
+    fn tick_epoch(&mut self) -> u64 {
+        self.epoch += 1;
+        self.epoch
+    }
+
+    /// Forgets every store, e.g. when the sim is reset.
+    pub fn reset(&mut self) {
+        self.stores.clear();
+    }
+
+    /// Orders every store of the cluster's last threadblock before what
+    /// comes next, e.g. when a new threadblock starts.
+    pub fn threadblock_start(&mut self, cluster: usize) {
+        self.threadblocks[cluster] += 1;
+    }
+
+    /// Records a released barrier of `cluster` from its `mask[cid][wid]`
+    /// thread masks.
+    pub fn barrier(&mut self, cluster: usize, arrived: &[Vec<LaneMask>]) {
+        self.stats.barriers += 1;
+        let epoch = self.tick_epoch();
+        let (num_warps, cluster_warps) = (self.num_warps, self.cluster_warps);
+        let participants: Vec<usize> = arrived
+            .iter()
+            .enumerate()
+            .flat_map(|(cid, warps)| {
+                warps
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, mask)| !mask.is_empty())
+                    .map(move |(wid, _)| cid * num_warps + wid)
+            })
+            .collect();
+        let synced = &mut self.synced[cluster];
+        for &a in &participants {
+            for &b in &participants {
+                synced[a * cluster_warps + b] = epoch;
+            }
+        }
+    }
+
+    /// Records a gmem fence by warp `wid` of core `cid`.
+    pub fn fence(&mut self, cluster: usize, cid: usize, wid: usize, order: FenceOrder) {
+        self.stats.fences += 1;
+        let epoch = self.tick_epoch();
+        let warp = self.warp_index(cluster, cid, wid);
+        if order != FenceOrder::Acquire {
+            self.released[warp] = epoch;
+        }
+        if order != FenceOrder::Release {
+            self.acquired[warp] = epoch;
+        }
+    }
+
+    fn ordered(&self, store: &LastStore, warp: usize) -> bool {
+        let writer = store.warp;
+        if writer == warp {
+            return true;
+        }
+        let cluster = writer / self.cluster_warps;
+        if self.threadblocks[cluster] != store.threadblock {
+            return true;
+        }
+        if cluster == warp / self.cluster_warps {
+            let (a, b) = (writer % self.cluster_warps, warp % self.cluster_warps);
+            if self.synced[cluster][a * self.cluster_warps + b] > store.epoch {
+                return true;
+            }
+        }
+        let released = self.released[writer];
+        released > store.epoch && self.acquired[warp] > released
+    }
+
+    /// Checks and records one lane's gmem access by core `cid` of
+    /// `cluster`. Panics on a race under `GmemRaceCheck::Panic`.
+    pub fn access(&mut self, cycle: u64, cluster: usize, cid: usize, line: &MemTraceLine) {
+        let warp = self.warp_index(cluster, cid, line.warp_id as usize);
+        let first = line.address & !3;
+        let last = line.address + line.size.max(1) as u64 - 1;
+        let words = (first..=last)
+            .step_by(4)
+            .filter(|word| !self.atomics.contains(word));
+        if line.store {
+            self.stats.stores += 1;
+        } else {
+            self.stats.loads += 1;
+        }
+        let mut race = None;
+        let words: Vec<u64> = words.collect();
+        for &word in &words {
+            if let Some(store) = self.stores.get(&word) {
+                if race.is_none() && !self.ordered(store, warp) {
+                    race = Some((word, *store));
+                }
+            }
+        }
+        if line.store {
+            let store = LastStore {
+                warp,
+                pc: line.pc,
+                cycle,
+                epoch: self.tick_epoch(),
+                threadblock: self.threadblocks[cluster],
+            };
+            for word in words {
+                self.stores.insert(word, store);
+            }
+        }
+        let Some((address, store)) = race else {
+            return;
+        };
+        let race = GmemRace {
+            cycle,
+            address,
+            store: line.store,
+            accessor: self.warp_at(warp, line.pc),
+            writer: self.warp_at(store.warp, store.pc),
+            written_at: store.cycle,
+        };
+        if self.action == GmemRaceCheck::Panic {
+            panic!("gmem race: {race}");
+        }
+        if self.stats.races == 0 {
+            log::warn!("gmem race: {race}");
+        }
+        self.stats.races += 1;
+        if self.stats.first_races.len() < KEPT_RACES {
+            self.stats.first_races.push(race);
+        }
+    }
+
+    pub fn stats(&self) -> &GmemRaceStats {
+        &self.stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn access(warp_id: u32, store: bool, address: u64) -> MemTraceLine {
+        MemTraceLine {
+            warp_id,
+            lane_id: 0,
+            pc: 0x100 + warp_id * 4,
+            is_smem: false,
+            store,
+            address,
+            size: 4,
+            data: 0,
+        }
+    }
+
+    fn checker(atomics: Vec<u64>) -> GmemRaceChecker {
+        let config = GmemRaceConfig {
+            check: GmemRaceCheck::Count,
+            atomics,
+        };
+        GmemRaceChecker::new(&config, 2, 1, 2)
+    }
+
+    fn check(checker: &mut GmemRaceChecker, cycle: u64, cluster: usize, line: MemTraceLine) {
+        checker.access(cycle, cluster, 0, &line);
+    }
+
+    #[test]
+    fn accesses_need_a_barrier_or_a_fence_pair_after_the_store() {
+        let mut checker = checker(Vec::new());
+        check(&mut checker, 1, 0, access(0, true, 0x1000));
+        check(&mut checker, 2, 0, access(0, false, 0x1000));
+        assert_eq!(checker.stats().races, 0);
+
+        // Another warp of the same cluster, then one on another cluster.
+        check(&mut checker, 3, 0, access(1, false, 0x1002));
+        check(&mut checker, 4, 1, access(0, true, 0x1000));
+        assert_eq!(checker.stats().races, 2);
+        assert_eq!(
+            checker.stats().first_races[0],
+            GmemRace {
+                cycle: 3,
+                address: 0x1000,
+                store: false,
+                accessor: GmemRaceWarp {
+                    cluster: 0,
+                    core: 0,
+                    warp: 1,
+                    pc: 0x104,
+                },
+                writer: GmemRaceWarp {
+                    cluster: 0,
+                    core: 0,
+                    warp: 0,
+                    pc: 0x100,
+                },
+                written_at: 1,
+            }
+        );
+
+        // Cluster 1's store now needs a fence pair, since the two clusters
+        // share no barrier.
+        let mut arrived = vec![vec![LaneMask::all(1); 2]];
+        checker.barrier(0, &arrived);
+        check(&mut checker, 5, 0, access(1, false, 0x1000));
+        assert_eq!(checker.stats().races, 3);
+        checker.fence(1, 0, 0, FenceOrder::Release);
+        checker.fence(0, 0, 1, FenceOrder::Acquire);
+        check(&mut checker, 6, 0, access(1, false, 0x1000));
+        assert_eq!(checker.stats().races, 3);
+
+        // A barrier the reader sat out orders nothing for it.
+        check(&mut checker, 7, 0, access(0, true, 0x2000));
+        arrived[0][1] = LaneMask::empty();
+        checker.barrier(0, &arrived);
+        check(&mut checker, 8, 0, access(1, false, 0x2000));
+        assert_eq!(checker.stats().races, 4);
+
+        // Nor does an acquire that came before the writer's release.
+        check(&mut checker, 9, 0, access(0, true, 0x3000));
+        checker.fence(0, 0, 1, FenceOrder::Acquire);
+        checker.fence(0, 0, 0, FenceOrder::Release);
+        check(&mut checker, 10, 0, access(1, false, 0x3000));
+        assert_eq!(checker.stats().races, 5);
+
+        // A new threadblock sees everything the last one stored.
+        checker.threadblock_start(0);
+        check(&mut checker, 11, 1, access(1, false, 0x3000));
+        assert_eq!(checker.stats().races, 5);
+        let stats = checker.stats();
+        assert_eq!((stats.stores, stats.loads, stats.fences), (4, 7, 4));
+    }
+
+    #[test]
+    fn atomic_words_are_never_races() {
+        let mut checker = checker(vec![0x1006]);
+        check(&mut checker, 1, 0, access(0, true, 0x1004));
+        check(&mut checker, 2, 1, access(1, true, 0x1004));
+        check(&mut checker, 3, 0, access(1, false, 0x1004));
+        assert_eq!(checker.stats().races, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "gmem race")]
+    fn panic_mode_stops_at_the_first_race() {
+        let config = GmemRaceConfig {
+            check: GmemRaceCheck::Panic,
+            atomics: Vec::new(),
+        };
+        let mut checker = GmemRaceChecker::new(&config, 1, 1, 2);
+        checker.access(1, 0, 0, &access(0, true, 0x0));
+        checker.access(2, 0, 0, &access(1, false, 0x0));
+    }
+}
@@ -0,0 +1,107 @@
+use crate::sim::trace::Line;
+use crate::timeflow::GmemCompletion;
+
+/// Fired after every simulated cycle.
+#[derive(Debug, Clone, Copy)]
+pub struct CycleEvent {
+    pub cycle: u64,
+}
+
+/// Fired for each instruction a warp commits, in program order per warp.
+#[derive(Debug, Clone, Copy)]
+pub struct RetireEvent<'a> {
+    pub cycle: u64,
+    pub cluster_id: usize,
+    pub core_id: usize,
+    pub warp_id: usize,
+    pub line: &'a Line,
+}
+
+/// Fired when the timing model retires a gmem request. Only produced with
+/// timing enabled.
+#[derive(Debug, Clone, Copy)]
+pub struct GmemCompletionEvent<'a> {
+    pub cycle: u64,
+    pub cluster_id: usize,
+    pub core_id: usize,
+    pub completion: &'a GmemCompletion,
+}
+
+/// Fired once, on the cycle every core has finished.
+#[derive(Debug, Clone, Copy)]
+pub struct KernelFinishEvent {
+    pub cycle: u64,
+}
+
+type Hook<E> = Box<dyn FnMut(&E) + Send + Sync>;
+type RetireHook = Box<dyn for<'a> FnMut(&RetireEvent<'a>) + Send + Sync>;
+type GmemCompletionHook = Box<dyn for<'a> FnMut(&GmemCompletionEvent<'a>) + Send + Sync>;
+
+/// Callbacks registered by code embedding the simulator. Hooks run in
+/// registration order at the end of `Sim::tick`.
+#[derive(Default)]
+pub struct SimHooks {
+    cycle: Vec<Hook<CycleEvent>>,
+    retire: Vec<RetireHook>,
+    gmem_completion: Vec<GmemCompletionHook>,
+    kernel_finish: Vec<Hook<KernelFinishEvent>>,
+}
+
+impl SimHooks {
+    pub fn on_cycle(&mut self, hook: impl FnMut(&CycleEvent) + Send + Sync + 'static) -> &mut Self {
+        self.cycle.push(Box::new(hook));
+        self
+    }
+
+    pub fn on_instruction_retired(
+        &mut self,
+        hook: impl for<'a> FnMut(&RetireEvent<'a>) + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.retire.push(Box::new(hook));
+        self
+    }
+
+    pub fn on_gmem_completion(
+        &mut self,
+        hook: impl for<'a> FnMut(&GmemCompletionEvent<'a>) + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.gmem_completion.push(Box::new(hook));
+        self
+    }
+
+    pub fn on_kernel_finish(
+        &mut self,
+        hook: impl FnMut(&KernelFinishEvent) + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.kernel_finish.push(Box::new(hook));
+        self
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    pub(crate) fn wants_retire(&self) -> bool {
+        !self.retire.is_empty()
+    }
+
+    pub(crate) fn wants_gmem_completion(&self) -> bool {
+        !self.gmem_completion.is_empty()
+    }
+
+    pub(crate) fn cycle(&mut self, event: &CycleEvent) {
+        self.cycle.iter_mut().for_each(|hook| hook(event));
+    }
+
+    pub(crate) fn retire(&mut self, event: &RetireEvent) {
+        self.retire.iter_mut().for_each(|hook| hook(event));
+    }
+
+    pub(crate) fn gmem_completion(&mut self, event: &GmemCompletionEvent) {
+        self.gmem_completion.iter_mut().for_each(|hook| hook(event));
+    }
+
+    pub(crate) fn kernel_finish(&mut self, event: &KernelFinishEvent) {
+        self.kernel_finish.iter_mut().for_each(|hook| hook(event));
+    }
+}
@@ -0,0 +1,443 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::timeq::Cycle;
+
+/// Everything a run takes from outside the simulator: the DPI calls of a
+/// cosimulation, host clock reads and SIGINTs. Recording these lets a run,
+/// including a failing RTL cosimulation, be replayed standalone.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JournalEvent {
+    /// `cyclotron_init_rs`, with the config.toml it read.
+    DpiInit {
+        elf: String,
+        trace_db: String,
+        config: Option<String>,
+    },
+    /// Any other DPI entrypoint, with its input arguments in declaration
+    /// order: scalars widened to u64, and each input array widened
+    /// element-wise.
+    Dpi {
+        entry: DpiEntry,
+        scalars: Vec<u64>,
+        arrays: Vec<Vec<u64>>,
+    },
+    /// Host wall clock, in nanoseconds since the Unix epoch.
+    HostTime(u64),
+    /// The simulate loop saw a SIGINT at this cycle.
+    Interrupt(Cycle),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DpiEntry {
+    Imem,
+    Fetch,
+    Gmem,
+    Frontend,
+    Backend,
+    Trace,
+    DifftestReg,
+    ProfilePerfCounters,
+    TileTick,
+    MemInit,
+    MemFree,
+    Mem,
+}
+
+impl DpiEntry {
+    const ALL: [DpiEntry; 12] = [
+        DpiEntry::Imem,
+        DpiEntry::Fetch,
+        DpiEntry::Gmem,
+        DpiEntry::Frontend,
+        DpiEntry::Backend,
+        DpiEntry::Trace,
+        DpiEntry::DifftestReg,
+        DpiEntry::ProfilePerfCounters,
+        DpiEntry::TileTick,
+        DpiEntry::MemInit,
+        DpiEntry::MemFree,
+        DpiEntry::Mem,
+    ];
+
+    fn from_tag(tag: u8) -> Option<Self> {
+        Self::ALL.get(tag as usize).copied()
+    }
+}
+
+const MAGIC: &[u8; 8] = b"CYCJRNL1";
+
+const TAG_DPI_INIT: u8 = 0;
+const TAG_DPI: u8 = 1;
+const TAG_HOST_TIME: u8 = 2;
+const TAG_INTERRUPT: u8 = 3;
+
+fn put_u32(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u64(out: &mut Vec<u8>, value: u64) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_str(out: &mut Vec<u8>, value: &str) {
+    put_u32(out, value.len() as u32);
+    out.extend_from_slice(value.as_bytes());
+}
+
+fn put_u64s(out: &mut Vec<u8>, values: &[u64]) {
+    put_u32(out, values.len() as u32);
+    values.iter().for_each(|&value| put_u64(out, value));
+}
+
+impl JournalEvent {
+    fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        match self {
+            JournalEvent::DpiInit {
+                elf,
+                trace_db,
+                config,
+            } => {
+                out.push(TAG_DPI_INIT);
+                put_str(&mut out, elf);
+                put_str(&mut out, trace_db);
+                out.push(config.is_some() as u8);
+                if let Some(config) = config {
+                    put_str(&mut out, config);
+                }
+            }
+            JournalEvent::Dpi {
+                entry,
+                scalars,
+                arrays,
+            } => {
+                out.push(TAG_DPI);
+                out.push(*entry as u8);
+                put_u64s(&mut out, scalars);
+                put_u32(&mut out, arrays.len() as u32);
+                arrays.iter().for_each(|array| put_u64s(&mut out, array));
+            }
+            JournalEvent::HostTime(nanos) => {
+                out.push(TAG_HOST_TIME);
+                put_u64(&mut out, *nanos);
+            }
+            JournalEvent::Interrupt(cycle) => {
+                out.push(TAG_INTERRUPT);
+                put_u64(&mut out, *cycle);
+            }
+        }
+        out
+    }
+}
+
+/// Cursor over an encoded journal. Reads past the end return `None`.
+struct Decoder<'a> {
+    bytes: &'a [u8],
+}
+
+impl Decoder<'_> {
+    fn take(&mut self, len: usize) -> Option<&[u8]> {
+        if self.bytes.len() < len {
+            return None;
+        }
+        let (head, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Some(head)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.take(1).map(|bytes| bytes[0])
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.take(4)
+            .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        self.take(8)
+            .map(|bytes| u64::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    fn str(&mut self) -> Option<String> {
+        let len = self.u32()? as usize;
+        let bytes = self.take(len)?;
+        Some(String::from_utf8_lossy(bytes).into_owned())
+    }
+
+    fn u64s(&mut self) -> Option<Vec<u64>> {
+        let len = self.u32()? as usize;
+        (0..len).map(|_| self.u64()).collect()
+    }
+
+    fn dpi_init(&mut self) -> Option<JournalEvent> {
+        let elf = self.str()?;
+        let trace_db = self.str()?;
+        let config = match self.u8()? {
+            0 => None,
+            _ => Some(self.str()?),
+        };
+        Some(JournalEvent::DpiInit {
+            elf,
+            trace_db,
+            config,
+        })
+    }
+
+    fn dpi(&mut self, entry: DpiEntry) -> Option<JournalEvent> {
+        let scalars = self.u64s()?;
+        let count = self.u32()? as usize;
+        let arrays = (0..count).map(|_| self.u64s()).collect::<Option<_>>()?;
+        Some(JournalEvent::Dpi {
+            entry,
+            scalars,
+            arrays,
+        })
+    }
+
+    fn event(&mut self) -> Result<Option<JournalEvent>, String> {
+        let Some(tag) = self.u8() else {
+            return Ok(None);
+        };
+        let event = match tag {
+            TAG_DPI_INIT => self.dpi_init(),
+            TAG_DPI => match self.u8() {
+                Some(entry) => {
+                    let entry = DpiEntry::from_tag(entry)
+                        .ok_or(format!("unknown DPI entrypoint {entry}"))?;
+                    self.dpi(entry)
+                }
+                None => None,
+            },
+            TAG_HOST_TIME => self.u64().map(JournalEvent::HostTime),
+            TAG_INTERRUPT => self.u64().map(JournalEvent::Interrupt),
+            _ => return Err(format!("unknown journal event tag {tag}")),
+        };
+        Ok(event)
+    }
+}
+
+/// Appends events to a journal file, flushing each one so that a journal
+/// cut short by a crash still holds every call up to the failing one.
+pub struct JournalWriter {
+    out: BufWriter<File>,
+}
+
+impl JournalWriter {
+    pub fn create(path: &Path) -> io::Result<Self> {
+        let mut out = BufWriter::new(File::create(path)?);
+        out.write_all(MAGIC)?;
+        out.flush()?;
+        Ok(Self { out })
+    }
+
+    pub fn write(&mut self, event: &JournalEvent) -> io::Result<()> {
+        self.out.write_all(&event.encode())?;
+        self.out.flush()
+    }
+}
+
+/// Reads every event of a journal. A truncated last event, as left by a
+/// recording process that died mid-write, is dropped with a warning.
+pub fn read_journal(path: &Path) -> Result<Vec<JournalEvent>, String> {
+    let bytes = std::fs::read(path).map_err(|err| format!("{}: {err}", path.display()))?;
+    decode_journal(&bytes).map_err(|err| format!("{}: {err}", path.display()))
+}
+
+fn decode_journal(bytes: &[u8]) -> Result<Vec<JournalEvent>, String> {
+    let Some(body) = bytes.strip_prefix(MAGIC.as_slice()) else {
+        return Err("not a cyclotron journal".to_string());
+    };
+    let mut decoder = Decoder { bytes: body };
+    let mut events = Vec::new();
+    loop {
+        let remaining = decoder.bytes.len();
+        match decoder.event()? {
+            Some(event) => events.push(event),
+            None if remaining == 0 => return Ok(events),
+            None => {
+                log::warn!(
+                    "journal ends in a truncated event; replaying the first {}",
+                    events.len()
+                );
+                return Ok(events);
+            }
+        }
+    }
+}
+
+/// A journal being recorded or replayed.
+pub enum Journal {
+    Record(JournalWriter),
+    Replay(VecDeque<JournalEvent>),
+}
+
+impl Journal {
+    /// Host wall clock since the Unix epoch: read and recorded, or the next
+    /// recorded reading.
+    pub fn host_time(&mut self) -> Duration {
+        match self {
+            Journal::Record(writer) => {
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default();
+                let _ = writer.write(&JournalEvent::HostTime(now.as_nanos() as u64));
+                now
+            }
+            Journal::Replay(events) => match events.front() {
+                Some(JournalEvent::HostTime(nanos)) => {
+                    let nanos = *nanos;
+                    events.pop_front();
+                    Duration::from_nanos(nanos)
+                }
+                next => {
+                    panic!("journal diverged: replay read the host clock, journal has {next:?}")
+                }
+            },
+        }
+    }
+
+    /// Whether the run is interrupted at `cycle`, given whether a SIGINT
+    /// arrived (`live`). Replay also interrupts where the recording was.
+    pub fn interrupted(&mut self, cycle: Cycle, live: bool) -> bool {
+        match self {
+            Journal::Record(writer) => {
+                if live {
+                    let _ = writer.write(&JournalEvent::Interrupt(cycle));
+                }
+                live
+            }
+            Journal::Replay(events) => {
+                let replayed =
+                    matches!(events.front(), Some(JournalEvent::Interrupt(at)) if *at == cycle);
+                if replayed {
+                    events.pop_front();
+                }
+                live || replayed
+            }
+        }
+    }
+}
+
+static ACTIVE: Mutex<Option<Journal>> = Mutex::new(None);
+
+/// Records everything external from here on to `path`.
+pub fn start_recording(path: &Path) -> io::Result<()> {
+    let writer = JournalWriter::create(path)?;
+    *ACTIVE.lock().unwrap() = Some(Journal::Record(writer));
+    Ok(())
+}
+
+/// Serves external inputs from `events` from here on.
+pub fn start_replay(events: Vec<JournalEvent>) {
+    *ACTIVE.lock().unwrap() = Some(Journal::Replay(events.into()));
+}
+
+/// Stops recording or replaying; a recorded journal is closed.
+pub fn stop() {
+    *ACTIVE.lock().unwrap() = None;
+}
+
+pub fn recording() -> bool {
+    matches!(*ACTIVE.lock().unwrap(), Some(Journal::Record(_)))
+}
+
+pub fn replaying() -> bool {
+    matches!(*ACTIVE.lock().unwrap(), Some(Journal::Replay(_)))
+}
+
+/// Appends the event `make` builds if recording; `make` is not called
+/// otherwise. A journal that cannot be written stops recording.
+pub fn record(make: impl FnOnce() -> JournalEvent) {
+    let mut active = ACTIVE.lock().unwrap();
+    if let Some(Journal::Record(writer)) = active.as_mut() {
+        if let Err(err) = writer.write(&make()) {
+            log::warn!("stopped recording the journal: {err}");
+            *active = None;
+        }
+    }
+}
+
+/// Takes the next replayed event, if replaying.
+pub fn next_replayed() -> Option<JournalEvent> {
+    match ACTIVE.lock().unwrap().as_mut() {
+        Some(Journal::Replay(events)) => events.pop_front(),
+        _ => None,
+    }
+}
+
+/// Wall clock since the Unix epoch, through the journal if one is active.
+pub fn host_time() -> Duration {
+    match ACTIVE.lock().unwrap().as_mut() {
+        Some(journal) => journal.host_time(),
+        None => SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default(),
+    }
+}
+
+/// `live` SIGINT state at `cycle`, through the journal if one is active.
+pub fn interrupted(cycle: Cycle, live: bool) -> bool {
+    match ACTIVE.lock().unwrap().as_mut() {
+        Some(journal) => journal.interrupted(cycle, live),
+        None => live,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Vec<JournalEvent> {
+        vec![
+            JournalEvent::DpiInit {
+                elf: "kernel.elf".to_string(),
+                trace_db: String::new(),
+                config: Some("[sim]\n".to_string()),
+            },
+            JournalEvent::HostTime(1_700_000_000_000_000_000),
+            JournalEvent::Dpi {
+                entry: DpiEntry::Gmem,
+                scalars: vec![],
+                arrays: vec![vec![1, 0], vec![0x8000_0000, 0x8000_0004]],
+            },
+            JournalEvent::Interrupt(42),
+        ]
+    }
+
+    fn encoded(events: &[JournalEvent]) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        events.iter().for_each(|event| bytes.extend(event.encode()));
+        bytes
+    }
+
+    #[test]
+    fn events_round_trip() {
+        let events = sample();
+        assert_eq!(decode_journal(&encoded(&events)).unwrap(), events);
+    }
+
+    #[test]
+    fn truncated_tail_is_dropped() {
+        let events = sample();
+        let mut bytes = encoded(&events);
+        bytes.truncate(bytes.len() - 3);
+        assert_eq!(decode_journal(&bytes).unwrap(), events[..3]);
+        assert!(decode_journal(b"not a journal").is_err());
+    }
+
+    #[test]
+    fn replay_serves_clock_and_interrupts_in_order() {
+        let mut journal =
+            Journal::Replay(vec![JournalEvent::HostTime(5), JournalEvent::Interrupt(3)].into());
+        assert_eq!(journal.host_time(), Duration::from_nanos(5));
+        assert!(!journal.interrupted(2, false));
+        assert!(journal.interrupted(3, false));
+        assert!(!journal.interrupted(4, false));
+    }
+}
@@ -0,0 +1,280 @@
+use crate::sim::analyze::analyze_config;
+use crate::timeflow::{ClusterGmemGraph, CoreGraphConfig, GmemRequest};
+use crate::timeq::Cycle;
+use serde::Serialize;
+use std::fmt;
+
+/// Address the chase buffer starts at; far from the code and data segments
+/// kernels use.
+const CHASE_BASE: u64 = 0x4000_0000;
+/// Cycles a single load may take before the probe gives up on it.
+const LOAD_TIMEOUT: Cycle = 1_000_000;
+
+/// Where the measured loads of one chase were served from.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct ServedCounts {
+    pub l0: u64,
+    pub l1: u64,
+    pub l2: u64,
+    pub dram: u64,
+}
+
+impl ServedCounts {
+    fn record(&mut self, request: &GmemRequest) {
+        let count = if request.l0_hit {
+            &mut self.l0
+        } else if request.l1_hit {
+            &mut self.l1
+        } else if request.l2_hit {
+            &mut self.l2
+        } else {
+            &mut self.dram
+        };
+        *count += 1;
+    }
+
+    pub fn total(&self) -> u64 {
+        self.l0 + self.l1 + self.l2 + self.dram
+    }
+
+    fn percent(&self, count: u64) -> f64 {
+        100.0 * count as f64 / self.total().max(1) as f64
+    }
+}
+
+/// Load-to-use latency of one pointer chase.
+#[derive(Debug, Clone, Serialize)]
+pub struct ChaseResult {
+    /// Level whose capacity the footprint was sized to fit (or, for DRAM,
+    /// to overflow).
+    pub target: &'static str,
+    pub footprint_bytes: u64,
+    pub stride_bytes: u64,
+    pub loads: u64,
+    pub avg_latency: f64,
+    pub min_latency: Cycle,
+    pub max_latency: Cycle,
+    pub served: ServedCounts,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LatencyProbeReport {
+    pub chases: Vec<ChaseResult>,
+    pub warnings: Vec<String>,
+}
+
+/// Runs one dependent-load chase per cache level, plus one past the last
+/// level, through a single-core gmem graph and reports the latency each
+/// load saw from issue to data return.
+///
+/// Each chase walks a ring of `footprint / stride` nodes one line apart,
+/// issuing the next load only once the previous one has returned, so no two
+/// loads overlap. The footprint of a level's chase is half its capacity for
+/// the nearest level and twice the previous level's capacity (capped at its
+/// own) beyond that, so the chase misses everything closer and fits in the
+/// target. A warm-up lap fills the caches before `samples` loads are timed.
+pub fn probe_latency(config: &CoreGraphConfig, samples: u64) -> LatencyProbeReport {
+    let (stride, targets) = chase_plan(config);
+    let samples = samples.max(1);
+    let mut chases = Vec::new();
+    let mut warnings = Vec::new();
+    for (target, footprint, warm_up) in targets {
+        match run_chase(config, target, footprint, stride, samples, warm_up) {
+            Ok(chase) => chases.push(chase),
+            Err(warning) => warnings.push(warning),
+        }
+    }
+    LatencyProbeReport { chases, warnings }
+}
+
+/// Runs only the chase `probe_latency` would run for `target` ("L1",
+/// "DRAM", ...).
+pub fn probe_level(
+    config: &CoreGraphConfig,
+    target: &str,
+    samples: u64,
+) -> Result<ChaseResult, String> {
+    let (stride, targets) = chase_plan(config);
+    let (target, footprint, warm_up) = targets
+        .into_iter()
+        .find(|(name, _, _)| *name == target)
+        .ok_or_else(|| format!("no {target} level in this config"))?;
+    run_chase(config, target, footprint, stride, samples.max(1), warm_up)
+}
+
+/// The chase stride and `(target, footprint, warm_up)` of every chase.
+fn chase_plan(config: &CoreGraphConfig) -> (u64, Vec<(&'static str, u64, bool)>) {
+    let analysis = analyze_config(config, 1, 1);
+    let stride = analysis
+        .levels
+        .iter()
+        .map(|level| level.line_bytes as u64)
+        .max()
+        .unwrap_or(64)
+        .max(4);
+
+    let mut targets = Vec::new();
+    let mut previous: Option<u64> = None;
+    for level in &analysis.levels {
+        let footprint = match previous {
+            None => level.size_bytes / 2,
+            Some(inner) => (inner * 2).min(level.size_bytes),
+        };
+        targets.push((level.name, footprint, true));
+        previous = Some(level.size_bytes);
+    }
+    // Every load misses the whole hierarchy, so there is nothing to warm.
+    targets.push(("DRAM", previous.unwrap_or(0) * 2, false));
+    for (_, footprint, _) in &mut targets {
+        *footprint = (*footprint).max(stride) / stride * stride;
+    }
+    (stride, targets)
+}
+
+fn run_chase(
+    config: &CoreGraphConfig,
+    target: &'static str,
+    footprint: u64,
+    stride: u64,
+    samples: u64,
+    warm_up: bool,
+) -> Result<ChaseResult, String> {
+    let memory = &config.memory;
+    let mut graph =
+        ClusterGmemGraph::new_with_regions(memory.gmem.clone(), memory.map.clone(), 1, 1);
+    let nodes = footprint / stride;
+    let warm_up_loads = if warm_up { nodes } else { 0 };
+
+    let mut now: Cycle = 0;
+    let mut served = ServedCounts::default();
+    let (mut sum, mut min, mut max) = (0u64, Cycle::MAX, 0);
+    for step in 0..warm_up_loads + samples {
+        let addr = CHASE_BASE + (step % nodes) * stride;
+        let mut request = GmemRequest::new(0, 4, 0x1, true);
+        request.addr = addr;
+        let deadline = now + LOAD_TIMEOUT;
+        let issued_at = loop {
+            match graph.issue(0, now, request) {
+                Ok(_) => break now,
+                Err(reject) => {
+                    request = reject.payload;
+                    let retry_at = reject.retry_at.max(now + 1);
+                    while now < retry_at {
+                        graph.tick(now);
+                        now += 1;
+                    }
+                }
+            }
+            if now > deadline {
+                return Err(format!(
+                    "{target} chase: load to 0x{addr:x} was never accepted"
+                ));
+            }
+        };
+        let completion = loop {
+            graph.tick(now);
+            if let Some(completion) = graph.pop_completion(0) {
+                break completion;
+            }
+            now += 1;
+            if now - issued_at > LOAD_TIMEOUT {
+                return Err(format!(
+                    "{target} chase: load to 0x{addr:x} never completed"
+                ));
+            }
+        };
+        // The dependent load issues the cycle after the data is back.
+        now = now.max(completion.completed_at) + 1;
+        if step < warm_up_loads {
+            continue;
+        }
+        let latency = completion.completed_at.saturating_sub(issued_at);
+        sum += latency;
+        min = min.min(latency);
+        max = max.max(latency);
+        served.record(&completion.request);
+    }
+
+    Ok(ChaseResult {
+        target,
+        footprint_bytes: footprint,
+        stride_bytes: stride,
+        loads: samples,
+        avg_latency: sum as f64 / samples as f64,
+        min_latency: min,
+        max_latency: max,
+        served,
+    })
+}
+
+impl fmt::Display for LatencyProbeReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<6} {:>10} {:>6} {:>6} {:>8} {:>6} {:>6} {:>6} {:>6} {:>6} {:>6}",
+            "target",
+            "footprint",
+            "stride",
+            "loads",
+            "avg lat",
+            "min",
+            "max",
+            "L0%",
+            "L1%",
+            "L2%",
+            "DRAM%"
+        )?;
+        for chase in &self.chases {
+            let served = &chase.served;
+            writeln!(
+                f,
+                "{:<6} {:>9}K {:>6} {:>6} {:>8.1} {:>6} {:>6} {:>6.1} {:>6.1} {:>6.1} {:>6.1}",
+                chase.target,
+                chase.footprint_bytes / 1024,
+                chase.stride_bytes,
+                chase.loads,
+                chase.avg_latency,
+                chase.min_latency,
+                chase.max_latency,
+                served.percent(served.l0),
+                served.percent(served.l1),
+                served.percent(served.l2),
+                served.percent(served.dram)
+            )?;
+        }
+        for warning in &self.warnings {
+            writeln!(f, "warning: {warning}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn latency_grows_with_footprint() {
+        // Shrink the caches so the warm-up laps stay short.
+        let mut config = CoreGraphConfig::default();
+        let policy = &mut config.memory.gmem.policy;
+        policy.l0_sets = 4;
+        policy.l1_sets = 8;
+        policy.l2_sets = 16;
+        let report = probe_latency(&config, 16);
+        let latency = |target: &str| {
+            report
+                .chases
+                .iter()
+                .find(|chase| chase.target == target)
+                .unwrap_or_else(|| panic!("no {target} chase"))
+        };
+        let l1 = latency("L1");
+        let l2 = latency("L2");
+        let dram = latency("DRAM");
+        assert_eq!(l1.served.l1 + l1.served.l0, l1.loads);
+        assert_eq!(dram.served.dram, dram.loads);
+        assert!(l1.avg_latency < l2.avg_latency);
+        assert!(l2.avg_latency < dram.avg_latency);
+    }
+}
@@ -0,0 +1,75 @@
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use serde::Serialize;
+
+use crate::sim::journal;
+
+/// Where a run came from and what it cost, attached to the perf summary and
+/// to state dumps so either can be traced back to a build, config and
+/// workload.
+#[derive(Debug, Clone, Serialize)]
+pub struct RunMetadata {
+    pub git_commit: String,
+    pub hostname: String,
+    /// Seconds since the Unix epoch when the run started.
+    pub started_at: u64,
+    pub workload: PathBuf,
+    /// FNV-1a hash of the workload ELF; `None` if it could not be read.
+    pub workload_hash: Option<String>,
+    pub wall_clock_secs: f64,
+    /// Peak resident set size, where the OS reports it.
+    pub peak_rss_kb: Option<u64>,
+    /// The config after includes and command-line overrides.
+    pub config: serde_json::Value,
+    #[serde(skip)]
+    start: Instant,
+}
+
+impl RunMetadata {
+    pub fn collect(workload: &Path) -> Self {
+        Self {
+            git_commit: env!("CYCLOTRON_GIT_COMMIT").to_string(),
+            hostname: hostname(),
+            started_at: journal::host_time().as_secs(),
+            workload: workload.to_path_buf(),
+            workload_hash: std::fs::read(workload)
+                .ok()
+                .map(|bytes| format!("{:016x}", fnv1a(&bytes))),
+            wall_clock_secs: 0.0,
+            peak_rss_kb: None,
+            config: serde_json::Value::Null,
+            start: Instant::now(),
+        }
+    }
+
+    /// Copy with the duration and peak RSS filled in as of now.
+    pub fn snapshot(&self) -> Self {
+        Self {
+            wall_clock_secs: self.start.elapsed().as_secs_f64(),
+            peak_rss_kb: peak_rss_kb(),
+            ..self.clone()
+        }
+    }
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+fn hostname() -> String {
+    std::fs::read_to_string("/proc/sys/kernel/hostname")
+        .ok()
+        .map(|name| name.trim().to_string())
+        .or_else(|| std::env::var("HOSTNAME").ok())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+fn peak_rss_kb() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmHWM:"))?;
+    line.split_whitespace().nth(1)?.parse().ok()
+}
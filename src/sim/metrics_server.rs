@@ -0,0 +1,267 @@
+//! Live simulation metrics in the Prometheus text exposition format.
+//!
+//! `SimMetrics` is a snapshot taken by `Sim` every `sim.metrics_interval`
+//! cycles. With the `metrics-server` feature, `MetricsServer` serves the
+//! latest snapshot over HTTP at `sim.metrics_addr` so a scraper can follow a
+//! long batch run.
+
+use crate::muon::gmem::CorePerfSummary;
+use std::fmt::Write;
+
+/// Requests a core has in flight when the snapshot is taken.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CoreOccupancy {
+    pub cluster_id: usize,
+    pub core_id: usize,
+    pub gmem_outstanding: usize,
+    pub smem_outstanding: usize,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SimMetrics {
+    pub cycle: u64,
+    /// Summed over cores; zero without the timing model.
+    pub instructions: u64,
+    pub gmem_bytes_completed: u64,
+    /// Gmem bytes returned to the cores per cycle since the previous
+    /// snapshot.
+    pub gmem_bandwidth: f64,
+    pub cores: Vec<CoreOccupancy>,
+}
+
+impl SimMetrics {
+    /// Builds a snapshot from per-core timing summaries; `previous` is the
+    /// last snapshot published, for the interval bandwidth.
+    pub fn collect(
+        cycle: u64,
+        summaries: &[CorePerfSummary],
+        cores: Vec<CoreOccupancy>,
+        previous: Option<&SimMetrics>,
+    ) -> Self {
+        let instructions = summaries
+            .iter()
+            .map(|summary| summary.cpi_stack.instructions)
+            .sum();
+        let gmem_bytes_completed = summaries
+            .iter()
+            .map(|summary| summary.gmem_stats.bytes_completed())
+            .sum();
+        let (last_cycle, last_bytes) =
+            previous.map_or((0, 0), |prev| (prev.cycle, prev.gmem_bytes_completed));
+        let elapsed = cycle.saturating_sub(last_cycle);
+        // Counters restart when warmup ends; report no bandwidth across it.
+        let gmem_bandwidth = if elapsed == 0 || gmem_bytes_completed < last_bytes {
+            0.0
+        } else {
+            (gmem_bytes_completed - last_bytes) as f64 / elapsed as f64
+        };
+        Self {
+            cycle,
+            instructions,
+            gmem_bytes_completed,
+            gmem_bandwidth,
+            cores,
+        }
+    }
+
+    pub fn ipc(&self) -> f64 {
+        if self.cycle == 0 {
+            0.0
+        } else {
+            self.instructions as f64 / self.cycle as f64
+        }
+    }
+
+    /// Renders the snapshot in the Prometheus text format.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let header = |out: &mut String, name: &str, kind: &str, help: &str| {
+            let _ = writeln!(out, "# HELP {name} {help}");
+            let _ = writeln!(out, "# TYPE {name} {kind}");
+        };
+        let totals = [
+            (
+                "cyclotron_cycles_total",
+                "counter",
+                "Cycles simulated.",
+                self.cycle.to_string(),
+            ),
+            (
+                "cyclotron_instructions_retired_total",
+                "counter",
+                "Instructions retired across all cores.",
+                self.instructions.to_string(),
+            ),
+            (
+                "cyclotron_ipc",
+                "gauge",
+                "Instructions retired per cycle since the start of the run.",
+                self.ipc().to_string(),
+            ),
+            (
+                "cyclotron_gmem_bytes_completed_total",
+                "counter",
+                "Gmem bytes returned to the cores.",
+                self.gmem_bytes_completed.to_string(),
+            ),
+            (
+                "cyclotron_gmem_bandwidth_bytes_per_cycle",
+                "gauge",
+                "Gmem bytes returned to the cores per cycle over the last interval.",
+                self.gmem_bandwidth.to_string(),
+            ),
+        ];
+        for (name, kind, help, value) in totals {
+            header(&mut out, name, kind, help);
+            let _ = writeln!(out, "{name} {value}");
+        }
+
+        let per_core = [
+            (
+                "cyclotron_gmem_outstanding_requests",
+                "Gmem requests in flight per core.",
+                self.cores
+                    .iter()
+                    .map(|core| core.gmem_outstanding)
+                    .collect::<Vec<_>>(),
+            ),
+            (
+                "cyclotron_smem_outstanding_requests",
+                "Smem requests in flight per core.",
+                self.cores
+                    .iter()
+                    .map(|core| core.smem_outstanding)
+                    .collect(),
+            ),
+        ];
+        for (name, help, values) in per_core {
+            header(&mut out, name, "gauge", help);
+            for (core, value) in self.cores.iter().zip(values) {
+                let _ = writeln!(
+                    out,
+                    "{name}{{cluster=\"{}\",core=\"{}\"}} {value}",
+                    core.cluster_id, core.core_id
+                );
+            }
+        }
+        out
+    }
+}
+
+#[cfg(feature = "metrics-server")]
+pub use server::MetricsServer;
+
+#[cfg(feature = "metrics-server")]
+mod server {
+    use super::SimMetrics;
+    use std::io::{self, Read, Write};
+    use std::net::{SocketAddr, TcpListener, TcpStream};
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+
+    /// Serves the last published snapshot to every HTTP request on a
+    /// background thread, whatever the path.
+    pub struct MetricsServer {
+        addr: SocketAddr,
+        body: Arc<Mutex<String>>,
+        last: Option<SimMetrics>,
+    }
+
+    impl MetricsServer {
+        pub fn bind(addr: &str) -> io::Result<Self> {
+            let listener = TcpListener::bind(addr)?;
+            let addr = listener.local_addr()?;
+            let body = Arc::new(Mutex::new(SimMetrics::default().render()));
+            let served = Arc::clone(&body);
+            thread::Builder::new()
+                .name("cyclotron-metrics".into())
+                .spawn(move || {
+                    for stream in listener.incoming().flatten() {
+                        let body = served.lock().expect("metrics lock poisoned").clone();
+                        if let Err(err) = respond(stream, &body) {
+                            log::debug!("metrics request failed: {err}");
+                        }
+                    }
+                })?;
+            Ok(Self {
+                addr,
+                body,
+                last: None,
+            })
+        }
+
+        pub fn local_addr(&self) -> SocketAddr {
+            self.addr
+        }
+
+        /// The snapshot currently being served, if any.
+        pub fn last(&self) -> Option<&SimMetrics> {
+            self.last.as_ref()
+        }
+
+        pub fn publish(&mut self, metrics: SimMetrics) {
+            *self.body.lock().expect("metrics lock poisoned") = metrics.render();
+            self.last = Some(metrics);
+        }
+    }
+
+    fn respond(mut stream: TcpStream, body: &str) -> io::Result<()> {
+        // The request itself does not matter; read it so the client sees a
+        // clean close.
+        let mut request = [0u8; 1024];
+        let _ = stream.read(&mut request)?;
+        write!(
+            stream,
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_counters_and_per_core_gauges() {
+        let metrics = SimMetrics {
+            cycle: 200,
+            instructions: 150,
+            gmem_bytes_completed: 4096,
+            gmem_bandwidth: 8.0,
+            cores: vec![CoreOccupancy {
+                cluster_id: 0,
+                core_id: 1,
+                gmem_outstanding: 3,
+                smem_outstanding: 0,
+            }],
+        };
+        assert_eq!(metrics.ipc(), 0.75);
+
+        let text = metrics.render();
+        assert!(text.contains("cyclotron_cycles_total 200\n"));
+        assert!(text.contains("cyclotron_instructions_retired_total 150\n"));
+        assert!(text.contains("# TYPE cyclotron_ipc gauge\n"));
+        assert!(text.contains("cyclotron_gmem_outstanding_requests{cluster=\"0\",core=\"1\"} 3\n"));
+        assert!(text.contains("cyclotron_smem_outstanding_requests{cluster=\"0\",core=\"1\"} 0\n"));
+    }
+
+    #[cfg(feature = "metrics-server")]
+    #[test]
+    fn serves_the_last_snapshot() {
+        use std::io::{Read, Write};
+
+        let mut server = MetricsServer::bind("127.0.0.1:0").unwrap();
+        server.publish(SimMetrics {
+            cycle: 42,
+            ..SimMetrics::default()
+        });
+        let mut stream = std::net::TcpStream::connect(server.local_addr()).unwrap();
+        stream.write_all(b"GET /metrics HTTP/1.1\r\n\r\n").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains("cyclotron_cycles_total 42\n"));
+        assert_eq!(server.last().map(|metrics| metrics.cycle), Some(42));
+    }
+}
@@ -0,0 +1,417 @@
+//! Shrinks the config of a failing run to a small one that still fails the
+//! same way. A failure is a panic (an assertion or a `check!` set to
+//! `abort`) or a `sim.deadlock_cycles` deadlock. Knobs are reduced one at a
+//! time, halving queue capacities, entry counts and the cluster, core and
+//! warp counts, and turning off every `enabled` subsystem, and a reduction
+//! is kept if the run still fails with the same message. Every run starts
+//! at reset and stops a little past the cycle the failure last fired at:
+//! a `Sim::checkpoint` holds a sim built from one config, and every
+//! candidate here is a different config, so no run can resume another's.
+
+use std::fmt;
+use std::panic::{self, catch_unwind, AssertUnwindSafe, PanicHookInfo};
+use std::thread;
+
+use serde::Serialize;
+use toml::{Table, Value};
+
+use crate::sim::top::Sim;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureKind {
+    Panic,
+    Deadlock,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Failure {
+    pub kind: FailureKind,
+    /// The panic message; empty for a deadlock.
+    pub message: String,
+    /// Cycle the run stopped at.
+    pub cycle: u64,
+}
+
+impl Failure {
+    /// Whether `other` is the same failure. Numbers in the message, which
+    /// are mostly cycles, ids and counts that move as the config shrinks,
+    /// are not compared.
+    pub fn same_as(&self, other: &Failure) -> bool {
+        let shape = |message: &str| {
+            let mut shape = String::new();
+            for c in message.chars() {
+                if !c.is_ascii_digit() {
+                    shape.push(c);
+                } else if !shape.ends_with('#') {
+                    shape.push('#');
+                }
+            }
+            shape
+        };
+        self.kind == other.kind && shape(&self.message) == shape(&other.message)
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            FailureKind::Panic => write!(f, "panic at cycle {}: {}", self.cycle, self.message),
+            FailureKind::Deadlock => write!(f, "deadlock at cycle {}", self.cycle),
+        }
+    }
+}
+
+type PanicHook = Box<dyn Fn(&PanicHookInfo<'_>) + Sync + Send + 'static>;
+
+/// Silences the panic hook until dropped, then puts the previous one back.
+struct SilencedPanics(Option<PanicHook>);
+
+impl SilencedPanics {
+    fn new() -> Self {
+        let hook = panic::take_hook();
+        panic::set_hook(Box::new(|_| {}));
+        Self(Some(hook))
+    }
+}
+
+impl Drop for SilencedPanics {
+    fn drop(&mut self) {
+        // `set_hook` panics on a panicking thread; that is already past
+        // reporting anyway.
+        if let Some(hook) = self.0.take().filter(|_| !thread::panicking()) {
+            panic::set_hook(hook);
+        }
+    }
+}
+
+/// Builds a sim with `make` and runs it, returning how it failed, if it
+/// did. A panic while building counts as a failure at cycle 0. The panic
+/// hook is silenced for the run and restored however it ends.
+pub fn run_to_failure(make: impl FnOnce() -> Sim) -> Option<Failure> {
+    let mut sim = None;
+    let result = {
+        let _silenced = SilencedPanics::new();
+        catch_unwind(AssertUnwindSafe(|| {
+            let sim = sim.insert(make());
+            let _ = sim.simulate();
+            sim.deadlocked_at()
+        }))
+    };
+    match result {
+        Ok(deadlocked_at) => deadlocked_at.map(|cycle| Failure {
+            kind: FailureKind::Deadlock,
+            message: String::new(),
+            cycle,
+        }),
+        Err(payload) => {
+            let message = payload
+                .downcast_ref::<String>()
+                .cloned()
+                .or_else(|| payload.downcast_ref::<&str>().map(|s| s.to_string()))
+                .unwrap_or_else(|| "unknown".to_string());
+            Some(Failure {
+                kind: FailureKind::Panic,
+                message,
+                cycle: sim.as_ref().map_or(0, Sim::cycle),
+            })
+        }
+    }
+}
+
+/// One kept reduction.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Reduction {
+    /// Dotted path of the key, e.g. `timing.gmem.nodes.dram.queue_capacity`.
+    pub key: String,
+    pub from: String,
+    pub to: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct MinimizeOptions {
+    /// Most runs to try, the first one included.
+    pub max_runs: u64,
+    /// Cycles each run goes on past the last failing cycle.
+    pub slack: u64,
+}
+
+impl Default for MinimizeOptions {
+    fn default() -> Self {
+        Self {
+            max_runs: 200,
+            slack: 1000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MinimizeReport {
+    /// The failure of the smallest config.
+    pub failure: Failure,
+    /// The failure of the config as given.
+    pub original: Failure,
+    pub reductions: Vec<Reduction>,
+    pub runs: u64,
+    /// `sim.timeout` of the minimized config: the failing cycle plus
+    /// `slack`.
+    pub timeout: u64,
+    /// The minimized config, timeout included.
+    #[serde(skip)]
+    pub config: Table,
+}
+
+impl fmt::Display for MinimizeReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "original: {}", self.original)?;
+        writeln!(f, "minimized: {}", self.failure)?;
+        writeln!(
+            f,
+            "{} reductions in {} runs, window 0..{}",
+            self.reductions.len(),
+            self.runs,
+            self.timeout
+        )?;
+        for reduction in &self.reductions {
+            writeln!(
+                f,
+                "  {} = {} (was {})",
+                reduction.key, reduction.to, reduction.from
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Keys halved down to 1.
+fn halves(path: &str, key: &str) -> bool {
+    const COUNTS: [&str; 3] = ["sim.num_clusters", "muon.num_cores", "muon.num_warps"];
+    COUNTS.contains(&path)
+        || (path.starts_with("timing.")
+            && (key.ends_with("capacity")
+                || key.ends_with("entries")
+                || key.starts_with("max_outstanding")
+                || key.starts_with("max_inflight")
+                || key == "max_per_warp"
+                || key == "queue_per_warp"
+                || key == "slots"))
+}
+
+fn get<'a>(config: &'a Table, path: &str) -> Option<&'a Value> {
+    let (tables, key) = path.rsplit_once('.').unwrap_or(("", path));
+    let mut table = config;
+    for name in tables.split('.').filter(|name| !name.is_empty()) {
+        table = table.get(name)?.as_table()?;
+    }
+    table.get(key)
+}
+
+fn set(config: &mut Table, path: &str, value: Value) {
+    let (tables, key) = path.rsplit_once('.').unwrap_or(("", path));
+    let mut table = config;
+    for name in tables.split('.').filter(|name| !name.is_empty()) {
+        table = table
+            .entry(name)
+            .or_insert_with(|| Table::new().into())
+            .as_table_mut()
+            .expect("config path crosses a value");
+    }
+    table.insert(key.to_string(), value);
+}
+
+fn collect_knobs(prefix: &str, table: &Table, knobs: &mut Vec<String>) {
+    for (key, value) in table {
+        let path = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{prefix}.{key}")
+        };
+        match value {
+            Value::Table(table) => collect_knobs(&path, table, knobs),
+            Value::Integer(_) if halves(&path, key) => knobs.push(path),
+            Value::Boolean(_) if path.starts_with("timing.") && key.ends_with("enabled") => {
+                knobs.push(path)
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Dotted paths of the knobs `minimize` tries to reduce, in config order.
+/// Knobs left at their defaults are not tried.
+pub fn knobs(config: &Table) -> Vec<String> {
+    let mut knobs = Vec::new();
+    collect_knobs("", config, &mut knobs);
+    knobs
+}
+
+/// The next smaller value of `knob`, if there is one.
+fn reduced(config: &Table, knob: &str) -> Option<Value> {
+    match get(config, knob)? {
+        Value::Integer(value) if *value > 1 => Some(Value::Integer(value / 2)),
+        Value::Boolean(true) => Some(Value::Boolean(false)),
+        _ => None,
+    }
+}
+
+/// Shrinks `config` while `run` keeps failing the same way as on the
+/// config as given. `run` gets the config with `sim.timeout` set; it
+/// returns `None` if the run did not fail. Returns `None` if the config as
+/// given does not fail.
+pub fn minimize(
+    config: &Table,
+    options: &MinimizeOptions,
+    mut run: impl FnMut(&Table) -> Option<Failure>,
+) -> Option<MinimizeReport> {
+    let original = run(config)?;
+    let mut runs = 1;
+    let mut failure = original.clone();
+    let mut config = config.clone();
+    let timeout = |failure: &Failure| Value::Integer((failure.cycle + options.slack) as i64);
+    set(&mut config, "sim.timeout", timeout(&failure));
+    let mut reductions = Vec::new();
+
+    'passes: loop {
+        let mut reduced_any = false;
+        for knob in knobs(&config) {
+            let Some(value) = reduced(&config, &knob) else {
+                continue;
+            };
+            if runs >= options.max_runs {
+                break 'passes;
+            }
+            let mut candidate = config.clone();
+            set(&mut candidate, &knob, value.clone());
+            runs += 1;
+            let Some(result) = run(&candidate).filter(|result| result.same_as(&original)) else {
+                continue;
+            };
+            reductions.push(Reduction {
+                from: get(&config, &knob)
+                    .map(Value::to_string)
+                    .unwrap_or_default(),
+                to: value.to_string(),
+                key: knob,
+            });
+            set(&mut candidate, "sim.timeout", timeout(&result));
+            config = candidate;
+            failure = result;
+            reduced_any = true;
+        }
+        if !reduced_any {
+            break;
+        }
+    }
+
+    Some(MinimizeReport {
+        timeout: failure.cycle + options.slack,
+        failure,
+        original,
+        reductions,
+        runs,
+        config,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> Table {
+        toml::from_str(
+            r#"
+            [sim]
+            num_clusters = 4
+            [muon]
+            num_cores = 2
+            [timing.gmem.nodes.dram]
+            queue_capacity = 16
+            base_latency = 100
+            [timing.icache]
+            enabled = true
+            "#,
+        )
+        .unwrap()
+    }
+
+    fn panic_at(cycle: u64) -> Option<Failure> {
+        Some(Failure {
+            kind: FailureKind::Panic,
+            message: format!("queue overflow at cycle {cycle}"),
+            cycle,
+        })
+    }
+
+    #[test]
+    fn knobs_cover_capacities_counts_and_subsystems() {
+        assert_eq!(
+            knobs(&config()),
+            [
+                "muon.num_cores",
+                "sim.num_clusters",
+                "timing.gmem.nodes.dram.queue_capacity",
+                "timing.icache.enabled",
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_every_reduction_that_still_fails_the_same_way() {
+        let options = MinimizeOptions {
+            max_runs: 100,
+            slack: 10,
+        };
+        // Fails once the DRAM queue holds 4 or more and only with the
+        // icache on; fewer clusters fail sooner, and one core fails
+        // differently.
+        let report = minimize(&config(), &options, |config| {
+            let int = |path: &str| get(config, path).and_then(Value::as_integer);
+            if int("muon.num_cores") == Some(1) {
+                return Some(Failure {
+                    kind: FailureKind::Deadlock,
+                    message: String::new(),
+                    cycle: 5,
+                });
+            }
+            let capacity = int("timing.gmem.nodes.dram.queue_capacity")?;
+            let icache = get(config, "timing.icache.enabled").and_then(Value::as_bool)?;
+            (capacity >= 4 && icache).then_some(())?;
+            panic_at(100 * int("sim.num_clusters").unwrap_or(1) as u64)
+        })
+        .unwrap();
+
+        assert_eq!(report.original.cycle, 400);
+        assert_eq!(report.failure, panic_at(100).unwrap());
+        assert_eq!(report.timeout, 110);
+        let kept: Vec<(&str, &str)> = report
+            .reductions
+            .iter()
+            .map(|r| (r.key.as_str(), r.to.as_str()))
+            .collect();
+        assert_eq!(
+            kept,
+            [
+                ("sim.num_clusters", "2"),
+                ("timing.gmem.nodes.dram.queue_capacity", "8"),
+                ("sim.num_clusters", "1"),
+                ("timing.gmem.nodes.dram.queue_capacity", "4"),
+            ]
+        );
+        assert_eq!(
+            get(&report.config, "sim.timeout"),
+            Some(&Value::Integer(110))
+        );
+        assert_eq!(
+            get(&report.config, "muon.num_cores"),
+            Some(&Value::Integer(2))
+        );
+        // The first two passes try all four knobs; the third, with one
+        // cluster left, the other three.
+        assert_eq!(report.runs, 1 + 4 + 4 + 3);
+    }
+
+    #[test]
+    fn a_passing_config_has_nothing_to_minimize() {
+        assert!(minimize(&config(), &MinimizeOptions::default(), |_| None).is_none());
+    }
+}
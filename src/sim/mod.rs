@@ -1,8 +1,29 @@
+pub mod analyze;
+pub mod calibrate;
+pub mod checkpoint;
 pub mod config;
+pub mod diff;
+pub mod dump;
 pub mod elf;
 pub mod flat_mem;
+pub mod gmem_race;
+pub mod hooks;
+pub mod journal;
+pub mod latency_probe;
 pub mod log;
+pub mod metadata;
+pub mod metrics_server;
+pub mod minimize;
+#[cfg(feature = "parquet")]
+pub mod parquet_sink;
 pub mod perf_log;
+pub mod perturb;
+pub mod pipeview;
+pub mod report;
+pub mod sched_log;
+pub mod smem_order;
+pub mod sqlite_log;
 pub mod top;
 pub mod trace;
 pub mod trace_db;
+pub mod vcd;
@@ -0,0 +1,432 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use arrow_array::builder::{
+    BooleanBuilder, Float64Builder, Int64Builder, NullBuilder, StringBuilder, UInt64Builder,
+};
+use arrow_array::{ArrayRef, RecordBatch};
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+use parquet::arrow::ArrowWriter;
+use parquet::basic::{Compression, ZstdLevel};
+use parquet::file::properties::WriterProperties;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::sim::perf_log::flatten;
+
+/// Bumped whenever the column layout of a perf log record changes in a way
+/// readers need to know about. Stored in every Parquet file's metadata under
+/// `cyclotron.schema_version`.
+pub const PERF_LOG_SCHEMA_VERSION: u32 = 1;
+
+const ROW_GROUP_ROWS: usize = 8192;
+
+/// Arrow type of a column, widened as records with other values come in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ColumnType {
+    /// Only nulls so far.
+    Null,
+    Bool,
+    U64,
+    I64,
+    F64,
+    Str,
+}
+
+impl ColumnType {
+    fn of(value: &Value) -> Self {
+        match value {
+            Value::Null => Self::Null,
+            Value::Bool(_) => Self::Bool,
+            Value::Number(n) if n.is_f64() => Self::F64,
+            Value::Number(n) if n.is_u64() => Self::U64,
+            Value::Number(_) => Self::I64,
+            _ => Self::Str,
+        }
+    }
+
+    fn fits(self, value: &Value) -> bool {
+        match (self, value) {
+            (_, Value::Null) => true,
+            (Self::Null, _) => false,
+            (Self::Bool, value) => value.is_boolean(),
+            (Self::U64, value) => value.is_u64(),
+            (Self::I64, value) => value.is_i64(),
+            (Self::F64, value) => value.is_number(),
+            (Self::Str, _) => true,
+        }
+    }
+
+    /// The narrowest type that holds both this column's values and `value`.
+    fn widen(self, value: &Value) -> Self {
+        match (self, Self::of(value)) {
+            (Self::Null, other) | (other, Self::Null) => other,
+            (a, b) if a == b => a,
+            (Self::U64, Self::I64) | (Self::I64, Self::U64) if value.is_i64() => Self::I64,
+            (Self::U64 | Self::I64 | Self::F64, Self::U64 | Self::I64 | Self::F64) => Self::F64,
+            _ => Self::Str,
+        }
+    }
+
+    fn data_type(self) -> DataType {
+        match self {
+            Self::Null => DataType::Null,
+            Self::Bool => DataType::Boolean,
+            Self::U64 => DataType::UInt64,
+            Self::I64 => DataType::Int64,
+            Self::F64 => DataType::Float64,
+            Self::Str => DataType::Utf8,
+        }
+    }
+
+    /// A builder for this type holding `nulls` nulls.
+    fn builder(self, nulls: usize) -> ColumnBuilder {
+        let mut builder = match self {
+            Self::Null => ColumnBuilder::Null(NullBuilder::new()),
+            Self::Bool => ColumnBuilder::Bool(BooleanBuilder::new()),
+            Self::U64 => ColumnBuilder::U64(UInt64Builder::new()),
+            Self::I64 => ColumnBuilder::I64(Int64Builder::new()),
+            Self::F64 => ColumnBuilder::F64(Float64Builder::new()),
+            Self::Str => ColumnBuilder::Str(StringBuilder::new()),
+        };
+        for _ in 0..nulls {
+            builder.append(None);
+        }
+        builder
+    }
+}
+
+enum ColumnBuilder {
+    Null(NullBuilder),
+    U64(UInt64Builder),
+    I64(Int64Builder),
+    F64(Float64Builder),
+    Bool(BooleanBuilder),
+    Str(StringBuilder),
+}
+
+impl ColumnBuilder {
+    /// Appends `value`, or a null if it is missing. The column's type must
+    /// fit `value`; strings take anything, as its JSON text.
+    fn append(&mut self, value: Option<&Value>) {
+        match self {
+            Self::Null(b) => b.append_null(),
+            Self::U64(b) => b.append_option(value.and_then(Value::as_u64)),
+            Self::I64(b) => b.append_option(value.and_then(Value::as_i64)),
+            Self::F64(b) => b.append_option(value.and_then(Value::as_f64)),
+            Self::Bool(b) => b.append_option(value.and_then(Value::as_bool)),
+            Self::Str(b) => match value {
+                None | Some(Value::Null) => b.append_null(),
+                Some(Value::String(s)) => b.append_value(s),
+                Some(other) => b.append_value(other.to_string()),
+            },
+        }
+    }
+
+    fn finish(&mut self) -> ArrayRef {
+        match self {
+            Self::Null(b) => Arc::new(b.finish()),
+            Self::U64(b) => Arc::new(b.finish()),
+            Self::I64(b) => Arc::new(b.finish()),
+            Self::F64(b) => Arc::new(b.finish()),
+            Self::Bool(b) => Arc::new(b.finish()),
+            Self::Str(b) => Arc::new(b.finish()),
+        }
+    }
+}
+
+/// Columnar, zstd-compressed sink for a stream of serializable records.
+/// Nested fields become dotted columns. The column set and types start
+/// from the first records and widen to fit later ones: until the first row
+/// group is written the file's schema changes in place, after that a
+/// record that does not fit closes the file and starts the next part,
+/// `<name>.1.parquet`, `<name>.2.parquet` and so on, with the wider schema.
+pub struct ParquetSink {
+    path: PathBuf,
+    record: String,
+    names: Vec<String>,
+    index: HashMap<String, usize>,
+    types: Vec<ColumnType>,
+    builders: Vec<ColumnBuilder>,
+    rows: usize,
+    /// The open part. Created when its first row group is written, so a
+    /// stream that never logs anything leaves no (invalid, footer-less)
+    /// file behind.
+    writer: Option<(ArrowWriter<File>, SchemaRef)>,
+    /// Parts closed so far.
+    parts: usize,
+    finished: bool,
+}
+
+impl ParquetSink {
+    pub fn new(path: &Path, record: &str) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            record: record.to_string(),
+            names: Vec::new(),
+            index: HashMap::new(),
+            types: Vec::new(),
+            builders: Vec::new(),
+            rows: 0,
+            writer: None,
+            parts: 0,
+            finished: false,
+        }
+    }
+
+    pub fn write<T: Serialize>(&mut self, record: &T) {
+        if self.finished {
+            return;
+        }
+        let Ok(value) = serde_json::to_value(record) else {
+            return;
+        };
+        let mut fields = Vec::new();
+        flatten("", value, &mut fields);
+
+        for (name, value) in &fields {
+            match self.index.get(name) {
+                Some(&column) if self.types[column].fits(value) => {}
+                Some(&column) => self.retype(column, self.types[column].widen(value)),
+                None => self.add_column(name, ColumnType::of(value)),
+            }
+        }
+        let row: HashMap<String, Value> = fields.into_iter().collect();
+        for (name, builder) in self.names.iter().zip(self.builders.iter_mut()) {
+            builder.append(row.get(name));
+        }
+        self.rows += 1;
+        if self.rows >= ROW_GROUP_ROWS {
+            self.flush();
+        }
+    }
+
+    /// Path of part `part`: the sink's own path, then `<stem>.<part>.<ext>`.
+    fn part_path(&self, part: usize) -> PathBuf {
+        if part == 0 {
+            return self.path.clone();
+        }
+        let stem = self.path.file_stem().unwrap_or_default().to_string_lossy();
+        let ext = self.path.extension().unwrap_or_default().to_string_lossy();
+        self.path.with_file_name(format!("{stem}.{part}.{ext}"))
+    }
+
+    fn add_column(&mut self, name: &str, ty: ColumnType) {
+        if self.writer.is_some() {
+            self.close_part();
+        }
+        self.index.insert(name.to_string(), self.names.len());
+        self.names.push(name.to_string());
+        self.types.push(ty);
+        self.builders.push(ty.builder(self.rows));
+    }
+
+    /// Only a column of nulls can change type under buffered rows; any
+    /// other change goes to a new part.
+    fn retype(&mut self, column: usize, ty: ColumnType) {
+        if self.writer.is_some() || (self.rows > 0 && self.types[column] != ColumnType::Null) {
+            self.close_part();
+        }
+        self.types[column] = ty;
+        self.builders[column] = ty.builder(self.rows);
+    }
+
+    fn open(&self) -> Option<(ArrowWriter<File>, SchemaRef)> {
+        let path = self.part_path(self.parts);
+        if self.parts == 0 {
+            self.remove_stale_parts();
+        }
+        let file = File::create(&path)
+            .map_err(|err| log::warn!("failed to create {}: {err}", path.display()))
+            .ok()?;
+        let metadata = HashMap::from([
+            (
+                "cyclotron.schema_version".to_string(),
+                PERF_LOG_SCHEMA_VERSION.to_string(),
+            ),
+            ("cyclotron.record".to_string(), self.record.clone()),
+            ("cyclotron.part".to_string(), self.parts.to_string()),
+        ]);
+        let props = WriterProperties::builder()
+            .set_compression(Compression::ZSTD(ZstdLevel::default()))
+            .build();
+        let schema = self.schema(metadata);
+        ArrowWriter::try_new(file, schema.clone(), Some(props))
+            .map_err(|err| log::warn!("failed to open parquet {} log: {err}", self.record))
+            .ok()
+            .map(|writer| (writer, schema))
+    }
+
+    /// Parts an earlier run left behind would otherwise read as part of
+    /// this one.
+    fn remove_stale_parts(&self) {
+        for part in 1.. {
+            if std::fs::remove_file(self.part_path(part)).is_err() {
+                break;
+            }
+        }
+    }
+
+    fn schema(&self, metadata: HashMap<String, String>) -> SchemaRef {
+        let fields: Vec<_> = self
+            .names
+            .iter()
+            .zip(&self.types)
+            .map(|(name, ty)| Field::new(name, ty.data_type(), true))
+            .collect();
+        Arc::new(Schema::new(fields).with_metadata(metadata))
+    }
+
+    /// Writes buffered rows out as a row group.
+    pub fn flush(&mut self) {
+        if self.rows == 0 {
+            return;
+        }
+        self.rows = 0;
+        if self.writer.is_none() {
+            self.writer = self.open();
+        }
+        let arrays: Vec<ArrayRef> = self
+            .builders
+            .iter_mut()
+            .map(ColumnBuilder::finish)
+            .collect();
+        let Some((writer, schema)) = self.writer.as_mut() else {
+            return;
+        };
+        let result = RecordBatch::try_new(schema.clone(), arrays)
+            .map_err(|err| err.to_string())
+            .and_then(|batch| writer.write(&batch).map_err(|err| err.to_string()));
+        if let Err(err) = result {
+            log::warn!("failed to write parquet {} log: {err}", self.record);
+        }
+    }
+
+    /// Flushes and writes the open part's footer. The next row group starts
+    /// a new part.
+    fn close_part(&mut self) {
+        self.flush();
+        if let Some((writer, _)) = self.writer.take() {
+            if let Err(err) = writer.close() {
+                log::warn!("failed to close parquet {} log: {err}", self.record);
+            }
+            self.parts += 1;
+        }
+    }
+
+    /// Flushes and writes the file footer. The sink ignores records after
+    /// this.
+    pub fn finish(&mut self) {
+        self.close_part();
+        self.finished = true;
+    }
+}
+
+// Every method takes `&mut self`, so a shared reference gives no access to
+// the (non-`Sync`) writer.
+unsafe impl Sync for ParquetSink {}
+
+impl Drop for ParquetSink {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow_array::{Array, BooleanArray, Float64Array, Int64Array, UInt64Array};
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+    use serde_json::json;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("cyclotron_{name}_{}.parquet", std::process::id()))
+    }
+
+    /// The file's schema and its rows as one batch; removes the file.
+    fn read(path: &Path) -> (SchemaRef, RecordBatch) {
+        let builder = ParquetRecordBatchReaderBuilder::try_new(File::open(path).unwrap()).unwrap();
+        let schema = builder.schema().clone();
+        let batch = builder.build().unwrap().next().unwrap().unwrap();
+        std::fs::remove_file(path).ok();
+        (schema, batch)
+    }
+
+    fn names(schema: &Schema) -> Vec<&str> {
+        schema.fields().iter().map(|f| f.name().as_str()).collect()
+    }
+
+    #[test]
+    fn records_round_trip_as_flattened_columns() {
+        let path = temp_path("parquet_sink");
+        let mut sink = ParquetSink::new(&path, "stats");
+        sink.write(&json!({"cycle": 1, "summary": {"ipc": 0.5, "lanes": [1, 2]}}));
+        sink.write(&json!({"cycle": 2, "summary": {"extra": true}}));
+        sink.finish();
+
+        let (schema, batch) = read(&path);
+        assert_eq!(
+            schema.metadata().get("cyclotron.schema_version"),
+            Some(&PERF_LOG_SCHEMA_VERSION.to_string())
+        );
+        // A field first seen in a later record still gets a column.
+        assert_eq!(
+            names(&schema),
+            ["cycle", "summary.ipc", "summary.lanes", "summary.extra"]
+        );
+        assert_eq!(batch.num_rows(), 2);
+        let cycle = batch
+            .column(0)
+            .as_any()
+            .downcast_ref::<UInt64Array>()
+            .unwrap();
+        assert_eq!(cycle.values(), &[1, 2]);
+        let ipc = batch
+            .column(1)
+            .as_any()
+            .downcast_ref::<Float64Array>()
+            .unwrap();
+        assert_eq!(ipc.value(0), 0.5);
+        assert!(ipc.is_null(1));
+        let extra = batch
+            .column(3)
+            .as_any()
+            .downcast_ref::<BooleanArray>()
+            .unwrap();
+        assert!(extra.is_null(0));
+        assert!(extra.value(1));
+    }
+
+    #[test]
+    fn records_that_do_not_fit_a_written_schema_start_a_new_part() {
+        let path = temp_path("parquet_parts");
+        let mut sink = ParquetSink::new(&path, "graph_backpressure");
+        sink.write(&json!({"cycle": 1, "capacity": null}));
+        sink.write(&json!({"cycle": 2, "capacity": 4}));
+        sink.flush();
+        sink.write(&json!({"cycle": 3, "capacity": -1, "reason": "full"}));
+        sink.finish();
+
+        let (schema, batch) = read(&path);
+        assert_eq!(names(&schema), ["capacity", "cycle"]);
+        assert_eq!(schema.field(0).data_type(), &DataType::UInt64);
+        assert_eq!(batch.num_rows(), 2);
+
+        let (schema, batch) = read(&sink.part_path(1));
+        assert_eq!(
+            schema.metadata().get("cyclotron.part"),
+            Some(&"1".to_string())
+        );
+        assert_eq!(names(&schema), ["capacity", "cycle", "reason"]);
+        assert_eq!(batch.num_rows(), 1);
+        let capacity = batch
+            .column(0)
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .unwrap();
+        assert_eq!(capacity.value(0), -1);
+        assert!(!sink.part_path(2).exists());
+    }
+}
@@ -1,15 +1,23 @@
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::env;
 use std::fs;
 use std::fs::File;
 use std::io::{BufWriter, Write};
 use std::ops::AddAssign;
 use std::path::{Path, PathBuf};
-use std::time::{SystemTime, UNIX_EPOCH};
 
 use serde::Serialize;
+use serde_json::Value;
 
 use crate::muon::gmem::CorePerfSummary;
+use crate::sim::config::{LogConfig, LogFormat};
+use crate::sim::journal;
+use crate::sim::metadata::RunMetadata;
+#[cfg(feature = "parquet")]
+use crate::sim::parquet_sink::ParquetSink;
+use crate::sim::sqlite_log::{GraphShape, Occupancy, RequestEvent, SqliteLog};
+use crate::timeflow::invariants::{self, InvariantSummary};
+use crate::timeflow::RequestPath;
 use crate::timeq::Cycle;
 
 fn core_file_name(base: &str, core_id: usize) -> String {
@@ -24,6 +32,42 @@ fn core_file_name(base: &str, core_id: usize) -> String {
 
 #[derive(Debug, Serialize)]
 pub struct RunPerfSummary {
+    pub metadata: RunMetadata,
+    pub per_core: Vec<CorePerfSummary>,
+    pub total: AggregatePerfSummary,
+    pub warmup_cycles: Cycle,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub warmup: Option<WarmupPerfSummary>,
+    /// Cycle a SIGINT or SIGTERM stopped the run at; the stats only cover
+    /// the cycles before it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interrupted_at: Option<Cycle>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub kernels: Vec<KernelPerfSummary>,
+    #[serde(skip_serializing_if = "InvariantSummary::is_clean")]
+    pub invariants: InvariantSummary,
+}
+
+/// Which clusters a co-scheduled kernel ran on and when it finished.
+#[derive(Debug, Clone, Serialize)]
+pub struct KernelReport {
+    pub name: String,
+    pub clusters: Vec<usize>,
+    pub finished_at: Option<Cycle>,
+}
+
+/// Totals over the cores of one kernel in a multi-kernel run.
+#[derive(Debug, Serialize)]
+pub struct KernelPerfSummary {
+    #[serde(flatten)]
+    pub kernel: KernelReport,
+    pub total: AggregatePerfSummary,
+}
+
+/// Stats accumulated during the warmup phase, kept apart from the
+/// steady-state numbers in `RunPerfSummary::total`.
+#[derive(Debug, Serialize)]
+pub struct WarmupPerfSummary {
     pub per_core: Vec<CorePerfSummary>,
     pub total: AggregatePerfSummary,
 }
@@ -31,6 +75,8 @@ pub struct RunPerfSummary {
 #[derive(Debug, Default, Serialize)]
 pub struct AggregatePerfSummary {
     pub num_cores: usize,
+    /// Cores among `num_cores` that ran without a timing graph.
+    pub functional_cores: usize,
     pub scheduler: crate::muon::gmem::SchedulerSummary,
     pub smem_util: crate::muon::gmem::SmemUtilSummary,
     pub execute_util: crate::muon::gmem::ExecuteUtilSummary,
@@ -40,13 +86,40 @@ pub struct AggregatePerfSummary {
     pub tensor_bytes_issued: u64,
     pub tensor_bytes_completed: u64,
     pub tensor_util: crate::muon::gmem::BasicUtilSummary,
+    pub neutrino_stats: crate::timeflow::NeutrinoStats,
+    pub neutrino_util: crate::muon::gmem::BasicUtilSummary,
+    pub copy_engine_stats: crate::timeflow::CopyEngineStats,
+    pub copy_engine_util: crate::muon::gmem::BasicUtilSummary,
     pub stall_summary: crate::muon::gmem::StallSummary,
+    pub retry_aging: crate::muon::gmem::RetryAgingSummary,
+    pub flush_ordering: crate::muon::gmem::FlushOrderingSummary,
+    pub request_timeouts: crate::timeflow::RequestTimeoutStats,
+    pub dependencies: crate::timeflow::DependencyStats,
+    pub warp_exits: crate::muon::gmem::WarpExitSummary,
+    pub wakeups: crate::muon::gmem::WakeupSummary,
+    pub cpi_stack: crate::muon::gmem::CpiStack,
+    pub power: crate::timeflow::PowerSummary,
+    pub context_switch: crate::timeflow::ContextSwitchStats,
+    pub reservation_stations: crate::timeflow::ReservationStationStats,
+    pub forwarding: crate::timeflow::ForwardingStats,
+    pub ibuffer: crate::timeflow::IbufferStats,
+    pub store_buffer: crate::timeflow::StoreBufferStats,
+    pub fences: crate::timeflow::FenceStats,
+    pub div_sqrt: crate::timeflow::DivSqrtStats,
     pub gmem_latency_hist: crate::muon::gmem::LatencyHistogram,
     pub smem_latency_hist: crate::muon::gmem::LatencyHistogram,
     pub smem_conflicts: crate::muon::gmem::SmemConflictSummary,
+    pub smem_warps: crate::muon::gmem::SmemWarpSummary,
+    pub access_patterns: crate::muon::gmem::AccessPatternSummary,
     pub gmem_hits: crate::muon::gmem::GmemHitSummary,
     pub latencies: crate::muon::gmem::LatencySummary,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub context_mem: Vec<crate::muon::gmem::ContextMemStats>,
+    pub mlp: crate::muon::gmem::MlpSummary,
     pub gmem_stats: crate::timeflow::GmemStats,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub memory_regions: Vec<crate::timeflow::MemoryRegionStats>,
+    pub crossbar: crate::timeflow::CrossbarPortStats,
     pub smem_stats: crate::timeflow::SmemStats,
     pub icache_stats: crate::timeflow::IcacheStats,
     pub lsu_stats: crate::timeflow::LsuStats,
@@ -73,13 +146,44 @@ impl AddAssign<&CorePerfSummary> for AggregatePerfSummary {
             .tensor_bytes_completed
             .saturating_add(core.tensor_bytes_completed);
         self.tensor_util += &core.tensor_util;
+        self.neutrino_stats += &core.neutrino_stats;
+        self.neutrino_util += &core.neutrino_util;
+        self.copy_engine_stats += &core.copy_engine_stats;
+        self.copy_engine_util += &core.copy_engine_util;
         self.stall_summary += &core.stall_summary;
+        self.retry_aging += &core.retry_aging;
+        self.flush_ordering += &core.flush_ordering;
+        self.request_timeouts += &core.request_timeouts;
+        self.dependencies += &core.dependencies;
+        self.warp_exits += &core.warp_exits;
+        self.wakeups += &core.wakeups;
+        self.cpi_stack += &core.cpi_stack;
+        self.power += &core.power;
+        self.context_switch += &core.context_switch;
+        self.reservation_stations += &core.reservation_stations;
+        self.forwarding += &core.forwarding;
+        self.ibuffer += &core.ibuffer;
+        self.store_buffer += &core.store_buffer;
+        self.fences += &core.fences;
+        self.div_sqrt += &core.div_sqrt;
         self.gmem_latency_hist += &core.gmem_latency_hist;
         self.smem_latency_hist += &core.smem_latency_hist;
         self.smem_conflicts += &core.smem_conflicts;
+        self.smem_warps += &core.smem_warps;
+        self.access_patterns += &core.access_patterns;
         self.gmem_hits += &core.gmem_hits;
         self.latencies += &core.latencies;
+        crate::muon::gmem::merge_context_mem(&mut self.context_mem, &core.context_mem);
+        self.mlp += &core.mlp;
         self.gmem_stats += &core.gmem_stats;
+        if self.memory_regions.len() < core.memory_regions.len() {
+            self.memory_regions
+                .resize_with(core.memory_regions.len(), Default::default);
+        }
+        for (total, region) in self.memory_regions.iter_mut().zip(&core.memory_regions) {
+            *total += region;
+        }
+        self.crossbar += &core.crossbar;
         self.smem_stats += &core.smem_stats;
         self.icache_stats += &core.icache_stats;
         self.lsu_stats += &core.lsu_stats;
@@ -93,6 +197,7 @@ impl AddAssign<&CorePerfSummary> for AggregatePerfSummary {
 #[derive(Debug, Serialize)]
 pub struct StatsRecord {
     pub cycle: Cycle,
+    pub warmup: bool,
     pub summary: CorePerfSummary,
 }
 
@@ -109,31 +214,129 @@ pub struct GraphBackpressureRecord {
     pub size_bytes: u32,
 }
 
+/// The nodes one sampled gmem request went through, written when it
+/// completes.
+#[derive(Debug, Serialize)]
+pub struct RequestPathRecord {
+    pub cycle: Cycle,
+    pub cluster_id: usize,
+    pub core_id: usize,
+    pub warp: usize,
+    pub request_id: u64,
+    pub addr: u64,
+    pub hops: RequestPath,
+}
+
+/// Flattens nested objects into `a.b.c` columns. Arrays are kept whole as
+/// JSON strings.
+pub(crate) fn flatten(prefix: &str, value: Value, out: &mut Vec<(String, Value)>) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                let name = if prefix.is_empty() {
+                    key
+                } else {
+                    format!("{prefix}.{key}")
+                };
+                flatten(&name, value, out);
+            }
+        }
+        Value::Array(_) => out.push((prefix.to_string(), Value::String(value.to_string()))),
+        scalar => out.push((prefix.to_string(), scalar)),
+    }
+}
+
+/// One streaming log file in the configured format.
+enum LogSink {
+    Jsonl(BufWriter<File>),
+    #[cfg(feature = "parquet")]
+    Parquet(Box<ParquetSink>),
+}
+
+impl LogSink {
+    /// Without the `parquet` feature every format is written as JSON lines.
+    fn create(run_dir: &Path, name: &str, format: LogFormat) -> Option<Self> {
+        let path = run_dir.join(format!("{name}.{}", format.extension()));
+        match format {
+            #[cfg(feature = "parquet")]
+            LogFormat::Parquet => Some(Self::Parquet(Box::new(ParquetSink::new(&path, name)))),
+            _ => File::create(path)
+                .ok()
+                .map(|file| Self::Jsonl(BufWriter::new(file))),
+        }
+    }
+
+    fn write<T: Serialize>(&mut self, record: &T) {
+        match self {
+            Self::Jsonl(writer) => {
+                if let Ok(payload) = serde_json::to_string(record) {
+                    let _ = writeln!(writer, "{payload}");
+                }
+            }
+            #[cfg(feature = "parquet")]
+            Self::Parquet(sink) => sink.write(record),
+        }
+    }
+
+    fn finish(&mut self) {
+        match self {
+            Self::Jsonl(writer) => {
+                let _ = writer.flush();
+            }
+            #[cfg(feature = "parquet")]
+            Self::Parquet(sink) => sink.finish(),
+        }
+    }
+}
+
 pub struct PerfLogSession {
     run_dir: PathBuf,
-    stats_writer: RefCell<BufWriter<File>>,
-    graph_writer: Option<RefCell<BufWriter<File>>>,
+    stats_writer: RefCell<LogSink>,
+    graph_writer: Option<RefCell<LogSink>>,
+    path_writer: Option<RefCell<LogSink>>,
+    path_sample: u64,
+    sqlite: Option<RefCell<SqliteLog>>,
+    warmup_cycles: Cell<Cycle>,
+    warmup: RefCell<Option<WarmupPerfSummary>>,
+    interrupted_at: Cell<Option<Cycle>>,
 }
 
 unsafe impl Send for PerfLogSession {}
 unsafe impl Sync for PerfLogSession {}
 
 impl PerfLogSession {
-    pub fn new() -> Option<Self> {
+    pub fn new(config: LogConfig) -> Option<Self> {
+        let format = config.format;
         let run_dir = create_run_dir()?;
-        let stats_file = File::create(run_dir.join("stats.jsonl")).ok()?;
+        let stats_writer = LogSink::create(&run_dir, "stats", format)?;
         let graph_writer = graph_log_enabled()
-            .then(|| {
-                File::create(run_dir.join("graph_backpressure.jsonl"))
-                    .ok()
-                    .map(|file| RefCell::new(BufWriter::new(file)))
-            })
+            .then(|| LogSink::create(&run_dir, "graph_backpressure", format).map(RefCell::new))
+            .flatten();
+        let path_writer = (config.path_sample > 0)
+            .then(|| LogSink::create(&run_dir, "request_paths", format).map(RefCell::new))
             .flatten();
+        let sqlite = config.sqlite.enabled.then(|| {
+            let path = run_dir.join("perf.sqlite");
+            SqliteLog::create(&path, config.sqlite)
+                .map_err(|err| log::warn!("failed to create {}: {err}", path.display()))
+                .ok()
+                .map(RefCell::new)
+        });
 
         Some(Self {
             run_dir,
-            stats_writer: RefCell::new(BufWriter::new(stats_file)),
+            stats_writer: RefCell::new(stats_writer),
             graph_writer,
+            path_sample: if path_writer.is_some() {
+                config.path_sample
+            } else {
+                0
+            },
+            path_writer,
+            sqlite: sqlite.flatten(),
+            warmup_cycles: Cell::new(0),
+            warmup: RefCell::new(None),
+            interrupted_at: Cell::new(None),
         })
     }
 
@@ -166,31 +369,135 @@ impl PerfLogSession {
     }
 
     pub fn write_stats(&self, record: &StatsRecord) {
-        self.write_json_line(&self.stats_writer, record);
+        self.write_record(&self.stats_writer, record);
+        self.with_sqlite(|sqlite| sqlite.write_stats(record));
+    }
+
+    /// Whether the SQLite log wants a graph occupancy snapshot at `cycle`.
+    pub fn snapshot_due(&self, cycle: Cycle) -> bool {
+        self.sqlite
+            .as_ref()
+            .is_some_and(|sqlite| sqlite.borrow().snapshot_due(cycle))
+    }
+
+    pub fn write_graph_snapshot(
+        &self,
+        cycle: Cycle,
+        shape: impl FnOnce() -> GraphShape,
+        nodes: &[Occupancy],
+        edges: &[Occupancy],
+    ) {
+        self.with_sqlite(|sqlite| sqlite.write_snapshot(cycle, shape, nodes, edges));
+    }
+
+    /// Whether request lifecycle events are being logged, so callers can
+    /// skip building them.
+    pub fn logs_request_events(&self) -> bool {
+        self.sqlite
+            .as_ref()
+            .is_some_and(|sqlite| sqlite.borrow().config().events)
+    }
+
+    pub fn write_request_event(&self, event: RequestEvent) {
+        self.with_sqlite(|sqlite| sqlite.write_event(event));
+    }
+
+    fn with_sqlite(&self, f: impl FnOnce(&mut SqliteLog)) {
+        if let Some(Ok(mut sqlite)) = self.sqlite.as_ref().map(RefCell::try_borrow_mut) {
+            f(&mut sqlite);
+        }
     }
 
     pub fn write_graph_backpressure(&self, record: &GraphBackpressureRecord) {
         if let Some(writer) = &self.graph_writer {
-            self.write_json_line(writer, record);
+            self.write_record(writer, record);
+        }
+    }
+
+    /// Every how many gmem requests one records its path; 0 if none do.
+    pub fn path_sample(&self) -> u64 {
+        self.path_sample
+    }
+
+    pub fn write_request_path(&self, record: &RequestPathRecord) {
+        if let Some(writer) = &self.path_writer {
+            self.write_record(writer, record);
         }
     }
 
-    pub fn write_summary(&self, per_core: Vec<CorePerfSummary>) {
+    pub fn set_warmup_cycles(&self, cycles: Cycle) {
+        self.warmup_cycles.set(cycles);
+    }
+
+    /// Marks the summary as partial, cut short at `cycle`.
+    pub fn set_interrupted_at(&self, cycle: Cycle) {
+        self.interrupted_at.set(Some(cycle));
+    }
+
+    pub fn in_warmup(&self, cycle: Cycle) -> bool {
+        cycle < self.warmup_cycles.get()
+    }
+
+    /// Stash the per-core stats gathered up to the end of warmup so the final
+    /// summary can report them separately.
+    pub fn record_warmup(&self, per_core: Vec<CorePerfSummary>) {
+        let warmup = WarmupPerfSummary {
+            total: aggregate_summaries(&per_core),
+            per_core,
+        };
+        *self.warmup.borrow_mut() = Some(warmup);
+    }
+
+    pub fn write_summary(
+        &self,
+        per_core: Vec<CorePerfSummary>,
+        kernels: Vec<KernelReport>,
+        metadata: RunMetadata,
+    ) {
+        let kernels = kernels
+            .into_iter()
+            .map(|kernel| {
+                let cores: Vec<_> = per_core
+                    .iter()
+                    .filter(|core| kernel.clusters.contains(&core.cluster_id))
+                    .cloned()
+                    .collect();
+                KernelPerfSummary {
+                    kernel,
+                    total: aggregate_summaries(&cores),
+                }
+            })
+            .collect();
         let summary = RunPerfSummary {
+            metadata,
             total: aggregate_summaries(&per_core),
+            kernels,
             per_core,
+            warmup_cycles: self.warmup_cycles.get(),
+            warmup: self.warmup.borrow_mut().take(),
+            interrupted_at: self.interrupted_at.get(),
+            invariants: invariants::summary(),
         };
         let path = self.run_dir.join("summary.json");
         if let Ok(payload) = serde_json::to_string_pretty(&summary) {
             let _ = fs::write(path, payload);
         }
+        // The summary is the last thing a run writes; close the streams so
+        // Parquet files get their footer even if the session is never dropped.
+        for writer in std::iter::once(&self.stats_writer)
+            .chain(&self.graph_writer)
+            .chain(&self.path_writer)
+        {
+            if let Ok(mut guard) = writer.try_borrow_mut() {
+                guard.finish();
+            }
+        }
+        self.with_sqlite(SqliteLog::flush);
     }
 
-    fn write_json_line<T: Serialize>(&self, writer: &RefCell<BufWriter<File>>, record: &T) {
+    fn write_record<T: Serialize>(&self, writer: &RefCell<LogSink>, record: &T) {
         if let Ok(mut guard) = writer.try_borrow_mut() {
-            if let Ok(payload) = serde_json::to_string(record) {
-                let _ = writeln!(guard, "{payload}");
-            }
+            guard.write(record);
         }
     }
 }
@@ -213,10 +520,7 @@ fn create_run_dir() -> Option<PathBuf> {
         return None;
     }
 
-    let ts = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .map(|d| d.as_nanos())
-        .unwrap_or(0);
+    let ts = journal::host_time().as_nanos();
     let pid = std::process::id();
     let run_dir = root.join(format!("run_{ts}_{pid}"));
     if fs::create_dir_all(&run_dir).is_err() {
@@ -228,6 +532,7 @@ fn create_run_dir() -> Option<PathBuf> {
 pub fn aggregate_summaries(per_core: &[CorePerfSummary]) -> AggregatePerfSummary {
     let mut total = AggregatePerfSummary {
         num_cores: per_core.len(),
+        functional_cores: per_core.iter().filter(|core| core.functional).count(),
         ..AggregatePerfSummary::default()
     };
     for core in per_core {
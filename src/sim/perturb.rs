@@ -0,0 +1,236 @@
+use std::fmt;
+
+use rand::Rng;
+use serde::Serialize;
+use toml::Value;
+
+use crate::muon::gmem::CorePerfSummary;
+
+/// Scales every integer latency in `timing` (`base_latency`, `latency` and
+/// other `*_latency` keys, at any depth) by `1 + u` with `u` drawn uniformly
+/// from `[-jitter, jitter]`, rounding to the nearest cycle. Keys the config
+/// leaves at their defaults are not touched. Returns the number of keys
+/// scaled.
+pub fn perturb_latencies<R: Rng>(timing: &mut Value, jitter: f64, rng: &mut R) -> usize {
+    match timing {
+        Value::Table(table) => table
+            .iter_mut()
+            .map(|(key, value)| match value {
+                Value::Integer(latency) if is_latency_key(key) => {
+                    let scale = 1.0 + rng.gen_range(-jitter..=jitter);
+                    *latency = (*latency as f64 * scale).round().max(0.0) as i64;
+                    1
+                }
+                _ => perturb_latencies(value, jitter, rng),
+            })
+            .sum(),
+        Value::Array(items) => items
+            .iter_mut()
+            .map(|item| perturb_latencies(item, jitter, rng))
+            .sum(),
+        _ => 0,
+    }
+}
+
+fn is_latency_key(key: &str) -> bool {
+    key == "latency" || key.ends_with("_latency")
+}
+
+/// End-of-run metrics of one perturbed run.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct RunMetrics {
+    pub seed: u64,
+    pub cycles: u64,
+    pub instructions: u64,
+    pub ipc: f64,
+    /// Mean gmem load-to-use latency, in cycles.
+    pub gmem_latency: f64,
+    /// Mean smem load-to-use latency, in cycles.
+    pub smem_latency: f64,
+}
+
+impl RunMetrics {
+    /// Sums the timed cores of a finished run.
+    pub fn from_summaries(seed: u64, cycles: u64, summaries: &[CorePerfSummary]) -> Self {
+        let timed = summaries.iter().filter(|summary| !summary.functional);
+        let mut instructions = 0;
+        let (mut gmem_count, mut gmem_sum, mut smem_count, mut smem_sum) = (0, 0, 0, 0);
+        for summary in timed {
+            instructions += summary.cpi_stack.instructions;
+            gmem_count += summary.latencies.gmem_count;
+            gmem_sum += summary.latencies.gmem_sum;
+            smem_count += summary.latencies.smem_count;
+            smem_sum += summary.latencies.smem_sum;
+        }
+        let mean = |sum: u64, count: u64| sum as f64 / count.max(1) as f64;
+        Self {
+            seed,
+            cycles,
+            instructions,
+            ipc: mean(instructions, cycles),
+            gmem_latency: mean(gmem_sum, gmem_count),
+            smem_latency: mean(smem_sum, smem_count),
+        }
+    }
+}
+
+/// Mean and spread of one metric over the runs.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MetricSpread {
+    pub metric: String,
+    pub mean: f64,
+    /// Sample standard deviation; 0 for a single run.
+    pub stddev: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+impl MetricSpread {
+    pub fn new(metric: &str, values: &[f64]) -> Self {
+        let n = values.len().max(1) as f64;
+        let mean = values.iter().sum::<f64>() / n;
+        let variance = if values.len() > 1 {
+            values
+                .iter()
+                .map(|value| (value - mean).powi(2))
+                .sum::<f64>()
+                / (n - 1.0)
+        } else {
+            0.0
+        };
+        Self {
+            metric: metric.to_string(),
+            mean,
+            stddev: variance.sqrt(),
+            min: values.iter().copied().fold(f64::INFINITY, f64::min),
+            max: values.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+        }
+    }
+
+    /// Standard deviation as a fraction of the mean.
+    pub fn relative_stddev(&self) -> f64 {
+        if self.mean == 0.0 {
+            0.0
+        } else {
+            self.stddev / self.mean.abs()
+        }
+    }
+}
+
+/// Metrics of every run of `cyclotron perturb` and their spread.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PerturbationReport {
+    /// Largest relative change applied to each latency.
+    pub jitter: f64,
+    pub runs: Vec<RunMetrics>,
+    pub spread: Vec<MetricSpread>,
+}
+
+impl PerturbationReport {
+    pub fn new(jitter: f64, runs: Vec<RunMetrics>) -> Self {
+        let metric = |name: &str, value: fn(&RunMetrics) -> f64| {
+            MetricSpread::new(name, &runs.iter().map(value).collect::<Vec<_>>())
+        };
+        let spread = vec![
+            metric("cycles", |run| run.cycles as f64),
+            metric("instructions", |run| run.instructions as f64),
+            metric("ipc", |run| run.ipc),
+            metric("gmem_latency", |run| run.gmem_latency),
+            metric("smem_latency", |run| run.smem_latency),
+        ];
+        Self {
+            jitter,
+            runs,
+            spread,
+        }
+    }
+}
+
+impl fmt::Display for PerturbationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} runs, latencies jittered by up to {:.1}%",
+            self.runs.len(),
+            self.jitter * 100.0
+        )?;
+        for run in &self.runs {
+            writeln!(
+                f,
+                "  seed {:<6} {:>10} cycles  IPC {:.3}  gmem {:.1}  smem {:.1}",
+                run.seed, run.cycles, run.ipc, run.gmem_latency, run.smem_latency
+            )?;
+        }
+        writeln!(
+            f,
+            "{:<14} {:>12} {:>12} {:>7} {:>12} {:>12}",
+            "metric", "mean", "stddev", "cv", "min", "max"
+        )?;
+        for spread in &self.spread {
+            writeln!(
+                f,
+                "{:<14} {:>12.3} {:>12.3} {:>6.2}% {:>12.3} {:>12.3}",
+                spread.metric,
+                spread.mean,
+                spread.stddev,
+                spread.relative_stddev() * 100.0,
+                spread.min,
+                spread.max
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn timing() -> Value {
+        toml::from_str(
+            r#"
+            [gmem.nodes.dram]
+            base_latency = 100
+            queue_capacity = 64
+
+            [[gmem.levels]]
+            data = { base_latency = 1000, warmup_latency = 0 }
+
+            [gmem.remote.link]
+            latency = 40
+            "#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn jitters_only_latency_keys_within_bounds() {
+        let mut perturbed = timing();
+        let scaled = perturb_latencies(&mut perturbed, 0.05, &mut StdRng::seed_from_u64(7));
+        assert_eq!(scaled, 4);
+
+        let dram = &perturbed["gmem"]["nodes"]["dram"];
+        assert!((95..=105).contains(&dram["base_latency"].as_integer().unwrap()));
+        assert_eq!(dram["queue_capacity"].as_integer(), Some(64));
+        let data = &perturbed["gmem"]["levels"][0]["data"];
+        assert!((950..=1050).contains(&data["base_latency"].as_integer().unwrap()));
+        assert_eq!(data["warmup_latency"].as_integer(), Some(0));
+
+        let mut again = timing();
+        perturb_latencies(&mut again, 0.05, &mut StdRng::seed_from_u64(7));
+        assert_eq!(again, perturbed);
+    }
+
+    #[test]
+    fn spread_is_sample_mean_and_stddev() {
+        let spread = MetricSpread::new("cycles", &[2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0]);
+        assert_eq!(spread.mean, 5.0);
+        assert!((spread.stddev - (32.0f64 / 7.0).sqrt()).abs() < 1e-12);
+        assert_eq!((spread.min, spread.max), (2.0, 9.0));
+
+        let single = MetricSpread::new("cycles", &[3.0]);
+        assert_eq!((single.mean, single.stddev), (3.0, 0.0));
+    }
+}
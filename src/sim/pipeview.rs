@@ -0,0 +1,154 @@
+//! Per-instruction pipeline log in gem5's O3PipeView format, for viewing
+//! how warps' instructions interleave and stall in Konata or gem5's
+//! `util/o3-pipeview.py`.
+//!
+//! Each instruction is one block of `O3PipeView:<stage>:<tick>` lines,
+//! written once it has finished. The stages map onto the core's timing
+//! path as follows:
+//!
+//! - `fetch`: the first cycle the warp tried to fetch the instruction.
+//! - `decode`, `rename`: the cycle it reached the head of the warp's
+//!   instruction buffer. Muon does not rename, so both get the same stamp.
+//! - `dispatch`: the first cycle the issue scheduler picked it.
+//! - `issue`: the cycle it entered an execute unit, the LSU or another
+//!   engine.
+//! - `complete`: the cycle its result was ready. For loads and cache flushes
+//!   this is when the last memory request completed.
+//! - `retire`: the cycle it left the warp's issue slot, or completed if that
+//!   was later. Stores also record when their last request completed.
+//!
+//! Sequence numbers are assigned in the order instructions finish, across
+//! every recorded core. The disassembly field names the core and warp,
+//! e.g. `c0.1 w3 0x0000000000a50513`.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+use crate::muon::gmem::InstLifecycle;
+use crate::sim::config::PipeviewConfig;
+
+pub struct PipeviewWriter<W: Write = BufWriter<File>> {
+    out: W,
+    ticks_per_cycle: u64,
+    cores: Option<Vec<usize>>,
+    next_seq: u64,
+}
+
+impl PipeviewWriter {
+    /// Creates `config.path`. Returns `Ok(None)` when no path is set.
+    pub fn create(config: &PipeviewConfig) -> io::Result<Option<Self>> {
+        let Some(path) = &config.path else {
+            return Ok(None);
+        };
+        let out = BufWriter::new(File::create(path)?);
+        let mut writer = Self::new(out, config.ticks_per_cycle);
+        writer.cores = config.cores.clone();
+        Ok(Some(writer))
+    }
+}
+
+impl<W: Write> PipeviewWriter<W> {
+    pub fn new(out: W, ticks_per_cycle: u64) -> Self {
+        Self {
+            out,
+            ticks_per_cycle: ticks_per_cycle.max(1),
+            cores: None,
+            next_seq: 1,
+        }
+    }
+
+    /// Whether the core with global id `global_id` is recorded.
+    pub fn records_core(&self, global_id: usize) -> bool {
+        self.cores
+            .as_ref()
+            .is_none_or(|ids| ids.contains(&global_id))
+    }
+
+    pub fn write(
+        &mut self,
+        cluster_id: usize,
+        core_id: usize,
+        inst: &InstLifecycle,
+    ) -> io::Result<()> {
+        let tick = |cycle: u64| cycle.saturating_mul(self.ticks_per_cycle);
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        writeln!(
+            self.out,
+            "O3PipeView:fetch:{}:0x{:08x}:0:{seq}:c{cluster_id}.{core_id} w{} 0x{:016x}",
+            tick(inst.fetch),
+            inst.pc,
+            inst.warp,
+            inst.raw
+        )?;
+        for (stage, cycle) in [
+            ("decode", inst.decode),
+            ("rename", inst.decode),
+            ("dispatch", inst.dispatch),
+            ("issue", inst.issue),
+            ("complete", inst.complete),
+        ] {
+            writeln!(self.out, "O3PipeView:{stage}:{}", tick(cycle))?;
+        }
+        writeln!(
+            self.out,
+            "O3PipeView:retire:{}:store:{}",
+            tick(inst.retire),
+            inst.store.map_or(0, tick)
+        )
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_one_block_per_instruction() {
+        let mut writer = PipeviewWriter::new(Vec::new(), 1000);
+        let load = InstLifecycle {
+            warp: 2,
+            pc: 0x8000_0010,
+            raw: 0x0005_2503,
+            fetch: 3,
+            decode: 5,
+            dispatch: 6,
+            issue: 7,
+            complete: 40,
+            retire: 40,
+            store: None,
+        };
+        writer.write(0, 1, &load).unwrap();
+        writer
+            .write(
+                0,
+                1,
+                &InstLifecycle {
+                    store: Some(50),
+                    ..load
+                },
+            )
+            .unwrap();
+
+        let text = String::from_utf8(writer.out).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(
+            lines[..7],
+            [
+                "O3PipeView:fetch:3000:0x80000010:0:1:c0.1 w2 0x0000000000052503",
+                "O3PipeView:decode:5000",
+                "O3PipeView:rename:5000",
+                "O3PipeView:dispatch:6000",
+                "O3PipeView:issue:7000",
+                "O3PipeView:complete:40000",
+                "O3PipeView:retire:40000:store:0",
+            ]
+        );
+        assert!(lines[7].contains(":0:2:"), "{}", lines[7]);
+        assert_eq!(lines[13], "O3PipeView:retire:40000:store:50000");
+    }
+}
@@ -0,0 +1,315 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+use std::path::Path;
+
+use serde::Serialize;
+use serde_json::Value;
+
+/// End-of-run metrics and config of one run of a sweep, read back from its
+/// `summary.json`.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SweepRun {
+    /// Path of the report relative to the merged directory, without
+    /// `summary.json` or the `.json` extension.
+    pub name: String,
+    pub cycles: u64,
+    pub instructions: u64,
+    pub ipc: f64,
+    /// Mean gmem load-to-use latency, in cycles.
+    pub gmem_latency: f64,
+    /// The run's config, flattened to dotted keys.
+    #[serde(skip)]
+    pub settings: BTreeMap<String, String>,
+}
+
+impl SweepRun {
+    /// `None` if `summary` is not a perf summary with its config attached.
+    pub fn from_summary(name: String, summary: &Value) -> Option<Self> {
+        let config = summary.pointer("/metadata/config")?;
+        let total = summary.get("total")?;
+        let number = |value: &Value, pointer: &str| value.pointer(pointer).and_then(Value::as_u64);
+        // Cores run in lockstep, so the run lasted as long as its longest
+        // timed core.
+        let cycles = summary
+            .get("per_core")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter(|core| core.get("functional") != Some(&Value::Bool(true)))
+            .filter_map(|core| number(core, "/cpi_stack/cycles"))
+            .max()
+            .unwrap_or(0);
+        let instructions = number(total, "/cpi_stack/instructions").unwrap_or(0);
+        let gmem_sum = number(total, "/latencies/gmem_sum").unwrap_or(0);
+        let gmem_count = number(total, "/latencies/gmem_count").unwrap_or(0);
+        let mut settings = BTreeMap::new();
+        flatten("", config, &mut settings);
+        Some(Self {
+            name,
+            cycles,
+            instructions,
+            ipc: instructions as f64 / cycles.max(1) as f64,
+            gmem_latency: gmem_sum as f64 / gmem_count.max(1) as f64,
+            settings,
+        })
+    }
+}
+
+fn flatten(prefix: &str, value: &Value, out: &mut BTreeMap<String, String>) {
+    match value {
+        Value::Object(table) => {
+            for (key, value) in table {
+                let key = if prefix.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{prefix}.{key}")
+                };
+                flatten(&key, value, out);
+            }
+        }
+        Value::Null => {}
+        Value::String(text) => {
+            out.insert(prefix.to_string(), text.clone());
+        }
+        other => {
+            out.insert(prefix.to_string(), other.to_string());
+        }
+    }
+}
+
+/// Reads every JSON report under `dir`, recursively, in path order. Files
+/// that are not perf summaries are skipped with a warning.
+pub fn load_runs(dir: &Path) -> std::io::Result<Vec<SweepRun>> {
+    let mut files = Vec::new();
+    collect_json_files(dir, &mut files)?;
+    files.sort();
+    let mut runs = Vec::new();
+    for path in files {
+        let summary = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|text| serde_json::from_str::<Value>(&text).ok());
+        let name = run_name(dir, &path);
+        match summary.and_then(|summary| SweepRun::from_summary(name, &summary)) {
+            Some(run) => runs.push(run),
+            None => log::warn!("skipping {}: not a perf summary", path.display()),
+        }
+    }
+    Ok(runs)
+}
+
+fn collect_json_files(dir: &Path, files: &mut Vec<std::path::PathBuf>) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_json_files(&path, files)?;
+        } else if path.extension().is_some_and(|ext| ext == "json") {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn run_name(dir: &Path, path: &Path) -> String {
+    let relative = path.strip_prefix(dir).unwrap_or(path);
+    let relative = if relative
+        .file_name()
+        .is_some_and(|name| name == "summary.json")
+    {
+        relative.parent().unwrap_or(relative).to_path_buf()
+    } else {
+        relative.with_extension("")
+    };
+    match relative.to_string_lossy() {
+        name if name.is_empty() => ".".to_string(),
+        name => name.into_owned(),
+    }
+}
+
+/// One run in a `MergedReport`.
+#[derive(Debug, Clone, Serialize)]
+pub struct MergedRow {
+    #[serde(flatten)]
+    pub run: SweepRun,
+    /// The run's value of each of `MergedReport::deltas`; empty where the
+    /// config does not set the key.
+    pub settings: Vec<String>,
+    /// Baseline cycles over this run's.
+    pub speedup: f64,
+}
+
+/// The runs of a sweep side by side: the config keys they differ in and
+/// their metrics against the baseline run.
+#[derive(Debug, Clone, Serialize)]
+pub struct MergedReport {
+    pub baseline: String,
+    /// Config keys not set to the same value in every run.
+    pub deltas: Vec<String>,
+    pub rows: Vec<MergedRow>,
+}
+
+impl MergedReport {
+    /// `baseline` names the run speedups are relative to; the first run
+    /// when unset.
+    pub fn new(runs: Vec<SweepRun>, baseline: Option<&str>) -> Result<Self, String> {
+        let Some(first) = runs.first() else {
+            return Err("no perf summaries to merge".to_string());
+        };
+        let base = match baseline {
+            Some(name) => runs
+                .iter()
+                .find(|run| run.name == name)
+                .ok_or_else(|| format!("no run named {name}"))?,
+            None => first,
+        };
+        let (baseline, base_cycles) = (base.name.clone(), base.cycles);
+
+        let keys: BTreeSet<&String> = runs.iter().flat_map(|run| run.settings.keys()).collect();
+        let deltas: Vec<String> = keys
+            .into_iter()
+            .filter(|key| {
+                let first = first.settings.get(*key);
+                runs.iter().any(|run| run.settings.get(*key) != first)
+            })
+            .cloned()
+            .collect();
+
+        let rows = runs
+            .into_iter()
+            .map(|run| MergedRow {
+                settings: deltas
+                    .iter()
+                    .map(|key| run.settings.get(key).cloned().unwrap_or_default())
+                    .collect(),
+                speedup: base_cycles as f64 / run.cycles.max(1) as f64,
+                run,
+            })
+            .collect();
+        Ok(Self {
+            baseline,
+            deltas,
+            rows,
+        })
+    }
+
+    fn header(&self) -> Vec<String> {
+        ["run"]
+            .into_iter()
+            .map(str::to_string)
+            .chain(self.deltas.iter().cloned())
+            .chain(
+                ["cycles", "instructions", "ipc", "gmem_latency", "speedup"]
+                    .into_iter()
+                    .map(str::to_string),
+            )
+            .collect()
+    }
+
+    fn cells(&self, row: &MergedRow) -> Vec<String> {
+        std::iter::once(row.run.name.clone())
+            .chain(row.settings.iter().cloned())
+            .chain([
+                row.run.cycles.to_string(),
+                row.run.instructions.to_string(),
+                format!("{:.4}", row.run.ipc),
+                format!("{:.2}", row.run.gmem_latency),
+                format!("{:.4}", row.speedup),
+            ])
+            .collect()
+    }
+
+    pub fn to_csv(&self) -> String {
+        let line = |cells: Vec<String>| {
+            cells
+                .iter()
+                .map(|cell| csv_field(cell))
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+        let mut out = line(self.header());
+        out.push('\n');
+        for row in &self.rows {
+            out.push_str(&line(self.cells(row)));
+            out.push('\n');
+        }
+        out
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let line = |cells: Vec<String>| {
+            let cells: Vec<String> = cells.iter().map(|cell| cell.replace('|', "\\|")).collect();
+            format!("| {} |\n", cells.join(" | "))
+        };
+        let header = self.header();
+        let mut out = String::new();
+        let _ = writeln!(out, "Speedup relative to `{}`.\n", self.baseline);
+        out.push_str(&line(header.clone()));
+        out.push_str(&line(header.iter().map(|_| "---".to_string()).collect()));
+        for row in &self.rows {
+            out.push_str(&line(self.cells(row)));
+        }
+        out
+    }
+}
+
+fn csv_field(cell: &str) -> String {
+    if cell.contains([',', '"', '\n']) {
+        format!("\"{}\"", cell.replace('"', "\"\""))
+    } else {
+        cell.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn summary(l2_kb: u64, cycles: u64, log_dir: &str) -> Value {
+        json!({
+            "metadata": {
+                "config": {
+                    "sim": { "elf": "vecadd.elf", "log_dir": log_dir },
+                    "timing": { "l2": { "size_kb": l2_kb } },
+                },
+            },
+            "per_core": [
+                { "functional": false, "cpi_stack": { "cycles": cycles } },
+                { "functional": true, "cpi_stack": { "cycles": 0 } },
+            ],
+            "total": {
+                "cpi_stack": { "instructions": 1000 },
+                "latencies": { "gmem_sum": 300, "gmem_count": 10 },
+            },
+        })
+    }
+
+    #[test]
+    fn merge_keeps_only_differing_keys_and_computes_speedups() {
+        let runs = vec![
+            SweepRun::from_summary("l2_64".into(), &summary(64, 2000, "a,b")).unwrap(),
+            SweepRun::from_summary("l2_128".into(), &summary(128, 1600, "c")).unwrap(),
+        ];
+        assert_eq!(runs[0].cycles, 2000);
+        assert_eq!(runs[0].gmem_latency, 30.0);
+
+        let report = MergedReport::new(runs, Some("l2_64")).unwrap();
+        assert_eq!(report.deltas, ["sim.log_dir", "timing.l2.size_kb"]);
+        assert_eq!(report.rows[1].speedup, 1.25);
+        let csv = report.to_csv();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(
+            lines[0],
+            "run,sim.log_dir,timing.l2.size_kb,cycles,instructions,ipc,gmem_latency,speedup"
+        );
+        assert_eq!(lines[1], "l2_64,\"a,b\",64,2000,1000,0.5000,30.00,1.0000");
+        assert!(report.to_markdown().contains("| l2_128 | c | 128 | 1600 |"));
+    }
+
+    #[test]
+    fn unknown_baseline_and_empty_sweeps_are_errors() {
+        let runs = vec![SweepRun::from_summary("a".into(), &summary(64, 10, "x")).unwrap()];
+        assert!(MergedReport::new(runs, Some("b")).is_err());
+        assert!(MergedReport::new(Vec::new(), None).is_err());
+        assert!(SweepRun::from_summary("c".into(), &json!({ "total": {} })).is_none());
+    }
+}
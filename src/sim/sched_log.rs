@@ -0,0 +1,412 @@
+//! Per-cycle log of issue scheduler decisions, for training and evaluating
+//! scheduling policies offline, and replay of a file of decisions in place
+//! of the built-in scheduler.
+//!
+//! The file starts with the 8-byte magic `CYCSCHD1`, followed by one record
+//! per core per cycle the core had an active warp. All integers are
+//! little-endian:
+//!
+//! - `cycle: u64`, `core: u32` (global id, `cluster * num_cores + core`),
+//!   `warps: u32`
+//! - one byte per warp: bit 0 active, bit 1 ready, bit 2 granted, and the
+//!   stall reason in bits 4-7: 0 none, then gmem, smem, fence, execute,
+//!   sync, icache and copy from 1.
+//!
+//! The warps granted in a record are the ones that issued. Ready warps
+//! that were not granted are the alternatives the scheduler passed over.
+//!
+//! With `log.format = "parquet"` the log is written as Parquet instead, to
+//! the path with its extension replaced by `.parquet`: one row per warp per
+//! record, with columns `cycle`, `core`, `warp`, `active`, `ready`,
+//! `granted` and `stall` (the code above). Replay reads the binary format
+//! only.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+#[cfg(feature = "parquet")]
+use serde::Serialize;
+
+use crate::muon::gmem::{CpiStall, IssuePolicy, SchedDecision, WarpSchedState};
+use crate::sim::config::{LogFormat, SchedLogConfig};
+#[cfg(feature = "parquet")]
+use crate::sim::parquet_sink::ParquetSink;
+use crate::timeq::Cycle;
+
+const MAGIC: &[u8; 8] = b"CYCSCHD1";
+
+const STALLS: [CpiStall; 7] = [
+    CpiStall::Gmem,
+    CpiStall::Smem,
+    CpiStall::Fence,
+    CpiStall::Execute,
+    CpiStall::Sync,
+    CpiStall::Icache,
+    CpiStall::Copy,
+];
+
+const ACTIVE: u8 = 1;
+const READY: u8 = 1 << 1;
+const GRANTED: u8 = 1 << 2;
+
+fn stall_code(stall: Option<CpiStall>) -> u8 {
+    stall
+        .and_then(|stall| STALLS.iter().position(|&s| s == stall))
+        .map_or(0, |index| index as u8 + 1)
+}
+
+fn encode_warp(warp: &WarpSchedState) -> u8 {
+    let stall = stall_code(warp.stall);
+    (warp.active as u8 * ACTIVE)
+        | (warp.ready as u8 * READY)
+        | (warp.granted as u8 * GRANTED)
+        | (stall << 4)
+}
+
+fn decode_warp(byte: u8) -> Result<WarpSchedState, String> {
+    let stall = match byte >> 4 {
+        0 => None,
+        code => Some(
+            *STALLS
+                .get(code as usize - 1)
+                .ok_or(format!("unknown stall reason {code}"))?,
+        ),
+    };
+    Ok(WarpSchedState {
+        active: byte & ACTIVE != 0,
+        ready: byte & READY != 0,
+        stall,
+        granted: byte & GRANTED != 0,
+    })
+}
+
+pub fn encode_decision(out: &mut Vec<u8>, decision: &SchedDecision) {
+    out.extend_from_slice(&decision.cycle.to_le_bytes());
+    out.extend_from_slice(&(decision.core_id as u32).to_le_bytes());
+    out.extend_from_slice(&(decision.warps.len() as u32).to_le_bytes());
+    out.extend(decision.warps.iter().map(encode_warp));
+}
+
+pub struct SchedLogWriter<W: Write = BufWriter<File>> {
+    out: W,
+    cores: Option<Vec<usize>>,
+    buf: Vec<u8>,
+}
+
+impl SchedLogWriter {
+    /// Creates `config.path`. Returns `Ok(None)` when no path is set.
+    pub fn create(config: &SchedLogConfig) -> io::Result<Option<Self>> {
+        let Some(path) = &config.path else {
+            return Ok(None);
+        };
+        let mut writer = Self::new(BufWriter::new(File::create(path)?))?;
+        writer.cores = config.cores.clone();
+        Ok(Some(writer))
+    }
+}
+
+impl<W: Write> SchedLogWriter<W> {
+    pub fn new(mut out: W) -> io::Result<Self> {
+        out.write_all(MAGIC)?;
+        Ok(Self {
+            out,
+            cores: None,
+            buf: Vec::new(),
+        })
+    }
+
+    /// Whether the core with global id `global_id` is recorded.
+    pub fn records_core(&self, global_id: usize) -> bool {
+        self.cores
+            .as_ref()
+            .is_none_or(|ids| ids.contains(&global_id))
+    }
+
+    pub fn write(&mut self, decision: &SchedDecision) -> io::Result<()> {
+        self.buf.clear();
+        encode_decision(&mut self.buf, decision);
+        self.out.write_all(&self.buf)
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+/// One warp of a record in the Parquet scheduler log.
+#[cfg(feature = "parquet")]
+#[derive(Serialize)]
+struct SchedWarpRecord {
+    cycle: Cycle,
+    core: usize,
+    warp: usize,
+    active: bool,
+    ready: bool,
+    granted: bool,
+    stall: u8,
+}
+
+/// The scheduler log in the configured log format.
+pub enum SchedLogSink {
+    Binary(SchedLogWriter),
+    #[cfg(feature = "parquet")]
+    Parquet {
+        sink: Box<ParquetSink>,
+        cores: Option<Vec<usize>>,
+    },
+}
+
+impl SchedLogSink {
+    /// Creates the log for `config.path`. Returns `Ok(None)` when no path
+    /// is set.
+    pub fn create(config: &SchedLogConfig, format: LogFormat) -> io::Result<Option<Self>> {
+        match format.supported() {
+            #[cfg(feature = "parquet")]
+            LogFormat::Parquet => Ok(config.path.as_ref().map(|path| Self::Parquet {
+                sink: Box::new(ParquetSink::new(&path.with_extension("parquet"), "sched")),
+                cores: config.cores.clone(),
+            })),
+            _ => Ok(SchedLogWriter::create(config)?.map(Self::Binary)),
+        }
+    }
+
+    /// Where `create` writes the log for `path`.
+    pub fn path(path: &Path, format: LogFormat) -> PathBuf {
+        match format.supported() {
+            LogFormat::Parquet => path.with_extension("parquet"),
+            LogFormat::Jsonl => path.to_path_buf(),
+        }
+    }
+
+    /// Whether the core with global id `global_id` is recorded.
+    pub fn records_core(&self, global_id: usize) -> bool {
+        match self {
+            Self::Binary(writer) => writer.records_core(global_id),
+            #[cfg(feature = "parquet")]
+            Self::Parquet { cores, .. } => {
+                cores.as_ref().is_none_or(|ids| ids.contains(&global_id))
+            }
+        }
+    }
+
+    pub fn write(&mut self, decision: &SchedDecision) -> io::Result<()> {
+        match self {
+            Self::Binary(writer) => writer.write(decision),
+            #[cfg(feature = "parquet")]
+            Self::Parquet { sink, .. } => {
+                for (warp, state) in decision.warps.iter().enumerate() {
+                    sink.write(&SchedWarpRecord {
+                        cycle: decision.cycle,
+                        core: decision.core_id,
+                        warp,
+                        active: state.active,
+                        ready: state.ready,
+                        granted: state.granted,
+                        stall: stall_code(state.stall),
+                    });
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// For Parquet this writes the file footer, after which the log takes
+    /// no more records, so it is only called once the run is over.
+    pub fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Binary(writer) => writer.flush(),
+            #[cfg(feature = "parquet")]
+            Self::Parquet { sink, .. } => {
+                sink.finish();
+                Ok(())
+            }
+        }
+    }
+}
+
+pub fn read_sched_log(path: &Path) -> Result<Vec<SchedDecision>, String> {
+    let bytes = std::fs::read(path).map_err(|err| format!("{}: {err}", path.display()))?;
+    decode_sched_log(&bytes).map_err(|err| format!("{}: {err}", path.display()))
+}
+
+pub fn decode_sched_log(bytes: &[u8]) -> Result<Vec<SchedDecision>, String> {
+    let Some(mut rest) = bytes.strip_prefix(MAGIC.as_slice()) else {
+        return Err("not a cyclotron scheduler log".to_string());
+    };
+    let mut decisions = Vec::new();
+    while !rest.is_empty() {
+        if rest.len() < 16 {
+            return Err(format!(
+                "truncated record after {} records",
+                decisions.len()
+            ));
+        }
+        let (header, body) = rest.split_at(16);
+        let cycle = u64::from_le_bytes(header[..8].try_into().unwrap());
+        let core_id = u32::from_le_bytes(header[8..12].try_into().unwrap()) as usize;
+        let warps = u32::from_le_bytes(header[12..].try_into().unwrap()) as usize;
+        if body.len() < warps {
+            return Err(format!(
+                "truncated record after {} records",
+                decisions.len()
+            ));
+        }
+        let (warp_bytes, body) = body.split_at(warps);
+        decisions.push(SchedDecision {
+            cycle,
+            core_id,
+            warps: warp_bytes
+                .iter()
+                .map(|&byte| decode_warp(byte))
+                .collect::<Result<_, _>>()?,
+        });
+        rest = body;
+    }
+    Ok(decisions)
+}
+
+/// An `IssuePolicy` that issues the warps a file of decisions grants, for
+/// the cores and cycles it has a record of. Other cycles keep the built-in
+/// choice.
+#[derive(Debug, Default)]
+pub struct DecisionReplay {
+    grants: HashMap<(usize, Cycle), Vec<bool>>,
+    /// Cycles a record was found for, and not found for.
+    pub replayed: u64,
+    pub missed: u64,
+}
+
+impl DecisionReplay {
+    pub fn new(decisions: impl IntoIterator<Item = SchedDecision>) -> Self {
+        let grants = decisions
+            .into_iter()
+            .map(|decision| ((decision.core_id, decision.cycle), decision.grants()))
+            .collect();
+        Self {
+            grants,
+            ..Self::default()
+        }
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, String> {
+        read_sched_log(path).map(Self::new)
+    }
+}
+
+impl IssuePolicy for DecisionReplay {
+    fn select(&mut self, decision: &SchedDecision) -> Option<Vec<bool>> {
+        let grants = self
+            .grants
+            .get(&(decision.core_id, decision.cycle))
+            .cloned();
+        match grants {
+            Some(_) => self.replayed += 1,
+            None => self.missed += 1,
+        }
+        grants
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn warp(active: bool, ready: bool, stall: Option<CpiStall>, granted: bool) -> WarpSchedState {
+        WarpSchedState {
+            active,
+            ready,
+            stall,
+            granted,
+        }
+    }
+
+    #[test]
+    fn decisions_round_trip() {
+        let decisions = vec![
+            SchedDecision {
+                cycle: 7,
+                core_id: 3,
+                warps: vec![
+                    warp(true, true, None, true),
+                    warp(true, true, None, false),
+                    warp(true, false, Some(CpiStall::Gmem), false),
+                    warp(false, false, None, false),
+                ],
+            },
+            SchedDecision {
+                cycle: 8,
+                core_id: 3,
+                warps: vec![warp(true, false, Some(CpiStall::Copy), false)],
+            },
+        ];
+        let mut writer = SchedLogWriter::new(Vec::new()).unwrap();
+        for decision in &decisions {
+            writer.write(decision).unwrap();
+        }
+        let bytes = writer.into_inner();
+        // Header, then 16 bytes and one byte per warp for each record.
+        assert_eq!(bytes.len(), 8 + 16 + 4 + 16 + 1);
+        assert_eq!(decode_sched_log(&bytes).unwrap(), decisions);
+        assert_eq!(decisions[0].chosen().collect::<Vec<_>>(), [0]);
+        assert_eq!(decisions[0].alternatives().collect::<Vec<_>>(), [1]);
+
+        assert!(decode_sched_log(&bytes[..bytes.len() - 1]).is_err());
+        assert!(decode_sched_log(b"CYCJRNL1").is_err());
+    }
+
+    #[test]
+    fn replay_grants_recorded_cycles_only() {
+        let mut decision = SchedDecision {
+            cycle: 4,
+            core_id: 1,
+            warps: vec![warp(true, true, None, false), warp(true, true, None, true)],
+        };
+        let mut replay = DecisionReplay::new([decision.clone()]);
+        decision.warps[1].granted = false;
+        assert_eq!(replay.select(&decision), Some(vec![false, true]));
+        decision.cycle = 5;
+        assert_eq!(replay.select(&decision), None);
+        assert_eq!((replay.replayed, replay.missed), (1, 1));
+    }
+
+    #[cfg(feature = "parquet")]
+    #[test]
+    fn parquet_log_has_a_row_per_warp() {
+        use parquet::file::reader::{FileReader, SerializedFileReader};
+
+        let config = SchedLogConfig {
+            path: Some(
+                std::env::temp_dir()
+                    .join(format!("cyclotron_sched_log_{}.bin", std::process::id())),
+            ),
+            cores: Some(vec![1]),
+        };
+        let mut log = SchedLogSink::create(&config, LogFormat::Parquet)
+            .unwrap()
+            .unwrap();
+        assert!(log.records_core(1) && !log.records_core(0));
+        let decision = SchedDecision {
+            cycle: 4,
+            core_id: 1,
+            warps: vec![
+                warp(true, true, None, true),
+                warp(true, false, Some(CpiStall::Gmem), false),
+            ],
+        };
+        log.write(&decision).unwrap();
+        log.write(&decision).unwrap();
+        log.flush().unwrap();
+
+        let path = SchedLogSink::path(config.path.as_ref().unwrap(), LogFormat::Parquet);
+        let reader = SerializedFileReader::new(File::open(&path).unwrap()).unwrap();
+        std::fs::remove_file(&path).ok();
+        assert_eq!(reader.metadata().file_metadata().num_rows(), 4);
+        assert!(!config.path.unwrap().exists());
+    }
+}
@@ -0,0 +1,240 @@
+//! Checks that SMEM stores reach other warps only through a barrier. A
+//! warp's stores become visible to another warp once both have taken part
+//! in a synchronizing barrier; a load of bytes another warp stored with no
+//! such barrier in between is a violation. Each cluster keeps one checker
+//! over the shared memory of its cores; see `sim.smem_order`.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::muon::lane_mask::LaneMask;
+use crate::sim::trace::MemTraceLine;
+
+/// What the checker does about a violation.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SmemOrderCheck {
+    #[default]
+    Off,
+    /// Count it, log the first one and keep going.
+    Count,
+    Panic,
+}
+
+/// A load that read another warp's store with no barrier between them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct SmemOrderViolation {
+    pub cycle: u64,
+    /// First byte of the load that another warp stored.
+    pub address: u64,
+    /// Cluster-local core and warp id of the load.
+    pub reader: (usize, usize),
+    /// Cluster-local core and warp id of the store.
+    pub writer: (usize, usize),
+}
+
+impl fmt::Display for SmemOrderViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "cycle {}: core {} warp {} loaded smem 0x{:x}, stored by core {} warp {} with no barrier between them",
+            self.cycle, self.reader.0, self.reader.1, self.address, self.writer.0, self.writer.1
+        )
+    }
+}
+
+/// Violations kept for the report; later ones are only counted.
+const KEPT_VIOLATIONS: usize = 8;
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SmemOrderStats {
+    pub loads: u64,
+    pub stores: u64,
+    pub barriers: u64,
+    /// Loads, one per lane, that read another warp's store unordered.
+    pub violations: u64,
+    /// The first few of them.
+    pub first_violations: Vec<SmemOrderViolation>,
+}
+
+#[derive(Clone)]
+pub struct SmemOrderChecker {
+    action: SmemOrderCheck,
+    /// Warps per core.
+    num_warps: usize,
+    /// Warps in the cluster.
+    warps: usize,
+    /// Byte address -> warp that last stored it, and the barriers released
+    /// before that store.
+    stores: HashMap<u64, (usize, u64)>,
+    /// `[a * warps + b]`: the last barrier, numbered from 1, that warps `a`
+    /// and `b` both took part in; 0 for none. Warps are numbered
+    /// `core * num_warps + warp`.
+    synced: Vec<u64>,
+    stats: SmemOrderStats,
+}
+
+impl SmemOrderChecker {
+    pub fn new(action: SmemOrderCheck, num_cores: usize, num_warps: usize) -> Self {
+        let warps = num_cores * num_warps;
+        Self {
+            action,
+            num_warps,
+            warps,
+            stores: HashMap::new(),
+            synced: vec![0; warps * warps],
+            stats: SmemOrderStats::default(),
+        }
+    }
+
+    /// Forgets every store, e.g. when a new threadblock starts.
+    pub fn reset(&mut self) {
+        self.stores.clear();
+    }
+
+    /// Records a released barrier from its `mask[cid][wid]` thread masks.
+    pub fn barrier(&mut self, arrived: &[Vec<LaneMask>]) {
+        self.stats.barriers += 1;
+        let barrier = self.stats.barriers;
+        let participants: Vec<usize> = arrived
+            .iter()
+            .enumerate()
+            .flat_map(|(cid, warps)| {
+                warps
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, mask)| !mask.is_empty())
+                    .map(move |(wid, _)| (cid, wid))
+            })
+            .map(|(cid, wid)| cid * self.num_warps + wid)
+            .collect();
+        for &a in &participants {
+            for &b in &participants {
+                self.synced[a * self.warps + b] = barrier;
+            }
+        }
+    }
+
+    /// Checks and records one lane's SMEM access by core `cid`. Panics on a
+    /// violation under `SmemOrderCheck::Panic`.
+    pub fn access(&mut self, cycle: u64, cid: usize, line: &MemTraceLine) {
+        let warp = cid * self.num_warps + line.warp_id as usize;
+        let bytes = line.address..line.address + line.size as u64;
+        if line.store {
+            self.stats.stores += 1;
+            let barriers = self.stats.barriers;
+            for address in bytes {
+                self.stores.insert(address, (warp, barriers));
+            }
+            return;
+        }
+        self.stats.loads += 1;
+        let warps = self.warps;
+        let unordered = bytes.into_iter().find_map(|address| {
+            let &(writer, barriers) = self.stores.get(&address)?;
+            (writer != warp && self.synced[writer * warps + warp] <= barriers)
+                .then_some((address, writer))
+        });
+        let Some((address, writer)) = unordered else {
+            return;
+        };
+        let violation = SmemOrderViolation {
+            cycle,
+            address,
+            reader: (cid, line.warp_id as usize),
+            writer: (writer / self.num_warps, writer % self.num_warps),
+        };
+        if self.action == SmemOrderCheck::Panic {
+            panic!("smem ordering violation: {violation}");
+        }
+        if self.stats.violations == 0 {
+            log::warn!("smem ordering violation: {violation}");
+        }
+        self.stats.violations += 1;
+        if self.stats.first_violations.len() < KEPT_VIOLATIONS {
+            self.stats.first_violations.push(violation);
+        }
+    }
+
+    pub fn stats(&self) -> &SmemOrderStats {
+        &self.stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn access(warp_id: u32, store: bool, address: u64) -> MemTraceLine {
+        MemTraceLine {
+            warp_id,
+            lane_id: 0,
+            pc: 0,
+            is_smem: true,
+            store,
+            address,
+            size: 4,
+            data: 0,
+        }
+    }
+
+    /// `mask[cid][wid]` with the given (core, warp) pairs taking part.
+    fn arrived(warps: &[(usize, usize)]) -> Vec<Vec<LaneMask>> {
+        let mut mask = vec![vec![LaneMask::empty(); 4]; 2];
+        for &(cid, wid) in warps {
+            mask[cid][wid] = LaneMask::all(1);
+        }
+        mask
+    }
+
+    #[test]
+    fn loads_need_a_barrier_both_warps_took_part_in() {
+        let mut checker = SmemOrderChecker::new(SmemOrderCheck::Count, 2, 4);
+        checker.access(1, 0, &access(0, true, 0x10));
+        // The storing warp reads its own data back freely.
+        checker.access(2, 0, &access(0, false, 0x10));
+        assert_eq!(checker.stats().violations, 0);
+
+        // Another warp on another core, overlapping one byte.
+        checker.access(3, 1, &access(2, false, 0x13));
+        assert_eq!(checker.stats().violations, 1);
+        assert_eq!(
+            checker.stats().first_violations[0],
+            SmemOrderViolation {
+                cycle: 3,
+                address: 0x13,
+                reader: (1, 2),
+                writer: (0, 0),
+            }
+        );
+
+        // A barrier the reader sat out orders nothing for it.
+        checker.barrier(&arrived(&[(0, 0), (0, 1)]));
+        checker.access(4, 1, &access(2, false, 0x10));
+        checker.access(4, 0, &access(1, false, 0x10));
+        assert_eq!(checker.stats().violations, 2);
+
+        checker.barrier(&arrived(&[(0, 0), (1, 2)]));
+        checker.access(5, 1, &access(2, false, 0x10));
+        assert_eq!(checker.stats().violations, 2);
+
+        // A store after the barrier needs another one.
+        checker.access(6, 0, &access(0, true, 0x10));
+        checker.access(7, 1, &access(2, false, 0x10));
+        assert_eq!(checker.stats().violations, 3);
+        checker.reset();
+        checker.access(8, 1, &access(2, false, 0x10));
+        assert_eq!(checker.stats().violations, 3);
+        assert_eq!((checker.stats().stores, checker.stats().loads), (2, 7));
+    }
+
+    #[test]
+    #[should_panic(expected = "smem ordering violation")]
+    fn panic_mode_stops_at_the_first_violation() {
+        let mut checker = SmemOrderChecker::new(SmemOrderCheck::Panic, 1, 2);
+        checker.access(1, 0, &access(0, true, 0x0));
+        checker.access(2, 0, &access(1, false, 0x0));
+    }
+}
@@ -0,0 +1,362 @@
+use std::path::Path;
+
+use rusqlite::{params, Connection, Transaction};
+use serde_json::Value;
+
+use crate::sim::config::SqliteLogConfig;
+use crate::sim::perf_log::{flatten, StatsRecord};
+use crate::timeq::Cycle;
+
+/// Bumped whenever a table or column changes in a way queries need to know
+/// about. Stored in the `schema_version` table.
+pub const SQLITE_SCHEMA_VERSION: u32 = 1;
+
+const SCHEMA: &str = "
+CREATE TABLE schema_version (version INTEGER NOT NULL);
+CREATE TABLE node (id INTEGER PRIMARY KEY, name TEXT NOT NULL);
+CREATE TABLE edge (id INTEGER PRIMARY KEY, name TEXT NOT NULL, src INTEGER NOT NULL, dst INTEGER NOT NULL);
+CREATE TABLE node_snapshot (cycle INTEGER NOT NULL, node INTEGER NOT NULL, outstanding INTEGER NOT NULL);
+CREATE INDEX node_snapshot_node ON node_snapshot (node, cycle);
+CREATE TABLE edge_snapshot (cycle INTEGER NOT NULL, edge INTEGER NOT NULL, occupancy INTEGER NOT NULL);
+CREATE INDEX edge_snapshot_edge ON edge_snapshot (edge, cycle);
+CREATE TABLE interval_stats (cycle INTEGER NOT NULL, warmup INTEGER NOT NULL, cluster_id INTEGER NOT NULL, core_id INTEGER NOT NULL, metric TEXT NOT NULL, value REAL NOT NULL);
+CREATE INDEX interval_stats_metric ON interval_stats (metric, core_id, cycle);
+CREATE TABLE request_event (cycle INTEGER NOT NULL, cluster_id INTEGER NOT NULL, core_id INTEGER NOT NULL, warp INTEGER NOT NULL, request_id INTEGER, event TEXT NOT NULL, bytes INTEGER NOT NULL, reason TEXT);
+CREATE INDEX request_event_request ON request_event (core_id, request_id);
+CREATE INDEX request_event_cycle ON request_event (cycle);
+";
+
+/// A request lifecycle event, as passed to the core's trace hook.
+#[derive(Debug, Clone)]
+pub struct RequestEvent {
+    pub cycle: Cycle,
+    pub cluster_id: usize,
+    pub core_id: usize,
+    pub warp: usize,
+    pub request_id: Option<u64>,
+    pub event: String,
+    pub bytes: u32,
+    pub reason: Option<String>,
+}
+
+/// Occupancy of one graph node or edge at a snapshot.
+#[derive(Debug, Clone, Copy)]
+pub struct Occupancy {
+    pub id: usize,
+    pub count: usize,
+}
+
+/// Shape of the graph the snapshots index into, written once.
+#[derive(Debug, Clone, Default)]
+pub struct GraphShape {
+    pub nodes: Vec<String>,
+    /// (name, src node, dst node) per edge.
+    pub edges: Vec<(String, usize, usize)>,
+}
+
+enum Row {
+    Node(Cycle, Occupancy),
+    Edge(Cycle, Occupancy),
+    Stat {
+        cycle: Cycle,
+        warmup: bool,
+        cluster_id: usize,
+        core_id: usize,
+        metric: String,
+        value: f64,
+    },
+    Event(RequestEvent),
+}
+
+/// SQLite database holding graph occupancy snapshots, per-interval core
+/// stats and request lifecycle events. Rows are buffered and written
+/// `batch_rows` at a time, in one transaction per batch.
+pub struct SqliteLog {
+    conn: Connection,
+    config: SqliteLogConfig,
+    rows: Vec<Row>,
+    shape_written: bool,
+    failed: bool,
+}
+
+impl SqliteLog {
+    /// Creates a fresh database at `path`, replacing any file already there.
+    pub fn create(path: &Path, config: SqliteLogConfig) -> rusqlite::Result<Self> {
+        if path.exists() {
+            let _ = std::fs::remove_file(path);
+        }
+        let conn = Connection::open(path)?;
+        conn.execute_batch(SCHEMA)?;
+        conn.execute(
+            "INSERT INTO schema_version (version) VALUES (?1)",
+            params![SQLITE_SCHEMA_VERSION],
+        )?;
+        Ok(Self {
+            conn,
+            config,
+            rows: Vec::new(),
+            shape_written: false,
+            failed: false,
+        })
+    }
+
+    pub fn config(&self) -> &SqliteLogConfig {
+        &self.config
+    }
+
+    /// Whether a graph snapshot is due at `cycle`.
+    pub fn snapshot_due(&self, cycle: Cycle) -> bool {
+        let interval = self.config.snapshot_interval;
+        interval > 0 && cycle.is_multiple_of(interval)
+    }
+
+    /// Records node and edge occupancy at `cycle`. `shape` is only read the
+    /// first time.
+    pub fn write_snapshot(
+        &mut self,
+        cycle: Cycle,
+        shape: impl FnOnce() -> GraphShape,
+        nodes: &[Occupancy],
+        edges: &[Occupancy],
+    ) {
+        if !self.shape_written {
+            self.shape_written = true;
+            let shape = shape();
+            if let Err(err) = self.write_shape(&shape) {
+                self.fail(err);
+            }
+        }
+        self.rows
+            .extend(nodes.iter().map(|&node| Row::Node(cycle, node)));
+        self.rows
+            .extend(edges.iter().map(|&edge| Row::Edge(cycle, edge)));
+        self.maybe_flush();
+    }
+
+    /// Records every numeric field of `record.summary` as one
+    /// `interval_stats` row, named by its dotted path.
+    pub fn write_stats(&mut self, record: &StatsRecord) {
+        let Ok(value) = serde_json::to_value(&record.summary) else {
+            return;
+        };
+        let mut fields = Vec::new();
+        flatten("", value, &mut fields);
+        let cluster_id = record.summary.cluster_id;
+        let core_id = record.summary.core_id;
+        for (metric, value) in fields {
+            let value = match value {
+                Value::Number(n) => n.as_f64(),
+                Value::Bool(b) => Some(b as u8 as f64),
+                _ => None,
+            };
+            if let Some(value) = value {
+                self.rows.push(Row::Stat {
+                    cycle: record.cycle,
+                    warmup: record.warmup,
+                    cluster_id,
+                    core_id,
+                    metric,
+                    value,
+                });
+            }
+        }
+        self.maybe_flush();
+    }
+
+    pub fn write_event(&mut self, event: RequestEvent) {
+        if !self.config.events {
+            return;
+        }
+        self.rows.push(Row::Event(event));
+        self.maybe_flush();
+    }
+
+    fn maybe_flush(&mut self) {
+        if self.rows.len() >= self.config.batch_rows.max(1) {
+            self.flush();
+        }
+    }
+
+    /// Writes buffered rows in one transaction.
+    pub fn flush(&mut self) {
+        if self.rows.is_empty() {
+            return;
+        }
+        let rows = std::mem::take(&mut self.rows);
+        if self.failed {
+            return;
+        }
+        let result = self
+            .conn
+            .transaction()
+            .and_then(|tx| insert_rows(&tx, &rows).and_then(|()| tx.commit()));
+        if let Err(err) = result {
+            self.fail(err);
+        }
+    }
+
+    fn write_shape(&mut self, shape: &GraphShape) -> rusqlite::Result<()> {
+        let tx = self.conn.transaction()?;
+        {
+            let mut node = tx.prepare("INSERT INTO node (id, name) VALUES (?1, ?2)")?;
+            for (id, name) in shape.nodes.iter().enumerate() {
+                node.execute(params![id as i64, name])?;
+            }
+            let mut edge =
+                tx.prepare("INSERT INTO edge (id, name, src, dst) VALUES (?1, ?2, ?3, ?4)")?;
+            for (id, (name, src, dst)) in shape.edges.iter().enumerate() {
+                edge.execute(params![id as i64, name, *src as i64, *dst as i64])?;
+            }
+        }
+        tx.commit()
+    }
+
+    /// Logs the first error and drops everything after it, so a full disk
+    /// does not stop the run.
+    fn fail(&mut self, err: rusqlite::Error) {
+        if !self.failed {
+            log::warn!("sqlite perf log stopped: {err}");
+        }
+        self.failed = true;
+        self.rows.clear();
+    }
+}
+
+impl Drop for SqliteLog {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+fn insert_rows(tx: &Transaction, rows: &[Row]) -> rusqlite::Result<()> {
+    let mut node = tx.prepare_cached(
+        "INSERT INTO node_snapshot (cycle, node, outstanding) VALUES (?1, ?2, ?3)",
+    )?;
+    let mut edge = tx
+        .prepare_cached("INSERT INTO edge_snapshot (cycle, edge, occupancy) VALUES (?1, ?2, ?3)")?;
+    let mut stat = tx.prepare_cached(
+        "INSERT INTO interval_stats (cycle, warmup, cluster_id, core_id, metric, value)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+    )?;
+    let mut event = tx.prepare_cached(
+        "INSERT INTO request_event (cycle, cluster_id, core_id, warp, request_id, event, bytes, reason)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+    )?;
+    for row in rows {
+        match row {
+            Row::Node(cycle, occ) => {
+                node.execute(params![*cycle as i64, occ.id as i64, occ.count as i64])?;
+            }
+            Row::Edge(cycle, occ) => {
+                edge.execute(params![*cycle as i64, occ.id as i64, occ.count as i64])?;
+            }
+            Row::Stat {
+                cycle,
+                warmup,
+                cluster_id,
+                core_id,
+                metric,
+                value,
+            } => {
+                stat.execute(params![
+                    *cycle as i64,
+                    warmup,
+                    *cluster_id as i64,
+                    *core_id as i64,
+                    metric,
+                    value
+                ])?;
+            }
+            Row::Event(e) => {
+                event.execute(params![
+                    e.cycle as i64,
+                    e.cluster_id as i64,
+                    e.core_id as i64,
+                    e.warp as i64,
+                    e.request_id.map(|id| id as i64),
+                    e.event,
+                    e.bytes,
+                    e.reason
+                ])?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::muon::gmem::CorePerfSummary;
+
+    fn count(conn: &Connection, table: &str) -> i64 {
+        conn.query_row(&format!("SELECT COUNT(*) FROM {table}"), [], |row| {
+            row.get(0)
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn batches_rows_into_indexed_tables() {
+        let path = std::env::temp_dir().join(format!(
+            "cyclotron_sqlite_log_{}.sqlite",
+            std::process::id()
+        ));
+        let config = SqliteLogConfig {
+            enabled: true,
+            snapshot_interval: 10,
+            events: true,
+            batch_rows: 3,
+        };
+        let mut log = SqliteLog::create(&path, config).unwrap();
+        assert!(log.snapshot_due(20) && !log.snapshot_due(25));
+        let shape = || GraphShape {
+            nodes: vec!["l1".to_string(), "dram".to_string()],
+            edges: vec![("l1->dram".to_string(), 0, 1)],
+        };
+        let nodes = [Occupancy { id: 0, count: 2 }, Occupancy { id: 1, count: 5 }];
+        let edges = [Occupancy { id: 0, count: 1 }];
+        log.write_snapshot(10, shape, &nodes, &edges);
+        log.write_snapshot(20, || panic!("shape is written once"), &nodes, &edges);
+        log.write_event(RequestEvent {
+            cycle: 12,
+            cluster_id: 0,
+            core_id: 1,
+            warp: 3,
+            request_id: Some(7),
+            event: "gmem_issue".to_string(),
+            bytes: 64,
+            reason: None,
+        });
+        log.write_stats(&StatsRecord {
+            cycle: 20,
+            warmup: false,
+            summary: CorePerfSummary::default(),
+        });
+
+        // Full batches are already on disk before the last flush.
+        let conn = Connection::open(&path).unwrap();
+        assert_eq!(count(&conn, "node_snapshot"), 4);
+        drop(log);
+
+        let version: u32 = conn
+            .query_row("SELECT version FROM schema_version", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(version, SQLITE_SCHEMA_VERSION);
+        assert_eq!(count(&conn, "node"), 2);
+        assert_eq!(count(&conn, "edge_snapshot"), 2);
+        assert_eq!(count(&conn, "request_event"), 1);
+        assert!(count(&conn, "interval_stats") > 0);
+        let dram: i64 = conn
+            .query_row(
+                "SELECT SUM(outstanding) FROM node_snapshot JOIN node ON node.id = node_snapshot.node
+                 WHERE node.name = 'dram'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(dram, 10);
+        drop(conn);
+        std::fs::remove_file(&path).ok();
+    }
+}
@@ -3,17 +3,39 @@ use crate::base::mem::HasMemory;
 use crate::cluster::Cluster;
 use crate::command_proc::CommandProcessor;
 use crate::muon::config::MuonConfig;
+use crate::muon::gmem::{
+    merge_context_mem, AccessPatternSummary, ContextMemStats, CorePerfSummary, SharedIssuePolicy,
+};
 use crate::neutrino::config::NeutrinoConfig;
-use crate::sim::config::{MemConfig, SimConfig};
+use crate::sim::checkpoint::{deep_copy, Checkpoint, Shared};
+use crate::sim::config::{KernelConfig, LogFormat, MemConfig, SimConfig};
+use crate::sim::dump::{self, SimStateDump};
 use crate::sim::elf::ElfBackedMem;
 use crate::sim::flat_mem::FlatMemory;
+use crate::sim::gmem_race::{GmemRaceCheck, GmemRaceChecker, GmemRaceStats};
+use crate::sim::hooks::{
+    CycleEvent, GmemCompletionEvent, KernelFinishEvent, RetireEvent, SimHooks,
+};
+use crate::sim::journal;
 use crate::sim::log::Logger;
-use crate::sim::perf_log::PerfLogSession;
-use crate::sim::trace_db::{default_trace_db_path, TraceDb};
-use crate::timeflow::CoreGraphConfig;
+use crate::sim::metadata::RunMetadata;
+#[cfg(feature = "metrics-server")]
+use crate::sim::metrics_server::{CoreOccupancy, MetricsServer, SimMetrics};
+use crate::sim::perf_log::{KernelReport, PerfLogSession};
+use crate::sim::pipeview::PipeviewWriter;
+use crate::sim::sched_log::{DecisionReplay, SchedLogSink};
+use crate::sim::sqlite_log::{GraphShape, Occupancy};
+use crate::sim::trace_db::{default_trace_db_path, trace_parquet_path, TraceDb};
+use crate::sim::vcd::VcdRecorder;
+use crate::timeflow::{invariants, ClusterGmemGraph, CoreGraphConfig};
 use serde::Deserialize;
-use std::path::{Path, PathBuf};
-use std::sync::{Arc, Mutex, RwLock};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::panic::{catch_unwind, resume_unwind, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, PoisonError};
 
 pub struct Sim {
     pub config: SimConfig,
@@ -21,44 +43,801 @@ pub struct Sim {
     pub logger: Arc<Logger>,
     perf_log_session: Option<Arc<PerfLogSession>>,
     trace_db: Option<Mutex<TraceDb>>,
+    hooks: SimHooks,
+    /// Cycles ticked since the last reset, as reported to hooks.
+    cycle: u64,
+    gmem_completion_log: bool,
+    kernel_finish_reported: bool,
+    /// Pending timing changes, sorted by cycle.
+    timing_phases: VecDeque<TimingPhase>,
+    /// Cycle each of `top.kernels` finished on, in the same order.
+    kernel_finished_at: Vec<Option<u64>>,
+    metadata: RunMetadata,
+    #[cfg(feature = "metrics-server")]
+    metrics_server: Option<MetricsServer>,
+    /// Gmem request `run_until` is waiting on, and the core it completed on.
+    watched_request: Option<u64>,
+    watched_completion: Option<(usize, usize)>,
+    /// Set by `sim.vcd.path`.
+    vcd: Option<VcdRecorder>,
+    /// Set by `sim.pipeview.path`.
+    pipeview: Option<PipeviewWriter>,
+    /// Set by `sim.sched_log.path`.
+    sched_log: Option<SchedLogSink>,
+    /// Cycle a SIGINT or SIGTERM stopped the last `simulate` at.
+    interrupted_at: Option<u64>,
+    /// Cycle the last `simulate` stopped at on `sim.deadlock_cycles`.
+    deadlocked_at: Option<u64>,
+    /// Set by `sim.gmem_race.check`; shared with every cluster.
+    gmem_race: Option<Shared<GmemRaceChecker>>,
+}
+
+/// End state of a run, compared by `sim.verify_checkpoint`.
+struct RunDigest {
+    cycle: u64,
+    kernel_finished_at: Vec<Option<u64>>,
+    summaries: Vec<serde_json::Value>,
+    gmem: u64,
+}
+
+impl RunDigest {
+    /// What differs between `self` and `other`, if anything.
+    fn mismatch(&self, other: &RunDigest) -> Option<String> {
+        if self.cycle != other.cycle {
+            return Some(format!(
+                "finished on cycle {} then {}",
+                self.cycle, other.cycle
+            ));
+        }
+        if self.kernel_finished_at != other.kernel_finished_at {
+            return Some(format!(
+                "kernels finished at {:?} then {:?}",
+                self.kernel_finished_at, other.kernel_finished_at
+            ));
+        }
+        if let Some(core) =
+            (0..self.summaries.len()).find(|&i| self.summaries[i] != other.summaries[i])
+        {
+            return Some(format!("timing summary of core {core} differs"));
+        }
+        if self.gmem != other.gmem {
+            return Some("gmem contents differ".to_string());
+        }
+        None
+    }
+}
+
+/// Something `Sim::run_until` can stop at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    /// A launched kernel finishes.
+    NextKernelBoundary,
+    /// A warp waiting at a barrier is let go.
+    NextBarrierRelease,
+    /// A warp's PC changes to this address.
+    WarpAtPc(u32),
+    /// The timing model completes the gmem request with this id, on any
+    /// core. Never fires without timing.
+    CompletionOfRequest(u64),
+    /// The sim has ticked this many cycles.
+    CycleReached(u64),
+}
+
+/// Where an `Event` fired.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventSource {
+    Sim,
+    /// Index into the launched kernels, as for `Sim::kernel_finished_at`.
+    Kernel(usize),
+    Core {
+        cluster_id: usize,
+        core_id: usize,
+    },
+    Warp {
+        cluster_id: usize,
+        core_id: usize,
+        warp_id: usize,
+    },
+}
+
+/// Why `Sim::run_until` returned. `cycle` is the sim's cycle count at that
+/// point.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunOutcome {
+    Fired {
+        event: Event,
+        source: EventSource,
+        cycle: u64,
+    },
+    /// Every core retired before the event fired.
+    Finished { cycle: u64 },
+    /// `sim.timeout` cycles went by before the event fired.
+    TimedOut { cycle: u64 },
+}
+
+/// Stats of every core once `Sim::drain_all` has emptied the timing model.
+#[derive(Debug, Clone)]
+pub struct DrainSnapshot {
+    /// Sim cycle the drain started and ended on.
+    pub started_at: u64,
+    pub drained_at: u64,
+    /// As from `Sim::timing_summaries`, taken at `drained_at`.
+    pub summaries: Vec<CorePerfSummary>,
+}
+
+/// `Sim::drain_all` ran out of `sim.drain_cycles` with work still in flight.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DrainTimeout {
+    pub cycle: u64,
+    pub budget: u64,
+    /// (cluster, core) of every core with something still in flight.
+    pub cores: Vec<(usize, usize)>,
+}
+
+impl fmt::Display for DrainTimeout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let cores: Vec<String> = self
+            .cores
+            .iter()
+            .map(|(cluster_id, core_id)| format!("cluster {cluster_id} core {core_id}"))
+            .collect();
+        write!(
+            f,
+            "timing model not drained after {} cycles (cycle {}); still in flight on {}",
+            self.budget,
+            self.cycle,
+            cores.join(", ")
+        )
+    }
+}
+
+/// A timing config to switch to once `cycle` cycles have been simulated.
+#[derive(Debug, Clone)]
+pub struct TimingPhase {
+    pub cycle: u64,
+    pub config: CoreGraphConfig,
 }
 
 impl Sim {
+    /// Hands committed instructions to the trace db and any retire hooks.
+    /// Lines are left in the tracers when neither wants them so embedders
+    /// can read them directly.
     fn drain_traces(&mut self) {
-        let Some(trace_db) = self.trace_db.as_ref() else {
+        let wants_retire = self.hooks.wants_retire();
+        if self.trace_db.is_none() && !wants_retire {
             return;
-        };
-        let trace_db = trace_db.lock().expect("trace db lock poisoned");
+        }
+        let mut trace_db = self
+            .trace_db
+            .as_ref()
+            .map(|db| db.lock().expect("trace db lock poisoned"));
 
         for (cluster_id, cluster) in self.top.clusters.iter_mut().enumerate() {
             for (core_id, core) in cluster.cores.iter_mut().enumerate() {
                 let num_warps = core.conf().num_warps;
                 for warp_id in 0..num_warps {
                     while let Some(line) = core.get_tracer_mut().consume(warp_id) {
-                        trace_db.record_inst_line(cluster_id as u32, core_id as u32, &line);
+                        if let Some(trace_db) = &mut trace_db {
+                            trace_db.record_inst_line(cluster_id as u32, core_id as u32, &line);
+                        }
+                        self.hooks.retire(&RetireEvent {
+                            cycle: self.cycle,
+                            cluster_id,
+                            core_id,
+                            warp_id,
+                            line: &line,
+                        });
+                    }
+                }
+                if let Some(trace_db) = &mut trace_db {
+                    while let Some(line) = core.get_mem_tracer_mut().consume() {
+                        trace_db.record_mem_line(cluster_id as u32, core_id as u32, &line);
                     }
                 }
-                while let Some(line) = core.get_mem_tracer_mut().consume() {
-                    trace_db.record_mem_line(cluster_id as u32, core_id as u32, &line);
+            }
+        }
+    }
+
+    /// Writes the events held in the trace ring (`sim.trace_ring_events`)
+    /// to the trace db. The sim calls this on errors, deadlocks,
+    /// interrupts and kernel ends; embedders can call it at any other point
+    /// of interest. Does nothing outside ring-buffer mode.
+    pub fn dump_trace_ring(&self, reason: &str) {
+        let Some(trace_db) = &self.trace_db else {
+            return;
+        };
+        let mut trace_db = trace_db.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(dump) = trace_db.dump_ring() {
+            println!(
+                "Cyclotron: dumped {} trace events ({}) at cycle {}, {} older ones overwritten",
+                dump.events, reason, self.cycle, dump.overwritten
+            );
+        }
+    }
+
+    /// Completes the trace's Parquet files once the run is over.
+    fn finish_trace(&self) {
+        if let Some(trace_db) = &self.trace_db {
+            trace_db
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .finish();
+        }
+    }
+
+    fn fire_gmem_completion_hooks(&mut self) {
+        let wants = self.hooks.wants_gmem_completion() || self.watched_request.is_some();
+        if wants != self.gmem_completion_log {
+            self.gmem_completion_log = wants;
+            for cluster in self.top.clusters.iter_mut() {
+                for core in cluster.cores.iter_mut() {
+                    core.set_gmem_completion_log(wants);
+                }
+            }
+        }
+        if !wants {
+            return;
+        }
+        for (cluster_id, cluster) in self.top.clusters.iter_mut().enumerate() {
+            for (core_id, core) in cluster.cores.iter_mut().enumerate() {
+                for completion in core.take_gmem_completions() {
+                    if self.watched_request == Some(completion.request.id)
+                        && self.watched_completion.is_none()
+                    {
+                        self.watched_completion = Some((cluster_id, core_id));
+                    }
+                    self.hooks.gmem_completion(&GmemCompletionEvent {
+                        cycle: self.cycle,
+                        cluster_id,
+                        core_id,
+                        completion: &completion,
+                    });
                 }
             }
         }
     }
 
+    /// Callbacks invoked from `tick`. Hooks registered before the first
+    /// tick see every event of the run.
+    pub fn hooks(&mut self) -> &mut SimHooks {
+        &mut self.hooks
+    }
+
+    /// Timing stats of every core, cluster by cluster.
+    pub fn timing_summaries(&self) -> Vec<CorePerfSummary> {
+        self.top
+            .clusters
+            .iter()
+            .flat_map(|cluster| cluster.cores.iter().map(|core| core.timing_summary()))
+            .collect()
+    }
+
     fn write_timing_summary(&self) {
-        if self.config.timing {
-            let summaries = self
-                .top
-                .clusters
-                .iter()
-                .flat_map(|cluster| cluster.cores.iter().map(|core| core.timing_summary()))
-                .collect::<Vec<_>>();
+        if self.config.timing && self.config.stats_enabled() {
+            let summaries = self.timing_summaries();
+            for summary in &summaries {
+                println!(
+                    "Cyclotron: cluster {} core {} {}",
+                    summary.cluster_id,
+                    summary.core_id,
+                    summary.cpi_stack.format_line()
+                );
+                if !summary.functional {
+                    println!(
+                        "Cyclotron: cluster {} core {} execute {}",
+                        summary.cluster_id,
+                        summary.core_id,
+                        summary.execute_util.format_line()
+                    );
+                    let warps: Vec<String> = summary
+                        .warp_retire
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, warp)| warp.active_cycles > 0)
+                        .map(|(wid, warp)| format!("w{wid} {:.3} ({})", warp.ipc(), warp.retired()))
+                        .collect();
+                    if !warps.is_empty() {
+                        println!(
+                            "Cyclotron: cluster {} core {} warp IPC (retired) {}",
+                            summary.cluster_id,
+                            summary.core_id,
+                            warps.join(" ")
+                        );
+                    }
+                    let contexts = &summary.context_switch;
+                    if contexts.switch_ins > 0 {
+                        println!(
+                            "Cyclotron: cluster {} core {} warp slots: {} switch-ins, {} saves, {} restores, {} overhead cycles",
+                            summary.cluster_id,
+                            summary.core_id,
+                            contexts.switch_ins,
+                            contexts.saves,
+                            contexts.restores,
+                            contexts.overhead_cycles
+                        );
+                    }
+                }
+            }
+            // Every core reports the same shared banks.
+            if let Some(summary) = summaries.iter().find(|summary| !summary.functional) {
+                let banks: Vec<String> = summary
+                    .cache_banks
+                    .iter()
+                    .map(|bank| format!("{} {:.1}%", bank.bank, 100.0 * bank.duty_cycle()))
+                    .collect();
+                if !banks.is_empty() {
+                    println!("Cyclotron: cache bank duty {}", banks.join(" "));
+                }
+                let hints = &summary.cache_hints;
+                for (name, counts) in [("cg", &hints.cg), ("cs", &hints.cs)] {
+                    if counts.loads > 0 {
+                        println!(
+                            "Cyclotron: {} loads {}: {} L0 hits, {} L1 hits, {} L2 hits, {} misses",
+                            name,
+                            counts.loads,
+                            counts.l0_hits,
+                            counts.l1_hits,
+                            counts.l2_hits,
+                            counts.misses
+                        );
+                    }
+                }
+                let injected = &summary.injectors;
+                if injected.issued > 0 {
+                    println!(
+                        "Cyclotron: injected L2 traffic {} requests ({} stores, {} L2 hits), {} dropped, avg latency {:.2}",
+                        injected.issued,
+                        injected.stores,
+                        injected.l2_hits,
+                        injected.dropped,
+                        injected.avg_latency()
+                    );
+                }
+                let combining = &summary.write_combining;
+                if combining.writebacks > 0 {
+                    println!(
+                        "Cyclotron: DRAM writebacks {} in {} bursts ({:.1}% combined), {} channel cycles saved",
+                        combining.writebacks,
+                        combining.bursts,
+                        100.0 * combining.combined_fraction(),
+                        combining.saved_cycles
+                    );
+                }
+                let adaptive = &summary.adaptive_routing;
+                if adaptive.routed > 0 {
+                    println!(
+                        "Cyclotron: return path responses {}, {} rerouted ({:.1}%)",
+                        adaptive.routed,
+                        adaptive.rerouted,
+                        100.0 * adaptive.rerouted_fraction()
+                    );
+                }
+                let contention = &summary.refill_contention;
+                for (level, stats) in [("L1", &contention.l1), ("L2", &contention.l2)] {
+                    if stats.refills > 0 {
+                        println!(
+                            "Cyclotron: {} refills {} held the data port {} cycles, {} of {} hits delayed, +{:.2} cycles per hit",
+                            level,
+                            stats.refills,
+                            stats.stolen_cycles,
+                            stats.delayed_hits,
+                            stats.hits,
+                            stats.avg_hit_inflation()
+                        );
+                    }
+                }
+                let mut bottlenecks = summary.gmem_bottlenecks.clone();
+                for summary in summaries.iter().filter(|summary| !summary.functional) {
+                    bottlenecks.merge(&summary.bottlenecks);
+                }
+                let lines = bottlenecks.format_lines();
+                if !lines.is_empty() {
+                    println!("Cyclotron: likely bottleneck");
+                    for line in lines {
+                        println!("Cyclotron:   {line}");
+                    }
+                }
+            }
+            if let Some(gmem_timing) = &self.top.gmem_timing {
+                let graph = gmem_timing.read().expect("gmem timing lock poisoned");
+                for stall in graph.injected_stalls() {
+                    println!(
+                        "Cyclotron: injected stall at {} cycles {}..{} turned away {} admissions",
+                        stall.node, stall.start, stall.end, stall.rejected
+                    );
+                }
+            }
+            let mut context_mem: Vec<ContextMemStats> = Vec::new();
+            for summary in &summaries {
+                merge_context_mem(&mut context_mem, &summary.context_mem);
+            }
+            if context_mem.len() > 1 {
+                for (idx, context) in context_mem.iter().enumerate() {
+                    let name = self
+                        .top
+                        .kernels
+                        .get(idx)
+                        .map_or_else(|| format!("context {idx}"), |kernel| kernel.name.clone());
+                    println!("Cyclotron: {} {}", name, context.format_line());
+                }
+            }
+            let mut access_patterns = AccessPatternSummary::default();
+            for summary in summaries.iter().filter(|summary| !summary.functional) {
+                access_patterns += &summary.access_patterns;
+            }
+            let lines = access_patterns.format_lines();
+            if !lines.is_empty() {
+                println!("Cyclotron: uncoalesced gmem accesses");
+                for line in lines {
+                    println!("Cyclotron:   {line}");
+                }
+            }
             if let Some(session) = &self.perf_log_session {
-                session.write_summary(summaries);
+                session.write_summary(summaries, self.kernel_reports(), self.metadata());
+            }
+        }
+    }
+
+    /// Per-kernel completion info for the perf summary; empty for ordinary
+    /// single-kernel runs.
+    fn kernel_reports(&self) -> Vec<KernelReport> {
+        if self.top.kernels.len() < 2 {
+            return Vec::new();
+        }
+        self.top
+            .kernels
+            .iter()
+            .zip(&self.kernel_finished_at)
+            .map(|(kernel, finished_at)| KernelReport {
+                name: kernel.name.clone(),
+                clusters: kernel.clusters.clone(),
+                finished_at: *finished_at,
+            })
+            .collect()
+    }
+
+    fn update_kernel_completions(&mut self) {
+        let mut finished = Vec::new();
+        for (idx, finished_at) in self.kernel_finished_at.iter_mut().enumerate() {
+            if finished_at.is_none() && self.top.kernel_finished(idx) {
+                *finished_at = Some(self.cycle);
+                if self.top.kernels.len() > 1 {
+                    println!(
+                        "Cyclotron: kernel {} finished after {} cycles",
+                        self.top.kernels[idx].name, self.cycle
+                    );
+                }
+                finished.push(idx);
+            }
+        }
+        for idx in finished {
+            self.dump_trace_ring(&format!("kernel {} finished", self.top.kernels[idx].name));
+        }
+    }
+
+    /// Cycle the `idx`-th launched kernel finished on, if it has.
+    pub fn kernel_finished_at(&self, idx: usize) -> Option<u64> {
+        self.kernel_finished_at.get(idx).copied().flatten()
+    }
+
+    #[cfg(feature = "metrics-server")]
+    fn start_metrics_server(config: &SimConfig) -> Option<MetricsServer> {
+        let addr = config.metrics_addr.as_deref()?;
+        match MetricsServer::bind(addr) {
+            Ok(server) => {
+                println!(
+                    "Cyclotron: serving metrics on http://{}/metrics",
+                    server.local_addr()
+                );
+                Some(server)
+            }
+            Err(err) => {
+                eprintln!("Cyclotron: cannot serve metrics on {addr}: {err}");
+                None
+            }
+        }
+    }
+
+    /// Takes a metrics snapshot every `sim.metrics_interval` cycles.
+    #[cfg(feature = "metrics-server")]
+    fn publish_metrics(&mut self) {
+        let interval = self.config.metrics_interval.max(1);
+        if self.metrics_server.is_none() || !self.cycle.is_multiple_of(interval) {
+            return;
+        }
+        let summaries = if self.config.timing {
+            self.timing_summaries()
+        } else {
+            Vec::new()
+        };
+        let cores = self
+            .top
+            .clusters
+            .iter()
+            .enumerate()
+            .flat_map(|(cluster_id, cluster)| {
+                cluster
+                    .cores
+                    .iter()
+                    .enumerate()
+                    .map(move |(core_id, core)| {
+                        let (gmem_outstanding, smem_outstanding) =
+                            core.outstanding_timing_requests();
+                        CoreOccupancy {
+                            cluster_id,
+                            core_id,
+                            gmem_outstanding,
+                            smem_outstanding,
+                        }
+                    })
+            })
+            .collect();
+        let Some(server) = self.metrics_server.as_mut() else {
+            return;
+        };
+        let metrics = SimMetrics::collect(self.cycle, &summaries, cores, server.last());
+        server.publish(metrics);
+    }
+
+    /// Stops the per-cycle stat sampling in every core and the shared gmem
+    /// graph; see `SimConfig::fast`.
+    fn disable_stats(top: &mut CyclotronTop) {
+        for cluster in top.clusters.iter_mut() {
+            for core in cluster.cores.iter_mut() {
+                core.set_stats(false);
+            }
+        }
+        if let Some(gmem_timing) = &top.gmem_timing {
+            gmem_timing
+                .write()
+                .expect("gmem timing lock poisoned")
+                .set_stats(false);
+        }
+    }
+
+    fn start_vcd(config: &SimConfig, top: &CyclotronTop) -> Option<VcdRecorder> {
+        let path = config.vcd.path.as_ref()?;
+        match VcdRecorder::new(&config.vcd, top) {
+            Ok(recorder) => {
+                println!("Cyclotron: writing VCD waveform to {}", path.display());
+                recorder
+            }
+            Err(err) => {
+                eprintln!("Cyclotron: cannot write VCD to {}: {err}", path.display());
+                None
+            }
+        }
+    }
+
+    fn sample_vcd(&mut self) {
+        let Some(vcd) = self.vcd.as_mut() else {
+            return;
+        };
+        if let Err(err) = vcd.sample(self.cycle, &self.top) {
+            eprintln!("Cyclotron: stopped writing VCD: {err}");
+            self.vcd = None;
+        }
+    }
+
+    /// Records the occupancy of every node and edge of the shared gmem graph
+    /// in the SQLite perf log, every `log.sqlite.snapshot_interval` cycles.
+    fn sample_sqlite(&self) {
+        let Some(session) = &self.perf_log_session else {
+            return;
+        };
+        let Some(gmem_timing) = &self.top.gmem_timing else {
+            return;
+        };
+        if !session.snapshot_due(self.cycle) {
+            return;
+        }
+        let graph = gmem_timing.read().expect("gmem timing lock poisoned");
+        let nodes: Vec<_> = (0..graph.node_count())
+            .map(|id| Occupancy {
+                id,
+                count: graph.node_outstanding(id),
+            })
+            .collect();
+        let edges: Vec<_> = (0..graph.edge_count())
+            .map(|id| Occupancy {
+                id,
+                count: graph.edge_occupancy(id),
+            })
+            .collect();
+        let shape = || GraphShape {
+            nodes: graph.node_names(),
+            edges: graph.edge_names(),
+        };
+        session.write_graph_snapshot(self.cycle, shape, &nodes, &edges);
+    }
+
+    /// Pushes buffered VCD and pipeline view output to disk so the files
+    /// are complete even if the process exits without dropping the sim.
+    fn flush_vcd(&mut self) {
+        if let Some(Err(err)) = self.vcd.as_mut().map(VcdRecorder::flush) {
+            eprintln!("Cyclotron: failed to flush VCD: {err}");
+        }
+        if let Some(Err(err)) = self.pipeview.as_mut().map(PipeviewWriter::flush) {
+            eprintln!("Cyclotron: failed to flush pipeline view: {err}");
+        }
+        if let Some(Err(err)) = self.sched_log.as_mut().map(SchedLogSink::flush) {
+            eprintln!("Cyclotron: failed to flush scheduler log: {err}");
+        }
+    }
+
+    fn start_pipeview(config: &SimConfig, top: &mut CyclotronTop) -> Option<PipeviewWriter> {
+        let path = config.pipeview.path.as_ref()?;
+        if !config.timing {
+            eprintln!(
+                "Cyclotron: sim.pipeview needs --timing; not writing {}",
+                path.display()
+            );
+            return None;
+        }
+        let writer = match PipeviewWriter::create(&config.pipeview) {
+            Ok(writer) => writer?,
+            Err(err) => {
+                eprintln!(
+                    "Cyclotron: cannot write pipeline view to {}: {err}",
+                    path.display()
+                );
+                return None;
+            }
+        };
+        println!("Cyclotron: writing pipeline view to {}", path.display());
+        for (cluster_id, cluster) in top.clusters.iter_mut().enumerate() {
+            let num_cores = cluster.cores.len();
+            for (core_id, core) in cluster.cores.iter_mut().enumerate() {
+                core.set_pipeview(writer.records_core(cluster_id * num_cores + core_id));
+            }
+        }
+        Some(writer)
+    }
+
+    fn drain_pipeview(&mut self) {
+        let Some(writer) = self.pipeview.as_mut() else {
+            return;
+        };
+        for (cluster_id, cluster) in self.top.clusters.iter_mut().enumerate() {
+            for (core_id, core) in cluster.cores.iter_mut().enumerate() {
+                for inst in core.take_pipeview() {
+                    if let Err(err) = writer.write(cluster_id, core_id, &inst) {
+                        eprintln!("Cyclotron: stopped writing pipeline view: {err}");
+                        self.pipeview = None;
+                        return;
+                    }
+                }
+            }
+        }
+    }
+
+    fn start_sched_log(config: &SimConfig, top: &mut CyclotronTop) -> Option<SchedLogSink> {
+        let path = SchedLogSink::path(config.sched_log.path.as_ref()?, config.log.format);
+        if !config.timing {
+            eprintln!(
+                "Cyclotron: sim.sched_log needs --timing; not writing {}",
+                path.display()
+            );
+            return None;
+        }
+        let writer = match SchedLogSink::create(&config.sched_log, config.log.format) {
+            Ok(writer) => writer?,
+            Err(err) => {
+                eprintln!(
+                    "Cyclotron: cannot write scheduler log to {}: {err}",
+                    path.display()
+                );
+                return None;
+            }
+        };
+        println!("Cyclotron: writing scheduler log to {}", path.display());
+        for (cluster_id, cluster) in top.clusters.iter_mut().enumerate() {
+            let num_cores = cluster.cores.len();
+            for (core_id, core) in cluster.cores.iter_mut().enumerate() {
+                core.set_sched_log(writer.records_core(cluster_id * num_cores + core_id));
+            }
+        }
+        Some(writer)
+    }
+
+    fn drain_sched_log(&mut self) {
+        let Some(writer) = self.sched_log.as_mut() else {
+            return;
+        };
+        for cluster in self.top.clusters.iter_mut() {
+            for core in cluster.cores.iter_mut() {
+                for decision in core.take_sched_log() {
+                    if let Err(err) = writer.write(&decision) {
+                        eprintln!("Cyclotron: stopped writing scheduler log: {err}");
+                        self.sched_log = None;
+                        return;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Lets `policy` pick the warps every timed core issues in place of the
+    /// built-in issue scheduler; `None` restores the built-in one. See
+    /// `IssuePolicy`.
+    pub fn set_issue_policy(&mut self, policy: Option<SharedIssuePolicy>) {
+        for cluster in self.top.clusters.iter_mut() {
+            for core in cluster.cores.iter_mut() {
+                core.set_issue_policy(policy.clone());
             }
         }
     }
 
+    /// Moves everything accumulated so far into the warmup bucket and restarts
+    /// the timing stats from zero.
+    fn end_warmup(&mut self) {
+        if !self.config.timing {
+            return;
+        }
+        let summaries = self.timing_summaries();
+        if let Some(session) = &self.perf_log_session {
+            session.record_warmup(summaries);
+        }
+        for cluster in self.top.clusters.iter_mut() {
+            for core in cluster.cores.iter_mut() {
+                core.clear_timing_stats();
+            }
+        }
+        if let Some(core) = self
+            .top
+            .clusters
+            .first_mut()
+            .and_then(|cluster| cluster.cores.first_mut())
+        {
+            core.clear_shared_timing_stats();
+        }
+    }
+
+    /// Swaps in the timing parameters from `config` that can change mid-run:
+    /// server latency, bandwidth and capacity of the gmem hierarchy nodes and
+    /// execute units, and the warp scheduler. Structural settings (bank and
+    /// warp counts, links, cache geometry) keep their original values.
+    /// In-flight requests finish with the timing they were issued under.
+    pub fn set_timing(&mut self, config: &CoreGraphConfig) {
+        if !self.config.timing {
+            return;
+        }
+        for cluster in self.top.clusters.iter_mut() {
+            for core in cluster.cores.iter_mut() {
+                core.set_timing(config);
+            }
+        }
+        if let Some(core) = self
+            .top
+            .clusters
+            .first_mut()
+            .and_then(|cluster| cluster.cores.first_mut())
+        {
+            core.set_shared_timing(config);
+        }
+    }
+
+    /// Queues `phase` to be applied by `set_timing` at the start of the tick
+    /// after `phase.cycle` cycles.
+    pub fn schedule_timing_phase(&mut self, phase: TimingPhase) {
+        let pos = self
+            .timing_phases
+            .partition_point(|queued| queued.cycle <= phase.cycle);
+        self.timing_phases.insert(pos, phase);
+    }
+
+    fn apply_timing_phases(&mut self) {
+        while self
+            .timing_phases
+            .front()
+            .is_some_and(|phase| phase.cycle <= self.cycle)
+        {
+            let phase = self.timing_phases.pop_front().expect("front just checked");
+            println!("Cyclotron: applying timing phase at cycle {}", self.cycle);
+            self.set_timing(&phase.config);
+        }
+    }
+
     pub fn new(
         sim_config: SimConfig,
         muon_config: MuonConfig,
@@ -71,6 +850,7 @@ impl Sim {
             neutrino_config,
             mem_config,
             CoreGraphConfig::default(),
+            BTreeMap::new(),
         )
     }
 
@@ -80,80 +860,646 @@ impl Sim {
         neutrino_config: NeutrinoConfig,
         mem_config: MemConfig,
         timing_config: CoreGraphConfig,
+        core_overrides: BTreeMap<usize, CoreOverride>,
     ) -> Sim {
-        let perf_log_session = if sim_config.timing {
-            PerfLogSession::new().map(Arc::new)
+        let stats_enabled = sim_config.stats_enabled();
+        if !stats_enabled {
+            println!("Cyclotron: fast mode; stats, logs and traces are off");
+        }
+        let perf_log_session = if sim_config.timing && stats_enabled {
+            PerfLogSession::new(sim_config.log).map(Arc::new)
         } else {
             None
         };
-        let trace_db = if sim_config.trace {
+        if let Some(session) = &perf_log_session {
+            session.set_warmup_cycles(sim_config.warmup_cycles);
+        }
+        let trace_db = if sim_config.trace && stats_enabled {
             let trace_db_path = default_trace_db_path(None, Some(sim_config.elf.as_path()));
+            let trace_path = match sim_config.log.format.supported() {
+                LogFormat::Parquet => trace_parquet_path(&trace_db_path, "*"),
+                LogFormat::Jsonl => trace_db_path.clone(),
+            };
             println!(
                 "Cyclotron: writing ISA instruction trace to {}",
-                trace_db_path.display()
+                trace_path.display()
             );
-            Some(Mutex::new(TraceDb::new(&trace_db_path)))
+            let trace_db = match sim_config.trace_ring_events {
+                0 => TraceDb::new(&trace_db_path, sim_config.log.format),
+                events => {
+                    TraceDb::with_ring(&trace_db_path, sim_config.log.format, events as usize)
+                }
+            };
+            Some(Mutex::new(trace_db))
         } else {
             None
         };
+        #[cfg(not(feature = "parquet"))]
+        if sim_config.log.format == LogFormat::Parquet {
+            eprintln!(
+                "Cyclotron: log.format = \"parquet\" is ignored; rebuild with --features parquet"
+            );
+        }
+        #[cfg(not(feature = "metrics-server"))]
+        if sim_config.metrics_addr.is_some() {
+            eprintln!(
+                "Cyclotron: sim.metrics_addr is ignored; rebuild with --features metrics-server"
+            );
+        }
         let logger = Arc::new(Logger::new(sim_config.log_level));
-        let top = CyclotronTop::new(
+        let metadata = RunMetadata::collect(&sim_config.elf);
+        let kernels = sim_config.kernel_placement();
+        let num_kernels = kernels.len();
+        let mut top = CyclotronTop::new(
             Arc::new(CyclotronConfig {
                 timeout: sim_config.timeout,
                 elf: sim_config.elf.clone(),
+                kernels,
                 cluster_config: ClusterConfig {
                     muon_config,
                     neutrino_config,
                     timing_config,
-                },
+                    core_overrides,
+                }
+                .salvaged(),
                 mem_config,
                 timing_enabled: sim_config.timing,
+                timed_cores: sim_config.timed_cores.clone(),
             }),
             &logger,
             perf_log_session.clone(),
         );
 
+        #[cfg(feature = "metrics-server")]
+        let metrics_server = stats_enabled
+            .then(|| Self::start_metrics_server(&sim_config))
+            .flatten();
+        let vcd = stats_enabled
+            .then(|| Self::start_vcd(&sim_config, &top))
+            .flatten();
+        let pipeview = stats_enabled
+            .then(|| Self::start_pipeview(&sim_config, &mut top))
+            .flatten();
+        let sched_log = stats_enabled
+            .then(|| Self::start_sched_log(&sim_config, &mut top))
+            .flatten();
+        if !stats_enabled {
+            Self::disable_stats(&mut top);
+        }
+        for cluster in top.clusters.iter_mut() {
+            cluster.set_smem_order_check(sim_config.smem_order);
+        }
+        let gmem_race = Self::start_gmem_race(&sim_config, &mut top);
+        invariants::configure(sim_config.invariants);
         let mut sim = Sim {
             config: sim_config,
             top,
             logger,
             perf_log_session,
             trace_db,
+            hooks: SimHooks::default(),
+            cycle: 0,
+            gmem_completion_log: false,
+            kernel_finish_reported: false,
+            timing_phases: VecDeque::new(),
+            kernel_finished_at: vec![None; num_kernels],
+            metadata,
+            #[cfg(feature = "metrics-server")]
+            metrics_server,
+            watched_request: None,
+            watched_completion: None,
+            vcd,
+            pipeview,
+            sched_log,
+            interrupted_at: None,
+            deadlocked_at: None,
+            gmem_race,
         };
         sim.top.reset();
+        sim.start_sched_replay();
         sim
     }
 
+    fn start_gmem_race(
+        sim_config: &SimConfig,
+        top: &mut CyclotronTop,
+    ) -> Option<Shared<GmemRaceChecker>> {
+        if sim_config.gmem_race.check == GmemRaceCheck::Off {
+            return None;
+        }
+        let cores = top.clusters.iter().map(|cluster| cluster.cores.len());
+        let warps = top
+            .clusters
+            .iter()
+            .flat_map(|cluster| &cluster.cores)
+            .map(|core| core.conf().num_warps);
+        let checker = Shared::new(GmemRaceChecker::new(
+            &sim_config.gmem_race,
+            top.clusters.len(),
+            cores.max().unwrap_or(0),
+            warps.max().unwrap_or(0),
+        ));
+        for cluster in top.clusters.iter_mut() {
+            cluster.set_gmem_race_check(Some(checker.clone()));
+        }
+        Some(checker)
+    }
+
+    fn start_sched_replay(&mut self) {
+        let Some(path) = self.config.sched_replay.clone() else {
+            return;
+        };
+        if !self.config.timing {
+            eprintln!(
+                "Cyclotron: sim.sched_replay needs --timing; not replaying {}",
+                path.display()
+            );
+            return;
+        }
+        match DecisionReplay::from_file(&path) {
+            Ok(replay) => {
+                println!(
+                    "Cyclotron: replaying scheduler decisions from {}",
+                    path.display()
+                );
+                self.set_issue_policy(Some(Arc::new(Mutex::new(replay))));
+            }
+            Err(err) => eprintln!("Cyclotron: cannot replay scheduler decisions: {err}"),
+        }
+    }
+
+    /// Records the fully resolved config in the run metadata.
+    pub fn set_run_config(&mut self, config: serde_json::Value) {
+        self.metadata.config = config;
+    }
+
+    /// Run metadata with the wall-clock time and peak RSS so far.
+    pub fn metadata(&self) -> RunMetadata {
+        self.metadata.snapshot()
+    }
+
+    /// Runs to completion or timeout. With `sim.state_dump` set, a panic,
+    /// deadlock or SIGINT also writes a state dump to that path, and with
+    /// `sim.trace_ring_events` set, dumps the trace ring. While a
+    /// journal records, SIGINT stops the run too, at a cycle the journal
+    /// keeps for replay. Outside those, SIGINT and SIGTERM only stop the
+    /// run if the embedder installed `dump::install_signal_handler`, as the
+    /// `cyclotron` binary does.
     pub fn simulate(&mut self) -> Result<(), u32> {
+        let trace_ring = self.config.trace && self.config.trace_ring_events > 0;
+        if self.config.state_dump.is_none() && !trace_ring && !journal::recording() {
+            return self.run();
+        }
+        dump::install_handlers();
+        match catch_unwind(AssertUnwindSafe(|| self.run())) {
+            Ok(result) => result,
+            Err(payload) => {
+                let message = dump::take_panic_message().unwrap_or_else(|| "unknown".to_string());
+                self.flush_vcd();
+                self.dump_trace_ring(&format!("panic: {message}"));
+                self.finish_trace();
+                self.write_state_dump(&format!("panic: {message}"));
+                resume_unwind(payload)
+            }
+        }
+    }
+
+    fn run(&mut self) -> Result<(), u32> {
+        let result = self.run_cycles();
+        self.flush_vcd();
+        self.finish_trace();
+        self.write_smem_order_report();
+        self.write_gmem_race_report();
+        self.write_invariant_report();
+        result
+    }
+
+    fn write_invariant_report(&self) {
+        for violations in invariants::summary().violations {
+            println!(
+                "Cyclotron: {} {} invariant violations, first: {}",
+                violations.count, violations.category, violations.first
+            );
+        }
+    }
+
+    fn write_smem_order_report(&self) {
+        for (cluster_id, cluster) in self.top.clusters.iter().enumerate() {
+            let Some(stats) = cluster.smem_order_stats() else {
+                continue;
+            };
+            println!(
+                "Cyclotron: cluster {} smem ordering: {} loads, {} stores, {} barriers, {} violations",
+                cluster_id, stats.loads, stats.stores, stats.barriers, stats.violations
+            );
+            for violation in &stats.first_violations {
+                println!("Cyclotron:   {violation}");
+            }
+        }
+    }
+
+    fn write_gmem_race_report(&self) {
+        let Some(checker) = &self.gmem_race else {
+            return;
+        };
+        let checker = checker.read().expect("gmem race checker lock poisoned");
+        let stats = checker.stats();
+        println!(
+            "Cyclotron: gmem races: {} loads, {} stores, {} fences, {} barriers, {} races",
+            stats.loads, stats.stores, stats.fences, stats.barriers, stats.races
+        );
+        for race in &stats.first_races {
+            println!("Cyclotron:   {race}");
+        }
+    }
+
+    fn run_cycles(&mut self) -> Result<(), u32> {
         self.top.reset();
-        for cycle in 0..self.top.timeout {
+        self.cycle = 0;
+        self.kernel_finish_reported = false;
+        self.kernel_finished_at.fill(None);
+        self.interrupted_at = None;
+        self.deadlocked_at = None;
+        dump::clear_interrupted();
+        // Without a signal, only a replayed journal can interrupt the run,
+        // so the journal is not consulted every cycle otherwise.
+        let replaying = journal::replaying();
+        let warmup_cycles = self.config.warmup_cycles;
+        let deadlock_cycles = self.config.deadlock_cycles;
+        let verify_at = self.config.verify_checkpoint;
+        // The checkpoint, and the end state of the run before restoring it.
+        let mut verify: Option<(Checkpoint, Option<RunDigest>)> = None;
+        let mut last_progress = (0, self.progress_fingerprint());
+        while self.cycle < self.top.timeout {
+            let cycle = self.cycle;
+            if verify.is_none() && verify_at == Some(cycle) {
+                verify = Some((self.checkpoint(), None));
+            }
+            if warmup_cycles > 0 && cycle == warmup_cycles {
+                self.end_warmup();
+            }
             if self.top.finished() {
+                if let Some((checkpoint, straight @ None)) = &mut verify {
+                    *straight = Some(self.run_digest());
+                    self.restore(checkpoint);
+                    last_progress = (self.cycle, self.progress_fingerprint());
+                    continue;
+                }
                 println!("simulation finished after {} cycles", cycle + 1);
+                match &verify {
+                    Some((checkpoint, Some(straight))) => {
+                        if let Some(mismatch) = straight.mismatch(&self.run_digest()) {
+                            panic!(
+                                "run restored from the checkpoint at cycle {} did not repeat: {}",
+                                checkpoint.cycle(),
+                                mismatch
+                            );
+                        }
+                        println!(
+                            "Cyclotron: run restored from the checkpoint at cycle {} repeated",
+                            checkpoint.cycle()
+                        );
+                    }
+                    _ => {
+                        if let Some(at) = verify_at {
+                            println!("Cyclotron: run finished before checkpoint cycle {at}");
+                        }
+                    }
+                }
                 self.write_timing_summary();
                 return self.check_tohost();
             }
+            let live = dump::interrupted();
+            if (live || replaying) && journal::interrupted(cycle, live) {
+                println!("simulation interrupted after {} cycles", cycle);
+                self.interrupted_at = Some(cycle);
+                if let Some(session) = &self.perf_log_session {
+                    session.set_interrupted_at(cycle);
+                }
+                self.dump_trace_ring("interrupted");
+                self.write_state_dump("interrupted");
+                self.write_timing_summary();
+                return Err(0);
+            }
+            if deadlock_cycles > 0 {
+                let fingerprint = self.progress_fingerprint();
+                if fingerprint != last_progress.1 {
+                    last_progress = (cycle, fingerprint);
+                } else if cycle - last_progress.0 >= deadlock_cycles {
+                    println!(
+                        "simulation deadlocked: no warp progress in {} cycles (cycle {})",
+                        deadlock_cycles, cycle
+                    );
+                    self.deadlocked_at = Some(cycle);
+                    self.dump_trace_ring("deadlock");
+                    self.write_state_dump("deadlock");
+                    self.write_timing_summary();
+                    return Err(0);
+                }
+            }
             self.tick();
         }
 
+        self.dump_trace_ring("timeout");
         self.write_timing_summary();
 
         Err(0)
     }
 
+    /// Ticks until `event` fires, every core retires, or the cycle count
+    /// reaches `sim.timeout`. Always advances at least one cycle unless the
+    /// sim is already finished or `CycleReached` names a past cycle, so
+    /// calling it in a loop steps from one occurrence to the next. Unlike
+    /// `simulate`, it neither resets the sim nor writes the perf summary.
+    pub fn run_until(&mut self, event: Event) -> RunOutcome {
+        if let Event::CycleReached(target) = event {
+            if self.cycle >= target {
+                return RunOutcome::Fired {
+                    event,
+                    source: EventSource::Sim,
+                    cycle: self.cycle,
+                };
+            }
+        }
+        if let Event::CompletionOfRequest(id) = event {
+            self.watched_request = Some(id);
+            self.watched_completion = None;
+        }
+        let outcome = loop {
+            if self.top.finished() {
+                break RunOutcome::Finished { cycle: self.cycle };
+            }
+            if self.cycle >= self.top.timeout {
+                break RunOutcome::TimedOut { cycle: self.cycle };
+            }
+            let before = self.warp_snapshot();
+            let kernels_done = self.kernel_finished_at.clone();
+            self.tick();
+            if let Some(source) = self.event_source(event, &before, &kernels_done) {
+                break RunOutcome::Fired {
+                    event,
+                    source,
+                    cycle: self.cycle,
+                };
+            }
+        };
+        self.watched_request = None;
+        self.watched_completion = None;
+        outcome
+    }
+
+    /// Every warp's PC and whether it is held at a barrier.
+    fn warp_snapshot(&self) -> Vec<(EventSource, u32, bool)> {
+        let mut warps = Vec::new();
+        for (cluster_id, cluster) in self.top.clusters.iter().enumerate() {
+            for (core_id, core) in cluster.cores.iter().enumerate() {
+                let barrier_mask = core.scheduler.barrier_wait_mask();
+                for warp_id in 0..core.warps.len() {
+                    let source = EventSource::Warp {
+                        cluster_id,
+                        core_id,
+                        warp_id,
+                    };
+                    let waiting = barrier_mask & (1 << warp_id) != 0;
+                    warps.push((source, core.scheduler.pc(warp_id), waiting));
+                }
+            }
+        }
+        warps
+    }
+
+    /// Where `event` fired during the tick that moved the warps from
+    /// `before` to their current state, if it did.
+    fn event_source(
+        &self,
+        event: Event,
+        before: &[(EventSource, u32, bool)],
+        kernels_done: &[Option<u64>],
+    ) -> Option<EventSource> {
+        match event {
+            Event::NextKernelBoundary => self
+                .kernel_finished_at
+                .iter()
+                .zip(kernels_done)
+                .position(|(now, was)| now.is_some() && was.is_none())
+                .map(EventSource::Kernel),
+            Event::NextBarrierRelease => self
+                .warp_snapshot()
+                .iter()
+                .zip(before)
+                .find(|((_, _, waiting), (_, _, was_waiting))| *was_waiting && !waiting)
+                .map(|((source, _, _), _)| *source),
+            Event::WarpAtPc(pc) => self
+                .warp_snapshot()
+                .iter()
+                .zip(before)
+                .find(|((_, now, _), (_, was, _))| *now == pc && *was != pc)
+                .map(|((source, _, _), _)| *source),
+            Event::CompletionOfRequest(_) => {
+                self.watched_completion
+                    .map(|(cluster_id, core_id)| EventSource::Core {
+                        cluster_id,
+                        core_id,
+                    })
+            }
+            Event::CycleReached(target) => (self.cycle >= target).then_some(EventSource::Sim),
+        }
+    }
+
+    /// Waits out everything the timing model has in flight, e.g. at a kernel
+    /// boundary before starting the next kernel. Every core ticks in
+    /// lockstep without fetching or issuing until no subgraph holds a
+    /// request, then the stats are snapshotted. Fails if that takes more
+    /// than `sim.drain_cycles` cycles.
+    pub fn drain_all(&mut self) -> Result<DrainSnapshot, DrainTimeout> {
+        let started_at = self.cycle;
+        loop {
+            let cores = self.undrained_cores();
+            if cores.is_empty() {
+                break;
+            }
+            if self.cycle - started_at >= self.config.drain_cycles {
+                return Err(DrainTimeout {
+                    cycle: self.cycle,
+                    budget: self.config.drain_cycles,
+                    cores,
+                });
+            }
+            self.top.clusters.iter_mut().for_each(Cluster::drain_step);
+            self.cycle += 1;
+        }
+        Ok(DrainSnapshot {
+            started_at,
+            drained_at: self.cycle,
+            summaries: self.timing_summaries(),
+        })
+    }
+
+    fn undrained_cores(&self) -> Vec<(usize, usize)> {
+        self.top
+            .clusters
+            .iter()
+            .enumerate()
+            .flat_map(|(cluster_id, cluster)| {
+                cluster
+                    .cores
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, core)| !core.timing_drained())
+                    .map(move |(core_id, _)| (cluster_id, core_id))
+            })
+            .collect()
+    }
+
+    /// Cycle a SIGINT or SIGTERM stopped the last `simulate` at, if one did.
+    pub fn interrupted_at(&self) -> Option<u64> {
+        self.interrupted_at
+    }
+
+    /// Cycle the last `simulate` gave up on a deadlock at, if it did.
+    pub fn deadlocked_at(&self) -> Option<u64> {
+        self.deadlocked_at
+    }
+
+    /// What the gmem race check found; `None` unless `sim.gmem_race` is on.
+    pub fn gmem_race_stats(&self) -> Option<GmemRaceStats> {
+        let checker = self.gmem_race.as_ref()?;
+        let checker = checker.read().expect("gmem race checker lock poisoned");
+        Some(checker.stats().clone())
+    }
+
+    /// Cycles ticked since the sim was built or last reset by `simulate`.
+    pub fn cycle(&self) -> u64 {
+        self.cycle
+    }
+
+    /// Copies everything the rest of the run depends on; see
+    /// `sim::checkpoint` for what that leaves out.
+    pub fn checkpoint(&self) -> Checkpoint {
+        let (top, gmem_race) = deep_copy(|| (self.top.clone(), self.gmem_race.clone()));
+        Checkpoint {
+            top,
+            gmem_race,
+            cycle: self.cycle,
+            kernel_finish_reported: self.kernel_finish_reported,
+            kernel_finished_at: self.kernel_finished_at.clone(),
+            timing_phases: self.timing_phases.clone(),
+            invariants: invariants::save(),
+        }
+    }
+
+    /// Puts the sim back to where it was when `checkpoint` was taken. Sinks
+    /// see the cycles after it a second time.
+    pub fn restore(&mut self, checkpoint: &Checkpoint) {
+        (self.top, self.gmem_race) =
+            deep_copy(|| (checkpoint.top.clone(), checkpoint.gmem_race.clone()));
+        self.cycle = checkpoint.cycle;
+        self.kernel_finish_reported = checkpoint.kernel_finish_reported;
+        self.kernel_finished_at = checkpoint.kernel_finished_at.clone();
+        self.timing_phases = checkpoint.timing_phases.clone();
+        invariants::restore(&checkpoint.invariants);
+    }
+
+    fn run_digest(&self) -> RunDigest {
+        RunDigest {
+            cycle: self.cycle,
+            kernel_finished_at: self.kernel_finished_at.clone(),
+            summaries: self
+                .timing_summaries()
+                .iter()
+                .map(|summary| serde_json::to_value(summary).expect("summary serializes"))
+                .collect(),
+            gmem: self.top.gmem.read().expect("lock poisoned").digest(),
+        }
+    }
+
+    /// Hash of every warp's PC and each core's active mask; stays constant
+    /// while no warp makes progress.
+    fn progress_fingerprint(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        for cluster in &self.top.clusters {
+            for core in &cluster.cores {
+                core.scheduler.active_warp_mask().hash(&mut hasher);
+                for wid in 0..core.warps.len() {
+                    core.scheduler.pc(wid).hash(&mut hasher);
+                }
+            }
+        }
+        hasher.finish()
+    }
+
+    /// Snapshots every warp's PC, thread mask, ibuffer head, scheduler wait
+    /// state and outstanding timing-model requests.
+    pub fn dump_state(&self, reason: &str) -> SimStateDump {
+        let cores: Vec<_> = self
+            .top
+            .clusters
+            .iter()
+            .enumerate()
+            .flat_map(|(cluster_id, cluster)| {
+                cluster
+                    .cores
+                    .iter()
+                    .enumerate()
+                    .map(move |(core_id, core)| core.dump_state(cluster_id, core_id))
+            })
+            .collect();
+        SimStateDump {
+            reason: reason.to_string(),
+            cycle: cores.iter().map(|core| core.cycle).max().unwrap_or(0),
+            finished: self.finished(),
+            cores,
+            metadata: self.metadata(),
+        }
+    }
+
+    fn write_state_dump(&self, reason: &str) {
+        let Some(path) = &self.config.state_dump else {
+            return;
+        };
+        match self.dump_state(reason).write(path) {
+            Ok(()) => println!(
+                "Cyclotron: wrote state dump ({}) to {}",
+                reason,
+                path.display()
+            ),
+            Err(err) => eprintln!(
+                "Cyclotron: failed to write state dump to {}: {}",
+                path.display(),
+                err
+            ),
+        }
+    }
+
+    /// Checks `tohost` on the first core of each kernel's first cluster and
+    /// returns the first failure.
     pub fn check_tohost(&self) -> Result<(), u32> {
-        if let Some(tohost) = self.top.clusters[0].cores[0].scheduler.tohost() {
+        let multi_kernel = self.top.kernels.len() > 1;
+        let mut result = Ok(());
+        for kernel in &self.top.kernels {
+            let cluster = &self.top.clusters[kernel.clusters[0]];
+            let Some(tohost) = cluster.cores[0].scheduler.tohost() else {
+                continue;
+            };
+            let label = if multi_kernel {
+                format!("isa-test {}", kernel.name)
+            } else {
+                "isa-test".to_string()
+            };
             if tohost != 0 {
                 let case = tohost >> 1;
                 println!(
-                    "Cyclotron: isa-test failed with tohost={}, case={}",
-                    tohost, case
+                    "Cyclotron: {} failed with tohost={}, case={}",
+                    label, tohost, case
                 );
-                return Err(tohost);
+                if result.is_ok() {
+                    result = Err(tohost);
+                }
             } else {
-                println!("Cyclotron: isa-test passed with tohost={}", tohost);
+                println!("Cyclotron: {} passed with tohost={}", label, tohost);
             }
         }
-        return Ok(());
+        result
     }
 
     /// Advances all cores by one instruction.
@@ -161,8 +1507,24 @@ impl Sim {
         if self.top.finished() {
             return;
         }
+        self.apply_timing_phases();
         self.top.tick_one();
+        self.cycle += 1;
+        self.sample_vcd();
+        self.sample_sqlite();
+        self.drain_pipeview();
+        self.drain_sched_log();
+        self.update_kernel_completions();
         self.drain_traces();
+        self.fire_gmem_completion_hooks();
+        #[cfg(feature = "metrics-server")]
+        self.publish_metrics();
+        self.hooks.cycle(&CycleEvent { cycle: self.cycle });
+        if !self.kernel_finish_reported && self.top.finished() {
+            self.kernel_finish_reported = true;
+            self.hooks
+                .kernel_finish(&KernelFinishEvent { cycle: self.cycle });
+        }
     }
 
     pub fn finished(&self) -> bool {
@@ -173,9 +1535,13 @@ impl Sim {
 pub struct CyclotronConfig {
     pub timeout: u64, // TODO: use sim
     pub elf: PathBuf, // TODO: use sim
+    /// Resolved kernel placement; see `SimConfig::kernel_placement`.
+    pub kernels: Vec<KernelConfig>,
     pub cluster_config: ClusterConfig,
     pub mem_config: MemConfig,
     pub timing_enabled: bool,
+    /// See `SimConfig::timed_cores`.
+    pub timed_cores: Option<Vec<usize>>,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -183,13 +1549,70 @@ pub struct ClusterConfig {
     pub muon_config: MuonConfig,
     pub neutrino_config: NeutrinoConfig,
     pub timing_config: CoreGraphConfig,
+    /// Per-core `[core.N]` overrides, keyed by cluster-local core id.
+    #[serde(skip)]
+    pub core_overrides: BTreeMap<usize, CoreOverride>,
+}
+
+/// Configuration of one core that differs from the cluster-wide defaults.
+#[derive(Debug, Clone)]
+pub struct CoreOverride {
+    pub muon_config: MuonConfig,
+    pub timing_config: CoreGraphConfig,
+}
+
+impl ClusterConfig {
+    pub fn core_muon_config(&self, cid: usize) -> MuonConfig {
+        self.core_overrides
+            .get(&cid)
+            .map_or(self.muon_config, |core| core.muon_config)
+    }
+
+    pub fn core_timing_config(&self, cid: usize) -> &CoreGraphConfig {
+        self.core_overrides
+            .get(&cid)
+            .map_or(&self.timing_config, |core| &core.timing_config)
+    }
+
+    /// Leaves out the cores `gmem.salvage.disabled_cores` fuses off and
+    /// numbers the rest densely, carrying their `[core.N]` overrides along.
+    /// Everything built from the result, the gmem graph included, only sees
+    /// the remaining cores.
+    pub fn salvaged(mut self) -> Self {
+        let salvage = &self.timing_config.memory.gmem.salvage;
+        if salvage.disabled_cores.is_empty() {
+            return self;
+        }
+        let enabled = salvage
+            .enabled_cores(self.muon_config.num_cores)
+            .unwrap_or_else(|err| panic!("{err}"));
+        let num_cores = enabled.len();
+        self.muon_config.num_cores = num_cores;
+        self.neutrino_config.muon_config.num_cores = num_cores;
+        let mut overrides = std::mem::take(&mut self.core_overrides);
+        self.core_overrides = enabled
+            .iter()
+            .enumerate()
+            .filter_map(|(cid, physical)| {
+                let mut core = overrides.remove(physical)?;
+                core.muon_config.num_cores = num_cores;
+                Some((cid, core))
+            })
+            .collect();
+        self
+    }
 }
 
+#[derive(Clone)]
 pub struct CyclotronTop {
     pub cproc: CommandProcessor,
     pub clusters: Vec<Cluster>,
+    pub kernels: Vec<KernelConfig>,
     pub timeout: u64,
-    pub gmem: Arc<RwLock<FlatMemory>>,
+    pub gmem: Shared<FlatMemory>,
+    /// L1/L2/DRAM timing graph shared by every cluster; `None` without
+    /// timing.
+    pub gmem_timing: Option<Shared<ClusterGmemGraph>>,
 }
 
 impl CyclotronTop {
@@ -198,55 +1621,94 @@ impl CyclotronTop {
         logger: &Arc<Logger>,
         perf_log_session: Option<Arc<PerfLogSession>>,
     ) -> CyclotronTop {
-        let elf_path = Path::new(&config.elf);
-        let imem = ElfBackedMem::new(&elf_path);
         let mut clusters = Vec::new();
-        let cluster_config = Arc::new(config.cluster_config.clone());
 
         // TODO: current implementation means imem is writable, but this is true
         // in hardware too?
         let mut gmem = FlatMemory::new(Some(config.mem_config));
-        gmem.copy_elf(&imem);
+        let num_clusters = config
+            .kernels
+            .iter()
+            .flat_map(|kernel| kernel.clusters.iter().map(|&id| id + 1))
+            .max()
+            .unwrap_or(1);
+        let mut cluster_configs = vec![None; num_clusters];
+        for kernel in &config.kernels {
+            // Kernels share gmem, so each must be linked at its own addresses.
+            let imem = ElfBackedMem::new(&kernel.elf);
+            gmem.copy_elf(&imem);
+            let mut cluster_config = config.cluster_config.clone();
+            if let Some(start_pc) = kernel.start_pc {
+                cluster_config.muon_config.start_pc = start_pc;
+                for core in cluster_config.core_overrides.values_mut() {
+                    core.muon_config.start_pc = start_pc;
+                }
+            }
+            let cluster_config = Arc::new(cluster_config);
+            for &id in &kernel.clusters {
+                cluster_configs[id] = Some(cluster_config.clone());
+            }
+        }
+        let cluster_configs: Vec<Arc<ClusterConfig>> = cluster_configs
+            .into_iter()
+            .map(|config| config.expect("every cluster is assigned a kernel"))
+            .collect();
 
-        let gmem = Arc::new(RwLock::new(gmem));
+        let gmem = Shared::new(gmem);
 
-        // TODO: parameterize
-        let num_clusters = 1;
-        let cores_per_cluster = cluster_config.muon_config.num_cores.max(1);
+        let cores_per_cluster = config.cluster_config.muon_config.num_cores.max(1);
+        let mut shared_gmem_timing = None;
         if config.timing_enabled {
-            let gmem_timing = Arc::new(RwLock::new(crate::timeflow::ClusterGmemGraph::new(
-                cluster_config.timing_config.memory.gmem.clone(),
+            let memory = &config.cluster_config.timing_config.memory;
+            let mut gmem_graph = ClusterGmemGraph::new_with_regions(
+                memory.gmem.clone(),
+                memory.map.clone(),
                 num_clusters,
                 cores_per_cluster,
-            )));
-            for id in 0..num_clusters {
+            );
+            let validation = gmem_graph.validate();
+            assert!(validation.is_ok(), "invalid gmem graph:\n{validation}");
+            if let Some(session) = &perf_log_session {
+                gmem_graph.set_path_sample(session.path_sample());
+            }
+            let gmem_timing = Shared::new(gmem_graph);
+            for (id, cluster_config) in cluster_configs.into_iter().enumerate() {
                 clusters.push(Cluster::new_timed(
-                    cluster_config.clone(),
+                    cluster_config,
                     id,
                     logger,
                     gmem.clone(),
                     gmem_timing.clone(),
                     perf_log_session.clone(),
+                    config.timed_cores.as_deref(),
                 ));
             }
+            shared_gmem_timing = Some(gmem_timing);
         } else {
-            for id in 0..num_clusters {
-                clusters.push(Cluster::new(
-                    cluster_config.clone(),
-                    id,
-                    logger,
-                    gmem.clone(),
-                ));
+            for (id, cluster_config) in cluster_configs.into_iter().enumerate() {
+                clusters.push(Cluster::new(cluster_config, id, logger, gmem.clone()));
+            }
+        }
+        // Tag each kernel's requests so co-running kernels are reported
+        // apart.
+        for (idx, kernel) in config.kernels.iter().enumerate() {
+            for &id in &kernel.clusters {
+                for core in clusters[id].cores.iter_mut() {
+                    core.set_context(idx);
+                }
             }
         }
         CyclotronTop {
             cproc: CommandProcessor::new(
                 Arc::new(config.cluster_config.muon_config.clone()),
-                1, /*FIXME: properly get thread dimension*/
+                num_clusters,
+                num_clusters, /*FIXME: properly get thread dimension*/
             ),
             clusters,
+            kernels: config.kernels.clone(),
             timeout: config.timeout,
             gmem,
+            gmem_timing: shared_gmem_timing,
         }
     }
 
@@ -261,7 +1723,7 @@ impl CyclotronTop {
     }
 
     /// Load a word from gmem.
-    pub fn gmem_load(&self, addr: u32) -> [u8; 4] {
+    pub fn gmem_load(&self, addr: u64) -> [u8; 4] {
         self.gmem
             .read()
             .expect("lock poisoned")
@@ -285,6 +1747,14 @@ impl CyclotronTop {
     pub fn finished(&self) -> bool {
         self.clusters.iter().all(|cl| cl.all_cores_retired())
     }
+
+    /// Whether every cluster running the `idx`-th kernel has retired.
+    pub fn kernel_finished(&self, idx: usize) -> bool {
+        self.kernels[idx]
+            .clusters
+            .iter()
+            .all(|&id| self.clusters[id].all_cores_retired())
+    }
 }
 
 impl ModuleBehaviors for CyclotronTop {
@@ -1,4 +1,5 @@
 use crate::muon::config::MuonConfig;
+use crate::muon::lane_mask::LaneMask;
 use crate::muon::warp::Writeback;
 use std::collections::VecDeque;
 use std::fmt;
@@ -6,6 +7,7 @@ use std::iter::zip;
 
 /// Queues instruction traces from a core into a buffer, and provides a per-warp consume()
 /// interface to dequeue instructions from the buffer in program-order.
+#[derive(Clone)]
 pub struct Tracer {
     /// per-warp program-order buffer of lines
     bufs: Vec<VecDeque<Line>>,
@@ -13,6 +15,7 @@ pub struct Tracer {
     rr: usize,
 }
 
+#[derive(Clone)]
 pub struct MemTracer {
     buf: VecDeque<MemTraceLine>,
 }
@@ -38,7 +41,7 @@ pub struct Line {
     pub imm32: u32,
     pub imm24: i32,
     pub csr_imm: u8,
-    pub tmask: u32,
+    pub tmask: LaneMask,
     pub raw: u64,
 }
 
@@ -46,9 +49,11 @@ pub struct Line {
 pub struct MemTraceLine {
     pub warp_id: u32,
     pub lane_id: u32,
+    /// PC of the load or store.
+    pub pc: u32,
     pub is_smem: bool,
     pub store: bool,
-    pub address: u32,
+    pub address: u64,
     pub size: u32,
     pub data: u32,
 }
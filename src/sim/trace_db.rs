@@ -1,66 +1,337 @@
 use crate::muon::decode::DecodedInst;
+use crate::sim::config::LogFormat;
+#[cfg(feature = "parquet")]
+use crate::sim::parquet_sink::ParquetSink;
 use crate::sim::trace::{Line, MemTraceLine};
 use rusqlite::Connection;
+#[cfg(feature = "parquet")]
+use serde::Serialize;
+use std::collections::VecDeque;
 use std::path::{Path, PathBuf};
 
 pub struct TraceDb {
-    conn: Connection,
+    store: TraceStore,
+    /// Set in ring-buffer mode, where events are only written by
+    /// `dump_ring`.
+    ring: Option<TraceRing>,
+}
+
+enum TraceEvent {
+    Inst {
+        cluster_id: u32,
+        core_id: u32,
+        line: Line,
+    },
+    Mem {
+        cluster_id: u32,
+        core_id: u32,
+        line: MemTraceLine,
+    },
+}
+
+/// The most recent `capacity` trace events; older ones are overwritten.
+struct TraceRing {
+    events: VecDeque<TraceEvent>,
+    capacity: usize,
+    /// Events overwritten since the last dump.
+    overwritten: u64,
+}
+
+impl TraceRing {
+    fn push(&mut self, event: TraceEvent) {
+        if self.capacity == 0 {
+            self.overwritten += 1;
+            return;
+        }
+        if self.events.len() == self.capacity {
+            self.events.pop_front();
+            self.overwritten += 1;
+        }
+        self.events.push_back(event);
+    }
+}
+
+/// What `TraceDb::dump_ring` wrote.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RingDump {
+    pub events: usize,
+    /// Events overwritten before they could be dumped.
+    pub overwritten: u64,
+}
+
+/// Where the trace goes: the `inst`, `dmem` and `smem` tables of a SQLite
+/// database, or with `log.format = "parquet"` one Parquet file per table
+/// next to where the database would be (see `trace_parquet_path`).
+enum TraceStore {
+    Sqlite(Connection),
+    #[cfg(feature = "parquet")]
+    Parquet {
+        inst: Box<ParquetSink>,
+        dmem: Box<ParquetSink>,
+        smem: Box<ParquetSink>,
+    },
+}
+
+impl TraceStore {
+    fn create(db_path: &Path, format: LogFormat) -> Self {
+        match format.supported() {
+            #[cfg(feature = "parquet")]
+            LogFormat::Parquet => {
+                let sink =
+                    |table| Box::new(ParquetSink::new(&trace_parquet_path(db_path, table), table));
+                Self::Parquet {
+                    inst: sink("inst"),
+                    dmem: sink("dmem"),
+                    smem: sink("smem"),
+                }
+            }
+            _ => Self::Sqlite(create_new_db_overwrite(db_path)),
+        }
+    }
+
+    fn insert_inst_line(&mut self, cluster_id: u32, core_id: u32, line: &Line) {
+        match self {
+            Self::Sqlite(conn) => insert_inst_line(conn, cluster_id, core_id, line),
+            #[cfg(feature = "parquet")]
+            Self::Parquet { inst, .. } => inst.write(&InstRecord::new(cluster_id, core_id, line)),
+        }
+    }
+
+    fn insert_mem_line(&mut self, cluster_id: u32, core_id: u32, line: &MemTraceLine) {
+        match self {
+            Self::Sqlite(conn) => insert_mem_line(conn, cluster_id, core_id, line),
+            #[cfg(feature = "parquet")]
+            Self::Parquet { dmem, smem, .. } => {
+                let sink = if line.is_smem { smem } else { dmem };
+                sink.write(&MemRecord::new(cluster_id, core_id, line));
+            }
+        }
+    }
 }
 
 impl TraceDb {
-    pub fn new(db_path: &Path) -> Self {
+    pub fn new(db_path: &Path, format: LogFormat) -> Self {
+        Self {
+            store: TraceStore::create(db_path, format),
+            ring: None,
+        }
+    }
+
+    /// Holds the last `capacity` events in memory instead of writing each
+    /// one as it comes; `dump_ring` writes them out.
+    pub fn with_ring(db_path: &Path, format: LogFormat, capacity: usize) -> Self {
         Self {
-            conn: create_new_db_overwrite(db_path),
+            store: TraceStore::create(db_path, format),
+            ring: Some(TraceRing {
+                events: VecDeque::new(),
+                capacity,
+                overwritten: 0,
+            }),
+        }
+    }
+
+    pub fn record_inst_line(&mut self, cluster_id: u32, core_id: u32, line: &Line) {
+        match &mut self.ring {
+            Some(ring) => ring.push(TraceEvent::Inst {
+                cluster_id,
+                core_id,
+                line: line.clone(),
+            }),
+            None => self.store.insert_inst_line(cluster_id, core_id, line),
         }
     }
 
-    pub fn record_inst_line(&self, cluster_id: u32, core_id: u32, line: &Line) {
-        let rs1_string = encode_lane_data(&line.rs1_data);
-        let rs2_string = encode_lane_data(&line.rs2_data);
+    pub fn record_mem_line(&mut self, cluster_id: u32, core_id: u32, line: &MemTraceLine) {
+        match &mut self.ring {
+            Some(ring) => ring.push(TraceEvent::Mem {
+                cluster_id,
+                core_id,
+                line: line.clone(),
+            }),
+            None => self.store.insert_mem_line(cluster_id, core_id, line),
+        }
+    }
+
+    /// Writes the Parquet files' footers; records after this are dropped.
+    /// SQLite needs nothing, it commits every insert.
+    pub fn finish(&mut self) {
+        match &mut self.store {
+            TraceStore::Sqlite(_) => {}
+            #[cfg(feature = "parquet")]
+            TraceStore::Parquet { inst, dmem, smem } => {
+                inst.finish();
+                dmem.finish();
+                smem.finish();
+            }
+        }
+    }
+
+    /// Appends the events the ring holds to the database and empties it.
+    /// `None` outside ring-buffer mode.
+    pub fn dump_ring(&mut self) -> Option<RingDump> {
+        let ring = self.ring.as_mut()?;
+        let dump = RingDump {
+            events: ring.events.len(),
+            overwritten: std::mem::take(&mut ring.overwritten),
+        };
+        let events = ring.events.drain(..);
+        match &mut self.store {
+            TraceStore::Sqlite(conn) => {
+                let tx = conn
+                    .transaction()
+                    .expect("failed to start trace dump transaction");
+                for event in events {
+                    match event {
+                        TraceEvent::Inst {
+                            cluster_id,
+                            core_id,
+                            line,
+                        } => insert_inst_line(&tx, cluster_id, core_id, &line),
+                        TraceEvent::Mem {
+                            cluster_id,
+                            core_id,
+                            line,
+                        } => insert_mem_line(&tx, cluster_id, core_id, &line),
+                    }
+                }
+                tx.commit().expect("failed to commit trace dump");
+            }
+            #[cfg(feature = "parquet")]
+            store @ TraceStore::Parquet { .. } => {
+                for event in events {
+                    match event {
+                        TraceEvent::Inst {
+                            cluster_id,
+                            core_id,
+                            line,
+                        } => store.insert_inst_line(cluster_id, core_id, &line),
+                        TraceEvent::Mem {
+                            cluster_id,
+                            core_id,
+                            line,
+                        } => store.insert_mem_line(cluster_id, core_id, &line),
+                    }
+                }
+            }
+        }
+        Some(dump)
+    }
+}
+
+fn insert_inst_line(conn: &Connection, cluster_id: u32, core_id: u32, line: &Line) {
+    let rs1_string = encode_lane_data(&line.rs1_data);
+    let rs2_string = encode_lane_data(&line.rs2_data);
+    let has_regs = decoded_inst(line).has_regs();
+    conn.execute(
+        "INSERT INTO inst (cluster_id, core_id, warp, pc, lane_mask, has_rs1, rs1_id, rs1_data, has_rs2, rs2_id, rs2_data)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+        (
+            cluster_id,
+            core_id,
+            line.warp_id,
+            line.pc,
+            line.tmask.to_u64() as i64,
+            has_regs.rs1,
+            line.rs1_addr,
+            &rs1_string,
+            has_regs.rs2,
+            line.rs2_addr,
+            &rs2_string,
+        ),
+    )
+    .expect("failed to insert into inst");
+}
+
+fn insert_mem_line(conn: &Connection, cluster_id: u32, core_id: u32, line: &MemTraceLine) {
+    let table = if line.is_smem { "smem" } else { "dmem" };
+    conn.execute(
+        &format!(
+            "INSERT INTO {table} (cluster_id, core_id, lane_id, store, address, size, data)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)"
+        ),
+        (
+            cluster_id,
+            core_id,
+            line.lane_id,
+            line.store,
+            line.address as i64,
+            line.size,
+            line.data,
+        ),
+    )
+    .expect("failed to insert into memory trace table");
+}
+
+/// A row of the Parquet `inst` file; the columns of the `inst` table.
+#[cfg(feature = "parquet")]
+#[derive(Serialize)]
+struct InstRecord {
+    cluster_id: u32,
+    core_id: u32,
+    warp: u32,
+    pc: u32,
+    lane_mask: u64,
+    has_rs1: bool,
+    rs1_id: u8,
+    rs1_data: String,
+    has_rs2: bool,
+    rs2_id: u8,
+    rs2_data: String,
+}
+
+#[cfg(feature = "parquet")]
+impl InstRecord {
+    fn new(cluster_id: u32, core_id: u32, line: &Line) -> Self {
         let has_regs = decoded_inst(line).has_regs();
-        self.conn
-            .execute(
-                "INSERT INTO inst (cluster_id, core_id, warp, pc, lane_mask, has_rs1, rs1_id, rs1_data, has_rs2, rs2_id, rs2_data)
-                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
-                (
-                    cluster_id,
-                    core_id,
-                    line.warp_id,
-                    line.pc,
-                    line.tmask,
-                    has_regs.rs1,
-                    line.rs1_addr,
-                    &rs1_string,
-                    has_regs.rs2,
-                    line.rs2_addr,
-                    &rs2_string,
-                ),
-            )
-            .expect("failed to insert into inst");
-    }
-
-    pub fn record_mem_line(&self, cluster_id: u32, core_id: u32, line: &MemTraceLine) {
-        let table = if line.is_smem { "smem" } else { "dmem" };
-        self.conn
-            .execute(
-                &format!(
-                    "INSERT INTO {table} (cluster_id, core_id, lane_id, store, address, size, data)
-                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)"
-                ),
-                (
-                    cluster_id,
-                    core_id,
-                    line.lane_id,
-                    line.store,
-                    line.address,
-                    line.size,
-                    line.data,
-                ),
-            )
-            .expect("failed to insert into memory trace table");
+        Self {
+            cluster_id,
+            core_id,
+            warp: line.warp_id,
+            pc: line.pc,
+            lane_mask: line.tmask.to_u64(),
+            has_rs1: has_regs.rs1,
+            rs1_id: line.rs1_addr,
+            rs1_data: encode_lane_data(&line.rs1_data),
+            has_rs2: has_regs.rs2,
+            rs2_id: line.rs2_addr,
+            rs2_data: encode_lane_data(&line.rs2_data),
+        }
+    }
+}
+
+/// A row of the Parquet `dmem` or `smem` file.
+#[cfg(feature = "parquet")]
+#[derive(Serialize)]
+struct MemRecord {
+    cluster_id: u32,
+    core_id: u32,
+    lane_id: u32,
+    store: bool,
+    address: u64,
+    size: u32,
+    data: u32,
+}
+
+#[cfg(feature = "parquet")]
+impl MemRecord {
+    fn new(cluster_id: u32, core_id: u32, line: &MemTraceLine) -> Self {
+        Self {
+            cluster_id,
+            core_id,
+            lane_id: line.lane_id,
+            store: line.store,
+            address: line.address,
+            size: line.size,
+            data: line.data,
+        }
     }
 }
 
+/// `<db stem>.<table>.parquet`, where the Parquet trace keeps `table`.
+pub fn trace_parquet_path(db_path: &Path, table: &str) -> PathBuf {
+    db_path.with_extension(format!("{table}.parquet"))
+}
+
 pub fn default_trace_db_path(trace_db_path_arg: Option<&Path>, elf_path: Option<&Path>) -> PathBuf {
     if let Some(path) = trace_db_path_arg {
         path.to_path_buf()
@@ -169,3 +440,109 @@ fn decoded_inst(line: &Line) -> DecodedInst {
         raw: line.raw,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn conn(db: &TraceDb) -> &Connection {
+        match &db.store {
+            TraceStore::Sqlite(conn) => conn,
+            #[cfg(feature = "parquet")]
+            TraceStore::Parquet { .. } => panic!("not a sqlite trace"),
+        }
+    }
+
+    fn count(db: &TraceDb, table: &str) -> i64 {
+        conn(db)
+            .query_row(&format!("SELECT COUNT(*) FROM {table}"), (), |row| {
+                row.get(0)
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn ring_keeps_the_latest_events_until_dumped() {
+        let path = std::env::temp_dir().join(format!(
+            "cyclotron_trace_ring_{}.sqlite",
+            std::process::id()
+        ));
+        let mut db = TraceDb::with_ring(&path, LogFormat::Jsonl, 3);
+        for pc in 0..5 {
+            let line = Line {
+                pc,
+                ..Line::default()
+            };
+            db.record_inst_line(0, 0, &line);
+        }
+        assert_eq!(count(&db, "inst"), 0);
+
+        let dump = db.dump_ring().unwrap();
+        assert_eq!(
+            dump,
+            RingDump {
+                events: 3,
+                overwritten: 2
+            }
+        );
+        let pcs: Vec<u32> = conn(&db)
+            .prepare("SELECT pc FROM inst ORDER BY id")
+            .unwrap()
+            .query_map((), |row| row.get(0))
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(pcs, [2, 3, 4]);
+        assert_eq!(db.dump_ring(), Some(RingDump::default()));
+
+        drop(db);
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{}", path.display(), suffix));
+        }
+    }
+
+    #[cfg(feature = "parquet")]
+    #[test]
+    fn parquet_trace_writes_a_file_per_table() {
+        use parquet::file::reader::{FileReader, SerializedFileReader};
+
+        let path = std::env::temp_dir().join(format!(
+            "cyclotron_trace_parquet_{}.sqlite",
+            std::process::id()
+        ));
+        let mut db = TraceDb::new(&path, LogFormat::Parquet);
+        for pc in [0x80, 0x84] {
+            let line = Line {
+                pc,
+                ..Line::default()
+            };
+            db.record_inst_line(0, 1, &line);
+        }
+        let store = MemTraceLine {
+            warp_id: 0,
+            lane_id: 3,
+            pc: 0x84,
+            is_smem: true,
+            store: true,
+            address: 0x40,
+            size: 4,
+            data: 7,
+        };
+        db.record_mem_line(0, 1, &store);
+        drop(db);
+
+        let rows = |table| {
+            let path = trace_parquet_path(&path, table);
+            let reader = std::fs::File::open(&path)
+                .ok()
+                .map(|file| SerializedFileReader::new(file).unwrap());
+            std::fs::remove_file(&path).ok();
+            reader.map(|reader| reader.metadata().file_metadata().num_rows())
+        };
+        assert_eq!(rows("inst"), Some(2));
+        assert_eq!(rows("smem"), Some(1));
+        // Tables nothing went to get no file, and there is no database.
+        assert_eq!(rows("dmem"), None);
+        assert!(!path.exists());
+    }
+}
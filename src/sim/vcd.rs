@@ -0,0 +1,515 @@
+//! Waveform dump of selected simulator signals in the Value Change Dump
+//! format, for viewing the model next to RTL in GTKWave or Surfer.
+//!
+//! `[sim.vcd]` names the signals to record:
+//!
+//! - `warp.pc`, `warp.active`, `warp.stalled`, `warp.ibuffer_valid`: per warp
+//!   of each recorded core.
+//! - `core.stall`: the CPI bucket the core's last cycle was charged to, as
+//!   an index into `CpiStack::components()` (0 = base, 1 = gmem, ...). The
+//!   mapping is repeated in the file header. Needs `--timing`.
+//! - `core.gmem_outstanding`, `core.smem_outstanding`: requests the core's
+//!   timing model has in flight.
+//! - `gmem.<node>`: requests queued or in service at a node of the shared
+//!   L1/L2/DRAM graph, e.g. `gmem.dram`. A trailing `*` matches every node
+//!   with that prefix. Needs `--timing`.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+use crate::muon::core::MuonCore;
+use crate::muon::gmem::CpiStack;
+use crate::sim::config::VcdConfig;
+use crate::sim::top::CyclotronTop;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum WarpSignal {
+    Pc,
+    Active,
+    Stalled,
+    IbufferValid,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CoreSignal {
+    Stall,
+    GmemOutstanding,
+    SmemOutstanding,
+}
+
+impl WarpSignal {
+    const ALL: [WarpSignal; 4] = [
+        WarpSignal::Pc,
+        WarpSignal::Active,
+        WarpSignal::Stalled,
+        WarpSignal::IbufferValid,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            WarpSignal::Pc => "pc",
+            WarpSignal::Active => "active",
+            WarpSignal::Stalled => "stalled",
+            WarpSignal::IbufferValid => "ibuffer_valid",
+        }
+    }
+
+    fn width(self) -> u32 {
+        match self {
+            WarpSignal::Pc => 32,
+            _ => 1,
+        }
+    }
+}
+
+impl CoreSignal {
+    const ALL: [CoreSignal; 3] = [
+        CoreSignal::Stall,
+        CoreSignal::GmemOutstanding,
+        CoreSignal::SmemOutstanding,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            CoreSignal::Stall => "stall",
+            CoreSignal::GmemOutstanding => "gmem_outstanding",
+            CoreSignal::SmemOutstanding => "smem_outstanding",
+        }
+    }
+
+    fn width(self) -> u32 {
+        match self {
+            CoreSignal::Stall => 4,
+            _ => 32,
+        }
+    }
+}
+
+/// The signals `[sim.vcd]` asked for.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct SignalSelection {
+    warp: Vec<WarpSignal>,
+    core: Vec<CoreSignal>,
+    /// Node names, or prefixes ending in `*`.
+    gmem: Vec<String>,
+}
+
+impl SignalSelection {
+    fn parse(signals: &[String]) -> Result<Self, String> {
+        let mut selection = SignalSelection::default();
+        for signal in signals {
+            let unknown = || format!("unknown VCD signal '{signal}'");
+            let (scope, name) = signal.split_once('.').ok_or_else(unknown)?;
+            match scope {
+                "warp" => {
+                    let warp = WarpSignal::ALL
+                        .into_iter()
+                        .find(|s| s.name() == name)
+                        .ok_or_else(unknown)?;
+                    if !selection.warp.contains(&warp) {
+                        selection.warp.push(warp);
+                    }
+                }
+                "core" => {
+                    let core = CoreSignal::ALL
+                        .into_iter()
+                        .find(|s| s.name() == name)
+                        .ok_or_else(unknown)?;
+                    if !selection.core.contains(&core) {
+                        selection.core.push(core);
+                    }
+                }
+                "gmem" if !name.is_empty() => selection.gmem.push(name.to_string()),
+                _ => return Err(unknown()),
+            }
+        }
+        Ok(selection)
+    }
+
+    fn matches_node(&self, node: &str) -> bool {
+        self.gmem
+            .iter()
+            .any(|pattern| match pattern.strip_suffix('*') {
+                Some(prefix) => node.starts_with(prefix),
+                None => node == pattern,
+            })
+    }
+}
+
+/// Identifier of a variable declared with `VcdWriter::var`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VcdVar(usize);
+
+struct VarState {
+    code: String,
+    width: u32,
+    last: Option<Option<u64>>,
+}
+
+/// Low-level VCD output: a header of nested scopes and variables, then one
+/// `#time` section per timestamp holding only the values that changed.
+/// `None` is written as `x`.
+pub struct VcdWriter<W: Write> {
+    out: W,
+    vars: Vec<VarState>,
+    time: Option<u64>,
+}
+
+impl<W: Write> VcdWriter<W> {
+    /// Starts the header. Timestamps are in cycles.
+    pub fn new(mut out: W, comment: &str) -> io::Result<Self> {
+        writeln!(out, "$version cyclotron {} $end", env!("CARGO_PKG_VERSION"))?;
+        if !comment.is_empty() {
+            writeln!(out, "$comment {comment} $end")?;
+        }
+        writeln!(out, "$timescale 1ns $end")?;
+        Ok(Self {
+            out,
+            vars: Vec::new(),
+            time: None,
+        })
+    }
+
+    pub fn scope(&mut self, name: &str) -> io::Result<()> {
+        writeln!(self.out, "$scope module {name} $end")
+    }
+
+    pub fn upscope(&mut self) -> io::Result<()> {
+        writeln!(self.out, "$upscope $end")
+    }
+
+    pub fn var(&mut self, name: &str, width: u32) -> io::Result<VcdVar> {
+        let code = id_code(self.vars.len());
+        writeln!(self.out, "$var wire {width} {code} {name} $end")?;
+        self.vars.push(VarState {
+            code,
+            width,
+            last: None,
+        });
+        Ok(VcdVar(self.vars.len() - 1))
+    }
+
+    pub fn end_definitions(&mut self) -> io::Result<()> {
+        writeln!(self.out, "$enddefinitions $end")
+    }
+
+    /// Records `var`'s value at `time`, which must not go backwards. Nothing
+    /// is written if the value did not change.
+    pub fn change(&mut self, time: u64, var: VcdVar, value: Option<u64>) -> io::Result<()> {
+        let state = &mut self.vars[var.0];
+        if state.last == Some(value) {
+            return Ok(());
+        }
+        state.last = Some(value);
+        if self.time != Some(time) {
+            self.time = Some(time);
+            writeln!(self.out, "#{time}")?;
+        }
+        let state = &self.vars[var.0];
+        match (state.width, value) {
+            (1, Some(bit)) => writeln!(self.out, "{}{}", bit & 1, state.code),
+            (1, None) => writeln!(self.out, "x{}", state.code),
+            (_, Some(value)) => writeln!(self.out, "b{value:b} {}", state.code),
+            (_, None) => writeln!(self.out, "bx {}", state.code),
+        }
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+/// Short printable identifier for the `index`-th variable: `!`, `"`, ...,
+/// `~`, then two characters, and so on.
+fn id_code(mut index: usize) -> String {
+    const FIRST: u8 = b'!';
+    const RADIX: usize = (b'~' - b'!' + 1) as usize;
+    let mut code = String::new();
+    loop {
+        code.push((FIRST + (index % RADIX) as u8) as char);
+        index /= RADIX;
+        if index == 0 {
+            return code;
+        }
+        index -= 1;
+    }
+}
+
+struct WarpVars {
+    warp_id: usize,
+    vars: Vec<(WarpSignal, VcdVar)>,
+}
+
+struct CoreVars {
+    cluster_id: usize,
+    core_id: usize,
+    vars: Vec<(CoreSignal, VcdVar)>,
+    warps: Vec<WarpVars>,
+    /// CPI stack at the previous sample, to find the bucket the last cycle
+    /// went to.
+    last_cpi: Option<CpiStack>,
+    stall: Option<u64>,
+}
+
+/// Samples the signals selected by `[sim.vcd]` from a `CyclotronTop` once a
+/// cycle.
+pub struct VcdRecorder {
+    writer: VcdWriter<BufWriter<File>>,
+    cores: Vec<CoreVars>,
+    gmem_vars: Vec<(usize, VcdVar)>,
+}
+
+impl VcdRecorder {
+    /// Creates `config.path` and writes the header. Returns `Ok(None)` when
+    /// no path is set; panics on an unknown signal name.
+    pub fn new(config: &VcdConfig, top: &CyclotronTop) -> io::Result<Option<Self>> {
+        let Some(path) = &config.path else {
+            return Ok(None);
+        };
+        let selection =
+            SignalSelection::parse(&config.signals).unwrap_or_else(|err| panic!("{err}"));
+        let stall_names: Vec<String> = CpiStack::default()
+            .components()
+            .iter()
+            .enumerate()
+            .map(|(idx, (name, _))| format!("{idx}={name}"))
+            .collect();
+        let comment = format!("core.stall: {}", stall_names.join(" "));
+        let mut writer = VcdWriter::new(BufWriter::new(File::create(path)?), &comment)?;
+
+        writer.scope("cyclotron")?;
+        let mut cores = Vec::new();
+        if !selection.warp.is_empty() || !selection.core.is_empty() {
+            for (cluster_id, cluster) in top.clusters.iter().enumerate() {
+                writer.scope(&format!("cluster{cluster_id}"))?;
+                let num_cores = cluster.cores.len();
+                for (core_id, core) in cluster.cores.iter().enumerate() {
+                    let global_id = cluster_id * num_cores + core_id;
+                    if config
+                        .cores
+                        .as_ref()
+                        .is_some_and(|ids| !ids.contains(&global_id))
+                    {
+                        continue;
+                    }
+                    cores.push(Self::declare_core(
+                        &mut writer,
+                        &selection,
+                        cluster_id,
+                        core_id,
+                        core,
+                    )?);
+                }
+                writer.upscope()?;
+            }
+        }
+
+        let mut gmem_vars = Vec::new();
+        if selection.gmem.is_empty() {
+        } else if let Some(gmem_timing) = &top.gmem_timing {
+            let names = gmem_timing
+                .read()
+                .expect("gmem timing lock poisoned")
+                .node_names();
+            writer.scope("gmem")?;
+            for (node, name) in names.iter().enumerate() {
+                if selection.matches_node(name) {
+                    gmem_vars.push((node, writer.var(name, 32)?));
+                }
+            }
+            writer.upscope()?;
+            if gmem_vars.is_empty() {
+                eprintln!(
+                    "Cyclotron: no gmem node matches the VCD signals {:?}",
+                    selection.gmem
+                );
+            }
+        } else {
+            eprintln!("Cyclotron: gmem VCD signals are only recorded with --timing");
+        }
+        writer.upscope()?;
+        writer.end_definitions()?;
+
+        Ok(Some(Self {
+            writer,
+            cores,
+            gmem_vars,
+        }))
+    }
+
+    fn declare_core(
+        writer: &mut VcdWriter<BufWriter<File>>,
+        selection: &SignalSelection,
+        cluster_id: usize,
+        core_id: usize,
+        core: &MuonCore,
+    ) -> io::Result<CoreVars> {
+        writer.scope(&format!("core{core_id}"))?;
+        let mut vars = Vec::new();
+        for &signal in &selection.core {
+            vars.push((signal, writer.var(signal.name(), signal.width())?));
+        }
+        let mut warps = Vec::new();
+        if !selection.warp.is_empty() {
+            for warp_id in 0..core.warps.len() {
+                writer.scope(&format!("warp{warp_id}"))?;
+                let mut warp_vars = Vec::new();
+                for &signal in &selection.warp {
+                    warp_vars.push((signal, writer.var(signal.name(), signal.width())?));
+                }
+                writer.upscope()?;
+                warps.push(WarpVars {
+                    warp_id,
+                    vars: warp_vars,
+                });
+            }
+        }
+        writer.upscope()?;
+        Ok(CoreVars {
+            cluster_id,
+            core_id,
+            vars,
+            warps,
+            last_cpi: None,
+            stall: None,
+        })
+    }
+
+    /// Writes the values that changed since the last sample, timestamped
+    /// `cycle`.
+    pub fn sample(&mut self, cycle: u64, top: &CyclotronTop) -> io::Result<()> {
+        for core_vars in &mut self.cores {
+            let core = &top.clusters[core_vars.cluster_id].cores[core_vars.core_id];
+            if let Some(cpi) = core.cpi_stack() {
+                if let Some(last) = &core_vars.last_cpi {
+                    let charged = cpi
+                        .components()
+                        .iter()
+                        .zip(last.components())
+                        .position(|((_, now), (_, before))| *now > before);
+                    if let Some(bucket) = charged {
+                        core_vars.stall = Some(bucket as u64);
+                    }
+                }
+                core_vars.last_cpi = Some(cpi);
+            }
+            let (gmem_outstanding, smem_outstanding) = core.outstanding_timing_requests();
+            for &(signal, var) in &core_vars.vars {
+                let value = match signal {
+                    CoreSignal::Stall => core_vars.stall,
+                    CoreSignal::GmemOutstanding => Some(gmem_outstanding as u64),
+                    CoreSignal::SmemOutstanding => Some(smem_outstanding as u64),
+                };
+                self.writer.change(cycle, var, value)?;
+            }
+            for warp in &core_vars.warps {
+                let state = core.scheduler.warp_state(warp.warp_id);
+                for &(signal, var) in &warp.vars {
+                    let value = match signal {
+                        WarpSignal::Pc => state.pc as u64,
+                        WarpSignal::Active => state.active as u64,
+                        WarpSignal::Stalled => state.stalled as u64,
+                        WarpSignal::IbufferValid => core.ibuffer_valid(warp.warp_id) as u64,
+                    };
+                    self.writer.change(cycle, var, Some(value))?;
+                }
+            }
+        }
+        // Read through `top` rather than a handle of our own, which a
+        // restored checkpoint would leave pointing at the old graph.
+        if let Some(gmem_timing) = top
+            .gmem_timing
+            .as_ref()
+            .filter(|_| !self.gmem_vars.is_empty())
+        {
+            let graph = gmem_timing.read().expect("gmem timing lock poisoned");
+            for &(node, var) in &self.gmem_vars {
+                self.writer
+                    .change(cycle, var, Some(graph.node_outstanding(node) as u64))?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_only_changed_values() {
+        let mut writer = VcdWriter::new(Vec::new(), "").unwrap();
+        writer.scope("core0").unwrap();
+        let pc = writer.var("pc", 32).unwrap();
+        let active = writer.var("active", 1).unwrap();
+        writer.upscope().unwrap();
+        writer.end_definitions().unwrap();
+        writer.change(0, pc, Some(0x80)).unwrap();
+        writer.change(0, active, None).unwrap();
+        writer.change(1, pc, Some(0x80)).unwrap();
+        writer.change(1, active, Some(1)).unwrap();
+        writer.change(2, pc, Some(0x84)).unwrap();
+        writer.change(2, active, Some(1)).unwrap();
+
+        let text = String::from_utf8(writer.out).unwrap();
+        let body: Vec<&str> = text
+            .lines()
+            .skip_while(|line| !line.starts_with("$scope"))
+            .collect();
+        assert_eq!(
+            body,
+            [
+                "$scope module core0 $end",
+                "$var wire 32 ! pc $end",
+                "$var wire 1 \" active $end",
+                "$upscope $end",
+                "$enddefinitions $end",
+                "#0",
+                "b10000000 !",
+                "x\"",
+                "#1",
+                "1\"",
+                "#2",
+                "b10000100 !",
+            ]
+        );
+    }
+
+    #[test]
+    fn id_codes_are_unique_and_printable() {
+        let codes: Vec<String> = (0..10_000).map(id_code).collect();
+        assert_eq!(codes[0], "!");
+        assert_eq!(codes[93], "~");
+        assert_eq!(codes[94], "!!");
+        let unique: std::collections::HashSet<&String> = codes.iter().collect();
+        assert_eq!(unique.len(), codes.len());
+        assert!(codes
+            .iter()
+            .all(|code| code.bytes().all(|b| (b'!'..=b'~').contains(&b))));
+    }
+
+    #[test]
+    fn signal_names_are_parsed_and_checked() {
+        let signals = [
+            "warp.pc",
+            "core.stall",
+            "gmem.dram*",
+            "gmem.l2_mshr",
+            "warp.pc",
+        ]
+        .map(String::from);
+        let selection = SignalSelection::parse(&signals).unwrap();
+        assert_eq!(selection.warp, [WarpSignal::Pc]);
+        assert_eq!(selection.core, [CoreSignal::Stall]);
+        assert!(selection.matches_node("dram_1"));
+        assert!(selection.matches_node("l2_mshr"));
+        assert!(!selection.matches_node("l2_mshr_0"));
+
+        let err = SignalSelection::parse(&["warp.npc".to_string()]).unwrap_err();
+        assert!(err.contains("warp.npc"), "{err}");
+    }
+}
@@ -1,5 +1,5 @@
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, VecDeque};
 use std::ops::AddAssign;
 
 use crate::timeq::{Cycle, ServerConfig, ServiceRequest, TimedServer};
@@ -10,6 +10,12 @@ pub struct BarrierConfig {
     pub enabled: bool,
     pub expected_warps: Option<usize>,
     pub barrier_id_bits: u32,
+    /// Warps handed back to the scheduler per cycle once a barrier releases
+    /// (0 = all at once).
+    pub release_per_cycle: u32,
+    /// Extra cycles before a released warp can issue, modeling the pipeline
+    /// refill after it was parked.
+    pub refill_penalty: Cycle,
     #[serde(flatten)]
     pub queue: ServerConfig,
 }
@@ -20,6 +26,8 @@ impl Default for BarrierConfig {
             enabled: false,
             expected_warps: None,
             barrier_id_bits: 0,
+            release_per_cycle: 0,
+            refill_penalty: 0,
             queue: ServerConfig {
                 base_latency: 1,
                 bytes_per_cycle: 1,
@@ -37,6 +45,12 @@ pub struct BarrierManager {
     server: TimedServer<u32>,
     states: HashMap<u32, BarrierState>,
     num_warps: usize,
+    release_per_cycle: usize,
+    refill_penalty: Cycle,
+    /// Warps whose barrier has released, waiting for release bandwidth.
+    release_queue: VecDeque<ReleasedWarp>,
+    /// Warps past the release port, waiting out the refill penalty.
+    refilling: Vec<(Cycle, ReleasedWarp)>,
 
     id_mask: Option<u32>,
     stats: BarrierSummary,
@@ -44,11 +58,19 @@ pub struct BarrierManager {
 
 struct BarrierState {
     arrived: Vec<bool>,
+    arrived_at: Vec<Cycle>,
     releasing: Vec<usize>,
     release_at: Option<Cycle>,
 }
 
-#[derive(Debug, Clone, Copy, Default, Serialize)]
+#[derive(Debug, Clone, Copy)]
+struct ReleasedWarp {
+    warp: usize,
+    barrier_id: u32,
+    arrived_at: Cycle,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct BarrierSummary {
     pub arrivals: u64,
     pub queue_rejects: u64,
@@ -57,6 +79,14 @@ pub struct BarrierSummary {
     pub max_release_batch: u64,
     pub total_scheduled_wait_cycles: u64,
     pub max_scheduled_wait_cycles: u64,
+    /// Cycles from a warp's arrival until it could issue again.
+    pub total_stall_cycles: u64,
+    pub max_stall_cycles: u64,
+    /// Cycles warps spent queued behind `release_per_cycle`.
+    pub release_throttle_cycles: u64,
+    pub refill_penalty_cycles: u64,
+    pub stall_cycles_per_warp: Vec<u64>,
+    pub stall_cycles_per_barrier: BTreeMap<u32, u64>,
 }
 
 impl AddAssign<&BarrierSummary> for BarrierSummary {
@@ -72,6 +102,31 @@ impl AddAssign<&BarrierSummary> for BarrierSummary {
         self.max_scheduled_wait_cycles = self
             .max_scheduled_wait_cycles
             .max(other.max_scheduled_wait_cycles);
+        self.total_stall_cycles = self
+            .total_stall_cycles
+            .saturating_add(other.total_stall_cycles);
+        self.max_stall_cycles = self.max_stall_cycles.max(other.max_stall_cycles);
+        self.release_throttle_cycles = self
+            .release_throttle_cycles
+            .saturating_add(other.release_throttle_cycles);
+        self.refill_penalty_cycles = self
+            .refill_penalty_cycles
+            .saturating_add(other.refill_penalty_cycles);
+        if self.stall_cycles_per_warp.len() < other.stall_cycles_per_warp.len() {
+            self.stall_cycles_per_warp
+                .resize(other.stall_cycles_per_warp.len(), 0);
+        }
+        for (dst, src) in self
+            .stall_cycles_per_warp
+            .iter_mut()
+            .zip(&other.stall_cycles_per_warp)
+        {
+            *dst = dst.saturating_add(*src);
+        }
+        for (&id, &cycles) in &other.stall_cycles_per_barrier {
+            let entry = self.stall_cycles_per_barrier.entry(id).or_insert(0);
+            *entry = entry.saturating_add(cycles);
+        }
     }
 }
 
@@ -100,12 +155,23 @@ impl BarrierManager {
             server: TimedServer::new(config.queue),
             states: HashMap::with_capacity(state_capacity),
             num_warps,
+            release_per_cycle: config.release_per_cycle as usize,
+            refill_penalty: config.refill_penalty,
+            release_queue: VecDeque::new(),
+            refilling: Vec::new(),
 
             id_mask,
-            stats: BarrierSummary::default(),
+            stats: BarrierSummary {
+                stall_cycles_per_warp: vec![0; num_warps],
+                ..BarrierSummary::default()
+            },
         }
     }
 
+    /// Records `warp` arriving at `barrier_id`. Returns the cycle the barrier
+    /// releases once the last expected warp has arrived; the warps themselves
+    /// come back from `tick`, possibly later under `release_per_cycle` and
+    /// `refill_penalty`.
     pub fn arrive(&mut self, now: Cycle, warp: usize, barrier_id: u32) -> Option<Cycle> {
         if !self.enabled {
             return Some(now);
@@ -118,6 +184,7 @@ impl BarrierManager {
         let id = self.apply_id_mask(barrier_id);
         let state = self.states.entry(id).or_insert_with(|| BarrierState {
             arrived: vec![false; self.num_warps],
+            arrived_at: vec![0; self.num_warps],
             releasing: Vec::new(),
             release_at: None,
         });
@@ -126,6 +193,9 @@ impl BarrierManager {
             return Some(release_at);
         }
 
+        if !state.arrived[warp] {
+            state.arrived_at[warp] = now;
+        }
         state.arrived[warp] = true;
         let arrived_count = state.arrived.iter().filter(|&&v| v).count();
         let ready = arrived_count >= self.expected_warps;
@@ -163,21 +233,53 @@ impl BarrierManager {
         if !self.enabled {
             return None;
         }
-        let mut released = Vec::new();
+        let release_queue = &mut self.release_queue;
+        let mut release_events = 0u64;
         self.server.service_ready(now, |result| {
             let barrier_id = result.payload;
             if let Some(state) = self.states.get_mut(&barrier_id) {
                 if !state.releasing.is_empty() {
-                    released.extend(state.releasing.drain(..));
+                    release_events += 1;
+                    release_queue.extend(state.releasing.drain(..).map(|warp| ReleasedWarp {
+                        warp,
+                        barrier_id,
+                        arrived_at: state.arrived_at[warp],
+                    }));
                 }
                 state.release_at = None;
             }
         });
+        self.stats.release_events = self.stats.release_events.saturating_add(release_events);
+
+        // Every warp still queued here was held back by release bandwidth.
+        let ports = match self.release_per_cycle {
+            0 => self.release_queue.len(),
+            n => n.min(self.release_queue.len()),
+        };
+        for entry in self.release_queue.drain(..ports) {
+            self.refilling
+                .push((now.saturating_add(self.refill_penalty), entry));
+        }
+        self.stats.release_throttle_cycles = self
+            .stats
+            .release_throttle_cycles
+            .saturating_add(self.release_queue.len() as u64);
+
+        let mut released = Vec::new();
+        let mut idx = 0;
+        while idx < self.refilling.len() {
+            if self.refilling[idx].0 > now {
+                idx += 1;
+                continue;
+            }
+            let (_, entry) = self.refilling.remove(idx);
+            self.record_stall(now, entry);
+            released.push(entry.warp);
+        }
 
         if released.is_empty() {
             None
         } else {
-            self.stats.release_events = self.stats.release_events.saturating_add(1);
             self.stats.warps_released = self
                 .stats
                 .warps_released
@@ -187,6 +289,30 @@ impl BarrierManager {
         }
     }
 
+    fn record_stall(&mut self, now: Cycle, entry: ReleasedWarp) {
+        let stall = now.saturating_sub(entry.arrived_at);
+        let stats = &mut self.stats;
+        stats.total_stall_cycles = stats.total_stall_cycles.saturating_add(stall);
+        stats.max_stall_cycles = stats.max_stall_cycles.max(stall);
+        stats.refill_penalty_cycles = stats
+            .refill_penalty_cycles
+            .saturating_add(self.refill_penalty);
+        if let Some(slot) = stats.stall_cycles_per_warp.get_mut(entry.warp) {
+            *slot = slot.saturating_add(stall);
+        }
+        let per_barrier = stats
+            .stall_cycles_per_barrier
+            .entry(entry.barrier_id)
+            .or_insert(0);
+        *per_barrier = per_barrier.saturating_add(stall);
+    }
+
+    /// True while released warps are still waiting on release bandwidth or
+    /// the refill penalty.
+    pub fn has_pending_release(&self) -> bool {
+        !self.release_queue.is_empty() || !self.refilling.is_empty()
+    }
+
     pub fn is_enabled(&self) -> bool {
         self.enabled
     }
@@ -196,10 +322,13 @@ impl BarrierManager {
     }
 
     pub fn stats(&self) -> BarrierSummary {
-        self.stats
+        self.stats.clone()
     }
 
     pub fn clear_stats(&mut self) {
-        self.stats = BarrierSummary::default();
+        self.stats = BarrierSummary {
+            stall_cycles_per_warp: vec![0; self.num_warps],
+            ..BarrierSummary::default()
+        };
     }
 }
@@ -0,0 +1,248 @@
+use std::cmp::Reverse;
+
+use serde::Serialize;
+
+use crate::timeflow::graph::FlowGraph;
+
+/// Nodes and chains `BottleneckReport::format_lines` prints.
+const REPORTED_NODES: usize = 5;
+const REPORTED_CHAINS: usize = 3;
+
+/// Contention at one graph node over a run.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct NodeBottleneck {
+    pub node: String,
+    pub cycles: u64,
+    pub busy_cycles: u64,
+    /// Requests the node's server turned away with a full queue, whether
+    /// offered by a link or from outside the graph.
+    pub queue_full_rejects: u64,
+    /// Link deliveries into the node it turned away, full or busy.
+    pub backpressure: u64,
+    pub full_cycles: u64,
+    /// Config keys that size the node's queue and bandwidth.
+    pub config_keys: Vec<String>,
+}
+
+impl NodeBottleneck {
+    /// Sort key: busiest first, then most queue-full rejects, then most
+    /// backpressure.
+    fn rank(&self) -> (u64, u64, u64) {
+        (self.busy_cycles, self.queue_full_rejects, self.backpressure)
+    }
+}
+
+/// Nodes, upstream first, whose queues stayed full together.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct FullChainReport {
+    pub nodes: Vec<String>,
+    /// Longest run of consecutive cycles the chain stayed full.
+    pub cycles: u64,
+    /// Config keys of the chain's downstream node, which holds back the
+    /// rest.
+    pub config_keys: Vec<String>,
+}
+
+/// Nodes ranked by contention and the longest full-queue chains, from one
+/// or more flow graphs.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct BottleneckReport {
+    pub nodes: Vec<NodeBottleneck>,
+    pub chains: Vec<FullChainReport>,
+}
+
+impl BottleneckReport {
+    /// Collects the stats of every node in `graph` that saw any contention.
+    /// `prefix` is prepended to node names, e.g. to tell cores apart.
+    pub fn from_graph<T: Send + Sync + 'static>(graph: &FlowGraph<T>, prefix: &str) -> Self {
+        let cycles = graph.sampled_cycles();
+        let nodes = (0..graph.node_count())
+            .map(|node| {
+                let name = graph.node_name(node);
+                let activity = graph.node_activity(node);
+                NodeBottleneck {
+                    node: format!("{prefix}{name}"),
+                    cycles,
+                    busy_cycles: activity.busy_cycles,
+                    queue_full_rejects: graph
+                        .node_server_stats(node)
+                        .map_or(0, |stats| stats.queue_full_rejects),
+                    backpressure: graph.node_backpressure(node),
+                    full_cycles: activity.full_cycles,
+                    config_keys: config_keys(name),
+                }
+            })
+            .filter(|node| node.rank() != (0, 0, 0))
+            .collect();
+        let chains = graph
+            .full_chains()
+            .iter()
+            .map(|chain| FullChainReport {
+                nodes: chain
+                    .nodes
+                    .iter()
+                    .map(|&node| format!("{prefix}{}", graph.node_name(node)))
+                    .collect(),
+                cycles: chain.cycles,
+                config_keys: chain
+                    .nodes
+                    .last()
+                    .map(|&node| config_keys(graph.node_name(node)))
+                    .unwrap_or_default(),
+            })
+            .collect();
+        let mut report = Self { nodes, chains };
+        report.sort();
+        report
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty() && self.chains.is_empty()
+    }
+
+    pub fn merge(&mut self, other: &BottleneckReport) {
+        self.nodes.extend(other.nodes.iter().cloned());
+        self.chains.extend(other.chains.iter().cloned());
+        self.sort();
+    }
+
+    fn sort(&mut self) {
+        self.nodes.sort_by_key(|node| Reverse(node.rank()));
+        self.chains
+            .sort_by_key(|chain| Reverse((chain.cycles, chain.nodes.len())));
+    }
+
+    /// The "likely bottleneck" section of the end-of-run report: the top
+    /// nodes and chains, each with the config keys to tune.
+    pub fn format_lines(&self) -> Vec<String> {
+        let tune = |keys: &[String]| {
+            if keys.is_empty() {
+                String::new()
+            } else {
+                format!("; tune {}", keys.join(", "))
+            }
+        };
+        let mut lines = Vec::new();
+        for node in self.nodes.iter().take(REPORTED_NODES) {
+            let busy = 100.0 * node.busy_cycles as f64 / node.cycles.max(1) as f64;
+            lines.push(format!(
+                "{} busy {busy:.1}% of {} cycles, {} queue-full rejects, {} backpressured deliveries{}",
+                node.node,
+                node.cycles,
+                node.queue_full_rejects,
+                node.backpressure,
+                tune(&node.config_keys)
+            ));
+        }
+        for chain in self.chains.iter().take(REPORTED_CHAINS) {
+            lines.push(format!(
+                "full chain {} for {} cycles{}",
+                chain.nodes.join(" -> "),
+                chain.cycles,
+                tune(&chain.config_keys)
+            ));
+        }
+        lines
+    }
+}
+
+/// Drops the `cluster{N}_` and `core{N}_` prefixes per-cluster and per-core
+/// node names carry.
+fn strip_instance(name: &str) -> &str {
+    let mut name = name;
+    for prefix in ["cluster", "core"] {
+        if let Some((id, rest)) = name
+            .strip_prefix(prefix)
+            .and_then(|rest| rest.split_once('_'))
+        {
+            if !id.is_empty() && id.chars().all(|c| c.is_ascii_digit()) {
+                name = rest;
+            }
+        }
+    }
+    name
+}
+
+/// Config keys that set the timing of the node named `name` in a graph
+/// built by this crate; empty for nodes it does not know.
+pub fn config_keys(name: &str) -> Vec<String> {
+    let name = strip_instance(name);
+    let keys: &[&str] = match name {
+        "dram" => &["gmem.nodes.dram", "gmem.dram.channels"],
+        "coalescer" => &["gmem.nodes.coalescer"],
+        "return" => &["gmem.nodes.return_path"],
+        "l0_flush_gate" => &["gmem.nodes.l0_flush_gate"],
+        "l1_flush_gate" => &["gmem.nodes.l1_flush_gate"],
+        "xbar" => &["gmem.crossbar"],
+        "lsu_issue" => &["lsu.issue"],
+        "smem_serial" => &["smem.serial"],
+        _ => &[],
+    };
+    if !keys.is_empty() {
+        return keys.iter().map(|key| key.to_string()).collect();
+    }
+    if name.strip_prefix("dram_").is_some() {
+        return config_keys("dram");
+    }
+    if let Some((_, node)) = name
+        .strip_prefix("die")
+        .and_then(|rest| rest.split_once('_'))
+    {
+        let key = if node == "dram" {
+            "gmem.remote.memory"
+        } else {
+            "gmem.remote.link"
+        };
+        return vec![key.to_string()];
+    }
+    for class in ["global_ldq", "global_stq", "shared_ldq", "shared_stq"] {
+        if name
+            .strip_prefix("lsu_")
+            .is_some_and(|rest| rest.starts_with(class))
+        {
+            return vec![
+                format!("lsu.queues.{class}"),
+                "lsu.queues.sharing".to_string(),
+            ];
+        }
+    }
+    for (prefix, keys) in [
+        ("smem_lane_", ["smem.lane", "smem.num_lanes"]),
+        ("smem_xbar_bank_", ["smem.crossbar", "smem.num_banks"]),
+        ("smem_subbank_", ["smem.subbank", "smem.num_subbanks"]),
+        ("smem_bank_", ["smem.bank", "smem.num_banks"]),
+    ] {
+        if name.starts_with(prefix) {
+            return keys.iter().map(|key| key.to_string()).collect();
+        }
+    }
+    cache_config_keys(name).unwrap_or_default()
+}
+
+/// `l0d_{kind}`, `l1_{kind}_{bank}` and `l2_{kind}_{bank}` cache nodes.
+fn cache_config_keys(name: &str) -> Option<Vec<String>> {
+    let (level, rest) = name.split_once('_')?;
+    let index = match level {
+        "l0d" => 0,
+        "l1" => 1,
+        "l2" => 2,
+        _ => return None,
+    };
+    let kind = rest.split('_').next()?;
+    let field = match kind {
+        "tag" => "tag",
+        "data" => "data",
+        "mshr" => "mshr",
+        "refill" => "refill",
+        "wb" => "writeback",
+        _ => return None,
+    };
+    let mut keys = vec![format!("gmem.levels[{index}].{field}")];
+    if kind == "mshr" {
+        keys.push(format!("gmem.levels[{index}].mshr_capacity"));
+    }
+    if index > 0 {
+        keys.push(format!("gmem.levels[{index}].banks"));
+    }
+    Some(keys)
+}
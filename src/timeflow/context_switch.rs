@@ -0,0 +1,255 @@
+use serde::{Deserialize, Serialize};
+use std::ops::AddAssign;
+
+use crate::timeq::Cycle;
+
+/// Where swapped-out warp contexts are kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ContextBacking {
+    Smem,
+    Gmem,
+}
+
+/// Timing of the path contexts take to and from their backing store.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct ContextPathConfig {
+    /// Cycles from a transfer leaving the channel to it being done.
+    pub latency: u64,
+    pub bytes_per_cycle: u32,
+}
+
+impl Default for ContextPathConfig {
+    fn default() -> Self {
+        Self {
+            latency: 4,
+            bytes_per_cycle: 64,
+        }
+    }
+}
+
+/// Hardware warp contexts per core, for running more warps than the core
+/// has slots for. A warp needs a slot to issue; warps without one take a
+/// free slot or swap out a resident warp that has been idle for
+/// `idle_threshold` cycles. Saving and restoring a context moves
+/// `context_bytes` over one channel per core to `backing`.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct ContextSwitchConfig {
+    pub enabled: bool,
+    pub slots: usize,
+    /// Bytes saved or restored per warp; 256 regs x 16 lanes x 4 bytes by
+    /// default.
+    pub context_bytes: u32,
+    pub backing: ContextBacking,
+    pub smem: ContextPathConfig,
+    pub gmem: ContextPathConfig,
+    /// Cycles a resident warp must go without an instruction ready to issue
+    /// before its slot can be taken.
+    pub idle_threshold: u64,
+}
+
+impl Default for ContextSwitchConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            slots: 4,
+            context_bytes: 16 * 1024,
+            backing: ContextBacking::Smem,
+            smem: ContextPathConfig::default(),
+            gmem: ContextPathConfig {
+                latency: 100,
+                bytes_per_cycle: 16,
+            },
+            idle_threshold: 32,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct ContextSwitchStats {
+    /// Warps given a slot, with or without a context to restore.
+    pub switch_ins: u64,
+    pub saves: u64,
+    pub restores: u64,
+    /// Context bytes moved by saves and restores.
+    pub bytes: u64,
+    /// Cycles from a warp being given a slot to its context being in place.
+    pub overhead_cycles: u64,
+    /// Cycles the channel spent moving contexts.
+    pub channel_busy_cycles: u64,
+    /// Warp-cycles an instruction was ready to issue but its warp had no
+    /// slot, or its context was still being restored.
+    pub slot_wait_cycles: u64,
+}
+
+impl AddAssign<&ContextSwitchStats> for ContextSwitchStats {
+    fn add_assign(&mut self, other: &ContextSwitchStats) {
+        self.switch_ins = self.switch_ins.saturating_add(other.switch_ins);
+        self.saves = self.saves.saturating_add(other.saves);
+        self.restores = self.restores.saturating_add(other.restores);
+        self.bytes = self.bytes.saturating_add(other.bytes);
+        self.overhead_cycles = self.overhead_cycles.saturating_add(other.overhead_cycles);
+        self.channel_busy_cycles = self
+            .channel_busy_cycles
+            .saturating_add(other.channel_busy_cycles);
+        self.slot_wait_cycles = self.slot_wait_cycles.saturating_add(other.slot_wait_cycles);
+    }
+}
+
+/// Per-core warp slots and the channel contexts are swapped over.
+#[derive(Debug, Clone)]
+pub struct WarpContexts {
+    config: ContextSwitchConfig,
+    /// Per warp, the cycle its context is in place while it holds a slot.
+    resident: Vec<Option<Cycle>>,
+    /// Per warp, the last cycle it had an instruction ready or got a slot.
+    last_ready: Vec<Cycle>,
+    /// Per warp, whether its context was saved when it lost its slot.
+    saved: Vec<bool>,
+    channel_free_at: Cycle,
+    denied_this_cycle: bool,
+    stats: ContextSwitchStats,
+}
+
+impl WarpContexts {
+    pub fn new(config: ContextSwitchConfig, num_warps: usize) -> Self {
+        Self {
+            config,
+            resident: vec![None; num_warps],
+            last_ready: vec![0; num_warps],
+            saved: vec![false; num_warps],
+            channel_free_at: 0,
+            denied_this_cycle: false,
+            stats: ContextSwitchStats::default(),
+        }
+    }
+
+    /// Swaps the slot count and transfer timings mid-run. Warps keep their
+    /// slots until the next switch, even beyond a lower `slots`.
+    pub fn set_config(&mut self, config: ContextSwitchConfig) {
+        self.config = config;
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    /// Narrows `eligible` to warps whose context is in place at `now`, and
+    /// hands slots to eligible warps without one, lowest warp id first.
+    pub fn admit(&mut self, now: Cycle, eligible: &[bool]) -> Vec<bool> {
+        self.denied_this_cycle = false;
+        if !self.config.enabled {
+            return eligible.to_vec();
+        }
+        for (warp, _) in eligible.iter().enumerate().filter(|(_, ready)| **ready) {
+            if let Some(last) = self.last_ready.get_mut(warp) {
+                *last = now;
+            }
+        }
+        for warp in 0..eligible.len().min(self.resident.len()) {
+            if eligible[warp] && self.resident[warp].is_none() {
+                self.switch_in(now, warp, eligible);
+            }
+        }
+        let admitted: Vec<bool> = eligible
+            .iter()
+            .enumerate()
+            .map(|(warp, &ready)| {
+                ready
+                    && self
+                        .resident
+                        .get(warp)
+                        .copied()
+                        .flatten()
+                        .is_some_and(|ready_at| ready_at <= now)
+            })
+            .collect();
+        let waiting = eligible
+            .iter()
+            .zip(&admitted)
+            .filter(|(ready, admitted)| **ready && !**admitted)
+            .count() as u64;
+        self.stats.slot_wait_cycles = self.stats.slot_wait_cycles.saturating_add(waiting);
+        self.denied_this_cycle = waiting > 0;
+        admitted
+    }
+
+    /// Whether `admit` held back a warp this cycle.
+    pub fn denied_this_cycle(&self) -> bool {
+        self.denied_this_cycle
+    }
+
+    /// Frees the slot of a warp that exited; its context is gone.
+    pub fn release(&mut self, warp: usize) {
+        if let Some(slot) = self.resident.get_mut(warp) {
+            *slot = None;
+        }
+        if let Some(saved) = self.saved.get_mut(warp) {
+            *saved = false;
+        }
+    }
+
+    pub fn is_resident(&self, warp: usize) -> bool {
+        !self.config.enabled || self.resident.get(warp).is_some_and(Option::is_some)
+    }
+
+    fn switch_in(&mut self, now: Cycle, warp: usize, eligible: &[bool]) {
+        let occupied = self.resident.iter().filter(|slot| slot.is_some()).count();
+        let mut ready_at = now;
+        if occupied >= self.config.slots.max(1) {
+            let threshold = self.config.idle_threshold;
+            let victim = (0..self.resident.len())
+                .filter(|&other| {
+                    !eligible.get(other).copied().unwrap_or(false)
+                        && self.resident[other].is_some_and(|at| at <= now)
+                        && now.saturating_sub(self.last_ready[other]) >= threshold
+                })
+                .min_by_key(|&other| self.last_ready[other]);
+            let Some(victim) = victim else {
+                return;
+            };
+            self.resident[victim] = None;
+            self.saved[victim] = true;
+            self.stats.saves = self.stats.saves.saturating_add(1);
+            ready_at = ready_at.max(self.transfer(now));
+        }
+        if self.saved[warp] {
+            self.saved[warp] = false;
+            self.stats.restores = self.stats.restores.saturating_add(1);
+            ready_at = ready_at.max(self.transfer(now));
+        }
+        self.resident[warp] = Some(ready_at);
+        self.last_ready[warp] = now;
+        self.stats.switch_ins = self.stats.switch_ins.saturating_add(1);
+        self.stats.overhead_cycles = self
+            .stats
+            .overhead_cycles
+            .saturating_add(ready_at.saturating_sub(now));
+    }
+
+    /// Moves one context over the channel, after whatever it is already
+    /// moving. Returns the cycle the transfer is done.
+    fn transfer(&mut self, now: Cycle) -> Cycle {
+        let path = match self.config.backing {
+            ContextBacking::Smem => self.config.smem,
+            ContextBacking::Gmem => self.config.gmem,
+        };
+        let bytes = self.config.context_bytes as u64;
+        let busy = bytes.div_ceil(path.bytes_per_cycle.max(1) as u64);
+        let start = now.max(self.channel_free_at);
+        self.channel_free_at = start.saturating_add(busy);
+        self.stats.bytes = self.stats.bytes.saturating_add(bytes);
+        self.stats.channel_busy_cycles = self.stats.channel_busy_cycles.saturating_add(busy);
+        self.channel_free_at.saturating_add(path.latency)
+    }
+
+    pub fn stats(&self) -> ContextSwitchStats {
+        self.stats
+    }
+
+    pub fn clear_stats(&mut self) {
+        self.stats = ContextSwitchStats::default();
+    }
+}
@@ -0,0 +1,296 @@
+use serde::{Deserialize, Serialize};
+use std::ops::AddAssign;
+
+use crate::timeflow::simple_queue::SimpleTimedQueue;
+use crate::timeq::{Cycle, ServerConfig};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CopyStreamKind {
+    /// Sequential loads feeding sequential stores (memcpy-like).
+    Copy,
+    /// Sequential stores with no sequential load stream (memset-like).
+    Set,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct CopyEngineStats {
+    /// Bytes of gmem traffic that belonged to a detected copy/set stream.
+    pub eligible_bytes: u64,
+    /// Eligible bytes actually served by the copy engine.
+    pub accelerated_bytes: u64,
+    pub copy_streams: u64,
+    pub set_streams: u64,
+    pub bursts: u64,
+    pub burst_bytes: u64,
+    /// Eligible accesses sent down the normal path because the engine was
+    /// busy or full.
+    pub fallbacks: u64,
+    pub hints: u64,
+}
+
+impl AddAssign<&CopyEngineStats> for CopyEngineStats {
+    fn add_assign(&mut self, other: &CopyEngineStats) {
+        self.eligible_bytes = self.eligible_bytes.saturating_add(other.eligible_bytes);
+        self.accelerated_bytes = self
+            .accelerated_bytes
+            .saturating_add(other.accelerated_bytes);
+        self.copy_streams = self.copy_streams.saturating_add(other.copy_streams);
+        self.set_streams = self.set_streams.saturating_add(other.set_streams);
+        self.bursts = self.bursts.saturating_add(other.bursts);
+        self.burst_bytes = self.burst_bytes.saturating_add(other.burst_bytes);
+        self.fallbacks = self.fallbacks.saturating_add(other.fallbacks);
+        self.hints = self.hints.saturating_add(other.hints);
+    }
+}
+
+/// Bulk copy/set detection and the DMA-like engine that serves detected
+/// streams. Detection always runs so eligible bytes are reported even when
+/// the engine is disabled; `enabled` only controls acceleration. The
+/// flattened `queue` is the engine itself: each burst of `burst_bytes` is
+/// one request on it.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct CopyEngineConfig {
+    pub enabled: bool,
+    /// Consecutive sequential accesses a stream needs before it is treated
+    /// as a bulk copy or set.
+    pub detect_after: u32,
+    pub burst_bytes: u32,
+    /// Writing one of these CSRs marks the warp's next stream as eligible
+    /// without waiting for `detect_after` accesses.
+    pub hint_csr_addrs: Vec<u32>,
+    #[serde(flatten)]
+    pub queue: ServerConfig,
+}
+
+impl Default for CopyEngineConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            detect_after: 4,
+            burst_bytes: 256,
+            hint_csr_addrs: Vec::new(),
+            queue: ServerConfig {
+                base_latency: 16,
+                bytes_per_cycle: 128,
+                queue_capacity: 4,
+                completions_per_cycle: 1,
+                ..ServerConfig::default()
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Stream {
+    last_addr: Option<u64>,
+    next_addr: u64,
+    run: u32,
+    burst_end: u64,
+    burst_ready_at: Cycle,
+}
+
+impl Stream {
+    /// Advances the stream with an access to `[addr, addr + bytes)`. A
+    /// replayed access to the same address leaves the stream untouched.
+    fn observe(&mut self, addr: u64, bytes: u32) {
+        if self.last_addr == Some(addr) {
+            return;
+        }
+        if self.last_addr.is_some() && self.next_addr == addr {
+            self.run = self.run.saturating_add(1);
+        } else {
+            *self = Stream {
+                run: 1,
+                ..Stream::default()
+            };
+        }
+        self.last_addr = Some(addr);
+        self.next_addr = addr.saturating_add(bytes as u64);
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct WarpStreams {
+    load: Stream,
+    store: Stream,
+    hinted: bool,
+    active: Option<CopyStreamKind>,
+}
+
+#[derive(Clone)]
+pub struct CopyEngine {
+    queue: SimpleTimedQueue<()>,
+    detect_after: u32,
+    burst_bytes: u32,
+    hint_csr_addrs: Vec<u32>,
+    warps: Vec<WarpStreams>,
+    stats: CopyEngineStats,
+}
+
+impl CopyEngine {
+    pub fn new(config: CopyEngineConfig, num_warps: usize) -> Self {
+        Self {
+            queue: SimpleTimedQueue::new(config.enabled, config.queue),
+            detect_after: config.detect_after.max(1),
+            burst_bytes: config.burst_bytes.max(1),
+            hint_csr_addrs: config.hint_csr_addrs,
+            warps: vec![WarpStreams::default(); num_warps],
+            stats: CopyEngineStats::default(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.queue.is_enabled()
+    }
+
+    pub fn is_busy(&self) -> bool {
+        self.queue.is_busy()
+    }
+
+    pub fn matches_hint_csr(&self, addr: u32) -> bool {
+        self.hint_csr_addrs.contains(&addr)
+    }
+
+    pub fn hint(&mut self, warp: usize) {
+        if let Some(state) = self.warps.get_mut(warp) {
+            state.hinted = true;
+            self.stats.hints = self.stats.hints.saturating_add(1);
+        }
+    }
+
+    /// Feeds one gmem access into the warp's stream detector. `span` is the
+    /// contiguous byte range the access covers, or `None` if its lanes are
+    /// scattered, which breaks the stream. Returns the kind of bulk stream
+    /// the access belongs to, if any.
+    pub fn observe(
+        &mut self,
+        warp: usize,
+        is_load: bool,
+        span: Option<(u64, u32)>,
+    ) -> Option<CopyStreamKind> {
+        let detect_after = self.detect_after;
+        let state = self.warps.get_mut(warp)?;
+        let stream = if is_load {
+            &mut state.load
+        } else {
+            &mut state.store
+        };
+        let Some((addr, bytes)) = span else {
+            *stream = Stream::default();
+            state.hinted = false;
+            state.active = None;
+            return None;
+        };
+        stream.observe(addr, bytes);
+
+        let threshold = if state.hinted { 1 } else { detect_after };
+        let loads_streaming = state.load.run >= threshold;
+        let stores_streaming = state.store.run >= threshold;
+        let kind = if is_load {
+            // A load stream alone is just a sequential read; it only counts
+            // once matching stores show up.
+            (loads_streaming && stores_streaming).then_some(CopyStreamKind::Copy)
+        } else if stores_streaming {
+            if loads_streaming {
+                Some(CopyStreamKind::Copy)
+            } else if state.load.run <= 1 {
+                Some(CopyStreamKind::Set)
+            } else {
+                None
+            }
+        } else {
+            None
+        };
+
+        if kind.is_some() && kind != state.active {
+            match kind {
+                Some(CopyStreamKind::Copy) => {
+                    self.stats.copy_streams = self.stats.copy_streams.saturating_add(1)
+                }
+                Some(CopyStreamKind::Set) => {
+                    self.stats.set_streams = self.stats.set_streams.saturating_add(1)
+                }
+                None => {}
+            }
+        }
+        if !is_load || kind.is_some() {
+            state.active = kind;
+        }
+        kind
+    }
+
+    /// Serves an eligible access from the engine. Accesses that fall inside
+    /// the stream's current burst complete with it; otherwise a new burst
+    /// starting at the access is issued. Returns the completion cycle, or
+    /// `None` if the engine is disabled or cannot take the burst.
+    pub fn try_accelerate(
+        &mut self,
+        now: Cycle,
+        warp: usize,
+        is_load: bool,
+        addr: u64,
+        bytes: u32,
+    ) -> Option<Cycle> {
+        if !self.queue.is_enabled() {
+            return None;
+        }
+        let burst_bytes = self.burst_bytes.max(bytes);
+        let state = self.warps.get_mut(warp)?;
+        let stream = if is_load {
+            &mut state.load
+        } else {
+            &mut state.store
+        };
+        let end = addr.saturating_add(bytes as u64);
+        if stream.burst_end > addr && end <= stream.burst_end {
+            return Some(stream.burst_ready_at.max(now));
+        }
+        match self.queue.try_issue(now, (), burst_bytes) {
+            Ok(ticket) => {
+                stream.burst_end = addr.saturating_add(burst_bytes as u64);
+                stream.burst_ready_at = ticket.ready_at();
+                self.stats.bursts = self.stats.bursts.saturating_add(1);
+                self.stats.burst_bytes = self.stats.burst_bytes.saturating_add(burst_bytes as u64);
+                Some(ticket.ready_at())
+            }
+            Err(_) => {
+                self.stats.fallbacks = self.stats.fallbacks.saturating_add(1);
+                None
+            }
+        }
+    }
+
+    /// Accounts an accepted eligible access.
+    pub fn record(&mut self, bytes: u32, accelerated: bool) {
+        self.stats.eligible_bytes = self.stats.eligible_bytes.saturating_add(bytes as u64);
+        if accelerated {
+            self.stats.accelerated_bytes =
+                self.stats.accelerated_bytes.saturating_add(bytes as u64);
+        }
+    }
+
+    pub fn tick(&mut self, now: Cycle) {
+        self.queue.tick(now, |_| {});
+    }
+
+    pub fn stats(&self) -> CopyEngineStats {
+        self.stats
+    }
+
+    pub fn clear_stats(&mut self) {
+        self.stats = CopyEngineStats::default();
+    }
+}
+
+/// Byte range covered by a set of lane addresses when they form one
+/// contiguous run of `bytes_per_lane`-sized elements.
+pub fn contiguous_span(lane_addrs: &[u64], bytes_per_lane: u32) -> Option<(u64, u32)> {
+    let mut addrs = lane_addrs.to_vec();
+    addrs.sort_unstable();
+    addrs.dedup();
+    let first = *addrs.first()?;
+    let step = bytes_per_lane.max(1) as u64;
+    let contiguous = addrs.windows(2).all(|pair| pair[1] - pair[0] == step);
+    contiguous.then(|| (first, (addrs.len() as u64 * step) as u32))
+}
@@ -1,24 +1,40 @@
+use crate::sim::checkpoint::Shared;
 use crate::sim::perf_log::PerfLogSession;
 use crate::timeflow::{
     barrier::BarrierConfig,
+    bottleneck::BottleneckReport,
+    context_switch::ContextSwitchConfig,
+    copy_engine::{CopyEngine, CopyEngineConfig, CopyEngineStats, CopyStreamKind},
+    crossbar::CrossbarPortStats,
+    div_sqrt::{DivSqrtOp, DivSqrtStats},
     dma::{DmaConfig, DmaQueue, DmaReject},
     execute::{ExecUnitKind, ExecutePipeline, ExecutePipelineConfig},
     fence::{FenceConfig, FenceIssue, FenceQueue, FenceReject, FenceRequest},
     gmem::{
-        ClusterGmemGraph, GmemCompletion, GmemFlowConfig, GmemIssue, GmemReject, GmemRequest,
-        GmemStats,
+        CacheBankActivity, CacheHintStats, ClusterGmemGraph, DieTrafficStats, DramChannelStats,
+        DramShareStats, FillForwardStats, GmemCompletion, GmemFlowConfig, GmemIssue, GmemReject,
+        GmemRequest, GmemStats, InjectorStats, MemoryMapConfig, MemoryRegionStats,
+        RefillContention, TrafficAgent, WriteCombiningStats,
     },
-    graph::FlowGraph,
+    graph::{AdaptiveRouteStats, FlowGraph},
+    ibuffer::IbufferConfig,
     icache::{
         IcacheFlowConfig, IcacheIssue, IcacheReject, IcacheRequest, IcacheStats, IcacheSubgraph,
     },
     lsu::{LsuCompletion, LsuFlowConfig, LsuIssue, LsuPayload, LsuReject, LsuStats, LsuSubgraph},
+    neutrino::{
+        NeutrinoIssue, NeutrinoOpKind, NeutrinoQueue, NeutrinoReject, NeutrinoStats,
+        NeutrinoTimingConfig,
+    },
     operand_fetch::{OperandFetchConfig, OperandFetchQueue, OperandFetchReject},
+    perf_counters::PerfCounterConfig,
+    power::PowerConfig,
     smem::{
         SmemCompletion, SmemFlowConfig, SmemIssue, SmemReject, SmemRequest, SmemStats,
         SmemSubgraph, SmemUtilSample,
     },
     tensor::{TensorConfig, TensorQueue, TensorReject},
+    tick_order::{TickOrderConfig, TickStage, TickUnit},
     types::CoreFlowPayload,
     warp_scheduler::WarpSchedulerConfig,
     writeback::{
@@ -28,10 +44,11 @@ use crate::timeflow::{
 };
 use crate::timeq::{Backpressure, Cycle, Ticket};
 use serde::Deserialize;
-use std::sync::{Arc, RwLock};
+use std::sync::{Arc, Mutex};
 
 #[derive(Debug, Clone, Default, Deserialize)]
 #[serde(default)]
+#[non_exhaustive]
 pub struct CoreGraphConfig {
     #[serde(flatten)]
     pub memory: MemoryConfig,
@@ -39,6 +56,7 @@ pub struct CoreGraphConfig {
     pub compute: ComputeConfig,
     #[serde(flatten)]
     pub io: IoConfig,
+    pub tick_order: TickOrderConfig,
 }
 
 #[derive(Debug, Clone, Default, Deserialize)]
@@ -50,14 +68,21 @@ pub struct MemoryConfig {
     pub icache: IcacheFlowConfig,
     pub writeback: WritebackConfig,
     pub operand_fetch: OperandFetchConfig,
+    /// Address regions with their own timing, from `[[memory.regions]]`.
+    #[serde(rename = "memory")]
+    pub map: MemoryMapConfig,
 }
 
 #[derive(Debug, Clone, Default, Deserialize)]
 #[serde(default)]
 pub struct ComputeConfig {
     pub tensor: TensorConfig,
+    pub ibuffer: IbufferConfig,
     pub scheduler: WarpSchedulerConfig,
     pub execute: ExecutePipelineConfig,
+    pub power: PowerConfig,
+    pub context_switch: ContextSwitchConfig,
+    pub perf_counters: PerfCounterConfig,
 }
 
 #[derive(Debug, Clone, Default, Deserialize)]
@@ -66,8 +91,24 @@ pub struct IoConfig {
     pub barrier: BarrierConfig,
     pub fence: FenceConfig,
     pub dma: DmaConfig,
+    pub neutrino: NeutrinoTimingConfig,
+    pub copy_engine: CopyEngineConfig,
+}
+
+/// Shared rather than boxed so a checkpointed copy of the graph keeps
+/// calling the embedder's hooks.
+type CompletionHook<T> = Arc<Mutex<dyn FnMut(&T) + Send>>;
+
+/// Callbacks embedders register on a `CoreGraph`; see
+/// `CoreGraph::on_gmem_completion`.
+#[derive(Clone, Default)]
+struct CompletionHooks {
+    gmem: Vec<CompletionHook<GmemCompletion>>,
+    smem: Vec<CompletionHook<SmemCompletion>>,
+    lsu: Vec<CompletionHook<LsuCompletion<LsuPayload>>>,
 }
 
+#[derive(Clone)]
 pub struct CoreGraph {
     pub(crate) graph: FlowGraph<CoreFlowPayload>,
     subgraphs: Vec<CoreSubgraph>,
@@ -80,7 +121,11 @@ pub struct CoreGraph {
     execute_index: usize,
     writeback_index: usize,
     fence_index: usize,
-    cluster_gmem: Option<Arc<RwLock<ClusterGmemGraph>>>,
+    neutrino_index: usize,
+    copy_engine_index: usize,
+    cluster_gmem: Option<Shared<ClusterGmemGraph>>,
+    tick_order: TickOrderConfig,
+    hooks: CompletionHooks,
 }
 
 // Small macro to implement repeated indexed accessors for subgraphs
@@ -114,7 +159,7 @@ impl CoreGraph {
     pub fn new(
         config: CoreGraphConfig,
         num_warps: usize,
-        cluster_gmem: Option<Arc<RwLock<ClusterGmemGraph>>>,
+        cluster_gmem: Option<Shared<ClusterGmemGraph>>,
         perf_log_session: Option<Arc<PerfLogSession>>,
     ) -> Self {
         let mut graph = FlowGraph::new();
@@ -131,7 +176,9 @@ impl CoreGraph {
         let execute = ExecutePipeline::new(config.compute.execute);
         let writeback = WritebackQueue::new(config.memory.writeback);
         let fence = FenceQueue::new(config.io.fence);
-        let mut subgraphs = Vec::with_capacity(10);
+        let neutrino = NeutrinoQueue::new(config.io.neutrino);
+        let copy_engine = CopyEngine::new(config.io.copy_engine, num_warps);
+        let mut subgraphs = Vec::with_capacity(12);
         let smem_index = subgraphs.len();
         subgraphs.push(CoreSubgraph::Smem(smem));
         let icache_index = subgraphs.len();
@@ -150,6 +197,10 @@ impl CoreGraph {
         subgraphs.push(CoreSubgraph::Writeback(writeback));
         let fence_index = subgraphs.len();
         subgraphs.push(CoreSubgraph::Fence(fence));
+        let neutrino_index = subgraphs.len();
+        subgraphs.push(CoreSubgraph::Neutrino(neutrino));
+        let copy_engine_index = subgraphs.len();
+        subgraphs.push(CoreSubgraph::CopyEngine(copy_engine));
         let cluster_gmem = cluster_gmem;
 
         Self {
@@ -164,7 +215,11 @@ impl CoreGraph {
             execute_index,
             writeback_index,
             fence_index,
+            neutrino_index,
+            copy_engine_index,
             cluster_gmem,
+            tick_order: config.tick_order,
+            hooks: CompletionHooks::default(),
         }
     }
 
@@ -176,40 +231,122 @@ impl CoreGraph {
         self.with_smem_mut(|smem, graph| smem.issue(graph, now, request))
     }
 
+    /// See `FlowGraph::set_stats`. The shared cluster gmem graph is left
+    /// to its owner.
+    pub fn set_stats(&mut self, enabled: bool) {
+        self.graph.set_stats(enabled);
+    }
+
     pub fn tick_front(&mut self, now: Cycle) {
-        for subgraph in &mut self.subgraphs {
-            subgraph.tick_phase(TickPhase::Front, now);
-        }
-        if let Some(cluster) = &mut self.cluster_gmem {
-            cluster.write().unwrap().tick(now);
-        }
+        self.tick_stage(TickStage::Front, now);
     }
 
     pub fn tick_graph(&mut self, now: Cycle) {
-        self.graph.tick(now);
-        for subgraph in &mut self.subgraphs {
-            subgraph.collect_completions(&mut self.graph, now);
-        }
+        self.tick_stage(TickStage::Graph, now);
     }
 
     pub fn tick_back(&mut self, now: Cycle) {
-        for subgraph in &mut self.subgraphs {
-            subgraph.tick_phase(TickPhase::Back, now);
+        self.tick_stage(TickStage::Back, now);
+    }
+
+    /// Ticks the units `tick_order` puts in `stage`, in order.
+    fn tick_stage(&mut self, stage: TickStage, now: Cycle) {
+        for idx in 0..self.tick_order.stage(stage).len() {
+            let unit = self.tick_order.stage(stage)[idx];
+            self.tick_unit(unit, now);
         }
     }
 
-    pub fn collect_cluster_gmem_completions(&self, core_id: usize) -> Vec<GmemCompletion> {
+    fn tick_unit(&mut self, unit: TickUnit, now: Cycle) {
+        let index = match unit {
+            TickUnit::Icache => self.icache_index,
+            TickUnit::Lsu => self.lsu_index,
+            TickUnit::OperandFetch => self.operand_fetch_index,
+            TickUnit::Dma => self.dma_index,
+            TickUnit::Tensor => self.tensor_index,
+            TickUnit::Execute => self.execute_index,
+            TickUnit::Neutrino => self.neutrino_index,
+            TickUnit::CopyEngine => self.copy_engine_index,
+            TickUnit::Writeback => self.writeback_index,
+            TickUnit::Fence => self.fence_index,
+            TickUnit::ClusterGmem => {
+                if let Some(cluster) = &mut self.cluster_gmem {
+                    cluster.write().unwrap().tick(now);
+                }
+                return;
+            }
+            TickUnit::Graph => {
+                self.graph.tick(now);
+                return;
+            }
+            TickUnit::GraphCompletions => {
+                let before = self.smem_ref().completions.len();
+                for subgraph in &mut self.subgraphs {
+                    subgraph.collect_completions(&mut self.graph, now);
+                }
+                if let CoreSubgraph::Smem(smem) = &self.subgraphs[self.smem_index] {
+                    for completion in smem.completions.iter().skip(before) {
+                        for hook in &self.hooks.smem {
+                            (hook.lock().unwrap())(completion);
+                        }
+                    }
+                }
+                return;
+            }
+        };
+        self.subgraphs[index].advance(now);
+    }
+
+    pub fn collect_cluster_gmem_completions(&mut self, core_id: usize) -> Vec<GmemCompletion> {
         let Some(cluster) = &self.cluster_gmem else {
             return Vec::new();
         };
         let mut completions = Vec::new();
         let mut handle = cluster.write().unwrap();
         while let Some(completion) = handle.pop_completion(core_id) {
+            for hook in &self.hooks.gmem {
+                (hook.lock().unwrap())(&completion);
+            }
             completions.push(completion);
         }
         completions
     }
 
+    /// Calls `hook` on every gmem completion as
+    /// `collect_cluster_gmem_completions` takes it from the cluster graph,
+    /// before the caller sees it. Loads the LSU forwards from a store never
+    /// reach the cluster graph and are not seen.
+    pub fn on_gmem_completion(
+        &mut self,
+        hook: impl FnMut(&GmemCompletion) + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.hooks.gmem.push(Arc::new(Mutex::new(hook)));
+        self
+    }
+
+    /// Calls `hook` on every smem completion as the graph collects it, in
+    /// the tick that queues it for `pop_smem_completion`.
+    pub fn on_smem_completion(
+        &mut self,
+        hook: impl FnMut(&SmemCompletion) + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.hooks.smem.push(Arc::new(Mutex::new(hook)));
+        self
+    }
+
+    /// Calls `hook` on every request `lsu_take_ready` takes out of the LSU.
+    pub fn on_lsu_completion(
+        &mut self,
+        hook: impl FnMut(&LsuCompletion<LsuPayload>) + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.hooks.lsu.push(Arc::new(Mutex::new(hook)));
+        self
+    }
+
+    pub fn clear_completion_hooks(&mut self) {
+        self.hooks = CompletionHooks::default();
+    }
+
     pub fn pop_smem_completion(&mut self) -> Option<SmemCompletion> {
         self.smem_mut().completions.pop_front()
     }
@@ -285,7 +422,24 @@ impl CoreGraph {
     }
 
     pub fn dma_try_issue(&mut self, now: Cycle, bytes: u32) -> Result<Ticket, DmaReject> {
-        self.with_dma_mut(|dma| dma.try_issue(now, bytes))
+        let ticket = self.with_dma_mut(|dma| dma.try_issue(now, bytes))?;
+        self.cluster_gmem_submit_background(TrafficAgent::Dma, bytes);
+        Ok(ticket)
+    }
+
+    pub fn dma_try_issue_with_id(
+        &mut self,
+        now: Cycle,
+        id: u64,
+        bytes: u32,
+    ) -> Result<Ticket, DmaReject> {
+        let ticket = self.with_dma_mut(|dma| dma.try_issue_with_id(now, id, bytes))?;
+        self.cluster_gmem_submit_background(TrafficAgent::Dma, bytes);
+        Ok(ticket)
+    }
+
+    pub fn dma_take_finished(&mut self) -> Vec<u64> {
+        self.with_dma_mut(|dma| dma.take_finished())
     }
 
     pub fn dma_is_busy(&self) -> bool {
@@ -304,6 +458,10 @@ impl CoreGraph {
         self.dma_ref().completed()
     }
 
+    pub fn clear_dma_stats(&mut self) {
+        self.with_dma_mut(|dma| dma.clear_stats());
+    }
+
     pub fn dma_matches_mmio(&self, addr: u64) -> bool {
         self.dma_ref().matches_mmio(addr)
     }
@@ -313,7 +471,24 @@ impl CoreGraph {
     }
 
     pub fn tensor_try_issue(&mut self, now: Cycle, bytes: u32) -> Result<Ticket, TensorReject> {
-        self.with_tensor_mut(|tensor| tensor.try_issue(now, bytes))
+        let ticket = self.with_tensor_mut(|tensor| tensor.try_issue(now, bytes))?;
+        self.cluster_gmem_submit_background(TrafficAgent::Tensor, bytes);
+        Ok(ticket)
+    }
+
+    pub fn tensor_try_issue_with_id(
+        &mut self,
+        now: Cycle,
+        id: u64,
+        bytes: u32,
+    ) -> Result<Ticket, TensorReject> {
+        let ticket = self.with_tensor_mut(|tensor| tensor.try_issue_with_id(now, id, bytes))?;
+        self.cluster_gmem_submit_background(TrafficAgent::Tensor, bytes);
+        Ok(ticket)
+    }
+
+    pub fn tensor_take_finished(&mut self) -> Vec<(u64, u32)> {
+        self.with_tensor_mut(|tensor| tensor.take_finished())
     }
 
     pub fn tensor_is_busy(&self) -> bool {
@@ -332,6 +507,10 @@ impl CoreGraph {
         self.tensor_ref().completed()
     }
 
+    pub fn clear_tensor_stats(&mut self) {
+        self.with_tensor_mut(|tensor| tensor.clear_stats());
+    }
+
     pub fn tensor_matches_mmio(&self, addr: u64) -> bool {
         self.tensor_ref().matches_mmio(addr)
     }
@@ -340,6 +519,89 @@ impl CoreGraph {
         self.tensor_ref().matches_csr(addr)
     }
 
+    pub fn neutrino_try_issue(
+        &mut self,
+        now: Cycle,
+        kind: NeutrinoOpKind,
+        active_lanes: u32,
+    ) -> Result<NeutrinoIssue, NeutrinoReject> {
+        self.with_neutrino_mut(|nu| nu.try_issue(now, kind, active_lanes))
+    }
+
+    pub fn neutrino_is_enabled(&self) -> bool {
+        self.neutrino_ref().is_enabled()
+    }
+
+    pub fn neutrino_is_busy(&self) -> bool {
+        self.neutrino_ref().is_busy()
+    }
+
+    pub fn neutrino_inflight(&self) -> usize {
+        self.neutrino_ref().inflight()
+    }
+
+    pub fn neutrino_stats(&self) -> NeutrinoStats {
+        match self.subgraphs[self.neutrino_index].stats_snapshot() {
+            Some(StatEnum::Neutrino(stats)) => stats,
+            _ => unreachable!("neutrino index always points to neutrino"),
+        }
+    }
+
+    pub fn clear_neutrino_stats(&mut self) {
+        self.subgraphs[self.neutrino_index].clear_stats();
+    }
+
+    pub fn copy_engine_is_enabled(&self) -> bool {
+        self.copy_engine_ref().is_enabled()
+    }
+
+    pub fn copy_engine_is_busy(&self) -> bool {
+        self.copy_engine_ref().is_busy()
+    }
+
+    pub fn copy_engine_matches_hint_csr(&self, addr: u32) -> bool {
+        self.copy_engine_ref().matches_hint_csr(addr)
+    }
+
+    pub fn copy_engine_hint(&mut self, warp: usize) {
+        self.with_copy_engine_mut(|engine| engine.hint(warp));
+    }
+
+    pub fn copy_engine_observe(
+        &mut self,
+        warp: usize,
+        is_load: bool,
+        span: Option<(u64, u32)>,
+    ) -> Option<CopyStreamKind> {
+        self.with_copy_engine_mut(|engine| engine.observe(warp, is_load, span))
+    }
+
+    pub fn copy_engine_try_accelerate(
+        &mut self,
+        now: Cycle,
+        warp: usize,
+        is_load: bool,
+        addr: u64,
+        bytes: u32,
+    ) -> Option<Cycle> {
+        self.with_copy_engine_mut(|engine| engine.try_accelerate(now, warp, is_load, addr, bytes))
+    }
+
+    pub fn copy_engine_record(&mut self, bytes: u32, accelerated: bool) {
+        self.with_copy_engine_mut(|engine| engine.record(bytes, accelerated));
+    }
+
+    pub fn copy_engine_stats(&self) -> CopyEngineStats {
+        match self.subgraphs[self.copy_engine_index].stats_snapshot() {
+            Some(StatEnum::CopyEngine(stats)) => stats,
+            _ => unreachable!("copy_engine index always points to copy_engine"),
+        }
+    }
+
+    pub fn clear_copy_engine_stats(&mut self) {
+        self.subgraphs[self.copy_engine_index].clear_stats();
+    }
+
     pub fn execute_issue(
         &mut self,
         now: Cycle,
@@ -349,6 +611,28 @@ impl CoreGraph {
         self.with_execute_mut(|exec| exec.issue(now, kind, active_lanes))
     }
 
+    pub fn execute_issue_div_sqrt(
+        &mut self,
+        now: Cycle,
+        warp: usize,
+        op: DivSqrtOp,
+        active_lanes: u32,
+    ) -> Result<Ticket, Backpressure<()>> {
+        self.with_execute_mut(|exec| exec.issue_div_sqrt(now, warp, op, active_lanes))
+    }
+
+    pub fn div_sqrt_enabled(&self) -> bool {
+        self.execute_ref().div_sqrt_enabled()
+    }
+
+    pub fn div_sqrt_stats(&self) -> DivSqrtStats {
+        self.execute_ref().div_sqrt_stats()
+    }
+
+    pub fn clear_execute_stats(&mut self) {
+        self.execute_mut().clear_stats();
+    }
+
     pub fn execute_is_busy(&self, kind: ExecUnitKind) -> bool {
         self.execute_ref().is_busy(kind)
     }
@@ -380,12 +664,42 @@ impl CoreGraph {
             .unwrap_or_default()
     }
 
+    pub fn cluster_gmem_region_stats(&self, core_id: usize) -> Vec<MemoryRegionStats> {
+        self.cluster_gmem
+            .as_ref()
+            .map(|cluster| cluster.read().unwrap().region_stats(core_id))
+            .unwrap_or_default()
+    }
+
+    pub fn cluster_gmem_crossbar_stats(&self, core_id: usize) -> CrossbarPortStats {
+        self.cluster_gmem
+            .as_ref()
+            .map(|cluster| cluster.read().unwrap().crossbar_stats(core_id))
+            .unwrap_or_default()
+    }
+
     pub fn cluster_gmem_clear_stats(&self, core_id: usize) {
         if let Some(cluster) = &self.cluster_gmem {
             cluster.write().unwrap().clear_stats(core_id);
         }
     }
 
+    pub fn execute_set_config(&mut self, config: &ExecutePipelineConfig) {
+        self.execute_mut().set_config(config);
+    }
+
+    pub fn cluster_gmem_set_timing(&self, config: &GmemFlowConfig) {
+        if let Some(cluster) = &self.cluster_gmem {
+            cluster.write().unwrap().set_timing(config);
+        }
+    }
+
+    pub fn cluster_gmem_clear_hierarchy_stats(&self) {
+        if let Some(cluster) = &self.cluster_gmem {
+            cluster.write().unwrap().clear_hierarchy_stats();
+        }
+    }
+
     pub fn cluster_gmem_hierarchy_stats_per_level(&self) -> (GmemStats, GmemStats, GmemStats) {
         self.cluster_gmem
             .as_ref()
@@ -393,6 +707,100 @@ impl CoreGraph {
             .unwrap_or_default()
     }
 
+    /// Charges a DMA or tensor transfer against the shared DRAM bandwidth,
+    /// if `gmem.background` is enabled.
+    pub fn cluster_gmem_submit_background(&self, agent: TrafficAgent, bytes: u32) -> bool {
+        self.cluster_gmem
+            .as_ref()
+            .is_some_and(|cluster| cluster.write().unwrap().submit_background(agent, bytes))
+    }
+
+    pub fn cluster_gmem_dram_share_stats(&self) -> DramShareStats {
+        self.cluster_gmem
+            .as_ref()
+            .map(|cluster| cluster.read().unwrap().dram_share_stats())
+            .unwrap_or_default()
+    }
+
+    pub fn cluster_gmem_dram_channel_stats(&self) -> Vec<DramChannelStats> {
+        self.cluster_gmem
+            .as_ref()
+            .map(|cluster| cluster.read().unwrap().dram_channel_stats())
+            .unwrap_or_default()
+    }
+
+    pub fn cluster_gmem_die_traffic_stats(&self) -> Vec<DieTrafficStats> {
+        self.cluster_gmem
+            .as_ref()
+            .map(|cluster| cluster.read().unwrap().die_traffic_stats())
+            .unwrap_or_default()
+    }
+
+    /// Contention in this core's own graph and its LSU, with `prefix` on
+    /// node names. The shared gmem graph is reported by
+    /// `cluster_gmem_bottlenecks`.
+    pub fn bottlenecks(&self, prefix: &str) -> BottleneckReport {
+        let mut report = BottleneckReport::from_graph(&self.graph, prefix);
+        report.merge(&self.lsu_ref().bottlenecks(prefix));
+        report
+    }
+
+    pub fn cluster_gmem_bottlenecks(&self) -> BottleneckReport {
+        self.cluster_gmem
+            .as_ref()
+            .map(|cluster| cluster.read().unwrap().bottlenecks())
+            .unwrap_or_default()
+    }
+
+    pub fn cluster_gmem_cache_bank_activity(&self) -> Vec<CacheBankActivity> {
+        self.cluster_gmem
+            .as_ref()
+            .map(|cluster| cluster.read().unwrap().cache_bank_activity())
+            .unwrap_or_default()
+    }
+
+    pub fn cluster_gmem_cache_hint_stats(&self) -> CacheHintStats {
+        self.cluster_gmem
+            .as_ref()
+            .map(|cluster| cluster.read().unwrap().cache_hint_stats())
+            .unwrap_or_default()
+    }
+
+    pub fn cluster_gmem_injector_stats(&self) -> InjectorStats {
+        self.cluster_gmem
+            .as_ref()
+            .map(|cluster| cluster.read().unwrap().injector_stats())
+            .unwrap_or_default()
+    }
+
+    pub fn cluster_gmem_fill_forward_stats(&self) -> FillForwardStats {
+        self.cluster_gmem
+            .as_ref()
+            .map(|cluster| cluster.read().unwrap().fill_forward_stats())
+            .unwrap_or_default()
+    }
+
+    pub fn cluster_gmem_write_combining_stats(&self) -> WriteCombiningStats {
+        self.cluster_gmem
+            .as_ref()
+            .map(|cluster| cluster.read().unwrap().write_combining_stats())
+            .unwrap_or_default()
+    }
+
+    pub fn cluster_gmem_adaptive_route_stats(&self) -> AdaptiveRouteStats {
+        self.cluster_gmem
+            .as_ref()
+            .map(|cluster| cluster.read().unwrap().adaptive_route_stats())
+            .unwrap_or_default()
+    }
+
+    pub fn cluster_gmem_refill_contention(&self) -> RefillContention {
+        self.cluster_gmem
+            .as_ref()
+            .map(|cluster| cluster.read().unwrap().refill_contention())
+            .unwrap_or_default()
+    }
+
     pub fn lsu_issue_gmem(
         &mut self,
         now: Cycle,
@@ -414,13 +822,37 @@ impl CoreGraph {
     }
 
     pub fn lsu_take_ready(&mut self, now: Cycle) -> Option<LsuCompletion<LsuPayload>> {
-        self.with_lsu_mut(|lsu| lsu.take_ready(now))
+        let completion = self.with_lsu_mut(|lsu| lsu.take_ready(now))?;
+        for hook in &self.hooks.lsu {
+            (hook.lock().unwrap())(&completion);
+        }
+        Some(completion)
+    }
+
+    pub fn lsu_can_forward(&self, payload: &LsuPayload) -> bool {
+        self.lsu_ref().can_forward(payload)
+    }
+
+    pub fn lsu_take_forwarded(&mut self, now: Cycle) -> Option<LsuPayload> {
+        self.with_lsu_mut(|lsu| lsu.take_forwarded(now))
     }
 
     pub fn lsu_release_issue_resources(&mut self, payload: &LsuPayload) {
         self.with_lsu_mut(|lsu| lsu.release_issue_resources(payload));
     }
 
+    pub fn lsu_note_miss(&mut self, warp: usize) {
+        self.with_lsu_mut(|lsu| lsu.note_miss(warp));
+    }
+
+    pub fn lsu_release_miss(&mut self, warp: usize) {
+        self.with_lsu_mut(|lsu| lsu.release_miss(warp));
+    }
+
+    pub fn lsu_outstanding_misses(&self, warp: usize) -> usize {
+        self.lsu_ref().outstanding_misses(warp)
+    }
+
     pub fn lsu_can_reserve_load_data(&self, payload: &LsuPayload) -> bool {
         self.lsu_ref().can_reserve_load_data(payload)
     }
@@ -561,8 +993,27 @@ impl CoreGraph {
         FenceQueue,
         "fence index always points to fence"
     );
+    impl_indexed_accessor!(
+        neutrino_ref,
+        neutrino_mut,
+        with_neutrino_mut,
+        neutrino_index,
+        CoreSubgraph::Neutrino,
+        NeutrinoQueue,
+        "neutrino index always points to neutrino"
+    );
+    impl_indexed_accessor!(
+        copy_engine_ref,
+        copy_engine_mut,
+        with_copy_engine_mut,
+        copy_engine_index,
+        CoreSubgraph::CopyEngine,
+        CopyEngine,
+        "copy_engine index always points to copy_engine"
+    );
 }
 
+#[derive(Clone)]
 enum CoreSubgraph {
     Smem(SmemSubgraph),
     Icache(IcacheSubgraph),
@@ -573,6 +1024,8 @@ enum CoreSubgraph {
     Execute(ExecutePipeline),
     Writeback(WritebackQueue),
     Fence(FenceQueue),
+    Neutrino(NeutrinoQueue),
+    CopyEngine(CopyEngine),
 }
 
 enum StatEnum {
@@ -580,16 +1033,13 @@ enum StatEnum {
     Icache(IcacheStats),
     Lsu(LsuStats),
     Writeback(WritebackStats),
-}
-
-#[derive(Debug, Clone, Copy)]
-enum TickPhase {
-    Front,
-    Back,
+    Neutrino(NeutrinoStats),
+    CopyEngine(CopyEngineStats),
 }
 
 trait Subgraph {
-    fn tick_phase(&mut self, phase: TickPhase, now: Cycle);
+    /// Advances the subgraph one cycle, at the point `tick_order` puts it.
+    fn advance(&mut self, _now: Cycle) {}
     fn collect_completions(&mut self, graph: &mut FlowGraph<CoreFlowPayload>, now: Cycle);
     fn stats_snapshot(&self) -> Option<StatEnum> {
         None
@@ -598,8 +1048,6 @@ trait Subgraph {
 }
 
 impl Subgraph for SmemSubgraph {
-    fn tick_phase(&mut self, _phase: TickPhase, _now: Cycle) {}
-
     fn collect_completions(&mut self, graph: &mut FlowGraph<CoreFlowPayload>, now: Cycle) {
         SmemSubgraph::collect_completions(self, graph, now);
     }
@@ -614,10 +1062,8 @@ impl Subgraph for SmemSubgraph {
 }
 
 impl Subgraph for IcacheSubgraph {
-    fn tick_phase(&mut self, phase: TickPhase, now: Cycle) {
-        if matches!(phase, TickPhase::Front) {
-            self.tick(now);
-        }
+    fn advance(&mut self, now: Cycle) {
+        self.tick(now);
     }
 
     fn collect_completions(&mut self, _graph: &mut FlowGraph<CoreFlowPayload>, _now: Cycle) {}
@@ -632,10 +1078,8 @@ impl Subgraph for IcacheSubgraph {
 }
 
 impl Subgraph for LsuSubgraph {
-    fn tick_phase(&mut self, phase: TickPhase, now: Cycle) {
-        if matches!(phase, TickPhase::Front) {
-            self.tick(now);
-        }
+    fn advance(&mut self, now: Cycle) {
+        self.tick(now);
     }
 
     fn collect_completions(&mut self, _graph: &mut FlowGraph<CoreFlowPayload>, _now: Cycle) {}
@@ -650,50 +1094,40 @@ impl Subgraph for LsuSubgraph {
 }
 
 impl Subgraph for OperandFetchQueue {
-    fn tick_phase(&mut self, phase: TickPhase, now: Cycle) {
-        if matches!(phase, TickPhase::Front) {
-            self.tick(now, |_| {});
-        }
+    fn advance(&mut self, now: Cycle) {
+        self.tick(now, |_| {});
     }
 
     fn collect_completions(&mut self, _graph: &mut FlowGraph<CoreFlowPayload>, _now: Cycle) {}
 }
 
 impl Subgraph for DmaQueue {
-    fn tick_phase(&mut self, phase: TickPhase, now: Cycle) {
-        if matches!(phase, TickPhase::Front) {
-            self.tick(now);
-        }
+    fn advance(&mut self, now: Cycle) {
+        self.tick(now);
     }
 
     fn collect_completions(&mut self, _graph: &mut FlowGraph<CoreFlowPayload>, _now: Cycle) {}
 }
 
 impl Subgraph for TensorQueue {
-    fn tick_phase(&mut self, phase: TickPhase, now: Cycle) {
-        if matches!(phase, TickPhase::Front) {
-            self.tick(now);
-        }
+    fn advance(&mut self, now: Cycle) {
+        self.tick(now);
     }
 
     fn collect_completions(&mut self, _graph: &mut FlowGraph<CoreFlowPayload>, _now: Cycle) {}
 }
 
 impl Subgraph for ExecutePipeline {
-    fn tick_phase(&mut self, phase: TickPhase, now: Cycle) {
-        if matches!(phase, TickPhase::Front) {
-            self.tick(now);
-        }
+    fn advance(&mut self, now: Cycle) {
+        self.tick(now);
     }
 
     fn collect_completions(&mut self, _graph: &mut FlowGraph<CoreFlowPayload>, _now: Cycle) {}
 }
 
 impl Subgraph for WritebackQueue {
-    fn tick_phase(&mut self, phase: TickPhase, now: Cycle) {
-        if matches!(phase, TickPhase::Back) {
-            self.tick(now);
-        }
+    fn advance(&mut self, now: Cycle) {
+        self.tick(now);
     }
 
     fn collect_completions(&mut self, _graph: &mut FlowGraph<CoreFlowPayload>, _now: Cycle) {}
@@ -708,27 +1142,59 @@ impl Subgraph for WritebackQueue {
 }
 
 impl Subgraph for FenceQueue {
-    fn tick_phase(&mut self, phase: TickPhase, now: Cycle) {
-        if matches!(phase, TickPhase::Back) {
-            self.tick(now);
-        }
+    fn advance(&mut self, now: Cycle) {
+        self.tick(now);
+    }
+
+    fn collect_completions(&mut self, _graph: &mut FlowGraph<CoreFlowPayload>, _now: Cycle) {}
+}
+
+impl Subgraph for NeutrinoQueue {
+    fn advance(&mut self, now: Cycle) {
+        self.tick(now);
+    }
+
+    fn collect_completions(&mut self, _graph: &mut FlowGraph<CoreFlowPayload>, _now: Cycle) {}
+
+    fn stats_snapshot(&self) -> Option<StatEnum> {
+        Some(StatEnum::Neutrino(self.stats()))
+    }
+
+    fn clear_stats(&mut self) {
+        NeutrinoQueue::clear_stats(self);
+    }
+}
+
+impl Subgraph for CopyEngine {
+    fn advance(&mut self, now: Cycle) {
+        self.tick(now);
     }
 
     fn collect_completions(&mut self, _graph: &mut FlowGraph<CoreFlowPayload>, _now: Cycle) {}
+
+    fn stats_snapshot(&self) -> Option<StatEnum> {
+        Some(StatEnum::CopyEngine(self.stats()))
+    }
+
+    fn clear_stats(&mut self) {
+        CopyEngine::clear_stats(self);
+    }
 }
 
 impl Subgraph for CoreSubgraph {
-    fn tick_phase(&mut self, phase: TickPhase, now: Cycle) {
+    fn advance(&mut self, now: Cycle) {
         match self {
-            CoreSubgraph::Smem(smem) => smem.tick_phase(phase, now),
-            CoreSubgraph::Icache(icache) => icache.tick_phase(phase, now),
-            CoreSubgraph::Lsu(lsu) => lsu.tick_phase(phase, now),
-            CoreSubgraph::OperandFetch(q) => q.tick_phase(phase, now),
-            CoreSubgraph::Dma(dma) => dma.tick_phase(phase, now),
-            CoreSubgraph::Tensor(tensor) => tensor.tick_phase(phase, now),
-            CoreSubgraph::Execute(exec) => exec.tick_phase(phase, now),
-            CoreSubgraph::Writeback(wb) => wb.tick_phase(phase, now),
-            CoreSubgraph::Fence(fence) => fence.tick_phase(phase, now),
+            CoreSubgraph::Smem(smem) => smem.advance(now),
+            CoreSubgraph::Icache(icache) => icache.advance(now),
+            CoreSubgraph::Lsu(lsu) => lsu.advance(now),
+            CoreSubgraph::OperandFetch(q) => q.advance(now),
+            CoreSubgraph::Dma(dma) => dma.advance(now),
+            CoreSubgraph::Tensor(tensor) => tensor.advance(now),
+            CoreSubgraph::Execute(exec) => exec.advance(now),
+            CoreSubgraph::Writeback(wb) => wb.advance(now),
+            CoreSubgraph::Fence(fence) => fence.advance(now),
+            CoreSubgraph::Neutrino(nu) => nu.advance(now),
+            CoreSubgraph::CopyEngine(engine) => engine.advance(now),
         }
     }
 
@@ -743,6 +1209,8 @@ impl Subgraph for CoreSubgraph {
             CoreSubgraph::Execute(exec) => exec.collect_completions(graph, now),
             CoreSubgraph::Writeback(wb) => wb.collect_completions(graph, now),
             CoreSubgraph::Fence(fence) => fence.collect_completions(graph, now),
+            CoreSubgraph::Neutrino(nu) => nu.collect_completions(graph, now),
+            CoreSubgraph::CopyEngine(engine) => engine.collect_completions(graph, now),
         }
     }
 
@@ -752,6 +1220,8 @@ impl Subgraph for CoreSubgraph {
             CoreSubgraph::Icache(icache) => icache.stats_snapshot(),
             CoreSubgraph::Lsu(lsu) => lsu.stats_snapshot(),
             CoreSubgraph::Writeback(wb) => wb.stats_snapshot(),
+            CoreSubgraph::Neutrino(nu) => nu.stats_snapshot(),
+            CoreSubgraph::CopyEngine(engine) => engine.stats_snapshot(),
             _ => None,
         }
     }
@@ -762,6 +1232,8 @@ impl Subgraph for CoreSubgraph {
             CoreSubgraph::Lsu(lsu) => lsu.clear_stats(),
             CoreSubgraph::Icache(icache) => icache.clear_stats(),
             CoreSubgraph::Writeback(wb) => wb.clear_stats(),
+            CoreSubgraph::Neutrino(nu) => nu.clear_stats(),
+            CoreSubgraph::CopyEngine(engine) => engine.clear_stats(),
             _ => {}
         }
     }
@@ -0,0 +1,258 @@
+use std::collections::VecDeque;
+use std::ops::AddAssign;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::sim::checkpoint::Shared;
+use crate::timeflow::graph::TimedNode;
+use crate::timeq::{Backpressure, Cycle, ServiceRequest, ServiceResult, Ticket};
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct CrossbarConfig {
+    pub enabled: bool,
+    /// Bytes each input port can move per cycle.
+    pub port_bytes_per_cycle: u32,
+    /// Bytes each output bank can take per cycle.
+    pub bank_bytes_per_cycle: u32,
+    /// Cycles from winning arbitration to arriving at the output.
+    pub latency: Cycle,
+    /// Requests buffered per input port while waiting for arbitration.
+    pub port_queue_capacity: usize,
+}
+
+impl Default for CrossbarConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port_bytes_per_cycle: 64,
+            bank_bytes_per_cycle: 64,
+            latency: 1,
+            port_queue_capacity: 4,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct CrossbarPortStats {
+    pub grants: u64,
+    pub bytes: u64,
+    /// Cycles the port's head request waited on a bank that was busy or
+    /// granted to another port.
+    pub conflict_cycles: u64,
+    /// Cycles the port's head request waited on the port's own bandwidth.
+    pub port_busy_cycles: u64,
+    pub queue_full_rejects: u64,
+}
+
+impl AddAssign<&CrossbarPortStats> for CrossbarPortStats {
+    fn add_assign(&mut self, other: &CrossbarPortStats) {
+        self.grants = self.grants.saturating_add(other.grants);
+        self.bytes = self.bytes.saturating_add(other.bytes);
+        self.conflict_cycles = self.conflict_cycles.saturating_add(other.conflict_cycles);
+        self.port_busy_cycles = self.port_busy_cycles.saturating_add(other.port_busy_cycles);
+        self.queue_full_rejects = self
+            .queue_full_rejects
+            .saturating_add(other.queue_full_rejects);
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CrossbarStats {
+    pub ports: Vec<CrossbarPortStats>,
+    pub bank_grants: Vec<u64>,
+}
+
+impl CrossbarStats {
+    pub fn clear(&mut self) {
+        self.ports.fill(CrossbarPortStats::default());
+        self.bank_grants.fill(0);
+    }
+}
+
+/// Stats of a `CrossbarNode` once it has been boxed into a graph.
+pub type CrossbarStatsHandle = Shared<CrossbarStats>;
+
+type SelectFn<T> = Arc<dyn Fn(&T) -> Option<usize> + Send + Sync>;
+
+#[derive(Clone)]
+struct Queued<T> {
+    payload: T,
+    size_bytes: u32,
+    issued_at: Cycle,
+}
+
+/// Input ports arbitrated onto output banks. Each cycle every port offers
+/// its oldest request; ports are visited round-robin and a request is
+/// granted if neither its port nor its bank is still busy moving an earlier
+/// request, so two ports targeting one bank in the same cycle serialize.
+/// Requests with no bank only contend for their port.
+#[derive(Clone)]
+pub struct CrossbarNode<T> {
+    name: Arc<str>,
+    config: CrossbarConfig,
+    port_of: SelectFn<T>,
+    bank_of: SelectFn<T>,
+    inputs: Vec<VecDeque<Queued<T>>>,
+    port_free_at: Vec<Cycle>,
+    bank_free_at: Vec<Cycle>,
+    next_port: usize,
+    output: VecDeque<ServiceResult<T>>,
+    stats: CrossbarStatsHandle,
+}
+
+impl<T> CrossbarNode<T> {
+    /// `port_of` picks the input port of a request (out-of-range ports use
+    /// the last one); `bank_of` its output bank, or `None` if it targets no
+    /// bank.
+    pub fn new(
+        name: impl Into<Arc<str>>,
+        config: CrossbarConfig,
+        ports: usize,
+        banks: usize,
+        port_of: impl Fn(&T) -> Option<usize> + Send + Sync + 'static,
+        bank_of: impl Fn(&T) -> Option<usize> + Send + Sync + 'static,
+    ) -> Self {
+        let ports = ports.max(1);
+        let banks = banks.max(1);
+        Self {
+            name: name.into(),
+            config,
+            port_of: Arc::new(port_of),
+            bank_of: Arc::new(bank_of),
+            inputs: (0..ports).map(|_| VecDeque::new()).collect(),
+            port_free_at: vec![0; ports],
+            bank_free_at: vec![0; banks],
+            next_port: 0,
+            output: VecDeque::new(),
+            stats: Shared::new(CrossbarStats {
+                ports: vec![CrossbarPortStats::default(); ports],
+                bank_grants: vec![0; banks],
+            }),
+        }
+    }
+
+    pub fn stats_handle(&self) -> CrossbarStatsHandle {
+        self.stats.clone()
+    }
+
+    fn port(&self, payload: &T) -> usize {
+        (self.port_of)(payload)
+            .unwrap_or(0)
+            .min(self.inputs.len() - 1)
+    }
+
+    fn busy_cycles(bytes: u32, bytes_per_cycle: u32) -> Cycle {
+        bytes.div_ceil(bytes_per_cycle.max(1)).max(1) as Cycle
+    }
+}
+
+impl<T: Clone + Send + Sync + 'static> TimedNode<T> for CrossbarNode<T> {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn try_put(
+        &mut self,
+        now: Cycle,
+        request: ServiceRequest<T>,
+    ) -> Result<Ticket, Backpressure<T>> {
+        let port = self.port(&request.payload);
+        let capacity = self.config.port_queue_capacity.max(1);
+        if self.inputs[port].len() >= capacity {
+            let mut stats = self.stats.write().expect("crossbar stats lock poisoned");
+            stats.ports[port].queue_full_rejects =
+                stats.ports[port].queue_full_rejects.saturating_add(1);
+            return Err(Backpressure::QueueFull { request, capacity });
+        }
+        // Arbitration happens from the next tick at the earliest.
+        let ready_at = now.saturating_add(1).saturating_add(self.config.latency);
+        self.inputs[port].push_back(Queued {
+            payload: request.payload,
+            size_bytes: request.size_bytes,
+            issued_at: now,
+        });
+        Ok(Ticket::new(now, ready_at, request.size_bytes))
+    }
+
+    fn tick(&mut self, now: Cycle) {
+        let ports = self.inputs.len();
+        let mut stats = self.stats.write().expect("crossbar stats lock poisoned");
+        for offset in 0..ports {
+            let port = (self.next_port + offset) % ports;
+            let Some(head) = self.inputs[port].front() else {
+                continue;
+            };
+            if self.port_free_at[port] > now {
+                stats.ports[port].port_busy_cycles =
+                    stats.ports[port].port_busy_cycles.saturating_add(1);
+                continue;
+            }
+            let bank = (self.bank_of)(&head.payload).map(|bank| bank % self.bank_free_at.len());
+            if bank.is_some_and(|bank| self.bank_free_at[bank] > now) {
+                stats.ports[port].conflict_cycles =
+                    stats.ports[port].conflict_cycles.saturating_add(1);
+                continue;
+            }
+            let Some(granted) = self.inputs[port].pop_front() else {
+                continue;
+            };
+            let bytes = granted.size_bytes;
+            self.port_free_at[port] =
+                now.saturating_add(Self::busy_cycles(bytes, self.config.port_bytes_per_cycle));
+            if let Some(bank) = bank {
+                self.bank_free_at[bank] =
+                    now.saturating_add(Self::busy_cycles(bytes, self.config.bank_bytes_per_cycle));
+                stats.bank_grants[bank] = stats.bank_grants[bank].saturating_add(1);
+            }
+            let port_stats = &mut stats.ports[port];
+            port_stats.grants = port_stats.grants.saturating_add(1);
+            port_stats.bytes = port_stats.bytes.saturating_add(bytes as u64);
+            self.output.push_back(ServiceResult {
+                payload: granted.payload,
+                ticket: Ticket::new(
+                    granted.issued_at,
+                    now.saturating_add(self.config.latency),
+                    bytes,
+                ),
+            });
+        }
+        self.next_port = (self.next_port + 1) % ports;
+    }
+
+    fn peek_ready(&mut self, now: Cycle) -> Option<&ServiceResult<T>> {
+        self.output
+            .front()
+            .filter(|result| result.ticket.is_ready(now))
+    }
+
+    fn take_ready(&mut self, now: Cycle) -> Option<ServiceResult<T>> {
+        if self.peek_ready(now).is_some() {
+            self.output.pop_front()
+        } else {
+            None
+        }
+    }
+
+    fn outstanding(&self) -> usize {
+        self.inputs.iter().map(VecDeque::len).sum::<usize>() + self.output.len()
+    }
+
+    fn squash(&mut self, pred: &mut dyn FnMut(&T, &Ticket) -> bool) -> usize {
+        let mut squashed = 0;
+        for queue in &mut self.inputs {
+            let before = queue.len();
+            queue.retain(|queued| {
+                let ticket = Ticket::new(queued.issued_at, queued.issued_at, queued.size_bytes);
+                !pred(&queued.payload, &ticket)
+            });
+            squashed += before - queue.len();
+        }
+        squashed
+    }
+
+    fn clone_box(&self) -> Box<dyn TimedNode<T>> {
+        Box::new(self.clone())
+    }
+}
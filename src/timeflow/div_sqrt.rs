@@ -0,0 +1,279 @@
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::ops::AddAssign;
+
+use crate::timeq::{Backpressure, Cycle, ServiceRequest, Ticket};
+
+/// Instructions the div/sqrt unit runs, each for its own number of cycles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DivSqrtOp {
+    /// Integer divide and remainder.
+    IntDiv,
+    FpDiv,
+    FpSqrt,
+}
+
+/// `[execute.div_sqrt]`: iterative dividers shared by the warps of a core.
+/// Unlike the pipelined units, a divider works on one instruction at a
+/// time, for all of its cycles.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct DivSqrtConfig {
+    /// Dividers. 0 sends integer divides to `int_div` and fp divides and
+    /// square roots to `fp`, pipelined like any other op.
+    pub units: usize,
+    pub int_div_cycles: u64,
+    pub fp_div_cycles: u64,
+    pub fp_sqrt_cycles: u64,
+    /// Instructions of one warp that may be waiting for or using a
+    /// divider; the next one is turned away, so a divide-heavy warp cannot
+    /// take every divider slot from the others.
+    pub queue_per_warp: usize,
+}
+
+impl Default for DivSqrtConfig {
+    fn default() -> Self {
+        Self {
+            units: 1,
+            int_div_cycles: 32,
+            fp_div_cycles: 16,
+            fp_sqrt_cycles: 16,
+            queue_per_warp: 2,
+        }
+    }
+}
+
+impl DivSqrtConfig {
+    pub fn enabled(&self) -> bool {
+        self.units > 0
+    }
+
+    pub fn cycles(&self, op: DivSqrtOp) -> u64 {
+        match op {
+            DivSqrtOp::IntDiv => self.int_div_cycles,
+            DivSqrtOp::FpDiv => self.fp_div_cycles,
+            DivSqrtOp::FpSqrt => self.fp_sqrt_cycles,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct DivSqrtStats {
+    pub int_divs: u64,
+    pub fp_divs: u64,
+    pub fp_sqrts: u64,
+    /// Issues turned away because their warp's queue was full.
+    pub queue_full_rejects: u64,
+    /// Cycles instructions waited for a free divider.
+    pub wait_cycles: u64,
+    pub max_wait: u64,
+    /// Busy dividers, summed over `cycles`.
+    pub busy_sum: u64,
+    /// Instructions waiting for a divider, summed over `cycles`.
+    pub backlog_sum: u64,
+    pub peak_backlog: u64,
+    pub cycles: u64,
+    pub units: u64,
+}
+
+impl DivSqrtStats {
+    pub fn utilization(&self) -> f64 {
+        let capacity = self.cycles.saturating_mul(self.units);
+        if capacity == 0 {
+            0.0
+        } else {
+            self.busy_sum as f64 / capacity as f64
+        }
+    }
+
+    pub fn avg_backlog(&self) -> f64 {
+        if self.cycles == 0 {
+            0.0
+        } else {
+            self.backlog_sum as f64 / self.cycles as f64
+        }
+    }
+}
+
+impl AddAssign<&DivSqrtStats> for DivSqrtStats {
+    fn add_assign(&mut self, other: &DivSqrtStats) {
+        self.int_divs = self.int_divs.saturating_add(other.int_divs);
+        self.fp_divs = self.fp_divs.saturating_add(other.fp_divs);
+        self.fp_sqrts = self.fp_sqrts.saturating_add(other.fp_sqrts);
+        self.queue_full_rejects = self
+            .queue_full_rejects
+            .saturating_add(other.queue_full_rejects);
+        self.wait_cycles = self.wait_cycles.saturating_add(other.wait_cycles);
+        self.max_wait = self.max_wait.max(other.max_wait);
+        self.busy_sum = self.busy_sum.saturating_add(other.busy_sum);
+        self.backlog_sum = self.backlog_sum.saturating_add(other.backlog_sum);
+        self.peak_backlog = self.peak_backlog.max(other.peak_backlog);
+        self.cycles = self.cycles.saturating_add(other.cycles);
+        self.units = self.units.saturating_add(other.units);
+    }
+}
+
+/// The dividers and the per-warp queues in front of them. An instruction
+/// is given the divider that frees up first when it issues, so its result
+/// cycle is known right away.
+#[derive(Clone)]
+pub struct DivSqrtUnit {
+    config: DivSqrtConfig,
+    /// Cycle each divider finishes the last instruction it was given.
+    free_at: Vec<Cycle>,
+    /// Start and ready cycles of each warp's instructions that are not
+    /// done yet, oldest first.
+    queues: Vec<VecDeque<(Cycle, Cycle)>>,
+    now: Cycle,
+    stats: DivSqrtStats,
+}
+
+impl DivSqrtUnit {
+    pub fn new(config: DivSqrtConfig) -> Self {
+        Self {
+            free_at: vec![0; config.units],
+            queues: Vec::new(),
+            now: 0,
+            stats: DivSqrtStats::default(),
+            config,
+        }
+    }
+
+    pub fn config(&self) -> &DivSqrtConfig {
+        &self.config
+    }
+
+    /// Resizes the unit; instructions already given a divider keep it.
+    pub fn set_config(&mut self, config: DivSqrtConfig) {
+        self.free_at.resize(config.units, 0);
+        self.config = config;
+    }
+
+    pub fn issue(
+        &mut self,
+        now: Cycle,
+        warp: usize,
+        op: DivSqrtOp,
+        active_lanes: u32,
+    ) -> Result<Ticket, Backpressure<()>> {
+        let size_bytes = active_lanes.max(1);
+        if warp >= self.queues.len() {
+            self.queues.resize_with(warp + 1, VecDeque::new);
+        }
+        let queue = &mut self.queues[warp];
+        while queue.front().is_some_and(|&(_, ready_at)| ready_at <= now) {
+            queue.pop_front();
+        }
+        let (divider, free_at) = match self.free_at.iter().enumerate().min_by_key(|&(_, &at)| at) {
+            Some((divider, &free_at)) if queue.len() < self.config.queue_per_warp => {
+                (divider, free_at)
+            }
+            _ => {
+                self.stats.queue_full_rejects = self.stats.queue_full_rejects.saturating_add(1);
+                let available_at = queue.front().map_or(now.saturating_add(1), |&(_, at)| at);
+                return Err(Backpressure::Busy {
+                    request: ServiceRequest::new((), size_bytes),
+                    available_at,
+                });
+            }
+        };
+        let start = free_at.max(now);
+        let ready_at = start.saturating_add(self.config.cycles(op).max(1));
+        self.free_at[divider] = ready_at;
+        queue.push_back((start, ready_at));
+
+        let count = match op {
+            DivSqrtOp::IntDiv => &mut self.stats.int_divs,
+            DivSqrtOp::FpDiv => &mut self.stats.fp_divs,
+            DivSqrtOp::FpSqrt => &mut self.stats.fp_sqrts,
+        };
+        *count = count.saturating_add(1);
+        let wait = start - now;
+        self.stats.wait_cycles = self.stats.wait_cycles.saturating_add(wait);
+        self.stats.max_wait = self.stats.max_wait.max(wait);
+        Ok(Ticket::new(now, ready_at, size_bytes))
+    }
+
+    pub fn tick(&mut self, now: Cycle) {
+        self.now = now;
+        if !self.config.enabled() {
+            return;
+        }
+        let busy = self.free_at.iter().filter(|&&at| at > now).count() as u64;
+        let backlog = self
+            .queues
+            .iter()
+            .flatten()
+            .filter(|&&(start, _)| start > now)
+            .count() as u64;
+        self.stats.busy_sum = self.stats.busy_sum.saturating_add(busy);
+        self.stats.backlog_sum = self.stats.backlog_sum.saturating_add(backlog);
+        self.stats.peak_backlog = self.stats.peak_backlog.max(backlog);
+        self.stats.cycles = self.stats.cycles.saturating_add(1);
+    }
+
+    /// Whether any divider was working at the last `tick`.
+    pub fn is_busy(&self) -> bool {
+        self.free_at.iter().any(|&at| at > self.now)
+    }
+
+    /// When the next divider frees up.
+    pub fn suggest_retry(&self) -> Cycle {
+        self.free_at.iter().copied().min().unwrap_or(self.now)
+    }
+
+    pub fn stats(&self) -> DivSqrtStats {
+        DivSqrtStats {
+            units: self.config.units as u64,
+            ..self.stats
+        }
+    }
+
+    pub fn clear_stats(&mut self) {
+        self.stats = DivSqrtStats::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn one_divider_serializes_instructions() {
+        let mut unit = DivSqrtUnit::new(DivSqrtConfig {
+            units: 1,
+            fp_div_cycles: 10,
+            queue_per_warp: 4,
+            ..DivSqrtConfig::default()
+        });
+        let first = unit.issue(0, 0, DivSqrtOp::FpDiv, 32).unwrap();
+        let second = unit.issue(0, 1, DivSqrtOp::FpDiv, 32).unwrap();
+        assert_eq!(first.ready_at(), 10);
+        assert_eq!(second.ready_at(), 20);
+        unit.tick(5);
+        assert!(unit.is_busy());
+        let stats = unit.stats();
+        assert_eq!(stats.fp_divs, 2);
+        assert_eq!(stats.wait_cycles, 10);
+        assert_eq!(stats.peak_backlog, 1);
+    }
+
+    #[test]
+    fn full_warp_queue_leaves_room_for_other_warps() {
+        let mut unit = DivSqrtUnit::new(DivSqrtConfig {
+            units: 2,
+            int_div_cycles: 8,
+            queue_per_warp: 1,
+            ..DivSqrtConfig::default()
+        });
+        unit.issue(0, 0, DivSqrtOp::IntDiv, 32).unwrap();
+        match unit.issue(1, 0, DivSqrtOp::IntDiv, 32) {
+            Err(Backpressure::Busy { available_at, .. }) => assert_eq!(available_at, 8),
+            other => panic!("expected the warp's queue to be full, got {other:?}"),
+        }
+        let other_warp = unit.issue(1, 1, DivSqrtOp::IntDiv, 32).unwrap();
+        assert_eq!(other_warp.ready_at(), 9);
+        assert!(unit.issue(8, 0, DivSqrtOp::IntDiv, 32).is_ok());
+        assert_eq!(unit.stats().queue_full_rejects, 1);
+    }
+}
@@ -35,8 +35,12 @@ impl Default for DmaConfig {
     }
 }
 
+#[derive(Clone)]
 pub struct DmaQueue {
-    queue: SimpleTimedQueue<()>,
+    /// Payload: the id given to `try_issue_with_id`, 0 if none.
+    queue: SimpleTimedQueue<u64>,
+    /// Nonzero ids of completed jobs, until `take_finished`.
+    finished: Vec<u64>,
     completed: u64,
     mmio_base: u64,
     mmio_size: u64,
@@ -49,6 +53,7 @@ impl DmaQueue {
     pub fn new(config: DmaConfig) -> Self {
         Self {
             queue: SimpleTimedQueue::new(config.enabled, config.queue),
+            finished: Vec::new(),
             completed: 0,
             mmio_base: config.mmio_base,
             mmio_size: config.mmio_size,
@@ -59,9 +64,24 @@ impl DmaQueue {
     }
 
     pub fn try_issue(&mut self, now: Cycle, bytes: u32) -> Result<Ticket, DmaReject> {
-        let res = self.queue.try_issue(now, (), bytes);
+        self.try_issue_with_id(now, 0, bytes)
+    }
+
+    /// Like `try_issue`, but reports a nonzero `id` through `take_finished`
+    /// once the job completes.
+    pub fn try_issue_with_id(
+        &mut self,
+        now: Cycle,
+        id: u64,
+        bytes: u32,
+    ) -> Result<Ticket, DmaReject> {
+        let res = self.queue.try_issue(now, id, bytes);
         if res.is_ok() {
             self.bytes_issued = self.bytes_issued.saturating_add(bytes as u64);
+            // A disabled queue finishes jobs as soon as they issue.
+            if id != 0 && !self.queue.is_enabled() {
+                self.finished.push(id);
+            }
         }
         match res {
             Ok(ticket) => Ok(ticket),
@@ -72,12 +92,19 @@ impl DmaQueue {
     pub fn tick(&mut self, now: Cycle) {
         self.queue.tick_with_service_result(now, |result| {
             self.completed = self.completed.saturating_add(1);
+            if result.payload != 0 {
+                self.finished.push(result.payload);
+            }
             self.bytes_completed = self
                 .bytes_completed
                 .saturating_add(result.ticket.size_bytes() as u64);
         });
     }
 
+    pub fn take_finished(&mut self) -> Vec<u64> {
+        std::mem::take(&mut self.finished)
+    }
+
     pub fn bytes_issued(&self) -> u64 {
         self.bytes_issued
     }
@@ -94,6 +121,12 @@ impl DmaQueue {
         self.completed
     }
 
+    pub fn clear_stats(&mut self) {
+        self.completed = 0;
+        self.bytes_issued = 0;
+        self.bytes_completed = 0;
+    }
+
     pub fn matches_mmio(&self, addr: u64) -> bool {
         if !self.queue.is_enabled() || self.mmio_size == 0 {
             return false;
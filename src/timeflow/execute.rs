@@ -1,5 +1,8 @@
+use crate::timeflow::div_sqrt::{DivSqrtConfig, DivSqrtOp, DivSqrtStats, DivSqrtUnit};
+use crate::timeflow::forwarding::ForwardingConfig;
 use crate::timeq::{Backpressure, Cycle, ServerConfig, ServiceRequest, Ticket, TimedServer};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use std::ops::AddAssign;
 
 #[derive(Debug, Clone, Deserialize)]
 #[serde(default)]
@@ -9,6 +12,11 @@ pub struct ExecutePipelineConfig {
     pub int_div: ServerConfig,
     pub fp: ServerConfig,
     pub sfu: ServerConfig,
+    /// Lane crossbar for vote and shuffle ops.
+    pub shuffle: ServerConfig,
+    pub div_sqrt: DivSqrtConfig,
+    pub reservation_stations: ReservationStationConfig,
+    pub forwarding: ForwardingConfig,
 }
 
 impl Default for ExecutePipelineConfig {
@@ -49,25 +57,218 @@ impl Default for ExecutePipelineConfig {
                 completions_per_cycle: 1,
                 warmup_latency: 0,
             },
+            shuffle: ServerConfig {
+                base_latency: 2,
+                bytes_per_cycle: 16,
+                queue_capacity: 2,
+                completions_per_cycle: 1,
+                warmup_latency: 0,
+            },
+            div_sqrt: DivSqrtConfig::default(),
+            reservation_stations: ReservationStationConfig::default(),
+            forwarding: ForwardingConfig::default(),
+        }
+    }
+}
+
+/// Reservation-station entries per execute unit; 0 leaves the unit without
+/// one. A unit with entries issues without holding its warp until the
+/// result is ready, and an instruction whose operands are still being
+/// produced waits in an entry instead of blocking its warp's ibuffer.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(default)]
+pub struct ReservationStationConfig {
+    pub alu: usize,
+    pub int_mul: usize,
+    pub int_div: usize,
+    pub fp: usize,
+    pub sfu: usize,
+    pub shuffle: usize,
+    pub div_sqrt: usize,
+}
+
+impl ReservationStationConfig {
+    pub fn entries(&self, kind: ExecUnitKind) -> usize {
+        match kind {
+            ExecUnitKind::Int => self.alu,
+            ExecUnitKind::IntMul => self.int_mul,
+            ExecUnitKind::IntDiv => self.int_div,
+            ExecUnitKind::Fp => self.fp,
+            ExecUnitKind::Sfu => self.sfu,
+            ExecUnitKind::Shuffle => self.shuffle,
+            ExecUnitKind::DivSqrt => self.div_sqrt,
+        }
+    }
+
+    pub fn any_enabled(&self) -> bool {
+        self.alu + self.int_mul + self.int_div + self.fp + self.sfu + self.shuffle + self.div_sqrt
+            > 0
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct ReservationStationStats {
+    /// Instructions that waited on operands in an entry rather than in the
+    /// ibuffer.
+    pub allocations: u64,
+    /// Operand waits served by a reservation station, in cycles; the
+    /// ibuffer head-of-line blocking they avoided.
+    pub avoided_wait_cycles: u64,
+    /// Instructions that had to wait on operands in the ibuffer.
+    pub operand_stalls: u64,
+    pub operand_stall_cycles: u64,
+    /// Of `operand_stalls`, those whose unit's entries were all taken.
+    pub full_stalls: u64,
+    pub occupancy_sum: u64,
+    pub peak_occupancy: u64,
+    pub cycles: u64,
+}
+
+impl ReservationStationStats {
+    pub fn avg_occupancy(&self) -> f64 {
+        if self.cycles == 0 {
+            0.0
+        } else {
+            self.occupancy_sum as f64 / self.cycles as f64
+        }
+    }
+}
+
+impl AddAssign<&ReservationStationStats> for ReservationStationStats {
+    fn add_assign(&mut self, other: &ReservationStationStats) {
+        self.allocations = self.allocations.saturating_add(other.allocations);
+        self.avoided_wait_cycles = self
+            .avoided_wait_cycles
+            .saturating_add(other.avoided_wait_cycles);
+        self.operand_stalls = self.operand_stalls.saturating_add(other.operand_stalls);
+        self.operand_stall_cycles = self
+            .operand_stall_cycles
+            .saturating_add(other.operand_stall_cycles);
+        self.full_stalls = self.full_stalls.saturating_add(other.full_stalls);
+        self.occupancy_sum = self.occupancy_sum.saturating_add(other.occupancy_sum);
+        self.peak_occupancy = self.peak_occupancy.max(other.peak_occupancy);
+        self.cycles = self.cycles.saturating_add(other.cycles);
+    }
+}
+
+/// Per-unit reservation stations. An entry is held from the cycle its
+/// instruction issues until the cycle its operands are ready and it
+/// dispatches to the unit.
+#[derive(Clone)]
+pub struct ReservationStations {
+    config: ReservationStationConfig,
+    /// Dispatch cycle of each occupied entry, indexed by unit.
+    held: [Vec<Cycle>; 7],
+    stats: ReservationStationStats,
+}
+
+impl ReservationStations {
+    pub fn new(config: ReservationStationConfig) -> Self {
+        Self {
+            config,
+            held: Default::default(),
+            stats: ReservationStationStats::default(),
+        }
+    }
+
+    fn unit_index(kind: ExecUnitKind) -> usize {
+        match kind {
+            ExecUnitKind::Int => 0,
+            ExecUnitKind::IntMul => 1,
+            ExecUnitKind::IntDiv => 2,
+            ExecUnitKind::Fp => 3,
+            ExecUnitKind::Sfu => 4,
+            ExecUnitKind::Shuffle => 5,
+            ExecUnitKind::DivSqrt => 6,
         }
     }
+
+    pub fn enabled(&self, kind: ExecUnitKind) -> bool {
+        self.config.entries(kind) > 0
+    }
+
+    pub fn any_enabled(&self) -> bool {
+        self.config.any_enabled()
+    }
+
+    /// Resizes the stations; entries already held stay until they dispatch.
+    pub fn set_config(&mut self, config: ReservationStationConfig) {
+        self.config = config;
+    }
+
+    pub fn has_free(&mut self, now: Cycle, kind: ExecUnitKind) -> bool {
+        let held = &mut self.held[Self::unit_index(kind)];
+        held.retain(|&dispatch_at| dispatch_at > now);
+        held.len() < self.config.entries(kind)
+    }
+
+    /// Takes an entry until `dispatch_at`; callers check `has_free` first.
+    pub fn allocate(&mut self, now: Cycle, kind: ExecUnitKind, dispatch_at: Cycle) {
+        self.held[Self::unit_index(kind)].push(dispatch_at);
+        self.stats.allocations = self.stats.allocations.saturating_add(1);
+        self.stats.avoided_wait_cycles = self
+            .stats
+            .avoided_wait_cycles
+            .saturating_add(dispatch_at.saturating_sub(now));
+    }
+
+    pub fn record_operand_stall(&mut self, wait_cycles: Cycle, full: bool) {
+        self.stats.operand_stalls = self.stats.operand_stalls.saturating_add(1);
+        self.stats.operand_stall_cycles =
+            self.stats.operand_stall_cycles.saturating_add(wait_cycles);
+        if full {
+            self.stats.full_stalls = self.stats.full_stalls.saturating_add(1);
+        }
+    }
+
+    pub fn sample(&mut self, now: Cycle) {
+        let occupied = self
+            .held
+            .iter()
+            .map(|held| {
+                held.iter()
+                    .filter(|&&dispatch_at| dispatch_at > now)
+                    .count()
+            })
+            .sum::<usize>() as u64;
+        self.stats.occupancy_sum = self.stats.occupancy_sum.saturating_add(occupied);
+        self.stats.peak_occupancy = self.stats.peak_occupancy.max(occupied);
+        self.stats.cycles = self.stats.cycles.saturating_add(1);
+    }
+
+    pub fn stats(&self) -> ReservationStationStats {
+        self.stats
+    }
+
+    pub fn clear_stats(&mut self) {
+        self.stats = ReservationStationStats::default();
+    }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum ExecUnitKind {
+    #[serde(rename = "alu")]
     Int,
     IntMul,
     IntDiv,
     Fp,
     Sfu,
+    Shuffle,
+    /// Integer divide and remainder, fp divide and square root; see
+    /// `DivSqrtConfig`.
+    DivSqrt,
 }
 
+#[derive(Clone)]
 pub struct ExecutePipeline {
     alu: TimedServer<()>,
     int_mul: TimedServer<()>,
     int_div: TimedServer<()>,
     fp: TimedServer<()>,
     sfu: TimedServer<()>,
+    shuffle: TimedServer<()>,
+    div_sqrt: DivSqrtUnit,
 }
 
 impl ExecutePipeline {
@@ -78,17 +279,33 @@ impl ExecutePipeline {
             int_div: TimedServer::new(config.int_div),
             fp: TimedServer::new(config.fp),
             sfu: TimedServer::new(config.sfu),
+            shuffle: TimedServer::new(config.shuffle),
+            div_sqrt: DivSqrtUnit::new(config.div_sqrt),
         }
     }
 
+    pub fn set_config(&mut self, config: &ExecutePipelineConfig) {
+        self.alu.set_config(config.alu);
+        self.int_mul.set_config(config.int_mul);
+        self.int_div.set_config(config.int_div);
+        self.fp.set_config(config.fp);
+        self.sfu.set_config(config.sfu);
+        self.shuffle.set_config(config.shuffle);
+        self.div_sqrt.set_config(config.div_sqrt);
+    }
+
     pub fn tick(&mut self, now: Cycle) {
         self.alu.service_ready(now, |_| {});
         self.int_mul.service_ready(now, |_| {});
         self.int_div.service_ready(now, |_| {});
         self.fp.service_ready(now, |_| {});
         self.sfu.service_ready(now, |_| {});
+        self.shuffle.service_ready(now, |_| {});
+        self.div_sqrt.tick(now);
     }
 
+    /// `DivSqrt` instructions go through `issue_div_sqrt`, which knows their
+    /// op and warp; here they count as integer divides of warp 0.
     pub fn issue(
         &mut self,
         now: Cycle,
@@ -103,9 +320,42 @@ impl ExecutePipeline {
             ExecUnitKind::IntDiv => self.int_div.try_enqueue(now, request),
             ExecUnitKind::Fp => self.fp.try_enqueue(now, request),
             ExecUnitKind::Sfu => self.sfu.try_enqueue(now, request),
+            ExecUnitKind::Shuffle => self.shuffle.try_enqueue(now, request),
+            ExecUnitKind::DivSqrt => self.issue_div_sqrt(now, 0, DivSqrtOp::IntDiv, active_lanes),
         }
     }
 
+    /// Issues to the dividers, or, with none configured, to the pipelined
+    /// `int_div` and `fp` units.
+    pub fn issue_div_sqrt(
+        &mut self,
+        now: Cycle,
+        warp: usize,
+        op: DivSqrtOp,
+        active_lanes: u32,
+    ) -> Result<Ticket, Backpressure<()>> {
+        if self.div_sqrt_enabled() {
+            return self.div_sqrt.issue(now, warp, op, active_lanes);
+        }
+        let request = ServiceRequest::new((), active_lanes.max(1));
+        match op {
+            DivSqrtOp::IntDiv => self.int_div.try_enqueue(now, request),
+            DivSqrtOp::FpDiv | DivSqrtOp::FpSqrt => self.fp.try_enqueue(now, request),
+        }
+    }
+
+    pub fn div_sqrt_enabled(&self) -> bool {
+        self.div_sqrt.config().enabled()
+    }
+
+    pub fn div_sqrt_stats(&self) -> DivSqrtStats {
+        self.div_sqrt.stats()
+    }
+
+    pub fn clear_stats(&mut self) {
+        self.div_sqrt.clear_stats();
+    }
+
     pub fn is_busy(&self, kind: ExecUnitKind) -> bool {
         match kind {
             ExecUnitKind::Int => self.alu.outstanding() > 0,
@@ -113,6 +363,8 @@ impl ExecutePipeline {
             ExecUnitKind::IntDiv => self.int_div.outstanding() > 0,
             ExecUnitKind::Fp => self.fp.outstanding() > 0,
             ExecUnitKind::Sfu => self.sfu.outstanding() > 0,
+            ExecUnitKind::Shuffle => self.shuffle.outstanding() > 0,
+            ExecUnitKind::DivSqrt => self.div_sqrt.is_busy(),
         }
     }
 
@@ -129,6 +381,8 @@ impl ExecutePipeline {
             ExecUnitKind::IntDiv => queue_full_retry(&self.int_div),
             ExecUnitKind::Fp => queue_full_retry(&self.fp),
             ExecUnitKind::Sfu => queue_full_retry(&self.sfu),
+            ExecUnitKind::Shuffle => queue_full_retry(&self.shuffle),
+            ExecUnitKind::DivSqrt => self.div_sqrt.suggest_retry(),
         }
     }
 }
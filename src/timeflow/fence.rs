@@ -1,15 +1,146 @@
 use std::collections::VecDeque;
+use std::ops::AddAssign;
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 use crate::timeflow::simple_queue::SimpleTimedQueue;
 pub use crate::timeflow::types::RejectReason as FenceRejectReason;
 use crate::timeq::{Cycle, ServerConfig, Ticket};
 
+/// Memory classes a fence orders.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FenceScope {
+    /// Gmem only; the fence flushes the warp's L1.
+    Global,
+    /// Smem only; nothing is flushed.
+    Shared,
+    All,
+}
+
+impl FenceScope {
+    pub fn orders_global(self) -> bool {
+        matches!(self, FenceScope::Global | FenceScope::All)
+    }
+
+    pub fn orders_shared(self) -> bool {
+        matches!(self, FenceScope::Shared | FenceScope::All)
+    }
+}
+
+/// Which side of a fence is ordered. A release fence waits for the warp's
+/// outstanding requests in its scope before it issues; an acquire fence
+/// holds the warp until the fence completes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FenceOrder {
+    Acquire,
+    Release,
+    AcqRel,
+}
+
+impl FenceOrder {
+    pub fn acquires(self) -> bool {
+        self != FenceOrder::Release
+    }
+
+    pub fn releases(self) -> bool {
+        self != FenceOrder::Acquire
+    }
+}
+
+/// Scope and order of a Muon `fence` (`f3` 0) or `fence.s` (`f3` 2).
+/// `fence` takes them from its RISC-V predecessor and successor sets in
+/// `imm[7:4]` and `imm[3:0]`: R/W bits order gmem and I/O bits order smem,
+/// and a set left empty drops that side of the fence. `fence 0,0` and
+/// `fence.s` are full fences over their memory.
+pub fn decode_fence(f3: u8, imm: u32) -> (FenceScope, FenceOrder) {
+    const RW: u32 = 0b0011;
+    const IO: u32 = 0b1100;
+    if f3 == 2 {
+        return (FenceScope::Shared, FenceOrder::AcqRel);
+    }
+    let pred = (imm >> 4) & 0xf;
+    let succ = imm & 0xf;
+    let order = match (pred != 0, succ != 0) {
+        (true, false) => FenceOrder::Release,
+        (false, true) => FenceOrder::Acquire,
+        _ => FenceOrder::AcqRel,
+    };
+    let sets = pred | succ;
+    let scope = match (sets & RW != 0, sets & IO != 0) {
+        (true, false) => FenceScope::Global,
+        (false, true) => FenceScope::Shared,
+        _ => FenceScope::All,
+    };
+    (scope, order)
+}
+
 #[derive(Debug, Clone)]
 pub struct FenceRequest {
     pub warp: usize,
     pub request_id: u64,
+    pub scope: FenceScope,
+    pub order: FenceOrder,
+}
+
+impl FenceRequest {
+    /// A full fence over every memory class.
+    pub fn new(warp: usize, request_id: u64) -> Self {
+        Self {
+            warp,
+            request_id,
+            scope: FenceScope::All,
+            order: FenceOrder::AcqRel,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct FenceScopeStats {
+    pub fences: u64,
+    /// Release fences that had to wait for outstanding requests.
+    pub drained: u64,
+    /// Cycles those fences waited.
+    pub drain_cycles: u64,
+    pub acquire_only: u64,
+    pub release_only: u64,
+}
+
+impl AddAssign<&FenceScopeStats> for FenceScopeStats {
+    fn add_assign(&mut self, other: &FenceScopeStats) {
+        self.fences = self.fences.saturating_add(other.fences);
+        self.drained = self.drained.saturating_add(other.drained);
+        self.drain_cycles = self.drain_cycles.saturating_add(other.drain_cycles);
+        self.acquire_only = self.acquire_only.saturating_add(other.acquire_only);
+        self.release_only = self.release_only.saturating_add(other.release_only);
+    }
+}
+
+/// Fences issued by a core, by scope.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct FenceStats {
+    pub global: FenceScopeStats,
+    pub shared: FenceScopeStats,
+    pub all: FenceScopeStats,
+}
+
+impl FenceStats {
+    pub fn scope_mut(&mut self, scope: FenceScope) -> &mut FenceScopeStats {
+        match scope {
+            FenceScope::Global => &mut self.global,
+            FenceScope::Shared => &mut self.shared,
+            FenceScope::All => &mut self.all,
+        }
+    }
+}
+
+impl AddAssign<&FenceStats> for FenceStats {
+    fn add_assign(&mut self, other: &FenceStats) {
+        self.global += &other.global;
+        self.shared += &other.shared;
+        self.all += &other.all;
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -42,6 +173,7 @@ impl Default for FenceConfig {
     }
 }
 
+#[derive(Clone)]
 pub struct FenceQueue {
     queue: SimpleTimedQueue<FenceRequest>,
     ready: VecDeque<FenceRequest>,
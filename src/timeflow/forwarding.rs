@@ -0,0 +1,172 @@
+use serde::{Deserialize, Serialize};
+use std::ops::AddAssign;
+
+use crate::timeflow::execute::ExecUnitKind;
+use crate::timeq::Cycle;
+
+/// A bypass from the output of one execute unit to the inputs of another.
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct ForwardPath {
+    pub from: ExecUnitKind,
+    pub to: ExecUnitKind,
+    /// Cycles from the result leaving `from` to `to` being able to read it.
+    #[serde(default)]
+    pub latency: u64,
+}
+
+/// Operand bypass network between the execute units. Without it, a result
+/// can be read the cycle its unit produces it. With it, a result reaches the
+/// register file `writeback_latency` cycles later, and only consumers on one
+/// of `paths` can read it sooner.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ForwardingConfig {
+    pub enabled: bool,
+    pub writeback_latency: u64,
+    pub paths: Vec<ForwardPath>,
+}
+
+impl Default for ForwardingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            writeback_latency: 2,
+            paths: vec![
+                ForwardPath {
+                    from: ExecUnitKind::Int,
+                    to: ExecUnitKind::Int,
+                    latency: 0,
+                },
+                ForwardPath {
+                    from: ExecUnitKind::Fp,
+                    to: ExecUnitKind::Fp,
+                    latency: 0,
+                },
+            ],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct ForwardingStats {
+    /// Operands read over a bypass path before their producer's writeback.
+    pub bypassed: u64,
+    /// Instructions held for an operand that no path could bypass.
+    pub writeback_stalls: u64,
+    /// Cycles those instructions waited past the producer's result being
+    /// ready.
+    pub writeback_stall_cycles: u64,
+}
+
+impl AddAssign<&ForwardingStats> for ForwardingStats {
+    fn add_assign(&mut self, other: &ForwardingStats) {
+        self.bypassed = self.bypassed.saturating_add(other.bypassed);
+        self.writeback_stalls = self.writeback_stalls.saturating_add(other.writeback_stalls);
+        self.writeback_stall_cycles = self
+            .writeback_stall_cycles
+            .saturating_add(other.writeback_stall_cycles);
+    }
+}
+
+#[derive(Clone)]
+pub struct Forwarding {
+    config: ForwardingConfig,
+    stats: ForwardingStats,
+}
+
+impl Forwarding {
+    pub fn new(config: ForwardingConfig) -> Self {
+        Self {
+            config,
+            stats: ForwardingStats::default(),
+        }
+    }
+
+    pub fn set_config(&mut self, config: ForwardingConfig) {
+        self.config = config;
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    /// Cycle a `consumer` can read a result `producer` has ready at
+    /// `result_at`. `None` consumers (memory, control) read the register
+    /// file.
+    pub fn operand_ready(
+        &self,
+        result_at: Cycle,
+        producer: ExecUnitKind,
+        consumer: Option<ExecUnitKind>,
+    ) -> Cycle {
+        if !self.config.enabled {
+            return result_at;
+        }
+        let bypass = consumer.and_then(|consumer| {
+            self.config
+                .paths
+                .iter()
+                .filter(|path| path.from == producer && path.to == consumer)
+                .map(|path| path.latency)
+                .min()
+        });
+        let delay = bypass.map_or(self.config.writeback_latency, |latency| {
+            latency.min(self.config.writeback_latency)
+        });
+        result_at.saturating_add(delay)
+    }
+
+    /// Whether an operand read at `now` came over a bypass path rather than
+    /// from the register file.
+    pub fn record_read(&mut self, now: Cycle, result_at: Cycle) {
+        if self.config.enabled && now < result_at.saturating_add(self.config.writeback_latency) {
+            self.stats.bypassed = self.stats.bypassed.saturating_add(1);
+        }
+    }
+
+    /// An instruction waited from `now` to `ready_at` for an operand that
+    /// its producer had ready at `result_at`.
+    pub fn record_stall(&mut self, now: Cycle, result_at: Cycle, ready_at: Cycle) {
+        let extra = ready_at.saturating_sub(now.max(result_at));
+        if extra > 0 {
+            self.stats.writeback_stalls = self.stats.writeback_stalls.saturating_add(1);
+            self.stats.writeback_stall_cycles =
+                self.stats.writeback_stall_cycles.saturating_add(extra);
+        }
+    }
+
+    pub fn stats(&self) -> ForwardingStats {
+        self.stats
+    }
+
+    pub fn clear_stats(&mut self) {
+        self.stats = ForwardingStats::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unbypassed_consumers_wait_for_writeback() {
+        let mut config = ForwardingConfig {
+            enabled: true,
+            ..ForwardingConfig::default()
+        };
+        config.paths.push(ForwardPath {
+            from: ExecUnitKind::IntMul,
+            to: ExecUnitKind::Int,
+            latency: 1,
+        });
+        let forwarding = Forwarding::new(config);
+        let alu = Some(ExecUnitKind::Int);
+        assert_eq!(forwarding.operand_ready(10, ExecUnitKind::Int, alu), 10);
+        assert_eq!(forwarding.operand_ready(10, ExecUnitKind::IntMul, alu), 11);
+        assert_eq!(forwarding.operand_ready(10, ExecUnitKind::Fp, alu), 12);
+        assert_eq!(forwarding.operand_ready(10, ExecUnitKind::Int, None), 12);
+
+        let ideal = Forwarding::new(ForwardingConfig::default());
+        assert_eq!(ideal.operand_ready(10, ExecUnitKind::Fp, None), 10);
+    }
+}
@@ -0,0 +1,67 @@
+use serde::Deserialize;
+
+use crate::timeflow::graph::FlowGraph;
+use crate::timeflow::types::{CoreFlowPayload, NodeId};
+
+/// Duplicate return paths per core and adaptive routing between them. No
+/// cache state is bound to a return path, so any response may take any of
+/// its core's paths; the L1 banks are address-bound and are never rerouted.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct AdaptiveRoutingConfig {
+    /// Return paths per core, each a `return_path` node of its own.
+    /// Responses from L1 bank `b` go to path `b % return_paths`, and the
+    /// rest to path 0.
+    pub return_paths: usize,
+    /// Off keeps every response on the path it was routed to.
+    pub enabled: bool,
+    /// Requests a return path may hold, counting those in the link to it,
+    /// before responses routed to it go to the least occupied path instead.
+    pub threshold: usize,
+}
+
+impl Default for AdaptiveRoutingConfig {
+    fn default() -> Self {
+        Self {
+            return_paths: 1,
+            enabled: false,
+            threshold: 4,
+        }
+    }
+}
+
+impl AdaptiveRoutingConfig {
+    pub(crate) fn return_paths(&self) -> usize {
+        self.return_paths.max(1)
+    }
+
+    /// Name of return path `path`. A single path keeps the plain `return`
+    /// name older configs and logs use.
+    pub(crate) fn return_name(&self, path: usize) -> String {
+        if self.return_paths() == 1 {
+            "return".to_string()
+        } else {
+            format!("return_{path}")
+        }
+    }
+
+    /// Lets `node`, whose outputs from `first` on are the return paths of
+    /// `cores` cores, one core after another, move responses between the
+    /// paths of the same core.
+    pub(crate) fn route_returns(
+        &self,
+        graph: &mut FlowGraph<CoreFlowPayload>,
+        node: NodeId,
+        first: usize,
+        cores: usize,
+    ) {
+        let paths = self.return_paths();
+        if !self.enabled || paths == 1 {
+            return;
+        }
+        let groups = (0..cores)
+            .map(|core| (0..paths).map(|path| first + core * paths + path).collect())
+            .collect();
+        graph.set_adaptive_route(node, groups, self.threshold);
+    }
+}
@@ -0,0 +1,225 @@
+use std::ops::AddAssign;
+
+use serde::{Deserialize, Serialize};
+
+use crate::timeflow::graph::TimedNode;
+use crate::timeflow::types::CoreFlowPayload;
+use crate::timeq::Cycle;
+
+/// DMA and tensor transfers charged against DRAM bandwidth.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct BackgroundTrafficConfig {
+    /// Off, DMA and tensor transfers only see their own queues and never
+    /// slow down demand misses.
+    pub enabled: bool,
+    /// Bandwidth cap per agent; 0 lets the agent take whatever DRAM has
+    /// spare.
+    pub dma_bytes_per_cycle: u32,
+    pub tensor_bytes_per_cycle: u32,
+    /// Transfers are charged in chunks of this size so demand misses can
+    /// slot in between.
+    pub chunk_bytes: u32,
+}
+
+impl Default for BackgroundTrafficConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            dma_bytes_per_cycle: 0,
+            tensor_bytes_per_cycle: 0,
+            chunk_bytes: 64,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrafficAgent {
+    Dma,
+    Tensor,
+}
+
+impl TrafficAgent {
+    const ALL: [TrafficAgent; 2] = [TrafficAgent::Dma, TrafficAgent::Tensor];
+
+    fn index(self) -> usize {
+        match self {
+            TrafficAgent::Dma => 0,
+            TrafficAgent::Tensor => 1,
+        }
+    }
+}
+
+/// How DRAM bandwidth was split between demand misses and background
+/// transfers. Shared by every core of the graph.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct DramShareStats {
+    pub cycles: u64,
+    /// Bytes the cache hierarchy moved through DRAM: refills and writebacks.
+    pub demand_bytes: u64,
+    pub dma_bytes: u64,
+    pub tensor_bytes: u64,
+    /// Cycles the agent had bytes waiting on its cap or on a busy DRAM.
+    pub dma_wait_cycles: u64,
+    pub tensor_wait_cycles: u64,
+}
+
+impl DramShareStats {
+    pub fn total_bytes(&self) -> u64 {
+        self.demand_bytes
+            .saturating_add(self.dma_bytes)
+            .saturating_add(self.tensor_bytes)
+    }
+
+    /// Fraction of the DRAM bytes that `bytes` accounts for.
+    pub fn share(&self, bytes: u64) -> f64 {
+        let total = self.total_bytes();
+        if total == 0 {
+            0.0
+        } else {
+            bytes as f64 / total as f64
+        }
+    }
+
+    pub fn demand_share(&self) -> f64 {
+        self.share(self.demand_bytes)
+    }
+
+    pub fn dma_share(&self) -> f64 {
+        self.share(self.dma_bytes)
+    }
+
+    pub fn tensor_share(&self) -> f64 {
+        self.share(self.tensor_bytes)
+    }
+}
+
+impl AddAssign<&DramShareStats> for DramShareStats {
+    fn add_assign(&mut self, other: &DramShareStats) {
+        self.cycles = self.cycles.saturating_add(other.cycles);
+        self.demand_bytes = self.demand_bytes.saturating_add(other.demand_bytes);
+        self.dma_bytes = self.dma_bytes.saturating_add(other.dma_bytes);
+        self.tensor_bytes = self.tensor_bytes.saturating_add(other.tensor_bytes);
+        self.dma_wait_cycles = self.dma_wait_cycles.saturating_add(other.dma_wait_cycles);
+        self.tensor_wait_cycles = self
+            .tensor_wait_cycles
+            .saturating_add(other.tensor_wait_cycles);
+    }
+}
+
+/// Feeds submitted DMA and tensor bytes into a DRAM channel one chunk at a
+/// time. An agent only takes DRAM when it is idle, so background traffic
+/// fills the gaps between demand misses and pushes back the ones that
+/// arrive while a chunk is being moved. The agents alternate priority each
+/// cycle.
+#[derive(Debug, Clone)]
+pub(crate) struct BackgroundTraffic {
+    config: BackgroundTrafficConfig,
+    pending: [u64; 2],
+    next_at: [Cycle; 2],
+    first: usize,
+    demand_total: u64,
+    demand_base: u64,
+    stats: DramShareStats,
+}
+
+impl BackgroundTraffic {
+    pub(crate) fn new(config: BackgroundTrafficConfig) -> Self {
+        Self {
+            config,
+            pending: [0; 2],
+            next_at: [0; 2],
+            first: 0,
+            demand_total: 0,
+            demand_base: 0,
+            stats: DramShareStats::default(),
+        }
+    }
+
+    pub(crate) fn set_config(&mut self, config: BackgroundTrafficConfig) {
+        self.config = config;
+    }
+
+    /// Queues `bytes` of `agent` traffic for DRAM. Returns false, and
+    /// drops the bytes, when background traffic is not modeled.
+    pub(crate) fn submit(&mut self, agent: TrafficAgent, bytes: u32) -> bool {
+        if !self.config.enabled {
+            return false;
+        }
+        let pending = &mut self.pending[agent.index()];
+        *pending = pending.saturating_add(bytes as u64);
+        true
+    }
+
+    pub(crate) fn pending_bytes(&self, agent: TrafficAgent) -> u64 {
+        self.pending[agent.index()]
+    }
+
+    /// Moves at most one chunk per agent into `dram` and returns the bytes
+    /// moved. `demand_total` is what the cache hierarchy has pushed through
+    /// every DRAM channel so far.
+    pub(crate) fn tick(
+        &mut self,
+        now: Cycle,
+        demand_total: u64,
+        dram: &mut dyn TimedNode<CoreFlowPayload>,
+    ) -> u64 {
+        self.demand_total = demand_total;
+        if !self.config.enabled {
+            return 0;
+        }
+        let mut moved_total = 0;
+        self.stats.cycles = self.stats.cycles.saturating_add(1);
+        let chunk_bytes = self.config.chunk_bytes.max(1) as u64;
+        for offset in 0..TrafficAgent::ALL.len() {
+            let agent = TrafficAgent::ALL[(self.first + offset) % TrafficAgent::ALL.len()];
+            let idx = agent.index();
+            if self.pending[idx] == 0 {
+                continue;
+            }
+            let dram_busy = dram.available_at().is_some_and(|at| at > now);
+            if self.next_at[idx] > now || dram_busy {
+                let waited = match agent {
+                    TrafficAgent::Dma => &mut self.stats.dma_wait_cycles,
+                    TrafficAgent::Tensor => &mut self.stats.tensor_wait_cycles,
+                };
+                *waited = waited.saturating_add(1);
+                continue;
+            }
+            let chunk = self.pending[idx].min(chunk_bytes);
+            if dram.reserve_bandwidth(now, chunk as u32).is_none() {
+                // Nothing to contend with; let the bytes go.
+                self.pending[idx] = 0;
+                continue;
+            }
+            self.pending[idx] -= chunk;
+            moved_total += chunk;
+            let moved = match agent {
+                TrafficAgent::Dma => &mut self.stats.dma_bytes,
+                TrafficAgent::Tensor => &mut self.stats.tensor_bytes,
+            };
+            *moved = moved.saturating_add(chunk);
+            let cap = match agent {
+                TrafficAgent::Dma => self.config.dma_bytes_per_cycle,
+                TrafficAgent::Tensor => self.config.tensor_bytes_per_cycle,
+            };
+            if cap > 0 {
+                self.next_at[idx] = now.saturating_add(chunk.div_ceil(cap as u64));
+            }
+        }
+        self.first = (self.first + 1) % TrafficAgent::ALL.len();
+        moved_total
+    }
+
+    pub(crate) fn stats(&self) -> DramShareStats {
+        DramShareStats {
+            demand_bytes: self.demand_total.saturating_sub(self.demand_base),
+            ..self.stats
+        }
+    }
+
+    pub(crate) fn clear_stats(&mut self) {
+        self.demand_base = self.demand_total;
+        self.stats = DramShareStats::default();
+    }
+}
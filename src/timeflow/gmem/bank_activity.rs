@@ -0,0 +1,95 @@
+use serde::Serialize;
+
+use crate::timeflow::graph::FlowGraph;
+use crate::timeflow::types::{CoreFlowPayload, NodeId};
+
+/// Node kinds that make up one cache bank in a graph built by
+/// `build_cluster_graph`.
+const BANK_NODE_KINDS: [&str; 5] = ["tag", "data", "mshr", "refill", "wb"];
+
+/// How often one cache bank had a request queued or in service in any of
+/// its tag, data, MSHR, refill or writeback nodes.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CacheBankActivity {
+    /// `l2_{bank}`, `cluster{c}_l1_{bank}` or `cluster{c}_core{k}_l0d`.
+    pub bank: String,
+    pub cycles: u64,
+    pub active_cycles: u64,
+}
+
+impl CacheBankActivity {
+    /// Cycles the bank could have been clock-gated.
+    pub fn idle_cycles(&self) -> u64 {
+        self.cycles.saturating_sub(self.active_cycles)
+    }
+
+    pub fn duty_cycle(&self) -> f64 {
+        if self.cycles == 0 {
+            0.0
+        } else {
+            self.active_cycles as f64 / self.cycles as f64
+        }
+    }
+}
+
+/// The bank a cache node belongs to, from the names `build_cluster_graph`
+/// gives them: `l2_tag_3` and `cluster0_l1_mshr_3` carry the bank last,
+/// `cluster0_core1_l0d_data` has one bank per core.
+fn bank_of(name: &str) -> Option<String> {
+    let (stem, last) = name.rsplit_once('_')?;
+    if !last.is_empty() && last.chars().all(|c| c.is_ascii_digit()) {
+        let (level, kind) = stem.rsplit_once('_')?;
+        let is_cache = level == "l2" || level.ends_with("_l1");
+        return (is_cache && BANK_NODE_KINDS.contains(&kind)).then(|| format!("{level}_{last}"));
+    }
+    (stem.ends_with("_l0d") && BANK_NODE_KINDS.contains(&last)).then(|| stem.to_string())
+}
+
+/// Per-bank activity of the caches in a cluster graph, sampled once per
+/// cycle.
+#[derive(Clone)]
+pub(crate) struct BankActivity {
+    banks: Vec<(Vec<NodeId>, CacheBankActivity)>,
+}
+
+impl BankActivity {
+    pub(crate) fn new(graph: &FlowGraph<CoreFlowPayload>) -> Self {
+        let mut banks: Vec<(Vec<NodeId>, CacheBankActivity)> = Vec::new();
+        for node in 0..graph.node_count() {
+            let Some(bank) = bank_of(graph.node_name(node)) else {
+                continue;
+            };
+            match banks.iter_mut().find(|(_, stats)| stats.bank == bank) {
+                Some((nodes, _)) => nodes.push(node),
+                None => banks.push((
+                    vec![node],
+                    CacheBankActivity {
+                        bank,
+                        ..CacheBankActivity::default()
+                    },
+                )),
+            }
+        }
+        Self { banks }
+    }
+
+    pub(crate) fn sample(&mut self, graph: &FlowGraph<CoreFlowPayload>) {
+        for (nodes, stats) in &mut self.banks {
+            stats.cycles += 1;
+            if nodes.iter().any(|&node| graph.node_outstanding(node) > 0) {
+                stats.active_cycles += 1;
+            }
+        }
+    }
+
+    pub(crate) fn stats(&self) -> Vec<CacheBankActivity> {
+        self.banks.iter().map(|(_, stats)| stats.clone()).collect()
+    }
+
+    pub(crate) fn clear_stats(&mut self) {
+        for (_, stats) in &mut self.banks {
+            stats.cycles = 0;
+            stats.active_cycles = 0;
+        }
+    }
+}
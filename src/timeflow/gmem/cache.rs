@@ -1,8 +1,9 @@
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub(crate) struct CacheTagArray {
     sets: usize,
     ways: usize,
     tags: Vec<Option<u64>>,
+    dirty: Vec<bool>,
     lru: Vec<Vec<usize>>,
 }
 
@@ -23,6 +24,7 @@ impl CacheTagArray {
             sets,
             ways,
             tags,
+            dirty: vec![false; sets * ways],
             lru,
         }
     }
@@ -50,50 +52,80 @@ impl CacheTagArray {
         debug_assert!(self.bounds_ok(set_idx, way));
         let idx = self.idx(set_idx, way);
         self.tags[idx] = val;
+        self.dirty[idx] = false;
     }
 
-    pub(crate) fn probe(&mut self, line_addr: u64) -> bool {
+    fn find_way(&self, set_idx: usize, line_addr: u64) -> Option<usize> {
+        (0..self.ways).find(|&way| self.get_tag(set_idx, way) == Some(line_addr))
+    }
+
+    /// Whether `line_addr` is resident, without touching LRU order.
+    #[cfg(test)]
+    pub(crate) fn contains(&self, line_addr: u64) -> bool {
+        let set_idx = (line_addr as usize) % self.sets;
+        self.find_way(set_idx, line_addr).is_some()
+    }
+
+    /// Way `fill` would replace for a line not already present.
+    fn victim_way(&self, set_idx: usize) -> usize {
+        (0..self.ways)
+            .find(|&way| self.get_tag(set_idx, way).is_none())
+            .unwrap_or_else(|| *self.lru[set_idx].last().unwrap_or(&0))
+    }
+
+    /// Marks a resident line dirty without touching LRU order; returns
+    /// whether the line was present.
+    pub(crate) fn mark_dirty(&mut self, line_addr: u64) -> bool {
         let set_idx = (line_addr as usize) % self.sets;
-        let mut hit_way = None;
-        for way in 0..self.ways {
-            let tag = self.get_tag(set_idx, way);
-            if tag == Some(line_addr) {
-                hit_way = Some(way);
-                break;
+        match self.find_way(set_idx, line_addr) {
+            Some(way) => {
+                let idx = self.idx(set_idx, way);
+                self.dirty[idx] = true;
+                true
             }
+            None => false,
         }
-        if let Some(way) = hit_way {
+    }
+
+    /// Whether filling `line_addr` now would evict a dirty line.
+    pub(crate) fn fill_evicts_dirty(&self, line_addr: u64) -> bool {
+        self.dirty_victim(line_addr).is_some()
+    }
+
+    /// The dirty line filling `line_addr` now would evict, if any.
+    pub(crate) fn dirty_victim(&self, line_addr: u64) -> Option<u64> {
+        let set_idx = (line_addr as usize) % self.sets;
+        if self.find_way(set_idx, line_addr).is_some() {
+            return None;
+        }
+        let way = self.victim_way(set_idx);
+        self.get_tag(set_idx, way)
+            .filter(|_| self.dirty[self.idx(set_idx, way)])
+    }
+
+    pub(crate) fn probe(&mut self, line_addr: u64) -> bool {
+        let set_idx = (line_addr as usize) % self.sets;
+        if let Some(way) = self.find_way(set_idx, line_addr) {
             self.touch(set_idx, way);
             return true;
         }
         false
     }
 
-    pub(crate) fn fill(&mut self, line_addr: u64) {
+    /// Installs `line_addr` and returns the dirty line it evicted, if any.
+    pub(crate) fn fill(&mut self, line_addr: u64) -> Option<u64> {
         let set_idx = (line_addr as usize) % self.sets;
-        let mut hit_way = None;
-        for way in 0..self.ways {
-            let tag = self.get_tag(set_idx, way);
-            if tag == Some(line_addr) {
-                hit_way = Some(way);
-                break;
-            }
-        }
-        if let Some(way) = hit_way {
+        if let Some(way) = self.find_way(set_idx, line_addr) {
             self.touch(set_idx, way);
-            return;
+            return None;
         }
 
-        let mut empty_way = None;
-        for way in 0..self.ways {
-            if self.get_tag(set_idx, way).is_none() {
-                empty_way = Some(way);
-                break;
-            }
-        }
-        let way = empty_way.unwrap_or_else(|| *self.lru[set_idx].last().unwrap_or(&0));
+        let way = self.victim_way(set_idx);
+        let idx = self.idx(set_idx, way);
+        let evicted = self.tags[idx].filter(|_| self.dirty[idx]);
         self.set_tag(set_idx, way, Some(line_addr));
         self.touch(set_idx, way);
+        evicted
     }
 
     pub(crate) fn invalidate_all(&mut self) {
@@ -0,0 +1,154 @@
+use std::collections::HashMap;
+use std::ops::AddAssign;
+
+use serde::{Deserialize, Serialize};
+
+use super::request::GmemRequest;
+
+/// Cache operator of a load: which levels it looks the line up in and
+/// fills on a miss. A bypassed level is still passed through as a miss;
+/// it just never hits or allocates the line.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub enum CacheHint {
+    /// Cache at every level.
+    #[default]
+    #[serde(rename = "ca")]
+    All,
+    /// Cache at the L1 and L2, bypassing the L0.
+    #[serde(rename = "cg")]
+    Global,
+    /// Streaming: cache at the L2 only.
+    #[serde(rename = "cs")]
+    Streaming,
+}
+
+impl CacheHint {
+    pub fn bypasses_l0(self) -> bool {
+        self != Self::All
+    }
+
+    pub fn bypasses_l1(self) -> bool {
+        self == Self::Streaming
+    }
+}
+
+/// A hint for the loads at one PC.
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct CacheHintEntry {
+    pub pc: u32,
+    pub hint: CacheHint,
+}
+
+/// `[gmem.cache_hints]`: hints for loads whose request does not carry one.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct CacheHintConfig {
+    pub pcs: Vec<CacheHintEntry>,
+}
+
+/// Where the loads of one hint were served.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct CacheHintCounts {
+    pub loads: u64,
+    pub l0_hits: u64,
+    pub l1_hits: u64,
+    pub l2_hits: u64,
+    /// Loads that missed every level they looked in.
+    pub misses: u64,
+}
+
+impl AddAssign<&CacheHintCounts> for CacheHintCounts {
+    fn add_assign(&mut self, other: &CacheHintCounts) {
+        self.loads = self.loads.saturating_add(other.loads);
+        self.l0_hits = self.l0_hits.saturating_add(other.l0_hits);
+        self.l1_hits = self.l1_hits.saturating_add(other.l1_hits);
+        self.l2_hits = self.l2_hits.saturating_add(other.l2_hits);
+        self.misses = self.misses.saturating_add(other.misses);
+    }
+}
+
+/// Loads by cache hint; shared by every core.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct CacheHintStats {
+    pub ca: CacheHintCounts,
+    pub cg: CacheHintCounts,
+    pub cs: CacheHintCounts,
+}
+
+impl CacheHintStats {
+    pub fn counts(&self, hint: CacheHint) -> &CacheHintCounts {
+        match hint {
+            CacheHint::All => &self.ca,
+            CacheHint::Global => &self.cg,
+            CacheHint::Streaming => &self.cs,
+        }
+    }
+
+    fn counts_mut(&mut self, hint: CacheHint) -> &mut CacheHintCounts {
+        match hint {
+            CacheHint::All => &mut self.ca,
+            CacheHint::Global => &mut self.cg,
+            CacheHint::Streaming => &mut self.cs,
+        }
+    }
+}
+
+impl AddAssign<&CacheHintStats> for CacheHintStats {
+    fn add_assign(&mut self, other: &CacheHintStats) {
+        self.ca += &other.ca;
+        self.cg += &other.cg;
+        self.cs += &other.cs;
+    }
+}
+
+/// The PC table of `[gmem.cache_hints]` and the loads of each hint.
+#[derive(Clone, Default)]
+pub(crate) struct CacheHints {
+    by_pc: HashMap<u32, CacheHint>,
+    stats: CacheHintStats,
+}
+
+impl CacheHints {
+    pub(crate) fn new(config: &CacheHintConfig) -> Self {
+        Self {
+            by_pc: config
+                .pcs
+                .iter()
+                .map(|entry| (entry.pc, entry.hint))
+                .collect(),
+            stats: CacheHintStats::default(),
+        }
+    }
+
+    /// Gives a load without a hint of its own the one of its PC.
+    pub(crate) fn assign(&self, request: &mut GmemRequest) {
+        if request.is_load && request.cache_hint == CacheHint::All {
+            if let Some(&hint) = self.by_pc.get(&request.pc) {
+                request.cache_hint = hint;
+            }
+        }
+    }
+
+    pub(crate) fn record(&mut self, request: &GmemRequest) {
+        let counts = self.stats.counts_mut(request.cache_hint);
+        counts.loads = counts.loads.saturating_add(1);
+        let level = if request.l0_hit {
+            &mut counts.l0_hits
+        } else if request.l1_hit {
+            &mut counts.l1_hits
+        } else if request.l2_hit {
+            &mut counts.l2_hits
+        } else {
+            &mut counts.misses
+        };
+        *level = level.saturating_add(1);
+    }
+
+    pub(crate) fn stats(&self) -> CacheHintStats {
+        self.stats
+    }
+
+    pub(crate) fn clear_stats(&mut self) {
+        self.stats = CacheHintStats::default();
+    }
+}
@@ -1,20 +1,38 @@
 use std::collections::VecDeque;
 
+use crate::check;
 use crate::timeflow::{
-    graph::FlowGraph,
+    bottleneck::BottleneckReport,
+    crossbar::{CrossbarPortStats, CrossbarStatsHandle},
+    graph::{
+        AdaptiveRouteStats, FlowGraph, InjectedStall, MisrouteStats, PathAccessor, RouteCoverage,
+    },
+    stats_switch::StatsSwitch,
     types::{CoreFlowPayload, NodeId},
 };
 use crate::timeq::{Backpressure, Cycle, ServiceRequest, Ticket};
 
+use super::background::{BackgroundTraffic, DramShareStats, TrafficAgent};
+use super::bank_activity::{BankActivity, CacheBankActivity};
 use super::cache::CacheTagArray;
-use super::graph_build::{build_cluster_graph, GmemFlowConfig};
+use super::cache_hint::{CacheHintStats, CacheHints};
+use super::data_port::{DataPorts, RefillContention};
+use super::dram::{DramChannelStats, DramChannels};
+use super::fill_forward::{FillForward, FillForwardStats};
+use super::graph_build::{build_cluster_graph, retime_cluster_graph, GmemFlowConfig};
+use super::injector::{InjectorNodes, InjectorStats, Injectors};
 use super::mshr::{MissLevel, MissMetadata, MshrTable};
-use super::policy::{bank_for, decide, line_addr, GmemPolicyConfig};
+use super::policy::{decide, line_addr, GmemPolicyConfig};
+use super::region::{MemoryMapConfig, MemoryRegionKind, MemoryRegionStats, MemoryRegions};
+use super::remote::{DieTrafficStats, RemoteDies};
 use super::request::{
     extract_gmem_request, GmemCompletion, GmemIssue, GmemReject, GmemRejectReason, GmemRequest,
     GmemResult,
 };
+use super::salvage::BankMap;
 use super::stats::GmemStats;
+use super::validate::{validate_graph, GraphShape, GraphValidation};
+use super::write_combining::{WriteCombiner, WriteCombiningStats};
 
 struct CacheLines {
     l0_line: u64,
@@ -57,6 +75,7 @@ struct RollbackSpec {
     l2_new: bool,
 }
 
+#[derive(Clone)]
 pub struct Bank {
     pub mshr: MshrTable,
     pub stats: GmemStats,
@@ -71,6 +90,7 @@ impl Bank {
     }
 }
 
+#[derive(Clone)]
 pub struct CacheLayer {
     pub tags: CacheTagArray,
     pub banks: Vec<Bank>,
@@ -87,6 +107,7 @@ impl CacheLayer {
     }
 }
 
+#[derive(Clone)]
 pub struct GmemHierarchy {
     pub l0: Vec<CacheLayer>,
     pub l1: Vec<CacheLayer>,
@@ -122,6 +143,13 @@ impl GmemHierarchy {
         let l2 = self.l2.stats();
         (l0, l1, l2)
     }
+
+    pub fn clear_stats(&mut self) {
+        for layer in self.l0.iter_mut().chain(self.l1.iter_mut()) {
+            layer.clear_stats();
+        }
+        self.l2.clear_stats();
+    }
 }
 
 impl CacheLayer {
@@ -190,32 +218,79 @@ impl CacheLayer {
         }
         s
     }
+
+    pub fn clear_stats(&mut self) {
+        for b in &mut self.banks {
+            b.stats = GmemStats::default();
+        }
+    }
 }
 
+#[derive(Clone)]
 pub struct ClusterGmemGraph {
     graph: FlowGraph<CoreFlowPayload>,
     cores: Vec<ClusterCoreState>,
     policy: GmemPolicyConfig,
     l1_banks: usize,
+    /// Where lines land among the L1 and L2 banks left after salvage.
+    l1_bank_map: BankMap,
+    l2_bank_map: BankMap,
     hierarchy: GmemHierarchy,
     last_tick: Cycle,
     stats_range: Option<super::graph_build::GmemStatsRange>,
+    regions: MemoryRegions,
+    dram: DramChannels,
+    remote: RemoteDies,
+    background: BackgroundTraffic,
+    fill_forward: FillForward,
+    cache_hints: CacheHints,
+    injectors: Injectors,
+    injector_nodes: InjectorNodes,
+    write_combining: WriteCombiner,
+    bank_activity: BankActivity,
+    /// Data array ports the L1 and L2 refills share with hits.
+    data_ports: DataPorts,
+    /// Flushes in flight at each core's L0 and each cluster's L1, for
+    /// `max_inflight_l0_flushes` and `max_inflight_l1_flushes`.
+    inflight_l0_flushes: Vec<u32>,
+    inflight_l1_flushes: Vec<u32>,
+    stats: StatsSwitch,
+    /// Every how many request ids one records its path; 0 records none.
+    path_sample: u64,
 }
 
 const L1_BANK_SEED: u64 = 0x1111_2222_3333_4444;
 const L2_BANK_SEED: u64 = 0x5555_6666_7777_8888;
 
+#[derive(Clone)]
 struct ClusterCoreState {
     ingress_node: NodeId,
-    return_node: NodeId,
+    return_nodes: Vec<NodeId>,
     completions: VecDeque<GmemCompletion>,
     stats: GmemStats,
     next_id: u64,
+    crossbar_stats: Option<CrossbarStatsHandle>,
+    crossbar_port: usize,
 }
 
 impl ClusterGmemGraph {
     pub fn new(config: GmemFlowConfig, num_clusters: usize, cores_per_cluster: usize) -> Self {
-        let (graph, core_nodes) = build_cluster_graph(&config, num_clusters, cores_per_cluster);
+        Self::new_with_regions(
+            config,
+            MemoryMapConfig::default(),
+            num_clusters,
+            cores_per_cluster,
+        )
+    }
+
+    pub fn new_with_regions(
+        config: GmemFlowConfig,
+        memory_map: MemoryMapConfig,
+        num_clusters: usize,
+        cores_per_cluster: usize,
+    ) -> Self {
+        let (graph, core_nodes, dram_nodes, remote_nodes, data_ports, injector_nodes) =
+            build_cluster_graph(&config, num_clusters, cores_per_cluster);
         let levels = &config.levels;
         assert!(
             levels.len() >= 3,
@@ -224,18 +299,21 @@ impl ClusterGmemGraph {
         let l0_level = &levels[0];
         let l1_level = &levels[1];
         let l2_level = &levels[2];
-        let l1_banks = l1_level.banks.max(1);
-        let l2_banks = l2_level.banks.max(1);
+        let (l1_bank_map, l2_bank_map) = config.bank_maps();
+        let l1_banks = l1_bank_map.len();
+        let l2_banks = l2_bank_map.len();
 
         let total_cores = num_clusters.saturating_mul(cores_per_cluster);
         let mut cores = Vec::with_capacity(total_cores);
-        for node in core_nodes {
+        for (core_id, node) in core_nodes.into_iter().enumerate() {
             cores.push(ClusterCoreState {
                 ingress_node: node.ingress_node,
-                return_node: node.return_node,
+                return_nodes: node.return_nodes,
                 completions: VecDeque::new(),
                 stats: GmemStats::default(),
                 next_id: 0,
+                crossbar_stats: node.crossbar_stats,
+                crossbar_port: core_id % cores_per_cluster.max(1),
             });
         }
 
@@ -273,18 +351,58 @@ impl ClusterGmemGraph {
             l2_mshr_capacity,
         );
         let hierarchy = GmemHierarchy::new(l0_layers, l1_layers, l2_layer);
+        let regions = MemoryRegions::new(memory_map, total_cores);
+        if let Err(err) = regions.check_dies(config.remote.dies) {
+            panic!("{err}");
+        }
 
         Self {
-            graph,
             cores,
             policy,
             l1_banks,
+            l1_bank_map,
+            l2_bank_map,
             hierarchy,
             last_tick: u64::MAX,
             stats_range: config.stats_range,
+            regions,
+            dram: DramChannels::new(config.dram, dram_nodes, config.nodes.dram.bytes_per_cycle),
+            remote: RemoteDies::new(remote_nodes, config.remote.link.bytes_per_cycle),
+            background: BackgroundTraffic::new(config.background),
+            fill_forward: FillForward::new(config.fill_forward),
+            cache_hints: CacheHints::new(&config.cache_hints),
+            injectors: Injectors::new(config.injectors),
+            injector_nodes,
+            write_combining: WriteCombiner::new(
+                config.write_combining,
+                config.nodes.dram.bytes_per_cycle,
+            ),
+            bank_activity: BankActivity::new(&graph),
+            data_ports,
+            inflight_l0_flushes: vec![0; total_cores],
+            inflight_l1_flushes: vec![0; num_clusters],
+            stats: StatsSwitch::default(),
+            path_sample: 0,
+            graph,
         }
     }
 
+    /// Starts or stops sampling bank activity and graph activity; see
+    /// `FlowGraph::set_stats`.
+    pub fn set_stats(&mut self, enabled: bool) {
+        self.stats = StatsSwitch::new(enabled);
+        self.graph.set_stats(enabled);
+    }
+
+    /// Records the nodes every `every`-th request (by id) goes through in
+    /// its `path`; 0 turns recording off.
+    pub fn set_path_sample(&mut self, every: u64) {
+        self.path_sample = every;
+        self.graph.set_path_accessor(
+            (every > 0).then_some(CoreFlowPayload::gmem_path as PathAccessor<_>),
+        );
+    }
+
     fn stats_enabled_for(&self, addr: u64) -> bool {
         match self.stats_range {
             Some(range) => addr >= range.start && addr < range.end,
@@ -312,7 +430,43 @@ impl ClusterGmemGraph {
             request.id
         };
         request.id = assigned_id;
+        self.cache_hints.assign(&mut request);
+
+        let region = if request.kind.is_mem() {
+            self.regions.lookup(request.addr)
+        } else {
+            None
+        };
+        request.region = region.map(|(idx, _)| idx);
+        request.die = region.map_or(0, |(idx, _)| self.regions.die(idx));
+        let bytes = request.bytes;
+        let result = match region {
+            Some((idx, kind)) if kind.bypasses_hierarchy() => {
+                self.issue_to_device(core_id, idx, now, request)
+            }
+            Some((_, MemoryRegionKind::Uncacheable)) => {
+                self.issue_uncacheable(core_id, now, request)
+            }
+            _ => self.issue_to_hierarchy(core_id, now, request),
+        };
+        if let Some((idx, _)) = region {
+            match &result {
+                Ok(issue) => self
+                    .regions
+                    .record_issue(core_id, idx, issue.request_id, bytes, now),
+                Err(_) => self.regions.record_reject(core_id, idx),
+            }
+        }
+        result
+    }
 
+    fn issue_to_hierarchy(
+        &mut self,
+        core_id: usize,
+        now: Cycle,
+        mut request: GmemRequest,
+    ) -> GmemResult<GmemIssue> {
+        let assigned_id = request.id;
         if !request.kind.is_mem() {
             request.bytes = self.policy.flush_bytes.max(1);
             request.l0_hit = false;
@@ -320,10 +474,36 @@ impl ClusterGmemGraph {
             request.l2_hit = false;
             request.l1_writeback = false;
             request.l2_writeback = false;
+            request.rmw = false;
             request.l1_bank = 0;
             request.l2_bank = 0;
+            request.dram_channel = 0;
+            request.dram_write_bytes = 0;
             request.line_addr = 0;
-            return self.issue_to_graph(core_id, now, request);
+            if self.flush_limit_reached(core_id, &request) {
+                if self.stats_enabled_for(request.addr) {
+                    self.cores[core_id].stats.record_flush_limit_reject();
+                }
+                return Err(GmemReject {
+                    payload: request,
+                    retry_at: now.saturating_add(1),
+                    reason: GmemRejectReason::FlushLimit,
+                });
+            }
+            let is_l1 = request.kind.is_flush_l1();
+            let cluster_id = request.cluster_id;
+            let result = self.issue_to_graph(core_id, now, request);
+            if result.is_ok() {
+                let inflight = if is_l1 {
+                    self.inflight_l1_flushes.get_mut(cluster_id)
+                } else {
+                    self.inflight_l0_flushes.get_mut(core_id)
+                };
+                if let Some(inflight) = inflight {
+                    *inflight += 1;
+                }
+            }
+            return result;
         }
 
         let cluster_id = request.cluster_id;
@@ -373,8 +553,14 @@ impl ClusterGmemGraph {
             } => (l0_new, l1_new, l2_new),
         };
 
+        let writeback_addr = self.writeback_addr(&request);
+        request.dram_write_bytes =
+            writeback_addr.map_or(0, |addr| self.write_combining.write_bytes(now, addr));
         let issue = match self.issue_to_graph(core_id, now, request) {
-            Ok(issue) => issue,
+            Ok(issue) => GmemIssue {
+                miss: !matches!(miss_level, MissLevel::None),
+                ..issue
+            },
             Err(err) => {
                 self.rollback_mshrs(
                     core_id,
@@ -392,6 +578,9 @@ impl ClusterGmemGraph {
             }
         };
 
+        if let Some(addr) = writeback_addr {
+            self.write_combining.record(now, addr);
+        }
         let ready_at = issue.ticket.ready_at();
         match miss_level {
             MissLevel::None => {}
@@ -426,36 +615,42 @@ impl ClusterGmemGraph {
         }
         self.last_tick = now;
 
+        self.inject(now);
+        self.dram.tick(now, &mut self.graph, &mut self.background);
+        self.remote.tick(&mut self.graph);
         self.graph.tick(now);
+        if self.stats.on() {
+            self.bank_activity.sample(&self.graph);
+        }
 
         for core_id in 0..self.cores.len() {
-            let return_node = self.cores[core_id].return_node;
             let mut drained = Vec::new();
-            self.graph.with_node_mut(return_node, |node| {
-                while let Some(result) = node.take_ready(now) {
-                    if let CoreFlowPayload::Gmem(request) = result.payload {
-                        drained.push((request, result.ticket));
+            for &return_node in &self.cores[core_id].return_nodes {
+                self.graph.with_node_mut(return_node, |node| {
+                    while let Some(result) = node.take_ready(now) {
+                        if let CoreFlowPayload::Gmem(mut request) = result.payload {
+                            if let Some(hop) = request.path.as_mut().and_then(|p| p.last_mut()) {
+                                hop.exit = Some(now);
+                            }
+                            drained.push((request, result.ticket));
+                        }
                     }
-                }
-            });
+                });
+            }
 
             // Apply completion effects for all ready requests
             for (request, _ticket) in &drained {
                 self.apply_completion_effects(request);
             }
 
-            // Collect merged completions (they will inherit the same ticket_ready_at as parent request)
-            let mut merged_all: Vec<(GmemRequest, crate::timeq::Ticket)> = Vec::new();
+            // Queue merged completions for release (they will inherit the same ticket_ready_at as parent request)
             for (request, ticket) in &drained {
                 let merged = self.drain_mshr_merges(request, now);
-                for m in merged {
-                    merged_all.push((m, ticket.clone()));
+                // Apply completion effects for merged completions so their fills are visible before any completions are recorded
+                for merged_req in &merged {
+                    self.apply_completion_effects(merged_req);
                 }
-            }
-
-            // Apply completion effects for merged completions so their fills are visible before any completions are recorded
-            for (merged_req, _ticket) in &merged_all {
-                self.apply_completion_effects(merged_req);
+                self.fill_forward.schedule(now, ticket.ready_at(), merged);
             }
 
             // Push/record original completions
@@ -464,20 +659,169 @@ impl ClusterGmemGraph {
                 self.push_completion(request.clone(), ticket_ready_at, now);
             }
 
-            // Push/record merged completions
-            for (merged_req, ticket) in merged_all.into_iter() {
-                let ticket_ready_at = ticket.ready_at();
-                self.push_completion(merged_req.clone(), ticket_ready_at, now);
+            // Push/record merged completions whose turn on the fill path has come
+            for (merged_req, ticket_ready_at) in self.fill_forward.pop_ready(now) {
+                self.push_completion(merged_req, ticket_ready_at, now);
             }
         }
+
+        for (request, ticket) in self.regions.pop_ready(now) {
+            self.push_completion(request, ticket.ready_at(), now);
+        }
+        self.complete_injected(now);
     }
 
-    fn issue_to_graph(
+    /// Offers this cycle's `gmem.injectors` requests to the L2 tag banks.
+    /// They look up and fill the L2 like an L1 miss would, but never merge
+    /// into or allocate an MSHR entry.
+    fn inject(&mut self, now: Cycle) {
+        if self.injector_nodes.sink.is_none() {
+            return;
+        }
+        let num_clusters = self.hierarchy.l1.len();
+        for (index, mut request) in self.injectors.offers() {
+            request.cluster_id = num_clusters + index;
+            let lines = self.compute_cache_lines(&mut request);
+            request.l2_hit = self.hierarchy.l2.probe(lines.l2_line);
+            if self.stats_enabled_for(request.addr) {
+                if let Some(bank) = self.hierarchy.l2.banks.get_mut(lines.l2_bank) {
+                    bank.stats.record_access(request.bytes);
+                    if request.l2_hit {
+                        bank.stats.record_hit(request.bytes);
+                    }
+                }
+            }
+            request.l2_writeback = !request.l2_hit
+                && if self.policy.tag_accurate() {
+                    self.hierarchy.l2.tags.fill_evicts_dirty(lines.l2_line)
+                } else {
+                    decide(
+                        self.policy.l2_writeback_rate,
+                        lines.l2_line ^ self.policy.seed ^ 0xE5E5_E5E5_E5E5_E5E5,
+                    )
+                };
+            let writeback_addr = self.writeback_addr(&request);
+            request.dram_write_bytes =
+                writeback_addr.map_or(0, |addr| self.write_combining.write_bytes(now, addr));
+
+            let tag_node = self.injector_nodes.l2_tags[lines.l2_bank];
+            let bytes = request.bytes;
+            let service_req = ServiceRequest::new(CoreFlowPayload::Gmem(request.clone()), bytes);
+            match self.graph.try_put(tag_node, now, service_req) {
+                Ok(_) => {
+                    if let Some(addr) = writeback_addr {
+                        self.write_combining.record(now, addr);
+                    }
+                    self.injectors.accepted(index, &request, now);
+                }
+                Err(_) => self.injectors.rejected(),
+            }
+        }
+    }
+
+    fn complete_injected(&mut self, now: Cycle) {
+        let Some(sink) = self.injector_nodes.sink else {
+            return;
+        };
+        let mut completed = Vec::new();
+        self.graph.with_node_mut(sink, |node| {
+            while let Some(result) = node.take_ready(now) {
+                if let CoreFlowPayload::Gmem(request) = result.payload {
+                    completed.push(request);
+                }
+            }
+        });
+        for request in completed {
+            self.apply_completion_effects(&request);
+            self.injectors.complete(&request, now);
+        }
+    }
+
+    /// Uncacheable accesses take the full miss path to DRAM but never touch
+    /// tags or MSHRs, so they neither hit nor leave lines behind.
+    fn issue_uncacheable(
         &mut self,
         core_id: usize,
         now: Cycle,
+        mut request: GmemRequest,
+    ) -> GmemResult<GmemIssue> {
+        if request.cluster_id >= self.hierarchy.l1.len() {
+            return Err(GmemReject {
+                payload: request,
+                retry_at: now.saturating_add(1),
+                reason: GmemRejectReason::QueueFull,
+            });
+        }
+        self.compute_cache_lines(&mut request);
+        request.l0_hit = false;
+        request.l1_hit = false;
+        request.l2_hit = false;
+        request.l1_writeback = false;
+        request.l2_writeback = false;
+        request.rmw = false;
+        self.issue_to_graph(core_id, now, request)
+            .map(|issue| GmemIssue {
+                miss: true,
+                ..issue
+            })
+    }
+
+    /// Scratchpad and MMIO accesses are served by the region's own device
+    /// server instead of the cache graph.
+    fn issue_to_device(
+        &mut self,
+        core_id: usize,
+        region: usize,
+        now: Cycle,
         request: GmemRequest,
     ) -> GmemResult<GmemIssue> {
+        let request_id = request.id;
+        let bytes = request.bytes;
+        let addr = request.addr;
+        let device = self.regions.device_mut(region);
+        match device.try_enqueue(now, ServiceRequest::new(request, bytes)) {
+            Ok(ticket) => {
+                self.record_issue_stats(core_id, request_id, bytes, addr);
+                Ok(GmemIssue {
+                    request_id,
+                    ticket,
+                    miss: false,
+                })
+            }
+            Err(Backpressure::Busy {
+                request,
+                available_at,
+            }) => Err(GmemReject {
+                payload: request.payload,
+                retry_at: available_at.max(now.saturating_add(1)),
+                reason: GmemRejectReason::Busy,
+            }),
+            Err(Backpressure::QueueFull { request, .. }) => Err(GmemReject {
+                payload: request.payload,
+                retry_at: now.saturating_add(1),
+                reason: GmemRejectReason::QueueFull,
+            }),
+        }
+    }
+
+    fn is_uncacheable(&self, request: &GmemRequest) -> bool {
+        request
+            .region
+            .is_some_and(|idx| self.regions.kind(idx) == MemoryRegionKind::Uncacheable)
+    }
+
+    fn issue_to_graph(
+        &mut self,
+        core_id: usize,
+        now: Cycle,
+        mut request: GmemRequest,
+    ) -> GmemResult<GmemIssue> {
+        if self.path_sample > 0
+            && request.id.is_multiple_of(self.path_sample)
+            && request.path.is_none()
+        {
+            request.path = Some(Vec::new());
+        }
         let request_id = request.id;
         let bytes = request.bytes;
         let addr = request.addr;
@@ -490,7 +834,11 @@ impl ClusterGmemGraph {
         {
             Ok(ticket) => {
                 self.record_issue_stats(core_id, request_id, bytes, addr);
-                Ok(GmemIssue { request_id, ticket })
+                Ok(GmemIssue {
+                    request_id,
+                    ticket,
+                    miss: false,
+                })
             }
             Err(bp) => match bp {
                 Backpressure::Busy {
@@ -529,6 +877,21 @@ impl ClusterGmemGraph {
         }
     }
 
+    /// Address of the line an L2 writeback to local DRAM writes out. With
+    /// tag-accurate writebacks this is the dirty victim; otherwise the
+    /// victim is not tracked and the missing line stands in for it.
+    fn writeback_addr(&self, request: &GmemRequest) -> Option<u64> {
+        if !request.l2_writeback || request.die > 0 {
+            return None;
+        }
+        let line = if self.policy.tag_accurate() {
+            self.hierarchy.l2.tags.dirty_victim(request.line_addr)?
+        } else {
+            request.line_addr
+        };
+        Some(line.saturating_mul(self.policy.l2_line_bytes.max(1) as u64))
+    }
+
     fn compute_cache_lines(&self, request: &mut GmemRequest) -> CacheLines {
         let policy = self.policy;
         let l0_enabled = policy.l0_enabled;
@@ -540,10 +903,9 @@ impl ClusterGmemGraph {
         let l1_line = line_addr(request.addr, policy.l1_line_bytes);
         let l2_line = line_addr(request.addr, policy.l2_line_bytes);
         request.line_addr = l2_line;
-        let l1_banks = self.l1_banks.max(1) as u64;
-        let l2_banks = self.hierarchy.l2.bank_count().max(1) as u64;
-        request.l1_bank = bank_for(l1_line, l1_banks, L1_BANK_SEED);
-        request.l2_bank = bank_for(l2_line, l2_banks, L2_BANK_SEED);
+        request.l1_bank = self.l1_bank_map.bank_for(l1_line, L1_BANK_SEED);
+        request.l2_bank = self.l2_bank_map.bank_for(l2_line, L2_BANK_SEED);
+        request.dram_channel = self.dram.channel_for(request.addr);
         CacheLines {
             l0_line,
             l1_line,
@@ -563,8 +925,9 @@ impl ClusterGmemGraph {
     ) -> MissLevel {
         let policy = self.policy;
         let l0_enabled = policy.l0_enabled;
+        let hint = request.cache_hint;
         let mut l0_hit = false;
-        if l0_enabled && core_id < self.hierarchy.l0.len() {
+        if l0_enabled && !hint.bypasses_l0() && core_id < self.hierarchy.l0.len() {
             l0_hit = self.hierarchy.l0[core_id].probe(lines.l0_line);
             if self.hierarchy.l0[core_id].bank_count() > 0 {
                 let bytes = request.bytes;
@@ -586,9 +949,9 @@ impl ClusterGmemGraph {
             request.l1_writeback = false;
             request.l2_writeback = false;
         } else {
-            let l1_hit = { self.hierarchy.l1[cluster_id].probe(lines.l1_line) };
+            let l1_hit = !hint.bypasses_l1() && self.hierarchy.l1[cluster_id].probe(lines.l1_line);
             request.l1_hit = l1_hit;
-            if cluster_id < self.hierarchy.l1.len() {
+            if !hint.bypasses_l1() && cluster_id < self.hierarchy.l1.len() {
                 let l1_layer = &mut self.hierarchy.l1[cluster_id];
                 if (l1_layer.bank_count() > 0) && (lines.l1_bank < l1_layer.bank_count()) {
                     let bytes = request.bytes;
@@ -621,25 +984,44 @@ impl ClusterGmemGraph {
                 }
             }
 
-            if !request.l1_hit {
-                let l1_key = lines.l1_line
-                    ^ (cluster_id as u64).wrapping_mul(0xc2b2_ae3d_27d4_eb4f)
-                    ^ policy.seed;
-                request.l1_writeback =
-                    decide(policy.l1_writeback_rate, l1_key ^ 0xD4D4_D4D4_D4D4_D4D4);
+            if policy.tag_accurate() {
+                let l1 = self.hierarchy.l1.get(cluster_id);
+                request.l1_writeback = !request.l1_hit
+                    && !hint.bypasses_l1()
+                    && l1.is_some_and(|l1| l1.tags.fill_evicts_dirty(lines.l1_line));
+                request.l2_writeback = !request.l1_hit
+                    && !request.l2_hit
+                    && self.hierarchy.l2.tags.fill_evicts_dirty(lines.l2_line);
             } else {
-                request.l1_writeback = false;
-            }
+                if !request.l1_hit && !hint.bypasses_l1() {
+                    let l1_key = lines.l1_line
+                        ^ (cluster_id as u64).wrapping_mul(0xc2b2_ae3d_27d4_eb4f)
+                        ^ policy.seed;
+                    request.l1_writeback =
+                        decide(policy.l1_writeback_rate, l1_key ^ 0xD4D4_D4D4_D4D4_D4D4);
+                } else {
+                    request.l1_writeback = false;
+                }
 
-            if !request.l1_hit && !request.l2_hit {
-                let l2_key = lines.l2_line ^ policy.seed;
-                request.l2_writeback =
-                    decide(policy.l2_writeback_rate, l2_key ^ 0xE5E5_E5E5_E5E5_E5E5);
-            } else {
-                request.l2_writeback = false;
+                if !request.l1_hit && !request.l2_hit {
+                    let l2_key = lines.l2_line ^ policy.seed;
+                    request.l2_writeback =
+                        decide(policy.l2_writeback_rate, l2_key ^ 0xE5E5_E5E5_E5E5_E5E5);
+                } else {
+                    request.l2_writeback = false;
+                }
             }
         }
 
+        if track_stats && request.is_load {
+            self.cache_hints.record(request);
+        }
+
+        request.rmw = false;
+        if !request.is_load && policy.partial_store_rmw {
+            self.record_partial_store(core_id, cluster_id, request, lines, track_stats);
+        }
+
         if l0_enabled {
             if request.l0_hit {
                 MissLevel::None
@@ -659,6 +1041,49 @@ impl ClusterGmemGraph {
         }
     }
 
+    /// A store hitting a line it only partially writes has to read the line
+    /// out of that level's data array and merge before writing it back.
+    /// Stores that miss everywhere go to DRAM, which takes byte-enables.
+    fn record_partial_store(
+        &mut self,
+        core_id: usize,
+        cluster_id: usize,
+        request: &mut GmemRequest,
+        lines: &CacheLines,
+        track_stats: bool,
+    ) {
+        let policy = self.policy;
+        let (line_bytes, layer, bank) = if request.l0_hit {
+            (policy.l0_line_bytes, self.hierarchy.l0.get_mut(core_id), 0)
+        } else if request.l1_hit {
+            (
+                policy.l1_line_bytes,
+                self.hierarchy.l1.get_mut(cluster_id),
+                lines.l1_bank,
+            )
+        } else if request.l2_hit {
+            (
+                policy.l2_line_bytes,
+                Some(&mut self.hierarchy.l2),
+                lines.l2_bank,
+            )
+        } else {
+            return;
+        };
+        if request.writes_whole_line(line_bytes) {
+            return;
+        }
+        request.rmw = true;
+        if let Some(stats) = layer
+            .and_then(|layer| layer.banks.get_mut(bank))
+            .map(|bank| &mut bank.stats)
+        {
+            if track_stats {
+                stats.record_rmw();
+            }
+        }
+    }
+
     fn allocate_cache_entries(
         &mut self,
         core_id: usize,
@@ -992,7 +1417,11 @@ impl ClusterGmemGraph {
     ) -> GmemIssue {
         self.record_issue_stats(core_id, request_id, bytes, addr);
         let ticket = Ticket::new(issued_at, ready_at, bytes);
-        GmemIssue { request_id, ticket }
+        GmemIssue {
+            request_id,
+            ticket,
+            miss: true,
+        }
     }
 
     fn record_issue_stats(&mut self, core_id: usize, request_id: u64, bytes: u32, addr: u64) {
@@ -1013,6 +1442,10 @@ impl ClusterGmemGraph {
         let track = self.stats_enabled_for(addr);
         let request = request;
 
+        if let Some(region) = request.region {
+            self.regions
+                .record_completion(core_id, region, request.id, now);
+        }
         if let Some(core_state) = self.cores.get_mut(core_id) {
             if track {
                 core_state.stats.record_completion(request.bytes, now);
@@ -1030,23 +1463,55 @@ impl ClusterGmemGraph {
         }
     }
 
+    /// Whether the level `request` flushes already has as many flushes in
+    /// flight as its `max_inflight_*_flushes` allows.
+    fn flush_limit_reached(&self, core_id: usize, request: &GmemRequest) -> bool {
+        let (limit, inflight) = if request.kind.is_flush_l1() {
+            (
+                self.policy.max_inflight_l1_flushes,
+                self.inflight_l1_flushes.get(request.cluster_id),
+            )
+        } else {
+            (
+                self.policy.max_inflight_l0_flushes,
+                self.inflight_l0_flushes.get(core_id),
+            )
+        };
+        limit > 0 && inflight.is_some_and(|&count| count >= limit)
+    }
+
     fn apply_completion_effects(&mut self, request: &GmemRequest) {
         if request.kind.is_flush_l0() {
             if self.policy.l0_enabled && request.core_id < self.hierarchy.l0.len() {
                 self.hierarchy.l0[request.core_id].tags.invalidate_all();
             }
+            if let Some(inflight) = self.inflight_l0_flushes.get_mut(request.core_id) {
+                check!(
+                    Inflight,
+                    *inflight > 0,
+                    "core {} completed an L0 flush it had none in flight",
+                    request.core_id
+                );
+                *inflight = inflight.saturating_sub(1);
+            }
             return;
         }
         if request.kind.is_flush_l1() {
             if request.cluster_id < self.hierarchy.l1.len() {
                 self.hierarchy.l1[request.cluster_id].tags.invalidate_all();
             }
+            if let Some(inflight) = self.inflight_l1_flushes.get_mut(request.cluster_id) {
+                check!(
+                    Inflight,
+                    *inflight > 0,
+                    "cluster {} completed an L1 flush it had none in flight",
+                    request.cluster_id
+                );
+                *inflight = inflight.saturating_sub(1);
+            }
             return;
         }
-        if !request.kind.is_mem() {
-            return;
-        }
-        if !request.is_load {
+        if !request.kind.is_mem() || self.is_uncacheable(request) {
             return;
         }
 
@@ -1060,19 +1525,52 @@ impl ClusterGmemGraph {
         let l1_line = line_addr(request.addr, policy.l1_line_bytes);
         let l2_line = line_addr(request.addr, policy.l2_line_bytes);
 
-        if l0_enabled && !request.l0_hit && request.core_id < self.hierarchy.l0.len() {
+        if !request.is_load {
+            if policy.tag_accurate() {
+                self.mark_store_dirty(request, l1_line, l2_line);
+            }
+            return;
+        }
+
+        let hint = request.cache_hint;
+        if l0_enabled
+            && !request.l0_hit
+            && !hint.bypasses_l0()
+            && request.core_id < self.hierarchy.l0.len()
+        {
             self.hierarchy.l0[request.core_id].tags.fill(l0_line);
         }
-        if !request.l1_hit && request.cluster_id < self.hierarchy.l1.len() {
-            self.hierarchy.l1[request.cluster_id].tags.fill(l1_line);
+        if !request.l1_hit && !hint.bypasses_l1() && request.cluster_id < self.hierarchy.l1.len() {
+            let evicted = self.hierarchy.l1[request.cluster_id].tags.fill(l1_line);
+            if let Some(evicted) = evicted.filter(|_| policy.tag_accurate()) {
+                // The dirty L1 victim is written back into L2.
+                let addr = evicted.saturating_mul(policy.l1_line_bytes as u64);
+                self.hierarchy
+                    .l2
+                    .tags
+                    .mark_dirty(line_addr(addr, policy.l2_line_bytes));
+            }
         }
         if !request.l2_hit {
             self.hierarchy.l2.tags.fill(l2_line);
         }
     }
 
+    /// Tag-accurate mode: a store dirties the line at the level that served
+    /// it. L0 is write-through, so an L0 hit dirties the L1 copy; stores
+    /// that miss everywhere go to DRAM without allocating.
+    fn mark_store_dirty(&mut self, request: &GmemRequest, l1_line: u64, l2_line: u64) {
+        if request.l0_hit || request.l1_hit {
+            if let Some(l1) = self.hierarchy.l1.get_mut(request.cluster_id) {
+                l1.tags.mark_dirty(l1_line);
+            }
+        } else if request.l2_hit {
+            self.hierarchy.l2.tags.mark_dirty(l2_line);
+        }
+    }
+
     fn drain_mshr_merges(&mut self, request: &GmemRequest, now: Cycle) -> Vec<GmemRequest> {
-        if !request.kind.is_mem() {
+        if !request.kind.is_mem() || self.is_uncacheable(request) {
             return Vec::new();
         }
 
@@ -1233,7 +1731,206 @@ impl ClusterGmemGraph {
     pub fn clear_stats(&mut self, core_id: usize) {
         if let Some(core) = self.cores.get_mut(core_id) {
             core.stats = GmemStats::default();
+            if let Some(handle) = &core.crossbar_stats {
+                let mut stats = handle.write().expect("crossbar stats lock poisoned");
+                if let Some(port) = stats.ports.get_mut(core.crossbar_port) {
+                    *port = CrossbarPortStats::default();
+                }
+            }
         }
+        self.regions.clear_stats(core_id);
+    }
+
+    /// This core's input port on the cluster crossbar; all zero when no
+    /// crossbar is configured.
+    pub fn crossbar_stats(&self, core_id: usize) -> CrossbarPortStats {
+        self.cores
+            .get(core_id)
+            .and_then(|core| {
+                let handle = core.crossbar_stats.as_ref()?;
+                let stats = handle.read().expect("crossbar stats lock poisoned");
+                stats.ports.get(core.crossbar_port).copied()
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn region_stats(&self, core_id: usize) -> Vec<MemoryRegionStats> {
+        self.regions.stats(core_id)
+    }
+
+    /// Swaps in the server timings from `config` mid-run; see
+    /// `retime_cluster_graph` for what can change.
+    pub fn set_timing(&mut self, config: &GmemFlowConfig) {
+        retime_cluster_graph(&mut self.graph, config);
+        self.dram
+            .set_bytes_per_cycle(config.nodes.dram.bytes_per_cycle);
+        self.remote
+            .set_link_bytes_per_cycle(config.remote.link.bytes_per_cycle);
+        self.background.set_config(config.background);
+        self.fill_forward.set_config(config.fill_forward);
+        self.injectors.set_config(config.injectors);
+        self.write_combining
+            .set_config(config.write_combining, config.nodes.dram.bytes_per_cycle);
+        let refill_cycles = |level: usize| {
+            config
+                .levels
+                .get(level)
+                .map_or(0, |level| level.refill_port_cycles)
+        };
+        self.data_ports
+            .set_refill_cycles(refill_cycles(1), refill_cycles(2));
+    }
+
+    /// Charges `bytes` moved by a DMA or tensor transfer against DRAM
+    /// bandwidth. Returns false when `gmem.background` is disabled.
+    pub fn submit_background(&mut self, agent: TrafficAgent, bytes: u32) -> bool {
+        self.background.submit(agent, bytes)
+    }
+
+    pub fn background_pending_bytes(&self, agent: TrafficAgent) -> u64 {
+        self.background.pending_bytes(agent)
+    }
+
+    pub fn dram_share_stats(&self) -> DramShareStats {
+        self.background.stats()
+    }
+
+    /// Traffic and utilization of each `gmem.dram` channel.
+    pub fn dram_channel_stats(&self) -> Vec<DramChannelStats> {
+        self.dram.stats()
+    }
+
+    /// Traffic to the local DRAM and to each `gmem.remote` die. Empty
+    /// without remote dies.
+    pub fn die_traffic_stats(&self) -> Vec<DieTrafficStats> {
+        self.remote.stats(&self.dram.stats())
+    }
+
+    pub fn fill_forward_stats(&self) -> FillForwardStats {
+        self.fill_forward.stats()
+    }
+
+    pub fn cache_hint_stats(&self) -> CacheHintStats {
+        self.cache_hints.stats()
+    }
+
+    /// Requests of the `gmem.injectors` clusters; all zero without them.
+    pub fn injector_stats(&self) -> InjectorStats {
+        self.injectors.stats()
+    }
+
+    /// L2 writebacks to local DRAM and the write bursts they took; all zero
+    /// unless `gmem.write_combining` is enabled.
+    pub fn write_combining_stats(&self) -> WriteCombiningStats {
+        self.write_combining.stats()
+    }
+
+    /// Responses moved between duplicate return paths; all zero unless
+    /// `gmem.adaptive_routing` is enabled.
+    pub fn adaptive_route_stats(&self) -> AdaptiveRouteStats {
+        self.graph.adaptive_route_stats()
+    }
+
+    /// Refills that took L1 and L2 data array ports from hits, and what
+    /// that cost the hits; all zero unless a level sets
+    /// `refill_port_cycles`.
+    pub fn refill_contention(&self) -> RefillContention {
+        self.data_ports.stats()
+    }
+
+    /// Active cycles of every L0, L1 and L2 bank.
+    pub fn cache_bank_activity(&self) -> Vec<CacheBankActivity> {
+        self.bank_activity.stats()
+    }
+
+    pub fn misroute_stats(&self) -> MisrouteStats {
+        self.graph.misroute_stats()
+    }
+
+    /// The `gmem.stall_injection` windows and the admissions each turned
+    /// away.
+    pub fn injected_stalls(&self) -> Vec<InjectedStall> {
+        self.graph.injected_stalls()
+    }
+
+    /// Names of the graph's nodes, indexed by node id.
+    pub fn node_names(&self) -> Vec<String> {
+        (0..self.graph.node_count())
+            .map(|node| self.graph.node_name(node).to_string())
+            .collect()
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.graph.node_count()
+    }
+
+    /// Requests queued or in service at `node`.
+    pub fn node_outstanding(&self, node: NodeId) -> usize {
+        self.graph.node_outstanding(node)
+    }
+
+    /// Names of the graph's edges with their source and destination nodes,
+    /// indexed by link id.
+    pub fn edge_names(&self) -> Vec<(String, NodeId, NodeId)> {
+        (0..self.graph.edge_count())
+            .map(|edge| {
+                let (src, dst) = self.graph.edge_endpoints(edge);
+                (self.graph.edge_name(edge).to_string(), src, dst)
+            })
+            .collect()
+    }
+
+    pub fn edge_count(&self) -> usize {
+        self.graph.edge_count()
+    }
+
+    /// Requests buffered in link `edge`.
+    pub fn edge_occupancy(&self, edge: usize) -> usize {
+        self.graph.edge_occupancy(edge)
+    }
+
+    /// Checks the graph for nodes no core can reach and for request kind and
+    /// flag combinations with no route back to the issuing core.
+    pub fn validate(&self) -> GraphValidation {
+        let ports: Vec<_> = self
+            .cores
+            .iter()
+            .map(|core| (core.ingress_node, core.return_nodes.clone()))
+            .collect();
+        let shape = GraphShape {
+            ports: &ports,
+            cores_per_cluster: self.cores.len() / self.hierarchy.l1.len().max(1),
+            l0_enabled: self.policy.l0_enabled,
+            l1_banks: self.l1_banks,
+            l2_banks: self.hierarchy.l2.bank_count(),
+            dram_channels: self.dram.channel_count(),
+            remote_dies: self.remote.die_count(),
+        };
+        validate_graph(&self.graph, &shape)
+    }
+
+    /// Nodes of the shared graph ranked by contention, and its longest
+    /// full-queue chains.
+    pub fn bottlenecks(&self) -> BottleneckReport {
+        BottleneckReport::from_graph(&self.graph, "")
+    }
+
+    /// Per-link predicate hit counts; see `FlowGraph::route_coverage`.
+    pub fn route_coverage(&self) -> Vec<RouteCoverage> {
+        self.graph.route_coverage()
+    }
+
+    pub fn clear_hierarchy_stats(&mut self) {
+        self.hierarchy.clear_stats();
+        self.dram.clear_stats();
+        self.remote.clear_stats();
+        self.background.clear_stats();
+        self.fill_forward.clear_stats();
+        self.cache_hints.clear_stats();
+        self.injectors.clear_stats();
+        self.write_combining.clear_stats();
+        self.bank_activity.clear_stats();
+        self.data_ports.clear_stats();
     }
 
     pub fn hierarchy_stats(&self) -> GmemStats {
@@ -1279,4 +1976,23 @@ impl ClusterGmemGraph {
         };
         (l0_rate, l1_rate, l2_rate)
     }
+
+    /// Whether any cache level holds the line of `addr`.
+    #[cfg(test)]
+    pub(crate) fn caches_line(&self, addr: u64) -> bool {
+        let policy = self.policy;
+        let l0_line = line_addr(addr, policy.l0_line_bytes);
+        let l1_line = line_addr(addr, policy.l1_line_bytes);
+        let l2_line = line_addr(addr, policy.l2_line_bytes);
+        self.hierarchy
+            .l0
+            .iter()
+            .any(|layer| layer.tags.contains(l0_line))
+            || self
+                .hierarchy
+                .l1
+                .iter()
+                .any(|layer| layer.tags.contains(l1_line))
+            || self.hierarchy.l2.tags.contains(l2_line)
+    }
 }
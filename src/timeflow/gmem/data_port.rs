@@ -0,0 +1,298 @@
+use std::ops::AddAssign;
+
+use serde::Serialize;
+
+use crate::sim::checkpoint::Shared;
+use crate::timeflow::graph::TimedNode;
+use crate::timeflow::server_node::ServerNode;
+use crate::timeq::{
+    Backpressure, Cycle, ServerConfig, ServerStats, ServiceRequest, ServiceResult, Ticket,
+};
+
+/// How refills into one cache level took its data arrays away from hits.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct DataPortStats {
+    pub refills: u64,
+    /// Cycles the refills held a data array port.
+    pub stolen_cycles: u64,
+    pub hits: u64,
+    /// Hits that started later than they would have with refills on a port
+    /// of their own.
+    pub delayed_hits: u64,
+    /// Those cycles, summed over `hits`.
+    pub hit_delay_cycles: u64,
+    pub max_hit_delay: u64,
+}
+
+impl DataPortStats {
+    /// Cycles refill contention added to the average hit.
+    pub fn avg_hit_inflation(&self) -> f64 {
+        if self.hits == 0 {
+            0.0
+        } else {
+            self.hit_delay_cycles as f64 / self.hits as f64
+        }
+    }
+}
+
+impl AddAssign<&DataPortStats> for DataPortStats {
+    fn add_assign(&mut self, other: &DataPortStats) {
+        self.refills = self.refills.saturating_add(other.refills);
+        self.stolen_cycles = self.stolen_cycles.saturating_add(other.stolen_cycles);
+        self.hits = self.hits.saturating_add(other.hits);
+        self.delayed_hits = self.delayed_hits.saturating_add(other.delayed_hits);
+        self.hit_delay_cycles = self.hit_delay_cycles.saturating_add(other.hit_delay_cycles);
+        self.max_hit_delay = self.max_hit_delay.max(other.max_hit_delay);
+    }
+}
+
+/// Refill contention at the L1 and L2; shared by every core.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct RefillContention {
+    pub l1: DataPortStats,
+    pub l2: DataPortStats,
+}
+
+/// The data array port of one bank, shared by its data and refill nodes.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct DataPort {
+    /// Cycles each refill holds the port; 0 gives refills a port of their
+    /// own.
+    refill_cycles: u64,
+    /// Stolen cycles the data node has not charged to its server yet.
+    pending: u64,
+    stats: DataPortStats,
+}
+
+impl DataPort {
+    pub(crate) fn new(refill_cycles: u64) -> Self {
+        Self {
+            refill_cycles,
+            ..Self::default()
+        }
+    }
+
+    fn refill(&mut self) {
+        if self.refill_cycles == 0 {
+            return;
+        }
+        self.pending = self.pending.saturating_add(self.refill_cycles);
+        self.stats.refills = self.stats.refills.saturating_add(1);
+        self.stats.stolen_cycles = self.stats.stolen_cycles.saturating_add(self.refill_cycles);
+    }
+
+    fn record_hit(&mut self, delay: u64) {
+        self.stats.hits = self.stats.hits.saturating_add(1);
+        if delay > 0 {
+            self.stats.delayed_hits = self.stats.delayed_hits.saturating_add(1);
+            self.stats.hit_delay_cycles = self.stats.hit_delay_cycles.saturating_add(delay);
+            self.stats.max_hit_delay = self.stats.max_hit_delay.max(delay);
+        }
+    }
+}
+
+pub(crate) type DataPortHandle = Shared<DataPort>;
+
+/// The data array ports of every L1 and L2 bank, cluster by cluster for
+/// the L1.
+#[derive(Clone, Default)]
+pub(crate) struct DataPorts {
+    pub(crate) l1: Vec<DataPortHandle>,
+    pub(crate) l2: Vec<DataPortHandle>,
+}
+
+impl DataPorts {
+    pub(crate) fn set_refill_cycles(&self, l1: u64, l2: u64) {
+        for (ports, cycles) in [(&self.l1, l1), (&self.l2, l2)] {
+            for port in ports {
+                port.write().unwrap().refill_cycles = cycles;
+            }
+        }
+    }
+
+    pub(crate) fn stats(&self) -> RefillContention {
+        let sum = |ports: &[DataPortHandle]| {
+            let mut stats = DataPortStats::default();
+            for port in ports {
+                stats += &port.read().unwrap().stats;
+            }
+            stats
+        };
+        RefillContention {
+            l1: sum(&self.l1),
+            l2: sum(&self.l2),
+        }
+    }
+
+    pub(crate) fn clear_stats(&self) {
+        for port in self.l1.iter().chain(&self.l2) {
+            port.write().unwrap().stats = DataPortStats::default();
+        }
+    }
+}
+
+/// A bank's data array, serving hits. Cycles refills take the port for
+/// are queued behind the hits already accepted and push back later ones.
+#[derive(Clone)]
+pub(crate) struct DataArrayNode<T> {
+    inner: ServerNode<T>,
+    port: DataPortHandle,
+    bytes_per_cycle: u32,
+    /// When the array would be free again had no refill taken the port.
+    hit_free_at: Cycle,
+}
+
+impl<T> DataArrayNode<T> {
+    pub(crate) fn new(inner: ServerNode<T>, config: ServerConfig, port: DataPortHandle) -> Self {
+        Self {
+            inner,
+            port,
+            bytes_per_cycle: config.bytes_per_cycle,
+            hit_free_at: 0,
+        }
+    }
+}
+
+impl<T: Clone + Send + Sync + 'static> TimedNode<T> for DataArrayNode<T> {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn try_put(
+        &mut self,
+        now: Cycle,
+        request: ServiceRequest<T>,
+    ) -> Result<Ticket, Backpressure<T>> {
+        let start = self.inner.available_at().unwrap_or(now).max(now);
+        let ticket = self.inner.try_put(now, request)?;
+        let service = self
+            .inner
+            .available_at()
+            .unwrap_or(start)
+            .saturating_sub(start);
+        let free_start = self.hit_free_at.max(now);
+        self.hit_free_at = free_start.saturating_add(service);
+        self.port
+            .write()
+            .unwrap()
+            .record_hit(start.saturating_sub(free_start));
+        Ok(ticket)
+    }
+
+    fn tick(&mut self, now: Cycle) {
+        let stolen = std::mem::take(&mut self.port.write().unwrap().pending);
+        if stolen > 0 {
+            let bytes = stolen.saturating_mul(self.bytes_per_cycle as u64);
+            self.inner
+                .reserve_bandwidth(now, bytes.min(u32::MAX as u64) as u32);
+        }
+        self.inner.tick(now);
+    }
+
+    fn peek_ready(&mut self, now: Cycle) -> Option<&ServiceResult<T>> {
+        self.inner.peek_ready(now)
+    }
+
+    fn take_ready(&mut self, now: Cycle) -> Option<ServiceResult<T>> {
+        self.inner.take_ready(now)
+    }
+
+    fn outstanding(&self) -> usize {
+        self.inner.outstanding()
+    }
+
+    fn set_server_config(&mut self, config: ServerConfig) -> bool {
+        self.bytes_per_cycle = config.bytes_per_cycle;
+        self.inner.set_server_config(config)
+    }
+
+    fn reserve_bandwidth(&mut self, now: Cycle, bytes: u32) -> Option<Cycle> {
+        self.inner.reserve_bandwidth(now, bytes)
+    }
+
+    fn available_at(&self) -> Option<Cycle> {
+        self.inner.available_at()
+    }
+
+    fn server_stats(&self) -> Option<ServerStats> {
+        self.inner.server_stats()
+    }
+
+    fn squash(&mut self, pred: &mut dyn FnMut(&T, &Ticket) -> bool) -> usize {
+        self.inner.squash(pred)
+    }
+
+    fn clone_box(&self) -> Box<dyn TimedNode<T>> {
+        Box::new(self.clone())
+    }
+}
+
+/// A bank's refill path. Every line it accepts takes the data array port
+/// away from hits.
+#[derive(Clone)]
+pub(crate) struct RefillNode<T> {
+    inner: ServerNode<T>,
+    port: DataPortHandle,
+}
+
+impl<T> RefillNode<T> {
+    pub(crate) fn new(inner: ServerNode<T>, port: DataPortHandle) -> Self {
+        Self { inner, port }
+    }
+}
+
+impl<T: Clone + Send + Sync + 'static> TimedNode<T> for RefillNode<T> {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn try_put(
+        &mut self,
+        now: Cycle,
+        request: ServiceRequest<T>,
+    ) -> Result<Ticket, Backpressure<T>> {
+        let ticket = self.inner.try_put(now, request)?;
+        self.port.write().unwrap().refill();
+        Ok(ticket)
+    }
+
+    fn tick(&mut self, now: Cycle) {
+        self.inner.tick(now);
+    }
+
+    fn peek_ready(&mut self, now: Cycle) -> Option<&ServiceResult<T>> {
+        self.inner.peek_ready(now)
+    }
+
+    fn take_ready(&mut self, now: Cycle) -> Option<ServiceResult<T>> {
+        self.inner.take_ready(now)
+    }
+
+    fn outstanding(&self) -> usize {
+        self.inner.outstanding()
+    }
+
+    fn set_server_config(&mut self, config: ServerConfig) -> bool {
+        self.inner.set_server_config(config)
+    }
+
+    fn reserve_bandwidth(&mut self, now: Cycle, bytes: u32) -> Option<Cycle> {
+        self.inner.reserve_bandwidth(now, bytes)
+    }
+
+    fn available_at(&self) -> Option<Cycle> {
+        self.inner.available_at()
+    }
+
+    fn server_stats(&self) -> Option<ServerStats> {
+        self.inner.server_stats()
+    }
+
+    fn squash(&mut self, pred: &mut dyn FnMut(&T, &Ticket) -> bool) -> usize {
+        self.inner.squash(pred)
+    }
+
+    fn clone_box(&self) -> Box<dyn TimedNode<T>> {
+        Box::new(self.clone())
+    }
+}
@@ -0,0 +1,176 @@
+use serde::{Deserialize, Serialize};
+
+use crate::timeflow::graph::FlowGraph;
+use crate::timeflow::types::{CoreFlowPayload, NodeId};
+use crate::timeq::{Cycle, ServerStats};
+
+use super::background::BackgroundTraffic;
+use super::policy::bank_for;
+
+const DRAM_CHANNEL_SEED: u64 = 0x9999_aaaa_bbbb_cccc;
+
+/// How an L2 miss picks its DRAM channel.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DramInterleave {
+    /// Consecutive `1 << interleave_bit`-byte blocks go to consecutive
+    /// channels.
+    #[default]
+    Bits,
+    /// Blocks are hashed onto channels, so power-of-two strides do not all
+    /// land on one channel.
+    Hash,
+}
+
+/// DRAM channels behind the L2. Each channel is its own copy of
+/// `gmem.nodes.dram`, with its own queue and bandwidth.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct DramConfig {
+    pub channels: usize,
+    pub interleave: DramInterleave,
+    /// Lowest address bit of the channel index: the interleaving
+    /// granularity is `1 << interleave_bit` bytes.
+    pub interleave_bit: u32,
+}
+
+impl Default for DramConfig {
+    fn default() -> Self {
+        Self {
+            channels: 1,
+            interleave: DramInterleave::Bits,
+            interleave_bit: 8,
+        }
+    }
+}
+
+impl DramConfig {
+    pub fn channel_count(&self) -> usize {
+        self.channels.max(1)
+    }
+
+    pub fn channel_for(&self, addr: u64) -> usize {
+        let channels = self.channel_count() as u64;
+        if channels == 1 {
+            return 0;
+        }
+        let block = addr >> self.interleave_bit.min(63);
+        match self.interleave {
+            DramInterleave::Bits => (block % channels) as usize,
+            DramInterleave::Hash => bank_for(block, channels, DRAM_CHANNEL_SEED),
+        }
+    }
+}
+
+/// Traffic one DRAM channel carried since stats were last cleared.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct DramChannelStats {
+    pub cycles: u64,
+    /// Refills and writebacks the channel accepted.
+    pub requests: u64,
+    pub demand_bytes: u64,
+    /// DMA and tensor bytes charged to the channel by `gmem.background`.
+    pub background_bytes: u64,
+    pub queue_full_rejects: u64,
+    pub max_outstanding: u64,
+    /// Fraction of the channel's peak bandwidth that was used.
+    pub utilization: f64,
+}
+
+/// The DRAM channel nodes of a cluster graph and the stats they have
+/// accumulated. Background transfers have no address, so they are charged
+/// to the channels in turn, one per cycle.
+#[derive(Clone)]
+pub(crate) struct DramChannels {
+    config: DramConfig,
+    nodes: Vec<NodeId>,
+    bytes_per_cycle: u32,
+    cycles: u64,
+    next_background: usize,
+    latest: Vec<ServerStats>,
+    base: Vec<ServerStats>,
+    background_bytes: Vec<u64>,
+}
+
+impl DramChannels {
+    pub(crate) fn new(config: DramConfig, nodes: Vec<NodeId>, bytes_per_cycle: u32) -> Self {
+        let channels = nodes.len();
+        Self {
+            config,
+            nodes,
+            bytes_per_cycle,
+            cycles: 0,
+            next_background: 0,
+            latest: vec![ServerStats::default(); channels],
+            base: vec![ServerStats::default(); channels],
+            background_bytes: vec![0; channels],
+        }
+    }
+
+    pub(crate) fn channel_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub(crate) fn channel_for(&self, addr: u64) -> usize {
+        self.config.channel_for(addr)
+    }
+
+    pub(crate) fn set_bytes_per_cycle(&mut self, bytes_per_cycle: u32) {
+        self.bytes_per_cycle = bytes_per_cycle;
+    }
+
+    pub(crate) fn tick(
+        &mut self,
+        now: Cycle,
+        graph: &mut FlowGraph<CoreFlowPayload>,
+        background: &mut BackgroundTraffic,
+    ) {
+        self.cycles = self.cycles.saturating_add(1);
+        for (latest, &node) in self.latest.iter_mut().zip(&self.nodes) {
+            if let Some(stats) = graph.with_node_mut(node, |dram| dram.server_stats()) {
+                *latest = stats;
+            }
+        }
+        let demand_total = self.latest.iter().map(|stats| stats.bytes_issued).sum();
+        let channel = self.next_background;
+        let moved = graph.with_node_mut(self.nodes[channel], |dram| {
+            background.tick(now, demand_total, dram)
+        });
+        self.background_bytes[channel] = self.background_bytes[channel].saturating_add(moved);
+        self.next_background = (channel + 1) % self.nodes.len();
+    }
+
+    pub(crate) fn stats(&self) -> Vec<DramChannelStats> {
+        let peak = self.cycles.saturating_mul(self.bytes_per_cycle as u64);
+        (0..self.nodes.len())
+            .map(|channel| {
+                let latest = &self.latest[channel];
+                let base = &self.base[channel];
+                let demand_bytes = latest.bytes_issued.saturating_sub(base.bytes_issued);
+                let background_bytes = self.background_bytes[channel];
+                let utilization = if peak == 0 {
+                    0.0
+                } else {
+                    demand_bytes.saturating_add(background_bytes) as f64 / peak as f64
+                };
+                DramChannelStats {
+                    cycles: self.cycles,
+                    requests: latest.issued.saturating_sub(base.issued),
+                    demand_bytes,
+                    background_bytes,
+                    queue_full_rejects: latest
+                        .queue_full_rejects
+                        .saturating_sub(base.queue_full_rejects),
+                    max_outstanding: latest.max_outstanding,
+                    utilization,
+                }
+            })
+            .collect()
+    }
+
+    pub(crate) fn clear_stats(&mut self) {
+        self.base.clone_from(&self.latest);
+        self.cycles = 0;
+        self.background_bytes.fill(0);
+    }
+}
@@ -0,0 +1,143 @@
+use std::ops::AddAssign;
+
+use serde::{Deserialize, Serialize};
+
+use crate::timeq::Cycle;
+
+use super::request::GmemRequest;
+
+/// How fast a completed MSHR entry hands its fill to the requests merged
+/// into it.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(default)]
+pub struct FillForwardConfig {
+    /// Requests released per cycle from one completed entry, counting the
+    /// one that allocated it. 0 releases them all the cycle the fill lands.
+    pub requests_per_cycle: u32,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct FillForwardStats {
+    /// Completed entries that had requests merged into them.
+    pub fills: u64,
+    /// Merged requests released to their cores.
+    pub released: u64,
+    /// Of those, how many waited past the cycle their fill landed.
+    pub delayed: u64,
+    /// Cycles between fill and release, summed over `released`.
+    pub delay_cycles: u64,
+    pub max_delay: u64,
+    /// Most requests one fill completed, the allocating one included.
+    pub max_fanout: u64,
+}
+
+impl FillForwardStats {
+    pub fn avg_delay(&self) -> f64 {
+        if self.released == 0 {
+            0.0
+        } else {
+            self.delay_cycles as f64 / self.released as f64
+        }
+    }
+}
+
+impl AddAssign<&FillForwardStats> for FillForwardStats {
+    fn add_assign(&mut self, other: &FillForwardStats) {
+        self.fills = self.fills.saturating_add(other.fills);
+        self.released = self.released.saturating_add(other.released);
+        self.delayed = self.delayed.saturating_add(other.delayed);
+        self.delay_cycles = self.delay_cycles.saturating_add(other.delay_cycles);
+        self.max_delay = self.max_delay.max(other.max_delay);
+        self.max_fanout = self.max_fanout.max(other.max_fanout);
+    }
+}
+
+#[derive(Clone)]
+struct PendingRelease {
+    request: GmemRequest,
+    ticket_ready_at: Cycle,
+    filled_at: Cycle,
+    release_at: Cycle,
+}
+
+/// Requests merged into an MSHR entry, waiting for their turn on the fill
+/// path once the entry completes. Fill effects are applied when the fill
+/// lands; only the completions are held back.
+#[derive(Clone)]
+pub(crate) struct FillForward {
+    config: FillForwardConfig,
+    pending: Vec<PendingRelease>,
+    stats: FillForwardStats,
+}
+
+impl FillForward {
+    pub(crate) fn new(config: FillForwardConfig) -> Self {
+        Self {
+            config,
+            pending: Vec::new(),
+            stats: FillForwardStats::default(),
+        }
+    }
+
+    pub(crate) fn set_config(&mut self, config: FillForwardConfig) {
+        self.config = config;
+    }
+
+    /// Queues the requests merged into an entry whose fill landed at `now`.
+    /// The allocating request takes the first release slot.
+    pub(crate) fn schedule(
+        &mut self,
+        now: Cycle,
+        ticket_ready_at: Cycle,
+        merged: Vec<GmemRequest>,
+    ) {
+        if merged.is_empty() {
+            return;
+        }
+        self.stats.fills = self.stats.fills.saturating_add(1);
+        self.stats.max_fanout = self.stats.max_fanout.max(merged.len() as u64 + 1);
+        for (idx, request) in merged.into_iter().enumerate() {
+            let release_at = match self.config.requests_per_cycle {
+                0 => now,
+                width => now.saturating_add((idx as u64 + 1) / width as u64),
+            };
+            self.pending.push(PendingRelease {
+                request,
+                ticket_ready_at,
+                filled_at: now,
+                release_at,
+            });
+        }
+    }
+
+    /// Takes the requests due by `now`, oldest fill first, with the ticket
+    /// ready cycle of the request that allocated their entry.
+    pub(crate) fn pop_ready(&mut self, now: Cycle) -> Vec<(GmemRequest, Cycle)> {
+        let mut ready = Vec::new();
+        let mut idx = 0;
+        while idx < self.pending.len() {
+            if self.pending[idx].release_at > now {
+                idx += 1;
+                continue;
+            }
+            let release = self.pending.remove(idx);
+            let delay = now.saturating_sub(release.filled_at);
+            self.stats.released = self.stats.released.saturating_add(1);
+            self.stats.delay_cycles = self.stats.delay_cycles.saturating_add(delay);
+            self.stats.max_delay = self.stats.max_delay.max(delay);
+            if delay > 0 {
+                self.stats.delayed = self.stats.delayed.saturating_add(1);
+            }
+            ready.push((release.request, release.ticket_ready_at));
+        }
+        ready
+    }
+
+    pub(crate) fn stats(&self) -> FillForwardStats {
+        self.stats
+    }
+
+    pub(crate) fn clear_stats(&mut self) {
+        self.stats = FillForwardStats::default();
+    }
+}
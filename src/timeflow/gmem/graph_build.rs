@@ -1,19 +1,37 @@
 use serde::Deserialize;
 
+use crate::sim::checkpoint::Shared;
 use crate::timeflow::{
-    graph::{FlowGraph, Link},
+    crossbar::{CrossbarConfig, CrossbarNode, CrossbarStatsHandle},
+    graph::{FlowGraph, Link, StallInjection},
     server_node::ServerNode,
     types::{CoreFlowPayload, NodeId},
 };
 use crate::timeq::{ServerConfig, TimedServer};
 
+use super::adaptive::AdaptiveRoutingConfig;
+use super::background::BackgroundTrafficConfig;
+use super::cache_hint::CacheHintConfig;
+use super::data_port::{DataArrayNode, DataPort, DataPortHandle, DataPorts, RefillNode};
+use super::dram::DramConfig;
+use super::fill_forward::FillForwardConfig;
+use super::injector::{InjectorConfig, InjectorNodes};
 use super::policy::GmemPolicyConfig;
+use super::remote::{RemoteConfig, RemoteDieNodes};
+use super::salvage::{BankMap, SalvageConfig};
+use super::write_combining::WriteCombiningConfig;
 
 #[derive(Debug, Clone, Copy, Deserialize)]
 #[serde(default)]
 pub struct LinkConfig {
     pub entries: usize,
     pub bytes: Option<u32>,
+    /// Propagation delay in cycles between entering the link and reaching
+    /// the destination node.
+    pub latency: u64,
+    /// Entries the destination node takes per cycle through this link,
+    /// e.g. 1 for a single-ported structure; unlimited if unset.
+    pub accepts_per_cycle: Option<u32>,
 }
 
 impl Default for LinkConfig {
@@ -21,15 +39,22 @@ impl Default for LinkConfig {
         Self {
             entries: 16,
             bytes: None,
+            latency: 0,
+            accepts_per_cycle: None,
         }
     }
 }
 
 impl LinkConfig {
     fn build<T>(&self) -> Link<T> {
-        match self.bytes {
+        let link = match self.bytes {
             Some(limit) => Link::with_byte_limit(self.entries, Some(limit)),
             None => Link::new(self.entries),
+        };
+        let link = link.with_latency(self.latency);
+        match self.accepts_per_cycle {
+            Some(accepts) => link.with_accepts_per_cycle(accepts),
+            None => link,
         }
     }
 }
@@ -54,6 +79,10 @@ pub struct CacheLevelConfig {
     pub mshr: ServerConfig,
     pub refill: ServerConfig,
     pub writeback: ServerConfig,
+    /// Cycles each refilled line takes a bank's data array port away from
+    /// hits. 0 gives refills a port of their own. The L0 has no refill
+    /// path of its own and ignores it.
+    pub refill_port_cycles: u64,
 }
 
 impl Default for CacheLevelConfig {
@@ -66,6 +95,7 @@ impl Default for CacheLevelConfig {
             mshr: ServerConfig::default(),
             refill: ServerConfig::default(),
             writeback: ServerConfig::default(),
+            refill_port_cycles: 0,
         }
     }
 }
@@ -87,6 +117,7 @@ impl CacheLevelConfig {
             mshr,
             refill,
             writeback,
+            refill_port_cycles: 0,
         }
     }
 }
@@ -206,6 +237,29 @@ pub struct GmemFlowConfig {
     pub policy: GmemPolicyConfig,
     pub levels: Vec<CacheLevelConfig>,
     pub stats_range: Option<GmemStatsRange>,
+    /// Optional per-cluster crossbar between the cores and the L1 banks.
+    pub crossbar: CrossbarConfig,
+    /// DRAM channels and how L2 misses are interleaved across them.
+    pub dram: DramConfig,
+    /// DMA and tensor transfers contending with demand misses for DRAM.
+    pub background: BackgroundTrafficConfig,
+    /// Release rate of requests merged into a completed MSHR entry.
+    pub fill_forward: FillForwardConfig,
+    /// Cache operators for loads, by PC.
+    pub cache_hints: CacheHintConfig,
+    /// Synthetic L2 traffic standing in for clusters not simulated.
+    pub injectors: InjectorConfig,
+    /// Memory on other dies, reached by L2 misses to `remote` regions.
+    pub remote: RemoteConfig,
+    /// Charging and combining of L2 writebacks at the local DRAM.
+    pub write_combining: WriteCombiningConfig,
+    /// Cores and cache banks fused off.
+    pub salvage: SalvageConfig,
+    /// Duplicate return paths and adaptive routing between them.
+    pub adaptive_routing: AdaptiveRoutingConfig,
+    /// Nodes forced to refuse admissions for a window of cycles, to check
+    /// that the rest of the graph backpressures without losing requests.
+    pub stall_injection: Vec<StallInjection>,
 }
 
 #[derive(Debug, Clone, Copy, Deserialize)]
@@ -326,6 +380,17 @@ impl Default for GmemFlowConfig {
             policy: GmemPolicyConfig::default(),
             levels: default_levels(),
             stats_range: None,
+            crossbar: CrossbarConfig::default(),
+            dram: DramConfig::default(),
+            background: BackgroundTrafficConfig::default(),
+            fill_forward: FillForwardConfig::default(),
+            cache_hints: CacheHintConfig::default(),
+            injectors: InjectorConfig::default(),
+            remote: RemoteConfig::default(),
+            write_combining: WriteCombiningConfig::default(),
+            salvage: SalvageConfig::default(),
+            adaptive_routing: AdaptiveRoutingConfig::default(),
+            stall_injection: Vec::new(),
         }
     }
 }
@@ -360,11 +425,27 @@ impl GmemFlowConfig {
         cfg.links.default.entries = 8;
         cfg
     }
+
+    /// The L1 and L2 banks left after `salvage`. Panics if `salvage` names
+    /// a bank the level does not have, or disables all of them.
+    pub(crate) fn bank_maps(&self) -> (BankMap, BankMap) {
+        let bank_map = |key, level: usize, disabled| {
+            let banks = self.levels.get(level).map_or(1, |level| level.banks);
+            BankMap::new(key, banks, disabled).unwrap_or_else(|err| panic!("{err}"))
+        };
+        (
+            bank_map("disabled_l1_banks", 1, &self.salvage.disabled_l1_banks),
+            bank_map("disabled_l2_banks", 2, &self.salvage.disabled_l2_banks),
+        )
+    }
 }
 
 pub(crate) struct ClusterCoreNodes {
     pub(crate) ingress_node: NodeId,
-    pub(crate) return_node: NodeId,
+    /// One per `adaptive_routing.return_paths`.
+    pub(crate) return_nodes: Vec<NodeId>,
+    /// Stats of the cluster crossbar, shared by every core in the cluster.
+    pub(crate) crossbar_stats: Option<CrossbarStatsHandle>,
 }
 
 struct CacheLevelNodes {
@@ -377,42 +458,69 @@ struct CacheLevelNodes {
 
 struct ClusterL1State {
     l1_flush_gate: NodeId,
+    /// Where core-side requests enter the cluster: the crossbar when one is
+    /// configured, otherwise the flush gate.
+    l1_ingress: NodeId,
+    crossbar_stats: Option<CrossbarStatsHandle>,
     l1_data_nodes: Vec<NodeId>,
     l1_refill_nodes: Vec<NodeId>,
 }
 
+/// A read-modify-write reads the line out of the data array before writing
+/// the merged line back, so it occupies the array for two transfers.
+fn rmw_data_size(payload: &CoreFlowPayload, size_bytes: u32) -> u32 {
+    match payload {
+        CoreFlowPayload::Gmem(req) if req.rmw => size_bytes.saturating_mul(2),
+        _ => size_bytes,
+    }
+}
+
 fn build_cache_level_nodes(
     graph: &mut FlowGraph<CoreFlowPayload>,
     prefix: &str,
     level: &CacheLevelConfig,
+    banks: &[usize],
+    ports: &mut Vec<DataPortHandle>,
 ) -> CacheLevelNodes {
-    let banks = level.banks.max(1);
-    let mut tag_nodes = Vec::with_capacity(banks);
-    let mut data_nodes = Vec::with_capacity(banks);
-    let mut mshr_nodes = Vec::with_capacity(banks);
-    let mut refill_nodes = Vec::with_capacity(banks);
-    let mut wb_nodes = Vec::with_capacity(banks);
-    for bank in 0..banks {
+    let mut tag_nodes = Vec::with_capacity(banks.len());
+    let mut data_nodes = Vec::with_capacity(banks.len());
+    let mut mshr_nodes = Vec::with_capacity(banks.len());
+    let mut refill_nodes = Vec::with_capacity(banks.len());
+    let mut wb_nodes = Vec::with_capacity(banks.len());
+    // Nodes are named after the physical bank, so disabled banks leave gaps.
+    for &bank in banks {
         tag_nodes.push(graph.add_node(ServerNode::new(
             format!("{prefix}_tag_{bank}"),
             TimedServer::new(level.tag),
         )));
-        data_nodes.push(graph.add_node(ServerNode::new(
-            format!("{prefix}_data_{bank}"),
-            TimedServer::new(level.data),
-        )));
+        let port: DataPortHandle = Shared::new(DataPort::new(level.refill_port_cycles));
+        data_nodes.push(
+            graph.add_node(DataArrayNode::new(
+                ServerNode::new(
+                    format!("{prefix}_data_{bank}"),
+                    TimedServer::new(level.data),
+                )
+                .with_size_fn(rmw_data_size),
+                level.data,
+                port.clone(),
+            )),
+        );
         mshr_nodes.push(graph.add_node(ServerNode::new(
             format!("{prefix}_mshr_{bank}"),
             TimedServer::new(level.mshr),
         )));
-        refill_nodes.push(graph.add_node(ServerNode::new(
-            format!("{prefix}_refill_{bank}"),
-            TimedServer::new(level.refill),
+        refill_nodes.push(graph.add_node(RefillNode::new(
+            ServerNode::new(
+                format!("{prefix}_refill_{bank}"),
+                TimedServer::new(level.refill),
+            ),
+            port.clone(),
         )));
         wb_nodes.push(graph.add_node(ServerNode::new(
             format!("{prefix}_wb_{bank}"),
             TimedServer::new(level.writeback),
         )));
+        ports.push(port);
     }
     CacheLevelNodes {
         tag_nodes,
@@ -423,31 +531,107 @@ fn build_cache_level_nodes(
     }
 }
 
+/// An L2 writeback that opened a write burst occupies the channel for the
+/// burst on top of the miss it travels with.
+fn dram_service_size(payload: &CoreFlowPayload, size_bytes: u32) -> u32 {
+    match payload {
+        CoreFlowPayload::Gmem(req) => size_bytes.saturating_add(req.dram_write_bytes),
+        _ => size_bytes,
+    }
+}
+
+/// A single channel keeps the plain `dram` name older configs and logs use.
+fn dram_node_name(channel: usize, channels: usize) -> String {
+    if channels == 1 {
+        "dram".to_string()
+    } else {
+        format!("dram_{channel}")
+    }
+}
+
+/// Outbound link, memory and inbound link of each remote die, chained in
+/// that order. The L2 connects to the links in `build_cluster_l2`.
+fn build_remote_dies(
+    graph: &mut FlowGraph<CoreFlowPayload>,
+    links: &GmemLinkConfig,
+    remote: &RemoteConfig,
+) -> Vec<RemoteDieNodes> {
+    (1..=remote.dies)
+        .map(|die| {
+            let link_out = graph.add_node(ServerNode::new(
+                format!("die{die}_link_out"),
+                TimedServer::new(remote.link),
+            ));
+            let memory = graph.add_node(ServerNode::new(
+                format!("die{die}_dram"),
+                TimedServer::new(remote.memory),
+            ));
+            let link_in = graph.add_node(ServerNode::new(
+                format!("die{die}_link_in"),
+                TimedServer::new(remote.link),
+            ));
+            graph.connect(
+                link_out,
+                memory,
+                format!("die{die}_link_out->die{die}_dram"),
+                links.default.build(),
+            );
+            graph.connect(
+                memory,
+                link_in,
+                format!("die{die}_dram->die{die}_link_in"),
+                links.default.build(),
+            );
+            RemoteDieNodes {
+                link_out,
+                memory,
+                link_in,
+            }
+        })
+        .collect()
+}
+
 fn build_cluster_l2(
     graph: &mut FlowGraph<CoreFlowPayload>,
     nodes: &GmemNodeConfig,
     links: &GmemLinkConfig,
     level: &CacheLevelConfig,
+    l2_banks: &[usize],
+    dram: &DramConfig,
+    remote: &RemoteConfig,
+    ports: &mut Vec<DataPortHandle>,
 ) -> (
     Vec<NodeId>,
     Vec<NodeId>,
     Vec<NodeId>,
     Vec<NodeId>,
     Vec<NodeId>,
-    NodeId,
+    Vec<NodeId>,
+    Vec<RemoteDieNodes>,
 ) {
-    let l2_nodes = build_cache_level_nodes(graph, "l2", level);
-    let l2_banks = level.banks.max(1);
+    let l2_nodes = build_cache_level_nodes(graph, "l2", level, l2_banks, ports);
 
-    let dram_node = graph.add_node(ServerNode::new("dram", TimedServer::new(nodes.dram)));
+    let channels = dram.channel_count();
+    let dram_nodes: Vec<NodeId> = (0..channels)
+        .map(|channel| {
+            graph.add_node(
+                ServerNode::new(
+                    dram_node_name(channel, channels),
+                    TimedServer::new(nodes.dram),
+                )
+                .with_size_fn(dram_service_size),
+            )
+        })
+        .collect();
+    let remote_dies = build_remote_dies(graph, links, remote);
     let link = |cfg: Option<LinkConfig>| cfg.unwrap_or(links.default).build();
 
-    for bank in 0..l2_banks {
-        let tag_node = l2_nodes.tag_nodes[bank];
-        let data_node = l2_nodes.data_nodes[bank];
-        let mshr_node = l2_nodes.mshr_nodes[bank];
-        let refill_node = l2_nodes.refill_nodes[bank];
-        let wb_node = l2_nodes.wb_nodes[bank];
+    for (i, &bank) in l2_banks.iter().enumerate() {
+        let tag_node = l2_nodes.tag_nodes[i];
+        let data_node = l2_nodes.data_nodes[i];
+        let mshr_node = l2_nodes.mshr_nodes[i];
+        let refill_node = l2_nodes.refill_nodes[i];
+        let wb_node = l2_nodes.wb_nodes[i];
 
         graph.connect(
             tag_node,
@@ -472,34 +656,84 @@ fn build_cluster_l2(
             format!("l2_mshr_{bank}->l2_wb_{bank}"),
             link(links.l2_mshr_to_l2_writeback),
         );
-        graph.connect(
-            mshr_node,
-            dram_node,
-            format!("l2_mshr_{bank}->dram"),
-            link(links.l2_mshr_to_dram),
-        );
-        graph.set_route_fn(mshr_node, |payload| match payload {
+        for (channel, &dram_node) in dram_nodes.iter().enumerate() {
+            let dram_name = dram_node_name(channel, channels);
+            graph.connect(
+                mshr_node,
+                dram_node,
+                format!("l2_mshr_{bank}->{dram_name}"),
+                link(links.l2_mshr_to_dram),
+            );
+        }
+        // Remote dies come after the local channels, so die `d` is edge
+        // `channels + d` here and `channels + d - 1` out of the writeback.
+        for (index, die) in remote_dies.iter().enumerate() {
+            graph.connect(
+                mshr_node,
+                die.link_out,
+                format!("l2_mshr_{bank}->die{}_link_out", index + 1),
+                link(links.l2_mshr_to_dram),
+            );
+        }
+        graph.set_route_fn(mshr_node, move |payload| match payload {
             CoreFlowPayload::Gmem(req) if req.l2_writeback => 0,
+            CoreFlowPayload::Gmem(req) if req.die > 0 => channels + req.die,
+            CoreFlowPayload::Gmem(req) => 1 + req.dram_channel,
             _ => 1,
         });
-        graph.connect(
-            wb_node,
-            dram_node,
-            format!("l2_wb_{bank}->dram"),
-            link(links.l2_writeback_to_dram),
-        );
+        for (channel, &dram_node) in dram_nodes.iter().enumerate() {
+            let dram_name = dram_node_name(channel, channels);
+            graph.connect(
+                wb_node,
+                dram_node,
+                format!("l2_wb_{bank}->{dram_name}"),
+                link(links.l2_writeback_to_dram),
+            );
+        }
+        for (index, die) in remote_dies.iter().enumerate() {
+            graph.connect(
+                wb_node,
+                die.link_out,
+                format!("l2_wb_{bank}->die{}_link_out", index + 1),
+                link(links.l2_writeback_to_dram),
+            );
+        }
+        if channels > 1 || !remote_dies.is_empty() {
+            graph.set_route_fn(wb_node, move |payload| match payload {
+                CoreFlowPayload::Gmem(req) if req.die > 0 => channels + req.die - 1,
+                CoreFlowPayload::Gmem(req) => req.dram_channel,
+                _ => 0,
+            });
+        }
 
-        graph.connect(
-            dram_node,
-            refill_node,
-            format!("dram->l2_refill_{bank}"),
-            link(links.dram_to_l2_refill),
-        );
+        for (channel, &dram_node) in dram_nodes.iter().enumerate() {
+            let dram_name = dram_node_name(channel, channels);
+            graph.connect(
+                dram_node,
+                refill_node,
+                format!("{dram_name}->l2_refill_{bank}"),
+                link(links.dram_to_l2_refill),
+            );
+        }
+        for (index, die) in remote_dies.iter().enumerate() {
+            graph.connect(
+                die.link_in,
+                refill_node,
+                format!("die{}_link_in->l2_refill_{bank}", index + 1),
+                link(links.dram_to_l2_refill),
+            );
+        }
+    }
+    let returns = dram_nodes
+        .iter()
+        .copied()
+        .chain(remote_dies.iter().map(|die| die.link_in));
+    for node in returns {
+        graph.set_route_fn(node, |payload| match payload {
+            CoreFlowPayload::Gmem(req) => req.l2_bank,
+            _ => 0,
+        });
     }
-    graph.set_route_fn(dram_node, |payload| match payload {
-        CoreFlowPayload::Gmem(req) => req.l2_bank,
-        _ => 0,
-    });
 
     (
         l2_nodes.tag_nodes,
@@ -507,7 +741,8 @@ fn build_cluster_l2(
         l2_nodes.mshr_nodes,
         l2_nodes.refill_nodes,
         l2_nodes.wb_nodes,
-        dram_node,
+        dram_nodes,
+        remote_dies,
     )
 }
 
@@ -515,26 +750,35 @@ fn build_cluster_l1(
     graph: &mut FlowGraph<CoreFlowPayload>,
     nodes: &GmemNodeConfig,
     links: &GmemLinkConfig,
+    crossbar: &CrossbarConfig,
     level: &CacheLevelConfig,
-    l2_banks: usize,
+    l1_banks: &[usize],
+    l2_banks: &[usize],
     cores_per_cluster: usize,
+    return_paths: usize,
     cluster_id: usize,
     l2_tag_nodes: &[NodeId],
+    ports: &mut Vec<DataPortHandle>,
 ) -> ClusterL1State {
     let l1_flush_gate = graph.add_node(ServerNode::new(
         format!("cluster{cluster_id}_l1_flush_gate"),
         TimedServer::new(nodes.l1_flush_gate),
     ));
 
-    let l1_nodes = build_cache_level_nodes(graph, &format!("cluster{cluster_id}_l1"), level);
-    let l1_banks = level.banks.max(1);
+    let l1_nodes = build_cache_level_nodes(
+        graph,
+        &format!("cluster{cluster_id}_l1"),
+        level,
+        l1_banks,
+        ports,
+    );
 
     let link = |cfg: Option<LinkConfig>| cfg.unwrap_or(links.default).build();
-    for bank in 0..l1_banks {
-        let tag_node = l1_nodes.tag_nodes[bank];
-        let data_node = l1_nodes.data_nodes[bank];
-        let mshr_node = l1_nodes.mshr_nodes[bank];
-        let wb_node = l1_nodes.wb_nodes[bank];
+    for (index, &bank) in l1_banks.iter().enumerate() {
+        let tag_node = l1_nodes.tag_nodes[index];
+        let data_node = l1_nodes.data_nodes[index];
+        let mshr_node = l1_nodes.mshr_nodes[index];
+        let wb_node = l1_nodes.wb_nodes[index];
 
         graph.connect(
             l1_flush_gate,
@@ -567,8 +811,7 @@ fn build_cluster_l1(
             link(links.l1_mshr_to_l1_writeback),
         );
 
-        for l2_bank in 0..l2_banks {
-            let l2_tag = l2_tag_nodes[l2_bank];
+        for (&l2_tag, l2_bank) in l2_tag_nodes.iter().zip(l2_banks) {
             graph.connect(
                 mshr_node,
                 l2_tag,
@@ -595,7 +838,7 @@ fn build_cluster_l1(
 
     let base_core = cluster_id.saturating_mul(cores_per_cluster);
     let max_core = cores_per_cluster.saturating_sub(1);
-    let l1_banks = l1_banks.max(1);
+    let l1_banks = l1_banks.len();
     graph.set_route_fn(l1_flush_gate, move |payload| match payload {
         CoreFlowPayload::Gmem(req) if req.kind.is_mem() => req.l1_bank,
         CoreFlowPayload::Gmem(req) if req.kind.is_flush_l1() => {
@@ -605,13 +848,43 @@ fn build_cluster_l1(
             } else {
                 local
             };
-            l1_banks + local
+            l1_banks + local * return_paths
         }
         _ => 0,
     });
 
+    let (l1_ingress, crossbar_stats) = if crossbar.enabled {
+        let xbar = CrossbarNode::new(
+            format!("cluster{cluster_id}_xbar"),
+            *crossbar,
+            cores_per_cluster,
+            l1_banks,
+            move |payload: &CoreFlowPayload| match payload {
+                CoreFlowPayload::Gmem(req) => Some(req.core_id.saturating_sub(base_core)),
+                _ => None,
+            },
+            |payload: &CoreFlowPayload| match payload {
+                CoreFlowPayload::Gmem(req) if req.kind.is_mem() => Some(req.l1_bank),
+                _ => None,
+            },
+        );
+        let stats = xbar.stats_handle();
+        let xbar = graph.add_node(xbar);
+        graph.connect(
+            xbar,
+            l1_flush_gate,
+            format!("cluster{cluster_id}_xbar->l1_flush"),
+            link(None),
+        );
+        (xbar, Some(stats))
+    } else {
+        (l1_flush_gate, None)
+    };
+
     ClusterL1State {
         l1_flush_gate,
+        l1_ingress,
+        crossbar_stats,
         l1_data_nodes: l1_nodes.data_nodes,
         l1_refill_nodes: l1_nodes.refill_nodes,
     }
@@ -623,19 +896,21 @@ fn connect_cluster_l2_to_l1_refills(
     l2_data_nodes: &[NodeId],
     l2_refill_nodes: &[NodeId],
     cluster_l1: &[ClusterL1State],
-    l1_banks: usize,
-    l2_banks: usize,
+    l1_banks: &[usize],
+    l2_banks: &[usize],
     num_clusters: usize,
+    injector_sink: Option<NodeId>,
 ) {
     let link = |cfg: Option<LinkConfig>| cfg.unwrap_or(links.default).build();
-    let l1_banks = l1_banks.max(1);
-    for l2_bank in 0..l2_banks {
-        let l2_data = l2_data_nodes[l2_bank];
-        let l2_refill = l2_refill_nodes[l2_bank];
+    // Injected requests carry a cluster id past the simulated ones and
+    // leave over one more edge, to the sink.
+    let sink_edge = injector_sink.map(|_| num_clusters * l1_banks.len());
+    for (index, &l2_bank) in l2_banks.iter().enumerate() {
+        let l2_data = l2_data_nodes[index];
+        let l2_refill = l2_refill_nodes[index];
         for cluster_id in 0..num_clusters {
             let l1_refill_nodes = &cluster_l1[cluster_id].l1_refill_nodes;
-            for l1_bank in 0..l1_banks {
-                let l1_refill = l1_refill_nodes[l1_bank];
+            for (&l1_refill, l1_bank) in l1_refill_nodes.iter().zip(l1_banks) {
                 graph.connect(
                     l2_data,
                     l1_refill,
@@ -650,11 +925,28 @@ fn connect_cluster_l2_to_l1_refills(
                 );
             }
         }
+        if let Some(sink) = injector_sink {
+            graph.connect(
+                l2_data,
+                sink,
+                format!("l2_hit_{l2_bank}->l2_injector_sink"),
+                link(links.l2_hit_to_l1_refill),
+            );
+            graph.connect(
+                l2_refill,
+                sink,
+                format!("l2_refill_{l2_bank}->l2_injector_sink"),
+                link(links.l2_refill_to_l1_refill),
+            );
+        }
 
-        let l1_banks = l1_banks;
+        let l1_banks = l1_banks.len();
         let num_clusters = num_clusters;
         graph.set_route_fn(l2_data, move |payload| match payload {
             CoreFlowPayload::Gmem(req) => {
+                if let Some(edge) = sink_edge.filter(|_| req.cluster_id >= num_clusters) {
+                    return edge;
+                }
                 if num_clusters == 0 {
                     return 0;
                 }
@@ -669,6 +961,9 @@ fn connect_cluster_l2_to_l1_refills(
         let num_clusters = num_clusters;
         graph.set_route_fn(l2_refill, move |payload| match payload {
             CoreFlowPayload::Gmem(req) => {
+                if let Some(edge) = sink_edge.filter(|_| req.cluster_id >= num_clusters) {
+                    return edge;
+                }
                 if num_clusters == 0 {
                     return 0;
                 }
@@ -687,14 +982,16 @@ fn build_cluster_core_nodes(
     links: &GmemLinkConfig,
     l0_level: Option<&CacheLevelConfig>,
     cluster_l1: &[ClusterL1State],
-    l1_banks: usize,
+    l1_banks: &[usize],
     num_clusters: usize,
     cores_per_cluster: usize,
     l0_enabled: bool,
+    adaptive: &AdaptiveRoutingConfig,
 ) -> Vec<ClusterCoreNodes> {
     let link = |cfg: Option<LinkConfig>| cfg.unwrap_or(links.default).build();
     let total_cores = num_clusters.saturating_mul(cores_per_cluster);
     let mut core_nodes = Vec::with_capacity(total_cores);
+    let paths = adaptive.return_paths();
 
     for cluster_id in 0..num_clusters {
         let base_core = cluster_id.saturating_mul(cores_per_cluster);
@@ -706,30 +1003,41 @@ fn build_cluster_core_nodes(
                 format!("cluster{cluster_id}_core{local_core}_coalescer"),
                 TimedServer::new(nodes.coalescer),
             ));
-            let l0_flush_gate = graph.add_node(ServerNode::new(
-                format!("cluster{cluster_id}_core{local_core}_l0_flush_gate"),
-                TimedServer::new(nodes.l0_flush_gate),
-            ));
-            let l0_level = l0_level.expect("gmem.levels[0] (l0) missing");
-            let l0_tag = graph.add_node(ServerNode::new(
-                format!("cluster{cluster_id}_core{local_core}_l0d_tag"),
-                TimedServer::new(l0_level.tag),
-            ));
-            let l0_data = graph.add_node(ServerNode::new(
-                format!("cluster{cluster_id}_core{local_core}_l0d_data"),
-                TimedServer::new(l0_level.data),
-            ));
-            let l0_mshr = graph.add_node(ServerNode::new(
-                format!("cluster{cluster_id}_core{local_core}_l0d_mshr"),
-                TimedServer::new(l0_level.mshr),
-            ));
-
-            let return_node = graph.add_node(ServerNode::new(
-                format!("cluster{cluster_id}_core{local_core}_return"),
-                TimedServer::new(nodes.return_path),
-            ));
+            let return_nodes: Vec<NodeId> = (0..paths)
+                .map(|path| {
+                    graph.add_node(ServerNode::new(
+                        format!(
+                            "cluster{cluster_id}_core{local_core}_{}",
+                            adaptive.return_name(path)
+                        ),
+                        TimedServer::new(nodes.return_path),
+                    ))
+                })
+                .collect();
 
+            // The L0 nodes only exist when the L0 is enabled.
             if l0_enabled {
+                let l0_flush_gate = graph.add_node(ServerNode::new(
+                    format!("cluster{cluster_id}_core{local_core}_l0_flush_gate"),
+                    TimedServer::new(nodes.l0_flush_gate),
+                ));
+                let l0_level = l0_level.expect("gmem.levels[0] (l0) missing");
+                let l0_tag = graph.add_node(ServerNode::new(
+                    format!("cluster{cluster_id}_core{local_core}_l0d_tag"),
+                    TimedServer::new(l0_level.tag),
+                ));
+                let l0_data = graph.add_node(
+                    ServerNode::new(
+                        format!("cluster{cluster_id}_core{local_core}_l0d_data"),
+                        TimedServer::new(l0_level.data),
+                    )
+                    .with_size_fn(rmw_data_size),
+                );
+                let l0_mshr = graph.add_node(ServerNode::new(
+                    format!("cluster{cluster_id}_core{local_core}_l0d_mshr"),
+                    TimedServer::new(l0_level.mshr),
+                ));
+
                 graph.connect(
                     ingress_node,
                     l0_flush_gate,
@@ -737,15 +1045,20 @@ fn build_cluster_core_nodes(
                     link(links.coalescer_to_l0_flush),
                 );
 
+                for (path, &return_node) in return_nodes.iter().enumerate() {
+                    graph.connect(
+                        l0_flush_gate,
+                        return_node,
+                        format!(
+                            "cluster{cluster_id}_core{local_core}_l0_flush->{}",
+                            adaptive.return_name(path)
+                        ),
+                        link(links.l0_flush_to_return),
+                    );
+                }
                 graph.connect(
                     l0_flush_gate,
-                    return_node,
-                    format!("cluster{cluster_id}_core{local_core}_l0_flush->return"),
-                    link(links.l0_flush_to_return),
-                );
-                graph.connect(
-                    l0_flush_gate,
-                    cluster_state.l1_flush_gate,
+                    cluster_state.l1_ingress,
                     format!("cluster{cluster_id}_core{local_core}_l0_flush->l1_flush"),
                     link(links.l0_flush_to_l1_flush),
                 );
@@ -755,11 +1068,12 @@ fn build_cluster_core_nodes(
                     format!("cluster{cluster_id}_core{local_core}_l0_flush->l0_tag"),
                     link(links.l0_flush_to_l0_tag),
                 );
-                graph.set_route_fn(l0_flush_gate, |payload| match payload {
+                graph.set_route_fn(l0_flush_gate, move |payload| match payload {
                     CoreFlowPayload::Gmem(req) if req.kind.is_flush_l0() => 0,
-                    CoreFlowPayload::Gmem(req) if req.kind.is_flush_l1() => 1,
-                    _ => 2,
+                    CoreFlowPayload::Gmem(req) if req.kind.is_flush_l1() => paths,
+                    _ => paths + 1,
                 });
+                adaptive.route_returns(graph, l0_flush_gate, 0, 1);
 
                 graph.connect(
                     l0_tag,
@@ -778,108 +1092,211 @@ fn build_cluster_core_nodes(
                     _ => 1,
                 });
 
-                graph.connect(
-                    l0_data,
-                    return_node,
-                    format!("cluster{cluster_id}_core{local_core}_l0_hit->return"),
-                    link(links.l0_hit_to_return),
-                );
+                for (path, &return_node) in return_nodes.iter().enumerate() {
+                    graph.connect(
+                        l0_data,
+                        return_node,
+                        format!(
+                            "cluster{cluster_id}_core{local_core}_l0_hit->{}",
+                            adaptive.return_name(path)
+                        ),
+                        link(links.l0_hit_to_return),
+                    );
+                }
+                if paths > 1 {
+                    graph.set_route_fn(l0_data, |_| 0);
+                    adaptive.route_returns(graph, l0_data, 0, 1);
+                }
                 graph.connect(
                     l0_mshr,
-                    cluster_state.l1_flush_gate,
+                    cluster_state.l1_ingress,
                     format!("cluster{cluster_id}_core{local_core}_l0_mshr->l1_flush"),
                     link(links.l0_mshr_to_l1_flush),
                 );
             } else {
+                for (path, &return_node) in return_nodes.iter().enumerate() {
+                    graph.connect(
+                        ingress_node,
+                        return_node,
+                        format!(
+                            "cluster{cluster_id}_core{local_core}_coalescer->{}_flush_l0",
+                            adaptive.return_name(path)
+                        ),
+                        link(links.coalescer_to_l0_flush),
+                    );
+                }
                 graph.connect(
                     ingress_node,
-                    return_node,
-                    format!("cluster{cluster_id}_core{local_core}_coalescer->return_flush_l0"),
-                    link(links.coalescer_to_l0_flush),
-                );
-                graph.connect(
-                    ingress_node,
-                    cluster_state.l1_flush_gate,
+                    cluster_state.l1_ingress,
                     format!("cluster{cluster_id}_core{local_core}_coalescer->l1_flush"),
                     link(links.coalescer_to_l0_flush),
                 );
-                graph.set_route_fn(ingress_node, |payload| match payload {
+                graph.set_route_fn(ingress_node, move |payload| match payload {
                     CoreFlowPayload::Gmem(req) if req.kind.is_flush_l0() => 0,
-                    _ => 1,
+                    _ => paths,
                 });
+                adaptive.route_returns(graph, ingress_node, 0, 1);
             }
 
-            graph.connect(
-                cluster_state.l1_flush_gate,
-                return_node,
-                format!("cluster{cluster_id}_core{local_core}_l1_flush->return"),
-                link(links.l1_flush_to_return),
-            );
+            for (path, &return_node) in return_nodes.iter().enumerate() {
+                graph.connect(
+                    cluster_state.l1_flush_gate,
+                    return_node,
+                    format!(
+                        "cluster{cluster_id}_core{local_core}_l1_flush->{}",
+                        adaptive.return_name(path)
+                    ),
+                    link(links.l1_flush_to_return),
+                );
+            }
 
-            cluster_returns.push(return_node);
+            cluster_returns.push(return_nodes.clone());
             core_nodes.push(ClusterCoreNodes {
                 ingress_node,
-                return_node,
+                return_nodes,
+                crossbar_stats: cluster_state.crossbar_stats.clone(),
             });
         }
+        adaptive.route_returns(
+            graph,
+            cluster_state.l1_flush_gate,
+            l1_banks.len(),
+            cores_per_cluster,
+        );
 
-        for bank in 0..l1_banks {
-            let l1_data = cluster_state.l1_data_nodes[bank];
-            let l1_refill = cluster_state.l1_refill_nodes[bank];
-            for (local_core, &return_node) in cluster_returns.iter().enumerate() {
-                graph.connect(
-                    l1_data,
-                    return_node,
-                    format!("cluster{cluster_id}_core{local_core}_l1_hit_{bank}->return"),
-                    link(links.l1_hit_to_return),
-                );
-                graph.connect(
-                    l1_refill,
-                    return_node,
-                    format!("cluster{cluster_id}_core{local_core}_l1_refill_{bank}->return"),
-                    link(links.l1_refill_to_return),
-                );
-            }
-
-            let base_core = base_core;
-            let cores_per_cluster = cores_per_cluster;
-            graph.set_route_fn(l1_data, move |payload| match payload {
-                CoreFlowPayload::Gmem(req) => {
-                    let local = req.core_id.saturating_sub(base_core);
-                    if local >= cores_per_cluster {
-                        0
-                    } else {
-                        local
-                    }
+        for (index, &bank) in l1_banks.iter().enumerate() {
+            let l1_data = cluster_state.l1_data_nodes[index];
+            let l1_refill = cluster_state.l1_refill_nodes[index];
+            for (local_core, return_nodes) in cluster_returns.iter().enumerate() {
+                for (path, &return_node) in return_nodes.iter().enumerate() {
+                    let return_name = adaptive.return_name(path);
+                    graph.connect(
+                        l1_data,
+                        return_node,
+                        format!(
+                            "cluster{cluster_id}_core{local_core}_l1_hit_{bank}->{return_name}"
+                        ),
+                        link(links.l1_hit_to_return),
+                    );
+                    graph.connect(
+                        l1_refill,
+                        return_node,
+                        format!(
+                            "cluster{cluster_id}_core{local_core}_l1_refill_{bank}->{return_name}"
+                        ),
+                        link(links.l1_refill_to_return),
+                    );
                 }
-                _ => 0,
-            });
+            }
 
-            let base_core = base_core;
-            let cores_per_cluster = cores_per_cluster;
-            graph.set_route_fn(l1_refill, move |payload| match payload {
+            // Banks take turns as the first choice of each core's paths.
+            let path = index % paths;
+            let route = move |payload: &CoreFlowPayload| match payload {
                 CoreFlowPayload::Gmem(req) => {
                     let local = req.core_id.saturating_sub(base_core);
                     if local >= cores_per_cluster {
-                        0
+                        path
                     } else {
-                        local
+                        local * paths + path
                     }
                 }
-                _ => 0,
-            });
+                _ => path,
+            };
+            graph.set_route_fn(l1_data, route);
+            graph.set_route_fn(l1_refill, route);
+            adaptive.route_returns(graph, l1_data, 0, cores_per_cluster);
+            adaptive.route_returns(graph, l1_refill, 0, cores_per_cluster);
         }
     }
 
     core_nodes
 }
 
+/// Re-applies the server timings in `config` to a graph built by
+/// `build_cluster_graph`, matching nodes by the names given there. Bank
+/// counts, links, cache geometry and policy are fixed at build time and are
+/// not touched.
+pub(crate) fn retime_cluster_graph(
+    graph: &mut FlowGraph<CoreFlowPayload>,
+    config: &GmemFlowConfig,
+) {
+    let nodes = &config.nodes;
+    graph.set_server_config(
+        |name| name == "dram" || name.strip_prefix("dram_").is_some_and(is_channel_index),
+        nodes.dram,
+    );
+    let remote = &config.remote;
+    graph.set_server_config(|name| is_die_node(name, "link_out"), remote.link);
+    graph.set_server_config(|name| is_die_node(name, "link_in"), remote.link);
+    graph.set_server_config(|name| is_die_node(name, "dram"), remote.memory);
+    graph.set_server_config(|name| name.ends_with("_coalescer"), nodes.coalescer);
+    graph.set_server_config(
+        |name| {
+            name.ends_with("_return")
+                || name
+                    .rsplit_once("_return_")
+                    .is_some_and(|(_, path)| is_channel_index(path))
+        },
+        nodes.return_path,
+    );
+    graph.set_server_config(|name| name.ends_with("_l0_flush_gate"), nodes.l0_flush_gate);
+    graph.set_server_config(|name| name.ends_with("_l1_flush_gate"), nodes.l1_flush_gate);
+
+    let levels = &config.levels;
+    if let Some(l0) = levels.first() {
+        for (suffix, server) in [("tag", l0.tag), ("data", l0.data), ("mshr", l0.mshr)] {
+            let suffix = format!("_l0d_{suffix}");
+            graph.set_server_config(|name| name.ends_with(&suffix), server);
+        }
+    }
+    for (prefix, level) in ["l1", "l2"].into_iter().zip(levels.iter().skip(1)) {
+        for (kind, server) in [
+            ("tag", level.tag),
+            ("data", level.data),
+            ("mshr", level.mshr),
+            ("refill", level.refill),
+            ("wb", level.writeback),
+        ] {
+            let stem = format!("{prefix}_{kind}_");
+            graph.set_server_config(|name| strip_cluster(name).starts_with(&stem), server);
+        }
+    }
+}
+
+/// Whether `name` is `die{N}_{node}`, a node of a remote die.
+fn is_die_node(name: &str, node: &str) -> bool {
+    name.strip_prefix("die")
+        .and_then(|rest| rest.split_once('_'))
+        .is_some_and(|(die, rest)| is_channel_index(die) && rest == node)
+}
+
+fn is_channel_index(suffix: &str) -> bool {
+    !suffix.is_empty() && suffix.chars().all(|c| c.is_ascii_digit())
+}
+
+/// Drops the `cluster{N}_` prefix that per-cluster node names carry.
+fn strip_cluster(name: &str) -> &str {
+    name.strip_prefix("cluster")
+        .and_then(|rest| rest.split_once('_'))
+        .filter(|(id, _)| id.chars().all(|c| c.is_ascii_digit()))
+        .map_or(name, |(_, rest)| rest)
+}
+
 pub(crate) fn build_cluster_graph(
     config: &GmemFlowConfig,
     num_clusters: usize,
     cores_per_cluster: usize,
-) -> (FlowGraph<CoreFlowPayload>, Vec<ClusterCoreNodes>) {
+) -> (
+    FlowGraph<CoreFlowPayload>,
+    Vec<ClusterCoreNodes>,
+    Vec<NodeId>,
+    Vec<RemoteDieNodes>,
+    DataPorts,
+    InjectorNodes,
+) {
     let mut graph = FlowGraph::new();
+    graph.set_age_threshold(Some(config.policy.retry_age_threshold));
+    graph.set_misroute_action(config.policy.misroute);
     let nodes = &config.nodes;
     let links = &config.links;
     let levels = &config.levels;
@@ -890,10 +1307,20 @@ pub(crate) fn build_cluster_graph(
     let l0_level = Some(&levels[0]);
     let l1_level = &levels[1];
     let l2_level = &levels[2];
-    let l1_banks = l1_level.banks.max(1);
-    let l2_banks = l2_level.banks.max(1);
-    let (l2_tag_nodes, l2_data_nodes, _l2_mshr_nodes, l2_refill_nodes, _l2_wb_nodes, _dram) =
-        build_cluster_l2(&mut graph, nodes, links, l2_level);
+    let (l1_banks, l2_banks) = config.bank_maps();
+    let (l1_banks, l2_banks) = (l1_banks.enabled(), l2_banks.enabled());
+    let mut ports = DataPorts::default();
+    let (l2_tag_nodes, l2_data_nodes, _l2_mshr_nodes, l2_refill_nodes, _l2_wb_nodes, dram, remote) =
+        build_cluster_l2(
+            &mut graph,
+            nodes,
+            links,
+            l2_level,
+            l2_banks,
+            &config.dram,
+            &config.remote,
+            &mut ports.l2,
+        );
 
     let mut cluster_l1 = Vec::with_capacity(num_clusters);
     for cluster_id in 0..num_clusters {
@@ -901,14 +1328,31 @@ pub(crate) fn build_cluster_graph(
             &mut graph,
             nodes,
             links,
+            &config.crossbar,
             l1_level,
+            l1_banks,
             l2_banks,
             cores_per_cluster,
+            config.adaptive_routing.return_paths(),
             cluster_id,
             &l2_tag_nodes,
+            &mut ports.l1,
         ));
     }
 
+    let injectors = if config.injectors.clusters > 0 {
+        let sink = graph.add_node(ServerNode::new(
+            "l2_injector_sink".to_string(),
+            TimedServer::new(nodes.return_path),
+        ));
+        InjectorNodes {
+            l2_tags: l2_tag_nodes.clone(),
+            sink: Some(sink),
+        }
+    } else {
+        InjectorNodes::default()
+    };
+
     connect_cluster_l2_to_l1_refills(
         &mut graph,
         links,
@@ -918,6 +1362,7 @@ pub(crate) fn build_cluster_graph(
         l1_banks,
         l2_banks,
         num_clusters,
+        injectors.sink,
     );
 
     let core_nodes = build_cluster_core_nodes(
@@ -930,7 +1375,15 @@ pub(crate) fn build_cluster_graph(
         num_clusters,
         cores_per_cluster,
         config.policy.l0_enabled && l0_level.is_some(),
+        &config.adaptive_routing,
     );
 
-    (graph, core_nodes)
+    for stall in &config.stall_injection {
+        let node = graph
+            .find_node(&stall.node)
+            .unwrap_or_else(|| panic!("gmem.stall_injection names unknown node {:?}", stall.node));
+        graph.inject_stall(node, stall.start..stall.start.saturating_add(stall.cycles));
+    }
+
+    (graph, core_nodes, dram, remote, ports, injectors)
 }
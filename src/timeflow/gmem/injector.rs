@@ -0,0 +1,268 @@
+use std::collections::HashMap;
+use std::ops::AddAssign;
+
+use serde::{Deserialize, Serialize};
+
+use crate::timeflow::types::NodeId;
+use crate::timeq::Cycle;
+
+use super::policy::{decide, hash_u64};
+use super::request::GmemRequest;
+
+/// Addresses an injector walks within its footprint.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InjectorPattern {
+    /// Back-to-back requests, wrapping at the end of the footprint.
+    #[default]
+    Stream,
+    /// Requests `stride` bytes apart, wrapping at the end of the footprint.
+    Strided,
+    /// A uniformly random request-sized block of the footprint each time.
+    Random,
+}
+
+/// `[gmem.injectors]`: clusters of the full chip that are not simulated.
+/// Each is replaced by an injector offering synthetic requests straight to
+/// the L2 tag banks, so the simulated clusters see the L2 and DRAM
+/// contention of the whole chip.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct InjectorConfig {
+    /// Clusters replaced by injectors; 0 models only the simulated ones.
+    pub clusters: usize,
+    /// Requests each injector offers per cycle; fractions carry over.
+    pub requests_per_cycle: f64,
+    pub pattern: InjectorPattern,
+    /// Bytes per request.
+    pub bytes: u32,
+    /// Distance between requests under `strided`.
+    pub stride: u64,
+    /// Fraction of the requests that are stores.
+    pub store_fraction: f64,
+    /// Injector `i` covers `footprint` bytes from `base + i * footprint`.
+    pub base: u64,
+    pub footprint: u64,
+    /// Requests one injector keeps in flight at most, like a cluster's
+    /// L1 MSHRs; offers past it are dropped.
+    pub max_outstanding: u32,
+    pub seed: u64,
+}
+
+impl Default for InjectorConfig {
+    fn default() -> Self {
+        Self {
+            clusters: 0,
+            requests_per_cycle: 0.1,
+            pattern: InjectorPattern::Stream,
+            bytes: 64,
+            stride: 256,
+            store_fraction: 0.0,
+            base: 0xc000_0000,
+            footprint: 1 << 20,
+            max_outstanding: 32,
+            seed: 0,
+        }
+    }
+}
+
+/// Requests of every injector together; shared by every core.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct InjectorStats {
+    pub issued: u64,
+    pub stores: u64,
+    /// Offers dropped at `max_outstanding` or a full L2 tag queue.
+    pub dropped: u64,
+    pub completed: u64,
+    pub l2_hits: u64,
+    /// Issue to completion, summed over `completed`.
+    pub latency_sum: u64,
+    pub max_latency: u64,
+}
+
+impl InjectorStats {
+    pub fn avg_latency(&self) -> f64 {
+        if self.completed == 0 {
+            0.0
+        } else {
+            self.latency_sum as f64 / self.completed as f64
+        }
+    }
+}
+
+impl AddAssign<&InjectorStats> for InjectorStats {
+    fn add_assign(&mut self, other: &InjectorStats) {
+        self.issued = self.issued.saturating_add(other.issued);
+        self.stores = self.stores.saturating_add(other.stores);
+        self.dropped = self.dropped.saturating_add(other.dropped);
+        self.completed = self.completed.saturating_add(other.completed);
+        self.l2_hits = self.l2_hits.saturating_add(other.l2_hits);
+        self.latency_sum = self.latency_sum.saturating_add(other.latency_sum);
+        self.max_latency = self.max_latency.max(other.max_latency);
+    }
+}
+
+/// Where injected requests enter and leave the graph: each L2 bank's tag
+/// node, by dense bank index, and a sink the L2 hands them back to in
+/// place of an L1 refill. Built only when `clusters` is nonzero.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct InjectorNodes {
+    pub(crate) l2_tags: Vec<NodeId>,
+    pub(crate) sink: Option<NodeId>,
+}
+
+/// The synthetic traffic of the clusters `[gmem.injectors]` stands in for.
+#[derive(Clone)]
+pub(crate) struct Injectors {
+    config: InjectorConfig,
+    credit: Vec<f64>,
+    next_offset: Vec<u64>,
+    outstanding: Vec<u32>,
+    /// Request id -> (injector, issue cycle).
+    inflight: HashMap<u64, (usize, Cycle)>,
+    next_id: u64,
+    stats: InjectorStats,
+}
+
+impl Injectors {
+    pub(crate) fn new(config: InjectorConfig) -> Self {
+        let count = config.clusters;
+        Self {
+            config,
+            credit: vec![0.0; count],
+            next_offset: vec![0; count],
+            outstanding: vec![0; count],
+            inflight: HashMap::new(),
+            next_id: 1,
+            stats: InjectorStats::default(),
+        }
+    }
+
+    /// Takes the rate and pattern of `config`; the number of injectors is
+    /// fixed when the graph is built.
+    pub(crate) fn set_config(&mut self, config: InjectorConfig) {
+        self.config = InjectorConfig {
+            clusters: self.config.clusters,
+            ..config
+        };
+    }
+
+    /// This cycle's offers as (injector, request). Requests come without
+    /// cache flags; `core_id` is `usize::MAX` so no core's L0 or L1 is
+    /// touched on their behalf.
+    pub(crate) fn offers(&mut self) -> Vec<(usize, GmemRequest)> {
+        let mut offers = Vec::new();
+        for index in 0..self.config.clusters {
+            self.credit[index] += self.config.requests_per_cycle.max(0.0);
+            while self.credit[index] >= 1.0 {
+                self.credit[index] -= 1.0;
+                if self.outstanding[index] >= self.config.max_outstanding {
+                    self.stats.dropped = self.stats.dropped.saturating_add(1);
+                    continue;
+                }
+                offers.push((index, self.next_request(index)));
+            }
+        }
+        offers
+    }
+
+    fn next_request(&mut self, index: usize) -> GmemRequest {
+        let config = self.config;
+        let id = self.next_id;
+        self.next_id += 1;
+        let bytes = config.bytes.max(1);
+        let footprint = config.footprint.max(bytes as u64);
+        let key = hash_u64(config.seed ^ id.wrapping_mul(0x9e37_79b9_7f4a_7c15));
+        let offset = match config.pattern {
+            InjectorPattern::Random => key % (footprint / bytes as u64) * bytes as u64,
+            pattern => {
+                let step = if pattern == InjectorPattern::Strided {
+                    config.stride.max(1)
+                } else {
+                    bytes as u64
+                };
+                let offset = self.next_offset[index];
+                self.next_offset[index] = (offset + step) % footprint;
+                offset
+            }
+        };
+        let is_load = !decide(config.store_fraction, key ^ 0x5a5a_5a5a_5a5a_5a5a);
+        let mut request = GmemRequest::new(0, bytes, 1, is_load);
+        request.id = id;
+        request.core_id = usize::MAX;
+        request.addr = config
+            .base
+            .saturating_add((index as u64).saturating_mul(footprint))
+            .saturating_add(offset);
+        request
+    }
+
+    pub(crate) fn accepted(&mut self, index: usize, request: &GmemRequest, now: Cycle) {
+        self.outstanding[index] += 1;
+        self.inflight.insert(request.id, (index, now));
+        self.stats.issued = self.stats.issued.saturating_add(1);
+        if !request.is_load {
+            self.stats.stores = self.stats.stores.saturating_add(1);
+        }
+    }
+
+    pub(crate) fn rejected(&mut self) {
+        self.stats.dropped = self.stats.dropped.saturating_add(1);
+    }
+
+    pub(crate) fn complete(&mut self, request: &GmemRequest, now: Cycle) {
+        let Some((index, issued_at)) = self.inflight.remove(&request.id) else {
+            return;
+        };
+        self.outstanding[index] = self.outstanding[index].saturating_sub(1);
+        let latency = now.saturating_sub(issued_at);
+        let stats = &mut self.stats;
+        stats.completed = stats.completed.saturating_add(1);
+        stats.l2_hits = stats.l2_hits.saturating_add(request.l2_hit as u64);
+        stats.latency_sum = stats.latency_sum.saturating_add(latency);
+        stats.max_latency = stats.max_latency.max(latency);
+    }
+
+    pub(crate) fn stats(&self) -> InjectorStats {
+        self.stats
+    }
+
+    pub(crate) fn clear_stats(&mut self) {
+        self.stats = InjectorStats::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn offers_follow_the_rate_and_stop_at_max_outstanding() {
+        let mut injectors = Injectors::new(InjectorConfig {
+            clusters: 2,
+            requests_per_cycle: 0.5,
+            pattern: InjectorPattern::Strided,
+            stride: 128,
+            footprint: 256,
+            max_outstanding: 1,
+            ..InjectorConfig::default()
+        });
+        assert!(injectors.offers().is_empty());
+        let offers = injectors.offers();
+        assert_eq!(offers.len(), 2);
+        let (index, request) = &offers[1];
+        assert_eq!(*index, 1);
+        assert_eq!(request.addr, 0xc000_0000 + 256);
+        injectors.accepted(1, request, 1);
+
+        injectors.offers();
+        let offers = injectors.offers();
+        assert_eq!(offers.len(), 1, "injector 1 is at max_outstanding");
+        assert_eq!(offers[0].1.addr, 0xc000_0000 + 128);
+        assert_eq!(injectors.stats().dropped, 1);
+
+        injectors.complete(request, 11);
+        let stats = injectors.stats();
+        assert_eq!((stats.completed, stats.max_latency), (1, 10));
+    }
+}
@@ -1,19 +1,47 @@
+mod adaptive;
+mod background;
+mod bank_activity;
 pub mod cache;
+mod cache_hint;
 mod cluster;
+mod data_port;
+mod dram;
+mod fill_forward;
 mod graph_build;
+mod injector;
 pub mod mshr;
 pub mod policy;
+mod region;
+mod remote;
 mod request;
+mod salvage;
 mod stats;
+mod validate;
+mod write_combining;
 
 #[cfg(test)]
 mod tests;
 
+pub use adaptive::AdaptiveRoutingConfig;
+pub use background::{BackgroundTrafficConfig, DramShareStats, TrafficAgent};
+pub use bank_activity::CacheBankActivity;
+pub use cache_hint::{CacheHint, CacheHintConfig, CacheHintCounts, CacheHintEntry, CacheHintStats};
 pub use cluster::ClusterGmemGraph;
-pub use graph_build::{GmemFlowConfig, GmemLinkConfig, GmemNodeConfig, GmemStatsRange, LinkConfig};
-pub use policy::GmemPolicyConfig;
+pub use data_port::{DataPortStats, RefillContention};
+pub use dram::{DramChannelStats, DramConfig, DramInterleave};
+pub use fill_forward::{FillForwardConfig, FillForwardStats};
+pub use graph_build::{
+    CacheLevelConfig, GmemFlowConfig, GmemLinkConfig, GmemNodeConfig, GmemStatsRange, LinkConfig,
+};
+pub use injector::{InjectorConfig, InjectorPattern, InjectorStats};
+pub use policy::{FlushOrdering, GmemPolicyConfig, GmemPolicyMode};
+pub use region::{MemoryMapConfig, MemoryRegionConfig, MemoryRegionKind, MemoryRegionStats};
+pub use remote::{DieTrafficStats, RemoteConfig};
 pub use request::{
-    GmemCompletion, GmemIssue, GmemReject, GmemRejectReason, GmemRequest, GmemRequestKind,
-    GmemResult,
+    ByteMask, GmemCompletion, GmemIssue, GmemReject, GmemRejectReason, GmemRequest,
+    GmemRequestKind, GmemResult,
 };
+pub use salvage::SalvageConfig;
 pub use stats::GmemStats;
+pub use validate::{GraphValidation, UnroutedRequest};
+pub use write_combining::{WriteCombiningConfig, WriteCombiningStats};
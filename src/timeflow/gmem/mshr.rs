@@ -12,6 +12,7 @@ pub(crate) struct MissMetadata {
     l2_writeback: bool,
     l1_bank: usize,
     l2_bank: usize,
+    dram_channel: usize,
 }
 
 impl MissMetadata {
@@ -25,6 +26,7 @@ impl MissMetadata {
             l2_writeback: request.l2_writeback,
             l1_bank: request.l1_bank,
             l2_bank: request.l2_bank,
+            dram_channel: request.dram_channel,
         }
     }
 
@@ -37,6 +39,7 @@ impl MissMetadata {
         request.l2_writeback = self.l2_writeback;
         request.l1_bank = self.l1_bank;
         request.l2_bank = self.l2_bank;
+        request.dram_channel = self.dram_channel;
     }
 }
 
@@ -48,7 +51,7 @@ pub(crate) enum MissLevel {
     L2,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub(crate) struct MshrEntry {
     line_addr: u64,
     meta: MissMetadata,
@@ -67,7 +70,7 @@ impl MshrEntry {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub(crate) struct MshrTable {
     capacity: usize,
     entries: Vec<MshrEntry>,
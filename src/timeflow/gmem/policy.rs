@@ -1,8 +1,38 @@
 use serde::Deserialize;
 
+use crate::timeflow::graph::MisrouteAction;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GmemPolicyMode {
+    /// Hits come from the tag arrays; dirty evictions are drawn at
+    /// `l1_writeback_rate`/`l2_writeback_rate` per missing line.
+    #[default]
+    Probabilistic,
+    /// Stores set dirty bits in the L1/L2 tag arrays, and a miss writes
+    /// back only if the line it would evict is dirty. Results depend only
+    /// on the address stream.
+    TagAccurate,
+}
+
+/// What an in-flight flush holds back in its core's pending issue queue.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FlushOrdering {
+    /// Flushes and memory requests reach the cluster in any order.
+    #[default]
+    Unordered,
+    /// Gmem requests issued after a flush by the same warp wait for it.
+    Warp,
+    /// Gmem requests issued after a flush by any warp on the core wait for
+    /// it.
+    Core,
+}
+
 #[derive(Debug, Clone, Copy, Deserialize)]
 #[serde(default)]
 pub struct GmemPolicyConfig {
+    pub mode: GmemPolicyMode,
     pub l0_enabled: bool,
     pub l1_writeback_rate: f64,
     pub l2_writeback_rate: f64,
@@ -19,12 +49,29 @@ pub struct GmemPolicyConfig {
     pub l0_flush_mmio_stride: u64,
     pub l0_flush_mmio_size: u64,
     pub flush_bytes: u32,
+    pub flush_ordering: FlushOrdering,
+    /// Flushes each core may have in flight at its L0, and each cluster at
+    /// its L1. Flushes past the limit are turned away with `FlushLimit`
+    /// until one completes. 0 means no limit.
+    pub max_inflight_l0_flushes: u32,
+    pub max_inflight_l1_flushes: u32,
+    /// Stores that do not cover a whole line read it out of the data array
+    /// before merging, occupying the array for a second line transfer.
+    pub partial_store_rmw: bool,
+    /// Requests (flushes in particular) that have been retried for this many
+    /// cycles take priority over younger traffic, both in the core's
+    /// pending issue queue and on graph links. 0 disables aging.
+    pub retry_age_threshold: u64,
+    /// What the graph does with a request none of its node's outgoing links
+    /// accepts.
+    pub misroute: MisrouteAction,
     pub seed: u64,
 }
 
 impl Default for GmemPolicyConfig {
     fn default() -> Self {
         let s = Self {
+            mode: GmemPolicyMode::Probabilistic,
             l0_enabled: true,
             l1_writeback_rate: 0.1,
             l2_writeback_rate: 0.1,
@@ -41,6 +88,12 @@ impl Default for GmemPolicyConfig {
             l0_flush_mmio_stride: 0x200,
             l0_flush_mmio_size: 0x100,
             flush_bytes: 4096,
+            flush_ordering: FlushOrdering::Unordered,
+            max_inflight_l0_flushes: 0,
+            max_inflight_l1_flushes: 0,
+            partial_store_rmw: true,
+            retry_age_threshold: 256,
+            misroute: MisrouteAction::Warn,
             seed: 0,
         };
         s.ensure_valid();
@@ -49,6 +102,14 @@ impl Default for GmemPolicyConfig {
 }
 
 impl GmemPolicyConfig {
+    pub fn tag_accurate(&self) -> bool {
+        self.mode == GmemPolicyMode::TagAccurate
+    }
+
+    pub fn orders_flushes(&self) -> bool {
+        self.flush_ordering != FlushOrdering::Unordered
+    }
+
     pub fn ensure_valid(&self) {
         assert!(self.l0_line_bytes > 0, "l0_line_bytes must be > 0");
         assert!(self.l1_line_bytes > 0, "l1_line_bytes must be > 0");
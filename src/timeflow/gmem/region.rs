@@ -0,0 +1,275 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::ops::AddAssign;
+
+use crate::timeq::{Cycle, ServerConfig, Ticket, TimedServer};
+
+use super::request::GmemRequest;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MemoryRegionKind {
+    /// Normal path through L0/L1/L2; only tagged for per-region stats.
+    #[default]
+    Cached,
+    /// Goes through the hierarchy as a miss at every level without
+    /// allocating or filling any cache lines.
+    Uncacheable,
+    /// Served by a dedicated fixed-latency device, bypassing L1/L2.
+    Scratchpad,
+    /// Served by a dedicated device with its own latency, bypassing L1/L2.
+    Mmio,
+    /// Cached like `cached`, but L2 misses go over an inter-die link to the
+    /// memory of another die; see `gmem.remote`.
+    Remote,
+}
+
+impl MemoryRegionKind {
+    pub fn bypasses_hierarchy(self) -> bool {
+        matches!(self, Self::Scratchpad | Self::Mmio)
+    }
+}
+
+/// One `[[memory.regions]]` entry covering `[start, end)`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct MemoryRegionConfig {
+    pub name: String,
+    pub start: u64,
+    pub end: u64,
+    pub kind: MemoryRegionKind,
+    /// Die serving a remote region, `1..=gmem.remote.dies`; ignored
+    /// otherwise.
+    pub die: usize,
+    /// Device timing for scratchpad and MMIO regions; ignored otherwise.
+    #[serde(flatten)]
+    pub device: ServerConfig,
+}
+
+impl Default for MemoryRegionConfig {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            start: 0,
+            end: 0,
+            kind: MemoryRegionKind::Cached,
+            die: 1,
+            device: ServerConfig {
+                base_latency: 4,
+                bytes_per_cycle: 64,
+                queue_capacity: 16,
+                ..ServerConfig::default()
+            },
+        }
+    }
+}
+
+impl MemoryRegionConfig {
+    pub fn contains(&self, addr: u64) -> bool {
+        addr >= self.start && addr < self.end
+    }
+}
+
+/// Address map applied to every gmem request. The first matching region
+/// wins; addresses outside all regions take the normal cached path.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct MemoryMapConfig {
+    pub regions: Vec<MemoryRegionConfig>,
+}
+
+impl MemoryMapConfig {
+    pub fn lookup(&self, addr: u64) -> Option<usize> {
+        self.regions.iter().position(|region| region.contains(addr))
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MemoryRegionStats {
+    pub name: String,
+    pub kind: MemoryRegionKind,
+    pub issued: u64,
+    pub completed: u64,
+    pub bytes: u64,
+    pub rejects: u64,
+    pub total_latency: u64,
+}
+
+impl MemoryRegionStats {
+    fn new(config: &MemoryRegionConfig) -> Self {
+        Self {
+            name: config.name.clone(),
+            kind: config.kind,
+            ..Self::default()
+        }
+    }
+
+    pub fn avg_latency(&self) -> f64 {
+        if self.completed == 0 {
+            0.0
+        } else {
+            self.total_latency as f64 / self.completed as f64
+        }
+    }
+}
+
+impl AddAssign<&MemoryRegionStats> for MemoryRegionStats {
+    fn add_assign(&mut self, other: &MemoryRegionStats) {
+        if self.name.is_empty() {
+            self.name = other.name.clone();
+            self.kind = other.kind;
+        }
+        self.issued = self.issued.saturating_add(other.issued);
+        self.completed = self.completed.saturating_add(other.completed);
+        self.bytes = self.bytes.saturating_add(other.bytes);
+        self.rejects = self.rejects.saturating_add(other.rejects);
+        self.total_latency = self.total_latency.saturating_add(other.total_latency);
+    }
+}
+
+/// Runtime state for the configured regions: a device server per
+/// scratchpad/MMIO region and per-core stats for every region.
+#[derive(Clone)]
+pub(crate) struct MemoryRegions {
+    config: MemoryMapConfig,
+    devices: Vec<Option<TimedServer<GmemRequest>>>,
+    stats: Vec<Vec<MemoryRegionStats>>,
+    /// (core, request id) -> (first issue cycle, outstanding children)
+    inflight: HashMap<(usize, u64), (Cycle, usize)>,
+}
+
+impl MemoryRegions {
+    pub(crate) fn new(config: MemoryMapConfig, num_cores: usize) -> Self {
+        let devices = config
+            .regions
+            .iter()
+            .map(|region| {
+                region
+                    .kind
+                    .bypasses_hierarchy()
+                    .then(|| TimedServer::new(region.device))
+            })
+            .collect();
+        let per_core: Vec<MemoryRegionStats> =
+            config.regions.iter().map(MemoryRegionStats::new).collect();
+        Self {
+            stats: vec![per_core; num_cores],
+            devices,
+            config,
+            inflight: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn lookup(&self, addr: u64) -> Option<(usize, MemoryRegionKind)> {
+        self.config
+            .lookup(addr)
+            .map(|idx| (idx, self.config.regions[idx].kind))
+    }
+
+    pub(crate) fn kind(&self, region: usize) -> MemoryRegionKind {
+        self.config.regions[region].kind
+    }
+
+    /// Die whose memory serves the region's L2 misses.
+    pub(crate) fn die(&self, region: usize) -> usize {
+        let region = &self.config.regions[region];
+        match region.kind {
+            MemoryRegionKind::Remote => region.die,
+            _ => 0,
+        }
+    }
+
+    /// Remote regions naming a die outside `1..=dies`.
+    pub(crate) fn check_dies(&self, dies: usize) -> Result<(), String> {
+        for region in &self.config.regions {
+            if region.kind == MemoryRegionKind::Remote && !(1..=dies).contains(&region.die) {
+                return Err(format!(
+                    "memory region {} is on die {}, but gmem.remote has {dies} remote dies",
+                    region.name, region.die
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Device server for a scratchpad or MMIO region.
+    pub(crate) fn device_mut(&mut self, region: usize) -> &mut TimedServer<GmemRequest> {
+        self.devices[region]
+            .as_mut()
+            .expect("region does not have a device")
+    }
+
+    pub(crate) fn pop_ready(&mut self, now: Cycle) -> Vec<(GmemRequest, Ticket)> {
+        let mut ready = Vec::new();
+        for device in self.devices.iter_mut().flatten() {
+            while let Some(result) = device.pop_ready(now) {
+                ready.push((result.payload, result.ticket));
+            }
+        }
+        ready
+    }
+
+    pub(crate) fn record_issue(
+        &mut self,
+        core_id: usize,
+        region: usize,
+        request_id: u64,
+        bytes: u32,
+        now: Cycle,
+    ) {
+        if let Some(stats) = self.stats.get_mut(core_id).and_then(|s| s.get_mut(region)) {
+            stats.issued = stats.issued.saturating_add(1);
+            stats.bytes = stats.bytes.saturating_add(bytes as u64);
+        }
+        let entry = self
+            .inflight
+            .entry((core_id, request_id))
+            .or_insert((now, 0));
+        entry.1 += 1;
+    }
+
+    pub(crate) fn record_reject(&mut self, core_id: usize, region: usize) {
+        if let Some(stats) = self.stats.get_mut(core_id).and_then(|s| s.get_mut(region)) {
+            stats.rejects = stats.rejects.saturating_add(1);
+        }
+    }
+
+    pub(crate) fn record_completion(
+        &mut self,
+        core_id: usize,
+        region: usize,
+        request_id: u64,
+        now: Cycle,
+    ) {
+        let key = (core_id, request_id);
+        let issued_at = match self.inflight.get_mut(&key) {
+            Some((issued_at, remaining)) => {
+                let issued_at = *issued_at;
+                *remaining = remaining.saturating_sub(1);
+                if *remaining == 0 {
+                    self.inflight.remove(&key);
+                }
+                issued_at
+            }
+            None => now,
+        };
+        if let Some(stats) = self.stats.get_mut(core_id).and_then(|s| s.get_mut(region)) {
+            stats.completed = stats.completed.saturating_add(1);
+            stats.total_latency = stats
+                .total_latency
+                .saturating_add(now.saturating_sub(issued_at));
+        }
+    }
+
+    pub(crate) fn stats(&self, core_id: usize) -> Vec<MemoryRegionStats> {
+        self.stats.get(core_id).cloned().unwrap_or_default()
+    }
+
+    pub(crate) fn clear_stats(&mut self, core_id: usize) {
+        if let Some(stats) = self.stats.get_mut(core_id) {
+            for (entry, region) in stats.iter_mut().zip(self.config.regions.iter()) {
+                *entry = MemoryRegionStats::new(region);
+            }
+        }
+    }
+}
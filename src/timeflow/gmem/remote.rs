@@ -0,0 +1,159 @@
+use serde::{Deserialize, Serialize};
+
+use crate::timeflow::graph::FlowGraph;
+use crate::timeflow::types::{CoreFlowPayload, NodeId};
+use crate::timeq::{ServerConfig, ServerStats};
+
+use super::dram::DramChannelStats;
+
+/// Memory on other dies (chiplets, HBM stacks) that `remote` regions map
+/// to. An L2 miss to a remote die leaves through the die's outbound link,
+/// is served by the die's memory and comes back through the inbound link.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct RemoteConfig {
+    /// Remote dies, numbered `1..=dies`; die 0 is the local DRAM.
+    pub dies: usize,
+    /// Each direction of a die's link.
+    pub link: ServerConfig,
+    /// Each remote die's memory.
+    pub memory: ServerConfig,
+}
+
+impl Default for RemoteConfig {
+    fn default() -> Self {
+        Self {
+            dies: 0,
+            link: ServerConfig {
+                base_latency: 40,
+                bytes_per_cycle: 16,
+                queue_capacity: 32,
+                ..ServerConfig::default()
+            },
+            memory: ServerConfig {
+                base_latency: 200,
+                bytes_per_cycle: 32,
+                queue_capacity: 64,
+                ..ServerConfig::default()
+            },
+        }
+    }
+}
+
+/// Graph nodes of one remote die.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct RemoteDieNodes {
+    pub(crate) link_out: NodeId,
+    pub(crate) memory: NodeId,
+    pub(crate) link_in: NodeId,
+}
+
+/// Traffic to one die's memory since stats were last cleared.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct DieTrafficStats {
+    /// 0 for the local DRAM channels, otherwise a `gmem.remote` die.
+    pub die: usize,
+    pub cycles: u64,
+    /// Refills and writebacks the die's memory accepted.
+    pub requests: u64,
+    pub bytes: u64,
+    /// Bytes sent to the die over its link; 0 for the local die.
+    pub link_out_bytes: u64,
+    /// Bytes returned from the die over its link; 0 for the local die.
+    pub link_in_bytes: u64,
+    pub link_queue_full_rejects: u64,
+    pub link_max_outstanding: u64,
+    /// Fraction of the outbound link's peak bandwidth that was used.
+    pub link_utilization: f64,
+}
+
+/// Server stats of a remote die's nodes, in `RemoteDieNodes` order.
+type DieSnapshot = [ServerStats; 3];
+
+/// The remote dies of a cluster graph and the stats they have accumulated.
+#[derive(Clone)]
+pub(crate) struct RemoteDies {
+    nodes: Vec<RemoteDieNodes>,
+    link_bytes_per_cycle: u32,
+    cycles: u64,
+    latest: Vec<DieSnapshot>,
+    base: Vec<DieSnapshot>,
+}
+
+impl RemoteDies {
+    pub(crate) fn new(nodes: Vec<RemoteDieNodes>, link_bytes_per_cycle: u32) -> Self {
+        let dies = nodes.len();
+        Self {
+            nodes,
+            link_bytes_per_cycle,
+            cycles: 0,
+            latest: vec![DieSnapshot::default(); dies],
+            base: vec![DieSnapshot::default(); dies],
+        }
+    }
+
+    pub(crate) fn die_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub(crate) fn set_link_bytes_per_cycle(&mut self, bytes_per_cycle: u32) {
+        self.link_bytes_per_cycle = bytes_per_cycle;
+    }
+
+    pub(crate) fn tick(&mut self, graph: &mut FlowGraph<CoreFlowPayload>) {
+        self.cycles = self.cycles.saturating_add(1);
+        for (latest, die) in self.latest.iter_mut().zip(&self.nodes) {
+            for (stats, node) in latest
+                .iter_mut()
+                .zip([die.link_out, die.memory, die.link_in])
+            {
+                if let Some(server) = graph.with_node_mut(node, |node| node.server_stats()) {
+                    *stats = server;
+                }
+            }
+        }
+    }
+
+    /// One entry per die, the local die first. Empty without remote dies.
+    pub(crate) fn stats(&self, dram: &[DramChannelStats]) -> Vec<DieTrafficStats> {
+        if self.nodes.is_empty() {
+            return Vec::new();
+        }
+        let local = DieTrafficStats {
+            die: 0,
+            cycles: self.cycles,
+            requests: dram.iter().map(|channel| channel.requests).sum(),
+            bytes: dram.iter().map(|channel| channel.demand_bytes).sum(),
+            ..DieTrafficStats::default()
+        };
+        let peak = self.cycles.saturating_mul(self.link_bytes_per_cycle as u64);
+        let remote = self.latest.iter().zip(&self.base).enumerate().map(
+            |(index, ([out, memory, link_in], [out_base, memory_base, in_base]))| {
+                let link_out_bytes = out.bytes_issued.saturating_sub(out_base.bytes_issued);
+                let link_utilization = if peak == 0 {
+                    0.0
+                } else {
+                    link_out_bytes as f64 / peak as f64
+                };
+                DieTrafficStats {
+                    die: index + 1,
+                    cycles: self.cycles,
+                    requests: memory.issued.saturating_sub(memory_base.issued),
+                    bytes: memory.bytes_issued.saturating_sub(memory_base.bytes_issued),
+                    link_out_bytes,
+                    link_in_bytes: link_in.bytes_issued.saturating_sub(in_base.bytes_issued),
+                    link_queue_full_rejects: (out.queue_full_rejects + link_in.queue_full_rejects)
+                        .saturating_sub(out_base.queue_full_rejects + in_base.queue_full_rejects),
+                    link_max_outstanding: out.max_outstanding.max(link_in.max_outstanding),
+                    link_utilization,
+                }
+            },
+        );
+        std::iter::once(local).chain(remote).collect()
+    }
+
+    pub(crate) fn clear_stats(&mut self) {
+        self.base.clone_from(&self.latest);
+        self.cycles = 0;
+    }
+}
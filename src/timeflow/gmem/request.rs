@@ -1,6 +1,59 @@
+use crate::timeflow::gmem::cache_hint::CacheHint;
+use crate::timeflow::graph::RequestPath;
 use crate::timeflow::types::CoreFlowPayload;
 use crate::timeq::{Cycle, ServiceRequest, Ticket};
 
+/// Per-byte write enables over a span of memory, bit `i` covering byte `i`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ByteMask {
+    words: Vec<u64>,
+}
+
+impl ByteMask {
+    pub fn new(len: u32) -> Self {
+        Self {
+            words: vec![0; (len as usize).div_ceil(64)],
+        }
+    }
+
+    /// Enables `len` bytes starting at `offset`, growing the mask if needed.
+    pub fn set_range(&mut self, offset: u32, len: u32) {
+        let end = offset.saturating_add(len) as usize;
+        if self.words.len() * 64 < end {
+            self.words.resize(end.div_ceil(64), 0);
+        }
+        for byte in offset as usize..end {
+            self.words[byte / 64] |= 1 << (byte % 64);
+        }
+    }
+
+    pub fn is_set(&self, byte: u32) -> bool {
+        let byte = byte as usize;
+        self.words
+            .get(byte / 64)
+            .is_some_and(|word| word & (1 << (byte % 64)) != 0)
+    }
+
+    /// Whether every byte in `[offset, offset + len)` is enabled.
+    pub fn covers(&self, offset: u32, len: u32) -> bool {
+        (offset..offset.saturating_add(len)).all(|byte| self.is_set(byte))
+    }
+
+    /// Enables every byte `other` enables.
+    pub fn union(&mut self, other: &ByteMask) {
+        if self.words.len() < other.words.len() {
+            self.words.resize(other.words.len(), 0);
+        }
+        for (word, other) in self.words.iter_mut().zip(&other.words) {
+            *word |= other;
+        }
+    }
+
+    pub fn count(&self) -> u32 {
+        self.words.iter().map(|word| word.count_ones()).sum()
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum GmemRequestKind {
     Load,
@@ -29,10 +82,20 @@ pub struct GmemRequest {
     pub core_id: usize,
     pub cluster_id: usize,
     pub warp: usize,
+    /// Kernel the issuing warp belongs to; see `CoreTimingModel::set_warp_context`.
+    pub context: usize,
+    /// PC of the load or store that issued the request; 0 for flushes.
+    pub pc: u32,
     pub addr: u64,
     pub line_addr: u64,
     pub lane_addrs: Option<Vec<u64>>,
     pub coalesced_lines: Option<Vec<u64>>,
+    /// Store byte-enables, one mask per entry in `coalesced_lines`. Consumed
+    /// when the request is split into per-line children.
+    pub line_byte_enables: Option<Vec<ByteMask>>,
+    /// Bytes a store actually writes, relative to `addr`. `None` means the
+    /// whole `bytes` range (loads, flushes, and unsplit requests).
+    pub byte_enable: Option<ByteMask>,
     pub bytes: u32,
     pub active_lanes: u32,
     pub is_load: bool,
@@ -45,6 +108,27 @@ pub struct GmemRequest {
     pub l2_writeback: bool,
     pub l1_bank: usize,
     pub l2_bank: usize,
+    /// DRAM channel an L2 miss goes to; see `gmem.dram`.
+    pub dram_channel: usize,
+    /// Bytes of write burst an L2 writeback adds to its DRAM channel's
+    /// service; see `gmem.write_combining`. Set by the cluster on issue.
+    pub dram_write_bytes: u32,
+    /// Die whose memory serves an L2 miss: 0 for the local DRAM, otherwise
+    /// a `gmem.remote` die. Set by the cluster on issue.
+    pub die: usize,
+    /// A partial store that has to read-modify-write the line in the data
+    /// array of the cache level it hits. Set by the cluster on issue.
+    pub rmw: bool,
+    /// Index into the `[[memory.regions]]` map, set by the cluster on issue.
+    pub region: Option<usize>,
+    /// Served from an older store in the LSU; never reached the caches.
+    pub forwarded: bool,
+    /// Nodes of the gmem graph the request went through, for the requests
+    /// `log.path_sample` picks. Set by the cluster on issue.
+    pub path: Option<RequestPath>,
+    /// Cache levels a load may hit and fill; set by the frontend, or on
+    /// issue from `gmem.cache_hints`.
+    pub cache_hint: CacheHint,
 }
 
 impl GmemRequest {
@@ -59,10 +143,14 @@ impl GmemRequest {
             core_id: 0,
             cluster_id: 0,
             warp,
+            context: 0,
+            pc: 0,
             addr: 0,
             line_addr: 0,
             lane_addrs: None,
             coalesced_lines: None,
+            line_byte_enables: None,
+            byte_enable: None,
             bytes,
             active_lanes,
             is_load,
@@ -75,6 +163,14 @@ impl GmemRequest {
             l2_writeback: false,
             l1_bank: 0,
             l2_bank: 0,
+            dram_channel: 0,
+            dram_write_bytes: 0,
+            die: 0,
+            rmw: false,
+            region: None,
+            forwarded: false,
+            path: None,
+            cache_hint: CacheHint::All,
         }
     }
 
@@ -84,10 +180,14 @@ impl GmemRequest {
             core_id: 0,
             cluster_id: 0,
             warp,
+            context: 0,
+            pc: 0,
             addr: 0,
             line_addr: 0,
             lane_addrs: None,
             coalesced_lines: None,
+            line_byte_enables: None,
+            byte_enable: None,
             bytes,
             active_lanes: 0,
             is_load: false,
@@ -100,6 +200,14 @@ impl GmemRequest {
             l2_writeback: false,
             l1_bank: 0,
             l2_bank: 0,
+            dram_channel: 0,
+            dram_write_bytes: 0,
+            die: 0,
+            rmw: false,
+            region: None,
+            forwarded: false,
+            path: None,
+            cache_hint: CacheHint::All,
         }
     }
 
@@ -109,10 +217,14 @@ impl GmemRequest {
             core_id: 0,
             cluster_id: 0,
             warp,
+            context: 0,
+            pc: 0,
             addr: 0,
             line_addr: 0,
             lane_addrs: None,
             coalesced_lines: None,
+            line_byte_enables: None,
+            byte_enable: None,
             bytes,
             active_lanes: 0,
             is_load: false,
@@ -125,8 +237,27 @@ impl GmemRequest {
             l2_writeback: false,
             l1_bank: 0,
             l2_bank: 0,
+            dram_channel: 0,
+            dram_write_bytes: 0,
+            die: 0,
+            rmw: false,
+            region: None,
+            forwarded: false,
+            path: None,
+            cache_hint: CacheHint::All,
         }
     }
+
+    /// Whether the store writes every byte of the `line_bytes`-sized line
+    /// containing `addr`. Requests without byte-enables are taken as full.
+    pub fn writes_whole_line(&self, line_bytes: u32) -> bool {
+        let Some(mask) = self.byte_enable.as_ref() else {
+            return true;
+        };
+        let line_bytes = line_bytes.max(1);
+        let start = (self.addr / line_bytes as u64) * line_bytes as u64;
+        start >= self.addr && mask.covers((start - self.addr) as u32, line_bytes)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -140,6 +271,9 @@ pub struct GmemCompletion {
 pub struct GmemIssue {
     pub request_id: u64,
     pub ticket: Ticket,
+    /// The request missed in the first cache level it looked up (including
+    /// MSHR merges and uncacheable accesses).
+    pub miss: bool,
 }
 
 pub use crate::timeflow::types::RejectReason as GmemRejectReason;
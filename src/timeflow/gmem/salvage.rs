@@ -0,0 +1,88 @@
+use serde::Deserialize;
+
+use super::policy::bank_for;
+
+/// Parts of the chip fused off, as on a binned part. The same elements are
+/// disabled in every cluster.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct SalvageConfig {
+    /// Cluster-local ids of the cores to leave out. The cores that remain
+    /// are numbered densely from 0, and `num_cores` shrinks to match, so a
+    /// kernel spreads its work over the cores the part still has.
+    pub disabled_cores: Vec<usize>,
+    /// L1 banks to leave out of every cluster, by index in `0..banks`.
+    pub disabled_l1_banks: Vec<usize>,
+    /// L2 banks to leave out, by index in `0..banks`.
+    pub disabled_l2_banks: Vec<usize>,
+}
+
+impl SalvageConfig {
+    pub fn is_empty(&self) -> bool {
+        self.disabled_cores.is_empty()
+            && self.disabled_l1_banks.is_empty()
+            && self.disabled_l2_banks.is_empty()
+    }
+
+    /// Cluster-local ids of the cores left out of `num_cores`, in order.
+    /// The i-th entry is the core that becomes core i.
+    pub fn enabled_cores(&self, num_cores: usize) -> Result<Vec<usize>, String> {
+        enabled("disabled_cores", num_cores, &self.disabled_cores)
+    }
+}
+
+fn enabled(key: &str, count: usize, disabled: &[usize]) -> Result<Vec<usize>, String> {
+    if let Some(&id) = disabled.iter().find(|&&id| id >= count) {
+        return Err(format!("salvage.{key}: {id} is out of range (0..{count})"));
+    }
+    let enabled: Vec<usize> = (0..count).filter(|id| !disabled.contains(id)).collect();
+    if enabled.is_empty() {
+        return Err(format!("salvage.{key} disables all {count}"));
+    }
+    Ok(enabled)
+}
+
+/// Spreads lines over the banks of a cache level that are left enabled.
+/// Banks are numbered by their place among the enabled ones, which is how
+/// the graph and the tag arrays index them.
+#[derive(Debug, Clone)]
+pub(crate) struct BankMap {
+    /// Physical id of each enabled bank.
+    enabled: Vec<usize>,
+    /// Physical id -> index among the enabled banks.
+    index: Vec<Option<usize>>,
+}
+
+const REMAP_SEED: u64 = 0x9e37_79b9_7f4a_7c15;
+
+impl BankMap {
+    pub(crate) fn new(key: &str, banks: usize, disabled: &[usize]) -> Result<Self, String> {
+        let banks = banks.max(1);
+        let enabled = enabled(key, banks, disabled)?;
+        let mut index = vec![None; banks];
+        for (i, &bank) in enabled.iter().enumerate() {
+            index[bank] = Some(i);
+        }
+        Ok(Self { enabled, index })
+    }
+
+    /// Physical ids of the enabled banks, in index order.
+    pub(crate) fn enabled(&self) -> &[usize] {
+        &self.enabled
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.enabled.len()
+    }
+
+    /// Index of the bank `line` maps to. Lines are hashed over every
+    /// physical bank as on a full part; the ones landing on a disabled
+    /// bank are hashed again over the enabled ones, so no other line moves.
+    pub(crate) fn bank_for(&self, line: u64, seed: u64) -> usize {
+        let physical = bank_for(line, self.index.len() as u64, seed);
+        match self.index[physical] {
+            Some(index) => index,
+            None => bank_for(line, self.enabled.len() as u64, seed ^ REMAP_SEED),
+        }
+    }
+}
@@ -1,18 +1,22 @@
+use crate::check;
 use crate::timeq::Cycle;
 use serde::Serialize;
 use std::ops::AddAssign;
 
 #[derive(Debug, Clone, Copy, Default, Serialize)]
+#[non_exhaustive]
 pub struct GmemStats {
     issued: u64,
     completed: u64,
     queue_full_rejects: u64,
     busy_rejects: u64,
+    flush_limit_rejects: u64,
     bytes_issued: u64,
     bytes_completed: u64,
     accesses: u64,
     hits: u64,
     bytes_hits: u64,
+    rmw: u64,
     inflight: u64,
     max_inflight: u64,
     max_completion_queue: u64,
@@ -36,6 +40,10 @@ impl GmemStats {
         self.busy_rejects
     }
 
+    pub fn flush_limit_rejects(&self) -> u64 {
+        self.flush_limit_rejects
+    }
+
     pub fn bytes_issued(&self) -> u64 {
         self.bytes_issued
     }
@@ -60,6 +68,10 @@ impl GmemStats {
         self.bytes_hits
     }
 
+    pub fn rmw(&self) -> u64 {
+        self.rmw
+    }
+
     pub fn max_inflight(&self) -> u64 {
         self.max_inflight
     }
@@ -88,6 +100,10 @@ impl GmemStats {
         self.bytes_hits = self.bytes_hits.saturating_add(bytes as u64);
     }
 
+    pub fn record_rmw(&mut self) {
+        self.rmw = self.rmw.saturating_add(1);
+    }
+
     pub fn record_busy_reject(&mut self) {
         self.busy_rejects = self.busy_rejects.saturating_add(1);
     }
@@ -96,7 +112,17 @@ impl GmemStats {
         self.queue_full_rejects = self.queue_full_rejects.saturating_add(1);
     }
 
+    pub fn record_flush_limit_reject(&mut self) {
+        self.flush_limit_rejects = self.flush_limit_rejects.saturating_add(1);
+    }
+
     pub fn record_completion(&mut self, bytes: u32, now: Cycle) {
+        check!(
+            Stats,
+            self.last_completion_cycle.is_none_or(|last| last <= now),
+            "gmem completion at cycle {now} after one at {:?}",
+            self.last_completion_cycle
+        );
         self.completed = self.completed.saturating_add(1);
         self.bytes_completed = self.bytes_completed.saturating_add(bytes as u64);
         self.inflight = self.inflight.saturating_sub(1);
@@ -119,10 +145,14 @@ impl AddAssign<&GmemStats> for GmemStats {
         self.accesses = self.accesses.saturating_add(other.accesses);
         self.hits = self.hits.saturating_add(other.hits);
         self.bytes_hits = self.bytes_hits.saturating_add(other.bytes_hits);
+        self.rmw = self.rmw.saturating_add(other.rmw);
         self.queue_full_rejects = self
             .queue_full_rejects
             .saturating_add(other.queue_full_rejects);
         self.busy_rejects = self.busy_rejects.saturating_add(other.busy_rejects);
+        self.flush_limit_rejects = self
+            .flush_limit_rejects
+            .saturating_add(other.flush_limit_rejects);
         self.bytes_issued = self.bytes_issued.saturating_add(other.bytes_issued);
         self.bytes_completed = self.bytes_completed.saturating_add(other.bytes_completed);
         self.max_inflight = self.max_inflight.max(other.max_inflight);
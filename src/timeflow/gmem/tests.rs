@@ -1,4 +1,5 @@
 use super::*;
+use crate::timeflow::StallInjection;
 use crate::timeq::Cycle;
 
 const MAX_CYCLES: u64 = 200;
@@ -164,6 +165,37 @@ fn l0_disabled_bypasses_l0_and_hits_l1() {
     assert!(comp.request.l1_hit, "expected L1 hit when L0 disabled");
 }
 
+#[test]
+fn streaming_hint_from_pc_table_allocates_only_in_l2() {
+    let mut cfg = GmemFlowConfig::zeroed();
+    cfg.policy.l0_enabled = true;
+    cfg.cache_hints.pcs = vec![CacheHintEntry {
+        pc: 0x40,
+        hint: CacheHint::Streaming,
+    }];
+    let mut cluster = ClusterGmemGraph::new(cfg, 1, 1);
+    let load = |pc: u32| {
+        let mut req = make_load(0x3000, 0);
+        req.pc = pc;
+        req
+    };
+
+    for _ in 0..2 {
+        cluster.issue(0, 0, load(0x40)).unwrap();
+        assert_completes!(&mut cluster, 0, 0, MAX_CYCLES);
+    }
+    cluster.issue(0, 0, load(0x80)).unwrap();
+    let comp = assert_completes!(&mut cluster, 0, 0, MAX_CYCLES);
+    assert_eq!(comp.request.cache_hint, CacheHint::All);
+    assert!(!comp.request.l1_hit, "streaming loads never filled the L1");
+    assert!(comp.request.l2_hit);
+
+    let stats = cluster.cache_hint_stats();
+    assert_eq!(stats.cs.loads, 2);
+    assert_eq!((stats.cs.misses, stats.cs.l2_hits), (1, 1));
+    assert_eq!((stats.ca.loads, stats.ca.l2_hits), (1, 1));
+}
+
 #[test]
 fn l1_flush_invalidates_cluster_l1() {
     let cfg = GmemFlowConfig::zeroed();
@@ -202,6 +234,88 @@ fn merge_completion_fanout_same_core() {
     assert_eq!(comp0.completed_at, comp1.completed_at);
 }
 
+#[test]
+fn write_combining_merges_writebacks_to_one_burst() {
+    let run = |window: u64| {
+        let mut cfg = GmemFlowConfig::zeroed();
+        cfg.policy.l0_enabled = false;
+        cfg.policy.l1_writeback_rate = 0.0;
+        cfg.policy.l2_writeback_rate = 1.0;
+        cfg.nodes.dram.bytes_per_cycle = 32;
+        cfg.write_combining.enabled = true;
+        cfg.write_combining.burst_bytes = 256;
+        cfg.write_combining.window = window;
+        let line = cfg.policy.l2_line_bytes as u64;
+        let mut cluster = ClusterGmemGraph::new(cfg, 1, 1);
+        for idx in 0..4 {
+            cluster.issue(0, 0, make_load(idx * line, 0)).unwrap();
+        }
+        let mut last = 0;
+        let mut completed = 0;
+        for cycle in 0..MAX_CYCLES {
+            cluster.tick(cycle);
+            while let Some(completion) = cluster.pop_completion(0) {
+                last = completion.completed_at;
+                completed += 1;
+            }
+        }
+        assert_eq!(completed, 4);
+        (last, cluster.write_combining_stats())
+    };
+
+    let (uncombined, stats) = run(0);
+    assert_eq!((stats.writebacks, stats.bursts), (4, 4));
+    assert_eq!(stats.write_bytes, 4 * 256);
+    assert_eq!(stats.saved_cycles, 0);
+
+    let (combined, stats) = run(64);
+    assert_eq!(
+        (stats.writebacks, stats.bursts, stats.combined()),
+        (4, 1, 3)
+    );
+    assert_eq!(stats.saved_cycles, 3 * 256 / 32);
+    assert!(combined < uncombined, "{combined} vs {uncombined}");
+}
+
+#[test]
+fn fill_forward_serializes_merge_release() {
+    let completion_cycles = |requests_per_cycle: u32| {
+        let mut cfg = GmemFlowConfig::zeroed();
+        cfg.fill_forward.requests_per_cycle = requests_per_cycle;
+        let mut cluster = ClusterGmemGraph::new(cfg, 1, 1);
+        for _ in 0..4 {
+            cluster.issue(0, 0, make_load(0x5000, 0)).unwrap();
+        }
+        let mut cycles = Vec::new();
+        for cycle in 0..MAX_CYCLES {
+            cluster.tick(cycle);
+            while let Some(completion) = cluster.pop_completion(0) {
+                cycles.push(completion.completed_at);
+            }
+        }
+        (cycles, cluster.fill_forward_stats())
+    };
+
+    let (all_at_once, stats) = completion_cycles(0);
+    assert_eq!(all_at_once.len(), 4);
+    assert!(all_at_once.iter().all(|&cycle| cycle == all_at_once[0]));
+    assert_eq!(stats.released, 3);
+    assert_eq!(stats.delayed, 0);
+    assert_eq!(stats.max_fanout, 4);
+
+    let (one_per_cycle, stats) = completion_cycles(1);
+    let fill = all_at_once[0];
+    assert_eq!(one_per_cycle, vec![fill, fill + 1, fill + 2, fill + 3]);
+    assert_eq!(stats.fills, 1);
+    assert_eq!(stats.delayed, 3);
+    assert_eq!(stats.delay_cycles, 1 + 2 + 3);
+    assert_eq!(stats.max_delay, 3);
+    assert_eq!(stats.avg_delay(), 2.0);
+
+    let (two_per_cycle, _) = completion_cycles(2);
+    assert_eq!(two_per_cycle, vec![fill, fill, fill + 1, fill + 1]);
+}
+
 #[test]
 fn mshr_full_rejects_with_retry_cycle() {
     let mut cfg = GmemFlowConfig::zeroed();
@@ -242,3 +356,638 @@ fn unaligned_address_handling_completes() {
     cluster.issue(0, 0, req).unwrap();
     let _ = assert_completes!(&mut cluster, 0, 0, MAX_CYCLES);
 }
+
+#[cfg(not(feature = "fast"))]
+#[test]
+fn cache_bank_activity_counts_only_banks_a_miss_visits() {
+    let mut cfg = GmemFlowConfig::zeroed();
+    cfg.levels[1].banks = 2;
+    cfg.levels[2].banks = 2;
+    let mut cluster = ClusterGmemGraph::new(cfg, 1, 1);
+    cluster.issue(0, 0, make_load(0x1000, 0)).unwrap();
+    let _ = assert_completes!(&mut cluster, 0, 0, MAX_CYCLES);
+
+    let banks = cluster.cache_bank_activity();
+    for prefix in ["cluster0_l1_", "l2_"] {
+        let level: Vec<_> = banks
+            .iter()
+            .filter(|bank| bank.bank.starts_with(prefix))
+            .collect();
+        assert_eq!(level.len(), 2, "{banks:?}");
+        let active: Vec<_> = level.iter().filter(|bank| bank.active_cycles > 0).collect();
+        assert_eq!(active.len(), 1, "{prefix}: {banks:?}");
+        assert!(active[0].duty_cycle() > 0.0 && active[0].duty_cycle() <= 1.0);
+        let idle = level.iter().find(|bank| bank.active_cycles == 0).unwrap();
+        assert_eq!(idle.idle_cycles(), idle.cycles);
+        assert!(idle.cycles > 0);
+    }
+}
+
+fn region(name: &str, start: u64, end: u64, kind: MemoryRegionKind) -> MemoryRegionConfig {
+    MemoryRegionConfig {
+        name: name.to_string(),
+        start,
+        end,
+        kind,
+        ..MemoryRegionConfig::default()
+    }
+}
+
+#[test]
+fn scratchpad_region_bypasses_hierarchy() {
+    let cfg = GmemFlowConfig::default();
+    let mut spad = region("spad", 0x8000, 0x9000, MemoryRegionKind::Scratchpad);
+    spad.device.base_latency = 3;
+    let map = MemoryMapConfig {
+        regions: vec![spad],
+    };
+    let mut cluster = ClusterGmemGraph::new_with_regions(cfg, map, 1, 1);
+
+    let issue = cluster
+        .issue(0, 0, make_load(0x8040, 0))
+        .expect("scratchpad load accepts");
+    assert!(issue.ticket.ready_at() <= 4);
+    let completion = assert_completes!(&mut cluster, 0, 0, MAX_CYCLES);
+    assert_eq!(completion.completed_at, issue.ticket.ready_at());
+    assert_eq!(cluster.hierarchy_stats().accesses(), 0);
+
+    let stats = cluster.region_stats(0);
+    assert_eq!(stats[0].name, "spad");
+    assert_eq!(stats[0].issued, 1);
+    assert_eq!(stats[0].completed, 1);
+}
+
+#[test]
+fn uncacheable_region_never_fills() {
+    let cfg = GmemFlowConfig::zeroed();
+    let map = MemoryMapConfig {
+        regions: vec![region("io", 0x4000, 0x5000, MemoryRegionKind::Uncacheable)],
+    };
+    let mut cluster = ClusterGmemGraph::new_with_regions(cfg, map, 1, 1);
+
+    let mut cycle = 0;
+    for _ in 0..2 {
+        let issue = cluster
+            .issue(0, cycle, make_load(0x4000, 0))
+            .expect("uncacheable load accepts");
+        let completion = assert_completes!(&mut cluster, 0, cycle, MAX_CYCLES);
+        assert!(!completion.request.l2_hit);
+        cycle = completion.completed_at.max(issue.ticket.ready_at()) + 1;
+    }
+
+    // No level took the line the uncacheable loads went through, while a
+    // cached load outside the region does fill.
+    assert!(!cluster.caches_line(0x4000));
+    cluster
+        .issue(0, cycle, make_load(0x6000, 0))
+        .expect("cached load accepts");
+    assert_completes!(&mut cluster, 0, cycle, MAX_CYCLES);
+    assert!(cluster.caches_line(0x6000));
+    assert_eq!(cluster.hierarchy_stats().hits(), 0);
+    assert_eq!(cluster.region_stats(0)[0].completed, 2);
+}
+
+#[test]
+fn set_timing_changes_dram_latency_mid_run() {
+    let mut cfg = GmemFlowConfig::zeroed();
+    let mut cluster = ClusterGmemGraph::new(cfg.clone(), 1, 1);
+
+    cluster.issue(0, 0, make_load(0x1000, 0)).unwrap();
+    let before = assert_completes!(&mut cluster, 0, 0, MAX_CYCLES);
+    assert!(before.completed_at < 50);
+
+    cfg.nodes.dram.base_latency = 100;
+    cluster.set_timing(&cfg);
+    let start = before.completed_at + 1;
+    cluster.issue(0, start, make_load(0x8000, 0)).unwrap();
+    let after = assert_completes!(&mut cluster, 0, start, MAX_CYCLES);
+    assert!(after.completed_at - start >= 100);
+}
+
+#[test]
+fn partial_store_hit_read_modify_writes_line() {
+    let mut cfg = GmemFlowConfig::zeroed();
+    cfg.levels[0].data.bytes_per_cycle = 8;
+    let mut cluster = ClusterGmemGraph::new(cfg, 1, 1);
+
+    cluster.issue(0, 0, make_load(0x3000, 0)).unwrap();
+    let fill = assert_completes!(&mut cluster, 0, 0, MAX_CYCLES);
+
+    let store = |start: u64, len: u32| {
+        let mut req = GmemRequest::new(0, 64, 1, false);
+        req.addr = 0x3000;
+        let mut mask = ByteMask::new(64);
+        mask.set_range(start as u32, len);
+        req.byte_enable = Some(mask);
+        req
+    };
+
+    let start = fill.completed_at + 1;
+    cluster.issue(0, start, store(0, 64)).unwrap();
+    let full = assert_completes!(&mut cluster, 0, start, MAX_CYCLES);
+    assert!(full.request.l0_hit);
+    assert!(!full.request.rmw, "full-line store should not RMW");
+
+    let start = full.completed_at + 1;
+    cluster.issue(0, start, store(4, 1)).unwrap();
+    let partial = assert_completes!(&mut cluster, 0, start, MAX_CYCLES);
+    assert!(partial.request.l0_hit);
+    assert!(partial.request.rmw, "byte store should RMW the L0 line");
+    assert!(
+        partial.completed_at - start >= (full.completed_at - fill.completed_at - 1) + 8,
+        "RMW should hold the data array for an extra line transfer"
+    );
+    let (l0, _, _) = cluster.hierarchy_stats_per_level();
+    assert_eq!(l0.rmw(), 1);
+}
+
+#[test]
+fn tag_accurate_writes_back_only_dirty_victims() {
+    let mut cfg = GmemFlowConfig::zeroed();
+    cfg.policy.mode = GmemPolicyMode::TagAccurate;
+    cfg.policy.l0_enabled = false;
+    cfg.policy.l1_sets = 1;
+    cfg.policy.l1_ways = 1;
+    cfg.policy.l1_writeback_rate = 1.0;
+    cfg.policy.l2_writeback_rate = 1.0;
+    let line = cfg.policy.l1_line_bytes as u64;
+    let mut cluster = ClusterGmemGraph::new(cfg, 1, 1);
+
+    // Clean misses never write back, whatever the rates say.
+    cluster.issue(0, 0, make_load(0, 0)).unwrap();
+    let fill = assert_completes!(&mut cluster, 0, 0, MAX_CYCLES);
+    assert!(!fill.request.l1_writeback && !fill.request.l2_writeback);
+
+    let mut store = GmemRequest::new(0, 16, 0xF, false);
+    store.addr = 0;
+    let start = fill.completed_at + 1;
+    cluster.issue(0, start, store).unwrap();
+    let stored = assert_completes!(&mut cluster, 0, start, MAX_CYCLES);
+    assert!(stored.request.l1_hit);
+
+    // The next miss in the single-line L1 evicts the dirty line.
+    let start = stored.completed_at + 1;
+    cluster.issue(0, start, make_load(line, 0)).unwrap();
+    let evict = assert_completes!(&mut cluster, 0, start, MAX_CYCLES);
+    assert!(evict.request.l1_writeback);
+    assert!(!evict.request.l2_writeback);
+
+    let start = evict.completed_at + 1;
+    cluster.issue(0, start, make_load(2 * line, 0)).unwrap();
+    let clean = assert_completes!(&mut cluster, 0, start, MAX_CYCLES);
+    assert!(!clean.request.l1_writeback);
+}
+
+#[test]
+fn background_dma_slows_demand_misses() {
+    let mut cfg = GmemFlowConfig::zeroed();
+    cfg.nodes.dram.bytes_per_cycle = 8;
+    let miss_latency = |cfg: &GmemFlowConfig, dma_bytes: u32| {
+        let mut cluster = ClusterGmemGraph::new(cfg.clone(), 1, 1);
+        cluster.submit_background(TrafficAgent::Dma, dma_bytes);
+        for cycle in 0..4 {
+            cluster.tick(cycle);
+        }
+        cluster.issue(0, 4, make_load(0x1000, 0)).unwrap();
+        let completion = assert_completes!(&mut cluster, 0, 4, MAX_CYCLES);
+        (completion.completed_at - 4, cluster.dram_share_stats())
+    };
+
+    let (quiet, _) = miss_latency(&cfg, 4096);
+    cfg.background.enabled = true;
+    let (contended, share) = miss_latency(&cfg, 4096);
+    assert!(contended > quiet, "{contended} <= {quiet}");
+    assert!(share.dma_bytes > 0);
+    assert!(share.demand_bytes > 0);
+    assert!(share.dma_wait_cycles > 0);
+    assert!((share.demand_share() + share.dma_share() - 1.0).abs() < 1e-9);
+    assert_eq!(share.tensor_bytes, 0);
+}
+
+#[test]
+fn background_cap_limits_agent_bandwidth() {
+    let mut cfg = GmemFlowConfig::zeroed();
+    cfg.background.enabled = true;
+    cfg.background.dma_bytes_per_cycle = 16;
+    let mut cluster = ClusterGmemGraph::new(cfg, 1, 1);
+    cluster.submit_background(TrafficAgent::Dma, 1024);
+    for cycle in 0..32 {
+        cluster.tick(cycle);
+    }
+    // 64-byte chunks at 16 bytes per cycle: one chunk every 4 cycles.
+    assert_eq!(cluster.dram_share_stats().dma_bytes, 8 * 64);
+    assert_eq!(
+        cluster.background_pending_bytes(TrafficAgent::Dma),
+        1024 - 8 * 64
+    );
+}
+
+#[test]
+fn dram_interleave_picks_channel_from_address() {
+    let bits = DramConfig {
+        channels: 4,
+        interleave: DramInterleave::Bits,
+        interleave_bit: 8,
+    };
+    let picked: Vec<usize> = [0x000, 0x0ff, 0x100, 0x200, 0x300, 0x400]
+        .into_iter()
+        .map(|addr| bits.channel_for(addr))
+        .collect();
+    assert_eq!(picked, [0, 0, 1, 2, 3, 0]);
+
+    // A 1 KiB stride lands on one channel under bit interleaving; hashing
+    // spreads it out.
+    let hash = DramConfig {
+        interleave: DramInterleave::Hash,
+        ..bits
+    };
+    let strided = |cfg: &DramConfig| {
+        let mut used = [false; 4];
+        for i in 0..64 {
+            used[cfg.channel_for(i * 0x400)] = true;
+        }
+        used.iter().filter(|&&used| used).count()
+    };
+    assert_eq!(strided(&bits), 1);
+    assert_eq!(strided(&hash), 4);
+    assert_eq!(DramConfig::default().channel_for(0x1234_5678), 0);
+}
+
+#[test]
+fn dram_channels_split_miss_bandwidth() {
+    let mut cfg = GmemFlowConfig::zeroed();
+    cfg.policy.l0_enabled = false;
+    cfg.nodes.dram.bytes_per_cycle = 4;
+    cfg.dram.interleave_bit = 5;
+    let stream = |cfg: &GmemFlowConfig| {
+        let mut cluster = ClusterGmemGraph::new(cfg.clone(), 1, 1);
+        let mut issued = 0u64;
+        let mut done = 0;
+        let mut cycle = 0;
+        while done < 8 {
+            assert!(cycle < 10 * MAX_CYCLES, "misses did not drain");
+            if issued < 8 && cluster.issue(0, cycle, make_load(issued * 32, 0)).is_ok() {
+                issued += 1;
+            }
+            cluster.tick(cycle);
+            while cluster.pop_completion(0).is_some() {
+                done += 1;
+            }
+            cycle += 1;
+        }
+        (cycle, cluster.dram_channel_stats())
+    };
+
+    let (one, stats) = stream(&cfg);
+    assert_eq!(stats.len(), 1);
+    assert_eq!(stats[0].requests, 8);
+
+    cfg.dram.channels = 2;
+    let (two, stats) = stream(&cfg);
+    assert!(two < one, "{two} >= {one}");
+    assert_eq!(stats.len(), 2);
+    for channel in &stats {
+        assert_eq!(channel.requests, 4);
+        assert!(channel.utilization > 0.0 && channel.utilization <= 1.0);
+    }
+}
+
+#[test]
+fn remote_region_misses_cross_the_die_link() {
+    let mut cfg = GmemFlowConfig::zeroed();
+    cfg.remote.dies = 2;
+    for server in [&mut cfg.remote.link, &mut cfg.remote.memory] {
+        server.base_latency = 0;
+        server.bytes_per_cycle = 1024;
+    }
+    cfg.remote.link.base_latency = 20;
+    let mut hbm = region("hbm", 0x10000, 0x20000, MemoryRegionKind::Remote);
+    hbm.die = 2;
+    let map = MemoryMapConfig { regions: vec![hbm] };
+    let miss = |addr: u64| {
+        let mut cluster = ClusterGmemGraph::new_with_regions(cfg.clone(), map.clone(), 1, 1);
+        cluster.issue(0, 0, make_load(addr, 0)).unwrap();
+        let completion = assert_completes!(&mut cluster, 0, 0, MAX_CYCLES);
+        (completion.completed_at, cluster.die_traffic_stats())
+    };
+
+    let (local_at, local_dies) = miss(0x1000);
+    let (remote_at, remote_dies) = miss(0x10000);
+    // Out over the link and back again.
+    assert!(remote_at >= local_at + 40, "{local_at} vs {remote_at}");
+
+    let requests =
+        |dies: &[DieTrafficStats]| dies.iter().map(|die| die.requests).collect::<Vec<_>>();
+    assert_eq!(requests(&local_dies), [1, 0, 0]);
+    assert_eq!(requests(&remote_dies), [0, 0, 1]);
+    assert!(remote_dies[2].link_out_bytes > 0);
+    assert!(remote_dies[2].link_in_bytes > 0);
+    assert_eq!(remote_dies[1].link_out_bytes, 0);
+}
+
+#[test]
+fn no_remote_dies_reports_no_die_traffic() {
+    let mut cluster = ClusterGmemGraph::new(GmemFlowConfig::zeroed(), 1, 1);
+    cluster.issue(0, 0, make_load(0x1000, 0)).unwrap();
+    let _ = assert_completes!(&mut cluster, 0, 0, MAX_CYCLES);
+    assert!(cluster.die_traffic_stats().is_empty());
+}
+
+#[test]
+#[should_panic(expected = "memory region hbm is on die 3, but gmem.remote has 2 remote dies")]
+fn remote_region_on_a_missing_die_is_rejected() {
+    let mut cfg = GmemFlowConfig::zeroed();
+    cfg.remote.dies = 2;
+    let mut hbm = region("hbm", 0x10000, 0x20000, MemoryRegionKind::Remote);
+    hbm.die = 3;
+    let map = MemoryMapConfig { regions: vec![hbm] };
+    let _ = ClusterGmemGraph::new_with_regions(cfg, map, 1, 1);
+}
+
+#[test]
+fn link_latency_adds_to_miss_latency() {
+    let mut cfg = GmemFlowConfig::zeroed();
+    let miss = |cfg: &GmemFlowConfig| {
+        let mut cluster = ClusterGmemGraph::new(cfg.clone(), 1, 1);
+        cluster.issue(0, 0, make_load(0x1000, 0)).unwrap();
+        assert_completes!(&mut cluster, 0, 0, MAX_CYCLES).completed_at
+    };
+    let base = miss(&cfg);
+    cfg.links.l2_mshr_to_dram = Some(LinkConfig {
+        latency: 10,
+        ..cfg.links.default
+    });
+    assert_eq!(miss(&cfg), base + 10);
+}
+
+#[test]
+fn built_graphs_route_every_request_shape() {
+    let mut cfg = GmemFlowConfig::zeroed();
+    cfg.levels[1].banks = 2;
+    cfg.levels[2].banks = 4;
+    cfg.dram.channels = 2;
+    cfg.remote.dies = 2;
+    for l0_enabled in [true, false] {
+        for crossbar in [true, false] {
+            cfg.policy.l0_enabled = l0_enabled;
+            cfg.crossbar.enabled = crossbar;
+            let validation = ClusterGmemGraph::new(cfg.clone(), 2, 3).validate();
+            assert!(validation.is_ok(), "{validation}");
+        }
+    }
+}
+
+#[test]
+fn salvaged_banks_are_not_built_or_mapped_to() {
+    let mut cfg = GmemFlowConfig::zeroed();
+    cfg.policy.l0_enabled = false;
+    cfg.levels[1].banks = 4;
+    cfg.levels[2].banks = 4;
+    cfg.salvage.disabled_l1_banks = vec![1];
+    cfg.salvage.disabled_l2_banks = vec![0, 3];
+    let mut cluster = ClusterGmemGraph::new(cfg.clone(), 1, 2);
+    let validation = cluster.validate();
+    assert!(validation.is_ok(), "{validation}");
+    let names = cluster.node_names();
+    for bank in ["cluster0_l1_tag_1", "l2_tag_0", "l2_tag_3"] {
+        assert!(!names.iter().any(|name| name == bank), "{bank} was built");
+    }
+    for bank in ["cluster0_l1_tag_3", "l2_tag_1", "l2_tag_2"] {
+        assert!(names.iter().any(|name| name == bank), "{bank} is missing");
+    }
+
+    // Lines still reach every enabled bank and come back.
+    let mut l2_banks = std::collections::BTreeSet::new();
+    for line in 0..32u64 {
+        let addr = 0x1000 + line * cfg.policy.l2_line_bytes as u64;
+        let issue = cluster.issue(0, line * 100, make_load(addr, 0)).unwrap();
+        let completion = assert_completes!(&mut cluster, 0, line * 100, MAX_CYCLES);
+        assert_eq!(completion.request.id, issue.request_id);
+        assert!(completion.request.l1_bank < 3);
+        l2_banks.insert(completion.request.l2_bank);
+    }
+    assert_eq!(l2_banks.into_iter().collect::<Vec<_>>(), [0, 1]);
+}
+
+#[test]
+fn salvage_only_moves_lines_of_disabled_banks() {
+    let full = salvage::BankMap::new("disabled_l2_banks", 4, &[]).unwrap();
+    let salvaged = salvage::BankMap::new("disabled_l2_banks", 4, &[2]).unwrap();
+    assert_eq!(salvaged.enabled(), [0, 1, 3]);
+    for line in 0..256 {
+        let bank = full.bank_for(line, 7);
+        let moved = salvaged.enabled()[salvaged.bank_for(line, 7)];
+        if bank != 2 {
+            assert_eq!(moved, bank);
+        }
+        assert_ne!(moved, 2);
+    }
+    assert!(salvage::BankMap::new("disabled_l2_banks", 2, &[0, 1]).is_err());
+    assert!(salvage::BankMap::new("disabled_l2_banks", 2, &[2]).is_err());
+}
+
+#[test]
+fn validation_reports_shapes_without_a_route() {
+    let cfg = GmemFlowConfig::zeroed();
+    let (graph, core_nodes, _, _, _, _) = graph_build::build_cluster_graph(&cfg, 1, 1);
+    let ports = [(
+        core_nodes[0].ingress_node,
+        core_nodes[0].return_nodes.clone(),
+    )];
+    // One more L2 bank than the graph was built with.
+    let shape = validate::GraphShape {
+        ports: &ports,
+        cores_per_cluster: 1,
+        l0_enabled: cfg.policy.l0_enabled,
+        l1_banks: 1,
+        l2_banks: 2,
+        dram_channels: 1,
+        remote_dies: 0,
+    };
+    let validation = validate::validate_graph(&graph, &shape);
+    assert!(validation.unreachable_nodes.is_empty());
+    assert!(!validation.unrouted.is_empty());
+    for unrouted in &validation.unrouted {
+        assert!(unrouted.request.contains("l2_bank=1"));
+        assert!(!unrouted.returns);
+        let stranded_at = if unrouted.request.contains("l1_writeback") {
+            "cluster0_l1_wb_0"
+        } else {
+            "cluster0_l1_mshr_0"
+        };
+        assert_eq!(unrouted.stranded_at, [stranded_at]);
+    }
+}
+
+#[test]
+fn adaptive_routing_spreads_responses_over_return_paths() {
+    let run = |enabled: bool| {
+        let mut cfg = GmemFlowConfig::zeroed();
+        cfg.policy.l0_enabled = false;
+        cfg.nodes.return_path.bytes_per_cycle = 4;
+        cfg.adaptive_routing.return_paths = 2;
+        cfg.adaptive_routing.enabled = enabled;
+        cfg.adaptive_routing.threshold = 1;
+        let mut cluster = ClusterGmemGraph::new(cfg, 1, 1);
+        assert!(cluster.validate().is_ok(), "{}", cluster.validate());
+        let mut pending: Vec<u64> = (0..8).map(|idx| idx * 0x40).collect();
+        let mut completed = 0;
+        for cycle in 0..MAX_CYCLES {
+            if let Some(&addr) = pending.first() {
+                if cluster.issue(0, cycle, make_load(addr, 0)).is_ok() {
+                    pending.remove(0);
+                }
+            }
+            cluster.tick(cycle);
+            while cluster.pop_completion(0).is_some() {
+                completed += 1;
+            }
+        }
+        assert_eq!(completed, 8);
+        cluster.adaptive_route_stats()
+    };
+
+    let stats = run(false);
+    assert_eq!((stats.routed, stats.rerouted), (0, 0));
+    let stats = run(true);
+    assert!(stats.routed >= 8);
+    assert!(stats.rerouted > 0, "{stats:?}");
+}
+
+#[test]
+fn refills_take_the_data_port_from_hits() {
+    let run = |refill_port_cycles: u64| {
+        let mut cfg = GmemFlowConfig::zeroed();
+        cfg.policy.l0_enabled = false;
+        cfg.levels[1].banks = 1;
+        cfg.levels[1].refill_port_cycles = refill_port_cycles;
+        let mut cluster = ClusterGmemGraph::new(cfg, 1, 1);
+        cluster.issue(0, 0, make_load(0x1000, 0)).unwrap();
+        let warm = complete_one(&mut cluster, 0, 0, MAX_CYCLES).completed_at;
+
+        // Misses to other lines refill the bank while hits stream in.
+        let mut latencies = Vec::new();
+        let mut issued_at = Vec::new();
+        for cycle in warm + 1..warm + MAX_CYCLES {
+            let step = cycle - warm;
+            if step <= 4 {
+                cluster
+                    .issue(0, cycle, make_load(0x2000 + step * 0x1000, 0))
+                    .unwrap();
+            } else if step <= 20 && cluster.issue(0, cycle, make_load(0x1000, 0)).is_ok() {
+                issued_at.push(cycle);
+            }
+            cluster.tick(cycle);
+            while let Some(completion) = cluster.pop_completion(0) {
+                if completion.request.addr == 0x1000 {
+                    let issued = issued_at[latencies.len()];
+                    latencies.push(completion.completed_at - issued);
+                }
+            }
+        }
+        assert_eq!(latencies.len(), issued_at.len());
+        (latencies, cluster.refill_contention())
+    };
+
+    let (free, stats) = run(0);
+    assert_eq!(stats.l1.refills, 0);
+    assert_eq!(stats.l1.delayed_hits, 0);
+
+    let (contended, stats) = run(4);
+    // The warming miss and the four later ones.
+    assert_eq!(stats.l1.refills, 5);
+    assert_eq!(stats.l1.stolen_cycles, 5 * 4);
+    assert_eq!(stats.l2.refills, 0);
+    assert!(stats.l1.delayed_hits > 0, "{stats:?}");
+    assert!(stats.l1.avg_hit_inflation() > 0.0);
+    let total = |latencies: &[u64]| latencies.iter().sum::<u64>();
+    assert!(
+        total(&contended) > total(&free),
+        "{contended:?} vs {free:?}"
+    );
+}
+
+#[test]
+fn sampled_requests_record_their_path() {
+    let cfg = GmemFlowConfig::zeroed();
+    let mut cluster = ClusterGmemGraph::new(cfg, 1, 1);
+    cluster.set_path_sample(2);
+    let mut cycle = 0;
+    for id in 1..=4u64 {
+        let mut req = make_load(0x1000 * id, 0);
+        req.id = id;
+        cluster.issue(0, cycle, req).unwrap();
+        let completion = assert_completes!(&mut cluster, 0, cycle, MAX_CYCLES);
+        assert_eq!(completion.request.id, id);
+        match completion.request.path {
+            Some(hops) => {
+                assert_eq!(id % 2, 0, "request {id} was not sampled");
+                assert!(hops.len() > 1);
+                let mut last_exit = 0;
+                for hop in &hops {
+                    let exit = hop.exit.expect("every hop is left by completion");
+                    assert!(hop.enter >= last_exit && exit >= hop.enter, "{hops:?}");
+                    last_exit = exit;
+                }
+            }
+            None => assert_eq!(id % 2, 1, "request {id} should be sampled"),
+        }
+        cycle += MAX_CYCLES;
+    }
+}
+
+#[test]
+fn injected_traffic_completes_at_the_sink_and_shares_the_l2() {
+    let mut cfg = GmemFlowConfig::zeroed();
+    cfg.injectors = InjectorConfig {
+        clusters: 1,
+        requests_per_cycle: 1.0,
+        max_outstanding: 4,
+        ..InjectorConfig::default()
+    };
+    let mut cluster = ClusterGmemGraph::new(cfg, 1, 1);
+    for cycle in 0..MAX_CYCLES {
+        cluster.tick(cycle);
+        assert!(cluster.pop_completion(0).is_none());
+    }
+    let stats = cluster.injector_stats();
+    assert!(stats.completed > 0, "{stats:?}");
+    assert!(stats.issued >= stats.completed);
+    assert!(stats.dropped > 0, "offers past max_outstanding are dropped");
+
+    let addr = InjectorConfig::default().base;
+    cluster.issue(0, MAX_CYCLES, make_load(addr, 0)).unwrap();
+    let comp = assert_completes!(&mut cluster, 0, MAX_CYCLES, MAX_CYCLES);
+    assert!(comp.request.l2_hit, "the injector filled the line");
+}
+
+#[test]
+fn stalled_dram_holds_misses_back_without_losing_them() {
+    const WINDOW: Cycle = 100;
+    let mut cfg = GmemFlowConfig::zeroed();
+    cfg.stall_injection = vec![StallInjection {
+        node: "dram".to_string(),
+        start: 0,
+        cycles: WINDOW,
+    }];
+    let mut cluster = ClusterGmemGraph::new(cfg, 1, 1);
+    let addrs: Vec<u64> = (0..4).map(|line| 0x1000 + line * 0x100).collect();
+    for &addr in &addrs {
+        cluster.issue(0, 0, make_load(addr, 0)).unwrap();
+    }
+
+    let mut completed = Vec::new();
+    for cycle in 0..WINDOW + MAX_CYCLES {
+        cluster.tick(cycle);
+        while let Some(completion) = cluster.pop_completion(0) {
+            assert!(completion.completed_at >= WINDOW, "{completion:?}");
+            completed.push(completion.request.addr);
+        }
+    }
+    completed.sort_unstable();
+    assert_eq!(completed, addrs);
+    let stalls = cluster.injected_stalls();
+    assert_eq!(stalls.len(), 1);
+    assert_eq!((stalls[0].start, stalls[0].end), (0, WINDOW));
+    assert!(stalls[0].rejected > 0, "{stalls:?}");
+}
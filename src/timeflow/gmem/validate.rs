@@ -0,0 +1,213 @@
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::timeflow::graph::FlowGraph;
+use crate::timeflow::types::{CoreFlowPayload, NodeId};
+
+use super::request::{GmemRequest, GmemRequestKind};
+
+/// A request shape that does not make it from its core's ingress node back
+/// to that core's return node.
+#[derive(Debug, Clone, Serialize)]
+pub struct UnroutedRequest {
+    pub core_id: usize,
+    /// Kind and routing fields, e.g.
+    /// `load l2_hit l1_writeback l1_bank=1 l2_bank=0 dram_channel=0`.
+    pub request: String,
+    /// Nodes where it matches none of the output edges.
+    pub stranded_at: Vec<String>,
+    /// Nodes without outputs it reaches other than its own return node.
+    pub wrong_sinks: Vec<String>,
+    /// Whether any path gets it back to its own return node.
+    pub returns: bool,
+}
+
+/// What `ClusterGmemGraph::validate` found.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct GraphValidation {
+    /// Nodes no core's ingress node leads to.
+    pub unreachable_nodes: Vec<String>,
+    pub unrouted: Vec<UnroutedRequest>,
+}
+
+impl GraphValidation {
+    pub fn is_ok(&self) -> bool {
+        self.unreachable_nodes.is_empty() && self.unrouted.is_empty()
+    }
+}
+
+impl fmt::Display for GraphValidation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for node in &self.unreachable_nodes {
+            writeln!(f, "node {node} is unreachable from every core")?;
+        }
+        for unrouted in &self.unrouted {
+            write!(f, "core {}: {}", unrouted.core_id, unrouted.request)?;
+            if !unrouted.returns {
+                write!(f, " never reaches its return node")?;
+            }
+            if !unrouted.stranded_at.is_empty() {
+                write!(f, " strands at {}", unrouted.stranded_at.join(", "))?;
+            }
+            if !unrouted.wrong_sinks.is_empty() {
+                write!(f, " ends at {}", unrouted.wrong_sinks.join(", "))?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// Ports and routing dimensions of a graph built by `build_cluster_graph`.
+pub(crate) struct GraphShape<'a> {
+    /// Ingress and return nodes of every core, by global core id.
+    pub(crate) ports: &'a [(NodeId, Vec<NodeId>)],
+    pub(crate) cores_per_cluster: usize,
+    pub(crate) l0_enabled: bool,
+    pub(crate) l1_banks: usize,
+    pub(crate) l2_banks: usize,
+    pub(crate) dram_channels: usize,
+    pub(crate) remote_dies: usize,
+}
+
+/// The level a memory request is served from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum HitLevel {
+    L0,
+    L1,
+    L2,
+    Dram,
+}
+
+/// Every kind and flag combination the cluster can issue for `core_id`.
+/// Each bank and channel index is tried, but not every combination of them.
+fn probes(shape: &GraphShape, core_id: usize) -> Vec<GmemRequest> {
+    let cluster_id = core_id / shape.cores_per_cluster.max(1);
+    let mut probes = vec![
+        GmemRequest::new_flush_l0(0, 0),
+        GmemRequest::new_flush_l1(0, 0),
+    ];
+    let levels = [HitLevel::L0, HitLevel::L1, HitLevel::L2, HitLevel::Dram]
+        .into_iter()
+        .filter(|&level| level != HitLevel::L0 || shape.l0_enabled);
+    let indices = shape.l1_banks.max(shape.l2_banks).max(shape.dram_channels);
+    for level in levels {
+        for l1_writeback in [false, true] {
+            for l2_writeback in [false, true] {
+                if (l1_writeback && level < HitLevel::L2)
+                    || (l2_writeback && level < HitLevel::Dram)
+                {
+                    continue;
+                }
+                // Only requests that reach memory care which die serves them.
+                let dies = if level == HitLevel::Dram {
+                    shape.remote_dies
+                } else {
+                    0
+                };
+                for (is_load, die) in [true, false]
+                    .into_iter()
+                    .flat_map(|is_load| (0..=dies).map(move |die| (is_load, die)))
+                {
+                    for index in 0..indices {
+                        let mut req = GmemRequest::new(0, 4, 1, is_load);
+                        req.die = die;
+                        req.l0_hit = level == HitLevel::L0;
+                        req.l1_hit = level == HitLevel::L1;
+                        req.l2_hit = level == HitLevel::L2;
+                        req.l1_writeback = l1_writeback;
+                        req.l2_writeback = l2_writeback;
+                        req.l1_bank = index % shape.l1_banks.max(1);
+                        req.l2_bank = index % shape.l2_banks.max(1);
+                        req.dram_channel = index % shape.dram_channels.max(1);
+                        probes.push(req);
+                    }
+                }
+            }
+        }
+    }
+    for probe in &mut probes {
+        probe.core_id = core_id;
+        probe.cluster_id = cluster_id;
+    }
+    probes
+}
+
+fn describe(req: &GmemRequest) -> String {
+    let kind = match req.kind {
+        GmemRequestKind::Load => "load",
+        GmemRequestKind::Store => "store",
+        GmemRequestKind::FlushL0 => "flush_l0",
+        GmemRequestKind::FlushL1 => "flush_l1",
+    };
+    if !req.kind.is_mem() {
+        return kind.to_string();
+    }
+    let mut parts = vec![kind.to_string()];
+    for (flag, set) in [
+        ("l0_hit", req.l0_hit),
+        ("l1_hit", req.l1_hit),
+        ("l2_hit", req.l2_hit),
+        ("l1_writeback", req.l1_writeback),
+        ("l2_writeback", req.l2_writeback),
+    ] {
+        if set {
+            parts.push(flag.to_string());
+        }
+    }
+    parts.push(format!(
+        "l1_bank={} l2_bank={} dram_channel={}",
+        req.l1_bank, req.l2_bank, req.dram_channel
+    ));
+    if req.die > 0 {
+        parts.push(format!("die={}", req.die));
+    }
+    parts.join(" ")
+}
+
+/// Checks that every node is reachable from some core and that every
+/// request the cluster can issue is routed back to its own core.
+pub(crate) fn validate_graph(
+    graph: &FlowGraph<CoreFlowPayload>,
+    shape: &GraphShape,
+) -> GraphValidation {
+    let names = |nodes: &[NodeId]| -> Vec<String> {
+        nodes
+            .iter()
+            .map(|&node| graph.node_name(node).to_string())
+            .collect()
+    };
+    let ingress: Vec<NodeId> = shape.ports.iter().map(|(ingress, _)| *ingress).collect();
+    let unreachable_nodes = names(&graph.unreachable_from(&ingress));
+
+    let mut unrouted = Vec::new();
+    for (core_id, (ingress, return_nodes)) in shape.ports.iter().enumerate() {
+        for probe in probes(shape, core_id) {
+            let request = describe(&probe);
+            let trace = graph.trace_route(*ingress, &CoreFlowPayload::Gmem(probe));
+            let wrong_sinks: Vec<NodeId> = trace
+                .sinks
+                .iter()
+                .copied()
+                .filter(|sink| !return_nodes.contains(sink))
+                .collect();
+            let returns = trace.sinks.iter().any(|sink| return_nodes.contains(sink));
+            if returns && wrong_sinks.is_empty() && trace.stranded.is_empty() {
+                continue;
+            }
+            unrouted.push(UnroutedRequest {
+                core_id,
+                request,
+                stranded_at: names(&trace.stranded),
+                wrong_sinks: names(&wrong_sinks),
+                returns,
+            });
+        }
+    }
+
+    GraphValidation {
+        unreachable_nodes,
+        unrouted,
+    }
+}
@@ -0,0 +1,163 @@
+use std::collections::VecDeque;
+use std::ops::AddAssign;
+
+use serde::{Deserialize, Serialize};
+
+use crate::timeq::Cycle;
+
+/// Write-combining buffer in front of the local DRAM channels. When
+/// enabled, an L2 writeback occupies its channel for a write burst on top
+/// of the miss it travels with, unless an earlier writeback to the same
+/// burst is still open in the buffer.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct WriteCombiningConfig {
+    /// Off leaves writebacks free at DRAM, as without this section.
+    pub enabled: bool,
+    /// DRAM write burst size; writebacks to the same aligned burst combine.
+    pub burst_bytes: u32,
+    /// Cycles a burst stays open to later writebacks after the one that
+    /// opened it. 0 never combines.
+    pub window: u64,
+    /// Bursts held open at once. Opening one more writes out the oldest.
+    pub entries: usize,
+}
+
+impl Default for WriteCombiningConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            burst_bytes: 256,
+            window: 64,
+            entries: 8,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct WriteCombiningStats {
+    /// L2 writebacks to local DRAM.
+    pub writebacks: u64,
+    /// Write bursts they took after combining.
+    pub bursts: u64,
+    pub write_bytes: u64,
+    /// Channel cycles the combined writebacks would have taken as bursts of
+    /// their own, which reads no longer wait behind.
+    pub saved_cycles: u64,
+}
+
+impl WriteCombiningStats {
+    pub fn combined(&self) -> u64 {
+        self.writebacks.saturating_sub(self.bursts)
+    }
+
+    pub fn combined_fraction(&self) -> f64 {
+        if self.writebacks == 0 {
+            0.0
+        } else {
+            self.combined() as f64 / self.writebacks as f64
+        }
+    }
+}
+
+impl AddAssign<&WriteCombiningStats> for WriteCombiningStats {
+    fn add_assign(&mut self, other: &WriteCombiningStats) {
+        self.writebacks = self.writebacks.saturating_add(other.writebacks);
+        self.bursts = self.bursts.saturating_add(other.bursts);
+        self.write_bytes = self.write_bytes.saturating_add(other.write_bytes);
+        self.saved_cycles = self.saved_cycles.saturating_add(other.saved_cycles);
+    }
+}
+
+/// Bursts open in the buffer, oldest first, with the cycle each opened.
+#[derive(Clone)]
+pub(crate) struct WriteCombiner {
+    config: WriteCombiningConfig,
+    dram_bytes_per_cycle: u32,
+    open: VecDeque<(u64, Cycle)>,
+    stats: WriteCombiningStats,
+}
+
+impl WriteCombiner {
+    pub(crate) fn new(config: WriteCombiningConfig, dram_bytes_per_cycle: u32) -> Self {
+        Self {
+            config,
+            dram_bytes_per_cycle,
+            open: VecDeque::new(),
+            stats: WriteCombiningStats::default(),
+        }
+    }
+
+    pub(crate) fn set_config(&mut self, config: WriteCombiningConfig, dram_bytes_per_cycle: u32) {
+        self.config = config;
+        self.dram_bytes_per_cycle = dram_bytes_per_cycle;
+        if !config.enabled {
+            self.open.clear();
+        }
+    }
+
+    fn burst(&self, addr: u64) -> u64 {
+        addr / self.config.burst_bytes.max(1) as u64
+    }
+
+    fn expire(&mut self, now: Cycle) {
+        let window = self.config.window;
+        while let Some(&(_, opened)) = self.open.front() {
+            if now.saturating_sub(opened) < window {
+                break;
+            }
+            self.open.pop_front();
+        }
+    }
+
+    fn is_open(&self, burst: u64) -> bool {
+        self.open.iter().any(|&(open, _)| open == burst)
+    }
+
+    /// Bytes a writeback of the line at `addr` would charge its DRAM
+    /// channel at `now`: 0 if it combines into an open burst.
+    pub(crate) fn write_bytes(&mut self, now: Cycle, addr: u64) -> u32 {
+        if !self.config.enabled {
+            return 0;
+        }
+        self.expire(now);
+        if self.is_open(self.burst(addr)) {
+            0
+        } else {
+            self.config.burst_bytes.max(1)
+        }
+    }
+
+    /// Puts the writeback of the line at `addr` into the buffer, once it
+    /// has been accepted.
+    pub(crate) fn record(&mut self, now: Cycle, addr: u64) {
+        if !self.config.enabled {
+            return;
+        }
+        self.expire(now);
+        let burst_bytes = self.config.burst_bytes.max(1);
+        let burst = self.burst(addr);
+        self.stats.writebacks = self.stats.writebacks.saturating_add(1);
+        if self.is_open(burst) {
+            let cycles = (burst_bytes as u64).div_ceil(self.dram_bytes_per_cycle.max(1) as u64);
+            self.stats.saved_cycles = self.stats.saved_cycles.saturating_add(cycles);
+            return;
+        }
+        if self.config.window > 0 {
+            if self.open.len() >= self.config.entries.max(1) {
+                self.open.pop_front();
+            }
+            self.open.push_back((burst, now));
+        }
+        self.stats.bursts = self.stats.bursts.saturating_add(1);
+        self.stats.write_bytes = self.stats.write_bytes.saturating_add(burst_bytes as u64);
+    }
+
+    pub(crate) fn stats(&self) -> WriteCombiningStats {
+        self.stats
+    }
+
+    pub(crate) fn clear_stats(&mut self) {
+        self.stats = WriteCombiningStats::default();
+    }
+}
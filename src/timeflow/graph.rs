@@ -1,9 +1,17 @@
 use std::collections::VecDeque;
+use std::ops::Range;
 use std::sync::Arc;
 
+use serde::{Deserialize, Serialize};
+
+use crate::check;
 use crate::sim::perf_log;
+use crate::timeflow::stats_switch::StatsSwitch;
 use crate::timeflow::types::{LinkId, NodeId};
-use crate::timeq::{normalize_retry, Backpressure, Cycle, ServiceRequest, ServiceResult, Ticket};
+use crate::timeq::{
+    normalize_retry, Backpressure, Cycle, ServerConfig, ServerStats, ServiceRequest, ServiceResult,
+    Ticket,
+};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum LinkBackpressure {
@@ -11,43 +19,64 @@ pub enum LinkBackpressure {
     Bytes { capacity: u32 },
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 struct LinkEntry<T> {
     result: ServiceResult<T>,
     size_bytes: u32,
+    /// Earliest cycle the entry can be handed to the destination.
+    deliver_at: Cycle,
 }
 
 impl<T> LinkEntry<T> {
-    fn new(result: ServiceResult<T>) -> Self {
+    fn new(result: ServiceResult<T>, deliver_at: Cycle) -> Self {
         let size_bytes = result.ticket.size_bytes();
-        Self { result, size_bytes }
+        Self {
+            result,
+            size_bytes,
+            deliver_at,
+        }
     }
 
     fn size_bytes(&self) -> u32 {
         self.size_bytes
     }
 
-    fn into_request(self) -> (ServiceRequest<T>, Ticket) {
-        let LinkEntry { result, size_bytes } = self;
+    fn into_request(self) -> (ServiceRequest<T>, Ticket, Cycle) {
+        let LinkEntry {
+            result,
+            size_bytes,
+            deliver_at,
+        } = self;
         let ServiceResult { payload, ticket } = result;
-        (ServiceRequest::new(payload, size_bytes), ticket)
+        (ServiceRequest::new(payload, size_bytes), ticket, deliver_at)
     }
 
-    fn from_parts(request: ServiceRequest<T>, ticket: Ticket) -> Self {
+    fn from_parts(request: ServiceRequest<T>, ticket: Ticket, deliver_at: Cycle) -> Self {
         let ServiceRequest {
             payload,
             size_bytes,
         } = request;
         let result = ServiceResult { payload, ticket };
-        Self { result, size_bytes }
+        Self {
+            result,
+            size_bytes,
+            deliver_at,
+        }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Link<T> {
     entries_capacity: usize,
     bytes_capacity: Option<u32>,
     bytes_in_use: u32,
+    /// Cycles an entry spends on the wire before it can be delivered, on
+    /// top of waiting for the destination. Entries still occupy the link
+    /// meanwhile.
+    latency: Cycle,
+    /// Deliveries the destination takes per cycle through this link,
+    /// counting what other links delivered to it that cycle.
+    accepts_per_cycle: Option<u32>,
     queue: VecDeque<LinkEntry<T>>,
 }
 
@@ -62,10 +91,37 @@ impl<T> Link<T> {
             entries_capacity,
             bytes_capacity,
             bytes_in_use: 0,
+            latency: 0,
+            accepts_per_cycle: None,
             queue: VecDeque::with_capacity(entries_capacity),
         }
     }
 
+    /// Delays delivery of every entry by `latency` cycles after it enters
+    /// the link, like a pipelined wire or a chain of registers.
+    pub fn with_latency(mut self, latency: Cycle) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    pub fn latency(&self) -> Cycle {
+        self.latency
+    }
+
+    /// Stops delivering through this link once its destination has taken
+    /// `accepts` entries in a cycle, from any link, however much queue
+    /// space it has left. Models a single-ported structure behind a wide
+    /// link.
+    pub fn with_accepts_per_cycle(mut self, accepts: u32) -> Self {
+        assert!(accepts > 0, "accepts_per_cycle must be > 0");
+        self.accepts_per_cycle = Some(accepts);
+        self
+    }
+
+    pub fn accepts_per_cycle(&self) -> Option<u32> {
+        self.accepts_per_cycle
+    }
+
     pub fn len(&self) -> usize {
         self.queue.len()
     }
@@ -86,7 +142,7 @@ impl<T> Link<T> {
         true
     }
 
-    fn try_push(&mut self, result: ServiceResult<T>) -> Result<(), LinkBackpressure> {
+    fn try_push(&mut self, now: Cycle, result: ServiceResult<T>) -> Result<(), LinkBackpressure> {
         let size_bytes = result.ticket.size_bytes();
         if self.queue.len() >= self.entries_capacity {
             return Err(LinkBackpressure::Capacity {
@@ -99,17 +155,59 @@ impl<T> Link<T> {
             }
         }
 
-        self.queue.push_back(LinkEntry::new(result));
+        let deliver_at = now.saturating_add(self.latency);
+        self.queue.push_back(LinkEntry::new(result, deliver_at));
         self.bytes_in_use = self.bytes_in_use.saturating_add(size_bytes);
         Ok(())
     }
 
     fn pop_front(&mut self) -> Option<LinkEntry<T>> {
         let entry = self.queue.pop_front()?;
-        self.bytes_in_use = self.bytes_in_use.saturating_sub(entry.size_bytes());
+        self.release_bytes(entry.size_bytes());
         Some(entry)
     }
 
+    /// Whether the head entry has finished crossing the link by `now`.
+    fn front_arrived(&self, now: Cycle) -> bool {
+        self.queue
+            .front()
+            .is_some_and(|entry| entry.deliver_at <= now)
+    }
+
+    /// Cycle the head entry became ready at its source, i.e. when it started
+    /// waiting in this link.
+    fn front_ready_at(&self) -> Option<Cycle> {
+        self.queue
+            .front()
+            .map(|entry| entry.result.ticket.ready_at())
+    }
+
+    /// Drops every entry matching `pred`, which sees the payload and the
+    /// ticket of the node the entry left; returns how many.
+    fn squash(&mut self, pred: &mut dyn FnMut(&T, &Ticket) -> bool) -> usize {
+        let before = self.queue.len();
+        let mut freed = 0u32;
+        self.queue.retain(|entry| {
+            let keep = !pred(&entry.result.payload, &entry.result.ticket);
+            if !keep {
+                freed = freed.saturating_add(entry.size_bytes());
+            }
+            keep
+        });
+        self.release_bytes(freed);
+        before - self.queue.len()
+    }
+
+    fn release_bytes(&mut self, bytes: u32) {
+        check!(
+            Link,
+            bytes <= self.bytes_in_use,
+            "link released {bytes} bytes with {} in use",
+            self.bytes_in_use
+        );
+        self.bytes_in_use = self.bytes_in_use.saturating_sub(bytes);
+    }
+
     fn push_front(&mut self, entry: LinkEntry<T>) {
         self.bytes_in_use = self.bytes_in_use.saturating_add(entry.size_bytes());
         self.queue.push_front(entry);
@@ -117,17 +215,175 @@ impl<T> Link<T> {
 }
 
 #[derive(Debug, Default, Clone)]
+#[non_exhaustive]
 pub struct EdgeStats {
     pub entries_pushed: u64,
     pub entries_delivered: u64,
     pub downstream_backpressure: u64,
     pub last_delivery_cycle: Option<Cycle>,
+    /// Entries delivered after waiting at least the graph's age threshold.
+    pub aged_deliveries: u64,
+    /// Longest an entry has waited in this link before delivery.
+    pub max_wait: Cycle,
+    /// Entries dropped by `FlowGraph::squash` before delivery.
+    pub squashed: u64,
+    /// Times the edge's predicate, or its source's route function, accepted
+    /// or turned away the source's head payload. A payload held back by a
+    /// full link is looked at again every cycle.
+    pub predicate_hits: u64,
+    pub predicate_misses: u64,
+    /// Cycles an arrived entry was held back because the destination had
+    /// already taken its `accepts_per_cycle`.
+    pub accept_limited: u64,
+    /// Entries sent down this edge in place of the one the source's route
+    /// function picked, whose destination was over the adaptive threshold.
+    pub adaptive_reroutes: u64,
+}
+
+/// How one node spent the cycles its graph has ticked.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NodeActivity {
+    /// Cycles the node's server was still moving an earlier transfer, or,
+    /// for nodes without a server, had requests outstanding.
+    pub busy_cycles: u64,
+    /// Cycles the node turned a link's delivery away because its queue was
+    /// full.
+    pub full_cycles: u64,
+}
+
+/// Nodes along a path, upstream first, whose queues were all full in the
+/// same cycles: the downstream one holds back the rest.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FullChain {
+    pub nodes: Vec<NodeId>,
+    /// Longest run of consecutive cycles the whole chain stayed full.
+    pub cycles: u64,
 }
 
+/// What `FlowGraph::tick` does with a ready payload that none of its node's
+/// output edges accepts, which would otherwise sit at the node forever.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MisrouteAction {
+    /// Count it and log a warning the first time a node strands a payload.
+    #[default]
+    Warn,
+    /// Count it and take it out of the node.
+    Drop,
+    Panic,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct MisrouteStats {
+    /// Payloads that matched none of their node's output edges.
+    pub misrouted: u64,
+    /// Of those, how many `MisrouteAction::Drop` discarded.
+    pub dropped: u64,
+}
+
+/// One `[[gmem.stall_injection]]` entry: the node named `node` refuses
+/// every admission for `cycles` cycles from `start`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct StallInjection {
+    pub node: String,
+    pub start: Cycle,
+    pub cycles: Cycle,
+}
+
+/// A window `FlowGraph::inject_stall` closed a node for, and how many
+/// admissions it turned away in it.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct InjectedStall {
+    pub node: String,
+    pub start: Cycle,
+    pub end: Cycle,
+    pub rejected: u64,
+}
+
+/// Traffic out of nodes with an adaptive route; see
+/// `FlowGraph::set_adaptive_route`.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct AdaptiveRouteStats {
+    /// Entries sent down edges that have alternates.
+    pub routed: u64,
+    /// Of those, how many went to an alternate of the edge they were routed
+    /// to.
+    pub rerouted: u64,
+}
+
+impl AdaptiveRouteStats {
+    pub fn rerouted_fraction(&self) -> f64 {
+        if self.routed == 0 {
+            0.0
+        } else {
+            self.rerouted as f64 / self.routed as f64
+        }
+    }
+}
+
+/// How often one edge's routing condition held; see `FlowGraph::route_coverage`.
+#[derive(Debug, Clone, Serialize)]
+pub struct RouteCoverage {
+    pub edge: String,
+    pub src: String,
+    pub dst: String,
+    /// Whether the edge has a predicate or its source a route function.
+    pub filtered: bool,
+    pub predicate_hits: u64,
+    pub predicate_misses: u64,
+    pub entries_pushed: u64,
+}
+
+impl RouteCoverage {
+    /// A filtered edge whose condition never held.
+    pub fn is_dead(&self) -> bool {
+        self.filtered && self.predicate_hits == 0
+    }
+}
+
+/// Where a payload put into a node can go; see `FlowGraph::trace_route`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RouteTrace {
+    /// Every node the payload can pass through, starting node included.
+    pub visited: Vec<NodeId>,
+    /// Nodes with outputs of which the payload matches none.
+    pub stranded: Vec<NodeId>,
+    /// Nodes without outputs the payload can end up at.
+    pub sinks: Vec<NodeId>,
+}
+
+/// One node a sampled request passed through.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PathHop {
+    pub node: String,
+    /// Cycle the node accepted the request.
+    pub enter: Cycle,
+    /// Cycle the request left the node; `None` while it is still there.
+    pub exit: Option<Cycle>,
+}
+
+/// The nodes a request visited, in order.
+pub type RequestPath = Vec<PathHop>;
+
+/// Finds the path a payload records its hops in, if it was picked for
+/// recording; see `FlowGraph::set_path_accessor`.
+pub type PathAccessor<T> = fn(&mut T) -> Option<&mut RequestPath>;
+
 type EdgePredicate<T> = Arc<dyn Fn(&T) -> bool + Send + Sync>;
 
+/// Outputs of a node whose destinations are interchangeable.
+#[derive(Clone)]
+struct AdaptiveRoute {
+    /// Output index -> its group in `groups`, if any.
+    group_of: Vec<Option<usize>>,
+    groups: Vec<Vec<usize>>,
+    threshold: usize,
+}
+
+#[derive(Clone)]
 struct Edge<T> {
-    _name: String,
+    name: String,
     buffer: Link<T>,
     src: NodeId,
     dst: NodeId,
@@ -147,7 +403,7 @@ impl<T> Edge<T> {
         predicate: Option<EdgePredicate<T>>,
     ) -> Self {
         Self {
-            _name: name.into(),
+            name: name.into(),
             buffer,
             src,
             dst,
@@ -170,14 +426,59 @@ pub trait TimedNode<T>: Send + Sync {
     fn peek_ready(&mut self, now: Cycle) -> Option<&ServiceResult<T>>;
     fn take_ready(&mut self, now: Cycle) -> Option<ServiceResult<T>>;
     fn outstanding(&self) -> usize;
+    /// Re-parameterizes the node's server, if it has one. Returns whether
+    /// the node accepted the new config.
+    fn set_server_config(&mut self, _config: ServerConfig) -> bool {
+        false
+    }
+    /// Occupies the node's bandwidth with `bytes` of traffic that moves
+    /// outside the graph, and returns the cycle it finishes. Nodes without a
+    /// bandwidth budget return `None`.
+    fn reserve_bandwidth(&mut self, _now: Cycle, _bytes: u32) -> Option<Cycle> {
+        None
+    }
+    /// Earliest cycle the node's server could start another transfer, if it
+    /// has one.
+    fn available_at(&self) -> Option<Cycle> {
+        None
+    }
+    fn server_stats(&self) -> Option<ServerStats> {
+        None
+    }
+    /// Drops queued requests matching `pred` that the node has not finished
+    /// servicing, and returns how many. Nodes that cannot squash keep
+    /// everything.
+    fn squash(&mut self, _pred: &mut dyn FnMut(&T, &Ticket) -> bool) -> usize {
+        0
+    }
+    /// A copy of the node with its queued and in-flight requests, for
+    /// `Sim::checkpoint`.
+    fn clone_box(&self) -> Box<dyn TimedNode<T>>;
 }
 
+impl<T> Clone for Box<dyn TimedNode<T>> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+#[derive(Clone)]
 struct GraphNode<T> {
     name: String,
     node: Box<dyn TimedNode<T>>,
     outputs: Vec<LinkId>,
     inputs: Vec<LinkId>,
     route_fn: Option<Arc<dyn Fn(&T) -> usize + Send + Sync>>,
+    adaptive: Option<AdaptiveRoute>,
+    /// Head payload last found matching no output edge, so each one is
+    /// counted once.
+    stranded: Option<Ticket>,
+    warned: bool,
+    activity: NodeActivity,
+    /// Last cycle the node turned a delivery away with a full queue.
+    full_at: Option<Cycle>,
+    /// Injected stall windows, each with the admissions it turned away.
+    stalls: Vec<(Range<Cycle>, u64)>,
 }
 
 impl<T> GraphNode<T> {
@@ -188,14 +489,38 @@ impl<T> GraphNode<T> {
             outputs: Vec::new(),
             inputs: Vec::new(),
             route_fn: None,
+            adaptive: None,
+            stranded: None,
+            warned: false,
+            activity: NodeActivity::default(),
+            full_at: None,
+            stalls: Vec::new(),
         }
     }
 }
 
+#[derive(Clone)]
 pub struct FlowGraph<T> {
     nodes: Vec<GraphNode<T>>,
     edges: Vec<Edge<T>>,
     perf_log_session: Option<Arc<perf_log::PerfLogSession>>,
+    /// Entries waiting at least this long get first claim on their
+    /// destination; see `set_age_threshold`.
+    age_threshold: Option<Cycle>,
+    squashed: u64,
+    misroute_action: MisrouteAction,
+    misroute_stats: MisrouteStats,
+    /// Cycles `NodeActivity` covers.
+    sampled_cycles: u64,
+    /// Chains full in the last sampled cycle, and the cycle each became
+    /// full.
+    open_chains: Vec<(Vec<NodeId>, Cycle)>,
+    full_chains: Vec<FullChain>,
+    stats: StatsSwitch,
+    path_accessor: Option<PathAccessor<T>>,
+    /// Requests each node admitted from its links in the current `tick`,
+    /// kept between ticks so the buffer is allocated once.
+    accepted: Vec<u32>,
 }
 
 impl<T: Send + Sync + 'static> FlowGraph<T> {
@@ -204,9 +529,69 @@ impl<T: Send + Sync + 'static> FlowGraph<T> {
             nodes: Vec::new(),
             edges: Vec::new(),
             perf_log_session: None,
+            age_threshold: None,
+            squashed: 0,
+            misroute_action: MisrouteAction::default(),
+            misroute_stats: MisrouteStats::default(),
+            sampled_cycles: 0,
+            open_chains: Vec::new(),
+            full_chains: Vec::new(),
+            stats: StatsSwitch::default(),
+            path_accessor: None,
+            accepted: Vec::new(),
         }
     }
 
+    /// Starts or stops sampling `NodeActivity` and full chains.
+    pub fn set_stats(&mut self, enabled: bool) {
+        self.stats = StatsSwitch::new(enabled);
+    }
+
+    pub fn set_misroute_action(&mut self, action: MisrouteAction) {
+        self.misroute_action = action;
+    }
+
+    /// Links are normally drained in creation order, so when several feed
+    /// the same node the later ones can be starved. With a threshold set, a
+    /// link whose head entry has waited `threshold` cycles is delivered
+    /// first (oldest first), and if its destination still rejects it, no
+    /// younger traffic is delivered to that destination in the same cycle.
+    pub fn set_age_threshold(&mut self, threshold: Option<Cycle>) {
+        self.age_threshold = threshold.filter(|&cycles| cycles > 0);
+    }
+
+    fn is_aged(&self, edge_id: LinkId, now: Cycle) -> bool {
+        match (
+            self.age_threshold,
+            self.edges[edge_id].buffer.front_ready_at(),
+        ) {
+            (Some(threshold), Some(ready_at)) => now.saturating_sub(ready_at) >= threshold,
+            _ => false,
+        }
+    }
+
+    /// Edge delivery order for this cycle, or `None` for plain creation
+    /// order when nothing has aged.
+    fn delivery_order(&self, now: Cycle) -> Option<Vec<LinkId>> {
+        let threshold = self.age_threshold?;
+        let mut aged: Vec<(Cycle, LinkId)> = self
+            .edges
+            .iter()
+            .enumerate()
+            .filter_map(|(edge_id, edge)| {
+                let ready_at = edge.buffer.front_ready_at()?;
+                (now.saturating_sub(ready_at) >= threshold).then_some((ready_at, edge_id))
+            })
+            .collect();
+        if aged.is_empty() {
+            return None;
+        }
+        aged.sort_unstable();
+        let first: Vec<LinkId> = aged.iter().map(|&(_, edge_id)| edge_id).collect();
+        let rest = (0..self.edges.len()).filter(|edge_id| !first.contains(edge_id));
+        Some(first.iter().copied().chain(rest).collect())
+    }
+
     pub fn set_perf_log_session(
         &mut self,
         perf_log_session: Option<Arc<perf_log::PerfLogSession>>,
@@ -214,6 +599,40 @@ impl<T: Send + Sync + 'static> FlowGraph<T> {
         self.perf_log_session = perf_log_session;
     }
 
+    /// Has payloads for which `accessor` returns a path record every node
+    /// they enter and leave; `None` turns recording off.
+    pub fn set_path_accessor(&mut self, accessor: Option<PathAccessor<T>>) {
+        self.path_accessor = accessor;
+    }
+
+    fn path_enter(&self, node: NodeId, now: Cycle, payload: &mut T) {
+        if let Some(path) = self.path_accessor.and_then(|path_of| path_of(payload)) {
+            path.push(PathHop {
+                node: self.nodes[node].name.clone(),
+                enter: now,
+                exit: None,
+            });
+        }
+    }
+
+    /// Takes back the hop `path_enter` added for a node that turned the
+    /// payload away.
+    fn path_retract(&self, payload: &mut T) {
+        if let Some(path) = self.path_accessor.and_then(|path_of| path_of(payload)) {
+            path.pop();
+        }
+    }
+
+    fn path_exit(&self, now: Cycle, payload: &mut T) {
+        if let Some(hop) = self
+            .path_accessor
+            .and_then(|path_of| path_of(payload))
+            .and_then(|path| path.last_mut())
+        {
+            hop.exit = Some(now);
+        }
+    }
+
     pub fn add_node<N>(&mut self, node: N) -> NodeId
     where
         N: TimedNode<T> + 'static,
@@ -276,13 +695,143 @@ impl<T: Send + Sync + 'static> FlowGraph<T> {
         }
     }
 
+    /// Lets payloads the route function sends to an output in one of
+    /// `groups` go to another output of that group instead, when the routed
+    /// one's destination holds more than `threshold` requests, counting
+    /// those still in the edge. They go to the group's least occupied
+    /// destination, if it holds fewer. Only for destinations any payload
+    /// may use, such as replicated return paths; `trace_route` follows the
+    /// whole group.
+    pub fn set_adaptive_route(
+        &mut self,
+        node_id: NodeId,
+        groups: Vec<Vec<usize>>,
+        threshold: usize,
+    ) {
+        let Some(node) = self.nodes.get_mut(node_id) else {
+            return;
+        };
+        let mut group_of = vec![None; node.outputs.len()];
+        for (index, group) in groups.iter().enumerate() {
+            for &output in group {
+                assert!(
+                    output < group_of.len(),
+                    "{}: adaptive route names output {output} of {}",
+                    node.name,
+                    group_of.len()
+                );
+                group_of[output] = Some(index);
+            }
+        }
+        node.adaptive = Some(AdaptiveRoute {
+            group_of,
+            groups,
+            threshold,
+        });
+    }
+
+    /// Requests held by the destination of `src`'s output `output`, counting
+    /// those still in the edge.
+    fn output_occupancy(&self, src: NodeId, output: usize) -> usize {
+        let edge = &self.edges[self.nodes[src].outputs[output]];
+        edge.buffer.len() + self.nodes[edge.dst].node.outstanding()
+    }
+
+    /// The output a payload routed to `output` of `src` goes to.
+    fn adaptive_output(&self, src: NodeId, output: usize) -> usize {
+        let Some(adaptive) = &self.nodes[src].adaptive else {
+            return output;
+        };
+        let Some(&Some(group)) = adaptive.group_of.get(output) else {
+            return output;
+        };
+        let occupancy = self.output_occupancy(src, output);
+        if occupancy <= adaptive.threshold {
+            return output;
+        }
+        adaptive.groups[group]
+            .iter()
+            .map(|&alternate| (self.output_occupancy(src, alternate), alternate))
+            .min()
+            .filter(|&(least, _)| least < occupancy)
+            .map_or(output, |(_, alternate)| alternate)
+    }
+
+    /// Traffic out of every node with an adaptive route.
+    pub fn adaptive_route_stats(&self) -> AdaptiveRouteStats {
+        let mut stats = AdaptiveRouteStats::default();
+        for node in &self.nodes {
+            let Some(adaptive) = &node.adaptive else {
+                continue;
+            };
+            for (output, &edge_id) in node.outputs.iter().enumerate() {
+                if adaptive.group_of.get(output).copied().flatten().is_some() {
+                    let edge = &self.edges[edge_id].stats;
+                    stats.routed += edge.entries_pushed;
+                    stats.rerouted += edge.adaptive_reroutes;
+                }
+            }
+        }
+        stats
+    }
+
     pub fn try_put(
+        &mut self,
+        node_id: NodeId,
+        now: Cycle,
+        mut request: ServiceRequest<T>,
+    ) -> Result<Ticket, Backpressure<T>> {
+        self.path_enter(node_id, now, &mut request.payload);
+        self.admit(node_id, now, request).map_err(|mut bp| {
+            self.path_retract(&mut bp.request_mut().payload);
+            bp
+        })
+    }
+
+    /// Offers `request` to `node_id`, which is busy until the end of any
+    /// injected stall window `now` falls in.
+    fn admit(
         &mut self,
         node_id: NodeId,
         now: Cycle,
         request: ServiceRequest<T>,
     ) -> Result<Ticket, Backpressure<T>> {
-        self.nodes[node_id].node.try_put(now, request)
+        let node = &mut self.nodes[node_id];
+        if let Some((window, rejected)) = node
+            .stalls
+            .iter_mut()
+            .find(|(window, _)| window.contains(&now))
+        {
+            *rejected += 1;
+            return Err(Backpressure::Busy {
+                request,
+                available_at: window.end,
+            });
+        }
+        node.node.try_put(now, request)
+    }
+
+    /// Makes `node_id` refuse every admission, from its input links and
+    /// from `try_put`, in the cycles of `window`, as if its server were
+    /// busy until the window ends. Work already inside the node carries on,
+    /// so upstream nodes and links fill up and have to hold their requests.
+    pub fn inject_stall(&mut self, node_id: NodeId, window: Range<Cycle>) {
+        self.nodes[node_id].stalls.push((window, 0));
+    }
+
+    /// Every injected stall window, in node order.
+    pub fn injected_stalls(&self) -> Vec<InjectedStall> {
+        self.nodes
+            .iter()
+            .flat_map(|node| {
+                node.stalls.iter().map(|(window, rejected)| InjectedStall {
+                    node: node.name.clone(),
+                    start: window.start,
+                    end: window.end,
+                    rejected: *rejected,
+                })
+            })
+            .collect()
     }
 
     pub fn tick(&mut self, now: Cycle) {
@@ -293,7 +842,7 @@ impl<T: Send + Sync + 'static> FlowGraph<T> {
         for edge_id in 0..self.edges.len() {
             let src = self.edges[edge_id].src;
             loop {
-                let (size_bytes, should_route) = {
+                let (size_bytes, routed_idx, predicate_pass) = {
                     let src_node = &mut self.nodes[src];
                     match src_node.node.peek_ready(now) {
                         Some(result) => {
@@ -301,22 +850,31 @@ impl<T: Send + Sync + 'static> FlowGraph<T> {
                                 .route_fn
                                 .as_ref()
                                 .map(|route| route(&result.payload));
-                            let predicate_pass = if let Some(route_idx) = routed_idx {
-                                route_idx == self.edges[edge_id].output_idx
-                            } else {
-                                self.edges[edge_id]
+                            let predicate_pass = match routed_idx {
+                                Some(_) => None,
+                                None => self.edges[edge_id]
                                     .predicate
                                     .as_ref()
-                                    .map(|pred| pred(&result.payload))
-                                    .unwrap_or(true)
+                                    .map(|pred| pred(&result.payload)),
                             };
-                            (result.ticket.size_bytes(), predicate_pass)
+                            (result.ticket.size_bytes(), routed_idx, predicate_pass)
                         }
                         None => break,
                     }
                 };
+                let chosen_idx = routed_idx.map(|idx| self.adaptive_output(src, idx));
+                let edge = &mut self.edges[edge_id];
+                let predicate_pass = match chosen_idx {
+                    Some(idx) => Some(idx == edge.output_idx),
+                    None => predicate_pass,
+                };
+                match predicate_pass {
+                    Some(true) => edge.stats.predicate_hits += 1,
+                    Some(false) => edge.stats.predicate_misses += 1,
+                    None => {}
+                }
 
-                if !should_route {
+                if !predicate_pass.unwrap_or(true) {
                     break;
                 }
 
@@ -324,25 +882,53 @@ impl<T: Send + Sync + 'static> FlowGraph<T> {
                     break;
                 }
 
-                let result = self.nodes[src]
+                let mut result = self.nodes[src]
                     .node
                     .take_ready(now)
                     .expect("peek_ready indicated availability");
+                self.path_exit(now, &mut result.payload);
                 self.edges[edge_id]
                     .buffer
-                    .try_push(result)
+                    .try_push(now, result)
                     .expect("capacity checked prior to push");
                 self.edges[edge_id].stats.entries_pushed += 1;
+                if routed_idx != chosen_idx {
+                    self.edges[edge_id].stats.adaptive_reroutes += 1;
+                }
             }
         }
+        self.check_misroutes(now);
 
-        for edge_id in 0..self.edges.len() {
+        let order = self.delivery_order(now);
+        // Destinations that turned away an aged entry this cycle.
+        let mut reserved: Vec<NodeId> = Vec::new();
+        let mut accepted = std::mem::take(&mut self.accepted);
+        accepted.clear();
+        accepted.resize(self.nodes.len(), 0);
+        for idx in 0..self.edges.len() {
+            let edge_id = order.as_ref().map_or(idx, |order| order[idx]);
             if now < self.edges[edge_id].next_retry_cycle {
                 continue;
             }
 
             let dst = self.edges[edge_id].dst;
+            let aged = self.is_aged(edge_id, now);
+            if !aged && reserved.contains(&dst) {
+                continue;
+            }
             loop {
+                let buffer = &self.edges[edge_id].buffer;
+                if !buffer.is_empty() && !buffer.front_arrived(now) {
+                    break;
+                }
+                if !buffer.is_empty()
+                    && buffer
+                        .accepts_per_cycle
+                        .is_some_and(|accepts| accepted[dst] >= accepts)
+                {
+                    self.edges[edge_id].stats.accept_limited += 1;
+                    break;
+                }
                 let entry = match self.edges[edge_id].buffer.pop_front() {
                     Some(entry) => entry,
                     None => {
@@ -351,9 +937,20 @@ impl<T: Send + Sync + 'static> FlowGraph<T> {
                     }
                 };
 
-                let (request, ticket) = entry.into_request();
-                match self.nodes[dst].node.try_put(now, request) {
+                let (mut request, ticket, deliver_at) = entry.into_request();
+                self.path_enter(dst, now, &mut request.payload);
+                match self.admit(dst, now, request) {
                     Ok(_) => {
+                        accepted[dst] += 1;
+                        let wait = now.saturating_sub(ticket.ready_at());
+                        let stats = &mut self.edges[edge_id].stats;
+                        stats.max_wait = stats.max_wait.max(wait);
+                        if self
+                            .age_threshold
+                            .is_some_and(|threshold| wait >= threshold)
+                        {
+                            stats.aged_deliveries += 1;
+                        }
                         self.edges[edge_id].stats.entries_delivered += 1;
                         self.edges[edge_id].stats.last_delivery_cycle = Some(now);
                         self.edges[edge_id].next_retry_cycle = now;
@@ -377,7 +974,7 @@ impl<T: Send + Sync + 'static> FlowGraph<T> {
                             };
                             let record = perf_log::GraphBackpressureRecord {
                                 cycle: now,
-                                edge: self.edges[edge_id]._name.clone(),
+                                edge: self.edges[edge_id].name.clone(),
                                 src: self.nodes[self.edges[edge_id].src].name.clone(),
                                 dst: self.nodes[self.edges[edge_id].dst].name.clone(),
                                 reason,
@@ -389,16 +986,281 @@ impl<T: Send + Sync + 'static> FlowGraph<T> {
                             session.write_graph_backpressure(&record);
                         }
 
-                        let request = bp.into_request();
-                        let restored = LinkEntry::from_parts(request, ticket);
+                        if matches!(bp, Backpressure::QueueFull { .. }) {
+                            self.nodes[dst].full_at = Some(now);
+                        }
+                        let mut request = bp.into_request();
+                        self.path_retract(&mut request.payload);
+                        let restored = LinkEntry::from_parts(request, ticket, deliver_at);
                         self.edges[edge_id].buffer.push_front(restored);
                         self.edges[edge_id].stats.downstream_backpressure += 1;
                         self.edges[edge_id].next_retry_cycle = retry_at;
+                        if aged {
+                            reserved.push(dst);
+                        }
                         break;
                     }
                 }
             }
         }
+        self.accepted = accepted;
+        if self.stats.on() {
+            self.sample_activity(now);
+        }
+    }
+
+    fn sample_activity(&mut self, now: Cycle) {
+        self.sampled_cycles += 1;
+        let mut any_full = false;
+        for node in &mut self.nodes {
+            let busy = match node.node.available_at() {
+                Some(available_at) => available_at > now,
+                None => node.node.outstanding() > 0,
+            };
+            let full = node.full_at == Some(now);
+            node.activity.busy_cycles += busy as u64;
+            node.activity.full_cycles += full as u64;
+            any_full |= full;
+        }
+        if any_full || !self.open_chains.is_empty() {
+            self.track_full_chains(now);
+        }
+    }
+
+    /// Longest path of full nodes ending at `node`, upstream first.
+    fn full_path_to(&self, node: NodeId, now: Cycle, on_path: &mut Vec<NodeId>) -> Vec<NodeId> {
+        on_path.push(node);
+        let mut longest = Vec::new();
+        for &edge_id in &self.nodes[node].inputs {
+            let src = self.edges[edge_id].src;
+            if self.nodes[src].full_at == Some(now) && !on_path.contains(&src) {
+                let path = self.full_path_to(src, now, on_path);
+                if path.len() > longest.len() {
+                    longest = path;
+                }
+            }
+        }
+        on_path.pop();
+        longest.push(node);
+        longest
+    }
+
+    /// Extends the runs of chains still full this cycle and starts new ones.
+    /// A chain's head is a full node none of whose outputs is full.
+    fn track_full_chains(&mut self, now: Cycle) {
+        let is_full = |node: &GraphNode<T>| node.full_at == Some(now);
+        let mut chains = Vec::new();
+        for (node_id, node) in self.nodes.iter().enumerate() {
+            let head = is_full(node)
+                && node
+                    .outputs
+                    .iter()
+                    .all(|&edge_id| !is_full(&self.nodes[self.edges[edge_id].dst]));
+            if head {
+                let path = self.full_path_to(node_id, now, &mut Vec::new());
+                if path.len() > 1 {
+                    chains.push(path);
+                }
+            }
+        }
+        let open = std::mem::take(&mut self.open_chains);
+        for nodes in chains {
+            let since = open
+                .iter()
+                .find(|(open, _)| *open == nodes)
+                .map_or(now, |&(_, since)| since);
+            let cycles = now - since + 1;
+            let best = self.full_chains.iter_mut().find(|c| c.nodes == nodes);
+            match best {
+                Some(chain) => chain.cycles = chain.cycles.max(cycles),
+                None => self.full_chains.push(FullChain {
+                    nodes: nodes.clone(),
+                    cycles,
+                }),
+            }
+            self.open_chains.push((nodes, since));
+        }
+    }
+
+    /// Cycles covered by `node_activity`.
+    pub fn sampled_cycles(&self) -> u64 {
+        self.sampled_cycles
+    }
+
+    pub fn node_activity(&self, node_id: NodeId) -> NodeActivity {
+        self.nodes[node_id].activity
+    }
+
+    /// Deliveries into `node_id` that it turned away, summed over its input
+    /// links.
+    pub fn node_backpressure(&self, node_id: NodeId) -> u64 {
+        self.nodes[node_id]
+            .inputs
+            .iter()
+            .map(|&edge_id| self.edges[edge_id].stats.downstream_backpressure)
+            .sum()
+    }
+
+    pub fn node_server_stats(&self, node_id: NodeId) -> Option<ServerStats> {
+        self.nodes[node_id].node.server_stats()
+    }
+
+    /// Every chain of two or more nodes that was full at once, with its
+    /// longest sustained run.
+    pub fn full_chains(&self) -> &[FullChain] {
+        &self.full_chains
+    }
+
+    /// Applies the misroute action to every node whose head payload none of
+    /// its output edges accepts. Nodes without outputs are drained from
+    /// outside the graph and are left alone.
+    fn check_misroutes(&mut self, now: Cycle) {
+        let edges = &self.edges;
+        for node in &mut self.nodes {
+            if node.outputs.is_empty() {
+                continue;
+            }
+            let Some(result) = node.node.peek_ready(now) else {
+                node.stranded = None;
+                continue;
+            };
+            let routable = match &node.route_fn {
+                Some(route) => route(&result.payload) < node.outputs.len(),
+                None => node.outputs.iter().any(|&edge_id| {
+                    edges[edge_id]
+                        .predicate
+                        .as_ref()
+                        .is_none_or(|pred| pred(&result.payload))
+                }),
+            };
+            if routable {
+                node.stranded = None;
+                continue;
+            }
+            let ticket = result.ticket;
+            if node.stranded == Some(ticket) {
+                continue;
+            }
+            node.stranded = Some(ticket);
+            self.misroute_stats.misrouted += 1;
+            match self.misroute_action {
+                MisrouteAction::Warn => {
+                    if !std::mem::replace(&mut node.warned, true) {
+                        log::warn!(
+                            "{}: ready payload at cycle {now} matches none of its {} output edges",
+                            node.name,
+                            node.outputs.len()
+                        );
+                    }
+                }
+                MisrouteAction::Drop => {
+                    node.node.take_ready(now);
+                    node.stranded = None;
+                    self.misroute_stats.dropped += 1;
+                }
+                MisrouteAction::Panic => panic!(
+                    "{}: ready payload at cycle {now} matches none of its {} output edges",
+                    node.name,
+                    node.outputs.len()
+                ),
+            }
+        }
+    }
+
+    pub fn misroute_stats(&self) -> MisrouteStats {
+        self.misroute_stats
+    }
+
+    /// Predicate hit counts of every edge, in creation order. Filtered edges
+    /// that were never hit are dead or misconfigured routes.
+    pub fn route_coverage(&self) -> Vec<RouteCoverage> {
+        self.edges
+            .iter()
+            .map(|edge| RouteCoverage {
+                edge: edge.name.clone(),
+                src: self.nodes[edge.src].name.clone(),
+                dst: self.nodes[edge.dst].name.clone(),
+                filtered: edge.predicate.is_some() || self.nodes[edge.src].route_fn.is_some(),
+                predicate_hits: edge.stats.predicate_hits,
+                predicate_misses: edge.stats.predicate_misses,
+                entries_pushed: edge.stats.entries_pushed,
+            })
+            .collect()
+    }
+
+    /// Follows `payload` from `start` through route functions and edge
+    /// predicates without moving anything. Where several unfiltered edges
+    /// accept it, or an adaptive route may take it, every one of them is
+    /// followed. Nodes are assumed to pass
+    /// payloads on unchanged.
+    pub fn trace_route(&self, start: NodeId, payload: &T) -> RouteTrace {
+        let mut trace = RouteTrace::default();
+        let mut seen = vec![false; self.nodes.len()];
+        let mut frontier = vec![start];
+        seen[start] = true;
+        while let Some(node_id) = frontier.pop() {
+            trace.visited.push(node_id);
+            let node = &self.nodes[node_id];
+            if node.outputs.is_empty() {
+                trace.sinks.push(node_id);
+                continue;
+            }
+            let next: Vec<LinkId> = match &node.route_fn {
+                Some(route) => {
+                    let output = route(payload);
+                    let group = node.adaptive.as_ref().and_then(|adaptive| {
+                        let group = adaptive.group_of.get(output).copied().flatten()?;
+                        Some(&adaptive.groups[group])
+                    });
+                    match group {
+                        Some(group) => group.iter().map(|&output| node.outputs[output]).collect(),
+                        None => node.outputs.get(output).copied().into_iter().collect(),
+                    }
+                }
+                None => node
+                    .outputs
+                    .iter()
+                    .copied()
+                    .filter(|&edge_id| {
+                        self.edges[edge_id]
+                            .predicate
+                            .as_ref()
+                            .is_none_or(|pred| pred(payload))
+                    })
+                    .collect(),
+            };
+            if next.is_empty() {
+                trace.stranded.push(node_id);
+            }
+            for edge_id in next {
+                let dst = self.edges[edge_id].dst;
+                if !std::mem::replace(&mut seen[dst], true) {
+                    frontier.push(dst);
+                }
+            }
+        }
+        trace.visited.sort_unstable();
+        trace.stranded.sort_unstable();
+        trace.sinks.sort_unstable();
+        trace
+    }
+
+    /// Nodes no edge path leads to from any of `roots`, ignoring routing.
+    pub fn unreachable_from(&self, roots: &[NodeId]) -> Vec<NodeId> {
+        let mut seen = vec![false; self.nodes.len()];
+        let mut frontier = roots.to_vec();
+        for &root in roots {
+            seen[root] = true;
+        }
+        while let Some(node_id) = frontier.pop() {
+            for &edge_id in &self.nodes[node_id].outputs {
+                let dst = self.edges[edge_id].dst;
+                if !std::mem::replace(&mut seen[dst], true) {
+                    frontier.push(dst);
+                }
+            }
+        }
+        (0..self.nodes.len()).filter(|&node| !seen[node]).collect()
     }
 
     pub fn with_node_mut<R>(
@@ -409,13 +1271,108 @@ impl<T: Send + Sync + 'static> FlowGraph<T> {
         f(self.nodes[node_id].node.as_mut())
     }
 
+    /// Applies `config` to every node whose name satisfies `matches` and
+    /// returns how many nodes took it.
+    pub fn set_server_config(
+        &mut self,
+        matches: impl Fn(&str) -> bool,
+        config: ServerConfig,
+    ) -> usize {
+        let mut updated = 0;
+        for node in self.nodes.iter_mut().filter(|node| matches(&node.name)) {
+            if node.node.set_server_config(config) {
+                updated += 1;
+            }
+        }
+        updated
+    }
+
+    /// Drops the request `ticket` was issued for by `node_id`, whether the
+    /// node is still servicing it or it is crossing one of the node's
+    /// outgoing links. Returns whether it was found.
+    ///
+    /// Like `squash`, this is only an API for models that kill or flush
+    /// warps; the simulator itself never cancels a request.
+    pub fn cancel(&mut self, node_id: NodeId, ticket: &Ticket) -> bool {
+        let mut found = false;
+        let mut matches = |_: &T, queued: &Ticket| {
+            let hit = !found && queued == ticket;
+            found |= hit;
+            hit
+        };
+        let mut squashed = self.nodes[node_id].node.squash(&mut matches);
+        for edge in self.edges.iter_mut().filter(|edge| edge.src == node_id) {
+            if squashed > 0 {
+                break;
+            }
+            squashed = edge.buffer.squash(&mut matches);
+            edge.stats.squashed = edge.stats.squashed.saturating_add(squashed as u64);
+        }
+        self.squashed = self.squashed.saturating_add(squashed as u64);
+        squashed > 0
+    }
+
+    /// Drops every request matching `pred` that is still waiting in a link
+    /// or queued in a node, e.g. all requests of a killed warp. Completions
+    /// already handed out of the graph are unaffected. Returns how many
+    /// requests were dropped.
+    pub fn squash(&mut self, mut pred: impl FnMut(&T) -> bool) -> usize {
+        let mut total = 0;
+        for edge in &mut self.edges {
+            let squashed = edge.buffer.squash(&mut |payload, _| pred(payload));
+            edge.stats.squashed = edge.stats.squashed.saturating_add(squashed as u64);
+            total += squashed;
+        }
+        for node in &mut self.nodes {
+            total += node.node.squash(&mut |payload, _| pred(payload));
+        }
+        self.squashed = self.squashed.saturating_add(total as u64);
+        total
+    }
+
+    /// Requests dropped by `cancel` and `squash` so far.
+    pub fn squashed(&self) -> u64 {
+        self.squashed
+    }
+
     pub fn node_name(&self, node_id: NodeId) -> &str {
         &self.nodes[node_id].name
     }
 
+    /// The node named `name`, if there is one.
+    pub fn find_node(&self, name: &str) -> Option<NodeId> {
+        self.nodes.iter().position(|node| node.name == name)
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn node_outstanding(&self, node_id: NodeId) -> usize {
+        self.nodes[node_id].node.outstanding()
+    }
+
     pub fn edge_stats(&self, link_id: LinkId) -> &EdgeStats {
         &self.edges[link_id].stats
     }
+
+    pub fn edge_count(&self) -> usize {
+        self.edges.len()
+    }
+
+    pub fn edge_name(&self, link_id: LinkId) -> &str {
+        &self.edges[link_id].name
+    }
+
+    pub fn edge_endpoints(&self, link_id: LinkId) -> (NodeId, NodeId) {
+        let edge = &self.edges[link_id];
+        (edge.src, edge.dst)
+    }
+
+    /// Requests buffered in the link, in flight or waiting to be delivered.
+    pub fn edge_occupancy(&self, link_id: LinkId) -> usize {
+        self.edges[link_id].buffer.len()
+    }
 }
 
 impl<T: Send + Sync + 'static> Default for FlowGraph<T> {
@@ -0,0 +1,418 @@
+use std::fmt;
+use std::ops::RangeInclusive;
+
+use crate::timeflow::graph::{FlowGraph, TimedNode};
+use crate::timeflow::lsu::{LsuPayload, LsuSubgraph};
+use crate::timeflow::types::NodeId;
+use crate::timeq::{Backpressure, Cycle, ServiceRequest};
+
+/// Anything a `Script` can drive: it accepts payloads, advances one cycle at
+/// a time and hands finished payloads back.
+pub trait HarnessTarget<T> {
+    /// Offers `payload` at `now`, returning it if the target refuses.
+    fn inject(&mut self, now: Cycle, payload: T, size_bytes: u32) -> Result<(), T>;
+    fn tick(&mut self, now: Cycle);
+    fn take_completed(&mut self, now: Cycle) -> Option<T>;
+}
+
+/// Any single node; pass it as `&mut node as &mut dyn TimedNode<_>`.
+impl<T> HarnessTarget<T> for dyn TimedNode<T> + '_ {
+    fn inject(&mut self, now: Cycle, payload: T, size_bytes: u32) -> Result<(), T> {
+        self.try_put(now, ServiceRequest::new(payload, size_bytes))
+            .map(|_| ())
+            .map_err(backpressure_payload)
+    }
+
+    fn tick(&mut self, now: Cycle) {
+        TimedNode::tick(self, now);
+    }
+
+    fn take_completed(&mut self, now: Cycle) -> Option<T> {
+        self.take_ready(now).map(|result| result.payload)
+    }
+}
+
+/// Drives a `FlowGraph`, injecting at `input` and collecting from `output`.
+pub struct GraphPorts<'a, T> {
+    graph: &'a mut FlowGraph<T>,
+    input: NodeId,
+    output: NodeId,
+}
+
+impl<'a, T> GraphPorts<'a, T> {
+    pub fn new(graph: &'a mut FlowGraph<T>, input: NodeId, output: NodeId) -> Self {
+        Self {
+            graph,
+            input,
+            output,
+        }
+    }
+}
+
+impl<T: Send + Sync + 'static> HarnessTarget<T> for GraphPorts<'_, T> {
+    fn inject(&mut self, now: Cycle, payload: T, size_bytes: u32) -> Result<(), T> {
+        self.graph
+            .try_put(self.input, now, ServiceRequest::new(payload, size_bytes))
+            .map(|_| ())
+            .map_err(backpressure_payload)
+    }
+
+    fn tick(&mut self, now: Cycle) {
+        self.graph.tick(now);
+    }
+
+    fn take_completed(&mut self, now: Cycle) -> Option<T> {
+        self.graph
+            .with_node_mut(self.output, |node| node.take_ready(now))
+            .map(|result| result.payload)
+    }
+}
+
+/// Mixed gmem/smem replay through the LSU. `size_bytes` is ignored; the LSU
+/// sizes requests itself.
+impl HarnessTarget<LsuPayload> for LsuSubgraph {
+    fn inject(
+        &mut self,
+        now: Cycle,
+        payload: LsuPayload,
+        _size_bytes: u32,
+    ) -> Result<(), LsuPayload> {
+        self.issue_payload(now, payload)
+            .map(|_| ())
+            .map_err(|reject| reject.payload)
+    }
+
+    fn tick(&mut self, now: Cycle) {
+        LsuSubgraph::tick(self, now);
+    }
+
+    fn take_completed(&mut self, now: Cycle) -> Option<LsuPayload> {
+        self.take_ready(now).map(|completion| completion.request)
+    }
+}
+
+fn backpressure_payload<T>(bp: Backpressure<T>) -> T {
+    match bp {
+        Backpressure::QueueFull { request, .. } | Backpressure::Busy { request, .. } => {
+            request.payload
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expect {
+    /// Completes at some point before the script's cycle limit.
+    Complete,
+    /// Completes `min..=max` cycles after it was accepted.
+    Within { min: Cycle, max: Cycle },
+    /// Refused at its issue cycle; never retried.
+    Rejected,
+}
+
+struct Step<T> {
+    at: Cycle,
+    label: String,
+    payload: Option<T>,
+    size_bytes: u32,
+    expect: Expect,
+}
+
+/// What happened to one scripted request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StepRecord {
+    pub label: String,
+    pub issue_at: Cycle,
+    pub accepted_at: Option<Cycle>,
+    pub completed_at: Option<Cycle>,
+    /// Refusals before the request was accepted.
+    pub retries: u32,
+}
+
+impl StepRecord {
+    pub fn latency(&self) -> Option<Cycle> {
+        Some(self.completed_at?.saturating_sub(self.accepted_at?))
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ScriptTrace {
+    pub steps: Vec<StepRecord>,
+    /// Labels in the order their requests completed.
+    pub completion_order: Vec<String>,
+}
+
+impl ScriptTrace {
+    pub fn get(&self, label: &str) -> Option<&StepRecord> {
+        self.steps.iter().find(|step| step.label == label)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Violation {
+    /// Still refused when the script ran out of cycles.
+    NeverAccepted { label: String },
+    /// Accepted although the script expected a refusal.
+    UnexpectedAccept { label: String, at: Cycle },
+    /// Accepted but not completed when the script ran out of cycles.
+    Incomplete { label: String },
+    Latency {
+        label: String,
+        latency: Cycle,
+        min: Cycle,
+        max: Cycle,
+    },
+    /// `later` completed before `earlier`.
+    OutOfOrder { earlier: String, later: String },
+    /// A completion that matches no outstanding scripted request.
+    Unexpected { label: String, at: Cycle },
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NeverAccepted { label } => write!(f, "{label}: never accepted"),
+            Self::UnexpectedAccept { label, at } => {
+                write!(f, "{label}: accepted at cycle {at}, expected a rejection")
+            }
+            Self::Incomplete { label } => write!(f, "{label}: accepted but never completed"),
+            Self::Latency {
+                label,
+                latency,
+                min,
+                max,
+            } => write!(f, "{label}: latency {latency} outside {min}..={max}"),
+            Self::OutOfOrder { earlier, later } => {
+                write!(f, "{later} completed before {earlier}")
+            }
+            Self::Unexpected { label, at } => {
+                write!(f, "{label}: unexpected completion at cycle {at}")
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ScriptError {
+    pub violations: Vec<Violation>,
+    pub trace: ScriptTrace,
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} script violation(s)", self.violations.len())?;
+        for violation in &self.violations {
+            write!(f, "\n  {violation}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ScriptError {}
+
+/// A scripted sequence of requests and the completions expected of them,
+/// built up step by step and checked against a `HarnessTarget`:
+///
+/// ```ignore
+/// Script::new()
+///     .issue(0, "a", payload_a, 16).within(2..=4)
+///     .issue(0, "b", payload_b, 16).rejected()
+///     .issue(3, "c", payload_c, 16)
+///     .in_order(&["a", "c"])
+///     .run(&mut node as &mut dyn TimedNode<_>, 100, |p| p.name.clone())?;
+/// ```
+///
+/// Requests refused while expecting completion are retried every cycle; their
+/// latency is measured from acceptance.
+pub struct Script<T> {
+    steps: Vec<Step<T>>,
+    orders: Vec<Vec<String>>,
+}
+
+impl<T> Default for Script<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Script<T> {
+    pub fn new() -> Self {
+        Self {
+            steps: Vec::new(),
+            orders: Vec::new(),
+        }
+    }
+
+    /// Injects `payload` at cycle `at`, expecting it to complete eventually.
+    pub fn issue(
+        mut self,
+        at: Cycle,
+        label: impl Into<String>,
+        payload: T,
+        size_bytes: u32,
+    ) -> Self {
+        self.steps.push(Step {
+            at,
+            label: label.into(),
+            payload: Some(payload),
+            size_bytes,
+            expect: Expect::Complete,
+        });
+        self
+    }
+
+    /// Requires the last issued request to complete within `latency` cycles.
+    pub fn within(self, latency: RangeInclusive<Cycle>) -> Self {
+        let (min, max) = latency.into_inner();
+        self.expect(Expect::Within { min, max })
+    }
+
+    /// Requires the last issued request to be refused.
+    pub fn rejected(self) -> Self {
+        self.expect(Expect::Rejected)
+    }
+
+    pub fn expect(mut self, expect: Expect) -> Self {
+        let step = self
+            .steps
+            .last_mut()
+            .expect("expectation given before any issue");
+        step.expect = expect;
+        self
+    }
+
+    /// Requires the labelled requests to complete in the given order.
+    pub fn in_order(mut self, labels: &[&str]) -> Self {
+        self.orders
+            .push(labels.iter().map(|label| label.to_string()).collect());
+        self
+    }
+
+    /// Runs the script for cycles `0..max_cycles`. `label_of` names a
+    /// completed payload so it can be matched to its step; labels should be
+    /// unique among requests in flight.
+    pub fn run<H: HarnessTarget<T> + ?Sized>(
+        mut self,
+        target: &mut H,
+        max_cycles: Cycle,
+        label_of: impl Fn(&T) -> String,
+    ) -> Result<ScriptTrace, ScriptError> {
+        self.steps.sort_by_key(|step| step.at);
+        let mut trace = ScriptTrace {
+            steps: self
+                .steps
+                .iter()
+                .map(|step| StepRecord {
+                    label: step.label.clone(),
+                    issue_at: step.at,
+                    accepted_at: None,
+                    completed_at: None,
+                    retries: 0,
+                })
+                .collect(),
+            completion_order: Vec::new(),
+        };
+        let mut violations = Vec::new();
+        let mut rejected = vec![false; self.steps.len()];
+
+        for cycle in 0..max_cycles {
+            for (idx, step) in self.steps.iter_mut().enumerate() {
+                if step.at > cycle {
+                    break;
+                }
+                let Some(payload) = step.payload.take() else {
+                    continue;
+                };
+                let record = &mut trace.steps[idx];
+                match target.inject(cycle, payload, step.size_bytes) {
+                    Ok(()) => {
+                        record.accepted_at = Some(cycle);
+                        if step.expect == Expect::Rejected {
+                            violations.push(Violation::UnexpectedAccept {
+                                label: step.label.clone(),
+                                at: cycle,
+                            });
+                        }
+                    }
+                    Err(_) if step.expect == Expect::Rejected => rejected[idx] = true,
+                    Err(payload) => {
+                        record.retries = record.retries.saturating_add(1);
+                        step.payload = Some(payload);
+                    }
+                }
+            }
+
+            target.tick(cycle);
+
+            while let Some(payload) = target.take_completed(cycle) {
+                let label = label_of(&payload);
+                let record = trace.steps.iter_mut().find(|record| {
+                    record.label == label
+                        && record.accepted_at.is_some()
+                        && record.completed_at.is_none()
+                });
+                match record {
+                    Some(record) => {
+                        record.completed_at = Some(cycle);
+                        trace.completion_order.push(label);
+                    }
+                    None => violations.push(Violation::Unexpected { label, at: cycle }),
+                }
+            }
+
+            let done = trace
+                .steps
+                .iter()
+                .zip(&rejected)
+                .all(|(record, &rejected)| rejected || record.completed_at.is_some());
+            if done {
+                break;
+            }
+        }
+
+        for (step, record) in self.steps.iter().zip(&trace.steps) {
+            let label = step.label.clone();
+            if step.expect == Expect::Rejected {
+                continue;
+            }
+            if record.accepted_at.is_none() {
+                violations.push(Violation::NeverAccepted { label });
+                continue;
+            }
+            let Some(latency) = record.latency() else {
+                violations.push(Violation::Incomplete { label });
+                continue;
+            };
+            if let Expect::Within { min, max } = step.expect {
+                if latency < min || latency > max {
+                    violations.push(Violation::Latency {
+                        label,
+                        latency,
+                        min,
+                        max,
+                    });
+                }
+            }
+        }
+
+        for order in &self.orders {
+            let position =
+                |label: &String| trace.completion_order.iter().position(|done| done == label);
+            for pair in order.windows(2) {
+                if let (Some(earlier), Some(later)) = (position(&pair[0]), position(&pair[1])) {
+                    if later < earlier {
+                        violations.push(Violation::OutOfOrder {
+                            earlier: pair[0].clone(),
+                            later: pair[1].clone(),
+                        });
+                    }
+                }
+            }
+        }
+
+        if violations.is_empty() {
+            Ok(trace)
+        } else {
+            Err(ScriptError { violations, trace })
+        }
+    }
+}
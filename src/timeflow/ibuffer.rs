@@ -0,0 +1,198 @@
+use serde::{Deserialize, Serialize};
+use std::ops::AddAssign;
+
+/// Bytes between consecutive Muon instructions.
+const INST_BYTES: u32 = 8;
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct IbufferConfig {
+    /// Off, a warp's next instruction is ready to issue as soon as the icache
+    /// returns it.
+    pub enabled: bool,
+    /// Decoded instructions each warp's ibuffer holds.
+    pub capacity: usize,
+    /// Instructions decoded into the ibuffers per cycle across the core
+    /// (0 = unlimited). A warp gets at most one per cycle.
+    pub decode_per_cycle: u32,
+}
+
+impl Default for IbufferConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            capacity: 2,
+            decode_per_cycle: 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct IbufferStats {
+    pub cycles: u64,
+    pub decoded: u64,
+    /// Entries held across all warps, summed over cycles.
+    pub occupancy_sum: u64,
+    /// Most entries one warp held.
+    pub max_occupancy: u64,
+    /// Warp-cycles fetch was held because the warp's ibuffer was full.
+    pub fetch_stall_cycles: u64,
+    /// Warp-cycles an ibuffer had room but the decoder served other warps.
+    pub decode_limited_cycles: u64,
+    /// Times a warp reached issue with nothing decoded.
+    pub empty_stalls: u64,
+    /// Taken branches and other PC redirects that discarded decoded entries.
+    pub redirect_flushes: u64,
+    pub flushed_entries: u64,
+}
+
+impl IbufferStats {
+    pub fn avg_occupancy(&self) -> f64 {
+        if self.cycles == 0 {
+            0.0
+        } else {
+            self.occupancy_sum as f64 / self.cycles as f64
+        }
+    }
+}
+
+impl AddAssign<&IbufferStats> for IbufferStats {
+    fn add_assign(&mut self, other: &IbufferStats) {
+        self.cycles = self.cycles.saturating_add(other.cycles);
+        self.decoded = self.decoded.saturating_add(other.decoded);
+        self.occupancy_sum = self.occupancy_sum.saturating_add(other.occupancy_sum);
+        self.max_occupancy = self.max_occupancy.max(other.max_occupancy);
+        self.fetch_stall_cycles = self
+            .fetch_stall_cycles
+            .saturating_add(other.fetch_stall_cycles);
+        self.decode_limited_cycles = self
+            .decode_limited_cycles
+            .saturating_add(other.decode_limited_cycles);
+        self.empty_stalls = self.empty_stalls.saturating_add(other.empty_stalls);
+        self.redirect_flushes = self.redirect_flushes.saturating_add(other.redirect_flushes);
+        self.flushed_entries = self.flushed_entries.saturating_add(other.flushed_entries);
+    }
+}
+
+/// Per-warp ibuffers between decode and issue. Only occupancy is tracked:
+/// the instructions themselves still come from the functional model at
+/// issue. The decoder fills buffers at the end of a cycle, so an entry is
+/// usable from the next one. An instruction takes its entry when it first
+/// reaches issue and keeps it while it replays; a PC that does not follow
+/// the previous instruction is a redirect and discards what was decoded
+/// down the old path.
+#[derive(Debug, Clone)]
+pub struct Ibuffers {
+    config: IbufferConfig,
+    occupancy: Vec<usize>,
+    /// PC of the instruction holding the head entry of each warp.
+    head_pc: Vec<Option<u32>>,
+    starved: Vec<bool>,
+    next_warp: usize,
+    stats: IbufferStats,
+}
+
+impl Ibuffers {
+    pub fn new(config: IbufferConfig, num_warps: usize) -> Self {
+        Self {
+            config,
+            occupancy: vec![0; num_warps],
+            head_pc: vec![None; num_warps],
+            starved: vec![false; num_warps],
+            next_warp: 0,
+            stats: IbufferStats::default(),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    /// Swaps capacity and decode width mid-run; buffers over the new
+    /// capacity keep their extra entries until they drain.
+    pub fn set_config(&mut self, config: IbufferConfig) {
+        self.config = config;
+    }
+
+    /// Whether the instruction at `pc` has a decoded entry to issue from.
+    pub fn try_issue(&mut self, warp: usize, pc: u32) -> bool {
+        if !self.config.enabled || warp >= self.occupancy.len() {
+            return true;
+        }
+        if self.head_pc[warp] == Some(pc) {
+            return true;
+        }
+        if let Some(prev) = self.head_pc[warp].take() {
+            if pc != prev.wrapping_add(INST_BYTES) && self.occupancy[warp] > 0 {
+                self.stats.redirect_flushes = self.stats.redirect_flushes.saturating_add(1);
+                self.stats.flushed_entries = self
+                    .stats
+                    .flushed_entries
+                    .saturating_add(self.occupancy[warp] as u64);
+                self.occupancy[warp] = 0;
+            }
+        }
+        if self.occupancy[warp] == 0 {
+            self.starved[warp] = true;
+            self.stats.empty_stalls = self.stats.empty_stalls.saturating_add(1);
+            return false;
+        }
+        self.occupancy[warp] -= 1;
+        self.head_pc[warp] = Some(pc);
+        self.starved[warp] = false;
+        true
+    }
+
+    /// Whether `warp` last reached issue with an empty ibuffer.
+    pub fn starved(&self, warp: usize) -> bool {
+        self.config.enabled && self.starved.get(warp).copied().unwrap_or(false)
+    }
+
+    /// Decodes into the buffers of the warps in `active_mask`, round-robin
+    /// from a different warp each cycle.
+    pub fn tick(&mut self, active_mask: u32) {
+        if !self.config.enabled {
+            return;
+        }
+        let warps = self.occupancy.len();
+        let capacity = self.config.capacity.max(1);
+        let mut budget = match self.config.decode_per_cycle {
+            0 => usize::MAX,
+            width => width as usize,
+        };
+        for offset in 0..warps {
+            let warp = (self.next_warp + offset) % warps;
+            if active_mask & (1 << warp) == 0 {
+                self.occupancy[warp] = 0;
+                self.head_pc[warp] = None;
+                self.starved[warp] = false;
+                continue;
+            }
+            if self.occupancy[warp] >= capacity {
+                self.stats.fetch_stall_cycles = self.stats.fetch_stall_cycles.saturating_add(1);
+            } else if budget == 0 {
+                self.stats.decode_limited_cycles =
+                    self.stats.decode_limited_cycles.saturating_add(1);
+            } else {
+                budget -= 1;
+                self.occupancy[warp] += 1;
+                self.stats.decoded = self.stats.decoded.saturating_add(1);
+            }
+            self.stats.max_occupancy = self.stats.max_occupancy.max(self.occupancy[warp] as u64);
+        }
+        self.next_warp = (self.next_warp + 1) % warps.max(1);
+        self.stats.cycles = self.stats.cycles.saturating_add(1);
+        self.stats.occupancy_sum = self
+            .stats
+            .occupancy_sum
+            .saturating_add(self.occupancy.iter().sum::<usize>() as u64);
+    }
+
+    pub fn stats(&self) -> IbufferStats {
+        self.stats
+    }
+
+    pub fn clear_stats(&mut self) {
+        self.stats = IbufferStats::default();
+    }
+}
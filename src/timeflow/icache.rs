@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
 use std::ops::AddAssign;
 
 pub use crate::timeflow::types::RejectReason as IcacheRejectReason;
@@ -16,6 +17,15 @@ pub struct IcacheStats {
     pub bytes_issued: u64,
     pub bytes_completed: u64,
     pub last_completion_cycle: Option<Cycle>,
+    /// Misses whose fetch was dropped and fetched again after the fill.
+    pub replays: u64,
+    /// Fetches that found the line a replay was waiting for.
+    pub replay_refetches: u64,
+    /// `penalty` cycles, summed over `replays`.
+    pub replay_penalty_cycles: u64,
+    /// Misses that waited for their fill because their warp already had
+    /// `max_per_warp` replays outstanding.
+    pub replay_cap_waits: u64,
 }
 
 impl AddAssign<&IcacheStats> for IcacheStats {
@@ -36,6 +46,12 @@ impl AddAssign<&IcacheStats> for IcacheStats {
             (None, Some(b)) => Some(b),
             (a, None) => a,
         };
+        self.replays = self.replays.saturating_add(other.replays);
+        self.replay_refetches = self.replay_refetches.saturating_add(other.replay_refetches);
+        self.replay_penalty_cycles = self
+            .replay_penalty_cycles
+            .saturating_add(other.replay_penalty_cycles);
+        self.replay_cap_waits = self.replay_cap_waits.saturating_add(other.replay_cap_waits);
     }
 }
 
@@ -67,6 +83,9 @@ impl IcacheRequest {
 #[derive(Debug, Clone)]
 pub struct IcacheIssue {
     pub ticket: Ticket,
+    /// Set when the miss was replayed: the fetch was dropped, and the warp
+    /// fetches the line again at this cycle.
+    pub replay_at: Option<Cycle>,
 }
 
 pub type IcacheReject = crate::timeflow::types::RejectWith<IcacheRequest>;
@@ -89,12 +108,37 @@ impl Default for IcachePolicyConfig {
     }
 }
 
+/// `[icache.replay]`: by default a warp whose fetch misses waits for the
+/// fill and takes the line from it. With replay enabled the fetch is
+/// dropped instead, and the warp fetches again, through the hit path,
+/// `penalty` cycles after the fill.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct IcacheReplayConfig {
+    pub enabled: bool,
+    pub penalty: u64,
+    /// Replays a warp may have outstanding; a miss beyond that waits for
+    /// its fill as without replay.
+    pub max_per_warp: usize,
+}
+
+impl Default for IcacheReplayConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            penalty: 4,
+            max_per_warp: 2,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Deserialize)]
 #[serde(default)]
 pub struct IcacheFlowConfig {
     pub hit: ServerConfig,
     pub miss: ServerConfig,
     pub policy: IcachePolicyConfig,
+    pub replay: IcacheReplayConfig,
 }
 
 impl Default for IcacheFlowConfig {
@@ -115,14 +159,19 @@ impl Default for IcacheFlowConfig {
                 warmup_latency: 0,
             },
             policy: IcachePolicyConfig::default(),
+            replay: IcacheReplayConfig::default(),
         }
     }
 }
 
+#[derive(Clone)]
 pub struct IcacheSubgraph {
     hit: SimpleTimedQueue<IcacheRequest>,
     miss: SimpleTimedQueue<IcacheRequest>,
     policy: IcachePolicyConfig,
+    replay: IcacheReplayConfig,
+    /// Line and refetch cycle of each warp's outstanding replays.
+    replays: Vec<VecDeque<(u64, Cycle)>>,
     stats: IcacheStats,
 }
 
@@ -132,6 +181,8 @@ impl IcacheSubgraph {
             hit: SimpleTimedQueue::new(true, config.hit),
             miss: SimpleTimedQueue::new(true, config.miss),
             policy: config.policy,
+            replay: config.replay,
+            replays: Vec::new(),
             stats: IcacheStats::default(),
         }
     }
@@ -143,9 +194,11 @@ impl IcacheSubgraph {
     ) -> Result<IcacheIssue, IcacheReject> {
         let line_bytes = self.policy.line_bytes.max(1);
         request.line_addr = line_addr(request.pc, line_bytes);
-        let hit = decide(self.policy.hit_rate, request.line_addr ^ self.policy.seed);
+        let refetch = self.take_replay(now, request.warp, request.line_addr);
+        let hit = refetch || decide(self.policy.hit_rate, request.line_addr ^ self.policy.seed);
         request.miss = !hit;
 
+        let (warp, line) = (request.warp, request.line_addr);
         let bytes = request.bytes;
         let queue = if hit { &mut self.hit } else { &mut self.miss };
 
@@ -158,14 +211,22 @@ impl IcacheSubgraph {
                 } else {
                     self.stats.misses = self.stats.misses.saturating_add(1);
                 }
-                Ok(IcacheIssue { ticket })
+                if refetch {
+                    self.stats.replay_refetches = self.stats.replay_refetches.saturating_add(1);
+                }
+                let replay_at = if !hit && self.replay.enabled {
+                    self.start_replay(warp, line, ticket.ready_at())
+                } else {
+                    None
+                };
+                Ok(IcacheIssue { ticket, replay_at })
             }
             Err(err) => {
                 match err.reason {
                     RejectReason::Busy => {
                         self.stats.busy_rejects = self.stats.busy_rejects.saturating_add(1);
                     }
-                    RejectReason::QueueFull => {
+                    RejectReason::QueueFull | RejectReason::FlushLimit => {
                         self.stats.queue_full_rejects =
                             self.stats.queue_full_rejects.saturating_add(1);
                     }
@@ -198,6 +259,41 @@ impl IcacheSubgraph {
         });
     }
 
+    /// Whether `line` is the line of one of `warp`'s replays, which its
+    /// fill has made a hit. Replays whose refetch cycle has passed without
+    /// the warp coming back for them are forgotten.
+    fn take_replay(&mut self, now: Cycle, warp: usize, line: u64) -> bool {
+        let Some(pending) = self.replays.get_mut(warp) else {
+            return false;
+        };
+        let found = pending
+            .iter()
+            .position(|&(replay_line, _)| replay_line == line)
+            .and_then(|idx| pending.remove(idx))
+            .is_some();
+        pending.retain(|&(_, refetch_at)| refetch_at > now);
+        found
+    }
+
+    fn start_replay(&mut self, warp: usize, line: u64, fill_at: Cycle) -> Option<Cycle> {
+        if warp >= self.replays.len() {
+            self.replays.resize_with(warp + 1, VecDeque::new);
+        }
+        let pending = &mut self.replays[warp];
+        if pending.len() >= self.replay.max_per_warp {
+            self.stats.replay_cap_waits = self.stats.replay_cap_waits.saturating_add(1);
+            return None;
+        }
+        let refetch_at = fill_at.saturating_add(self.replay.penalty);
+        pending.push_back((line, refetch_at));
+        self.stats.replays = self.stats.replays.saturating_add(1);
+        self.stats.replay_penalty_cycles = self
+            .stats
+            .replay_penalty_cycles
+            .saturating_add(self.replay.penalty);
+        Some(refetch_at)
+    }
+
     pub fn stats(&self) -> IcacheStats {
         self.stats
     }
@@ -0,0 +1,232 @@
+//! Invariants of the timing model, checked with `check!`. Counters that
+//! used to be clamped with `saturating_sub` report when they would have
+//! gone wrong instead of hiding it. What a violation does (nothing, a log
+//! line, or a panic) is set per category in `[sim.invariants]`, and
+//! `summary` totals the violations of the run so far. The state is per
+//! thread, as a simulation runs on one.
+
+use std::cell::RefCell;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// Which kind of invariant a check guards.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InvariantCategory {
+    /// Occupancy and in-flight counters never drop below zero.
+    Inflight,
+    /// Stats only move forward: completion cycles never go back in time.
+    Stats,
+    /// A link's `bytes_in_use` matches the entries it holds.
+    Link,
+}
+
+impl InvariantCategory {
+    pub const ALL: [Self; 3] = [Self::Inflight, Self::Stats, Self::Link];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Inflight => "inflight",
+            Self::Stats => "stats",
+            Self::Link => "link",
+        }
+    }
+}
+
+impl fmt::Display for InvariantCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// What a failed check does.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InvariantAction {
+    /// Neither count nor report it.
+    Ignore,
+    /// Count it and log the first one of its category.
+    #[default]
+    Log,
+    /// Panic, so `sim.state_dump` can capture the state.
+    Abort,
+}
+
+/// `[sim.invariants]`: the action for each category.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(default)]
+pub struct InvariantConfig {
+    pub inflight: InvariantAction,
+    pub stats: InvariantAction,
+    pub link: InvariantAction,
+}
+
+impl InvariantConfig {
+    pub fn action(&self, category: InvariantCategory) -> InvariantAction {
+        match category {
+            InvariantCategory::Inflight => self.inflight,
+            InvariantCategory::Stats => self.stats,
+            InvariantCategory::Link => self.link,
+        }
+    }
+}
+
+/// Violations of one category.
+#[derive(Debug, Clone, Serialize)]
+pub struct InvariantViolations {
+    pub category: InvariantCategory,
+    pub count: u64,
+    /// Message of the first one.
+    pub first: String,
+}
+
+/// The categories with violations, in `InvariantCategory::ALL` order.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct InvariantSummary {
+    pub violations: Vec<InvariantViolations>,
+}
+
+impl InvariantSummary {
+    pub fn is_clean(&self) -> bool {
+        self.violations.is_empty()
+    }
+
+    pub fn count(&self, category: InvariantCategory) -> u64 {
+        self.violations
+            .iter()
+            .find(|v| v.category == category)
+            .map_or(0, |v| v.count)
+    }
+}
+
+#[derive(Clone, Default)]
+struct Checker {
+    config: InvariantConfig,
+    counts: [u64; InvariantCategory::ALL.len()],
+    first: [Option<String>; InvariantCategory::ALL.len()],
+}
+
+thread_local! {
+    static CHECKER: RefCell<Checker> = RefCell::new(Checker::default());
+}
+
+/// Sets the actions for this thread's checks and forgets earlier
+/// violations; called when a simulation starts.
+pub fn configure(config: InvariantConfig) {
+    CHECKER.with(|checker| {
+        *checker.borrow_mut() = Checker {
+            config,
+            ..Checker::default()
+        };
+    });
+}
+
+/// This thread's check actions and violation counts, as saved by
+/// `Sim::checkpoint`.
+#[derive(Clone)]
+pub struct CheckerState(Checker);
+
+pub fn save() -> CheckerState {
+    CHECKER.with(|checker| CheckerState(checker.borrow().clone()))
+}
+
+pub fn restore(state: &CheckerState) {
+    CHECKER.with(|checker| *checker.borrow_mut() = state.0.clone());
+}
+
+/// Violations counted on this thread since the last `configure`.
+pub fn summary() -> InvariantSummary {
+    CHECKER.with(|checker| {
+        let checker = checker.borrow();
+        let violations = InvariantCategory::ALL
+            .iter()
+            .enumerate()
+            .filter(|&(idx, _)| checker.counts[idx] > 0)
+            .map(|(idx, &category)| InvariantViolations {
+                category,
+                count: checker.counts[idx],
+                first: checker.first[idx].clone().unwrap_or_default(),
+            })
+            .collect();
+        InvariantSummary { violations }
+    })
+}
+
+/// Called by `check!` when a check fails.
+#[doc(hidden)]
+pub fn violated(category: InvariantCategory, message: fmt::Arguments<'_>) {
+    let action = CHECKER.with(|checker| checker.borrow().config.action(category));
+    match action {
+        InvariantAction::Ignore => {}
+        InvariantAction::Log => CHECKER.with(|checker| {
+            let mut checker = checker.borrow_mut();
+            let idx = category as usize;
+            checker.counts[idx] = checker.counts[idx].saturating_add(1);
+            if checker.first[idx].is_none() {
+                let message = message.to_string();
+                log::warn!("{category} invariant violated: {message}");
+                checker.first[idx] = Some(message);
+            }
+        }),
+        InvariantAction::Abort => panic!("{category} invariant violated: {message}"),
+    }
+}
+
+/// Checks that `cond` holds, and hands a violation to the action its
+/// category is configured with; evaluates to `cond`, so the caller can
+/// still clamp the value it guards. The message is only formatted for a
+/// violation.
+///
+/// `check!(Inflight, count > 0, "core {core} released a slot it never held")`
+#[macro_export]
+macro_rules! check {
+    ($category:ident, $cond:expr, $($arg:tt)+) => {{
+        let holds: bool = $cond;
+        if !holds {
+            $crate::timeflow::invariants::violated(
+                $crate::timeflow::invariants::InvariantCategory::$category,
+                format_args!($($arg)+),
+            );
+        }
+        holds
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn log_counts_and_keeps_the_first_message() {
+        configure(InvariantConfig::default());
+        assert!(check!(Link, true, "never shown"));
+        for n in 0..3 {
+            assert!(!check!(Link, n > 5, "link bytes off by {n}"));
+        }
+        let summary = summary();
+        assert_eq!(summary.count(InvariantCategory::Link), 3);
+        assert_eq!(summary.count(InvariantCategory::Inflight), 0);
+        assert_eq!(summary.violations[0].first, "link bytes off by 0");
+    }
+
+    #[test]
+    fn ignore_drops_violations() {
+        configure(InvariantConfig {
+            stats: InvariantAction::Ignore,
+            ..InvariantConfig::default()
+        });
+        assert!(!check!(Stats, false, "ignored"));
+        assert!(summary().is_clean());
+    }
+
+    #[test]
+    #[should_panic(expected = "inflight invariant violated: slot 2")]
+    fn abort_panics() {
+        configure(InvariantConfig {
+            inflight: InvariantAction::Abort,
+            ..InvariantConfig::default()
+        });
+        check!(Inflight, false, "slot {}", 2);
+    }
+}
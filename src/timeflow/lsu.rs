@@ -1,11 +1,15 @@
 use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
 use std::ops::AddAssign;
 
 use crate::timeflow::{
+    bottleneck::BottleneckReport,
     gmem::GmemRequest,
     graph::{FlowGraph, Link},
+    request_timeout::RequestTimeoutConfig,
     server_node::ServerNode,
     smem::SmemRequest,
+    store_buffer::StoreBufferConfig,
     types::NodeId,
 };
 use crate::timeq::{
@@ -72,6 +76,15 @@ impl LsuPayload {
         }
     }
 
+    /// Address space and byte range the access touches, for store-to-load
+    /// forwarding.
+    fn footprint(&self) -> (bool, u64, u64) {
+        match self {
+            LsuPayload::Gmem(req) => (false, req.addr, req.addr + req.bytes.max(1) as u64),
+            LsuPayload::Smem(req) => (true, req.addr, req.addr + req.bytes.max(1) as u64),
+        }
+    }
+
     pub(crate) fn needs_load_data(&self) -> bool {
         match self {
             LsuPayload::Gmem(req) => req.kind.is_mem() && req.is_load,
@@ -88,6 +101,19 @@ pub(crate) enum LsuQueueKind {
     SharedStore,
 }
 
+impl LsuQueueKind {
+    const ALL: [Self; 4] = [
+        Self::GlobalLoad,
+        Self::GlobalStore,
+        Self::SharedLoad,
+        Self::SharedStore,
+    ];
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
 #[derive(Debug, Clone, Copy, Default, Serialize)]
 pub struct LsuStats {
     pub issued: u64,
@@ -110,6 +136,74 @@ pub struct LsuStats {
     pub global_stq_busy_rejects: u64,
     pub shared_ldq_busy_rejects: u64,
     pub shared_stq_busy_rejects: u64,
+    /// Busy rejects caused by the per-warp outstanding miss cap.
+    pub mlp_cap_rejects: u64,
+    /// Queue-full rejects from a warp exhausting its credits on a shared
+    /// queue.
+    pub credit_rejects: u64,
+    /// Cycles over which queue occupancy was sampled.
+    pub occupancy_cycles: u64,
+    /// Per-cycle sums of entries held in each queue class, across warps.
+    pub global_ldq_occupancy_sum: u64,
+    pub global_stq_occupancy_sum: u64,
+    pub shared_ldq_occupancy_sum: u64,
+    pub shared_stq_occupancy_sum: u64,
+    pub global_ldq_peak_occupancy: u64,
+    pub global_stq_peak_occupancy: u64,
+    pub shared_ldq_peak_occupancy: u64,
+    pub shared_stq_peak_occupancy: u64,
+    /// Loads completed from an older store in the STQ.
+    pub forwarded_loads: u64,
+    /// Loads held back because an older store only partly covered them.
+    pub forward_partial_stalls: u64,
+}
+
+impl LsuStats {
+    fn occupancy_mut(&mut self, kind: LsuQueueKind) -> (&mut u64, &mut u64) {
+        match kind {
+            LsuQueueKind::GlobalLoad => (
+                &mut self.global_ldq_occupancy_sum,
+                &mut self.global_ldq_peak_occupancy,
+            ),
+            LsuQueueKind::GlobalStore => (
+                &mut self.global_stq_occupancy_sum,
+                &mut self.global_stq_peak_occupancy,
+            ),
+            LsuQueueKind::SharedLoad => (
+                &mut self.shared_ldq_occupancy_sum,
+                &mut self.shared_ldq_peak_occupancy,
+            ),
+            LsuQueueKind::SharedStore => (
+                &mut self.shared_stq_occupancy_sum,
+                &mut self.shared_stq_peak_occupancy,
+            ),
+        }
+    }
+
+    /// Mean entries held in the global load queue(s) per sampled cycle.
+    pub fn global_ldq_avg_occupancy(&self) -> f64 {
+        self.avg_occupancy(self.global_ldq_occupancy_sum)
+    }
+
+    pub fn global_stq_avg_occupancy(&self) -> f64 {
+        self.avg_occupancy(self.global_stq_occupancy_sum)
+    }
+
+    pub fn shared_ldq_avg_occupancy(&self) -> f64 {
+        self.avg_occupancy(self.shared_ldq_occupancy_sum)
+    }
+
+    pub fn shared_stq_avg_occupancy(&self) -> f64 {
+        self.avg_occupancy(self.shared_stq_occupancy_sum)
+    }
+
+    fn avg_occupancy(&self, sum: u64) -> f64 {
+        if self.occupancy_cycles == 0 {
+            0.0
+        } else {
+            sum as f64 / self.occupancy_cycles as f64
+        }
+    }
 }
 
 impl AddAssign<&LsuStats> for LsuStats {
@@ -168,6 +262,37 @@ impl AddAssign<&LsuStats> for LsuStats {
         self.shared_stq_busy_rejects = self
             .shared_stq_busy_rejects
             .saturating_add(other.shared_stq_busy_rejects);
+        self.mlp_cap_rejects = self.mlp_cap_rejects.saturating_add(other.mlp_cap_rejects);
+        self.credit_rejects = self.credit_rejects.saturating_add(other.credit_rejects);
+        self.occupancy_cycles = self.occupancy_cycles.saturating_add(other.occupancy_cycles);
+        self.global_ldq_occupancy_sum = self
+            .global_ldq_occupancy_sum
+            .saturating_add(other.global_ldq_occupancy_sum);
+        self.global_stq_occupancy_sum = self
+            .global_stq_occupancy_sum
+            .saturating_add(other.global_stq_occupancy_sum);
+        self.shared_ldq_occupancy_sum = self
+            .shared_ldq_occupancy_sum
+            .saturating_add(other.shared_ldq_occupancy_sum);
+        self.shared_stq_occupancy_sum = self
+            .shared_stq_occupancy_sum
+            .saturating_add(other.shared_stq_occupancy_sum);
+        self.global_ldq_peak_occupancy = self
+            .global_ldq_peak_occupancy
+            .max(other.global_ldq_peak_occupancy);
+        self.global_stq_peak_occupancy = self
+            .global_stq_peak_occupancy
+            .max(other.global_stq_peak_occupancy);
+        self.shared_ldq_peak_occupancy = self
+            .shared_ldq_peak_occupancy
+            .max(other.shared_ldq_peak_occupancy);
+        self.shared_stq_peak_occupancy = self
+            .shared_stq_peak_occupancy
+            .max(other.shared_stq_peak_occupancy);
+        self.forwarded_loads = self.forwarded_loads.saturating_add(other.forwarded_loads);
+        self.forward_partial_stalls = self
+            .forward_partial_stalls
+            .saturating_add(other.forward_partial_stalls);
     }
 }
 
@@ -181,15 +306,32 @@ pub struct LsuCompletion<T> {
 #[derive(Debug, Clone)]
 pub struct LsuIssue {
     pub ticket: Ticket,
+    /// The load was served from the STQ and will come back from
+    /// `take_forwarded` instead of the memory hierarchy.
+    pub forwarded: bool,
 }
 
 pub use crate::timeflow::types::RejectReason as LsuRejectReason;
 
 pub type LsuReject = crate::timeflow::types::RejectWith<LsuPayload>;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LsuQueueSharing {
+    /// Every warp gets its own LDQ/STQ per address space.
+    Private,
+    /// One LDQ/STQ per address space shared by all warps; `queue_capacity`
+    /// is the unified size.
+    Shared,
+}
+
 #[derive(Debug, Clone, Deserialize)]
 #[serde(default)]
 pub struct LsuQueueConfig {
+    pub sharing: LsuQueueSharing,
+    /// Entries one warp may hold in each shared queue (0 = no partitioning).
+    /// Ignored for private queues.
+    pub credits_per_warp: usize,
     pub global_ldq: ServerConfig,
     pub global_stq: ServerConfig,
     pub shared_ldq: ServerConfig,
@@ -206,6 +348,8 @@ impl Default for LsuQueueConfig {
             ..ServerConfig::default()
         };
         Self {
+            sharing: LsuQueueSharing::Private,
+            credits_per_warp: 0,
             global_ldq: queue(8),
             global_stq: queue(4),
             shared_ldq: queue(4),
@@ -220,6 +364,9 @@ pub struct LsuResourceConfig {
     pub address_entries: usize,
     pub store_data_entries: usize,
     pub load_data_entries: usize,
+    /// Outstanding gmem misses a warp may have before further gmem
+    /// accesses from it are rejected; 0 disables the cap.
+    pub max_outstanding_misses_per_warp: usize,
 }
 
 impl Default for LsuResourceConfig {
@@ -228,6 +375,27 @@ impl Default for LsuResourceConfig {
             address_entries: 16,
             store_data_entries: 8,
             load_data_entries: 16,
+            max_outstanding_misses_per_warp: 0,
+        }
+    }
+}
+
+/// Store-to-load forwarding. When enabled, a load only waits on older stores
+/// from its warp that overlap it: one fully covered by the youngest
+/// overlapping store completes from the STQ after `latency` cycles, and a
+/// partial overlap stalls until the store drains.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct LsuForwardingConfig {
+    pub enabled: bool,
+    pub latency: u64,
+}
+
+impl Default for LsuForwardingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            latency: 1,
         }
     }
 }
@@ -237,8 +405,15 @@ impl Default for LsuResourceConfig {
 pub struct LsuFlowConfig {
     pub queues: LsuQueueConfig,
     pub resources: LsuResourceConfig,
+    pub forwarding: LsuForwardingConfig,
+    pub store_buffer: StoreBufferConfig,
     pub issue: ServerConfig,
     pub link_capacity: usize,
+    /// Allow each warp one outstanding gmem or smem access at a time, so
+    /// its memory operations issue and complete in program order. Meant
+    /// for correlating against simple in-order RTL configurations.
+    pub strict_inorder: bool,
+    pub timeout: RequestTimeoutConfig,
 }
 
 impl Default for LsuFlowConfig {
@@ -246,6 +421,8 @@ impl Default for LsuFlowConfig {
         Self {
             queues: LsuQueueConfig::default(),
             resources: LsuResourceConfig::default(),
+            forwarding: LsuForwardingConfig::default(),
+            store_buffer: StoreBufferConfig::default(),
             issue: ServerConfig {
                 base_latency: 1,
                 bytes_per_cycle: 1024,
@@ -254,10 +431,13 @@ impl Default for LsuFlowConfig {
                 ..ServerConfig::default()
             },
             link_capacity: 4,
+            strict_inorder: false,
+            timeout: RequestTimeoutConfig::default(),
         }
     }
 }
 
+#[derive(Clone)]
 struct WarpQueues {
     global_ldq: NodeId,
     global_stq: NodeId,
@@ -265,12 +445,25 @@ struct WarpQueues {
     shared_stq: NodeId,
 }
 
+#[derive(Clone)]
 pub struct LsuSubgraph {
     graph: FlowGraph<LsuPayload>,
     issue_node: NodeId,
+    /// One entry per warp for private queues, a single entry when shared.
     queues: Vec<WarpQueues>,
+    num_warps: usize,
+    credits_per_warp: usize,
+    /// Entries each warp holds per queue class, from issue until the
+    /// request leaves the LSU.
+    in_flight: Vec<[usize; 4]>,
     store_pending_global: Vec<u32>,
     store_pending_shared: Vec<u32>,
+    /// Footprints of each warp's stores still in the STQ, oldest first.
+    pending_stores: Vec<VecDeque<(bool, u64, u64)>>,
+    forwarding: LsuForwardingConfig,
+    /// Forwarded loads and the cycle their data is ready.
+    forwarded: VecDeque<(Cycle, LsuPayload)>,
+    outstanding_misses: Vec<usize>,
     resources: LsuResourceConfig,
     address_in_use: usize,
     store_in_use: usize,
@@ -284,22 +477,29 @@ impl LsuSubgraph {
         let issue_node =
             graph.add_node(ServerNode::new("lsu_issue", TimedServer::new(config.issue)));
 
-        let mut queues = Vec::with_capacity(num_warps);
-        for warp in 0..num_warps {
+        let shared = config.queues.sharing == LsuQueueSharing::Shared;
+        let num_sets = if shared { 1 } else { num_warps };
+        let mut queues = Vec::with_capacity(num_sets);
+        for warp in 0..num_sets {
+            let suffix = if shared {
+                String::new()
+            } else {
+                format!("_w{warp}")
+            };
             let global_ldq = graph.add_node(ServerNode::new(
-                format!("lsu_global_ldq_w{warp}"),
+                format!("lsu_global_ldq{suffix}"),
                 TimedServer::new(config.queues.global_ldq),
             ));
             let global_stq = graph.add_node(ServerNode::new(
-                format!("lsu_global_stq_w{warp}"),
+                format!("lsu_global_stq{suffix}"),
                 TimedServer::new(config.queues.global_stq),
             ));
             let shared_ldq = graph.add_node(ServerNode::new(
-                format!("lsu_shared_ldq_w{warp}"),
+                format!("lsu_shared_ldq{suffix}"),
                 TimedServer::new(config.queues.shared_ldq),
             ));
             let shared_stq = graph.add_node(ServerNode::new(
-                format!("lsu_shared_stq_w{warp}"),
+                format!("lsu_shared_stq{suffix}"),
                 TimedServer::new(config.queues.shared_stq),
             ));
             queues.push(WarpQueues {
@@ -314,7 +514,7 @@ impl LsuSubgraph {
             graph.connect(
                 nodes.shared_ldq,
                 issue_node,
-                format!("lsu_shared_ldq_w{warp}->issue"),
+                link_name("shared_ldq", shared, warp),
                 Link::new(config.link_capacity),
             );
         }
@@ -322,7 +522,7 @@ impl LsuSubgraph {
             graph.connect(
                 nodes.shared_stq,
                 issue_node,
-                format!("lsu_shared_stq_w{warp}->issue"),
+                link_name("shared_stq", shared, warp),
                 Link::new(config.link_capacity),
             );
         }
@@ -330,7 +530,7 @@ impl LsuSubgraph {
             graph.connect(
                 nodes.global_ldq,
                 issue_node,
-                format!("lsu_global_ldq_w{warp}->issue"),
+                link_name("global_ldq", shared, warp),
                 Link::new(config.link_capacity),
             );
         }
@@ -338,18 +538,29 @@ impl LsuSubgraph {
             graph.connect(
                 nodes.global_stq,
                 issue_node,
-                format!("lsu_global_stq_w{warp}->issue"),
+                link_name("global_stq", shared, warp),
                 Link::new(config.link_capacity),
             );
         }
 
-        let num_warps = queues.len();
+        let credits_per_warp = if shared {
+            config.queues.credits_per_warp
+        } else {
+            0
+        };
         Self {
             graph,
             issue_node,
             queues,
+            num_warps,
+            credits_per_warp,
+            in_flight: vec![[0; 4]; num_warps],
             store_pending_global: vec![0; num_warps],
             store_pending_shared: vec![0; num_warps],
+            pending_stores: vec![VecDeque::new(); num_warps],
+            forwarding: config.forwarding,
+            forwarded: VecDeque::new(),
+            outstanding_misses: vec![0; num_warps],
             resources: config.resources,
             address_in_use: 0,
             store_in_use: 0,
@@ -373,10 +584,39 @@ impl LsuSubgraph {
     ) -> Result<LsuIssue, LsuReject> {
         let retry_next = now.saturating_add(1);
         let kind = payload.queue_kind();
-        if self.load_blocked_by_store(&payload) {
+        if self.forwarding.enabled && payload.needs_load_data() {
+            match self.forward_source(&payload) {
+                Some(true) if self.can_reserve_load_data(&payload) => {
+                    return Ok(self.forward_load(now, payload));
+                }
+                Some(_) => {
+                    self.stats.forward_partial_stalls =
+                        self.stats.forward_partial_stalls.saturating_add(1);
+                    return Err(LsuReject::new(payload, retry_next, LsuRejectReason::Busy));
+                }
+                None => {}
+            }
+        } else if self.load_blocked_by_store(&payload) {
+            return Err(LsuReject::new(payload, retry_next, LsuRejectReason::Busy));
+        }
+        if self.miss_cap_reached(&payload) {
+            self.stats.busy_rejects = self.stats.busy_rejects.saturating_add(1);
+            self.stats.mlp_cap_rejects = self.stats.mlp_cap_rejects.saturating_add(1);
+            self.record_busy_reject(kind);
             return Err(LsuReject::new(payload, retry_next, LsuRejectReason::Busy));
         }
 
+        if self.credits_exhausted(&payload) {
+            self.stats.queue_full_rejects = self.stats.queue_full_rejects.saturating_add(1);
+            self.stats.credit_rejects = self.stats.credit_rejects.saturating_add(1);
+            self.record_queue_full_reject(kind);
+            return Err(LsuReject::new(
+                payload,
+                retry_next,
+                LsuRejectReason::QueueFull,
+            ));
+        }
+
         if !self.can_reserve(&payload) {
             return Err(LsuReject::new(
                 payload,
@@ -404,13 +644,24 @@ impl LsuSubgraph {
                 self.stats.issued = self.stats.issued.saturating_add(1);
                 self.record_issued(kind);
                 self.bump_store_pending(&payload_clone, true);
+                if let Some(slots) = self.in_flight.get_mut(warp) {
+                    slots[kind.index()] += 1;
+                }
+                if payload_clone.needs_store_data() {
+                    if let Some(stores) = self.pending_stores.get_mut(warp) {
+                        stores.push_back(payload_clone.footprint());
+                    }
+                }
                 if Self::needs_address(&payload_clone) {
                     self.address_in_use = self.address_in_use.saturating_add(1);
                 }
                 if Self::needs_store_data(&payload_clone) {
                     self.store_in_use = self.store_in_use.saturating_add(1);
                 }
-                Ok(LsuIssue { ticket })
+                Ok(LsuIssue {
+                    ticket,
+                    forwarded: false,
+                })
             }
             Err(bp) => match bp {
                 Backpressure::Busy {
@@ -441,12 +692,90 @@ impl LsuSubgraph {
 
     pub fn tick(&mut self, now: Cycle) {
         self.graph.tick(now);
+        self.sample_occupancy();
+    }
+
+    fn sample_occupancy(&mut self) {
+        self.stats.occupancy_cycles = self.stats.occupancy_cycles.saturating_add(1);
+        for kind in LsuQueueKind::ALL {
+            let held: usize = self.in_flight.iter().map(|slots| slots[kind.index()]).sum();
+            let (sum, peak) = self.stats.occupancy_mut(kind);
+            *sum = sum.saturating_add(held as u64);
+            *peak = (*peak).max(held as u64);
+        }
+    }
+
+    /// Looks for the youngest older store from the same warp that overlaps the
+    /// load: `Some(true)` if it covers the load entirely, `Some(false)` if it
+    /// only partly overlaps, `None` if no store overlaps.
+    fn forward_source(&self, payload: &LsuPayload) -> Option<bool> {
+        let (space, start, end) = payload.footprint();
+        self.pending_stores
+            .get(payload.warp())?
+            .iter()
+            .rev()
+            .find(|&&(store_space, store_start, store_end)| {
+                store_space == space && store_start < end && start < store_end
+            })
+            .map(|&(_, store_start, store_end)| store_start <= start && end <= store_end)
+    }
+
+    /// Whether a load issued now would be forwarded from the STQ.
+    pub fn can_forward(&self, payload: &LsuPayload) -> bool {
+        self.forwarding.enabled
+            && payload.needs_load_data()
+            && self.forward_source(payload) == Some(true)
+    }
+
+    fn forward_load(&mut self, now: Cycle, mut payload: LsuPayload) -> LsuIssue {
+        if let LsuPayload::Gmem(req) = &mut payload {
+            req.forwarded = true;
+        }
+        let ready_at = now.saturating_add(self.forwarding.latency);
+        let size_bytes = payload.bytes();
+        self.reserve_load_data(&payload);
+        self.stats.issued = self.stats.issued.saturating_add(1);
+        self.stats.forwarded_loads = self.stats.forwarded_loads.saturating_add(1);
+        self.record_issued(payload.queue_kind());
+        self.forwarded.push_back((ready_at, payload));
+        LsuIssue {
+            ticket: Ticket::new(now, ready_at, size_bytes),
+            forwarded: true,
+        }
+    }
+
+    /// Pops a forwarded load whose data is ready by `now`.
+    pub fn take_forwarded(&mut self, now: Cycle) -> Option<LsuPayload> {
+        if self.forwarded.front()?.0 > now {
+            return None;
+        }
+        let (_, payload) = self.forwarded.pop_front()?;
+        self.stats.completed = self.stats.completed.saturating_add(1);
+        self.record_completed(payload.queue_kind());
+        Some(payload)
+    }
+
+    /// Whether the payload's warp already holds all its credits in the shared
+    /// queue it targets.
+    fn credits_exhausted(&self, payload: &LsuPayload) -> bool {
+        if self.credits_per_warp == 0 {
+            return false;
+        }
+        self.in_flight
+            .get(payload.warp())
+            .is_some_and(|slots| slots[payload.queue_kind().index()] >= self.credits_per_warp)
     }
 
     pub fn stats(&self) -> LsuStats {
         self.stats
     }
 
+    /// Contention in the LSU's issue node and queues, with `prefix` on
+    /// node names.
+    pub fn bottlenecks(&self, prefix: &str) -> BottleneckReport {
+        BottleneckReport::from_graph(&self.graph, prefix)
+    }
+
     pub fn clear_stats(&mut self) {
         self.stats = LsuStats::default();
         self.address_in_use = 0;
@@ -463,6 +792,23 @@ impl LsuSubgraph {
         }
     }
 
+    /// Records a gmem miss from `warp` entering the memory system.
+    pub fn note_miss(&mut self, warp: usize) {
+        if let Some(slot) = self.outstanding_misses.get_mut(warp) {
+            *slot = slot.saturating_add(1);
+        }
+    }
+
+    pub fn release_miss(&mut self, warp: usize) {
+        if let Some(slot) = self.outstanding_misses.get_mut(warp) {
+            *slot = slot.saturating_sub(1);
+        }
+    }
+
+    pub fn outstanding_misses(&self, warp: usize) -> usize {
+        self.outstanding_misses.get(warp).copied().unwrap_or(0)
+    }
+
     pub fn reserve_load_data(&mut self, payload: &LsuPayload) -> bool {
         if !payload.needs_load_data() {
             return true;
@@ -494,6 +840,19 @@ impl LsuSubgraph {
             self.stats.completed = self.stats.completed.saturating_add(1);
             self.record_completed(result.payload.queue_kind());
             self.bump_store_pending(&result.payload, false);
+            let kind = result.payload.queue_kind();
+            let warp = result.payload.warp();
+            if let Some(slots) = self.in_flight.get_mut(warp) {
+                slots[kind.index()] = slots[kind.index()].saturating_sub(1);
+            }
+            if result.payload.needs_store_data() {
+                let footprint = result.payload.footprint();
+                if let Some(stores) = self.pending_stores.get_mut(warp) {
+                    if let Some(pos) = stores.iter().position(|&store| store == footprint) {
+                        stores.remove(pos);
+                    }
+                }
+            }
             LsuCompletion {
                 request: result.payload,
                 ticket_ready_at: result.ticket.ready_at(),
@@ -503,7 +862,12 @@ impl LsuSubgraph {
     }
 
     fn queue_node(&self, warp: usize, kind: LsuQueueKind) -> Option<NodeId> {
-        let queues = self.queues.get(warp)?;
+        if warp >= self.num_warps {
+            return None;
+        }
+        // Shared queues are a single set used by every warp.
+        let set = if self.queues.len() == 1 { 0 } else { warp };
+        let queues = self.queues.get(set)?;
         Some(match kind {
             LsuQueueKind::GlobalLoad => queues.global_ldq,
             LsuQueueKind::GlobalStore => queues.global_stq,
@@ -527,6 +891,16 @@ impl LsuSubgraph {
         }
     }
 
+    fn miss_cap_reached(&self, payload: &LsuPayload) -> bool {
+        let cap = self.resources.max_outstanding_misses_per_warp;
+        match payload {
+            LsuPayload::Gmem(req) if cap > 0 && req.kind.is_mem() => {
+                self.outstanding_misses(req.warp) >= cap
+            }
+            _ => false,
+        }
+    }
+
     fn bump_store_pending(&mut self, payload: &LsuPayload, increment: bool) {
         let warp = payload.warp();
         match payload.queue_kind() {
@@ -658,3 +1032,11 @@ impl LsuSubgraph {
         }
     }
 }
+
+fn link_name(queue: &str, shared: bool, warp: usize) -> String {
+    if shared {
+        format!("lsu_{queue}->issue")
+    } else {
+        format!("lsu_{queue}_w{warp}->issue")
+    }
+}
@@ -1,52 +1,124 @@
 pub mod barrier;
+pub mod bottleneck;
+pub mod context_switch;
+pub mod copy_engine;
 pub mod core_graph;
+pub mod crossbar;
+pub mod div_sqrt;
 pub mod dma;
 pub mod execute;
 pub mod fence;
+pub mod forwarding;
 pub mod gmem;
 pub mod graph;
+pub mod harness;
+pub mod ibuffer;
 pub mod icache;
+pub mod invariants;
 pub mod lsu;
+pub mod neutrino;
 pub mod operand_fetch;
+pub mod perf_counters;
+pub mod pipeline_node;
+pub mod power;
+pub mod request_deps;
+pub mod request_timeout;
 pub mod server_node;
 pub mod simple_queue;
 pub mod smem;
+pub mod stats_switch;
+pub mod store_buffer;
 pub mod tensor;
+pub mod tick_order;
 pub mod types;
 pub mod unit_tests;
 pub mod warp_scheduler;
 pub mod writeback;
 
 pub use barrier::{BarrierConfig, BarrierManager, BarrierSummary};
+pub use bottleneck::{BottleneckReport, FullChainReport, NodeBottleneck};
+pub use context_switch::{
+    ContextBacking, ContextPathConfig, ContextSwitchConfig, ContextSwitchStats, WarpContexts,
+};
+pub use copy_engine::{
+    contiguous_span, CopyEngine, CopyEngineConfig, CopyEngineStats, CopyStreamKind,
+};
 pub use core_graph::{CoreGraph, CoreGraphConfig};
+pub use crossbar::{
+    CrossbarConfig, CrossbarNode, CrossbarPortStats, CrossbarStats, CrossbarStatsHandle,
+};
+pub use div_sqrt::{DivSqrtConfig, DivSqrtOp, DivSqrtStats, DivSqrtUnit};
 pub use dma::{DmaConfig, DmaQueue, DmaReject, DmaRejectReason};
-pub use execute::{ExecUnitKind, ExecutePipeline, ExecutePipelineConfig};
+pub use execute::{
+    ExecUnitKind, ExecutePipeline, ExecutePipelineConfig, ReservationStationConfig,
+    ReservationStationStats, ReservationStations,
+};
 pub use fence::{
-    FenceConfig, FenceIssue, FenceQueue, FenceReject, FenceRejectReason, FenceRequest,
+    decode_fence, FenceConfig, FenceIssue, FenceOrder, FenceQueue, FenceReject, FenceRejectReason,
+    FenceRequest, FenceScope, FenceScopeStats, FenceStats,
 };
+pub use forwarding::{ForwardPath, Forwarding, ForwardingConfig, ForwardingStats};
 pub use gmem::{
-    ClusterGmemGraph, GmemCompletion, GmemFlowConfig, GmemIssue, GmemPolicyConfig, GmemReject,
-    GmemRejectReason, GmemRequest, GmemRequestKind, GmemStats,
+    BackgroundTrafficConfig, ByteMask, CacheBankActivity, CacheHint, CacheHintConfig,
+    CacheHintStats, ClusterGmemGraph, DataPortStats, DieTrafficStats, DramChannelStats, DramConfig,
+    DramInterleave, DramShareStats, FillForwardConfig, FillForwardStats, FlushOrdering,
+    GmemCompletion, GmemFlowConfig, GmemIssue, GmemPolicyConfig, GmemPolicyMode, GmemReject,
+    GmemRejectReason, GmemRequest, GmemRequestKind, GmemStats, GraphValidation, InjectorConfig,
+    InjectorPattern, InjectorStats, MemoryMapConfig, MemoryRegionConfig, MemoryRegionKind,
+    MemoryRegionStats, RefillContention, RemoteConfig, SalvageConfig, TrafficAgent,
+    UnroutedRequest, WriteCombiningConfig, WriteCombiningStats,
+};
+pub use graph::{
+    AdaptiveRouteStats, EdgeStats, FlowGraph, FullChain, InjectedStall, Link, LinkBackpressure,
+    MisrouteAction, MisrouteStats, NodeActivity, PathAccessor, PathHop, RequestPath, RouteCoverage,
+    RouteTrace, StallInjection, TimedNode,
+};
+pub use harness::{
+    Expect, GraphPorts, HarnessTarget, Script, ScriptError, ScriptTrace, StepRecord, Violation,
 };
-pub use graph::{EdgeStats, FlowGraph, Link, LinkBackpressure, TimedNode};
+pub use ibuffer::{IbufferConfig, IbufferStats, Ibuffers};
 pub use icache::{
-    IcacheFlowConfig, IcacheIssue, IcacheReject, IcacheRejectReason, IcacheRequest, IcacheStats,
-    IcacheSubgraph,
+    IcacheFlowConfig, IcacheIssue, IcacheReject, IcacheRejectReason, IcacheReplayConfig,
+    IcacheRequest, IcacheStats, IcacheSubgraph,
+};
+pub use invariants::{
+    InvariantAction, InvariantCategory, InvariantConfig, InvariantSummary, InvariantViolations,
 };
 pub use lsu::{
-    LsuCompletion, LsuFlowConfig, LsuIssue, LsuReject, LsuRejectReason, LsuStats, LsuSubgraph,
+    LsuCompletion, LsuFlowConfig, LsuIssue, LsuQueueSharing, LsuReject, LsuRejectReason, LsuStats,
+    LsuSubgraph,
+};
+pub use neutrino::{
+    NeutrinoIssue, NeutrinoOpKind, NeutrinoQueue, NeutrinoReject, NeutrinoRejectReason,
+    NeutrinoStats, NeutrinoTimingConfig,
 };
 pub use operand_fetch::{
     OperandFetchConfig, OperandFetchQueue, OperandFetchReject, OperandFetchRejectReason,
 };
+pub use perf_counters::{
+    PerfCounterConfig, PerfCounterFile, PerfCounterMode, PerfCounters, RetireClass, WarpRetireStats,
+};
+pub use pipeline_node::{PipelineConfig, PipelineNode, PipelineStats};
+pub use power::{PowerConfig, PowerGovernor, PowerSummary, ThrottleMode};
+pub use request_deps::{
+    DependencyError, DependencyStats, DependencyTable, RequestKind, RequestRef,
+};
+pub use request_timeout::{
+    RequestSpace, RequestTimeoutConfig, RequestTimeoutStats, RequestTimeouts, TimedOutRequest,
+    TimeoutAction,
+};
 pub use server_node::ServerNode;
 pub use smem::{
     SmemCompletion, SmemFlowConfig, SmemIssue, SmemReject, SmemRejectReason, SmemRequest, SmemStats,
 };
+pub use stats_switch::{StatsSwitch, STATS_COMPILED};
+pub use store_buffer::{StoreBuffer, StoreBufferConfig, StoreBufferLookup, StoreBufferStats};
 pub use tensor::{TensorConfig, TensorQueue, TensorReject, TensorRejectReason};
-pub use types::{CoreFlowPayload, LinkId, NodeId};
+pub use tick_order::{TickOrderConfig, TickStage, TickUnit};
+pub use types::{CoreFlowPayload, CustomRequest, LinkId, NodeId};
 pub use warp_scheduler::{WarpIssueScheduler, WarpSchedulerConfig};
 pub use writeback::{
-    WritebackConfig, WritebackIssue, WritebackPayload, WritebackQueue, WritebackReject,
-    WritebackRejectReason, WritebackStats,
+    ExecuteWriteback, TensorWriteback, WritebackClass, WritebackClassCounters, WritebackClassStats,
+    WritebackConfig, WritebackIssue, WritebackPayload, WritebackQueue, WritebackQuotaConfig,
+    WritebackReject, WritebackRejectReason, WritebackStats,
 };
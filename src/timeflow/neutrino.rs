@@ -0,0 +1,260 @@
+use serde::{Deserialize, Serialize};
+use std::ops::AddAssign;
+
+use crate::muon::execute::Opcode;
+use crate::timeflow::simple_queue::SimpleTimedQueue;
+pub use crate::timeflow::types::RejectReason as NeutrinoRejectReason;
+use crate::timeq::{Cycle, ServerConfig, Ticket};
+
+pub type NeutrinoReject = crate::timeflow::types::Reject;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NeutrinoOpKind {
+    Invoke,
+    Payload,
+    Complete,
+}
+
+impl NeutrinoOpKind {
+    pub fn from_extended_opcode(extended_opcode: u16) -> Option<Self> {
+        match extended_opcode {
+            Opcode::NU_INVOKE | Opcode::NU_INVOKE_IMM => Some(Self::Invoke),
+            Opcode::NU_PAYLOAD => Some(Self::Payload),
+            Opcode::NU_COMPLETE => Some(Self::Complete),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct NeutrinoStats {
+    pub invokes: u64,
+    pub payloads: u64,
+    pub completes: u64,
+    pub dispatched: u64,
+    pub completed: u64,
+    pub queue_full_rejects: u64,
+    pub busy_rejects: u64,
+    pub concurrency_rejects: u64,
+    pub bytes_issued: u64,
+    pub inflight: u64,
+    pub max_inflight: u64,
+    pub total_invoke_latency: u64,
+}
+
+impl AddAssign<&NeutrinoStats> for NeutrinoStats {
+    fn add_assign(&mut self, other: &NeutrinoStats) {
+        self.invokes = self.invokes.saturating_add(other.invokes);
+        self.payloads = self.payloads.saturating_add(other.payloads);
+        self.completes = self.completes.saturating_add(other.completes);
+        self.dispatched = self.dispatched.saturating_add(other.dispatched);
+        self.completed = self.completed.saturating_add(other.completed);
+        self.queue_full_rejects = self
+            .queue_full_rejects
+            .saturating_add(other.queue_full_rejects);
+        self.busy_rejects = self.busy_rejects.saturating_add(other.busy_rejects);
+        self.concurrency_rejects = self
+            .concurrency_rejects
+            .saturating_add(other.concurrency_rejects);
+        self.bytes_issued = self.bytes_issued.saturating_add(other.bytes_issued);
+        self.inflight = self.inflight.saturating_add(other.inflight);
+        self.max_inflight = self.max_inflight.max(other.max_inflight);
+        self.total_invoke_latency = self
+            .total_invoke_latency
+            .saturating_add(other.total_invoke_latency);
+    }
+}
+
+/// Timing parameters for the Neutrino offload path. The flattened `queue`
+/// models the command queue; its `bytes_per_cycle` is the payload transfer
+/// bandwidth into the unit.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct NeutrinoTimingConfig {
+    pub enabled: bool,
+    pub command_bytes: u32,
+    pub payload_bytes_per_lane: u32,
+    pub completion_latency: Cycle,
+    pub max_inflight: usize,
+    #[serde(flatten)]
+    pub queue: ServerConfig,
+}
+
+impl Default for NeutrinoTimingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            command_bytes: 8,
+            payload_bytes_per_lane: 4,
+            completion_latency: 32,
+            max_inflight: 4,
+            queue: ServerConfig {
+                base_latency: 2,
+                bytes_per_cycle: 16,
+                queue_capacity: 4,
+                completions_per_cycle: 1,
+                ..ServerConfig::default()
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct NeutrinoIssue {
+    /// Ticket for the command leaving the command queue.
+    pub ticket: Ticket,
+    /// Cycle at which the offloaded work finishes; equal to the dispatch
+    /// cycle for payload and complete commands.
+    pub complete_at: Cycle,
+}
+
+#[derive(Clone)]
+pub struct NeutrinoQueue {
+    queue: SimpleTimedQueue<NeutrinoOpKind>,
+    command_bytes: u32,
+    payload_bytes_per_lane: u32,
+    completion_latency: Cycle,
+    max_inflight: usize,
+    inflight: Vec<Cycle>,
+    stats: NeutrinoStats,
+}
+
+impl NeutrinoQueue {
+    pub fn new(config: NeutrinoTimingConfig) -> Self {
+        Self {
+            queue: SimpleTimedQueue::new(config.enabled, config.queue),
+            command_bytes: config.command_bytes,
+            payload_bytes_per_lane: config.payload_bytes_per_lane,
+            completion_latency: config.completion_latency,
+            max_inflight: config.max_inflight.max(1),
+            inflight: Vec::new(),
+            stats: NeutrinoStats::default(),
+        }
+    }
+
+    pub fn command_size(&self, kind: NeutrinoOpKind, active_lanes: u32) -> u32 {
+        match kind {
+            NeutrinoOpKind::Payload => self.command_bytes.saturating_add(
+                self.payload_bytes_per_lane
+                    .saturating_mul(active_lanes.max(1)),
+            ),
+            _ => self.command_bytes,
+        }
+    }
+
+    pub fn try_issue(
+        &mut self,
+        now: Cycle,
+        kind: NeutrinoOpKind,
+        active_lanes: u32,
+    ) -> Result<NeutrinoIssue, NeutrinoReject> {
+        if !self.queue.is_enabled() {
+            return Ok(NeutrinoIssue {
+                ticket: Ticket::new(now, now, 0),
+                complete_at: now,
+            });
+        }
+
+        if kind == NeutrinoOpKind::Invoke {
+            self.retire(now);
+            if self.inflight.len() >= self.max_inflight {
+                self.stats.concurrency_rejects = self.stats.concurrency_rejects.saturating_add(1);
+                let retry_at = self
+                    .inflight
+                    .iter()
+                    .copied()
+                    .min()
+                    .unwrap_or(now)
+                    .max(now.saturating_add(1));
+                return Err(NeutrinoReject::new(retry_at, NeutrinoRejectReason::Busy));
+            }
+        }
+
+        let bytes = self.command_size(kind, active_lanes);
+        match self.queue.try_issue(now, kind, bytes) {
+            Ok(ticket) => {
+                self.stats.bytes_issued = self.stats.bytes_issued.saturating_add(bytes as u64);
+                let complete_at = match kind {
+                    NeutrinoOpKind::Invoke => {
+                        self.stats.invokes = self.stats.invokes.saturating_add(1);
+                        let complete_at = ticket.ready_at().saturating_add(self.completion_latency);
+                        self.inflight.push(complete_at);
+                        self.stats.inflight = self.inflight.len() as u64;
+                        self.stats.max_inflight = self.stats.max_inflight.max(self.stats.inflight);
+                        self.stats.total_invoke_latency = self
+                            .stats
+                            .total_invoke_latency
+                            .saturating_add(complete_at.saturating_sub(now));
+                        complete_at
+                    }
+                    NeutrinoOpKind::Payload => {
+                        self.stats.payloads = self.stats.payloads.saturating_add(1);
+                        ticket.ready_at()
+                    }
+                    NeutrinoOpKind::Complete => {
+                        self.stats.completes = self.stats.completes.saturating_add(1);
+                        ticket.ready_at()
+                    }
+                };
+                Ok(NeutrinoIssue {
+                    ticket,
+                    complete_at,
+                })
+            }
+            Err(err) => {
+                match err.reason {
+                    NeutrinoRejectReason::QueueFull | NeutrinoRejectReason::FlushLimit => {
+                        self.stats.queue_full_rejects =
+                            self.stats.queue_full_rejects.saturating_add(1);
+                    }
+                    NeutrinoRejectReason::Busy => {
+                        self.stats.busy_rejects = self.stats.busy_rejects.saturating_add(1);
+                    }
+                }
+                Err(err)
+            }
+        }
+    }
+
+    pub fn tick(&mut self, now: Cycle) {
+        let mut dispatched = 0u64;
+        self.queue.tick(now, |_| {
+            dispatched += 1;
+        });
+        self.stats.dispatched = self.stats.dispatched.saturating_add(dispatched);
+        self.retire(now);
+    }
+
+    fn retire(&mut self, now: Cycle) {
+        let before = self.inflight.len();
+        self.inflight.retain(|&complete_at| complete_at > now);
+        let retired = before - self.inflight.len();
+        if retired > 0 {
+            self.stats.completed = self.stats.completed.saturating_add(retired as u64);
+            self.stats.inflight = self.inflight.len() as u64;
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.queue.is_enabled()
+    }
+
+    pub fn is_busy(&self) -> bool {
+        self.queue.is_busy() || !self.inflight.is_empty()
+    }
+
+    pub fn inflight(&self) -> usize {
+        self.inflight.len()
+    }
+
+    pub fn stats(&self) -> NeutrinoStats {
+        self.stats
+    }
+
+    pub fn clear_stats(&mut self) {
+        self.stats = NeutrinoStats {
+            inflight: self.inflight.len() as u64,
+            ..NeutrinoStats::default()
+        };
+    }
+}
@@ -0,0 +1,231 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct PerfCounterConfig {
+    /// Off, the counter CSRs read 0.
+    pub enabled: bool,
+    pub mode: PerfCounterMode,
+}
+
+impl Default for PerfCounterConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            mode: PerfCounterMode::Continue,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PerfCounterMode {
+    /// Every read sees the counters as of the cycle it executes.
+    #[default]
+    Continue,
+    /// Back-to-back counter reads by a warp all see the values of the
+    /// first one, so a low/high pair or a group of counters is consistent.
+    /// Any other instruction ends the run.
+    Freeze,
+}
+
+/// Core counters as the guest sees them. Each is 64 bits wide, read as a
+/// low half at its CSR address and a high half at the address + 0x80; the
+/// `mcycle`-style addresses at 0xbNN and the user aliases at 0xcNN read the
+/// same value. All but `cycle` restart when the timing stats are cleared at
+/// the end of warmup.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PerfCounters {
+    /// 0xb00 / 0xc00.
+    pub cycle: u64,
+    /// Instructions executed by the core's warps; 0xb02 / 0xc02.
+    pub instret: u64,
+    /// Gmem accesses of the core by level, 0xb03 to 0xb08 / 0xc03 to 0xc08.
+    pub l0_hits: u64,
+    pub l0_misses: u64,
+    pub l1_hits: u64,
+    pub l1_misses: u64,
+    pub l2_hits: u64,
+    pub l2_misses: u64,
+    /// Cycles the core had active warps but issued nothing; 0xb09 / 0xc09.
+    pub stall_cycles: u64,
+    /// The reading warp's own retired instructions by class, 0xb0a to
+    /// 0xb0f / 0xc0a to 0xc0f, and its active cycles, 0xb10 / 0xc10.
+    pub warp: WarpRetireStats,
+}
+
+impl PerfCounters {
+    pub fn is_counter_csr(addr: u32) -> bool {
+        Self::index(addr).is_some()
+    }
+
+    /// Counter number (the low byte of its `mcycle`-style address) of a
+    /// counter CSR, either half, either alias.
+    fn index(addr: u32) -> Option<u32> {
+        match addr & !0x80 {
+            0xb00 | 0xb02..=0xb10 => Some(addr & 0x7f),
+            0xc00 | 0xc02..=0xc10 => Some(addr & 0x7f),
+            _ => None,
+        }
+    }
+
+    pub fn csr(&self, addr: u32) -> Option<u32> {
+        let value = match Self::index(addr)? {
+            0x00 => self.cycle,
+            0x02 => self.instret,
+            0x03 => self.l0_hits,
+            0x04 => self.l0_misses,
+            0x05 => self.l1_hits,
+            0x06 => self.l1_misses,
+            0x07 => self.l2_hits,
+            0x08 => self.l2_misses,
+            0x09 => self.stall_cycles,
+            0x0a => self.warp.alu,
+            0x0b => self.warp.fpu,
+            0x0c => self.warp.load,
+            0x0d => self.warp.store,
+            0x0e => self.warp.branch,
+            0x0f => self.warp.sfu,
+            _ => self.warp.active_cycles,
+        };
+        let half = if addr & 0x80 != 0 { value >> 32 } else { value };
+        Some(half as u32)
+    }
+}
+
+/// Class a retired instruction is counted under; every instruction falls
+/// in exactly one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RetireClass {
+    Alu,
+    Fpu,
+    Load,
+    /// Stores, fences and cache flushes.
+    Store,
+    /// Branches and jumps.
+    Branch,
+    /// SFU ops, warp shuffles and votes, warp control and environment
+    /// calls.
+    Sfu,
+}
+
+/// Instructions one warp retired, by class, and the cycles it had to
+/// retire them in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct WarpRetireStats {
+    pub alu: u64,
+    pub fpu: u64,
+    pub load: u64,
+    pub store: u64,
+    pub branch: u64,
+    pub sfu: u64,
+    /// Cycles the warp was active, not waiting at a barrier and not
+    /// waiting on a Neutrino op it issued. The excluded cycles are the ones
+    /// the CPI stack charges to `sync`.
+    pub active_cycles: u64,
+}
+
+impl WarpRetireStats {
+    pub fn retired(&self) -> u64 {
+        self.alu + self.fpu + self.load + self.store + self.branch + self.sfu
+    }
+
+    /// Retired instructions per active cycle.
+    pub fn ipc(&self) -> f64 {
+        if self.active_cycles == 0 {
+            0.0
+        } else {
+            self.retired() as f64 / self.active_cycles as f64
+        }
+    }
+
+    fn count(&mut self, class: RetireClass) {
+        let counter = match class {
+            RetireClass::Alu => &mut self.alu,
+            RetireClass::Fpu => &mut self.fpu,
+            RetireClass::Load => &mut self.load,
+            RetireClass::Store => &mut self.store,
+            RetireClass::Branch => &mut self.branch,
+            RetireClass::Sfu => &mut self.sfu,
+        };
+        *counter = counter.saturating_add(1);
+    }
+}
+
+/// Per-warp read state for the counter CSRs.
+#[derive(Debug, Clone)]
+pub struct PerfCounterFile {
+    config: PerfCounterConfig,
+    frozen: Vec<Option<PerfCounters>>,
+    retired: u64,
+    warps: Vec<WarpRetireStats>,
+}
+
+impl PerfCounterFile {
+    pub fn new(config: PerfCounterConfig, num_warps: usize) -> Self {
+        Self {
+            config,
+            frozen: vec![None; num_warps],
+            retired: 0,
+            warps: vec![WarpRetireStats::default(); num_warps],
+        }
+    }
+
+    pub fn set_config(&mut self, config: PerfCounterConfig) {
+        self.config = config;
+        self.frozen.iter_mut().for_each(|frozen| *frozen = None);
+    }
+
+    /// Instructions retired since the stats were last cleared.
+    pub fn retired(&self) -> u64 {
+        self.retired
+    }
+
+    /// Per-warp counts since the stats were last cleared; they sum to
+    /// `retired`.
+    pub fn warp_stats(&self) -> &[WarpRetireStats] {
+        &self.warps
+    }
+
+    pub fn warp(&self, warp: usize) -> WarpRetireStats {
+        self.warps.get(warp).copied().unwrap_or_default()
+    }
+
+    pub fn clear_stats(&mut self) {
+        self.retired = 0;
+        self.warps.fill(WarpRetireStats::default());
+    }
+
+    /// Counts a cycle `warp` was active and not waiting at a barrier or on
+    /// a Neutrino op.
+    pub fn count_active_cycle(&mut self, warp: usize) {
+        if let Some(stats) = self.warps.get_mut(warp) {
+            stats.active_cycles = stats.active_cycles.saturating_add(1);
+        }
+    }
+
+    /// The counters `warp` reads now, given their `live` values.
+    pub fn read(&mut self, warp: usize, live: PerfCounters) -> PerfCounters {
+        if !self.config.enabled {
+            return PerfCounters::default();
+        }
+        match (self.config.mode, self.frozen.get_mut(warp)) {
+            (PerfCounterMode::Freeze, Some(frozen)) => *frozen.get_or_insert(live),
+            _ => live,
+        }
+    }
+
+    /// Counts an instruction of `warp` as executed; anything but a counter
+    /// read ends the warp's frozen run.
+    pub fn retire(&mut self, warp: usize, class: RetireClass, counter_read: bool) {
+        self.retired = self.retired.saturating_add(1);
+        if let Some(stats) = self.warps.get_mut(warp) {
+            stats.count(class);
+        }
+        if !counter_read {
+            if let Some(frozen) = self.frozen.get_mut(warp) {
+                *frozen = None;
+            }
+        }
+    }
+}
@@ -0,0 +1,225 @@
+use std::collections::VecDeque;
+use std::ops::AddAssign;
+use std::sync::Arc;
+
+use serde::Deserialize;
+
+use crate::timeflow::graph::TimedNode;
+use crate::timeq::{Backpressure, Cycle, ServiceRequest, ServiceResult, Ticket};
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct PipelineConfig {
+    /// Pipeline depth; a request that never stalls leaves after this many
+    /// cycles.
+    pub stages: usize,
+    /// Results held at the exit while downstream is not taking them. Once
+    /// it fills, the last stage stalls and the stall ripples back to the
+    /// input.
+    pub skid_depth: usize,
+}
+
+impl Default for PipelineConfig {
+    fn default() -> Self {
+        Self {
+            stages: 1,
+            skid_depth: 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PipelineStats {
+    pub accepted: u64,
+    pub completed: u64,
+    /// Requests turned away because the first stage was still occupied.
+    pub input_stalls: u64,
+    /// Stage-cycles an entry spent unable to advance.
+    pub stage_stall_cycles: u64,
+    /// Sum over cycles of occupied stages, for average occupancy.
+    pub occupancy_sum: u64,
+    pub cycles: u64,
+    pub squashed: u64,
+}
+
+impl PipelineStats {
+    pub fn avg_occupancy(&self) -> f64 {
+        if self.cycles == 0 {
+            0.0
+        } else {
+            self.occupancy_sum as f64 / self.cycles as f64
+        }
+    }
+}
+
+impl AddAssign<&PipelineStats> for PipelineStats {
+    fn add_assign(&mut self, other: &PipelineStats) {
+        self.accepted = self.accepted.saturating_add(other.accepted);
+        self.completed = self.completed.saturating_add(other.completed);
+        self.input_stalls = self.input_stalls.saturating_add(other.input_stalls);
+        self.stage_stall_cycles = self
+            .stage_stall_cycles
+            .saturating_add(other.stage_stall_cycles);
+        self.occupancy_sum = self.occupancy_sum.saturating_add(other.occupancy_sum);
+        self.cycles = self.cycles.saturating_add(other.cycles);
+        self.squashed = self.squashed.saturating_add(other.squashed);
+    }
+}
+
+#[derive(Clone)]
+struct StageEntry<T> {
+    payload: T,
+    ticket: Ticket,
+    /// Cycle the entry moved into its current stage; it may advance from the
+    /// following cycle.
+    entered_at: Cycle,
+}
+
+/// Fixed-depth pipeline with one request per stage, for structures such as
+/// tag lookup or crossbar stages where occupancy matters cycle by cycle.
+/// Unlike `ServerNode`, there is no queue in front: a request is accepted
+/// only when the first stage is free, and a full skid buffer at the exit
+/// stalls every stage behind it.
+#[derive(Clone)]
+pub struct PipelineNode<T> {
+    name: Arc<str>,
+    stages: Vec<Option<StageEntry<T>>>,
+    skid: VecDeque<ServiceResult<T>>,
+    skid_depth: usize,
+    stats: PipelineStats,
+}
+
+impl<T> PipelineNode<T> {
+    pub fn new(name: impl Into<Arc<str>>, config: PipelineConfig) -> Self {
+        let stages = config.stages.max(1);
+        Self {
+            name: name.into(),
+            stages: (0..stages).map(|_| None).collect(),
+            skid: VecDeque::new(),
+            skid_depth: config.skid_depth.max(1),
+            stats: PipelineStats::default(),
+        }
+    }
+
+    pub fn depth(&self) -> usize {
+        self.stages.len()
+    }
+
+    /// Which stages hold a request, first stage first.
+    pub fn occupied_stages(&self) -> Vec<bool> {
+        self.stages.iter().map(Option::is_some).collect()
+    }
+
+    pub fn skid_len(&self) -> usize {
+        self.skid.len()
+    }
+
+    pub fn stats(&self) -> PipelineStats {
+        self.stats
+    }
+
+    pub fn clear_stats(&mut self) {
+        self.stats = PipelineStats::default();
+    }
+}
+
+impl<T: Clone + Send + Sync + 'static> TimedNode<T> for PipelineNode<T> {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn try_put(
+        &mut self,
+        now: Cycle,
+        request: ServiceRequest<T>,
+    ) -> Result<Ticket, Backpressure<T>> {
+        if self.stages[0].is_some() {
+            self.stats.input_stalls = self.stats.input_stalls.saturating_add(1);
+            return Err(Backpressure::Busy {
+                request,
+                available_at: now.saturating_add(1),
+            });
+        }
+        let ready_at = now.saturating_add(self.stages.len() as Cycle);
+        let ticket = Ticket::new(now, ready_at, request.size_bytes);
+        self.stages[0] = Some(StageEntry {
+            payload: request.payload,
+            ticket,
+            entered_at: now,
+        });
+        self.stats.accepted = self.stats.accepted.saturating_add(1);
+        Ok(ticket)
+    }
+
+    fn tick(&mut self, now: Cycle) {
+        // Walk from the exit backwards so a stage freed this cycle can be
+        // refilled by the one behind it.
+        let last = self.stages.len() - 1;
+        for idx in (0..=last).rev() {
+            let movable = self.stages[idx]
+                .as_ref()
+                .is_some_and(|entry| entry.entered_at < now);
+            if !movable {
+                continue;
+            }
+            let has_room = if idx == last {
+                self.skid.len() < self.skid_depth
+            } else {
+                self.stages[idx + 1].is_none()
+            };
+            if !has_room {
+                self.stats.stage_stall_cycles = self.stats.stage_stall_cycles.saturating_add(1);
+                continue;
+            }
+            let Some(mut entry) = self.stages[idx].take() else {
+                continue;
+            };
+            if idx == last {
+                self.skid.push_back(ServiceResult {
+                    payload: entry.payload,
+                    ticket: entry.ticket,
+                });
+            } else {
+                entry.entered_at = now;
+                self.stages[idx + 1] = Some(entry);
+            }
+        }
+
+        let occupied = self.stages.iter().filter(|stage| stage.is_some()).count();
+        self.stats.occupancy_sum = self.stats.occupancy_sum.saturating_add(occupied as u64);
+        self.stats.cycles = self.stats.cycles.saturating_add(1);
+    }
+
+    fn peek_ready(&mut self, _now: Cycle) -> Option<&ServiceResult<T>> {
+        self.skid.front()
+    }
+
+    fn take_ready(&mut self, _now: Cycle) -> Option<ServiceResult<T>> {
+        let result = self.skid.pop_front()?;
+        self.stats.completed = self.stats.completed.saturating_add(1);
+        Some(result)
+    }
+
+    fn outstanding(&self) -> usize {
+        self.stages.iter().filter(|stage| stage.is_some()).count() + self.skid.len()
+    }
+
+    fn squash(&mut self, pred: &mut dyn FnMut(&T, &Ticket) -> bool) -> usize {
+        let mut squashed = 0;
+        for stage in &mut self.stages {
+            if stage
+                .as_ref()
+                .is_some_and(|entry| pred(&entry.payload, &entry.ticket))
+            {
+                *stage = None;
+                squashed += 1;
+            }
+        }
+        self.stats.squashed = self.stats.squashed.saturating_add(squashed as u64);
+        squashed
+    }
+
+    fn clone_box(&self) -> Box<dyn TimedNode<T>> {
+        Box::new(self.clone())
+    }
+}
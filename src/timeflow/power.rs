@@ -0,0 +1,234 @@
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::ops::AddAssign;
+
+use crate::timeq::Cycle;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ThrottleMode {
+    /// Cap the number of warps issued per cycle.
+    IssueWidth,
+    /// Let only one of every `duty_cycle_period` cycles issue.
+    DutyCycle,
+}
+
+/// Activity-based energy estimate and the throttling governor built on it.
+/// Energies are in arbitrary units; only their ratios and `budget` matter.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct PowerConfig {
+    pub enabled: bool,
+    /// Energy spent every cycle regardless of activity.
+    pub static_energy: f64,
+    /// Energy per issued warp instruction.
+    pub issue_energy: f64,
+    /// Energy per gmem request accepted by the LSU.
+    pub gmem_energy: f64,
+    /// Energy per smem request accepted by the LSU.
+    pub smem_energy: f64,
+    /// Cycles in the moving average the governor compares to `budget`.
+    pub window: usize,
+    /// Average energy per cycle the governor holds the core to (0 = never
+    /// throttle).
+    pub budget: f64,
+    pub mode: ThrottleMode,
+    /// Warps issued per cycle while throttled in `issue_width` mode.
+    pub throttled_issue_width: usize,
+    pub duty_cycle_period: u64,
+}
+
+impl Default for PowerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            static_energy: 1.0,
+            issue_energy: 4.0,
+            gmem_energy: 8.0,
+            smem_energy: 2.0,
+            window: 64,
+            budget: 0.0,
+            mode: ThrottleMode::IssueWidth,
+            throttled_issue_width: 1,
+            duty_cycle_period: 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct PowerSummary {
+    pub cycles: u64,
+    pub static_energy: f64,
+    pub issue_energy: f64,
+    pub gmem_energy: f64,
+    pub smem_energy: f64,
+    /// Highest moving-average power seen.
+    pub peak_power: f64,
+    /// Cycles the governor was throttling.
+    pub throttled_cycles: u64,
+    /// Eligible warps held back by the governor.
+    pub throttled_issues: u64,
+}
+
+impl PowerSummary {
+    pub fn total_energy(&self) -> f64 {
+        self.static_energy + self.issue_energy + self.gmem_energy + self.smem_energy
+    }
+
+    pub fn avg_power(&self) -> f64 {
+        if self.cycles == 0 {
+            0.0
+        } else {
+            self.total_energy() / self.cycles as f64
+        }
+    }
+}
+
+impl AddAssign<&PowerSummary> for PowerSummary {
+    fn add_assign(&mut self, other: &PowerSummary) {
+        self.cycles = self.cycles.saturating_add(other.cycles);
+        self.static_energy += other.static_energy;
+        self.issue_energy += other.issue_energy;
+        self.gmem_energy += other.gmem_energy;
+        self.smem_energy += other.smem_energy;
+        self.peak_power = self.peak_power.max(other.peak_power);
+        self.throttled_cycles = self.throttled_cycles.saturating_add(other.throttled_cycles);
+        self.throttled_issues = self.throttled_issues.saturating_add(other.throttled_issues);
+    }
+}
+
+/// Per-core power governor. Events are charged as they happen and folded into
+/// the moving average by `end_cycle`; the decision to throttle takes effect
+/// from the next cycle.
+#[derive(Debug, Clone)]
+pub struct PowerGovernor {
+    config: PowerConfig,
+    history: VecDeque<f64>,
+    window_energy: f64,
+    cycle_energy: f64,
+    throttling: bool,
+    denied_this_cycle: bool,
+    summary: PowerSummary,
+}
+
+impl PowerGovernor {
+    pub fn new(config: PowerConfig) -> Self {
+        Self {
+            history: VecDeque::with_capacity(config.window.max(1)),
+            config,
+            window_energy: 0.0,
+            cycle_energy: 0.0,
+            throttling: false,
+            denied_this_cycle: false,
+            summary: PowerSummary::default(),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    /// Swaps energies and budget mid-run; the moving average and totals carry
+    /// over.
+    pub fn set_config(&mut self, config: PowerConfig) {
+        self.config = config;
+        while self.history.len() > self.config.window.max(1) {
+            if let Some(old) = self.history.pop_front() {
+                self.window_energy -= old;
+            }
+        }
+    }
+
+    pub fn record_gmem(&mut self) {
+        if self.config.enabled {
+            self.cycle_energy += self.config.gmem_energy;
+            self.summary.gmem_energy += self.config.gmem_energy;
+        }
+    }
+
+    pub fn record_smem(&mut self) {
+        if self.config.enabled {
+            self.cycle_energy += self.config.smem_energy;
+            self.summary.smem_energy += self.config.smem_energy;
+        }
+    }
+
+    /// Clears grants in `grants` beyond what the governor allows this cycle.
+    pub fn throttle(&mut self, now: Cycle, grants: &mut [bool]) {
+        if !self.config.enabled || !self.throttling {
+            return;
+        }
+        let limit = match self.config.mode {
+            ThrottleMode::IssueWidth => self.config.throttled_issue_width,
+            ThrottleMode::DutyCycle => {
+                if now.is_multiple_of(self.config.duty_cycle_period.max(1)) {
+                    return;
+                }
+                0
+            }
+        };
+        // Rotate the survivors so throttling does not starve high warp ids.
+        let n = grants.len();
+        let start = if n == 0 { 0 } else { now as usize % n };
+        let mut kept = 0;
+        for offset in 0..n {
+            let wid = (start + offset) % n;
+            if !grants[wid] {
+                continue;
+            }
+            if kept < limit {
+                kept += 1;
+            } else {
+                grants[wid] = false;
+                self.denied_this_cycle = true;
+                self.summary.throttled_issues = self.summary.throttled_issues.saturating_add(1);
+            }
+        }
+    }
+
+    /// Whether `throttle` held back a warp this cycle.
+    pub fn denied_this_cycle(&self) -> bool {
+        self.denied_this_cycle
+    }
+
+    /// Charges static and issue energy for the cycle, updates the moving
+    /// average and decides whether to throttle the next cycle.
+    pub fn end_cycle(&mut self, issued_warps: u32) {
+        if !self.config.enabled {
+            return;
+        }
+        let issue_energy = self.config.issue_energy * issued_warps as f64;
+        let energy = self.cycle_energy + self.config.static_energy + issue_energy;
+        self.cycle_energy = 0.0;
+        self.summary.cycles = self.summary.cycles.saturating_add(1);
+        self.summary.static_energy += self.config.static_energy;
+        self.summary.issue_energy += issue_energy;
+        if self.throttling {
+            self.summary.throttled_cycles = self.summary.throttled_cycles.saturating_add(1);
+        }
+        self.denied_this_cycle = false;
+
+        self.history.push_back(energy);
+        self.window_energy += energy;
+        if self.history.len() > self.config.window.max(1) {
+            if let Some(old) = self.history.pop_front() {
+                self.window_energy -= old;
+            }
+        }
+        let power = self.window_energy / self.history.len() as f64;
+        self.summary.peak_power = self.summary.peak_power.max(power);
+        self.throttling = self.config.budget > 0.0 && power > self.config.budget;
+    }
+
+    pub fn is_throttling(&self) -> bool {
+        self.throttling
+    }
+
+    pub fn summary(&self) -> PowerSummary {
+        self.summary
+    }
+
+    pub fn clear_stats(&mut self) {
+        self.summary = PowerSummary::default();
+    }
+}
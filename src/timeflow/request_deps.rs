@@ -0,0 +1,283 @@
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::ops::AddAssign;
+
+/// Timing requests a core can order against each other. Each kind has its
+/// own id space.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RequestKind {
+    Gmem,
+    Smem,
+    Dma,
+    Tensor,
+}
+
+impl RequestKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Gmem => "gmem",
+            Self::Smem => "smem",
+            Self::Dma => "dma",
+            Self::Tensor => "tensor",
+        }
+    }
+}
+
+/// One timing request, by kind and id.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+pub struct RequestRef {
+    pub kind: RequestKind,
+    pub id: u64,
+}
+
+impl RequestRef {
+    pub fn new(kind: RequestKind, id: u64) -> Self {
+        Self { kind, id }
+    }
+
+    pub fn gmem(id: u64) -> Self {
+        Self::new(RequestKind::Gmem, id)
+    }
+
+    pub fn smem(id: u64) -> Self {
+        Self::new(RequestKind::Smem, id)
+    }
+
+    pub fn dma(id: u64) -> Self {
+        Self::new(RequestKind::Dma, id)
+    }
+
+    pub fn tensor(id: u64) -> Self {
+        Self::new(RequestKind::Tensor, id)
+    }
+}
+
+impl fmt::Display for RequestRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} request {}", self.kind.name(), self.id)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DependencyError {
+    /// A request declared to wait on itself.
+    SelfDependency(RequestRef),
+    /// The dependency would close a cycle; the requests on it, starting
+    /// with the waiter.
+    Cycle(Vec<RequestRef>),
+}
+
+impl fmt::Display for DependencyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::SelfDependency(request) => write!(f, "{request} cannot wait on itself"),
+            Self::Cycle(path) => {
+                write!(f, "dependency cycle: ")?;
+                for request in path {
+                    write!(f, "{request} -> ")?;
+                }
+                match path.first() {
+                    Some(first) => write!(f, "{first}"),
+                    None => Ok(()),
+                }
+            }
+        }
+    }
+}
+
+impl std::error::Error for DependencyError {}
+
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct DependencyStats {
+    pub declared: u64,
+    /// Declared on a request that had already completed, so nothing waited.
+    pub already_complete: u64,
+    /// Waiters whose last dependency completed.
+    pub released: u64,
+    /// Issue attempts held back by a dependency.
+    pub blocked_issues: u64,
+}
+
+impl AddAssign<&DependencyStats> for DependencyStats {
+    fn add_assign(&mut self, other: &DependencyStats) {
+        self.declared = self.declared.saturating_add(other.declared);
+        self.already_complete = self.already_complete.saturating_add(other.already_complete);
+        self.released = self.released.saturating_add(other.released);
+        self.blocked_issues = self.blocked_issues.saturating_add(other.blocked_issues);
+    }
+}
+
+/// Which requests may not issue until others complete. Requests are
+/// opened when their id is handed out and closed when they complete; a
+/// dependency on a request that is not open is already satisfied.
+#[derive(Debug, Clone, Default)]
+pub struct DependencyTable {
+    open: HashSet<RequestRef>,
+    /// Waiter -> requests it still waits on.
+    blockers: HashMap<RequestRef, HashSet<RequestRef>>,
+    /// Request -> waiters to release when it completes.
+    waiters: HashMap<RequestRef, Vec<RequestRef>>,
+    stats: DependencyStats,
+}
+
+impl DependencyTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Marks `request` as handed out but not yet complete.
+    pub fn open(&mut self, request: RequestRef) {
+        self.open.insert(request);
+    }
+
+    pub fn is_open(&self, request: RequestRef) -> bool {
+        self.open.contains(&request)
+    }
+
+    /// Holds `waiter` until `on` completes. `pending` says whether `on` is
+    /// still outstanding, for requests the table was not told about.
+    pub fn add(
+        &mut self,
+        waiter: RequestRef,
+        on: RequestRef,
+        pending: bool,
+    ) -> Result<(), DependencyError> {
+        if waiter == on {
+            return Err(DependencyError::SelfDependency(waiter));
+        }
+        if let Some(mut path) = self.path(on, waiter) {
+            path.insert(0, waiter);
+            path.pop();
+            return Err(DependencyError::Cycle(path));
+        }
+        self.stats.declared = self.stats.declared.saturating_add(1);
+        if !pending && !self.is_open(on) {
+            self.stats.already_complete = self.stats.already_complete.saturating_add(1);
+            return Ok(());
+        }
+        if self.blockers.entry(waiter).or_default().insert(on) {
+            self.waiters.entry(on).or_default().push(waiter);
+        }
+        Ok(())
+    }
+
+    /// Requests from `from` to `to` along blocker edges, if `from` waits on
+    /// `to` at all.
+    fn path(&self, from: RequestRef, to: RequestRef) -> Option<Vec<RequestRef>> {
+        let mut seen = HashSet::new();
+        let mut stack = vec![vec![from]];
+        while let Some(path) = stack.pop() {
+            let last = *path.last().expect("paths are never empty");
+            if last == to {
+                return Some(path);
+            }
+            if !seen.insert(last) {
+                continue;
+            }
+            for &next in self.blockers.get(&last).into_iter().flatten() {
+                let mut longer = path.clone();
+                longer.push(next);
+                stack.push(longer);
+            }
+        }
+        None
+    }
+
+    pub fn is_blocked(&self, request: RequestRef) -> bool {
+        self.blockers.contains_key(&request)
+    }
+
+    /// Counts an issue attempt `is_blocked` turned away.
+    pub fn record_blocked(&mut self) {
+        self.stats.blocked_issues = self.stats.blocked_issues.saturating_add(1);
+    }
+
+    /// `request` completed. Returns the waiters it was the last blocker of.
+    pub fn complete(&mut self, request: RequestRef) -> Vec<RequestRef> {
+        self.open.remove(&request);
+        let Some(waiters) = self.waiters.remove(&request) else {
+            return Vec::new();
+        };
+        let mut released = Vec::new();
+        for waiter in waiters {
+            let Some(blockers) = self.blockers.get_mut(&waiter) else {
+                continue;
+            };
+            blockers.remove(&request);
+            if blockers.is_empty() {
+                self.blockers.remove(&waiter);
+                released.push(waiter);
+            }
+        }
+        self.stats.released = self.stats.released.saturating_add(released.len() as u64);
+        released
+    }
+
+    pub fn stats(&self) -> DependencyStats {
+        self.stats
+    }
+
+    pub fn clear_stats(&mut self) {
+        self.stats = DependencyStats::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn waiter_is_released_by_its_last_blocker() {
+        let mut table = DependencyTable::new();
+        let (dma, fill, load) = (
+            RequestRef::dma(1),
+            RequestRef::dma(2),
+            RequestRef::tensor(1),
+        );
+        table.open(dma);
+        table.open(fill);
+        table.add(load, dma, false).unwrap();
+        table.add(load, fill, false).unwrap();
+        assert!(table.is_blocked(load));
+
+        assert!(table.complete(dma).is_empty());
+        assert!(table.is_blocked(load));
+        assert_eq!(table.complete(fill), vec![load]);
+        assert!(!table.is_blocked(load));
+
+        // Both are closed now, so a new dependency on either is satisfied.
+        table.add(RequestRef::tensor(2), dma, false).unwrap();
+        assert!(!table.is_blocked(RequestRef::tensor(2)));
+        let stats = table.stats();
+        assert_eq!(
+            (stats.declared, stats.already_complete, stats.released),
+            (3, 1, 1)
+        );
+    }
+
+    #[test]
+    fn cycles_are_rejected() {
+        let mut table = DependencyTable::new();
+        let (a, b, c) = (
+            RequestRef::gmem(1),
+            RequestRef::gmem(2),
+            RequestRef::smem(1),
+        );
+        for request in [a, b, c] {
+            table.open(request);
+        }
+        assert_eq!(
+            table.add(a, a, false),
+            Err(DependencyError::SelfDependency(a))
+        );
+        table.add(b, a, false).unwrap();
+        table.add(c, b, false).unwrap();
+        assert_eq!(
+            table.add(a, c, false),
+            Err(DependencyError::Cycle(vec![a, c, b]))
+        );
+        assert!(!table.is_blocked(a));
+    }
+}
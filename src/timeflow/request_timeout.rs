@@ -0,0 +1,215 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::ops::AddAssign;
+
+use crate::timeq::Cycle;
+
+/// What happens to a request still in flight `cycles` after it issued.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimeoutAction {
+    /// Report it, then forget it: its warp stops waiting, and a completion
+    /// that still turns up later is ignored.
+    #[default]
+    Drop,
+    /// Report it and stop the run.
+    Error,
+}
+
+/// `[lsu.timeout]`: a lifetime for gmem and smem requests, past which a
+/// request that never completed is reported instead of wedging its warp.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(default)]
+pub struct RequestTimeoutConfig {
+    /// Cycles a request may stay in flight. 0 disables the check.
+    pub cycles: u64,
+    pub action: TimeoutAction,
+    /// Record each request's path through the core (LSU, cluster issue,
+    /// bank, completions) so the report can list it.
+    pub history: bool,
+}
+
+/// Gmem and smem request ids are allocated separately.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RequestSpace {
+    Gmem,
+    Smem,
+}
+
+impl RequestSpace {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Gmem => "gmem",
+            Self::Smem => "smem",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct RequestTimeoutStats {
+    pub timed_out: u64,
+    pub dropped: u64,
+    /// Completions of dropped requests that arrived after all.
+    pub late_completions: u64,
+}
+
+impl AddAssign<&RequestTimeoutStats> for RequestTimeoutStats {
+    fn add_assign(&mut self, other: &RequestTimeoutStats) {
+        self.timed_out = self.timed_out.saturating_add(other.timed_out);
+        self.dropped = self.dropped.saturating_add(other.dropped);
+        self.late_completions = self.late_completions.saturating_add(other.late_completions);
+    }
+}
+
+/// A request that outlived `[lsu.timeout]`.
+#[derive(Debug, Clone, Serialize)]
+pub struct TimedOutRequest {
+    pub space: RequestSpace,
+    pub id: u64,
+    pub warp: usize,
+    pub core_id: usize,
+    pub issued_at: Cycle,
+    pub detected_at: Cycle,
+    /// Split parts of the request that had not completed.
+    pub parts_outstanding: usize,
+    /// Empty unless `history` is set.
+    pub history: Vec<(Cycle, String)>,
+}
+
+impl fmt::Display for TimedOutRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "core {} warp {}: {} request {} issued at cycle {} still has {} part(s) in flight at cycle {}",
+            self.core_id,
+            self.warp,
+            self.space.name(),
+            self.id,
+            self.issued_at,
+            self.parts_outstanding,
+            self.detected_at
+        )?;
+        for (cycle, event) in &self.history {
+            write!(f, "\n  @{cycle} {event}")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+pub struct RequestTimeouts {
+    config: RequestTimeoutConfig,
+    history: HashMap<(RequestSpace, u64), Vec<(Cycle, String)>>,
+    /// Requests dropped so far; kept so their late completions can be told
+    /// apart from ones the core never issued.
+    dropped: HashSet<(RequestSpace, u64)>,
+    stats: RequestTimeoutStats,
+}
+
+impl RequestTimeouts {
+    pub fn new(config: RequestTimeoutConfig) -> Self {
+        Self {
+            config,
+            history: HashMap::new(),
+            dropped: HashSet::new(),
+            stats: RequestTimeoutStats::default(),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.config.cycles > 0
+    }
+
+    pub fn action(&self) -> TimeoutAction {
+        self.config.action
+    }
+
+    /// Whether a request issued at `issued_at` has run out of time at `now`.
+    pub fn expired(&self, now: Cycle, issued_at: Cycle) -> bool {
+        self.enabled() && now.saturating_sub(issued_at) >= self.config.cycles
+    }
+
+    pub fn tracks_history(&self) -> bool {
+        self.enabled() && self.config.history
+    }
+
+    /// Appends `event` to the history of request `id`. Callers check
+    /// `tracks_history` before formatting the event.
+    pub fn note(&mut self, now: Cycle, space: RequestSpace, id: u64, event: String) {
+        self.history
+            .entry((space, id))
+            .or_default()
+            .push((now, event));
+    }
+
+    /// The request completed; its history is no longer needed.
+    pub fn forget(&mut self, space: RequestSpace, id: u64) {
+        self.history.remove(&(space, id));
+    }
+
+    /// Counts the timeout and hands back the history recorded for it.
+    pub fn expire(&mut self, space: RequestSpace, id: u64) -> Vec<(Cycle, String)> {
+        self.stats.timed_out = self.stats.timed_out.saturating_add(1);
+        self.history.remove(&(space, id)).unwrap_or_default()
+    }
+
+    pub fn record_drop(&mut self, space: RequestSpace, id: u64) {
+        self.stats.dropped = self.stats.dropped.saturating_add(1);
+        self.dropped.insert((space, id));
+    }
+
+    /// Whether a completion for `id` belongs to a dropped request, counting
+    /// it if so.
+    pub fn is_late(&mut self, space: RequestSpace, id: u64) -> bool {
+        let late = self.dropped.contains(&(space, id));
+        if late {
+            self.stats.late_completions = self.stats.late_completions.saturating_add(1);
+        }
+        late
+    }
+
+    pub fn stats(&self) -> RequestTimeoutStats {
+        self.stats
+    }
+
+    pub fn clear_stats(&mut self) {
+        self.stats = RequestTimeoutStats::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expiry_and_late_completions() {
+        let mut timeouts = RequestTimeouts::new(RequestTimeoutConfig {
+            cycles: 100,
+            history: true,
+            ..RequestTimeoutConfig::default()
+        });
+        assert!(!timeouts.expired(99, 0));
+        assert!(timeouts.expired(100, 0));
+
+        timeouts.note(5, RequestSpace::Gmem, 7, "issue".to_string());
+        timeouts.note(5, RequestSpace::Smem, 7, "issue".to_string());
+        assert_eq!(
+            timeouts.expire(RequestSpace::Gmem, 7),
+            vec![(5, "issue".to_string())]
+        );
+        timeouts.record_drop(RequestSpace::Gmem, 7);
+        assert!(timeouts.is_late(RequestSpace::Gmem, 7));
+        assert!(!timeouts.is_late(RequestSpace::Smem, 7));
+
+        let stats = timeouts.stats();
+        assert_eq!(
+            (stats.timed_out, stats.dropped, stats.late_completions),
+            (1, 1, 1)
+        );
+
+        let off = RequestTimeouts::new(RequestTimeoutConfig::default());
+        assert!(!off.expired(u64::MAX, 0));
+    }
+}
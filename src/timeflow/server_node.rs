@@ -1,11 +1,18 @@
 use std::sync::Arc;
 
 use crate::timeflow::graph::TimedNode;
-use crate::timeq::{Backpressure, Cycle, ServiceRequest, ServiceResult, Ticket, TimedServer};
+use crate::timeq::{
+    Backpressure, Cycle, ServerConfig, ServerStats, ServiceRequest, ServiceResult, Ticket,
+    TimedServer,
+};
 
+type SizeFn<T> = Arc<dyn Fn(&T, u32) -> u32 + Send + Sync>;
+
+#[derive(Clone)]
 pub struct ServerNode<T> {
     name: Arc<str>,
     server: TimedServer<T>,
+    size_fn: Option<SizeFn<T>>,
 }
 
 impl<T> ServerNode<T> {
@@ -13,11 +20,23 @@ impl<T> ServerNode<T> {
         Self {
             name: name.into(),
             server,
+            size_fn: None,
         }
     }
+
+    /// Occupies the server for `size_fn(payload, size)` bytes per request
+    /// instead of the request's own size, for nodes whose service time
+    /// depends on the payload. Downstream links still see the real size.
+    pub fn with_size_fn(
+        mut self,
+        size_fn: impl Fn(&T, u32) -> u32 + Send + Sync + 'static,
+    ) -> Self {
+        self.size_fn = Some(Arc::new(size_fn));
+        self
+    }
 }
 
-impl<T: Send + Sync + 'static> TimedNode<T> for ServerNode<T> {
+impl<T: Clone + Send + Sync + 'static> TimedNode<T> for ServerNode<T> {
     fn name(&self) -> &str {
         &self.name
     }
@@ -27,7 +46,13 @@ impl<T: Send + Sync + 'static> TimedNode<T> for ServerNode<T> {
         now: Cycle,
         request: ServiceRequest<T>,
     ) -> Result<Ticket, Backpressure<T>> {
-        self.server.try_enqueue(now, request)
+        match self.size_fn.as_ref() {
+            Some(size_fn) => {
+                let service_bytes = size_fn(&request.payload, request.size_bytes);
+                self.server.try_enqueue_charged(now, request, service_bytes)
+            }
+            None => self.server.try_enqueue(now, request),
+        }
     }
 
     fn tick(&mut self, now: Cycle) {
@@ -45,4 +70,29 @@ impl<T: Send + Sync + 'static> TimedNode<T> for ServerNode<T> {
     fn outstanding(&self) -> usize {
         self.server.outstanding()
     }
+
+    fn set_server_config(&mut self, config: ServerConfig) -> bool {
+        self.server.set_config(config);
+        true
+    }
+
+    fn reserve_bandwidth(&mut self, now: Cycle, bytes: u32) -> Option<Cycle> {
+        Some(self.server.reserve(now, bytes))
+    }
+
+    fn available_at(&self) -> Option<Cycle> {
+        Some(self.server.available_at())
+    }
+
+    fn server_stats(&self) -> Option<ServerStats> {
+        Some(self.server.stats())
+    }
+
+    fn squash(&mut self, pred: &mut dyn FnMut(&T, &Ticket) -> bool) -> usize {
+        self.server.squash(pred)
+    }
+
+    fn clone_box(&self) -> Box<dyn TimedNode<T>> {
+        Box::new(self.clone())
+    }
 }
@@ -4,6 +4,7 @@ use crate::timeq::{
     TimedServer,
 };
 
+#[derive(Clone)]
 pub struct SimpleTimedQueue<T> {
     enabled: bool,
     server: TimedServer<T>,
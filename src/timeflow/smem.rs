@@ -1,6 +1,7 @@
 use std::collections::VecDeque;
 use std::ops::AddAssign;
 
+use crate::check;
 use crate::timeflow::{
     graph::{FlowGraph, Link},
     server_node::ServerNode,
@@ -93,6 +94,8 @@ pub struct SmemUtilSample {
 pub struct SmemRequest {
     pub id: u64,
     pub warp: usize,
+    /// Kernel the issuing warp belongs to, as on `GmemRequest`.
+    pub context: usize,
     pub addr: u64,
     pub lane_addrs: Option<Vec<u64>>,
     pub bytes: u32,
@@ -107,6 +110,7 @@ impl SmemRequest {
         Self {
             id: 0,
             warp,
+            context: 0,
             addr: 0,
             lane_addrs: None,
             bytes,
@@ -200,6 +204,7 @@ impl Default for SmemFlowConfig {
     }
 }
 
+#[derive(Clone)]
 pub(crate) struct SmemSubgraph {
     serial_node: Option<NodeId>,
     lane_nodes: Vec<NodeId>,
@@ -513,6 +518,14 @@ impl SmemSubgraph {
                 while let Some(result) = node.take_ready(now) {
                     match result.payload {
                         CoreFlowPayload::Smem(request) => {
+                            check!(
+                                Stats,
+                                self.stats
+                                    .last_completion_cycle
+                                    .is_none_or(|last| last <= now),
+                                "smem completion at cycle {now} after one at {:?}",
+                                self.stats.last_completion_cycle
+                            );
                             self.stats.completed = self.stats.completed.saturating_add(1);
                             if request.is_store {
                                 self.stats.write_completed =
@@ -0,0 +1,25 @@
+/// False when built with the `fast` feature, which compiles every stats
+/// sample guarded by a `StatsSwitch` out of the binary.
+pub const STATS_COMPILED: bool = !cfg!(feature = "fast");
+
+/// Runtime switch for per-cycle stats sampling, turned off by `sim.fast`.
+/// Only stats that no timing decision reads may sit behind it.
+#[derive(Debug, Clone, Copy)]
+pub struct StatsSwitch(bool);
+
+impl Default for StatsSwitch {
+    fn default() -> Self {
+        Self(true)
+    }
+}
+
+impl StatsSwitch {
+    pub fn new(enabled: bool) -> Self {
+        Self(enabled)
+    }
+
+    #[inline(always)]
+    pub fn on(self) -> bool {
+        STATS_COMPILED && self.0
+    }
+}
@@ -0,0 +1,319 @@
+use std::collections::{HashMap, VecDeque};
+use std::ops::AddAssign;
+
+use serde::{Deserialize, Serialize};
+
+use crate::timeflow::gmem::GmemRequest;
+use crate::timeq::Cycle;
+
+/// Per-core store buffer between the LSU and gmem. Stores complete once
+/// buffered; same-warp stores to a line merge into one entry, and entries
+/// drain to gmem oldest first.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct StoreBufferConfig {
+    pub enabled: bool,
+    /// Line entries, shared by the core's warps.
+    pub entries: usize,
+    pub drain_per_cycle: usize,
+    /// Cycles an entry stays open for merges before it may drain. Entries
+    /// drain early while the buffer is full or a load or flush waits on them.
+    pub coalesce_window: Cycle,
+    /// Cycles for a load covered by a buffered store to complete from it.
+    pub forward_latency: Cycle,
+}
+
+impl Default for StoreBufferConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            entries: 8,
+            drain_per_cycle: 1,
+            coalesce_window: 4,
+            forward_latency: 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct StoreBufferStats {
+    /// Per-line stores that entered the buffer.
+    pub stores: u64,
+    /// Of those, how many merged into an entry already open for their line.
+    pub merged: u64,
+    /// Entries written to gmem.
+    pub drained: u64,
+    /// Load lines served from the buffer.
+    pub forwarded_loads: u64,
+    /// Load lines and flushes held until overlapping entries drained.
+    pub held: u64,
+    /// Cycles a store waited in the LSU because the buffer was full.
+    pub full_stall_cycles: u64,
+    pub occupancy_sum: u64,
+    pub occupancy_cycles: u64,
+    pub max_occupancy: u64,
+}
+
+impl StoreBufferStats {
+    /// Fraction of buffered stores that merged instead of taking an entry.
+    pub fn coalesce_ratio(&self) -> f64 {
+        if self.stores == 0 {
+            0.0
+        } else {
+            self.merged as f64 / self.stores as f64
+        }
+    }
+
+    pub fn avg_occupancy(&self) -> f64 {
+        if self.occupancy_cycles == 0 {
+            0.0
+        } else {
+            self.occupancy_sum as f64 / self.occupancy_cycles as f64
+        }
+    }
+}
+
+impl AddAssign<&StoreBufferStats> for StoreBufferStats {
+    fn add_assign(&mut self, other: &StoreBufferStats) {
+        self.stores = self.stores.saturating_add(other.stores);
+        self.merged = self.merged.saturating_add(other.merged);
+        self.drained = self.drained.saturating_add(other.drained);
+        self.forwarded_loads = self.forwarded_loads.saturating_add(other.forwarded_loads);
+        self.held = self.held.saturating_add(other.held);
+        self.full_stall_cycles = self
+            .full_stall_cycles
+            .saturating_add(other.full_stall_cycles);
+        self.occupancy_sum = self.occupancy_sum.saturating_add(other.occupancy_sum);
+        self.occupancy_cycles = self.occupancy_cycles.saturating_add(other.occupancy_cycles);
+        self.max_occupancy = self.max_occupancy.max(other.max_occupancy);
+    }
+}
+
+/// What a load line finds in the store buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StoreBufferLookup {
+    /// No buffered store overlaps it.
+    Miss,
+    /// A buffered store writes every byte it reads.
+    Forward,
+    /// A buffered store writes some of its bytes; it has to wait.
+    Partial,
+}
+
+#[derive(Clone)]
+struct Entry {
+    request: GmemRequest,
+    allocated_at: Cycle,
+}
+
+#[derive(Clone)]
+pub struct StoreBuffer {
+    config: StoreBufferConfig,
+    entries: VecDeque<Entry>,
+    /// Load lines and flushes waiting on entries of their warp.
+    held: Vec<GmemRequest>,
+    /// Buffered completions: accepted stores and forwarded loads.
+    completed: Vec<(Cycle, GmemRequest)>,
+    /// Request id -> drained entries whose gmem completion is still due.
+    draining: HashMap<u64, usize>,
+    last_full_stall: Option<Cycle>,
+    stats: StoreBufferStats,
+}
+
+impl StoreBuffer {
+    pub fn new(config: StoreBufferConfig) -> Self {
+        Self {
+            config,
+            entries: VecDeque::new(),
+            held: Vec::new(),
+            completed: Vec::new(),
+            draining: HashMap::new(),
+            last_full_stall: None,
+            stats: StoreBufferStats::default(),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    fn entry_for(&self, warp: usize, line: u64) -> Option<usize> {
+        self.entries
+            .iter()
+            .position(|entry| entry.request.warp == warp && entry.request.line_addr == line)
+    }
+
+    /// Whether every line of a split store fits, merged or in a free entry.
+    pub fn can_accept(&self, lines: &[GmemRequest]) -> bool {
+        let mut needed: Vec<(usize, u64)> = lines
+            .iter()
+            .filter(|line| self.entry_for(line.warp, line.line_addr).is_none())
+            .map(|line| (line.warp, line.line_addr))
+            .collect();
+        needed.sort_unstable();
+        needed.dedup();
+        self.entries.len() + needed.len() <= self.config.entries.max(1)
+    }
+
+    /// Counts a cycle the LSU held a store back for want of an entry.
+    pub fn note_full(&mut self, now: Cycle) {
+        if self.last_full_stall != Some(now) {
+            self.last_full_stall = Some(now);
+            self.stats.full_stall_cycles = self.stats.full_stall_cycles.saturating_add(1);
+        }
+    }
+
+    /// Buffers one line of a store; it completes this cycle.
+    pub fn insert(&mut self, now: Cycle, line: GmemRequest) {
+        self.stats.stores = self.stats.stores.saturating_add(1);
+        match self.entry_for(line.warp, line.line_addr) {
+            Some(idx) => {
+                let entry = &mut self.entries[idx].request;
+                entry.byte_enable = match (entry.byte_enable.take(), &line.byte_enable) {
+                    (Some(mut mask), Some(other)) => {
+                        mask.union(other);
+                        Some(mask)
+                    }
+                    _ => None,
+                };
+                self.stats.merged = self.stats.merged.saturating_add(1);
+            }
+            None => self.entries.push_back(Entry {
+                request: line.clone(),
+                allocated_at: now,
+            }),
+        }
+        let mut done = line;
+        done.forwarded = true;
+        self.completed.push((now, done));
+    }
+
+    /// Checks the bytes `[start, end)` of `load`'s line against the buffered
+    /// stores of its warp. A line outside that range (a strided load) is
+    /// checked whole.
+    pub fn lookup(&self, load: &GmemRequest, start: u64, end: u64) -> StoreBufferLookup {
+        let Some(idx) = self.entry_for(load.warp, load.line_addr) else {
+            return StoreBufferLookup::Miss;
+        };
+        let entry = &self.entries[idx].request;
+        let line = entry.line_addr;
+        let line_end = line + entry.bytes as u64;
+        let (offset, len) = if start < line_end && line < end {
+            let (start, end) = (start.max(line), end.min(line_end));
+            ((start - line) as u32, (end - start) as u32)
+        } else {
+            (0, entry.bytes)
+        };
+        match &entry.byte_enable {
+            None => StoreBufferLookup::Forward,
+            Some(mask) if mask.covers(offset, len) => StoreBufferLookup::Forward,
+            Some(mask) if (offset..offset + len).any(|byte| mask.is_set(byte)) => {
+                StoreBufferLookup::Partial
+            }
+            Some(_) => StoreBufferLookup::Miss,
+        }
+    }
+
+    /// Completes a load line from the buffer after `forward_latency`.
+    pub fn forward(&mut self, now: Cycle, mut load: GmemRequest) {
+        load.forwarded = true;
+        self.stats.forwarded_loads = self.stats.forwarded_loads.saturating_add(1);
+        self.completed
+            .push((now.saturating_add(self.config.forward_latency), load));
+    }
+
+    /// Whether a flush from `warp` has to wait for its buffered stores.
+    pub fn has_entries(&self, warp: usize) -> bool {
+        self.entries.iter().any(|entry| entry.request.warp == warp)
+    }
+
+    /// Whether nothing is buffered, held, or waiting on a gmem completion.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+            && self.held.is_empty()
+            && self.completed.is_empty()
+            && self.draining.is_empty()
+    }
+
+    /// Holds a load line or flush until the entries it waits on drain.
+    pub fn hold(&mut self, request: GmemRequest) {
+        self.stats.held = self.stats.held.saturating_add(1);
+        self.held.push(request);
+    }
+
+    fn waits(&self, request: &GmemRequest) -> bool {
+        if request.kind.is_mem() {
+            self.entry_for(request.warp, request.line_addr).is_some()
+        } else {
+            self.has_entries(request.warp)
+        }
+    }
+
+    /// Drains due entries, then releases held requests that no longer wait
+    /// on any. Both go to gmem in the order returned.
+    pub fn tick(&mut self, now: Cycle) -> Vec<GmemRequest> {
+        let occupancy = self.entries.len() as u64;
+        self.stats.occupancy_sum = self.stats.occupancy_sum.saturating_add(occupancy);
+        self.stats.occupancy_cycles = self.stats.occupancy_cycles.saturating_add(1);
+        self.stats.max_occupancy = self.stats.max_occupancy.max(occupancy);
+
+        let mut out = Vec::new();
+        let pressed = !self.held.is_empty() || self.entries.len() >= self.config.entries.max(1);
+        for _ in 0..self.config.drain_per_cycle.max(1) {
+            let due = self.entries.front().is_some_and(|entry| {
+                pressed
+                    || now
+                        >= entry
+                            .allocated_at
+                            .saturating_add(self.config.coalesce_window)
+            });
+            if !due {
+                break;
+            }
+            let entry = self.entries.pop_front().expect("due entry");
+            *self.draining.entry(entry.request.id).or_insert(0) += 1;
+            self.stats.drained = self.stats.drained.saturating_add(1);
+            out.push(entry.request);
+        }
+
+        let held = std::mem::take(&mut self.held);
+        let (waiting, released): (Vec<_>, Vec<_>) =
+            held.into_iter().partition(|request| self.waits(request));
+        self.held = waiting;
+        out.extend(released);
+        out
+    }
+
+    /// Takes the buffered completions due by `now`.
+    pub fn take_completed(&mut self, now: Cycle) -> Vec<GmemRequest> {
+        let (ready, pending): (Vec<_>, Vec<_>) = std::mem::take(&mut self.completed)
+            .into_iter()
+            .partition(|(ready_at, _)| *ready_at <= now);
+        self.completed = pending;
+        ready.into_iter().map(|(_, request)| request).collect()
+    }
+
+    /// Whether `request` is the gmem completion of a drained entry, whose
+    /// stores already completed when buffered. Consumes it if so.
+    pub fn take_drained(&mut self, request: &GmemRequest) -> bool {
+        if request.is_load || !request.kind.is_mem() {
+            return false;
+        }
+        let Some(count) = self.draining.get_mut(&request.id) else {
+            return false;
+        };
+        *count -= 1;
+        if *count == 0 {
+            self.draining.remove(&request.id);
+        }
+        true
+    }
+
+    pub fn stats(&self) -> StoreBufferStats {
+        self.stats
+    }
+
+    pub fn clear_stats(&mut self) {
+        self.stats = StoreBufferStats::default();
+    }
+}
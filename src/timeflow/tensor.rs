@@ -35,8 +35,12 @@ impl Default for TensorConfig {
     }
 }
 
+#[derive(Clone)]
 pub struct TensorQueue {
-    queue: SimpleTimedQueue<()>,
+    /// Payload: the id given to `try_issue_with_id`, 0 if none.
+    queue: SimpleTimedQueue<u64>,
+    /// Completed jobs as (id, bytes), until `take_finished`.
+    finished: Vec<(u64, u32)>,
     completed: u64,
     mmio_base: u64,
     mmio_size: u64,
@@ -49,6 +53,7 @@ impl TensorQueue {
     pub fn new(config: TensorConfig) -> Self {
         Self {
             queue: SimpleTimedQueue::new(config.enabled, config.queue),
+            finished: Vec::new(),
             completed: 0,
             mmio_base: config.mmio_base,
             mmio_size: config.mmio_size,
@@ -59,9 +64,23 @@ impl TensorQueue {
     }
 
     pub fn try_issue(&mut self, now: Cycle, bytes: u32) -> Result<Ticket, TensorReject> {
-        let res = self.queue.try_issue(now, (), bytes);
+        self.try_issue_with_id(now, 0, bytes)
+    }
+
+    /// Like `try_issue`, but tags the job with `id` for `take_finished`.
+    pub fn try_issue_with_id(
+        &mut self,
+        now: Cycle,
+        id: u64,
+        bytes: u32,
+    ) -> Result<Ticket, TensorReject> {
+        let res = self.queue.try_issue(now, id, bytes);
         if res.is_ok() {
             self.bytes_issued = self.bytes_issued.saturating_add(bytes as u64);
+            // A disabled queue finishes jobs as soon as they issue.
+            if !self.queue.is_enabled() {
+                self.finished.push((id, bytes));
+            }
         }
         match res {
             Ok(ticket) => Ok(ticket),
@@ -72,12 +91,18 @@ impl TensorQueue {
     pub fn tick(&mut self, now: Cycle) {
         self.queue.tick_with_service_result(now, |result| {
             self.completed = self.completed.saturating_add(1);
+            self.finished
+                .push((result.payload, result.ticket.size_bytes()));
             self.bytes_completed = self
                 .bytes_completed
                 .saturating_add(result.ticket.size_bytes() as u64);
         });
     }
 
+    pub fn take_finished(&mut self) -> Vec<(u64, u32)> {
+        std::mem::take(&mut self.finished)
+    }
+
     pub fn bytes_issued(&self) -> u64 {
         self.bytes_issued
     }
@@ -94,6 +119,12 @@ impl TensorQueue {
         self.completed
     }
 
+    pub fn clear_stats(&mut self) {
+        self.completed = 0;
+        self.bytes_issued = 0;
+        self.bytes_completed = 0;
+    }
+
     pub fn matches_mmio(&self, addr: u64) -> bool {
         if !self.queue.is_enabled() || self.mmio_size == 0 {
             return false;
@@ -0,0 +1,231 @@
+use std::fmt;
+
+use serde::Deserialize;
+
+/// A subsystem `CoreGraph` ticks once per cycle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TickUnit {
+    Icache,
+    Lsu,
+    OperandFetch,
+    Dma,
+    Tensor,
+    Execute,
+    Neutrino,
+    CopyEngine,
+    /// The L1/L2/DRAM graph shared by every core. Only the first core to
+    /// reach it in a cycle ticks it.
+    ClusterGmem,
+    /// The core's own flow graph.
+    Graph,
+    /// Hands what the graph delivered to the subgraphs waiting on it, e.g.
+    /// smem completions.
+    GraphCompletions,
+    Writeback,
+    Fence,
+}
+
+/// Where in `CoreTimingModel::tick` a unit is ticked:
+///
+/// - `Front`: before the core issues this cycle's LSU, gmem and smem
+///   requests and collects shared gmem completions.
+/// - `Graph`: after those issues, before completions are handed to
+///   writeback.
+/// - `Back`: after completions are queued for writeback, before writeback
+///   results and finished fences are popped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TickStage {
+    Front,
+    Graph,
+    Back,
+}
+
+impl TickStage {
+    pub const ALL: [TickStage; 3] = [TickStage::Front, TickStage::Graph, TickStage::Back];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            TickStage::Front => "front",
+            TickStage::Graph => "graph",
+            TickStage::Back => "back",
+        }
+    }
+}
+
+/// How a unit may be scheduled.
+struct TickUnitSpec {
+    stage: TickStage,
+    /// The unit has to stay in `stage`.
+    pinned: bool,
+    /// Units that must tick earlier in the cycle.
+    after: &'static [TickUnit],
+}
+
+impl TickUnit {
+    pub const ALL: [TickUnit; 13] = [
+        TickUnit::Icache,
+        TickUnit::Lsu,
+        TickUnit::OperandFetch,
+        TickUnit::Dma,
+        TickUnit::Tensor,
+        TickUnit::Execute,
+        TickUnit::Neutrino,
+        TickUnit::CopyEngine,
+        TickUnit::ClusterGmem,
+        TickUnit::Graph,
+        TickUnit::GraphCompletions,
+        TickUnit::Writeback,
+        TickUnit::Fence,
+    ];
+
+    fn spec(self) -> TickUnitSpec {
+        let front = TickUnitSpec {
+            stage: TickStage::Front,
+            pinned: false,
+            after: &[],
+        };
+        match self {
+            // The graph stage is defined by the graph tick.
+            TickUnit::Graph => TickUnitSpec {
+                stage: TickStage::Graph,
+                pinned: true,
+                after: &[],
+            },
+            // Completions are collected the cycle the graph delivers them.
+            TickUnit::GraphCompletions => TickUnitSpec {
+                stage: TickStage::Graph,
+                pinned: true,
+                after: &[TickUnit::Graph],
+            },
+            TickUnit::Writeback | TickUnit::Fence => TickUnitSpec {
+                stage: TickStage::Back,
+                ..front
+            },
+            _ => front,
+        }
+    }
+
+    pub fn default_stage(self) -> TickStage {
+        self.spec().stage
+    }
+}
+
+impl fmt::Display for TickUnit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            TickUnit::Icache => "icache",
+            TickUnit::Lsu => "lsu",
+            TickUnit::OperandFetch => "operand_fetch",
+            TickUnit::Dma => "dma",
+            TickUnit::Tensor => "tensor",
+            TickUnit::Execute => "execute",
+            TickUnit::Neutrino => "neutrino",
+            TickUnit::CopyEngine => "copy_engine",
+            TickUnit::ClusterGmem => "cluster_gmem",
+            TickUnit::Graph => "graph",
+            TickUnit::GraphCompletions => "graph_completions",
+            TickUnit::Writeback => "writeback",
+            TickUnit::Fence => "fence",
+        };
+        f.write_str(name)
+    }
+}
+
+/// `[tick_order]`: the units ticked in each stage, in order. A stage left
+/// out keeps its default list; together the lists must name every unit
+/// exactly once and respect each unit's declared constraints. Structural:
+/// timing phases cannot change it mid-run.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "TickOrderLists")]
+pub struct TickOrderConfig {
+    front: Vec<TickUnit>,
+    graph: Vec<TickUnit>,
+    back: Vec<TickUnit>,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+struct TickOrderLists {
+    front: Vec<TickUnit>,
+    graph: Vec<TickUnit>,
+    back: Vec<TickUnit>,
+}
+
+impl Default for TickOrderLists {
+    fn default() -> Self {
+        let stage = |stage| {
+            TickUnit::ALL
+                .into_iter()
+                .filter(|unit| unit.default_stage() == stage)
+                .collect()
+        };
+        Self {
+            front: stage(TickStage::Front),
+            graph: stage(TickStage::Graph),
+            back: stage(TickStage::Back),
+        }
+    }
+}
+
+impl Default for TickOrderConfig {
+    fn default() -> Self {
+        let lists = TickOrderLists::default();
+        Self {
+            front: lists.front,
+            graph: lists.graph,
+            back: lists.back,
+        }
+    }
+}
+
+impl TryFrom<TickOrderLists> for TickOrderConfig {
+    type Error = String;
+
+    fn try_from(lists: TickOrderLists) -> Result<Self, String> {
+        TickOrderConfig::new(lists.front, lists.graph, lists.back)
+    }
+}
+
+impl TickOrderConfig {
+    /// Checks the order against the units' declarations.
+    pub fn new(
+        front: Vec<TickUnit>,
+        graph: Vec<TickUnit>,
+        back: Vec<TickUnit>,
+    ) -> Result<Self, String> {
+        let config = Self { front, graph, back };
+        let mut seen = Vec::new();
+        for stage in TickStage::ALL {
+            for &unit in config.stage(stage) {
+                if seen.contains(&unit) {
+                    return Err(format!("tick_order lists {unit} more than once"));
+                }
+                let spec = unit.spec();
+                if spec.pinned && spec.stage != stage {
+                    return Err(format!(
+                        "tick_order: {unit} must stay in the {} stage",
+                        spec.stage.name()
+                    ));
+                }
+                if let Some(dep) = spec.after.iter().find(|dep| !seen.contains(dep)) {
+                    return Err(format!("tick_order: {unit} must tick after {dep}"));
+                }
+                seen.push(unit);
+            }
+        }
+        if let Some(missing) = TickUnit::ALL.iter().find(|unit| !seen.contains(unit)) {
+            return Err(format!("tick_order does not schedule {missing}"));
+        }
+        Ok(config)
+    }
+
+    /// Units to tick in `stage`, in order.
+    pub fn stage(&self, stage: TickStage) -> &[TickUnit] {
+        match stage {
+            TickStage::Front => &self.front,
+            TickStage::Graph => &self.graph,
+            TickStage::Back => &self.back,
+        }
+    }
+}
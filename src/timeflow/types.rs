@@ -1,5 +1,8 @@
+use crate::timeflow::graph::RequestPath;
 use crate::timeflow::{gmem::GmemRequest, smem::SmemRequest};
 use crate::timeq::Cycle;
+use std::any::Any;
+use std::fmt;
 
 pub type NodeId = usize;
 pub type LinkId = usize;
@@ -8,12 +11,90 @@ pub type LinkId = usize;
 pub enum CoreFlowPayload {
     Gmem(GmemRequest),
     Smem(SmemRequest),
+    /// A request type defined outside this crate. Built-in nodes route it
+    /// like any payload they do not recognize; route functions and custom
+    /// nodes get at it with `downcast_ref`.
+    Custom(Box<dyn CustomRequest>),
+}
+
+/// A request type that can travel through a `FlowGraph<CoreFlowPayload>`.
+/// Implemented for every `Clone + Debug + Send + Sync + 'static` type.
+pub trait CustomRequest: Any + fmt::Debug + Send + Sync {
+    fn clone_boxed(&self) -> Box<dyn CustomRequest>;
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+    fn into_any(self: Box<Self>) -> Box<dyn Any>;
+}
+
+impl<T: Any + Clone + fmt::Debug + Send + Sync> CustomRequest for T {
+    fn clone_boxed(&self) -> Box<dyn CustomRequest> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn into_any(self: Box<Self>) -> Box<dyn Any> {
+        self
+    }
+}
+
+impl Clone for Box<dyn CustomRequest> {
+    fn clone(&self) -> Self {
+        (**self).clone_boxed()
+    }
+}
+
+impl CoreFlowPayload {
+    pub fn custom<T: CustomRequest>(request: T) -> Self {
+        Self::Custom(Box::new(request))
+    }
+
+    /// The custom request, if this payload carries one of type `T`.
+    pub fn downcast_ref<T: CustomRequest>(&self) -> Option<&T> {
+        match self {
+            Self::Custom(request) => (**request).as_any().downcast_ref(),
+            _ => None,
+        }
+    }
+
+    pub fn downcast_mut<T: CustomRequest>(&mut self) -> Option<&mut T> {
+        match self {
+            Self::Custom(request) => (**request).as_any_mut().downcast_mut(),
+            _ => None,
+        }
+    }
+
+    /// The hops a sampled gmem request records; a `PathAccessor` for
+    /// `FlowGraph::set_path_accessor`.
+    pub fn gmem_path(&mut self) -> Option<&mut RequestPath> {
+        match self {
+            Self::Gmem(request) => request.path.as_mut(),
+            _ => None,
+        }
+    }
+
+    /// Takes the custom request out; `None` if the payload does not carry
+    /// a `T`. Check with `downcast_ref` first to keep such payloads.
+    pub fn into_custom<T: CustomRequest>(self) -> Option<T> {
+        match self {
+            Self::Custom(request) => request.into_any().downcast().ok().map(|request| *request),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum RejectReason {
     QueueFull,
     Busy,
+    /// Too many flushes already in flight at the level.
+    FlushLimit,
 }
 
 #[derive(Debug, Clone)]
@@ -143,3 +143,48 @@ fn barrier_with_different_ids_interleaved() {
     }
     assert_eq!(released.len(), 4);
 }
+
+#[test]
+fn release_bandwidth_and_refill_penalty_stagger_warps() {
+    let mut cfg = BarrierConfig::default();
+    cfg.enabled = true;
+    cfg.expected_warps = Some(3);
+    cfg.queue.base_latency = 0;
+    cfg.release_per_cycle = 1;
+    cfg.refill_penalty = 2;
+
+    let mut barrier = BarrierManager::new(cfg, 3);
+    assert!(barrier.arrive(0, 0, 5).is_none());
+    assert!(barrier.arrive(1, 1, 5).is_none());
+    let release_at = barrier.arrive(2, 2, 5).expect("barrier should schedule");
+
+    let mut released_at = Vec::new();
+    for cycle in release_at..release_at + 8 {
+        for warp in barrier.tick(cycle).unwrap_or_default() {
+            released_at.push((warp, cycle));
+        }
+    }
+    assert!(!barrier.has_pending_release());
+    assert_eq!(
+        released_at,
+        [
+            (0, release_at + 2),
+            (1, release_at + 3),
+            (2, release_at + 4)
+        ]
+    );
+
+    let stats = barrier.stats();
+    assert_eq!(stats.release_events, 1);
+    assert_eq!(stats.release_throttle_cycles, 3);
+    assert_eq!(stats.refill_penalty_cycles, 6);
+    let per_warp: Vec<u64> = (0..3)
+        .map(|warp| released_at[warp].1 - warp as u64)
+        .collect();
+    assert_eq!(stats.stall_cycles_per_warp, per_warp);
+    assert_eq!(
+        stats.stall_cycles_per_barrier.get(&5).copied(),
+        Some(per_warp.iter().sum())
+    );
+    assert_eq!(stats.max_stall_cycles, release_at + 2);
+}
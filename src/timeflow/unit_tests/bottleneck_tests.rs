@@ -0,0 +1,128 @@
+use crate::timeflow::bottleneck::config_keys;
+// The report is built from sampled graph stats, which the `fast` feature
+// compiles out.
+#[cfg(not(feature = "fast"))]
+use crate::{
+    timeflow::bottleneck::BottleneckReport,
+    timeflow::graph::{FlowGraph, Link},
+    timeflow::server_node::ServerNode,
+    timeq::{ServerConfig, ServiceRequest, TimedServer},
+};
+
+#[cfg(not(feature = "fast"))]
+fn server(name: &str, queue_capacity: usize) -> ServerNode<u32> {
+    ServerNode::new(
+        name,
+        TimedServer::new(ServerConfig {
+            base_latency: 1,
+            bytes_per_cycle: 4,
+            queue_capacity,
+            ..ServerConfig::default()
+        }),
+    )
+}
+
+/// `src -> a -> b -> sink`, where nothing drains `sink`, so backpressure
+/// fills every queue back to `src`.
+#[cfg(not(feature = "fast"))]
+fn stalled_chain(cycles: u64) -> FlowGraph<u32> {
+    let mut graph = FlowGraph::new();
+    let src = graph.add_node(server("src", 8));
+    let a = graph.add_node(server("a", 1));
+    let b = graph.add_node(server("b", 1));
+    let sink = graph.add_node(server("sink", 1));
+    graph.connect(src, a, "src->a", Link::new(1));
+    graph.connect(a, b, "a->b", Link::new(1));
+    graph.connect(b, sink, "b->sink", Link::new(1));
+    for cycle in 0..cycles {
+        let _ = graph.try_put(src, cycle, ServiceRequest::new(cycle as u32, 4));
+        graph.tick(cycle);
+    }
+    graph
+}
+
+#[cfg(not(feature = "fast"))]
+#[test]
+fn backpressure_builds_a_sustained_full_chain() {
+    let graph = stalled_chain(40);
+    let chain = graph
+        .full_chains()
+        .iter()
+        .max_by_key(|chain| chain.nodes.len())
+        .expect("stalled graph has a full chain");
+    let names: Vec<&str> = chain
+        .nodes
+        .iter()
+        .map(|&node| graph.node_name(node))
+        .collect();
+    assert_eq!(names, ["a", "b", "sink"]);
+    assert!(chain.cycles > 20, "{chain:?}");
+
+    let sink = graph.node_activity(3);
+    assert!(sink.full_cycles > 20);
+    assert!(graph.node_backpressure(3) > 0);
+    assert_eq!(graph.node_activity(0).full_cycles, 0);
+    assert_eq!(graph.sampled_cycles(), 40);
+}
+
+#[cfg(not(feature = "fast"))]
+#[test]
+fn report_ranks_nodes_and_prints_chains() {
+    let graph = stalled_chain(40);
+    let report = BottleneckReport::from_graph(&graph, "core0_");
+    assert!(report
+        .nodes
+        .iter()
+        .all(|node| node.node.starts_with("core0_")));
+    for pair in report.nodes.windows(2) {
+        let rank = |node: &crate::timeflow::NodeBottleneck| {
+            (node.busy_cycles, node.queue_full_rejects, node.backpressure)
+        };
+        assert!(rank(&pair[0]) >= rank(&pair[1]));
+    }
+    assert_eq!(report.chains[0].nodes, ["core0_a", "core0_b", "core0_sink"]);
+
+    let lines = report.format_lines();
+    assert!(lines
+        .iter()
+        .any(|line| line.starts_with("full chain core0_a -> core0_b -> core0_sink for ")));
+    assert!(BottleneckReport::default().format_lines().is_empty());
+}
+
+#[test]
+fn config_keys_name_the_knobs_of_built_nodes() {
+    assert_eq!(
+        config_keys("l2_mshr_3"),
+        [
+            "gmem.levels[2].mshr",
+            "gmem.levels[2].mshr_capacity",
+            "gmem.levels[2].banks"
+        ]
+    );
+    assert_eq!(
+        config_keys("cluster1_l1_wb_0"),
+        ["gmem.levels[1].writeback", "gmem.levels[1].banks"]
+    );
+    assert_eq!(
+        config_keys("cluster0_core2_l0d_tag"),
+        ["gmem.levels[0].tag"]
+    );
+    assert_eq!(
+        config_keys("cluster0_l1_flush_gate"),
+        ["gmem.nodes.l1_flush_gate"]
+    );
+    assert_eq!(
+        config_keys("dram_1"),
+        ["gmem.nodes.dram", "gmem.dram.channels"]
+    );
+    assert_eq!(config_keys("die2_link_in"), ["gmem.remote.link"]);
+    assert_eq!(
+        config_keys("cluster0_core1_lsu_global_ldq_w3"),
+        ["lsu.queues.global_ldq", "lsu.queues.sharing"]
+    );
+    assert_eq!(
+        config_keys("smem_bank_r_2"),
+        ["smem.bank", "smem.num_banks"]
+    );
+    assert!(config_keys("mystery").is_empty());
+}
@@ -44,3 +44,16 @@ fn single_set_single_way_cache() {
     assert!(!tags.probe(1));
     assert!(tags.probe(2));
 }
+
+#[test]
+fn fill_reports_dirty_victim() {
+    let mut tags = CacheTagArray::new(1, 1);
+    tags.fill(0);
+    assert!(!tags.fill_evicts_dirty(1));
+    assert!(tags.mark_dirty(0));
+    assert!(!tags.mark_dirty(7));
+    assert!(tags.fill_evicts_dirty(1));
+    assert!(!tags.fill_evicts_dirty(0));
+    assert_eq!(tags.fill(1), Some(0));
+    assert_eq!(tags.fill(2), None);
+}
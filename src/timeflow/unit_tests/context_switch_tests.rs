@@ -0,0 +1,63 @@
+use crate::timeflow::context_switch::{ContextSwitchConfig, WarpContexts};
+
+fn contexts(slots: usize, idle_threshold: u64) -> WarpContexts {
+    WarpContexts::new(
+        ContextSwitchConfig {
+            enabled: true,
+            slots,
+            context_bytes: 256,
+            idle_threshold,
+            ..ContextSwitchConfig::default()
+        },
+        4,
+    )
+}
+
+#[test]
+fn warps_beyond_the_slots_wait_for_one() {
+    let mut contexts = contexts(2, 8);
+    let admitted = contexts.admit(0, &[true; 4]);
+    assert_eq!(admitted, [true, true, false, false]);
+    assert!(contexts.denied_this_cycle());
+    assert_eq!(contexts.stats().switch_ins, 2);
+    assert_eq!(contexts.stats().overhead_cycles, 0);
+    assert_eq!(contexts.stats().slot_wait_cycles, 2);
+
+    contexts.release(0);
+    let admitted = contexts.admit(1, &[false, true, true, true]);
+    assert_eq!(admitted, [false, true, true, false]);
+}
+
+#[test]
+fn idle_warp_is_swapped_out_and_restored_later() {
+    // 256 bytes over a 64 B/cycle smem path: 4 busy cycles plus 4 latency.
+    let mut contexts = contexts(1, 8);
+    assert!(contexts.admit(0, &[true, false, false, false])[0]);
+
+    // Warp 0 goes idle; warp 1 cannot take its slot until the threshold.
+    assert!(!contexts.admit(4, &[false, true, false, false])[1]);
+    assert_eq!(contexts.stats().saves, 0);
+    assert!(!contexts.admit(8, &[false, true, false, false])[1]);
+    assert_eq!(contexts.stats().saves, 1);
+    assert!(contexts.is_resident(1));
+    assert!(!contexts.is_resident(0));
+    // Warp 1 never ran, so only the save is charged.
+    assert!(!contexts.admit(15, &[false, true, false, false])[1]);
+    assert!(contexts.admit(16, &[false, true, false, false])[1]);
+
+    // Warp 0 comes back after warp 1 idles: save 1, then restore 0.
+    assert!(!contexts.admit(40, &[true, false, false, false])[0]);
+    let stats = contexts.stats();
+    assert_eq!((stats.saves, stats.restores), (2, 1));
+    assert!(!contexts.admit(51, &[true, false, false, false])[0]);
+    assert!(contexts.admit(52, &[true, false, false, false])[0]);
+    assert_eq!(contexts.stats().bytes, 3 * 256);
+    assert_eq!(contexts.stats().overhead_cycles, 8 + 12);
+}
+
+#[test]
+fn disabled_admits_every_eligible_warp() {
+    let mut contexts = WarpContexts::new(ContextSwitchConfig::default(), 4);
+    assert_eq!(contexts.admit(0, &[true; 4]), [true; 4]);
+    assert_eq!(contexts.stats().switch_ins, 0);
+}
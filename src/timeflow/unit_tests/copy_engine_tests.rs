@@ -0,0 +1,99 @@
+use crate::timeflow::copy_engine::{contiguous_span, CopyEngine, CopyEngineConfig, CopyStreamKind};
+
+fn enabled_config() -> CopyEngineConfig {
+    let mut cfg = CopyEngineConfig::default();
+    cfg.enabled = true;
+    cfg.detect_after = 2;
+    cfg
+}
+
+#[test]
+fn contiguous_span_requires_dense_lanes() {
+    assert_eq!(
+        Some((0x100, 16)),
+        contiguous_span(&[0x10c, 0x100, 0x108, 0x104], 4)
+    );
+    assert_eq!(None, contiguous_span(&[0x100, 0x108], 4));
+    assert_eq!(Some((0x40, 4)), contiguous_span(&[0x40], 4));
+}
+
+#[test]
+fn copy_loop_is_detected_after_threshold() {
+    let mut engine = CopyEngine::new(enabled_config(), 1);
+    let mut kinds = Vec::new();
+    for i in 0..3u64 {
+        kinds.push(engine.observe(0, true, Some((0x1000 + i * 64, 64))));
+        kinds.push(engine.observe(0, false, Some((0x8000 + i * 64, 64))));
+    }
+    assert_eq!(
+        vec![
+            None,
+            None,
+            None,
+            Some(CopyStreamKind::Copy),
+            Some(CopyStreamKind::Copy),
+            Some(CopyStreamKind::Copy),
+        ],
+        kinds
+    );
+    assert_eq!(1, engine.stats().copy_streams);
+}
+
+#[test]
+fn store_only_stream_is_a_set_and_replays_do_not_break_it() {
+    let mut engine = CopyEngine::new(enabled_config(), 1);
+    assert_eq!(None, engine.observe(0, false, Some((0x2000, 64))));
+    assert_eq!(None, engine.observe(0, false, Some((0x2000, 64))));
+    assert_eq!(
+        Some(CopyStreamKind::Set),
+        engine.observe(0, false, Some((0x2040, 64)))
+    );
+    assert_eq!(None, engine.observe(0, false, None));
+    assert_eq!(1, engine.stats().set_streams);
+}
+
+#[test]
+fn hint_skips_detection() {
+    let mut cfg = enabled_config();
+    cfg.hint_csr_addrs = vec![0x7c8];
+    let mut engine = CopyEngine::new(cfg, 2);
+    assert!(engine.matches_hint_csr(0x7c8));
+    engine.hint(1);
+    assert_eq!(
+        Some(CopyStreamKind::Set),
+        engine.observe(1, false, Some((0x3000, 64)))
+    );
+    assert_eq!(None, engine.observe(0, false, Some((0x3000, 64))));
+}
+
+#[test]
+fn accesses_within_a_burst_share_its_completion() {
+    let mut cfg = enabled_config();
+    cfg.burst_bytes = 256;
+    cfg.queue.base_latency = 4;
+    cfg.queue.bytes_per_cycle = 64;
+    let mut engine = CopyEngine::new(cfg, 1);
+
+    let first = engine
+        .try_accelerate(0, 0, true, 0x1000, 64)
+        .expect("engine accepts burst");
+    let second = engine
+        .try_accelerate(1, 0, true, 0x1040, 64)
+        .expect("covered by burst");
+    assert_eq!(first, second);
+    engine.record(64, true);
+    engine.record(64, false);
+
+    let stats = engine.stats();
+    assert_eq!(1, stats.bursts);
+    assert_eq!(256, stats.burst_bytes);
+    assert_eq!(128, stats.eligible_bytes);
+    assert_eq!(64, stats.accelerated_bytes);
+}
+
+#[test]
+fn disabled_engine_never_accelerates() {
+    let mut engine = CopyEngine::new(CopyEngineConfig::default(), 1);
+    assert_eq!(None, engine.try_accelerate(0, 0, false, 0x1000, 64));
+    assert_eq!(0, engine.stats().bursts);
+}
@@ -0,0 +1,119 @@
+use crate::timeflow::crossbar::{CrossbarConfig, CrossbarNode};
+use crate::timeflow::gmem::{ClusterGmemGraph, GmemFlowConfig, GmemRequest};
+use crate::timeflow::graph::TimedNode;
+use crate::timeq::{Backpressure, Cycle, ServiceRequest};
+
+/// Payloads are `(port, bank)` pairs.
+fn crossbar(config: CrossbarConfig) -> CrossbarNode<(usize, usize)> {
+    CrossbarNode::new(
+        "xbar",
+        config,
+        2,
+        2,
+        |&(port, _)| Some(port),
+        |&(_, bank)| Some(bank),
+    )
+}
+
+fn config() -> CrossbarConfig {
+    CrossbarConfig {
+        enabled: true,
+        port_bytes_per_cycle: 64,
+        bank_bytes_per_cycle: 64,
+        latency: 0,
+        port_queue_capacity: 2,
+    }
+}
+
+fn run(node: &mut CrossbarNode<(usize, usize)>, cycles: Cycle) -> Vec<((usize, usize), Cycle)> {
+    let mut done = Vec::new();
+    for cycle in 1..=cycles {
+        node.tick(cycle);
+        while let Some(result) = node.take_ready(cycle) {
+            done.push((result.payload, cycle));
+        }
+    }
+    done
+}
+
+#[test]
+fn same_bank_requests_serialize() {
+    let mut node = crossbar(config());
+    node.try_put(0, ServiceRequest::new((0, 1), 64)).unwrap();
+    node.try_put(0, ServiceRequest::new((1, 1), 64)).unwrap();
+    let done = run(&mut node, 4);
+    assert_eq!(done, vec![((0, 1), 1), ((1, 1), 2)]);
+
+    let stats = node.stats_handle();
+    let stats = stats.read().unwrap();
+    assert_eq!(stats.ports[0].conflict_cycles, 0);
+    assert_eq!(stats.ports[1].conflict_cycles, 1);
+    assert_eq!(stats.bank_grants, vec![0, 2]);
+}
+
+#[test]
+fn different_banks_proceed_in_parallel() {
+    let mut node = crossbar(config());
+    node.try_put(0, ServiceRequest::new((0, 0), 64)).unwrap();
+    node.try_put(0, ServiceRequest::new((1, 1), 64)).unwrap();
+    let done = run(&mut node, 4);
+    assert_eq!(done, vec![((0, 0), 1), ((1, 1), 1)]);
+
+    let stats = node.stats_handle();
+    let stats = stats.read().unwrap();
+    assert!(stats.ports.iter().all(|port| port.conflict_cycles == 0));
+}
+
+#[test]
+fn port_bandwidth_and_queue_capacity_limit_one_port() {
+    let mut node = crossbar(CrossbarConfig {
+        port_bytes_per_cycle: 32,
+        ..config()
+    });
+    node.try_put(0, ServiceRequest::new((0, 0), 64)).unwrap();
+    node.try_put(0, ServiceRequest::new((0, 1), 64)).unwrap();
+    assert!(matches!(
+        node.try_put(0, ServiceRequest::new((0, 0), 64)),
+        Err(Backpressure::QueueFull { capacity: 2, .. })
+    ));
+    // A 64-byte request holds a 32 B/cycle port for two cycles.
+    let done = run(&mut node, 4);
+    assert_eq!(done, vec![((0, 0), 1), ((0, 1), 3)]);
+
+    let stats = node.stats_handle();
+    let stats = stats.read().unwrap();
+    assert_eq!(stats.ports[0].grants, 2);
+    assert_eq!(stats.ports[0].bytes, 128);
+    assert_eq!(stats.ports[0].port_busy_cycles, 1);
+    assert_eq!(stats.ports[0].queue_full_rejects, 1);
+}
+
+#[test]
+fn cluster_requests_pass_through_the_crossbar() {
+    let mut cfg = GmemFlowConfig::zeroed();
+    cfg.policy.l0_enabled = false;
+    cfg.crossbar = config();
+    let mut cluster = ClusterGmemGraph::new(cfg, 1, 2);
+    for core_id in 0..2 {
+        let mut req = GmemRequest::new(0, 16, 0xF, true);
+        req.addr = 0x4000 + core_id as u64 * 0x1000;
+        cluster.issue(core_id, 0, req).unwrap();
+    }
+
+    let mut done = [false; 2];
+    for cycle in 0..200 {
+        cluster.tick(cycle);
+        for (core_id, done) in done.iter_mut().enumerate() {
+            if cluster.pop_completion(core_id).is_some() {
+                *done = true;
+            }
+        }
+    }
+    assert_eq!(done, [true, true]);
+    for core_id in 0..2 {
+        assert_eq!(cluster.crossbar_stats(core_id).grants, 1);
+    }
+    cluster.clear_stats(0);
+    assert_eq!(cluster.crossbar_stats(0).grants, 0);
+    assert_eq!(cluster.crossbar_stats(1).grants, 1);
+}
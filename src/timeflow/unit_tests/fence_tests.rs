@@ -1,4 +1,6 @@
-use crate::timeflow::fence::{FenceConfig, FenceQueue, FenceRequest};
+use crate::timeflow::fence::{
+    decode_fence, FenceConfig, FenceOrder, FenceQueue, FenceRequest, FenceScope,
+};
 
 #[test]
 fn fence_queue_delays_release() {
@@ -8,15 +10,7 @@ fn fence_queue_delays_release() {
     cfg.queue.queue_capacity = 1;
     let mut fence = FenceQueue::new(cfg);
 
-    assert!(fence
-        .try_issue(
-            0,
-            FenceRequest {
-                warp: 0,
-                request_id: 1
-            }
-        )
-        .is_ok());
+    assert!(fence.try_issue(0, FenceRequest::new(0, 1)).is_ok());
     fence.tick(1);
     assert!(fence.pop_ready().is_none());
     fence.tick(2);
@@ -28,15 +22,7 @@ fn disabled_fence_immediate() {
     let mut cfg = FenceConfig::default();
     cfg.enabled = false;
     let mut fence = FenceQueue::new(cfg);
-    fence
-        .try_issue(
-            5,
-            FenceRequest {
-                warp: 0,
-                request_id: 1,
-            },
-        )
-        .unwrap();
+    fence.try_issue(5, FenceRequest::new(0, 1)).unwrap();
     assert!(fence.pop_ready().is_some());
 }
 
@@ -47,23 +33,9 @@ fn queue_full_rejects() {
     cfg.queue.queue_capacity = 1;
     let mut fence = FenceQueue::new(cfg);
 
-    fence
-        .try_issue(
-            0,
-            FenceRequest {
-                warp: 0,
-                request_id: 1,
-            },
-        )
-        .unwrap();
+    fence.try_issue(0, FenceRequest::new(0, 1)).unwrap();
     let err = fence
-        .try_issue(
-            0,
-            FenceRequest {
-                warp: 0,
-                request_id: 2,
-            },
-        )
+        .try_issue(0, FenceRequest::new(0, 2))
         .expect_err("queue should be full");
     assert_eq!(
         crate::timeflow::fence::FenceRejectReason::QueueFull,
@@ -79,24 +51,8 @@ fn multiple_fences_queue_fifo() {
     cfg.queue.queue_capacity = 4;
     let mut fence = FenceQueue::new(cfg);
 
-    fence
-        .try_issue(
-            0,
-            FenceRequest {
-                warp: 0,
-                request_id: 1,
-            },
-        )
-        .unwrap();
-    fence
-        .try_issue(
-            0,
-            FenceRequest {
-                warp: 1,
-                request_id: 2,
-            },
-        )
-        .unwrap();
+    fence.try_issue(0, FenceRequest::new(0, 1)).unwrap();
+    fence.try_issue(0, FenceRequest::new(1, 2)).unwrap();
 
     fence.tick(0);
     let first = fence.pop_ready().expect("first ready");
@@ -113,15 +69,26 @@ fn fence_at_cycle_zero() {
     cfg.queue.base_latency = 0;
     let mut fence = FenceQueue::new(cfg);
 
-    fence
-        .try_issue(
-            0,
-            FenceRequest {
-                warp: 0,
-                request_id: 1,
-            },
-        )
-        .unwrap();
+    fence.try_issue(0, FenceRequest::new(0, 1)).unwrap();
     fence.tick(0);
     assert!(fence.pop_ready().is_some());
 }
+
+#[test]
+fn decodes_fence_scope_and_order() {
+    assert_eq!(decode_fence(0, 0xff), (FenceScope::All, FenceOrder::AcqRel));
+    assert_eq!(
+        decode_fence(0, 0x33),
+        (FenceScope::Global, FenceOrder::AcqRel)
+    );
+    assert_eq!(
+        decode_fence(0, 0x30),
+        (FenceScope::Global, FenceOrder::Release)
+    );
+    assert_eq!(
+        decode_fence(0, 0x0c),
+        (FenceScope::Shared, FenceOrder::Acquire)
+    );
+    assert_eq!(decode_fence(0, 0x00), (FenceScope::All, FenceOrder::AcqRel));
+    assert_eq!(decode_fence(2, 0), (FenceScope::Shared, FenceOrder::AcqRel));
+}
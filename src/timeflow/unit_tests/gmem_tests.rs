@@ -184,6 +184,29 @@ fn l1_flush_invalidates_cluster_l1() {
     assert!(comp.request.l2_hit, "expected L2 hit after L1 flush");
 }
 
+#[test]
+fn l1_flush_limit_rejects_until_one_completes() {
+    let mut cfg = GmemFlowConfig::zeroed();
+    cfg.policy.max_inflight_l1_flushes = 1;
+    let mut cluster = ClusterGmemGraph::new(cfg, 1, 2);
+    let cycle = 0;
+
+    issue_flush_l1(&mut cluster, 0, cycle, 0);
+    let flush = GmemRequest::new_flush_l1(1, 1);
+    let err = cluster
+        .issue(1, cycle, flush)
+        .expect_err("second L1 flush should hit the limit");
+    assert_eq!(GmemRejectReason::FlushLimit, err.reason);
+    assert_eq!(cluster.stats(1).flush_limit_rejects(), 1);
+    assert_eq!(cluster.stats(1).queue_full_rejects(), 0);
+
+    // An L0 flush has its own limit.
+    issue_flush_l0(&mut cluster, 1, cycle, 0);
+
+    let comp = assert_completes!(&mut cluster, 0, cycle, MAX_CYCLES);
+    issue_flush_l1(&mut cluster, 1, comp.completed_at, 0);
+}
+
 #[test]
 fn merge_completion_fanout_same_core() {
     let mut cfg = GmemFlowConfig::zeroed();
@@ -1,4 +1,4 @@
-use crate::timeflow::graph::{FlowGraph, Link, TimedNode};
+use crate::timeflow::graph::{FlowGraph, Link, MisrouteAction, TimedNode};
 use crate::timeflow::server_node::ServerNode;
 use crate::timeq::{
     Backpressure, Cycle, ServerConfig, ServiceRequest, ServiceResult, Ticket, TimedServer,
@@ -299,6 +299,81 @@ fn filtered_link_with_false_predicate_blocks() {
     graph.with_node_mut(dst, |n| {
         assert!(n.peek_ready(10).is_none());
     });
+    // Stuck for five cycles, but counted once.
+    assert_eq!(graph.misroute_stats().misrouted, 1);
+    assert_eq!(graph.misroute_stats().dropped, 0);
+}
+
+fn split_graph(action: MisrouteAction) -> (FlowGraph<u32>, usize, usize, usize) {
+    let mut graph: FlowGraph<u32> = FlowGraph::new();
+    graph.set_misroute_action(action);
+    let server = || {
+        TimedServer::new(ServerConfig {
+            queue_capacity: 8,
+            ..ServerConfig::default()
+        })
+    };
+    let src = graph.add_node(ServerNode::new("src", server()));
+    let even = graph.add_node(ServerNode::new("even", server()));
+    let odd = graph.add_node(ServerNode::new("odd", server()));
+    // Odd first, so it looks at every payload before the even edge takes it.
+    graph.connect_filtered(src, odd, "src->odd", Link::new(4), |v| {
+        v % 2 == 1 && *v < 100
+    });
+    graph.connect_filtered(src, even, "src->even", Link::new(4), |v| v % 2 == 0);
+    (graph, src, even, odd)
+}
+
+#[test]
+fn route_coverage_flags_dead_edges() {
+    let (mut graph, src, _, _) = split_graph(MisrouteAction::Warn);
+    for (cycle, value) in [2u32, 4, 6].into_iter().enumerate() {
+        graph
+            .try_put(src, cycle as Cycle, ServiceRequest::new(value, 4))
+            .unwrap();
+        graph.tick(cycle as Cycle);
+    }
+    for cycle in 3..40 {
+        graph.tick(cycle);
+    }
+    let coverage = graph.route_coverage();
+    assert_eq!(coverage.len(), 2);
+    assert_eq!(coverage[0].dst, "odd");
+    assert_eq!(coverage[0].predicate_misses, 3);
+    assert!(coverage[0].is_dead());
+    assert_eq!(coverage[1].edge, "src->even");
+    assert_eq!(coverage[1].predicate_hits, 3);
+    assert_eq!(coverage[1].entries_pushed, 3);
+    assert!(!coverage[1].is_dead());
+    assert_eq!(graph.misroute_stats().misrouted, 0);
+}
+
+#[test]
+fn misroute_drop_unblocks_the_source() {
+    let (mut graph, src, even, _) = split_graph(MisrouteAction::Drop);
+    graph
+        .try_put(src, 0, ServiceRequest::new(101u32, 4))
+        .unwrap();
+    graph.try_put(src, 0, ServiceRequest::new(8u32, 4)).unwrap();
+    for cycle in 0..40 {
+        graph.tick(cycle);
+    }
+    assert_eq!(graph.misroute_stats().misrouted, 1);
+    assert_eq!(graph.misroute_stats().dropped, 1);
+    let delivered = graph.with_node_mut(even, |n| n.take_ready(40).map(|r| r.payload));
+    assert_eq!(delivered, Some(8));
+}
+
+#[test]
+#[should_panic(expected = "src: ready payload")]
+fn misroute_panic_names_the_node() {
+    let (mut graph, src, _, _) = split_graph(MisrouteAction::Panic);
+    graph
+        .try_put(src, 0, ServiceRequest::new(101u32, 4))
+        .unwrap();
+    for cycle in 0..10 {
+        graph.tick(cycle);
+    }
 }
 
 #[test]
@@ -382,6 +457,54 @@ fn multiple_inputs_to_one_node() {
     });
 }
 
+/// `a` keeps a one-entry sink busy every cycle; returns where `b` landed in
+/// the sink's output order.
+fn starved_input_position(age_threshold: Option<Cycle>) -> usize {
+    let mut graph: FlowGraph<&'static str> = FlowGraph::new();
+    graph.set_age_threshold(age_threshold);
+    let server = |queue_capacity| {
+        TimedServer::new(ServerConfig {
+            base_latency: 0,
+            bytes_per_cycle: 1,
+            queue_capacity,
+            ..ServerConfig::default()
+        })
+    };
+    let src_a = graph.add_node(ServerNode::new("a", server(32)));
+    let src_b = graph.add_node(ServerNode::new("b", server(32)));
+    let sink = graph.add_node(ServerNode::new("sink", server(1)));
+    let out = graph.add_node(ServerNode::new("out", server(64)));
+    graph.connect(src_a, sink, "a->sink", Link::new(32));
+    graph.connect(src_b, sink, "b->sink", Link::new(32));
+    graph.connect(sink, out, "sink->out", Link::new(32));
+
+    for _ in 0..20 {
+        graph
+            .try_put(src_a, 0, ServiceRequest::new("a", 1))
+            .unwrap();
+    }
+    graph
+        .try_put(src_b, 0, ServiceRequest::new("b", 1))
+        .unwrap();
+    for cycle in 0..64 {
+        graph.tick(cycle);
+    }
+    graph.with_node_mut(out, |node| {
+        let mut order = Vec::new();
+        while let Some(result) = node.take_ready(64) {
+            order.push(result.payload);
+        }
+        assert_eq!(order.len(), 21);
+        order.iter().position(|&payload| payload == "b").unwrap()
+    })
+}
+
+#[test]
+fn aged_link_entries_get_priority_at_shared_destination() {
+    assert_eq!(starved_input_position(None), 20);
+    assert!(starved_input_position(Some(4)) <= 6);
+}
+
 #[test]
 fn deep_pipeline_100_nodes() {
     let mut graph: FlowGraph<u32> = FlowGraph::new();
@@ -469,6 +592,7 @@ fn wide_fanout_32_branches() {
 
 #[test]
 fn busy_nodes_propagate_backpressure() {
+    #[derive(Clone)]
     struct BusyNode;
     impl TimedNode<&'static str> for BusyNode {
         fn name(&self) -> &str {
@@ -494,6 +618,9 @@ fn busy_nodes_propagate_backpressure() {
         fn outstanding(&self) -> usize {
             0
         }
+        fn clone_box(&self) -> Box<dyn TimedNode<&'static str>> {
+            Box::new(self.clone())
+        }
     }
 
     let mut graph: FlowGraph<&'static str> = FlowGraph::new();
@@ -515,3 +642,410 @@ fn busy_nodes_propagate_backpressure() {
     assert!(stats.downstream_backpressure > 0);
     assert!(stats.last_delivery_cycle.is_none());
 }
+
+#[test]
+fn squash_and_cancel_drop_queued_requests() {
+    let mut graph: FlowGraph<&'static str> = FlowGraph::new();
+    let a = graph.add_node(ServerNode::new(
+        "a",
+        TimedServer::new(ServerConfig {
+            base_latency: 1,
+            bytes_per_cycle: 4,
+            queue_capacity: 4,
+            ..ServerConfig::default()
+        }),
+    ));
+    let b = graph.add_node(ServerNode::new(
+        "b",
+        TimedServer::new(ServerConfig {
+            base_latency: 20,
+            bytes_per_cycle: 4,
+            queue_capacity: 1,
+            ..ServerConfig::default()
+        }),
+    ));
+    let link = graph.connect(a, b, "a->b", Link::new(4));
+
+    let mut tickets = Vec::new();
+    for payload in ["w0-x", "w1-x", "w0-y", "w1-y"] {
+        let ticket = graph
+            .try_put(a, 0, ServiceRequest::new(payload, 4))
+            .expect("enqueue should succeed");
+        tickets.push(ticket);
+    }
+    assert!(graph.cancel(a, &tickets[3]));
+    assert!(!graph.cancel(a, &tickets[3]));
+
+    // w0-x is in service at b and w1-x waits in the link behind it.
+    for cycle in 0..6 {
+        graph.tick(cycle);
+    }
+    assert_eq!(graph.squash(|payload| payload.starts_with("w0")), 2);
+    assert_eq!(graph.squashed(), 3);
+    assert_eq!(graph.edge_stats(link).squashed, 1);
+
+    let mut delivered = Vec::new();
+    for cycle in 6..60 {
+        graph.tick(cycle);
+        graph.with_node_mut(b, |node| {
+            while let Some(result) = node.take_ready(cycle) {
+                delivered.push(result.payload);
+            }
+        });
+    }
+    assert_eq!(delivered, ["w1-x"]);
+}
+
+#[test]
+fn cancel_reaches_requests_crossing_a_link() {
+    let mut graph: FlowGraph<&'static str> = FlowGraph::new();
+    let config = ServerConfig {
+        base_latency: 1,
+        bytes_per_cycle: 4,
+        queue_capacity: 4,
+        ..ServerConfig::default()
+    };
+    let a = graph.add_node(ServerNode::new("a", TimedServer::new(config)));
+    let b = graph.add_node(ServerNode::new("b", TimedServer::new(config)));
+    let link = graph.connect(a, b, "a->b", Link::new(4).with_latency(10));
+
+    let tickets: Vec<_> = ["x", "y"]
+        .into_iter()
+        .map(|payload| {
+            graph
+                .try_put(a, 0, ServiceRequest::new(payload, 4))
+                .expect("enqueue should succeed")
+        })
+        .collect();
+
+    // Both requests have left `a` but are still crossing the link.
+    for cycle in 0..5 {
+        graph.tick(cycle);
+    }
+    assert_eq!(graph.with_node_mut(a, |node| node.outstanding()), 0);
+    assert!(graph.cancel(a, &tickets[0]));
+    assert!(!graph.cancel(a, &tickets[0]));
+    assert_eq!(graph.squashed(), 1);
+    assert_eq!(graph.edge_stats(link).squashed, 1);
+
+    let mut delivered = Vec::new();
+    for cycle in 5..40 {
+        graph.tick(cycle);
+        graph.with_node_mut(b, |node| {
+            while let Some(result) = node.take_ready(cycle) {
+                delivered.push(result.payload);
+            }
+        });
+    }
+    assert_eq!(delivered, ["y"]);
+}
+
+#[test]
+fn custom_payloads_share_a_graph_with_builtin_requests() {
+    use crate::timeflow::{CoreFlowPayload, GmemRequest};
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Probe {
+        tag: u32,
+    }
+
+    let mut graph: FlowGraph<CoreFlowPayload> = FlowGraph::new();
+    let src = graph.add_node(ServerNode::new(
+        "src",
+        TimedServer::new(ServerConfig {
+            base_latency: 0,
+            bytes_per_cycle: 4,
+            queue_capacity: 8,
+            ..ServerConfig::default()
+        }),
+    ));
+    let gmem_sink = graph.add_node(ServerNode::new(
+        "gmem",
+        TimedServer::new(ServerConfig::default()),
+    ));
+    let probe_sink = graph.add_node(ServerNode::new(
+        "probe",
+        TimedServer::new(ServerConfig::default()),
+    ));
+    graph.connect(src, gmem_sink, "src->gmem", Link::new(4));
+    graph.connect(src, probe_sink, "src->probe", Link::new(4));
+    graph.set_route_fn(src, |payload| {
+        usize::from(payload.downcast_ref::<Probe>().is_some())
+    });
+
+    let gmem = CoreFlowPayload::Gmem(GmemRequest::new(0, 4, 1, true));
+    graph.try_put(src, 0, ServiceRequest::new(gmem, 4)).unwrap();
+    let probe = CoreFlowPayload::custom(Probe { tag: 7 });
+    assert!(probe.downcast_ref::<u32>().is_none());
+    assert!(probe.clone().into_custom::<u32>().is_none());
+    graph
+        .try_put(src, 1, ServiceRequest::new(probe.clone(), 4))
+        .unwrap();
+
+    for cycle in 0..10 {
+        graph.tick(cycle);
+    }
+
+    graph.with_node_mut(gmem_sink, |node| {
+        let result = node.take_ready(10).expect("gmem result");
+        assert!(matches!(result.payload, CoreFlowPayload::Gmem(_)));
+    });
+    graph.with_node_mut(probe_sink, |node| {
+        let mut result = node.take_ready(10).expect("probe result");
+        result.payload.downcast_mut::<Probe>().unwrap().tag += 1;
+        let payload = result.payload.into_custom::<Probe>().unwrap();
+        assert_eq!(payload, Probe { tag: 8 });
+    });
+    assert_eq!(probe.into_custom::<Probe>().unwrap(), Probe { tag: 7 });
+}
+
+#[test]
+fn link_latency_delays_delivery_but_keeps_throughput() {
+    let arrivals = |latency: Cycle| {
+        let mut graph: FlowGraph<u32> = FlowGraph::new();
+        let server = ServerConfig {
+            base_latency: 0,
+            bytes_per_cycle: 4,
+            queue_capacity: 8,
+            ..ServerConfig::default()
+        };
+        let src = graph.add_node(ServerNode::new("src", TimedServer::new(server)));
+        let dst = graph.add_node(ServerNode::new("dst", TimedServer::new(server)));
+        graph.connect(src, dst, "src->dst", Link::new(8).with_latency(latency));
+        for value in 0..4 {
+            graph
+                .try_put(src, 0, ServiceRequest::new(value, 4))
+                .expect("enqueue should succeed");
+        }
+        let mut arrivals = Vec::new();
+        for cycle in 0..20 {
+            graph.tick(cycle);
+            graph.with_node_mut(dst, |node| {
+                while let Some(result) = node.take_ready(cycle) {
+                    arrivals.push((result.payload, cycle));
+                }
+            });
+        }
+        arrivals
+    };
+
+    let direct = arrivals(0);
+    let delayed = arrivals(3);
+    assert_eq!(direct.len(), 4);
+    assert_eq!(delayed.len(), 4);
+    for (&(value, at), &(delayed_value, delayed_at)) in direct.iter().zip(&delayed) {
+        assert_eq!(value, delayed_value);
+        assert_eq!(delayed_at, at + 3, "entry {value}");
+    }
+}
+
+#[test]
+fn trace_route_follows_predicates_without_moving_payloads() {
+    let (mut graph, src, even, odd) = split_graph(MisrouteAction::Warn);
+    let orphan = graph.add_node(ServerNode::new(
+        "orphan",
+        TimedServer::new(ServerConfig::default()),
+    ));
+
+    let trace = graph.trace_route(src, &3);
+    assert_eq!(trace.visited, [src, odd]);
+    assert_eq!(trace.sinks, [odd]);
+    assert!(trace.stranded.is_empty());
+
+    let trace = graph.trace_route(src, &101);
+    assert_eq!(trace.stranded, [src]);
+    assert!(trace.sinks.is_empty());
+
+    assert_eq!(graph.trace_route(src, &4).sinks, [even]);
+    assert_eq!(graph.unreachable_from(&[src]), [orphan]);
+    graph.tick(0);
+    assert_eq!(graph.misroute_stats().misrouted, 0);
+}
+
+#[test]
+fn accepts_per_cycle_limits_deliveries_into_a_node() {
+    let server = ServerConfig {
+        base_latency: 0,
+        bytes_per_cycle: 1024,
+        queue_capacity: 8,
+        ..ServerConfig::default()
+    };
+    // `dst` turns everything away until cycle 4, so the link fills up.
+    let warming = ServerConfig {
+        warmup_latency: 4,
+        ..server
+    };
+    let accepted = |link: Link<u32>| {
+        let mut graph: FlowGraph<u32> = FlowGraph::new();
+        let src = graph.add_node(ServerNode::new("src", TimedServer::new(server)));
+        let dst = graph.add_node(ServerNode::new("dst", TimedServer::new(warming)));
+        let edge = graph.connect(src, dst, "src->dst", link);
+        for value in 0..4 {
+            graph
+                .try_put(src, 0, ServiceRequest::new(value, 4))
+                .expect("enqueue should succeed");
+        }
+        let per_cycle: Vec<usize> = (0..8)
+            .map(|cycle| {
+                let before = graph.edge_stats(edge).entries_delivered;
+                graph.tick(cycle);
+                (graph.edge_stats(edge).entries_delivered - before) as usize
+            })
+            .collect();
+        (per_cycle, graph.edge_stats(edge).accept_limited)
+    };
+
+    let (wide, limited) = accepted(Link::new(8));
+    assert_eq!(wide, [0, 0, 0, 0, 4, 0, 0, 0]);
+    assert_eq!(limited, 0);
+
+    let (single, limited) = accepted(Link::new(8).with_accepts_per_cycle(1));
+    assert_eq!(single, [0, 0, 0, 0, 1, 1, 1, 1]);
+    assert_eq!(limited, 3);
+}
+
+#[test]
+fn accepts_per_cycle_counts_deliveries_from_every_link() {
+    let server = ServerConfig {
+        base_latency: 0,
+        bytes_per_cycle: 1024,
+        queue_capacity: 8,
+        ..ServerConfig::default()
+    };
+    let mut graph: FlowGraph<u32> = FlowGraph::new();
+    let a = graph.add_node(ServerNode::new("a", TimedServer::new(server)));
+    let b = graph.add_node(ServerNode::new("b", TimedServer::new(server)));
+    let dst = graph.add_node(ServerNode::new("dst", TimedServer::new(server)));
+    let from_a = graph.connect(a, dst, "a->dst", Link::new(4));
+    let from_b = graph.connect(b, dst, "b->dst", Link::new(4).with_accepts_per_cycle(1));
+    graph
+        .try_put(a, 0, ServiceRequest::new(0, 4))
+        .expect("enqueue should succeed");
+    graph
+        .try_put(b, 0, ServiceRequest::new(1, 4))
+        .expect("enqueue should succeed");
+
+    // Both are ready at cycle 1. `a` is delivered first and uses up the one
+    // slot `b`'s link allows.
+    graph.tick(0);
+    graph.tick(1);
+    assert_eq!(graph.edge_stats(from_a).entries_delivered, 1);
+    assert_eq!(graph.edge_stats(from_b).entries_delivered, 0);
+    assert_eq!(graph.edge_stats(from_b).accept_limited, 1);
+    graph.tick(2);
+    assert_eq!(graph.edge_stats(from_b).entries_delivered, 1);
+}
+
+#[test]
+fn adaptive_route_moves_traffic_off_a_full_destination() {
+    let run = |adaptive: bool| {
+        let mut graph: FlowGraph<u32> = FlowGraph::new();
+        let src = graph.add_node(ServerNode::new(
+            "src",
+            TimedServer::new(ServerConfig {
+                bytes_per_cycle: 64,
+                queue_capacity: 16,
+                ..ServerConfig::default()
+            }),
+        ));
+        let slow = || {
+            TimedServer::new(ServerConfig {
+                bytes_per_cycle: 1,
+                queue_capacity: 16,
+                ..ServerConfig::default()
+            })
+        };
+        let a = graph.add_node(ServerNode::new("a", slow()));
+        let b = graph.add_node(ServerNode::new("b", slow()));
+        graph.connect(src, a, "src->a", Link::new(4));
+        graph.connect(src, b, "src->b", Link::new(4));
+        graph.set_route_fn(src, |_| 0);
+        if adaptive {
+            graph.set_adaptive_route(src, vec![vec![0, 1]], 2);
+        }
+        for value in 0..8u32 {
+            graph
+                .try_put(src, 0, ServiceRequest::new(value, 4))
+                .unwrap();
+        }
+        for cycle in 0..40 {
+            graph.tick(cycle);
+        }
+        let stats = graph.adaptive_route_stats();
+        let to_b = graph.edge_stats(1).entries_pushed;
+        (to_b, stats)
+    };
+
+    let (to_b, stats) = run(false);
+    assert_eq!(to_b, 0);
+    assert_eq!((stats.routed, stats.rerouted), (0, 0));
+
+    let (to_b, stats) = run(true);
+    assert!(to_b > 0);
+    assert_eq!(stats.routed, 8);
+    assert_eq!(stats.rerouted, to_b);
+}
+
+#[test]
+fn stalled_node_backpressures_until_its_window_ends() {
+    let mut graph: FlowGraph<u32> = FlowGraph::new();
+    let server = || {
+        TimedServer::new(ServerConfig {
+            base_latency: 0,
+            bytes_per_cycle: 1,
+            queue_capacity: 2,
+            ..ServerConfig::default()
+        })
+    };
+    let src = graph.add_node(ServerNode::new("src", server()));
+    let mid = graph.add_node(ServerNode::new("mid", server()));
+    let sink = graph.add_node(ServerNode::new(
+        "sink",
+        TimedServer::new(ServerConfig {
+            queue_capacity: 16,
+            ..ServerConfig::default()
+        }),
+    ));
+    graph.connect(src, mid, "src->mid", Link::new(2));
+    graph.connect(mid, sink, "mid->sink", Link::new(2));
+    assert_eq!(graph.find_node("mid"), Some(mid));
+    graph.inject_stall(mid, 0..20);
+
+    match graph.try_put(mid, 0, ServiceRequest::new(99, 1)) {
+        Err(Backpressure::Busy { available_at, .. }) => assert_eq!(available_at, 20),
+        other => panic!("stalled node admitted a request: {other:?}"),
+    }
+    let mut offered = 0;
+    let mut offered_by_10 = 0;
+    for cycle in 0..40 {
+        while offered < 8
+            && graph
+                .try_put(src, cycle, ServiceRequest::new(offered, 1))
+                .is_ok()
+        {
+            offered += 1;
+        }
+        if cycle == 10 {
+            // src and its link are full, so nothing more gets in upstream.
+            offered_by_10 = offered;
+        }
+        graph.tick(cycle);
+        if cycle < 20 {
+            assert_eq!(graph.node_outstanding(sink), 0, "cycle {cycle}");
+        }
+    }
+    assert!(offered_by_10 < 8);
+
+    let delivered = graph.with_node_mut(sink, |node| {
+        let mut delivered = Vec::new();
+        while let Some(result) = node.take_ready(40) {
+            delivered.push(result.payload);
+        }
+        delivered
+    });
+    assert_eq!(delivered, (0..8).collect::<Vec<_>>());
+    let stalls = graph.injected_stalls();
+    assert_eq!(stalls.len(), 1);
+    assert_eq!(stalls[0].node, "mid");
+    assert!(stalls[0].rejected >= 2, "{stalls:?}");
+}
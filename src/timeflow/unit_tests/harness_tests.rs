@@ -0,0 +1,129 @@
+use crate::timeflow::graph::{FlowGraph, Link, TimedNode};
+use crate::timeflow::harness::{GraphPorts, Script, Violation};
+use crate::timeflow::lsu::{LsuFlowConfig, LsuPayload, LsuSubgraph};
+use crate::timeflow::server_node::ServerNode;
+use crate::timeflow::{GmemRequest, SmemRequest};
+use crate::timeq::{ServerConfig, TimedServer};
+
+fn server(name: &str, base_latency: u64, queue_capacity: usize) -> ServerNode<&'static str> {
+    ServerNode::new(
+        name,
+        TimedServer::new(ServerConfig {
+            base_latency,
+            bytes_per_cycle: 4,
+            queue_capacity,
+            ..ServerConfig::default()
+        }),
+    )
+}
+
+fn lsu_label(payload: &LsuPayload) -> String {
+    match payload {
+        LsuPayload::Gmem(req) => format!("g{}", req.id),
+        LsuPayload::Smem(req) => format!("s{}", req.id),
+    }
+}
+
+#[test]
+fn script_checks_latency_windows_and_rejections() {
+    let node = &mut server("n0", 2, 1);
+    let trace = Script::new()
+        .issue(0, "a", "a", 4)
+        .within(2..=4)
+        .issue(0, "b", "b", 4)
+        .rejected()
+        .issue(1, "c", "c", 4)
+        .in_order(&["a", "c"])
+        .run(node as &mut dyn TimedNode<_>, 32, |payload| {
+            payload.to_string()
+        })
+        .expect("script should pass");
+
+    assert_eq!(trace.completion_order, vec!["a", "c"]);
+    assert!(trace.get("c").unwrap().accepted_at.is_some());
+}
+
+#[test]
+fn script_reports_violations() {
+    let node = &mut server("n0", 3, 4);
+    let err = Script::new()
+        .issue(0, "a", "a", 4)
+        .within(0..=1)
+        .issue(0, "b", "b", 4)
+        .rejected()
+        .in_order(&["b", "a"])
+        .run(node as &mut dyn TimedNode<_>, 32, |payload| {
+            payload.to_string()
+        })
+        .expect_err("script should fail");
+
+    assert!(err
+        .violations
+        .iter()
+        .any(|v| matches!(v, Violation::Latency { label, .. } if label == "a")));
+    assert!(err
+        .violations
+        .iter()
+        .any(|v| matches!(v, Violation::UnexpectedAccept { label, .. } if label == "b")));
+    assert!(err
+        .violations
+        .iter()
+        .any(|v| matches!(v, Violation::OutOfOrder { .. })));
+}
+
+#[test]
+fn script_drives_graph_ports() {
+    let mut graph: FlowGraph<&'static str> = FlowGraph::new();
+    let n0 = graph.add_node(server("n0", 1, 4));
+    let n1 = graph.add_node(server("n1", 1, 4));
+    graph.connect(n0, n1, "n0->n1", Link::new(4));
+
+    let trace = Script::new()
+        .issue(0, "x", "x", 4)
+        .issue(0, "y", "y", 4)
+        .in_order(&["x", "y"])
+        .run(&mut GraphPorts::new(&mut graph, n0, n1), 32, |payload| {
+            payload.to_string()
+        })
+        .expect("script should pass");
+
+    let first = trace.get("x").unwrap().latency().unwrap();
+    assert!(first >= 2, "two hops should take at least two cycles");
+}
+
+#[test]
+fn mixed_smem_gmem_replay_through_lsu() {
+    let mut config = LsuFlowConfig::default();
+    config.issue = ServerConfig {
+        base_latency: 0,
+        bytes_per_cycle: 1024,
+        queue_capacity: 4,
+        completions_per_cycle: 1,
+        ..ServerConfig::default()
+    };
+    config.link_capacity = 1;
+    let mut lsu = LsuSubgraph::new(config, 2);
+
+    let gmem = |id, warp| {
+        let mut req = GmemRequest::new(warp, 16, 0xF, true);
+        req.id = id;
+        LsuPayload::Gmem(req)
+    };
+    let smem = |id, warp| {
+        let mut req = SmemRequest::new(warp, 16, 0xF, false, 0);
+        req.id = id;
+        LsuPayload::Smem(req)
+    };
+
+    let trace = Script::new()
+        .issue(0, "g0", gmem(0, 0), 16)
+        .issue(0, "s0", smem(0, 0), 16)
+        .issue(1, "g1", gmem(1, 1), 16)
+        .issue(1, "s1", smem(1, 1), 16)
+        .within(0..=16)
+        .in_order(&["s0", "g0"])
+        .run(&mut lsu, 64, lsu_label)
+        .expect("mixed replay should pass");
+
+    assert_eq!(trace.completion_order.len(), 4);
+}
@@ -1,4 +1,6 @@
-use crate::timeflow::icache::{IcacheFlowConfig, IcacheRequest, IcacheSubgraph};
+use crate::timeflow::icache::{
+    IcacheFlowConfig, IcacheReplayConfig, IcacheRequest, IcacheSubgraph,
+};
 
 #[test]
 fn icache_hit_ready_at_now_with_zero_latency() {
@@ -118,3 +120,58 @@ fn icache_pc_zero_handling() {
     let stats = icache.stats();
     assert_eq!(1, stats.completed);
 }
+
+#[test]
+fn icache_replayed_miss_refetches_through_the_hit_path() {
+    let mut cfg = IcacheFlowConfig::default();
+    cfg.policy.hit_rate = 0.0;
+    cfg.miss.base_latency = 10;
+    cfg.hit.base_latency = 1;
+    cfg.replay = IcacheReplayConfig {
+        enabled: true,
+        penalty: 3,
+        max_per_warp: 2,
+    };
+    let mut icache = IcacheSubgraph::new(cfg);
+    let issue = icache
+        .issue(0, IcacheRequest::new(0, 0x1000, 8))
+        .expect("miss should accept");
+    assert_eq!(issue.ticket.ready_at(), 10);
+    assert_eq!(issue.replay_at, Some(13));
+
+    let refetch = icache
+        .issue(13, IcacheRequest::new(0, 0x1004, 8))
+        .expect("refetch should hit");
+    assert_eq!(refetch.replay_at, None);
+    assert_eq!(refetch.ticket.ready_at(), 14);
+    let stats = icache.stats();
+    assert_eq!((stats.misses, stats.hits), (1, 1));
+    assert_eq!((stats.replays, stats.replay_refetches), (1, 1));
+    assert_eq!(stats.replay_penalty_cycles, 3);
+}
+
+#[test]
+fn icache_replay_cap_makes_misses_wait_for_the_fill() {
+    let mut cfg = IcacheFlowConfig::default();
+    cfg.policy.hit_rate = 0.0;
+    cfg.replay = IcacheReplayConfig {
+        enabled: true,
+        penalty: 2,
+        max_per_warp: 1,
+    };
+    let mut icache = IcacheSubgraph::new(cfg);
+    let first = icache
+        .issue(0, IcacheRequest::new(0, 0x1000, 8))
+        .expect("miss should accept");
+    assert!(first.replay_at.is_some());
+    let second = icache
+        .issue(1, IcacheRequest::new(0, 0x2000, 8))
+        .expect("miss should accept");
+    assert_eq!(second.replay_at, None);
+    let other_warp = icache
+        .issue(1, IcacheRequest::new(1, 0x2000, 8))
+        .expect("miss should accept");
+    assert!(other_warp.replay_at.is_some());
+    let stats = icache.stats();
+    assert_eq!((stats.replays, stats.replay_cap_waits), (2, 1));
+}
@@ -1,5 +1,5 @@
 use crate::timeflow::lsu::{
-    LsuCompletion, LsuFlowConfig, LsuPayload, LsuRejectReason, LsuSubgraph,
+    LsuCompletion, LsuFlowConfig, LsuPayload, LsuQueueSharing, LsuRejectReason, LsuSubgraph,
 };
 use crate::timeflow::{GmemRequest, GmemRequestKind, SmemRequest};
 use crate::timeq::{Cycle, ServerConfig};
@@ -285,3 +285,101 @@ fn rapid_issue_complete_cycle() {
     assert!(issued > 0);
     assert!(lsu.stats().completed > 0);
 }
+
+#[test]
+fn lsu_caps_outstanding_misses_per_warp() {
+    let mut config = LsuFlowConfig::default();
+    config.issue = default_issue_config();
+    config.resources.max_outstanding_misses_per_warp = 2;
+
+    let mut lsu = LsuSubgraph::new(config, 2);
+    lsu.note_miss(0);
+    lsu.note_miss(0);
+
+    let err = lsu
+        .issue_gmem(0, GmemRequest::new(0, 16, 0xF, true))
+        .expect_err("warp 0 is at its miss cap");
+    assert_eq!(err.reason, LsuRejectReason::Busy);
+    assert_eq!(1, lsu.stats().mlp_cap_rejects);
+
+    // Other warps and smem traffic are unaffected.
+    assert!(lsu
+        .issue_gmem(0, GmemRequest::new(1, 16, 0xF, true))
+        .is_ok());
+    assert!(lsu
+        .issue_smem(0, SmemRequest::new(0, 16, 0xF, false, 0))
+        .is_ok());
+
+    lsu.release_miss(0);
+    assert_eq!(1, lsu.outstanding_misses(0));
+    assert!(lsu
+        .issue_gmem(1, GmemRequest::new(0, 16, 0xF, true))
+        .is_ok());
+}
+
+#[test]
+fn shared_queues_partition_entries_by_credit() {
+    let mut config = LsuFlowConfig::default();
+    config.issue = default_issue_config();
+    config.queues.sharing = LsuQueueSharing::Shared;
+    config.queues.credits_per_warp = 2;
+    config.queues.global_ldq.queue_capacity = 4;
+
+    let mut lsu = LsuSubgraph::new(config, 2);
+    let load = |warp| GmemRequest::new(warp, 16, 0xF, true);
+    assert!(lsu.issue_gmem(0, load(0)).is_ok());
+    assert!(lsu.issue_gmem(0, load(0)).is_ok());
+    let err = lsu
+        .issue_gmem(0, load(0))
+        .expect_err("warp 0 is out of credits");
+    assert_eq!(err.reason, LsuRejectReason::QueueFull);
+    assert!(lsu.issue_gmem(0, load(1)).is_ok());
+
+    lsu.tick(0);
+    let stats = lsu.stats();
+    assert_eq!(stats.credit_rejects, 1);
+    assert_eq!(stats.global_ldq_peak_occupancy, 3);
+
+    let completed = drain_issued(&mut lsu, 1, 20);
+    assert_eq!(completed.len(), 3);
+    assert!(lsu.issue_gmem(21, load(0)).is_ok());
+}
+
+#[test]
+fn loads_forward_from_covering_older_store() {
+    let mut config = LsuFlowConfig::default();
+    config.issue = default_issue_config();
+    config.forwarding.enabled = true;
+    config.forwarding.latency = 2;
+
+    let mut lsu = LsuSubgraph::new(config, 1);
+    let access = |addr, bytes, is_load| {
+        let mut req = GmemRequest::new(0, bytes, 0xF, is_load);
+        req.addr = addr;
+        req
+    };
+
+    assert!(lsu.issue_gmem(0, access(0x100, 16, false)).is_ok());
+
+    let issue = lsu.issue_gmem(0, access(0x104, 4, true)).unwrap();
+    assert!(issue.forwarded);
+    assert_eq!(issue.ticket.ready_at(), 2);
+
+    let err = lsu
+        .issue_gmem(0, access(0x10c, 16, true))
+        .expect_err("partial overlap should stall");
+    assert_eq!(err.reason, LsuRejectReason::Busy);
+
+    let issue = lsu.issue_gmem(0, access(0x200, 4, true)).unwrap();
+    assert!(!issue.forwarded);
+
+    assert!(lsu.take_forwarded(1).is_none());
+    match lsu.take_forwarded(2) {
+        Some(LsuPayload::Gmem(req)) => assert!(req.forwarded),
+        other => panic!("expected forwarded gmem load, got {other:?}"),
+    }
+
+    let stats = lsu.stats();
+    assert_eq!(stats.forwarded_loads, 1);
+    assert_eq!(stats.forward_partial_stalls, 1);
+}
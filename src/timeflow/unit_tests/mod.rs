@@ -1,10 +1,18 @@
 #[cfg(test)]
 mod barrier_tests;
 #[cfg(test)]
+mod bottleneck_tests;
+#[cfg(test)]
 mod cache_tests;
 #[cfg(test)]
+mod context_switch_tests;
+#[cfg(test)]
+mod copy_engine_tests;
+#[cfg(test)]
 mod core_graph_tests;
 #[cfg(test)]
+mod crossbar_tests;
+#[cfg(test)]
 mod dma_tests;
 #[cfg(test)]
 mod fence_tests;
@@ -13,18 +21,32 @@ mod gmem_tests;
 #[cfg(test)]
 mod graph_tests;
 #[cfg(test)]
+mod harness_tests;
+#[cfg(test)]
+mod ibuffer_tests;
+#[cfg(test)]
 mod icache_tests;
 #[cfg(test)]
 mod lsu_tests;
 #[cfg(test)]
 mod mshr_tests;
 #[cfg(test)]
+mod neutrino_tests;
+#[cfg(test)]
+mod pipeline_node_tests;
+#[cfg(test)]
 mod policy_tests;
 #[cfg(test)]
+mod power_tests;
+#[cfg(test)]
 mod server_node_tests;
 #[cfg(test)]
 mod smem_tests;
 #[cfg(test)]
+mod store_buffer_tests;
+#[cfg(test)]
+mod tick_order_tests;
+#[cfg(test)]
 mod warp_scheduler_tests;
 #[cfg(test)]
 mod writeback_tests;
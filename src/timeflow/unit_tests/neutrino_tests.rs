@@ -0,0 +1,93 @@
+use crate::timeflow::neutrino::{
+    NeutrinoOpKind, NeutrinoQueue, NeutrinoRejectReason, NeutrinoTimingConfig,
+};
+
+fn enabled_config() -> NeutrinoTimingConfig {
+    let mut cfg = NeutrinoTimingConfig::default();
+    cfg.enabled = true;
+    cfg
+}
+
+#[test]
+fn invoke_completes_after_completion_latency() {
+    let mut cfg = enabled_config();
+    cfg.queue.base_latency = 2;
+    cfg.completion_latency = 10;
+    let mut nu = NeutrinoQueue::new(cfg);
+
+    let issue = nu
+        .try_issue(0, NeutrinoOpKind::Invoke, 16)
+        .expect("invoke should accept");
+    assert_eq!(issue.ticket.ready_at() + 10, issue.complete_at);
+
+    nu.tick(issue.complete_at - 1);
+    assert_eq!(1, nu.inflight());
+    nu.tick(issue.complete_at);
+    assert_eq!(0, nu.inflight());
+    assert_eq!(1, nu.stats().completed);
+}
+
+#[test]
+fn payload_transfer_is_bandwidth_limited() {
+    let mut cfg = enabled_config();
+    cfg.queue.base_latency = 0;
+    cfg.queue.bytes_per_cycle = 8;
+    cfg.command_bytes = 8;
+    cfg.payload_bytes_per_lane = 4;
+    let mut nu = NeutrinoQueue::new(cfg);
+
+    let issue = nu
+        .try_issue(0, NeutrinoOpKind::Payload, 16)
+        .expect("payload should accept");
+    // 8 command bytes + 16 lanes * 4 bytes at 8 bytes/cycle
+    assert_eq!(9, issue.ticket.ready_at());
+    assert_eq!(issue.ticket.ready_at(), issue.complete_at);
+    assert_eq!(72, nu.stats().bytes_issued);
+}
+
+#[test]
+fn max_inflight_limits_concurrent_invokes() {
+    let mut cfg = enabled_config();
+    cfg.max_inflight = 1;
+    cfg.completion_latency = 20;
+    let mut nu = NeutrinoQueue::new(cfg);
+
+    let first = nu
+        .try_issue(0, NeutrinoOpKind::Invoke, 1)
+        .expect("first invoke");
+    nu.tick(first.ticket.ready_at());
+    let err = nu
+        .try_issue(first.ticket.ready_at(), NeutrinoOpKind::Invoke, 1)
+        .expect_err("second invoke should exceed concurrency");
+    assert_eq!(NeutrinoRejectReason::Busy, err.reason);
+    assert_eq!(first.complete_at, err.retry_at);
+    assert_eq!(1, nu.stats().concurrency_rejects);
+
+    nu.tick(first.complete_at);
+    assert!(nu
+        .try_issue(first.complete_at, NeutrinoOpKind::Invoke, 1)
+        .is_ok());
+}
+
+#[test]
+fn command_queue_full_rejects() {
+    let mut cfg = enabled_config();
+    cfg.queue.queue_capacity = 1;
+    cfg.queue.base_latency = 4;
+    let mut nu = NeutrinoQueue::new(cfg);
+
+    assert!(nu.try_issue(0, NeutrinoOpKind::Payload, 1).is_ok());
+    let err = nu
+        .try_issue(0, NeutrinoOpKind::Payload, 1)
+        .expect_err("queue full");
+    assert_eq!(NeutrinoRejectReason::QueueFull, err.reason);
+}
+
+#[test]
+fn disabled_neutrino_immediate() {
+    let mut nu = NeutrinoQueue::new(NeutrinoTimingConfig::default());
+    let issue = nu.try_issue(7, NeutrinoOpKind::Invoke, 4).expect("issue");
+    assert_eq!(7, issue.ticket.ready_at());
+    assert_eq!(7, issue.complete_at);
+    assert_eq!(0, nu.inflight());
+}
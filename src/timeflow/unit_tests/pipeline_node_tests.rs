@@ -0,0 +1,65 @@
+use crate::timeflow::graph::TimedNode;
+use crate::timeflow::pipeline_node::{PipelineConfig, PipelineNode};
+use crate::timeq::ServiceRequest;
+
+fn pipeline(stages: usize, skid_depth: usize) -> PipelineNode<u32> {
+    PipelineNode::new("pipe", PipelineConfig { stages, skid_depth })
+}
+
+#[test]
+fn pipeline_accepts_one_per_cycle_with_fixed_latency() {
+    let mut node = pipeline(3, 1);
+    let mut done = Vec::new();
+    for cycle in 0..10u64 {
+        node.tick(cycle);
+        while let Some(result) = node.take_ready(cycle) {
+            done.push((result.payload, cycle));
+        }
+        if cycle < 4 {
+            let ticket = node
+                .try_put(cycle, ServiceRequest::new(cycle as u32, 4))
+                .expect("first stage should be free every cycle");
+            assert_eq!(ticket.ready_at(), cycle + 3);
+        }
+    }
+    assert_eq!(done, vec![(0, 3), (1, 4), (2, 5), (3, 6)]);
+    assert_eq!(node.stats().stage_stall_cycles, 0);
+}
+
+#[test]
+fn full_skid_buffer_stalls_back_to_the_input() {
+    let mut node = pipeline(2, 1);
+    let mut accepted = 0;
+    for cycle in 0..6u64 {
+        node.tick(cycle);
+        if node
+            .try_put(cycle, ServiceRequest::new(accepted, 4))
+            .is_ok()
+        {
+            accepted += 1;
+        }
+    }
+    // Nothing drains: one result in the skid buffer and both stages full.
+    assert_eq!(accepted, 3);
+    assert_eq!(node.skid_len(), 1);
+    assert_eq!(node.occupied_stages(), vec![true, true]);
+    assert!(node.stats().input_stalls > 0);
+    assert!(node.stats().stage_stall_cycles > 0);
+
+    // Draining one result lets every stage advance in the same cycle.
+    assert_eq!(node.take_ready(6).unwrap().payload, 0);
+    node.tick(6);
+    assert_eq!(node.occupied_stages(), vec![false, true]);
+    assert_eq!(node.skid_len(), 1);
+    assert_eq!(node.outstanding(), 2);
+}
+
+#[test]
+fn squash_drops_in_flight_stages() {
+    let mut node = pipeline(4, 1);
+    node.try_put(0, ServiceRequest::new(1, 4)).unwrap();
+    node.tick(1);
+    node.try_put(1, ServiceRequest::new(2, 4)).unwrap();
+    assert_eq!(node.squash(&mut |payload, _| *payload == 1), 1);
+    assert_eq!(node.outstanding(), 1);
+}
@@ -0,0 +1,62 @@
+use crate::timeflow::power::{PowerConfig, PowerGovernor, ThrottleMode};
+
+fn governor(mode: ThrottleMode, budget: f64) -> PowerGovernor {
+    PowerGovernor::new(PowerConfig {
+        enabled: true,
+        static_energy: 1.0,
+        issue_energy: 4.0,
+        window: 4,
+        budget,
+        mode,
+        ..PowerConfig::default()
+    })
+}
+
+fn run(governor: &mut PowerGovernor, eligible: &[bool], cycles: u64) -> Vec<usize> {
+    (0..cycles)
+        .map(|now| {
+            let mut grants = eligible.to_vec();
+            governor.throttle(now, &mut grants);
+            let issued = grants.iter().filter(|&&g| g).count();
+            governor.end_cycle(issued as u32);
+            issued
+        })
+        .collect()
+}
+
+#[test]
+fn over_budget_cores_drop_to_throttled_issue_width() {
+    let mut gov = governor(ThrottleMode::IssueWidth, 6.0);
+    let issued = run(&mut gov, &[true; 4], 6);
+    // 17 energy in cycle 0 keeps the 4-cycle average above budget until it
+    // ages out of the window.
+    assert_eq!(issued, [4, 1, 1, 1, 1, 4]);
+
+    let summary = gov.summary();
+    assert_eq!(summary.cycles, 6);
+    assert_eq!(summary.throttled_cycles, 4);
+    assert_eq!(summary.throttled_issues, 12);
+    assert_eq!(summary.peak_power, 17.0);
+    assert_eq!(summary.total_energy(), 6.0 + 4.0 * 12.0);
+}
+
+#[test]
+fn duty_cycle_mode_stalls_alternate_cycles() {
+    let mut gov = governor(ThrottleMode::DutyCycle, 0.5);
+    let issued = run(&mut gov, &[true], 6);
+    assert_eq!(issued, [1, 0, 1, 0, 1, 0]);
+    assert!(gov.is_throttling());
+}
+
+#[test]
+fn disabled_governor_neither_throttles_nor_counts() {
+    let mut gov = PowerGovernor::new(PowerConfig {
+        budget: 0.1,
+        ..PowerConfig::default()
+    });
+    gov.record_gmem();
+    let issued = run(&mut gov, &[true; 2], 4);
+    assert_eq!(issued, [2, 2, 2, 2]);
+    assert_eq!(gov.summary().cycles, 0);
+    assert_eq!(gov.summary().total_energy(), 0.0);
+}
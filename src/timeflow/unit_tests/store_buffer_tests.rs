@@ -0,0 +1,108 @@
+use crate::timeflow::gmem::{ByteMask, GmemRequest};
+use crate::timeflow::store_buffer::{StoreBuffer, StoreBufferConfig, StoreBufferLookup};
+
+fn store_buffer(entries: usize, coalesce_window: u64) -> StoreBuffer {
+    StoreBuffer::new(StoreBufferConfig {
+        enabled: true,
+        entries,
+        drain_per_cycle: 1,
+        coalesce_window,
+        forward_latency: 1,
+    })
+}
+
+/// One line of a store from `warp` writing `len` bytes at `offset`.
+fn store(id: u64, warp: usize, line: u64, offset: u32, len: u32) -> GmemRequest {
+    let mut request = GmemRequest::new(warp, 64, 1, false);
+    request.id = id;
+    request.addr = line;
+    request.line_addr = line;
+    let mut mask = ByteMask::new(64);
+    mask.set_range(offset, len);
+    request.byte_enable = Some(mask);
+    request
+}
+
+fn load(warp: usize, line: u64) -> GmemRequest {
+    let mut request = GmemRequest::new(warp, 64, 1, true);
+    request.addr = line;
+    request.line_addr = line;
+    request
+}
+
+#[test]
+fn stores_to_a_line_merge_and_complete_on_entry() {
+    let mut sb = store_buffer(4, 4);
+    sb.insert(0, store(1, 0, 0x1000, 0, 4));
+    sb.insert(1, store(2, 0, 0x1000, 4, 4));
+    // Another warp's store to the same line takes its own entry.
+    sb.insert(1, store(3, 1, 0x1000, 8, 4));
+
+    let done = sb.take_completed(1);
+    assert_eq!(vec![1, 2, 3], done.iter().map(|r| r.id).collect::<Vec<_>>());
+    assert!(done.iter().all(|request| request.forwarded));
+
+    // Nothing drains inside the coalescing window.
+    assert!(sb.tick(3).is_empty());
+    let drained = sb.tick(4);
+    assert_eq!(1, drained.len());
+    assert_eq!(1, drained[0].id);
+    assert!(drained[0].byte_enable.as_ref().unwrap().covers(0, 8));
+    assert!(sb.take_drained(&drained[0]));
+    assert!(!sb.take_drained(&drained[0]));
+
+    let stats = sb.stats();
+    assert_eq!(3, stats.stores);
+    assert_eq!(1, stats.merged);
+    assert_eq!(1, stats.drained);
+    assert!((stats.coalesce_ratio() - 1.0 / 3.0).abs() < 1e-9);
+}
+
+#[test]
+fn full_buffer_rejects_new_lines_and_drains_early() {
+    let mut sb = store_buffer(1, 100);
+    sb.insert(0, store(1, 0, 0x1000, 0, 4));
+    assert!(sb.can_accept(&[store(2, 0, 0x1000, 4, 4)]));
+    assert!(!sb.can_accept(&[store(2, 0, 0x2000, 0, 4)]));
+    sb.note_full(1);
+    sb.note_full(1);
+    assert_eq!(1, sb.stats().full_stall_cycles);
+    // Full, so the entry does not wait out its window.
+    assert_eq!(1, sb.tick(1).len());
+    assert!(sb.can_accept(&[store(2, 0, 0x2000, 0, 4)]));
+}
+
+#[test]
+fn loads_forward_or_wait_on_buffered_stores() {
+    let mut sb = store_buffer(4, 100);
+    sb.insert(0, store(1, 0, 0x1000, 0, 8));
+
+    let covered = load(0, 0x1000);
+    assert_eq!(
+        StoreBufferLookup::Forward,
+        sb.lookup(&covered, 0x1000, 0x1004)
+    );
+    assert_eq!(
+        StoreBufferLookup::Partial,
+        sb.lookup(&covered, 0x1004, 0x1010)
+    );
+    assert_eq!(StoreBufferLookup::Miss, sb.lookup(&covered, 0x1010, 0x1014));
+    assert_eq!(
+        StoreBufferLookup::Miss,
+        sb.lookup(&load(1, 0x1000), 0x1000, 0x1004)
+    );
+
+    sb.take_completed(0);
+    sb.forward(1, covered);
+    assert!(sb.take_completed(1).is_empty());
+    assert_eq!(1, sb.take_completed(2).len());
+
+    // A partly covered load is held, which pushes the entry out early.
+    sb.hold(load(0, 0x1000));
+    let out = sb.tick(2);
+    assert_eq!(2, out.len());
+    assert!(!out[0].is_load);
+    assert!(out[1].is_load);
+    assert_eq!(1, sb.stats().forwarded_loads);
+    assert_eq!(1, sb.stats().held);
+}
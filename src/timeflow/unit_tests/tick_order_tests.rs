@@ -0,0 +1,110 @@
+use crate::timeflow::core_graph::CoreGraphConfig;
+use crate::timeflow::{
+    CoreGraph, GmemCompletion, GmemRequest, TickOrderConfig, TickStage, TickUnit, WritebackPayload,
+};
+
+fn parse(toml: &str) -> Result<TickOrderConfig, toml::de::Error> {
+    toml::from_str(toml)
+}
+
+#[test]
+fn default_order_matches_fixed_stages() {
+    let order = TickOrderConfig::default();
+    assert_eq!(
+        order.stage(TickStage::Front),
+        [
+            TickUnit::Icache,
+            TickUnit::Lsu,
+            TickUnit::OperandFetch,
+            TickUnit::Dma,
+            TickUnit::Tensor,
+            TickUnit::Execute,
+            TickUnit::Neutrino,
+            TickUnit::CopyEngine,
+            TickUnit::ClusterGmem,
+        ]
+    );
+    assert_eq!(
+        order.stage(TickStage::Graph),
+        [TickUnit::Graph, TickUnit::GraphCompletions]
+    );
+    assert_eq!(
+        order.stage(TickStage::Back),
+        [TickUnit::Writeback, TickUnit::Fence]
+    );
+    assert_eq!(parse("").unwrap(), order);
+}
+
+#[test]
+fn omitted_stages_keep_their_defaults() {
+    let order = parse(r#"back = ["fence", "writeback"]"#).unwrap();
+    assert_eq!(
+        order.stage(TickStage::Back),
+        [TickUnit::Fence, TickUnit::Writeback]
+    );
+    assert_eq!(
+        order.stage(TickStage::Front),
+        TickOrderConfig::default().stage(TickStage::Front)
+    );
+}
+
+#[test]
+fn invalid_orders_are_rejected() {
+    let cases = [
+        (r#"back = ["writeback"]"#, "does not schedule fence"),
+        (
+            r#"back = ["writeback", "fence", "icache"]"#,
+            "lists icache more than once",
+        ),
+        (
+            r#"graph = ["graph_completions"]
+               back = ["writeback", "fence", "graph"]"#,
+            "graph_completions must tick after graph",
+        ),
+        (
+            r#"graph = ["graph_completions"]
+               front = ["graph", "icache", "lsu", "operand_fetch", "dma", "tensor",
+                        "execute", "neutrino", "copy_engine", "cluster_gmem"]"#,
+            "graph must stay in the graph stage",
+        ),
+        (
+            r#"graph = ["graph_completions", "graph"]"#,
+            "graph_completions must tick after graph",
+        ),
+        (r#"front = ["l3"]"#, "unknown variant"),
+    ];
+    for (toml, expected) in cases {
+        let err = parse(toml).unwrap_err().to_string();
+        assert!(err.contains(expected), "{toml}: {err}");
+    }
+}
+
+#[test]
+fn writeback_moved_to_front_ticks_in_front_stage() {
+    let mut cfg = CoreGraphConfig::default();
+    cfg.memory.writeback.enabled = true;
+    cfg.memory.writeback.queue.base_latency = 1;
+    cfg.tick_order = parse(
+        r#"front = ["writeback", "icache", "lsu", "operand_fetch", "dma", "tensor",
+                    "execute", "neutrino", "copy_engine", "cluster_gmem"]
+           back = ["fence"]"#,
+    )
+    .unwrap();
+    let mut graph = CoreGraph::new(cfg, 1, None, None);
+    let completion = GmemCompletion {
+        request: GmemRequest::new(0, 4, 0xF, true),
+        ticket_ready_at: 0,
+        completed_at: 0,
+    };
+    graph
+        .writeback_try_issue(0, WritebackPayload::Gmem(completion))
+        .expect("writeback issue");
+
+    for cycle in 0..4 {
+        graph.tick_back(cycle);
+    }
+    assert!(graph.writeback_pop_ready().is_none());
+
+    graph.tick_front(4);
+    assert!(graph.writeback_pop_ready().is_some());
+}
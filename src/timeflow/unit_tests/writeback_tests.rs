@@ -1,7 +1,9 @@
 use crate::timeflow::gmem::GmemCompletion;
 use crate::timeflow::gmem::GmemRequest;
 use crate::timeflow::smem::{SmemCompletion, SmemRequest};
-use crate::timeflow::writeback::{WritebackConfig, WritebackPayload, WritebackQueue};
+use crate::timeflow::writeback::{
+    ExecuteWriteback, TensorWriteback, WritebackConfig, WritebackPayload, WritebackQueue,
+};
 
 #[test]
 fn writeback_queue_throttles_completions() {
@@ -143,3 +145,183 @@ fn interleaved_gmem_smem_completions() {
     assert!(matches!(first, WritebackPayload::Gmem(_)));
     assert!(matches!(second, WritebackPayload::Smem(_)));
 }
+
+#[test]
+fn wide_loads_serialize_on_writeback_bus() {
+    let mut cfg = WritebackConfig::default();
+    cfg.enabled = true;
+    cfg.bus_bytes_per_cycle = 16;
+    cfg.queue.base_latency = 0;
+    cfg.queue.completions_per_cycle = 4;
+    let mut queue = WritebackQueue::new(cfg);
+
+    let load = |bytes| GmemCompletion {
+        request: GmemRequest::new(0, bytes, 0xF, true),
+        ticket_ready_at: 0,
+        completed_at: 0,
+    };
+    queue
+        .try_issue(0, WritebackPayload::Gmem(load(64)))
+        .unwrap();
+    queue
+        .try_issue(0, WritebackPayload::Gmem(load(16)))
+        .unwrap();
+
+    let mut done = Vec::new();
+    for cycle in 0..8 {
+        queue.tick(cycle);
+        while let Some(payload) = queue.pop_ready() {
+            done.push((cycle, payload.data_bytes()));
+        }
+    }
+
+    assert_eq!(done, vec![(3, 64), (4, 16)]);
+    let stats = queue.stats();
+    assert_eq!(stats.bus_bytes, 80);
+    assert_eq!(stats.bus_busy_cycles, 5);
+    assert!(stats.bus_wait_cycles > 0);
+    assert!(stats.bus_utilization() > 0.5);
+}
+
+#[test]
+fn split_loads_merge_into_one_writeback_slot() {
+    let mut cfg = WritebackConfig::default();
+    cfg.enabled = true;
+    cfg.merge_window = 2;
+    cfg.queue.base_latency = 0;
+    cfg.queue.completions_per_cycle = 1;
+    let mut queue = WritebackQueue::new(cfg);
+
+    let load = |warp, id| {
+        let mut request = GmemRequest::new(warp, 16, 0xF, true);
+        request.id = id;
+        WritebackPayload::Gmem(GmemCompletion {
+            request,
+            ticket_ready_at: 0,
+            completed_at: 0,
+        })
+    };
+
+    let mut done = Vec::new();
+    for cycle in 0..6 {
+        if cycle == 0 {
+            queue.try_issue(cycle, load(0, 7)).unwrap();
+            queue.try_issue(cycle, load(1, 8)).unwrap();
+        }
+        if cycle == 1 {
+            queue.try_issue(cycle, load(0, 7)).unwrap();
+        }
+        queue.tick(cycle);
+        while let Some(WritebackPayload::Gmem(completion)) = queue.pop_ready() {
+            done.push((cycle, completion.request.warp));
+        }
+    }
+
+    assert_eq!(done, vec![(2, 0), (2, 0), (3, 1)]);
+    let stats = queue.stats();
+    assert_eq!(stats.issued, 3);
+    assert_eq!(stats.merged_completions, 1);
+    assert_eq!(stats.writeback_slots, 2);
+}
+
+fn gmem_load() -> WritebackPayload {
+    WritebackPayload::Gmem(GmemCompletion {
+        request: GmemRequest::new(0, 4, 0xF, true),
+        ticket_ready_at: 0,
+        completed_at: 0,
+    })
+}
+
+fn alu_result() -> WritebackPayload {
+    WritebackPayload::Execute(ExecuteWriteback {
+        warp: 0,
+        active_lanes: 32,
+    })
+}
+
+#[test]
+fn quotas_keep_slots_for_execute_results() {
+    let mut cfg = WritebackConfig::default();
+    cfg.enabled = true;
+    cfg.queue.base_latency = 4;
+    cfg.queue.queue_capacity = 4;
+    cfg.quota.gmem = 1;
+    cfg.quota.execute = 1;
+    let mut queue = WritebackQueue::new(cfg);
+
+    // Each class is owed two of the four slots.
+    assert!(queue.try_issue(0, gmem_load()).is_ok());
+    assert!(queue.try_issue(0, gmem_load()).is_ok());
+    assert!(queue.try_issue(0, gmem_load()).is_err());
+    assert!(queue.try_issue(0, alu_result()).is_ok());
+    assert!(queue.try_issue(0, alu_result()).is_ok());
+    assert!(queue.try_issue(0, alu_result()).is_err());
+
+    let classes = queue.stats().classes;
+    assert_eq!((classes.gmem.admitted, classes.gmem.over_quota), (2, 1));
+    assert_eq!(
+        (classes.execute.admitted, classes.execute.over_quota),
+        (2, 1)
+    );
+    assert_eq!(queue.stats().queue_full_rejects, 0);
+}
+
+#[test]
+fn class_borrows_slots_nobody_is_owed() {
+    let mut cfg = WritebackConfig::default();
+    cfg.enabled = true;
+    cfg.queue.base_latency = 4;
+    cfg.queue.queue_capacity = 5;
+    cfg.quota.gmem = 1;
+    cfg.quota.smem = 1;
+    let mut queue = WritebackQueue::new(cfg);
+
+    // Two slots each leaves a fifth that either class may take.
+    for _ in 0..3 {
+        assert!(queue.try_issue(0, gmem_load()).is_ok());
+    }
+    assert!(queue.try_issue(0, gmem_load()).is_err());
+    let smem = SmemCompletion {
+        request: SmemRequest::new(0, 4, 0xF, false, 0),
+        ticket_ready_at: 0,
+        completed_at: 0,
+    };
+    for _ in 0..2 {
+        assert!(queue
+            .try_issue(0, WritebackPayload::Smem(smem.clone()))
+            .is_ok());
+    }
+    let classes = queue.stats().classes;
+    assert_eq!((classes.gmem.admitted, classes.gmem.over_quota), (3, 1));
+    assert_eq!((classes.smem.admitted, classes.smem.starved), (2, 0));
+}
+
+#[test]
+fn starved_class_is_counted_once_per_cycle() {
+    let mut cfg = WritebackConfig::default();
+    cfg.enabled = true;
+    cfg.queue.base_latency = 4;
+    cfg.queue.queue_capacity = 2;
+    // Every class is owed a slot, which is more than the queue holds.
+    cfg.quota.gmem = 1;
+    cfg.quota.smem = 1;
+    cfg.quota.execute = 1;
+    cfg.quota.tensor = 1;
+    let mut queue = WritebackQueue::new(cfg);
+
+    assert!(queue.try_issue(0, gmem_load()).is_ok());
+    assert!(queue
+        .try_issue(0, WritebackPayload::Tensor(TensorWriteback { bytes: 64 }))
+        .is_ok());
+    assert!(queue.try_issue(0, alu_result()).is_err());
+    assert!(queue.try_issue(0, alu_result()).is_err());
+    assert!(queue.try_issue(1, alu_result()).is_err());
+
+    let execute = queue.stats().classes.execute;
+    assert_eq!((execute.rejected, execute.starved), (3, 3));
+    assert_eq!(execute.starved_cycles, 2);
+
+    // The slot comes back once a writeback drains.
+    queue.tick(4);
+    assert!(queue.try_issue(4, alu_result()).is_ok());
+}
@@ -4,6 +4,7 @@ use std::ops::AddAssign;
 use serde::Deserialize;
 use serde::Serialize;
 
+use crate::check;
 use crate::timeflow::simple_queue::SimpleTimedQueue;
 pub use crate::timeflow::types::RejectReason as WritebackRejectReason;
 use crate::timeq::{Cycle, ServerConfig, Ticket};
@@ -11,10 +12,86 @@ use crate::timeq::{Cycle, ServerConfig, Ticket};
 use super::gmem::GmemCompletion;
 use super::smem::SmemCompletion;
 
+/// An execute-unit result; see `WritebackConfig::execute_results`.
+#[derive(Debug, Clone, Copy)]
+pub struct ExecuteWriteback {
+    pub warp: usize,
+    pub active_lanes: u32,
+}
+
+/// The result of a finished tensor job; see `WritebackConfig::tensor_results`.
+#[derive(Debug, Clone, Copy)]
+pub struct TensorWriteback {
+    pub bytes: u32,
+}
+
 #[derive(Debug, Clone)]
 pub enum WritebackPayload {
     Gmem(GmemCompletion),
     Smem(SmemCompletion),
+    Execute(ExecuteWriteback),
+    Tensor(TensorWriteback),
+}
+
+impl WritebackPayload {
+    /// Bytes this completion writes into the register file; stores write
+    /// nothing back.
+    pub fn data_bytes(&self) -> u32 {
+        match self {
+            Self::Gmem(completion) if completion.request.is_load => completion.request.bytes,
+            Self::Smem(completion) if !completion.request.is_store => completion.request.bytes,
+            Self::Execute(result) => result.active_lanes.saturating_mul(4),
+            Self::Tensor(result) => result.bytes,
+            _ => 0,
+        }
+    }
+
+    pub fn class(&self) -> WritebackClass {
+        match self {
+            Self::Gmem(_) => WritebackClass::Gmem,
+            Self::Smem(_) => WritebackClass::Smem,
+            Self::Execute(_) => WritebackClass::Execute,
+            Self::Tensor(_) => WritebackClass::Tensor,
+        }
+    }
+
+    /// Completions of the same instruction share a warp and request id.
+    /// Execute and tensor results never merge.
+    fn merge_key(&self) -> Option<(usize, bool, u64)> {
+        match self {
+            Self::Gmem(completion) => Some((completion.request.warp, false, completion.request.id)),
+            Self::Smem(completion) => Some((completion.request.warp, true, completion.request.id)),
+            Self::Execute(_) | Self::Tensor(_) => None,
+        }
+    }
+}
+
+/// Where a writeback comes from, for `[writeback.quota]`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WritebackClass {
+    Gmem,
+    Smem,
+    Execute,
+    Tensor,
+}
+
+impl WritebackClass {
+    pub const ALL: [Self; 4] = [Self::Gmem, Self::Smem, Self::Execute, Self::Tensor];
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Completions that share one writeback slot.
+type WritebackGroup = Vec<WritebackPayload>;
+
+#[derive(Clone)]
+struct OpenGroup {
+    key: (usize, bool, u64),
+    opened_at: Cycle,
+    payloads: WritebackGroup,
 }
 
 #[derive(Debug, Clone)]
@@ -24,12 +101,98 @@ pub struct WritebackIssue {
 
 pub type WritebackReject = crate::timeflow::types::Reject;
 
+/// Admissions of one writeback class.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct WritebackClassCounters {
+    pub admitted: u64,
+    /// Turned away because the queue was full.
+    pub rejected: u64,
+    /// Turned away for holding its share while other classes had a claim
+    /// on the free slots.
+    pub over_quota: u64,
+    /// Rejects while the class held less than its share, i.e. while other
+    /// classes occupied slots it was owed.
+    pub starved: u64,
+    /// Cycles with at least one `starved` reject.
+    pub starved_cycles: u64,
+}
+
+impl AddAssign<&WritebackClassCounters> for WritebackClassCounters {
+    fn add_assign(&mut self, other: &WritebackClassCounters) {
+        self.admitted = self.admitted.saturating_add(other.admitted);
+        self.rejected = self.rejected.saturating_add(other.rejected);
+        self.over_quota = self.over_quota.saturating_add(other.over_quota);
+        self.starved = self.starved.saturating_add(other.starved);
+        self.starved_cycles = self.starved_cycles.saturating_add(other.starved_cycles);
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct WritebackClassStats {
+    pub gmem: WritebackClassCounters,
+    pub smem: WritebackClassCounters,
+    pub execute: WritebackClassCounters,
+    pub tensor: WritebackClassCounters,
+}
+
+impl WritebackClassStats {
+    pub fn get(&self, class: WritebackClass) -> &WritebackClassCounters {
+        match class {
+            WritebackClass::Gmem => &self.gmem,
+            WritebackClass::Smem => &self.smem,
+            WritebackClass::Execute => &self.execute,
+            WritebackClass::Tensor => &self.tensor,
+        }
+    }
+
+    fn get_mut(&mut self, class: WritebackClass) -> &mut WritebackClassCounters {
+        match class {
+            WritebackClass::Gmem => &mut self.gmem,
+            WritebackClass::Smem => &mut self.smem,
+            WritebackClass::Execute => &mut self.execute,
+            WritebackClass::Tensor => &mut self.tensor,
+        }
+    }
+}
+
+impl AddAssign<&WritebackClassStats> for WritebackClassStats {
+    fn add_assign(&mut self, other: &WritebackClassStats) {
+        self.gmem += &other.gmem;
+        self.smem += &other.smem;
+        self.execute += &other.execute;
+        self.tensor += &other.tensor;
+    }
+}
+
 #[derive(Debug, Clone, Copy, Default, Serialize)]
 pub struct WritebackStats {
     pub issued: u64,
     pub completed: u64,
     pub queue_full_rejects: u64,
     pub busy_rejects: u64,
+    /// Load data moved over the register-file write bus.
+    pub bus_bytes: u64,
+    /// Bus bytes available over the cycles the bus was modelled.
+    pub bus_capacity_bytes: u64,
+    /// Cycles the bus moved any data.
+    pub bus_busy_cycles: u64,
+    /// Completion-cycles spent waiting on the bus behind earlier data.
+    pub bus_wait_cycles: u64,
+    /// Completions folded into an earlier completion's writeback slot.
+    pub merged_completions: u64,
+    /// Writeback slots used; equals `issued` when merging is off.
+    pub writeback_slots: u64,
+    pub classes: WritebackClassStats,
+}
+
+impl WritebackStats {
+    pub fn bus_utilization(&self) -> f64 {
+        if self.bus_capacity_bytes == 0 {
+            0.0
+        } else {
+            self.bus_bytes as f64 / self.bus_capacity_bytes as f64
+        }
+    }
 }
 
 impl AddAssign<&WritebackStats> for WritebackStats {
@@ -40,6 +203,17 @@ impl AddAssign<&WritebackStats> for WritebackStats {
             .queue_full_rejects
             .saturating_add(other.queue_full_rejects);
         self.busy_rejects = self.busy_rejects.saturating_add(other.busy_rejects);
+        self.bus_bytes = self.bus_bytes.saturating_add(other.bus_bytes);
+        self.bus_capacity_bytes = self
+            .bus_capacity_bytes
+            .saturating_add(other.bus_capacity_bytes);
+        self.bus_busy_cycles = self.bus_busy_cycles.saturating_add(other.bus_busy_cycles);
+        self.bus_wait_cycles = self.bus_wait_cycles.saturating_add(other.bus_wait_cycles);
+        self.merged_completions = self
+            .merged_completions
+            .saturating_add(other.merged_completions);
+        self.writeback_slots = self.writeback_slots.saturating_add(other.writeback_slots);
+        self.classes += &other.classes;
     }
 }
 
@@ -47,6 +221,22 @@ impl AddAssign<&WritebackStats> for WritebackStats {
 #[serde(default)]
 pub struct WritebackConfig {
     pub enabled: bool,
+    /// Width of the register-file write bus shared by completing loads
+    /// (0 = unlimited). Wider loads take several cycles to write back.
+    pub bus_bytes_per_cycle: u32,
+    /// Completions of one instruction (same warp and request) arriving within
+    /// this many cycles of the first share a writeback slot (0 = no
+    /// merging). The first completion waits out the window.
+    pub merge_window: u64,
+    /// Completions for warps that have already exited skip the writeback
+    /// queue instead of taking a slot for a register nobody will read.
+    pub suppress_exited: bool,
+    /// Execute-unit results also take a writeback slot, holding their warp
+    /// until it drains. Units with a reservation station are left out.
+    pub execute_results: bool,
+    /// Finished tensor jobs also take a writeback slot.
+    pub tensor_results: bool,
+    pub quota: WritebackQuotaConfig,
     #[serde(flatten)]
     pub queue: ServerConfig,
 }
@@ -55,6 +245,12 @@ impl Default for WritebackConfig {
     fn default() -> Self {
         Self {
             enabled: false,
+            bus_bytes_per_cycle: 0,
+            merge_window: 0,
+            suppress_exited: false,
+            execute_results: false,
+            tensor_results: false,
+            quota: WritebackQuotaConfig::default(),
             queue: ServerConfig {
                 base_latency: 0,
                 bytes_per_cycle: 1024,
@@ -66,9 +262,64 @@ impl Default for WritebackConfig {
     }
 }
 
+/// `[writeback.quota]`: each class's weight in the writeback slots. A class
+/// is owed `queue_capacity * weight / total` slots (at least one), and may
+/// take more only while they are not owed to another class. All weights 0
+/// turns quotas off.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(default)]
+pub struct WritebackQuotaConfig {
+    pub gmem: u32,
+    pub smem: u32,
+    pub execute: u32,
+    pub tensor: u32,
+}
+
+impl WritebackQuotaConfig {
+    pub fn weight(&self, class: WritebackClass) -> u32 {
+        match class {
+            WritebackClass::Gmem => self.gmem,
+            WritebackClass::Smem => self.smem,
+            WritebackClass::Execute => self.execute,
+            WritebackClass::Tensor => self.tensor,
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        WritebackClass::ALL
+            .iter()
+            .any(|&class| self.weight(class) > 0)
+    }
+
+    /// Slots owed to each class out of `capacity`.
+    fn shares(&self, capacity: usize) -> [usize; 4] {
+        let total: u64 = WritebackClass::ALL
+            .iter()
+            .map(|&class| self.weight(class) as u64)
+            .sum();
+        WritebackClass::ALL.map(|class| match self.weight(class) as u64 {
+            0 => 0,
+            weight => ((capacity as u64 * weight / total) as usize).max(1),
+        })
+    }
+}
+
+#[derive(Clone)]
 pub struct WritebackQueue {
-    queue: SimpleTimedQueue<WritebackPayload>,
+    queue: SimpleTimedQueue<WritebackGroup>,
+    merge_window: u64,
+    /// Groups still collecting completions, oldest first; bounded by the
+    /// queue capacity.
+    open: VecDeque<OpenGroup>,
+    open_capacity: usize,
+    bus: VecDeque<(WritebackGroup, u32)>,
+    bus_bytes_per_cycle: u32,
     ready: VecDeque<WritebackPayload>,
+    quotas: bool,
+    shares: [usize; 4],
+    /// Slots each class holds, open groups included.
+    occupancy: [usize; 4],
+    last_starved: [Option<Cycle>; 4],
     stats: WritebackStats,
 }
 
@@ -78,7 +329,16 @@ impl WritebackQueue {
         cfg.base_latency = cfg.base_latency.max(0);
         Self {
             queue: SimpleTimedQueue::new(config.enabled, cfg),
+            merge_window: config.merge_window,
+            open: VecDeque::new(),
+            open_capacity: cfg.queue_capacity.max(1),
+            bus: VecDeque::new(),
+            bus_bytes_per_cycle: config.bus_bytes_per_cycle,
             ready: VecDeque::new(),
+            quotas: config.quota.enabled(),
+            shares: config.quota.shares(cfg.queue_capacity.max(1)),
+            occupancy: [0; 4],
+            last_starved: [None; 4],
             stats: WritebackStats::default(),
         }
     }
@@ -88,38 +348,199 @@ impl WritebackQueue {
         now: Cycle,
         payload: WritebackPayload,
     ) -> Result<WritebackIssue, WritebackReject> {
+        let class = payload.class();
         if !self.queue.is_enabled() {
             self.ready.push_back(payload);
-            self.stats.issued = self.stats.issued.saturating_add(1);
+            self.record_admitted(class);
             return Ok(WritebackIssue {
                 ticket: Ticket::new(now, now, 0),
             });
         }
 
-        match self.queue.try_issue(now, payload, 0) {
-            Ok(ticket) => {
-                self.stats.issued = self.stats.issued.saturating_add(1);
-                Ok(WritebackIssue { ticket })
+        let key = payload.merge_key().filter(|_| self.merge_window > 0);
+        let open = key.and_then(|key| self.open.iter_mut().find(|group| group.key == key));
+        if let Some(group) = open {
+            group.payloads.push(payload);
+            let closes_at = group.opened_at.saturating_add(self.merge_window);
+            self.stats.merged_completions = self.stats.merged_completions.saturating_add(1);
+            self.record_admitted(class);
+            return Ok(WritebackIssue {
+                ticket: Ticket::new(now, closes_at, 0),
+            });
+        }
+        if !self.admits(class) {
+            let counters = self.stats.classes.get_mut(class);
+            counters.over_quota = counters.over_quota.saturating_add(1);
+            return Err(WritebackReject::new(
+                now.saturating_add(1),
+                WritebackRejectReason::QueueFull,
+            ));
+        }
+
+        let result = match key {
+            Some(key) => self.open_group(now, key, payload),
+            None => self.queue.try_issue(now, vec![payload], 0).map(|ticket| {
+                self.stats.writeback_slots = self.stats.writeback_slots.saturating_add(1);
+                WritebackIssue { ticket }
+            }),
+        };
+        match &result {
+            Ok(_) => {
+                self.record_admitted(class);
+                self.occupancy[class.index()] += 1;
             }
             Err(err) => {
-                match err.reason {
-                    WritebackRejectReason::QueueFull => {
-                        self.stats.queue_full_rejects =
-                            self.stats.queue_full_rejects.saturating_add(1);
-                    }
-                    WritebackRejectReason::Busy => {
-                        self.stats.busy_rejects = self.stats.busy_rejects.saturating_add(1);
-                    }
+                self.record_reject(err.reason);
+                self.record_class_reject(now, class);
+            }
+        }
+        result
+    }
+
+    /// Opens a group that completions of the same instruction join until
+    /// its merge window closes.
+    fn open_group(
+        &mut self,
+        now: Cycle,
+        key: (usize, bool, u64),
+        payload: WritebackPayload,
+    ) -> Result<WritebackIssue, WritebackReject> {
+        if self.open.len() >= self.open_capacity {
+            return Err(WritebackReject::new(
+                now.saturating_add(1),
+                WritebackRejectReason::QueueFull,
+            ));
+        }
+        self.open.push_back(OpenGroup {
+            key,
+            opened_at: now,
+            payloads: vec![payload],
+        });
+        Ok(WritebackIssue {
+            ticket: Ticket::new(now, now.saturating_add(self.merge_window), 0),
+        })
+    }
+
+    /// Whether `class` may take another slot: always while it holds less
+    /// than its share, otherwise only if that leaves every other class the
+    /// rest of its share.
+    fn admits(&self, class: WritebackClass) -> bool {
+        let idx = class.index();
+        if !self.quotas || self.occupancy[idx] < self.shares[idx] {
+            return true;
+        }
+        let used: usize = self.occupancy.iter().sum();
+        let owed: usize = WritebackClass::ALL
+            .iter()
+            .filter(|&&other| other != class)
+            .map(|&other| {
+                let idx = other.index();
+                self.shares[idx].saturating_sub(self.occupancy[idx])
+            })
+            .sum();
+        used + owed < self.open_capacity
+    }
+
+    fn record_admitted(&mut self, class: WritebackClass) {
+        self.stats.issued = self.stats.issued.saturating_add(1);
+        let counters = self.stats.classes.get_mut(class);
+        counters.admitted = counters.admitted.saturating_add(1);
+    }
+
+    fn record_class_reject(&mut self, now: Cycle, class: WritebackClass) {
+        let idx = class.index();
+        let counters = self.stats.classes.get_mut(class);
+        counters.rejected = counters.rejected.saturating_add(1);
+        if !self.quotas || self.occupancy[idx] >= self.shares[idx] {
+            return;
+        }
+        counters.starved = counters.starved.saturating_add(1);
+        if self.last_starved[idx] != Some(now) {
+            self.last_starved[idx] = Some(now);
+            counters.starved_cycles = counters.starved_cycles.saturating_add(1);
+        }
+    }
+
+    /// Hands groups whose merge window has passed to the writeback queue, in
+    /// the order they opened.
+    fn close_groups(&mut self, now: Cycle) {
+        while let Some(group) = self.open.front() {
+            if now < group.opened_at.saturating_add(self.merge_window) {
+                break;
+            }
+            let Some(group) = self.open.pop_front() else {
+                break;
+            };
+            match self.queue.try_issue_with_payload(now, group.payloads, 0) {
+                Ok(_) => {
+                    self.stats.writeback_slots = self.stats.writeback_slots.saturating_add(1);
+                }
+                Err(err) => {
+                    self.open.push_front(OpenGroup {
+                        payloads: err.payload,
+                        ..group
+                    });
+                    break;
                 }
-                Err(WritebackReject::new(err.retry_at, err.reason))
+            }
+        }
+    }
+
+    fn record_reject(&mut self, reason: WritebackRejectReason) {
+        match reason {
+            WritebackRejectReason::QueueFull | WritebackRejectReason::FlushLimit => {
+                self.stats.queue_full_rejects = self.stats.queue_full_rejects.saturating_add(1);
+            }
+            WritebackRejectReason::Busy => {
+                self.stats.busy_rejects = self.stats.busy_rejects.saturating_add(1);
             }
         }
     }
 
     pub fn tick(&mut self, now: Cycle) {
-        self.queue.tick(now, |payload| {
-            self.ready.push_back(payload);
+        if !self.open.is_empty() {
+            self.close_groups(now);
+        }
+        if self.bus_bytes_per_cycle == 0 {
+            self.queue.tick(now, |group| {
+                leave(&mut self.occupancy, &group);
+                self.ready.extend(group);
+            });
+            return;
+        }
+        self.queue.tick(now, |group| {
+            leave(&mut self.occupancy, &group);
+            let bytes = group.iter().map(WritebackPayload::data_bytes).sum();
+            self.bus.push_back((group, bytes));
         });
+        self.drive_bus();
+    }
+
+    /// Moves up to one bus width of load data, oldest completion first.
+    fn drive_bus(&mut self) {
+        let width = self.bus_bytes_per_cycle;
+        self.stats.bus_capacity_bytes = self.stats.bus_capacity_bytes.saturating_add(width as u64);
+        let mut budget = width;
+        while let Some((_, remaining)) = self.bus.front_mut() {
+            let moved = (*remaining).min(budget);
+            *remaining -= moved;
+            budget -= moved;
+            if *remaining > 0 {
+                break;
+            }
+            if let Some((group, _)) = self.bus.pop_front() {
+                self.ready.extend(group);
+            }
+        }
+        let moved = width - budget;
+        if moved > 0 {
+            self.stats.bus_bytes = self.stats.bus_bytes.saturating_add(moved as u64);
+            self.stats.bus_busy_cycles = self.stats.bus_busy_cycles.saturating_add(1);
+        }
+        self.stats.bus_wait_cycles = self
+            .stats
+            .bus_wait_cycles
+            .saturating_add(self.bus.len() as u64);
     }
 
     pub fn pop_ready(&mut self) -> Option<WritebackPayload> {
@@ -146,3 +567,16 @@ impl WritebackQueue {
         self.stats = WritebackStats::default();
     }
 }
+
+/// A group left the queue: its class gets the slot back.
+fn leave(occupancy: &mut [usize; 4], group: &WritebackGroup) {
+    if let Some(payload) = group.first() {
+        let held = &mut occupancy[payload.class().index()];
+        check!(
+            Inflight,
+            *held > 0,
+            "writeback group left a queue its class held no slot in"
+        );
+        *held = held.saturating_sub(1);
+    }
+}
@@ -14,7 +14,7 @@ pub fn module_now<M: IsModule>(module: &M) -> Cycle {
 }
 
 // Result of queueing a request with a timed server
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Ticket {
     issued_at: Cycle,
     ready_at: Cycle,
@@ -72,7 +72,7 @@ impl<T> ServiceRequest<T> {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ServiceResult<T> {
     pub payload: T,
     pub ticket: Ticket,
@@ -101,6 +101,13 @@ impl<T> Backpressure<T> {
             Backpressure::Busy { request, .. } => request,
         }
     }
+
+    pub fn request_mut(&mut self) -> &mut ServiceRequest<T> {
+        match self {
+            Backpressure::QueueFull { request, .. } => request,
+            Backpressure::Busy { request, .. } => request,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, Deserialize)]
@@ -130,7 +137,7 @@ impl Default for ServerConfig {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 struct Inflight<T> {
     payload: T,
     ticket: Ticket,
@@ -145,11 +152,12 @@ pub struct ServerStats {
     pub bytes_issued: u64,
     pub bytes_completed: u64,
     pub max_outstanding: u64,
+    pub squashed: u64,
 }
 
 // Single-lane server that enforces the configured latency/bandwidth budget and
 // keeps track of outstanding work
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct TimedServer<T> {
     config: ServerConfig,
     inflight: VecDeque<Inflight<T>>,
@@ -187,6 +195,18 @@ impl<T> TimedServer<T> {
         &mut self,
         now: Cycle,
         request: ServiceRequest<T>,
+    ) -> Result<Ticket, Backpressure<T>> {
+        let service_bytes = request.size_bytes;
+        self.try_enqueue_charged(now, request, service_bytes)
+    }
+
+    // Like try_enqueue, but occupies the server for service_bytes instead of the request's size
+    // The ticket still carries the request's own size downstream
+    pub fn try_enqueue_charged(
+        &mut self,
+        now: Cycle,
+        request: ServiceRequest<T>,
+        service_bytes: u32,
     ) -> Result<Ticket, Backpressure<T>> {
         if self.outstanding_len() >= self.config.queue_capacity {
             self.stats.queue_full_rejects = self.stats.queue_full_rejects.saturating_add(1);
@@ -205,10 +225,7 @@ impl<T> TimedServer<T> {
         }
 
         let start = self.next_issue_at.max(now);
-        let service_cycles = ceil_div_u64(
-            request.size_bytes as u64,
-            self.config.bytes_per_cycle as u64,
-        );
+        let service_cycles = ceil_div_u64(service_bytes as u64, self.config.bytes_per_cycle as u64);
         let ready_at = start
             .saturating_add(self.config.base_latency)
             .saturating_add(service_cycles);
@@ -231,6 +248,15 @@ impl<T> TimedServer<T> {
     }
 
     // Drain any requests that have completed and invoke the callback with the results
+    // Occupy the server for bytes of traffic that carries no payload, starting no earlier than now
+    // Returns the cycle the transfer finishes; the transfer shows up in no stats
+    pub fn reserve(&mut self, now: Cycle, bytes: u32) -> Cycle {
+        let start = self.next_issue_at.max(now).max(self.warmup_until);
+        let service_cycles = ceil_div_u64(bytes as u64, self.config.bytes_per_cycle as u64);
+        self.next_issue_at = start.saturating_add(service_cycles);
+        self.next_issue_at
+    }
+
     pub fn service_ready<F>(&mut self, now: Cycle, mut callback: F)
     where
         F: FnMut(ServiceResult<T>),
@@ -242,6 +268,21 @@ impl<T> TimedServer<T> {
         self.update_idle(now);
     }
 
+    // Drop queued requests matching the predicate before they complete and return how many were
+    // dropped. Completions already waiting to be drained are kept. Service time reserved by a
+    // dropped request is not handed back, so later tickets keep their ready times.
+    pub fn squash<F>(&mut self, mut pred: F) -> usize
+    where
+        F: FnMut(&T, &Ticket) -> bool,
+    {
+        let before = self.inflight.len();
+        self.inflight
+            .retain(|inflight| !pred(&inflight.payload, &inflight.ticket));
+        let squashed = before - self.inflight.len();
+        self.stats.squashed = self.stats.squashed.saturating_add(squashed as u64);
+        squashed
+    }
+
     // Returns the earliest cycle that a new request can begin service
     pub fn available_at(&self) -> Cycle {
         self.next_issue_at.max(self.warmup_until)
@@ -321,6 +362,24 @@ impl<T> TimedServer<T> {
     pub fn set_warmup_until(&mut self, cycle: Cycle) {
         self.warmup_until = cycle;
     }
+
+    // Swap latency/bandwidth/capacity mid-run. Requests already accepted keep
+    // their tickets; only later enqueues see the new parameters. If the new
+    // capacity is below the current occupancy, new requests are rejected until
+    // enough drain.
+    pub fn set_config(&mut self, config: ServerConfig) {
+        assert!(config.bytes_per_cycle > 0, "bytes_per_cycle must be > 0");
+        assert!(config.queue_capacity > 0, "queue_capacity must be > 0");
+        assert!(
+            config.completions_per_cycle > 0,
+            "completions_per_cycle must be > 0"
+        );
+        self.config = config;
+    }
+
+    pub fn config(&self) -> ServerConfig {
+        self.config
+    }
 }
 
 fn ceil_div_u64(nom: u64, denom: u64) -> Cycle {
@@ -899,4 +958,37 @@ mod tests {
         assert_eq!(12, stats.bytes_completed);
         assert_eq!(2, stats.max_outstanding);
     }
+
+    #[test]
+    // A config swap applies to requests enqueued afterwards; tickets already
+    // handed out keep their original ready cycle
+    fn set_config_only_affects_later_requests() {
+        let mut server = make_server(ServerConfig {
+            base_latency: 2,
+            bytes_per_cycle: 4,
+            queue_capacity: 4,
+            ..ServerConfig::default()
+        });
+
+        let t0 = server
+            .try_enqueue(0, ServiceRequest::new("req0", 4))
+            .unwrap();
+        server.set_config(ServerConfig {
+            base_latency: 10,
+            bytes_per_cycle: 4,
+            queue_capacity: 1,
+            ..ServerConfig::default()
+        });
+        assert_eq!(3, t0.ready_at());
+        // Occupancy already meets the new capacity
+        assert!(server
+            .try_enqueue(0, ServiceRequest::new("req1", 4))
+            .is_err());
+
+        assert!(server.pop_ready(3).is_some());
+        let t1 = server
+            .try_enqueue(3, ServiceRequest::new("req1", 4))
+            .unwrap();
+        assert_eq!(14, t1.ready_at());
+    }
 }
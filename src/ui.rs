@@ -1,9 +1,20 @@
 use crate::muon::config::MuonConfig;
 use crate::neutrino::config::NeutrinoConfig;
-use crate::sim::config::{Config, MemConfig, SimConfig};
-use crate::sim::top::Sim;
+use crate::sim::analyze::analyze_config;
+use crate::sim::calibrate::{calibrate, CalibrationOptions, CalibrationTargets};
+use crate::sim::config::{Config, LogConfig, MemConfig, SimConfig};
+use crate::sim::diff::{find_divergence, DiffOptions};
+use crate::sim::journal::{self, JournalEvent};
+use crate::sim::latency_probe::probe_latency;
+use crate::sim::minimize::{minimize, run_to_failure, MinimizeOptions};
+use crate::sim::perturb::{perturb_latencies, PerturbationReport, RunMetrics};
+use crate::sim::report::{load_runs, MergedReport};
+use crate::sim::top::{CoreOverride, Sim, TimingPhase};
 use crate::timeflow::CoreGraphConfig;
-use clap::Parser;
+use clap::{Args, CommandFactory, Parser};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
 use toml::{Table, Value};
 
@@ -26,17 +37,678 @@ pub struct CyclotronArgs {
     pub gen_trace: Option<bool>,
     #[arg(long, help = "Enable timing model")]
     pub timing: bool,
+    #[arg(long, help = "Skip all stats, logs and traces (see sim.fast)")]
+    pub fast: bool,
+    #[arg(long, help = "Exclude the first N cycles from reported timing stats")]
+    pub warmup_cycles: Option<u64>,
+    #[arg(
+        long,
+        value_name = "CYCLE",
+        help = "Checkpoint at CYCLE and check that a run restored from it ends the same (see sim.verify_checkpoint)"
+    )]
+    pub verify_checkpoint: Option<u64>,
+    #[arg(
+        long,
+        value_name = "cycle=N:FILE",
+        value_parser = parse_phase_arg,
+        help = "Apply timing overrides from FILE after N cycles (repeatable)"
+    )]
+    pub phase_config: Vec<PhaseArg>,
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with = "replay",
+        help = "Record host time and SIGINTs to a journal for --replay"
+    )]
+    pub record: Option<PathBuf>,
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Replay a journal from --record or CYCLOTRON_RECORD (a DPI journal ignores the config)"
+    )]
+    pub replay: Option<PathBuf>,
 }
 
+/// One `--phase-config cycle=N:FILE` argument.
+#[derive(Debug, Clone)]
+pub struct PhaseArg {
+    pub cycle: u64,
+    pub path: PathBuf,
+}
+
+fn parse_phase_arg(arg: &str) -> Result<PhaseArg, String> {
+    let (cycle, path) = arg
+        .strip_prefix("cycle=")
+        .and_then(|rest| rest.split_once(':'))
+        .ok_or_else(|| format!("expected cycle=N:FILE, got '{arg}'"))?;
+    let cycle = cycle
+        .parse()
+        .map_err(|err| format!("invalid cycle '{cycle}': {err}"))?;
+    Ok(PhaseArg {
+        cycle,
+        path: PathBuf::from(path),
+    })
+}
+
+/// Subcommands that replace the default `cyclotron <config.toml>` run.
+#[derive(Parser)]
+#[command(name = "cyclotron", version, about)]
+pub enum CyclotronCommand {
+    /// Run two configurations lockstep and report the first instruction
+    /// where their committed state diverges
+    Diff(DiffArgs),
+    /// Report cache sizes and bandwidths derived from a config and warn
+    /// about likely mismatches, without running anything
+    AnalyzeConfig(AnalyzeConfigArgs),
+    /// Measure load-to-use latency per cache level by pointer-chasing
+    /// through the gmem timing model
+    ProbeLatency(ProbeLatencyArgs),
+    /// Fit timing parameters to measured latencies and bandwidths and
+    /// write out a calibrated timing config
+    Calibrate(CalibrateArgs),
+    /// Rerun a config with every core's latencies randomly jittered and
+    /// report the spread of cycles, IPC and memory latency
+    Perturb(PerturbArgs),
+    /// Merge the perf summaries of a sweep into one table of config
+    /// differences, metrics and speedups
+    Report(ReportArgs),
+    /// Shrink a config that panics or deadlocks to a small one that still
+    /// fails the same way, and bundle it up to reproduce the failure
+    Minimize(MinimizeArgs),
+}
+
+#[derive(Args)]
+pub struct DiffArgs {
+    #[arg(help = "Path to the first config.toml")]
+    pub config_a: PathBuf,
+    #[arg(help = "Path to the second config.toml")]
+    pub config_b: PathBuf,
+    #[arg(long, help = "Override binary path for both runs")]
+    pub binary_path: Option<PathBuf>,
+    #[arg(
+        long,
+        default_value_t = 1000,
+        help = "Committed instructions per warp between comparisons"
+    )]
+    pub interval: u64,
+    #[arg(long, help = "Stop after N cycles (default: sim timeout)")]
+    pub max_cycles: Option<u64>,
+    #[arg(long, help = "Write the divergence report as JSON to this path")]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Args)]
+pub struct AnalyzeConfigArgs {
+    #[arg(help = "Path to config.toml")]
+    pub config_path: PathBuf,
+    #[arg(long, help = "Print the analysis as JSON")]
+    pub json: bool,
+}
+
+#[derive(Args)]
+pub struct ProbeLatencyArgs {
+    #[arg(help = "Path to config.toml")]
+    pub config_path: PathBuf,
+    #[arg(long, default_value_t = 256, help = "Timed loads per chase")]
+    pub samples: u64,
+    #[arg(long, help = "Print the measurements as JSON")]
+    pub json: bool,
+}
+
+#[derive(Args)]
+pub struct CalibrateArgs {
+    #[arg(help = "Path to config.toml")]
+    pub config_path: PathBuf,
+    #[arg(help = "TOML file of measured numbers (l1_hit, l2_hit, dram, smem, dram_bandwidth)")]
+    pub targets_path: PathBuf,
+    #[arg(
+        long,
+        default_value = "calibrated.toml",
+        help = "Where to write the calibrated timing config"
+    )]
+    pub output: PathBuf,
+    #[arg(long, default_value_t = 64, help = "Timed loads per latency probe")]
+    pub samples: u64,
+    #[arg(long, help = "Print the fit as JSON")]
+    pub json: bool,
+}
+
+#[derive(Args)]
+pub struct PerturbArgs {
+    #[arg(help = "Path to config.toml")]
+    pub config_path: PathBuf,
+    #[arg(long, help = "Override binary path")]
+    pub binary_path: Option<PathBuf>,
+    #[arg(long, default_value_t = 8, help = "Number of perturbed runs")]
+    pub runs: u64,
+    #[arg(
+        long,
+        default_value_t = 5.0,
+        help = "Largest change to each latency, in percent"
+    )]
+    pub jitter: f64,
+    #[arg(
+        long,
+        default_value_t = 0,
+        help = "Seed of the first run; run i uses seed + i"
+    )]
+    pub seed: u64,
+    #[arg(long, help = "Write the runs and their spread as JSON to this path")]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Args)]
+pub struct ReportArgs {
+    #[arg(long, help = "Directory to read summary.json files from, recursively")]
+    pub merge: PathBuf,
+    #[arg(long, help = "Run to compute speedups against (default: the first)")]
+    pub baseline: Option<String>,
+    #[arg(long, help = "Write a markdown table instead of CSV")]
+    pub markdown: bool,
+    #[arg(long, help = "Write the table to this path instead of stdout")]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Args)]
+pub struct MinimizeArgs {
+    #[arg(help = "Path to config.toml")]
+    pub config_path: PathBuf,
+    #[arg(long, help = "Override binary path")]
+    pub binary_path: Option<PathBuf>,
+    #[arg(long, help = "Enable timing model")]
+    pub timing: bool,
+    #[arg(long, default_value_t = 200, help = "Most runs to try")]
+    pub max_runs: u64,
+    #[arg(
+        long,
+        default_value_t = 1000,
+        help = "Cycles each run goes on past the failing cycle"
+    )]
+    pub slack: u64,
+    #[arg(
+        long,
+        default_value = "repro",
+        help = "Directory to write the reproduction bundle to"
+    )]
+    pub output: PathBuf,
+}
+
+/// Parses a subcommand if the first argument names one; otherwise the
+/// caller falls back to `CyclotronArgs`.
+pub fn parse_command() -> Option<CyclotronCommand> {
+    let first = std::env::args().nth(1)?;
+    CyclotronCommand::command()
+        .get_subcommands()
+        .any(|cmd| cmd.get_name() == first)
+        .then(CyclotronCommand::parse)
+}
+
+pub fn run_command(command: CyclotronCommand) -> Result<(), u32> {
+    match command {
+        CyclotronCommand::Diff(args) => run_diff(&args),
+        CyclotronCommand::AnalyzeConfig(args) => run_analyze_config(&args),
+        CyclotronCommand::ProbeLatency(args) => run_probe_latency(&args),
+        CyclotronCommand::Calibrate(args) => run_calibrate(&args),
+        CyclotronCommand::Perturb(args) => run_perturb(&args),
+        CyclotronCommand::Report(args) => run_report(&args),
+        CyclotronCommand::Minimize(args) => run_minimize(&args),
+    }
+}
+
+/// Parses `config_path` and the timing config it points at.
+fn load_config_file(config_path: &Path) -> Result<(Option<Table>, CoreGraphConfig), u32> {
+    let toml_string = read_toml(config_path);
+    let config_table: Table = toml::from_str(&toml_string).unwrap_or_else(|err| {
+        eprintln!("cyclotron: cannot parse {}: {}", config_path.display(), err);
+        std::process::exit(1);
+    });
+    let config_table = Some(config_table);
+    let timing_value = load_timing_value(Some(config_path), config_table.as_ref());
+    let timing_config: CoreGraphConfig = timing_value.try_into().map_err(|err| {
+        eprintln!("cyclotron: invalid timing config: {err}");
+        1u32
+    })?;
+    Ok((config_table, timing_config))
+}
+
+fn run_probe_latency(args: &ProbeLatencyArgs) -> Result<(), u32> {
+    let (_, timing_config) = load_config_file(&args.config_path)?;
+    let report = probe_latency(&timing_config, args.samples);
+    if args.json {
+        let report = serde_json::to_string_pretty(&report).expect("failed to serialize probe");
+        println!("{report}");
+    } else {
+        print!("{report}");
+    }
+    Ok(())
+}
+
+fn run_calibrate(args: &CalibrateArgs) -> Result<(), u32> {
+    let (config_table, timing_config) = load_config_file(&args.config_path)?;
+    let targets: CalibrationTargets =
+        toml::from_str(&read_toml(&args.targets_path)).map_err(|err| {
+            eprintln!(
+                "cyclotron: invalid targets in {}: {err}",
+                args.targets_path.display()
+            );
+            1u32
+        })?;
+    let sim_config = SimConfig::from_section(maybe_get(&config_table, "sim"));
+    let muon_config = MuonConfig::from_section(maybe_get(&config_table, "muon"));
+    let options = CalibrationOptions {
+        samples: args.samples,
+        num_clusters: sim_config.cluster_count(),
+        cores_per_cluster: muon_config.num_cores,
+    };
+    let (_, report) = calibrate(&timing_config, &targets, &options);
+    if args.json {
+        let report = serde_json::to_string_pretty(&report).expect("failed to serialize fit");
+        println!("{report}");
+    } else {
+        print!("{report}");
+    }
+
+    let mut timing = load_timing_value(Some(&args.config_path), config_table.as_ref());
+    report.apply_to(&mut timing).map_err(|err| {
+        eprintln!("cyclotron calibrate: {err}");
+        1u32
+    })?;
+    let calibrated = toml::to_string(&timing).expect("failed to render timing config");
+    std::fs::write(&args.output, calibrated).map_err(|err| {
+        eprintln!(
+            "cyclotron calibrate: failed to write {}: {err}",
+            args.output.display()
+        );
+        1u32
+    })?;
+    if !args.json {
+        println!("wrote {}", args.output.display());
+    }
+    Ok(())
+}
+
+fn run_perturb(args: &PerturbArgs) -> Result<(), u32> {
+    if args.runs == 0 || !(0.0..100.0).contains(&args.jitter) {
+        eprintln!("cyclotron perturb: need --runs > 0 and 0 <= --jitter < 100");
+        return Err(1);
+    }
+    let (config_table, _) = load_config_file(&args.config_path)?;
+    let config_table = config_table.unwrap_or_default();
+    let timing = load_timing_value(Some(&args.config_path), Some(&config_table));
+    let muon_config = MuonConfig::from_section(config_table.get("muon"));
+    let jitter = args.jitter / 100.0;
+
+    let mut runs = Vec::new();
+    for run in 0..args.runs {
+        let seed = args.seed.wrapping_add(run);
+        let config = perturbed_config(&config_table, &timing, muon_config.num_cores, jitter, seed);
+        let cli_args = CyclotronArgs {
+            config_path: args.config_path.clone(),
+            binary_path: args.binary_path.clone(),
+            gen_trace: Some(false),
+            timing: true,
+            ..CyclotronArgs::default()
+        };
+        let toml_string = toml::to_string(&config).expect("failed to render perturbed config");
+        let mut sim = make_sim(Some(&toml_string), &Some(cli_args));
+        if sim.simulate().is_err() {
+            eprintln!("cyclotron perturb: run {run} (seed {seed}) failed");
+            return Err(1);
+        }
+        runs.push(RunMetrics::from_summaries(
+            seed,
+            sim.cycle(),
+            &sim.timing_summaries(),
+        ));
+    }
+
+    let report = PerturbationReport::new(jitter, runs);
+    print!("{report}");
+    if let Some(path) = &args.output {
+        let json = serde_json::to_string_pretty(&report).expect("failed to serialize runs");
+        std::fs::write(path, json).map_err(|err| {
+            eprintln!(
+                "cyclotron perturb: failed to write {}: {err}",
+                path.display()
+            );
+            1u32
+        })?;
+    }
+    Ok(())
+}
+
+fn run_report(args: &ReportArgs) -> Result<(), u32> {
+    let runs = load_runs(&args.merge).map_err(|err| {
+        eprintln!(
+            "cyclotron report: cannot read {}: {err}",
+            args.merge.display()
+        );
+        1u32
+    })?;
+    let report = MergedReport::new(runs, args.baseline.as_deref()).map_err(|err| {
+        eprintln!("cyclotron report: {err}");
+        1u32
+    })?;
+    let table = if args.markdown {
+        report.to_markdown()
+    } else {
+        report.to_csv()
+    };
+    match &args.output {
+        Some(path) => std::fs::write(path, table).map_err(|err| {
+            eprintln!(
+                "cyclotron report: failed to write {}: {err}",
+                path.display()
+            );
+            1u32
+        }),
+        None => {
+            print!("{table}");
+            Ok(())
+        }
+    }
+}
+
+/// The `[sim]` table of `config`, added if missing.
+fn sim_section(config: &mut Table) -> &mut Table {
+    config
+        .entry("sim")
+        .or_insert_with(|| Table::new().into())
+        .as_table_mut()
+        .expect("[sim] is not a table")
+}
+
+fn run_minimize(args: &MinimizeArgs) -> Result<(), u32> {
+    let (config_table, _) = load_config_file(&args.config_path)?;
+    let mut config = config_table.unwrap_or_default();
+    let timing = load_timing_value(Some(&args.config_path), Some(&config));
+    config.insert("timing".to_string(), timing);
+    let sim = sim_section(&mut config);
+    if let Some(path) = &args.binary_path {
+        sim.insert("elf".into(), path.display().to_string().into());
+    }
+    if args.timing {
+        sim.insert("timing".into(), true.into());
+    }
+    sim.remove("state_dump");
+
+    let run = |config: &Table| {
+        let toml_string = toml::to_string(config).expect("failed to render config");
+        let cli_args = CyclotronArgs {
+            config_path: args.config_path.clone(),
+            gen_trace: Some(false),
+            ..CyclotronArgs::default()
+        };
+        run_to_failure(|| make_sim(Some(&toml_string), &Some(cli_args)))
+    };
+    let options = MinimizeOptions {
+        max_runs: args.max_runs,
+        slack: args.slack,
+    };
+    let Some(report) = minimize(&config, &options, run) else {
+        eprintln!("cyclotron minimize: the config neither panics nor deadlocks");
+        return Err(1);
+    };
+    print!("{report}");
+
+    let fail = |what: &str, path: &Path, err: std::io::Error| {
+        eprintln!(
+            "cyclotron minimize: failed to {what} {}: {err}",
+            path.display()
+        );
+        1u32
+    };
+    let dir = &args.output;
+    std::fs::create_dir_all(dir).map_err(|err| fail("create", dir, err))?;
+    let mut bundled = report.config.clone();
+    let elf = sim_section(&mut bundled)
+        .get("elf")
+        .and_then(Value::as_str)
+        .map(PathBuf::from)
+        .unwrap_or_default();
+    if !elf.as_os_str().is_empty() {
+        let copy = dir.join("kernel.elf");
+        std::fs::copy(&elf, &copy).map_err(|err| fail("copy", &elf, err))?;
+        sim_section(&mut bundled).insert("elf".into(), "kernel.elf".into());
+    }
+    sim_section(&mut bundled).insert("state_dump".into(), "state.json".into());
+    let config_path = dir.join("config.toml");
+    let rendered = toml::to_string(&bundled).expect("failed to render config");
+    std::fs::write(&config_path, rendered).map_err(|err| fail("write", &config_path, err))?;
+
+    // Rerun the bundled config with its paths pointing into the bundle, to
+    // check it and to record the journal and the state dump.
+    let journal_path = dir.join("journal.bin");
+    let mut confirm = bundled.clone();
+    let sim = sim_section(&mut confirm);
+    for (key, file) in [("elf", "kernel.elf"), ("state_dump", "state.json")] {
+        if sim.contains_key(key) {
+            sim.insert(key.into(), dir.join(file).display().to_string().into());
+        }
+    }
+    journal::start_recording(&journal_path).map_err(|err| fail("write", &journal_path, err))?;
+    let confirmed = run(&confirm);
+    journal::stop();
+    if !confirmed.is_some_and(|failure| failure.same_as(&report.original)) {
+        eprintln!("cyclotron minimize: the bundled config did not fail the same way again");
+    }
+
+    let report_path = dir.join("report.json");
+    let json = serde_json::to_string_pretty(&report).expect("failed to serialize report");
+    std::fs::write(&report_path, json).map_err(|err| fail("write", &report_path, err))?;
+    println!("wrote {}", dir.display());
+    Ok(())
+}
+
+/// `config` with its latencies jittered for one run of `cyclotron perturb`.
+/// The `gmem` keys set the cluster-wide memory graph, so they are drawn once
+/// into `[timing]`; everything else is drawn per core into a `[core.N]`
+/// section that spells out that core's whole timing config.
+fn perturbed_config(
+    config: &Table,
+    timing: &Value,
+    num_cores: usize,
+    jitter: f64,
+    seed: u64,
+) -> Table {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut config = config.clone();
+    let mut shared = timing.clone();
+    if let Some(gmem) = shared.get_mut("gmem") {
+        perturb_latencies(gmem, jitter, &mut rng);
+    }
+
+    let user_cores = match config.remove("core") {
+        Some(Value::Table(cores)) => cores,
+        _ => Table::new(),
+    };
+    let mut cores = Table::new();
+    for cid in 0..num_cores {
+        let mut core_timing = timing.clone();
+        if let Value::Table(table) = &mut core_timing {
+            table.remove("gmem");
+        }
+        if let Some(section) = user_cores.get(&cid.to_string()) {
+            merge_values(&mut core_timing, section.clone());
+        }
+        perturb_latencies(&mut core_timing, jitter, &mut rng);
+        cores.insert(cid.to_string(), core_timing);
+    }
+    config.insert("timing".to_string(), shared);
+    config.insert("core".to_string(), Value::Table(cores));
+    config
+}
+
+fn run_analyze_config(args: &AnalyzeConfigArgs) -> Result<(), u32> {
+    let (config_table, timing_config) = load_config_file(&args.config_path)?;
+    let sim_config = SimConfig::from_section(maybe_get(&config_table, "sim"));
+    let muon_config = MuonConfig::from_section(maybe_get(&config_table, "muon"));
+
+    let analysis = analyze_config(
+        &timing_config,
+        sim_config.cluster_count(),
+        muon_config.num_cores,
+    );
+    if args.json {
+        let report = serde_json::to_string_pretty(&analysis).expect("failed to serialize analysis");
+        println!("{report}");
+    } else {
+        print!("{analysis}");
+    }
+    Ok(())
+}
+
+fn run_diff(args: &DiffArgs) -> Result<(), u32> {
+    let toml_a = read_toml(&args.config_a);
+    let toml_b = read_toml(&args.config_b);
+    let make = |path: &Path, toml_string: &str| {
+        let cli_args = CyclotronArgs {
+            config_path: path.to_path_buf(),
+            binary_path: args.binary_path.clone(),
+            gen_trace: Some(false),
+            ..CyclotronArgs::default()
+        };
+        make_sim(Some(toml_string), &Some(cli_args))
+    };
+    let make_a = || make(&args.config_a, &toml_a);
+    let make_b = || make(&args.config_b, &toml_b);
+
+    let options = DiffOptions {
+        interval: args.interval,
+        max_cycles: args.max_cycles.unwrap_or_else(|| make_a().top.timeout),
+    };
+    let Some(divergence) = find_divergence(make_a, make_b, &options) else {
+        println!("cyclotron diff: no divergence found");
+        return Ok(());
+    };
+
+    let w = &divergence.warp;
+    println!(
+        "cyclotron diff: cluster {} core {} warp {} diverges at instruction {} (pc range 0x{:x}..=0x{:x})",
+        w.cluster, w.core, w.warp, divergence.index, divergence.pc_range.0, divergence.pc_range.1
+    );
+    for (side, commit) in [("a", &divergence.commit_a), ("b", &divergence.commit_b)] {
+        match commit {
+            Some(c) => println!(
+                "  {side}: pc=0x{:x} raw=0x{:x} tmask=0x{:x} rd={} data={:?}",
+                c.pc, c.raw, c.tmask, c.rd_addr, c.rd_data
+            ),
+            None => println!("  {side}: <no commit>"),
+        }
+    }
+    let report = serde_json::to_string_pretty(&divergence).expect("failed to serialize divergence");
+    match &args.output {
+        Some(path) => std::fs::write(path, report).unwrap_or_else(|err| {
+            eprintln!(
+                "cyclotron diff: failed to write {}: {}",
+                path.display(),
+                err
+            );
+        }),
+        None => println!("{report}"),
+    }
+    Err(1)
+}
+
+/// Starts `--record` or `--replay`. A DPI cosimulation journal replays on
+/// its own rather than alongside a CLI run, so it is handed back instead.
+pub fn start_journal(args: &CyclotronArgs) -> Result<Option<Vec<JournalEvent>>, u32> {
+    if let Some(path) = &args.record {
+        journal::start_recording(path).map_err(|err| {
+            eprintln!(
+                "cyclotron: cannot record journal to {}: {err}",
+                path.display()
+            );
+            1u32
+        })?;
+    }
+    let Some(path) = &args.replay else {
+        return Ok(None);
+    };
+    let events = journal::read_journal(path).map_err(|err| {
+        eprintln!("cyclotron: cannot replay journal: {err}");
+        1u32
+    })?;
+    if matches!(events.first(), Some(JournalEvent::DpiInit { .. })) {
+        return Ok(Some(events));
+    }
+    journal::start_replay(events);
+    Ok(None)
+}
+
+/// Reads a TOML config file. A top-level `include = [...]` list names files,
+/// relative to the including one, that are loaded first and deep-merged in
+/// order; the file's own keys are merged on top. A file without `include`
+/// is returned as written.
 pub fn read_toml(filepath: &Path) -> String {
-    std::fs::read_to_string(filepath).unwrap_or_else(|err| {
+    let toml_string = std::fs::read_to_string(filepath).unwrap_or_else(|err| {
         eprintln!(
             "cyclotron: failed to read config file at {}: {}",
             filepath.display(),
             err
         );
         std::process::exit(1);
-    })
+    });
+    let has_includes =
+        toml::from_str::<Table>(&toml_string).is_ok_and(|table| table.contains_key("include"));
+    if !has_includes {
+        return toml_string;
+    }
+    let table = load_toml_with_includes(filepath, &mut Vec::new()).unwrap_or_else(|err| {
+        eprintln!("cyclotron: {err}");
+        std::process::exit(1);
+    });
+    toml::to_string(&table).expect("merged config serializes")
+}
+
+/// Loads `path` with its includes expanded. `stack` holds the files being
+/// expanded, so an include cycle is reported instead of recursing forever.
+fn load_toml_with_includes(path: &Path, stack: &mut Vec<PathBuf>) -> Result<Table, String> {
+    let canonical = path
+        .canonicalize()
+        .map_err(|err| format!("failed to read config file at {}: {err}", path.display()))?;
+    if let Some(start) = stack.iter().position(|seen| *seen == canonical) {
+        let chain: Vec<String> = stack[start..]
+            .iter()
+            .chain([&canonical])
+            .map(|path| path.display().to_string())
+            .collect();
+        return Err(format!("config include cycle: {}", chain.join(" -> ")));
+    }
+    let toml_string = std::fs::read_to_string(path)
+        .map_err(|err| format!("failed to read config file at {}: {err}", path.display()))?;
+    let mut table: Table = toml::from_str(&toml_string)
+        .map_err(|err| format!("cannot parse {}: {err}", path.display()))?;
+    let includes = match table.remove("include") {
+        None => return Ok(table),
+        Some(Value::Array(includes)) => includes,
+        Some(_) => {
+            return Err(format!(
+                "{}: `include` must be an array of paths",
+                path.display()
+            ))
+        }
+    };
+
+    let base_dir = path.parent().unwrap_or_else(|| Path::new("."));
+    let mut merged = Value::Table(Table::new());
+    stack.push(canonical);
+    for entry in includes {
+        let Value::String(include) = entry else {
+            return Err(format!(
+                "{}: `include` must be an array of paths",
+                path.display()
+            ));
+        };
+        let included = load_toml_with_includes(&base_dir.join(include), stack)?;
+        merge_values(&mut merged, Value::Table(included));
+    }
+    stack.pop();
+    merge_values(&mut merged, Value::Table(table));
+    match merged {
+        Value::Table(table) => Ok(table),
+        _ => unreachable!("merging tables yields a table"),
+    }
 }
 
 fn maybe_get<'a>(table: &'a Option<Table>, key: &str) -> Option<&'a toml::Value> {
@@ -61,15 +733,24 @@ fn merge_values(dst: &mut Value, src: Value) {
     }
 }
 
-fn load_timing_config(config_path: Option<&Path>, config_table: Option<&Table>) -> CoreGraphConfig {
+fn parse_timing_toml(path: &Path) -> Value {
+    let toml_string = read_toml(path);
+    toml::from_str(&toml_string).unwrap_or_else(|err| {
+        eprintln!("failed to parse timing config {}: {}", path.display(), err);
+        std::process::exit(1);
+    })
+}
+
+/// Merged `[timing]` includes and inline keys, before deserializing.
+fn load_timing_value(config_path: Option<&Path>, config_table: Option<&Table>) -> Value {
+    let mut merged = Value::Table(Table::new());
     let Some(config_table) = config_table else {
-        return CoreGraphConfig::default();
+        return merged;
     };
 
     let base_dir = config_path
         .and_then(|path| path.parent())
         .unwrap_or_else(|| Path::new("."));
-    let mut merged = Value::Table(Table::new());
 
     if let Some(Value::Table(timing_table)) = config_table.get("timing") {
         if let Some(Value::Array(includes)) = timing_table.get("include") {
@@ -78,17 +759,7 @@ fn load_timing_config(config_path: Option<&Path>, config_table: Option<&Table>)
                     Value::String(path) => path,
                     _ => continue,
                 };
-                let include_path = base_dir.join(path);
-                let toml_string = read_toml(&include_path);
-                let value: Value = toml::from_str(&toml_string).unwrap_or_else(|err| {
-                    eprintln!(
-                        "failed to parse timing config {}: {}",
-                        include_path.display(),
-                        err
-                    );
-                    std::process::exit(1);
-                });
-                merge_values(&mut merged, value);
+                merge_values(&mut merged, parse_timing_toml(&base_dir.join(path)));
             }
         }
 
@@ -99,15 +770,104 @@ fn load_timing_config(config_path: Option<&Path>, config_table: Option<&Table>)
         }
     }
 
-    merged.try_into().unwrap_or_default()
+    merged
+}
+
+/// Builds the timing config for each `--phase-config`, in cycle order. Each
+/// phase file is merged on top of the base config and all earlier phases.
+fn load_timing_phases(base: &Value, phases: &[PhaseArg]) -> Vec<TimingPhase> {
+    let mut phases = phases.to_vec();
+    phases.sort_by_key(|phase| phase.cycle);
+    let mut merged = base.clone();
+    phases
+        .iter()
+        .map(|phase| {
+            merge_values(&mut merged, parse_timing_toml(&phase.path));
+            let config = merged.clone().try_into().unwrap_or_else(|err| {
+                eprintln!(
+                    "invalid timing phase config {}: {}",
+                    phase.path.display(),
+                    err
+                );
+                std::process::exit(1);
+            });
+            TimingPhase {
+                cycle: phase.cycle,
+                config,
+            }
+        })
+        .collect()
 }
 
 /// Make a Sim object.
 /// If `toml_string` is given, override default configs with TOML values.
 /// If `cli_args` is given, override post-TOML configs with CLI arguments.
+/// `[core.N]` keys that override `MuonConfig`; all other keys are merged onto
+/// the `[timing]` config for that core.
+const CORE_MUON_KEYS: [&str; 2] = ["num_warps", "num_regs"];
+
+/// Parses the `[core.N]` sections of the config into per-core overrides of
+/// `muon_config` and the merged `[timing]` value.
+fn load_core_overrides(
+    config_table: Option<&Table>,
+    muon_config: &MuonConfig,
+    timing_value: &Value,
+) -> Result<BTreeMap<usize, CoreOverride>, String> {
+    let mut overrides = BTreeMap::new();
+    let Some(Value::Table(cores)) = config_table.and_then(|t| t.get("core")) else {
+        return Ok(overrides);
+    };
+    for (key, section) in cores {
+        let cid: usize = key
+            .parse()
+            .map_err(|_| format!("[core.{key}]: core index must be an integer"))?;
+        if cid >= muon_config.num_cores {
+            return Err(format!(
+                "[core.{cid}]: core index out of range (num_cores = {})",
+                muon_config.num_cores
+            ));
+        }
+        let Value::Table(section) = section else {
+            return Err(format!("[core.{cid}]: expected a table"));
+        };
+
+        let mut core_muon = *muon_config;
+        let mut timing = section.clone();
+        for key in CORE_MUON_KEYS {
+            let Some(value) = timing.remove(key) else {
+                continue;
+            };
+            let value = value
+                .as_integer()
+                .and_then(|v| usize::try_from(v).ok())
+                .ok_or_else(|| format!("[core.{cid}]: {key} must be a non-negative integer"))?;
+            match key {
+                "num_warps" => core_muon.num_warps = value,
+                "num_regs" => core_muon.num_regs = value,
+                _ => unreachable!(),
+            }
+        }
+
+        let mut core_timing = timing_value.clone();
+        merge_values(&mut core_timing, Value::Table(timing));
+        let timing_config = core_timing
+            .try_into()
+            .map_err(|err| format!("[core.{cid}]: invalid timing override: {err}"))?;
+        overrides.insert(
+            cid,
+            CoreOverride {
+                muon_config: core_muon,
+                timing_config,
+            },
+        );
+    }
+    Ok(overrides)
+}
+
 pub fn make_sim(toml_string: Option<&str>, cli_args: &Option<CyclotronArgs>) -> Sim {
     let config_table = toml_string.map(|s| toml::from_str(s).expect("cannot parse config toml"));
     let mut sim_config = SimConfig::from_section(maybe_get(&config_table, "sim"));
+    sim_config.log = LogConfig::from_section(maybe_get(&config_table, "log"));
     let mem_config = MemConfig::from_section(maybe_get(&config_table, "mem"));
     let mut muon_config = MuonConfig::from_section(maybe_get(&config_table, "muon"));
     let mut neutrino_config = NeutrinoConfig::from_section(maybe_get(&config_table, "neutrino"));
@@ -119,7 +879,12 @@ pub fn make_sim(toml_string: Option<&str>, cli_args: &Option<CyclotronArgs>) ->
             Some(args.config_path.as_path())
         }
     });
-    let timing_config = load_timing_config(config_path, config_table.as_ref());
+    let timing_value = load_timing_value(config_path, config_table.as_ref());
+    let timing_config: CoreGraphConfig = timing_value.clone().try_into().unwrap_or_default();
+    let timing_phases = cli_args
+        .as_ref()
+        .map(|args| load_timing_phases(&timing_value, &args.phase_config))
+        .unwrap_or_default();
 
     if let Some(args) = cli_args {
         sim_config.elf = args.binary_path.as_ref().cloned().unwrap_or(sim_config.elf);
@@ -128,18 +893,311 @@ pub fn make_sim(toml_string: Option<&str>, cli_args: &Option<CyclotronArgs>) ->
         if args.timing {
             sim_config.timing = true;
         }
+        if args.fast {
+            sim_config.fast = true;
+        }
+        sim_config.warmup_cycles = args.warmup_cycles.unwrap_or(sim_config.warmup_cycles);
+        sim_config.verify_checkpoint = args.verify_checkpoint.or(sim_config.verify_checkpoint);
         muon_config.num_lanes = args.num_lanes.unwrap_or(muon_config.num_lanes);
         muon_config.num_warps = args.num_warps.unwrap_or(muon_config.num_warps);
         muon_config.num_cores = args.num_cores.unwrap_or(muon_config.num_cores);
     }
 
+    let core_overrides = load_core_overrides(config_table.as_ref(), &muon_config, &timing_value)
+        .unwrap_or_else(|err| {
+            eprintln!("cyclotron: invalid core config: {err}");
+            std::process::exit(1);
+        });
+
     neutrino_config.muon_config = muon_config.clone();
+    // Neutrino tracks barrier arrivals per warp slot, so size it for the
+    // widest core.
+    neutrino_config.muon_config.num_warps = core_overrides
+        .values()
+        .map(|core| core.muon_config.num_warps)
+        .fold(muon_config.num_warps, usize::max);
 
-    Sim::new_with_timing(
+    let resolved = resolved_config(
+        config_table,
+        &sim_config,
+        &muon_config,
+        cli_args.is_some(),
+        timing_value,
+    );
+
+    let mut sim = Sim::new_with_timing(
         sim_config,
         muon_config,
         neutrino_config,
         mem_config,
         timing_config,
-    )
+        core_overrides,
+    );
+    for phase in timing_phases {
+        sim.schedule_timing_phase(phase);
+    }
+    sim.set_run_config(serde_json::to_value(resolved).unwrap_or_default());
+    sim
+}
+
+/// The config file with `[timing]` includes expanded and command-line
+/// overrides applied, as recorded in the run metadata.
+fn resolved_config(
+    config_table: Option<Table>,
+    sim_config: &SimConfig,
+    muon_config: &MuonConfig,
+    has_cli_args: bool,
+    timing_value: Value,
+) -> Table {
+    let mut resolved = config_table.unwrap_or_default();
+    resolved.insert("timing".to_string(), timing_value);
+    if has_cli_args {
+        let mut overrides = Table::new();
+        overrides.insert(
+            "elf".to_string(),
+            Value::String(sim_config.elf.display().to_string()),
+        );
+        overrides.insert(
+            "log_level".to_string(),
+            Value::Integer(sim_config.log_level as i64),
+        );
+        overrides.insert("trace".to_string(), Value::Boolean(sim_config.trace));
+        overrides.insert("timing".to_string(), Value::Boolean(sim_config.timing));
+        overrides.insert(
+            "warmup_cycles".to_string(),
+            Value::Integer(sim_config.warmup_cycles as i64),
+        );
+        let mut muon = Table::new();
+        for (key, value) in [
+            ("num_lanes", muon_config.num_lanes),
+            ("num_warps", muon_config.num_warps),
+            ("num_cores", muon_config.num_cores),
+        ] {
+            muon.insert(key.to_string(), Value::Integer(value as i64));
+        }
+        for (section, values) in [("sim", overrides), ("muon", muon)] {
+            let mut merged = resolved
+                .remove(section)
+                .unwrap_or_else(|| Value::Table(Table::new()));
+            merge_values(&mut merged, Value::Table(values));
+            resolved.insert(section.to_string(), merged);
+        }
+    }
+    resolved
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn core_sections_override_one_core() {
+        let config: Table = toml::from_str(
+            r#"
+            [timing.execute.alu]
+            bytes_per_cycle = 16
+
+            [core.1]
+            num_warps = 4
+
+            [core.1.execute.alu]
+            bytes_per_cycle = 4
+            "#,
+        )
+        .unwrap();
+        let muon_config = MuonConfig {
+            num_cores: 2,
+            ..MuonConfig::default()
+        };
+        let timing_value = load_timing_value(None, Some(&config));
+        let overrides = load_core_overrides(Some(&config), &muon_config, &timing_value).unwrap();
+
+        assert_eq!(overrides.keys().copied().collect::<Vec<_>>(), [1]);
+        let core = &overrides[&1];
+        assert_eq!(core.muon_config.num_warps, 4);
+        assert_eq!(core.muon_config.num_lanes, muon_config.num_lanes);
+        assert_eq!(core.timing_config.compute.execute.alu.bytes_per_cycle, 4);
+    }
+
+    #[test]
+    fn core_sections_past_num_cores_are_rejected() {
+        let config: Table = toml::from_str("[core.2]\nnum_warps = 4\n").unwrap();
+        let muon_config = MuonConfig {
+            num_cores: 2,
+            ..MuonConfig::default()
+        };
+        let err = load_core_overrides(Some(&config), &muon_config, &Value::Table(Table::new()))
+            .unwrap_err();
+        assert!(err.contains("out of range"), "{err}");
+    }
+
+    #[test]
+    fn salvaged_cores_are_left_out_and_the_rest_renumbered() {
+        let config: Table = toml::from_str(
+            r#"
+            [timing.gmem.salvage]
+            disabled_cores = [0]
+
+            [core.1]
+            num_warps = 4
+            "#,
+        )
+        .unwrap();
+        let muon_config = MuonConfig {
+            num_cores: 3,
+            ..MuonConfig::default()
+        };
+        let timing_value = load_timing_value(None, Some(&config));
+        let cluster_config = crate::sim::top::ClusterConfig {
+            muon_config,
+            neutrino_config: NeutrinoConfig::default(),
+            timing_config: timing_value.clone().try_into().unwrap(),
+            core_overrides: load_core_overrides(Some(&config), &muon_config, &timing_value)
+                .unwrap(),
+        }
+        .salvaged();
+
+        assert_eq!(cluster_config.muon_config.num_cores, 2);
+        assert_eq!(cluster_config.neutrino_config.muon_config.num_cores, 2);
+        // Core 1 is now core 0 and keeps its override.
+        assert_eq!(cluster_config.core_muon_config(0).num_warps, 4);
+        assert_eq!(cluster_config.core_muon_config(0).num_cores, 2);
+        assert_eq!(
+            cluster_config.core_muon_config(1).num_warps,
+            muon_config.num_warps
+        );
+    }
+
+    #[test]
+    fn perturbed_config_jitters_each_core_and_keeps_core_keys() {
+        let config: Table = toml::from_str(
+            r#"
+            [timing.gmem.nodes.dram]
+            base_latency = 1000
+
+            [timing.smem.bank]
+            base_latency = 1000
+
+            [core.1]
+            num_warps = 4
+            "#,
+        )
+        .unwrap();
+        let timing = load_timing_value(None, Some(&config));
+        let perturbed = perturbed_config(&config, &timing, 2, 0.05, 3);
+
+        let dram = perturbed["timing"]["gmem"]["nodes"]["dram"]["base_latency"]
+            .as_integer()
+            .unwrap();
+        assert!((950..=1050).contains(&dram));
+        let bank = |cid: &str| {
+            perturbed["core"][cid]["smem"]["bank"]["base_latency"]
+                .as_integer()
+                .unwrap()
+        };
+        assert_ne!(bank("0"), bank("1"));
+        assert!(perturbed["core"]["0"].get("gmem").is_none());
+        assert_eq!(perturbed["core"]["1"]["num_warps"].as_integer(), Some(4));
+        assert_eq!(perturbed, perturbed_config(&config, &timing, 2, 0.05, 3));
+
+        let muon_config = MuonConfig {
+            num_cores: 2,
+            ..MuonConfig::default()
+        };
+        let timing = load_timing_value(None, Some(&perturbed));
+        let overrides = load_core_overrides(Some(&perturbed), &muon_config, &timing).unwrap();
+        assert_eq!(overrides[&1].muon_config.num_warps, 4);
+        assert_eq!(
+            overrides[&0].timing_config.memory.smem.bank.base_latency as i64,
+            bank("0")
+        );
+    }
+
+    #[test]
+    fn resolved_config_applies_cli_overrides() {
+        let config: Table = toml::from_str(
+            r#"
+            [sim]
+            elf = "a.elf"
+            timeout = 5
+
+            [timing]
+            include = ["missing.toml"]
+            "#,
+        )
+        .unwrap();
+        let sim_config = SimConfig {
+            elf: "b.elf".into(),
+            timing: true,
+            ..SimConfig::default()
+        };
+        let muon_config = MuonConfig {
+            num_warps: 2,
+            ..MuonConfig::default()
+        };
+        let timing = toml::from_str("[lsu]\nlink_capacity = 3\n").unwrap();
+        let resolved = resolved_config(Some(config), &sim_config, &muon_config, true, timing);
+
+        assert_eq!(resolved["sim"]["elf"].as_str(), Some("b.elf"));
+        assert_eq!(resolved["sim"]["timeout"].as_integer(), Some(5));
+        assert_eq!(resolved["sim"]["timing"].as_bool(), Some(true));
+        assert_eq!(resolved["muon"]["num_warps"].as_integer(), Some(2));
+        assert_eq!(
+            resolved["timing"]["lsu"]["link_capacity"].as_integer(),
+            Some(3)
+        );
+        assert!(resolved["timing"].get("include").is_none());
+    }
+
+    fn write_config(dir: &Path, name: &str, contents: &str) -> PathBuf {
+        let path = dir.join(name);
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn includes_deep_merge_under_the_including_file() {
+        let dir = std::env::temp_dir().join(format!("cyclotron_include_{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("base")).unwrap();
+        write_config(
+            &dir,
+            "base/machine.toml",
+            "[muon]\nnum_warps = 8\nnum_lanes = 16\n\n[sim]\ntimeout = 5\n",
+        );
+        write_config(
+            &dir,
+            "base/defaults.toml",
+            "include = [\"machine.toml\"]\n\n[sim]\ntimeout = 10\ntrace = true\n",
+        );
+        let exp = write_config(
+            &dir,
+            "exp.toml",
+            "include = [\"base/defaults.toml\"]\n\n[muon]\nnum_warps = 4\n",
+        );
+
+        let table: Table = toml::from_str(&read_toml(&exp)).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(table.get("include").is_none());
+        assert_eq!(table["muon"]["num_warps"].as_integer(), Some(4));
+        assert_eq!(table["muon"]["num_lanes"].as_integer(), Some(16));
+        assert_eq!(table["sim"]["timeout"].as_integer(), Some(10));
+        assert_eq!(table["sim"]["trace"].as_bool(), Some(true));
+    }
+
+    #[test]
+    fn include_cycles_are_reported() {
+        let dir =
+            std::env::temp_dir().join(format!("cyclotron_include_cycle_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let a = write_config(&dir, "a.toml", "include = [\"b.toml\"]\n");
+        write_config(&dir, "b.toml", "include = [\"a.toml\"]\n");
+
+        let err = load_toml_with_includes(&a, &mut Vec::new()).unwrap_err();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(err.starts_with("config include cycle:"), "{err}");
+        assert!(
+            err.contains("a.toml -> ") && err.ends_with("a.toml"),
+            "{err}"
+        );
+    }
 }
@@ -0,0 +1,313 @@
+//! Two-pass assembler for the slice of the Muon ISA the workload kernels
+//! use. Muon keeps the RISC-V opcodes and register names but widens every
+//! instruction to 64 bits:
+//!
+//! | bits    | field                                   |
+//! |---------|-----------------------------------------|
+//! | 6:0     | opcode                                  |
+//! | 8:7     | opext (1 selects `.shared` memory ops)  |
+//! | 16:9    | rd, or imm[31:24] for stores/branches   |
+//! | 19:17   | funct3                                  |
+//! | 27:20   | rs1                                     |
+//! | 35:28   | rs2, or imm[31:24] for other immediates |
+//! | 58:52   | funct7                                  |
+//! | 59:36   | imm[23:0]                               |
+//!
+//! so `li` takes any 32-bit immediate and branch offsets are byte offsets.
+
+use std::collections::HashMap;
+
+const LOAD: u64 = 0b0000011;
+const CUSTOM0: u64 = 0b0001011;
+const OP_IMM: u64 = 0b0010011;
+const STORE: u64 = 0b0100011;
+const OP: u64 = 0b0110011;
+const BRANCH: u64 = 0b1100011;
+const JAL: u64 = 0b1101111;
+const SYSTEM: u64 = 0b1110011;
+
+const INST_BYTES: u32 = 8;
+
+fn reg(name: &str) -> u64 {
+    const ABI: [&str; 32] = [
+        "zero", "ra", "sp", "gp", "tp", "t0", "t1", "t2", "s0", "s1", "a0", "a1", "a2", "a3", "a4",
+        "a5", "a6", "a7", "s2", "s3", "s4", "s5", "s6", "s7", "s8", "s9", "s10", "s11", "t3", "t4",
+        "t5", "t6",
+    ];
+    if let Some(idx) = ABI.iter().position(|abi| *abi == name) {
+        return idx as u64;
+    }
+    name.strip_prefix('x')
+        .and_then(|idx| idx.parse::<u64>().ok())
+        .filter(|&idx| idx < 256)
+        .unwrap_or_else(|| panic!("unknown register `{name}`"))
+}
+
+fn imm(text: &str) -> u32 {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text),
+    };
+    let value = match digits.strip_prefix("0x") {
+        Some(hex) => i64::from_str_radix(&hex.replace('_', ""), 16),
+        None => digits.replace('_', "").parse::<i64>(),
+    }
+    .unwrap_or_else(|_| panic!("bad immediate `{text}`"));
+    (if negative { -value } else { value }) as u32
+}
+
+/// Splits `off(reg)` into its offset and base register.
+fn mem_operand(text: &str) -> (u32, u64) {
+    let (offset, base) = text
+        .strip_suffix(')')
+        .and_then(|text| text.split_once('('))
+        .unwrap_or_else(|| panic!("bad memory operand `{text}`"));
+    let offset = if offset.is_empty() { 0 } else { imm(offset) };
+    (offset, reg(base))
+}
+
+fn encode(opcode: u64, opext: u64, rd: u64, f3: u64, rs1: u64, rs2: u64, f7: u64) -> u64 {
+    opcode | opext << 7 | rd << 9 | f3 << 17 | rs1 << 20 | rs2 << 28 | f7 << 52
+}
+
+/// Places a 32-bit immediate; stores and branches keep its top byte in the
+/// rd field, everything else in the rs2 field.
+fn with_imm(inst: u64, imm: u32, high_in_rd: bool) -> u64 {
+    let low = (imm & 0x00ff_ffff) as u64;
+    let high = (imm >> 24) as u64;
+    inst | low << 36 | if high_in_rd { high << 9 } else { high << 28 }
+}
+
+struct Line<'a> {
+    number: usize,
+    mnemonic: &'a str,
+    operands: Vec<&'a str>,
+}
+
+/// Assembles `source` for a program loaded at `base`. Accepts one
+/// instruction or label per line and `#` comments.
+pub fn assemble(source: &str, base: u32) -> Vec<u64> {
+    let mut labels = HashMap::new();
+    let mut lines = Vec::new();
+    for (number, text) in source.lines().enumerate() {
+        let text = text.split('#').next().unwrap_or("").trim();
+        if text.is_empty() {
+            continue;
+        }
+        if let Some(label) = text.strip_suffix(':') {
+            let pc = base + INST_BYTES * lines.len() as u32;
+            assert!(
+                labels.insert(label.trim(), pc).is_none(),
+                "line {}: duplicate label `{label}`",
+                number + 1
+            );
+            continue;
+        }
+        let (mnemonic, rest) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+        let operands = rest
+            .split(',')
+            .map(str::trim)
+            .filter(|operand| !operand.is_empty())
+            .collect();
+        lines.push(Line {
+            number: number + 1,
+            mnemonic,
+            operands,
+        });
+    }
+
+    lines
+        .iter()
+        .enumerate()
+        .map(|(idx, line)| {
+            let pc = base + INST_BYTES * idx as u32;
+            let target = |label: &str| {
+                labels
+                    .get(label)
+                    .map(|&target: &u32| target.wrapping_sub(pc))
+                    .unwrap_or_else(|| panic!("line {}: unknown label `{label}`", line.number))
+            };
+            encode_line(line, target)
+        })
+        .collect()
+}
+
+fn encode_line(line: &Line, target: impl Fn(&str) -> u32) -> u64 {
+    let ops = &line.operands;
+    let arg = |idx: usize| {
+        *ops.get(idx).unwrap_or_else(|| {
+            panic!(
+                "line {}: `{}` is missing operand {}",
+                line.number,
+                line.mnemonic,
+                idx + 1
+            )
+        })
+    };
+    let (mnemonic, opext) = match line.mnemonic.strip_suffix(".shared") {
+        Some(mnemonic) => (mnemonic, 1),
+        None => (
+            line.mnemonic
+                .strip_suffix(".global")
+                .unwrap_or(line.mnemonic),
+            0,
+        ),
+    };
+
+    let op_f3_f7 = match mnemonic {
+        "add" => Some((0b000, 0b0000000)),
+        "sub" => Some((0b000, 0b0100000)),
+        "sll" => Some((0b001, 0b0000000)),
+        "slt" => Some((0b010, 0b0000000)),
+        "sltu" => Some((0b011, 0b0000000)),
+        "xor" => Some((0b100, 0b0000000)),
+        "srl" => Some((0b101, 0b0000000)),
+        "sra" => Some((0b101, 0b0100000)),
+        "or" => Some((0b110, 0b0000000)),
+        "and" => Some((0b111, 0b0000000)),
+        "mul" => Some((0b000, 0b0000001)),
+        "div" => Some((0b100, 0b0000001)),
+        "divu" => Some((0b101, 0b0000001)),
+        "rem" => Some((0b110, 0b0000001)),
+        "remu" => Some((0b111, 0b0000001)),
+        _ => None,
+    };
+    if let Some((f3, f7)) = op_f3_f7 {
+        return encode(OP, 0, reg(arg(0)), f3, reg(arg(1)), reg(arg(2)), f7);
+    }
+
+    let warp_f3 = match mnemonic {
+        "vx_vote.all" => Some(0b000),
+        "vx_vote.any" => Some(0b001),
+        "vx_vote.uni" => Some(0b010),
+        "vx_ballot" => Some(0b011),
+        "vx_shfl.up" => Some(0b100),
+        "vx_shfl.down" => Some(0b101),
+        "vx_shfl.bfly" => Some(0b110),
+        "vx_shfl.idx" => Some(0b111),
+        _ => None,
+    };
+    if let Some(f3) = warp_f3 {
+        let rs2 = if f3 >= 0b100 { reg(arg(2)) } else { 0 };
+        return encode(CUSTOM0, 0, reg(arg(0)), f3, reg(arg(1)), rs2, 0b0000010);
+    }
+
+    let op_imm_f3 = match mnemonic {
+        "addi" => Some(0b000),
+        "slti" => Some(0b010),
+        "sltiu" => Some(0b011),
+        "xori" => Some(0b100),
+        "ori" => Some(0b110),
+        "andi" => Some(0b111),
+        "slli" => Some(0b001),
+        "srli" => Some(0b101),
+        _ => None,
+    };
+    if let Some(f3) = op_imm_f3 {
+        let inst = encode(OP_IMM, 0, reg(arg(0)), f3, reg(arg(1)), 0, 0);
+        return with_imm(inst, imm(arg(2)), false);
+    }
+
+    let load_f3 = match mnemonic {
+        "lb" => Some(0),
+        "lh" => Some(1),
+        "lw" => Some(2),
+        "lbu" => Some(4),
+        "lhu" => Some(5),
+        _ => None,
+    };
+    if let Some(f3) = load_f3 {
+        let (offset, base) = mem_operand(arg(1));
+        let inst = encode(LOAD, opext, reg(arg(0)), f3, base, 0, 0);
+        return with_imm(inst, offset, false);
+    }
+
+    let store_f3 = match mnemonic {
+        "sb" => Some(0),
+        "sh" => Some(1),
+        "sw" => Some(2),
+        _ => None,
+    };
+    if let Some(f3) = store_f3 {
+        let (offset, base) = mem_operand(arg(1));
+        let inst = encode(STORE, opext, 0, f3, base, reg(arg(0)), 0);
+        return with_imm(inst, offset, true);
+    }
+
+    let branch_f3 = match mnemonic {
+        "beq" => Some(0b000),
+        "bne" => Some(0b001),
+        "blt" => Some(0b100),
+        "bge" => Some(0b101),
+        "bltu" => Some(0b110),
+        "bgeu" => Some(0b111),
+        _ => None,
+    };
+    if let Some(f3) = branch_f3 {
+        let inst = encode(BRANCH, 0, 0, f3, reg(arg(0)), reg(arg(1)), 0);
+        return with_imm(inst, target(arg(2)), true);
+    }
+
+    match mnemonic {
+        "li" => with_imm(
+            encode(OP_IMM, 0, reg(arg(0)), 0, 0, 0, 0),
+            imm(arg(1)),
+            false,
+        ),
+        "mv" => encode(OP_IMM, 0, reg(arg(0)), 0, reg(arg(1)), 0, 0),
+        "beqz" => with_imm(
+            encode(BRANCH, 0, 0, 0b000, reg(arg(0)), 0, 0),
+            target(arg(1)),
+            true,
+        ),
+        "bnez" => with_imm(
+            encode(BRANCH, 0, 0, 0b001, reg(arg(0)), 0, 0),
+            target(arg(1)),
+            true,
+        ),
+        "j" => with_imm(encode(JAL, 0, 0, 0, 0, 0, 0), target(arg(0)), false),
+        "csrr" => with_imm(
+            encode(SYSTEM, 0, reg(arg(0)), 0b010, 0, 0, 0),
+            imm(arg(1)),
+            false,
+        ),
+        "vx_tmc" => encode(CUSTOM0, 0, 0, 0b000, reg(arg(0)), 0, 0),
+        "vx_bar" => encode(CUSTOM0, 0, 0, 0b100, reg(arg(0)), reg(arg(1)), 0),
+        // Ends the warp, reporting a0 as `tohost` (0 passes).
+        "ecall" => encode(SYSTEM, 0, 0, 0, reg("a0"), 0, 0),
+        _ => panic!(
+            "line {}: unknown instruction `{}`",
+            line.number, line.mnemonic
+        ),
+    }
+}
+
+#[test]
+fn encodes_like_the_muon_toolchain() {
+    // Encodings from test/isa-tests/mu32-p-flush.dump.
+    let program = assemble(
+        "
+        start:
+            j reset
+            ori gp, gp, 0x539
+        reset:
+            li t0, -1
+            vx_tmc t0
+        ",
+        0x1000_0000,
+    );
+    let word = |bytes: [u8; 8]| u64::from_le_bytes(bytes);
+    assert_eq!(program[0], 0x6f | 0x10 << 36);
+    assert_eq!(
+        program[1],
+        word([0x13, 0x06, 0x3c, 0x00, 0x90, 0x53, 0x00, 0x00])
+    );
+    assert_eq!(
+        program[2],
+        word([0x13, 0x0a, 0x00, 0xf0, 0xff, 0xff, 0xff, 0x0f])
+    );
+    assert_eq!(
+        program[3],
+        word([0x0b, 0x00, 0x50, 0x00, 0x00, 0x00, 0x00, 0x00])
+    );
+}
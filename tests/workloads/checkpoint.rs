@@ -0,0 +1,72 @@
+use std::sync::{Arc, Mutex};
+
+use cyclotron::sim::top::{Event, RunOutcome, Sim};
+
+use crate::harness::Workload;
+use crate::vecadd::{vecadd_source, A, B, C, LANES, N};
+
+fn vecadd() -> Workload {
+    let a: Vec<u32> = (0..N).collect();
+    let b: Vec<u32> = (0..N).map(|i| 1000 * i + 7).collect();
+    Workload::new("vecadd_checkpoint", vecadd_source())
+        .lanes(LANES as usize)
+        .words(A, &a)
+        .words(B, &b)
+}
+
+/// What the rest of a run did: the gmem requests completed, by id and
+/// cycle, the cycle the kernel finished on, the timing summaries and `C`.
+#[derive(Debug, PartialEq)]
+struct Rest {
+    completions: Vec<(u64, u64)>,
+    finished_at: u64,
+    summaries: String,
+    c: Vec<u32>,
+}
+
+fn finish(sim: &mut Sim, completions: &Mutex<Vec<(u64, u64)>>) -> Rest {
+    completions.lock().unwrap().clear();
+    let RunOutcome::Fired { cycle, .. } = sim.run_until(Event::NextKernelBoundary) else {
+        panic!("kernel never finished");
+    };
+    Rest {
+        completions: completions.lock().unwrap().clone(),
+        finished_at: cycle,
+        summaries: serde_json::to_string(&sim.timing_summaries()).unwrap(),
+        c: (0..N)
+            .map(|i| u32::from_le_bytes(sim.top.gmem_load((C + 4 * i).into())))
+            .collect(),
+    }
+}
+
+#[test]
+fn restored_sim_repeats_the_rest_of_the_run() {
+    let mut sim = vecadd().build();
+    let completions = Arc::new(Mutex::new(Vec::new()));
+    let log = completions.clone();
+    sim.hooks().on_gmem_completion(move |event| {
+        log.lock()
+            .unwrap()
+            .push((event.completion.request.id, event.cycle));
+    });
+
+    // Partway through the loop, with loads and stores in flight.
+    sim.run_until(Event::CycleReached(600));
+    let checkpoint = sim.checkpoint();
+    let straight = finish(&mut sim, &completions);
+    assert!(!straight.completions.is_empty());
+
+    for _ in 0..2 {
+        sim.restore(&checkpoint);
+        assert_eq!(sim.cycle(), 600);
+        assert_eq!(sim.kernel_finished_at(0), None);
+        assert_eq!(finish(&mut sim, &completions), straight);
+    }
+}
+
+#[test]
+fn verify_checkpoint_passes_on_a_deterministic_run() {
+    let run = vecadd().sim_option("verify_checkpoint", 600).run();
+    let c = run.read_words(C, N as usize);
+    assert_eq!(c[5], 5 + 1000 * 5 + 7);
+}
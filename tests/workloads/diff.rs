@@ -0,0 +1,84 @@
+use std::sync::{Arc, Mutex};
+
+use cyclotron::sim::diff::{find_divergence, CommitRecord, DiffOptions, WarpKey};
+use cyclotron::sim::top::Sim;
+
+use crate::harness::Workload;
+use crate::vecadd::{vecadd_source, A, B, LANES, N};
+
+/// The element whose `B` word differs between the two runs.
+const ODD: u32 = 37;
+
+fn vecadd(name: &str, odd_b: u32) -> Sim {
+    let a: Vec<u32> = (0..N).collect();
+    let mut b: Vec<u32> = (0..N).map(|i| 1000 * i + 7).collect();
+    b[ODD as usize] = odd_b;
+    Workload::new(name, vecadd_source())
+        .lanes(LANES as usize)
+        .words(A, &a)
+        .words(B, &b)
+        .build()
+}
+
+/// Every commit of the warp, with the cycle it retired on.
+fn commits(mut sim: Sim) -> Vec<(u64, CommitRecord)> {
+    let commits = Arc::new(Mutex::new(Vec::new()));
+    let log = commits.clone();
+    sim.hooks().on_instruction_retired(move |event| {
+        log.lock()
+            .unwrap()
+            .push((event.cycle, CommitRecord::from(event.line)));
+    });
+    assert_eq!(sim.simulate(), Ok(()));
+    let commits = commits.lock().unwrap().clone();
+    commits
+}
+
+#[test]
+fn diff_finds_the_load_of_the_one_differing_word() {
+    let same = 1000 * ODD + 7;
+    let make_a = || vecadd("vecadd_diff_a", same);
+    let make_b = || vecadd("vecadd_diff_b", same + 1);
+
+    // Found independently: the first commit whose record differs. Only the
+    // data differs, so both runs commit it on the same cycle.
+    let (a, b) = (commits(make_a()), commits(make_b()));
+    let first = a
+        .iter()
+        .zip(&b)
+        .position(|(a, b)| a != b)
+        .expect("the runs never diverge");
+    let (cycle, commit_a) = a[first].clone();
+    let (_, commit_b) = b[first].clone();
+    assert_eq!(b[first].0, cycle);
+    // It is the load of `B` in the iteration that covers `ODD`.
+    let lane = (ODD % LANES) as usize;
+    assert_eq!(commit_a.rd_data[lane], Some(same));
+    assert_eq!(commit_b.rd_data[lane], Some(same + 1));
+
+    let options = DiffOptions {
+        interval: 8,
+        max_cycles: 200_000,
+    };
+    let divergence = find_divergence(make_a, make_b, &options).expect("no divergence found");
+    assert_eq!(
+        divergence.warp,
+        WarpKey {
+            cluster: 0,
+            core: 0,
+            warp: 0
+        }
+    );
+    assert_eq!(divergence.index, first as u64);
+    assert_eq!(divergence.window_start, first as u64 / 8 * 8);
+    assert_eq!(divergence.cycle_a, Some(cycle));
+    assert_eq!(divergence.cycle_b, Some(cycle));
+    assert_eq!(divergence.commit_a, Some(commit_a));
+    assert_eq!(divergence.commit_b, Some(commit_b));
+    let odd = divergence
+        .memory
+        .iter()
+        .find(|word| word.addr == (B + 4 * ODD) as u64)
+        .expect("the differing word was not reported");
+    assert_eq!((odd.a, odd.b), (same, same + 1));
+}
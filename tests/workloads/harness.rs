@@ -0,0 +1,325 @@
+//! Builds a workload ELF from assembly and data words, and runs it through
+//! the full `Sim` with the shipped timing config (`config.toml`).
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use cyclotron::sim::top::Sim;
+use cyclotron::ui::make_sim;
+
+use crate::asm::assemble;
+
+pub const TEXT_BASE: u32 = 0x1000_0000;
+pub const THREAD_ID_CSR: u32 = 0xcc0;
+
+pub struct Workload {
+    name: String,
+    source: String,
+    data: BTreeMap<u32, u32>,
+    lanes: usize,
+    cores: usize,
+    co_kernels: Vec<(String, Vec<usize>)>,
+    muon_options: toml::Table,
+    sim_options: toml::Table,
+    timing_options: toml::Table,
+    timeout: u64,
+}
+
+pub struct WorkloadRun {
+    pub sim: Sim,
+    /// Cycle the kernel retired on.
+    pub cycles: u64,
+}
+
+impl Workload {
+    pub fn new(name: &str, source: impl Into<String>) -> Self {
+        Self {
+            name: name.to_string(),
+            source: source.into(),
+            data: BTreeMap::new(),
+            lanes: 16,
+            cores: 1,
+            co_kernels: Vec::new(),
+            muon_options: toml::Table::new(),
+            sim_options: toml::Table::new(),
+            timing_options: toml::Table::new(),
+            timeout: 200_000,
+        }
+    }
+
+    pub fn word(mut self, addr: u32, value: u32) -> Self {
+        assert_eq!(addr % 4, 0, "data words must be aligned");
+        self.data.insert(addr, value);
+        self
+    }
+
+    pub fn words(self, addr: u32, values: &[u32]) -> Self {
+        values
+            .iter()
+            .enumerate()
+            .fold(self, |workload, (idx, &value)| {
+                workload.word(addr + 4 * idx as u32, value)
+            })
+    }
+
+    pub fn lanes(mut self, lanes: usize) -> Self {
+        self.lanes = lanes;
+        self
+    }
+
+    /// Runs the kernel on every one of `cores` single-warp cores.
+    pub fn cores(mut self, cores: usize) -> Self {
+        self.cores = cores;
+        self
+    }
+
+    /// Also runs the kernel as a `[[sim.kernels]]` entry named `name` on
+    /// `clusters`. The clusters not listed keep running it as `sim.elf`.
+    pub fn co_kernel(mut self, name: &str, clusters: &[usize]) -> Self {
+        self.co_kernels.push((name.to_string(), clusters.to_vec()));
+        self
+    }
+
+    /// Sets `key` in the `[muon]` section.
+    pub fn muon_option(mut self, key: &str, value: impl Into<toml::Value>) -> Self {
+        self.muon_options.insert(key.into(), value.into());
+        self
+    }
+
+    /// Sets `key` in the `[sim]` section.
+    pub fn sim_option(mut self, key: &str, value: impl Into<toml::Value>) -> Self {
+        self.sim_options.insert(key.into(), value.into());
+        self
+    }
+
+    /// Sets the dotted `path` (e.g. `execute.alu.base_latency`) in the
+    /// `[timing]` section, on top of the shipped timing files.
+    pub fn timing_option(mut self, path: &str, value: impl Into<toml::Value>) -> Self {
+        let (tables, key) = path.rsplit_once('.').unwrap_or(("", path));
+        let mut table = &mut self.timing_options;
+        for name in tables.split('.').filter(|name| !name.is_empty()) {
+            table = table
+                .entry(name)
+                .or_insert_with(|| toml::Table::new().into())
+                .as_table_mut()
+                .expect("timing option path crosses a value");
+        }
+        table.insert(key.into(), value.into());
+        self
+    }
+
+    /// Assembles, loads and runs the kernel, and checks that it exited with `a0 == 0`.
+    pub fn run(self) -> WorkloadRun {
+        let name = self.name.clone();
+        let mut sim = self.build();
+        let result = sim.simulate();
+        assert_eq!(result, Ok(()), "{name} did not pass");
+        let cycles = sim
+            .kernel_finished_at(0)
+            .unwrap_or_else(|| panic!("{name} did not finish"));
+        WorkloadRun { sim, cycles }
+    }
+
+    /// Assembles and loads the kernel without running it.
+    pub fn build(self) -> Sim {
+        let out_dir =
+            std::env::temp_dir().join(format!("cyclotron-workloads-{}", std::process::id()));
+        fs::create_dir_all(&out_dir).expect("cannot create workload dir");
+        // Keep the per-run perf logs out of the source tree.
+        std::env::set_var("CYCLOTRON_PERF_LOG_DIR", out_dir.join("perf"));
+
+        let elf = out_dir.join(format!("{}.elf", self.name));
+        let text = assemble(&self.source, TEXT_BASE)
+            .iter()
+            .flat_map(|inst| inst.to_le_bytes())
+            .collect::<Vec<u8>>();
+        let mut sections = vec![Section::text(TEXT_BASE, text)];
+        sections.extend(self.data_sections());
+        write_elf(&elf, &sections);
+
+        make_sim(Some(&self.config(&elf)), &None)
+    }
+
+    fn config(&self, elf: &Path) -> String {
+        let base = fs::read_to_string("config.toml").expect("cannot read config.toml");
+        let mut config: toml::Table = toml::from_str(&base).expect("cannot parse config.toml");
+        let section = |config: &mut toml::Table, name: &str| -> toml::Table {
+            match config.remove(name) {
+                Some(toml::Value::Table(table)) => table,
+                _ => toml::Table::new(),
+            }
+        };
+        let mut muon = section(&mut config, "muon");
+        muon.insert("num_lanes".into(), (self.lanes as i64).into());
+        muon.insert("num_warps".into(), 1.into());
+        muon.insert("num_cores".into(), (self.cores as i64).into());
+        muon.insert("start_pc".into(), (TEXT_BASE as i64).into());
+        muon.extend(self.muon_options.clone());
+        config.insert("muon".into(), muon.into());
+        let mut sim = section(&mut config, "sim");
+        sim.insert("elf".into(), elf.display().to_string().into());
+        sim.insert("timeout".into(), (self.timeout as i64).into());
+        sim.insert("timing".into(), true.into());
+        if !self.co_kernels.is_empty() {
+            let kernels: Vec<toml::Value> = self
+                .co_kernels
+                .iter()
+                .map(|(name, clusters)| {
+                    let mut kernel = toml::Table::new();
+                    kernel.insert("name".into(), name.clone().into());
+                    kernel.insert("elf".into(), elf.display().to_string().into());
+                    let clusters: Vec<toml::Value> =
+                        clusters.iter().map(|&id| (id as i64).into()).collect();
+                    kernel.insert("clusters".into(), clusters.into());
+                    kernel.into()
+                })
+                .collect();
+            sim.insert("kernels".into(), kernels.into());
+        }
+        sim.extend(self.sim_options.clone());
+        config.insert("sim".into(), sim.into());
+        let mut timing = section(&mut config, "timing");
+        timing.extend(self.timing_options.clone());
+        config.insert("timing".into(), timing.into());
+        toml::to_string(&config).expect("cannot render config")
+    }
+
+    /// One section per run of consecutive data words.
+    fn data_sections(&self) -> Vec<Section> {
+        let mut sections: Vec<Section> = Vec::new();
+        for (&addr, &value) in &self.data {
+            match sections.last_mut() {
+                Some(section) if section.addr + section.bytes.len() as u32 == addr => {
+                    section.bytes.extend(value.to_le_bytes());
+                }
+                _ => sections.push(Section::data(addr, value.to_le_bytes().to_vec())),
+            }
+        }
+        sections
+    }
+}
+
+impl WorkloadRun {
+    pub fn read_word(&self, addr: u32) -> u32 {
+        u32::from_le_bytes(self.sim.top.gmem_load(addr.into()))
+    }
+
+    pub fn read_words(&self, addr: u32, count: usize) -> Vec<u32> {
+        (0..count)
+            .map(|idx| self.read_word(addr + 4 * idx as u32))
+            .collect()
+    }
+}
+
+struct Section {
+    name: &'static str,
+    flags: u32,
+    addr: u32,
+    bytes: Vec<u8>,
+}
+
+impl Section {
+    const ALLOC: u32 = 0x2;
+    const WRITE: u32 = 0x1;
+    const EXEC: u32 = 0x4;
+
+    fn text(addr: u32, bytes: Vec<u8>) -> Self {
+        Self {
+            name: ".text",
+            flags: Self::ALLOC | Self::EXEC,
+            addr,
+            bytes,
+        }
+    }
+
+    fn data(addr: u32, bytes: Vec<u8>) -> Self {
+        Self {
+            name: ".data",
+            flags: Self::ALLOC | Self::WRITE,
+            addr,
+            bytes,
+        }
+    }
+}
+
+/// Writes a minimal ELF32 RISC-V executable: section headers only, which is
+/// all `ElfBackedMem` reads.
+fn write_elf(path: &Path, sections: &[Section]) {
+    const EHDR_BYTES: u32 = 52;
+    const SHDR_BYTES: u32 = 40;
+    const SHT_PROGBITS: u32 = 1;
+    const SHT_STRTAB: u32 = 3;
+
+    let mut shstrtab = vec![0u8];
+    let mut name_offsets = Vec::new();
+    for name in sections
+        .iter()
+        .map(|section| section.name)
+        .chain([".shstrtab"])
+    {
+        name_offsets.push(shstrtab.len() as u32);
+        shstrtab.extend(name.as_bytes());
+        shstrtab.push(0);
+    }
+
+    let mut body = Vec::new();
+    let mut offsets = Vec::new();
+    for section in sections {
+        offsets.push(EHDR_BYTES + body.len() as u32);
+        body.extend(&section.bytes);
+    }
+    let shstrtab_offset = EHDR_BYTES + body.len() as u32;
+    body.extend(&shstrtab);
+    while body.len() % 4 != 0 {
+        body.push(0);
+    }
+    let shoff = EHDR_BYTES + body.len() as u32;
+    let shnum = sections.len() as u16 + 2;
+
+    let mut elf = Vec::new();
+    let u16le = |elf: &mut Vec<u8>, value: u16| elf.extend(value.to_le_bytes());
+    let u32le = |elf: &mut Vec<u8>, value: u32| elf.extend(value.to_le_bytes());
+    elf.extend([0x7f, b'E', b'L', b'F', 1, 1, 1, 0]);
+    elf.extend([0; 8]);
+    u16le(&mut elf, 2); // ET_EXEC
+    u16le(&mut elf, 243); // EM_RISCV
+    u32le(&mut elf, 1);
+    u32le(&mut elf, TEXT_BASE);
+    u32le(&mut elf, 0); // no program headers
+    u32le(&mut elf, shoff);
+    u32le(&mut elf, 0);
+    u16le(&mut elf, EHDR_BYTES as u16);
+    u16le(&mut elf, 32);
+    u16le(&mut elf, 0);
+    u16le(&mut elf, SHDR_BYTES as u16);
+    u16le(&mut elf, shnum);
+    u16le(&mut elf, shnum - 1);
+    elf.extend(body);
+
+    let mut header = |name: u32, kind: u32, flags: u32, addr: u32, offset: u32, size: u32| {
+        for field in [name, kind, flags, addr, offset, size, 0, 0, 1, 0] {
+            u32le(&mut elf, field);
+        }
+    };
+    header(0, 0, 0, 0, 0, 0);
+    for ((section, &name), &offset) in sections.iter().zip(&name_offsets).zip(&offsets) {
+        header(
+            name,
+            SHT_PROGBITS,
+            section.flags,
+            section.addr,
+            offset,
+            section.bytes.len() as u32,
+        );
+    }
+    header(
+        name_offsets[sections.len()],
+        SHT_STRTAB,
+        0,
+        0,
+        shstrtab_offset,
+        shstrtab.len() as u32,
+    );
+    fs::write(path, elf).expect("cannot write workload ELF");
+}
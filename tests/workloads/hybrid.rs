@@ -0,0 +1,53 @@
+use crate::harness::{Workload, THREAD_ID_CSR};
+
+const OUT: u32 = 0x2000_0000;
+const LANES: u32 = 4;
+
+/// Each lane sums 1..=64 and stores the result to its own word.
+fn sum_source() -> String {
+    format!(
+        "
+            li t0, -1
+            vx_tmc t0
+            csrr t1, {THREAD_ID_CSR}
+            slli t1, t1, 2
+            li t2, 0
+            li t3, 1
+            li t4, 65
+        loop:
+            add t2, t2, t3
+            addi t3, t3, 1
+            blt t3, t4, loop
+            li t5, {OUT}
+            add t5, t5, t1
+            sw t2, 0(t5)
+            li a0, 0
+            ecall
+        "
+    )
+}
+
+#[test]
+fn functional_cores_run_beside_timed_ones() {
+    let run = Workload::new("hybrid", sum_source())
+        .lanes(LANES as usize)
+        .cores(2)
+        .sim_option("timed_cores", vec![1i64])
+        .run();
+
+    assert_eq!(
+        run.read_words(OUT, LANES as usize),
+        vec![2080; LANES as usize]
+    );
+    let summaries: Vec<_> = run.sim.top.clusters[0]
+        .cores
+        .iter()
+        .map(|core| core.timing_summary())
+        .collect();
+    assert!(summaries[0].functional);
+    assert!(!summaries[1].functional);
+    assert_eq!(summaries[0].core_id, 0);
+    // 7 setup, 64 iterations of 3 and 5 to store and exit.
+    assert_eq!(summaries[0].cpi_stack.instructions, 204);
+    assert!(summaries[1].cpi_stack.instructions > 0);
+}
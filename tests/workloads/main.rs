@@ -0,0 +1,15 @@
+//! End-to-end workloads: small kernels assembled at test time and run
+//! through the full simulator with timing enabled. Each checks its results
+//! in gmem and that its cycle count stays inside a coarse envelope, so a
+//! change in any layer that breaks or badly slows a kernel shows up here.
+
+mod asm;
+mod checkpoint;
+mod diff;
+mod harness;
+mod hybrid;
+mod memory;
+mod perf_counters;
+mod run_until;
+mod vecadd;
+mod warp_ops;
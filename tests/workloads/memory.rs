@@ -0,0 +1,424 @@
+use crate::harness::{Workload, WorkloadRun, THREAD_ID_CSR};
+
+const RESULT: u32 = 0x2f00_0000;
+
+/// One lane follows a ring of `nodes` pointers spaced `spacing` bytes
+/// apart for `steps` loads and stores where it ended up.
+fn pointer_chase(name: &str, nodes: u32, spacing: u32, steps: u32) -> (WorkloadRun, u32) {
+    pointer_chase_with(name, nodes, spacing, steps, |workload| workload)
+}
+
+/// `pointer_chase` with `configure` applied to the workload before it runs.
+fn pointer_chase_with(
+    name: &str,
+    nodes: u32,
+    spacing: u32,
+    steps: u32,
+    configure: impl FnOnce(Workload) -> Workload,
+) -> (WorkloadRun, u32) {
+    const HEAD: u32 = 0x3000_0000;
+    let source = format!(
+        "
+            li t0, {HEAD}
+            li t1, {steps}
+        chase:
+            lw t0, 0(t0)
+            addi t1, t1, -1
+            bnez t1, chase
+            li t2, {RESULT}
+            sw t0, 0(t2)
+            li a0, 0
+            ecall
+        "
+    );
+    let node = |idx: u32| HEAD + (idx % nodes) * spacing;
+    let workload = (0..nodes).fold(Workload::new(name, source).lanes(1), |workload, idx| {
+        workload.word(node(idx), node(idx + 1))
+    });
+    (configure(workload).run(), node(steps))
+}
+
+#[test]
+fn pointer_chase_latency_grows_with_footprint() {
+    const STEPS: u32 = 64;
+    // Eight lines that stay cached after the first lap.
+    let (small, end) = pointer_chase("chase_small", 8, 64, STEPS);
+    assert_eq!(small.read_word(RESULT), end);
+    // Lines 4 KiB apart fold onto a few sets at every level and keep
+    // missing to DRAM.
+    let (large, end) = pointer_chase("chase_large", 256, 4096, STEPS);
+    assert_eq!(large.read_word(RESULT), end);
+
+    assert!(
+        large.cycles > 2 * small.cycles,
+        "large footprint took {} cycles, small {}",
+        large.cycles,
+        small.cycles
+    );
+    assert!(
+        (4_000..64_000).contains(&large.cycles),
+        "large chase took {} cycles",
+        large.cycles
+    );
+}
+
+#[test]
+fn stalled_dram_delays_misses_without_losing_them() {
+    const WINDOW: i64 = 5_000;
+    let (plain, end) = pointer_chase("chase_unstalled", 16, 4096, 16);
+    let (stalled, stalled_end) = pointer_chase_with("chase_stalled", 16, 4096, 16, |workload| {
+        let mut stall = toml::Table::new();
+        stall.insert("node".into(), "dram".into());
+        stall.insert("start".into(), 0.into());
+        stall.insert("cycles".into(), WINDOW.into());
+        workload.timing_option("gmem.stall_injection", vec![toml::Value::from(stall)])
+    });
+    assert_eq!(plain.read_word(RESULT), end);
+    assert_eq!(stalled.read_word(RESULT), stalled_end);
+    assert!(
+        stalled.cycles > plain.cycles.max(WINDOW as u64),
+        "stalled chase took {} cycles, unstalled {}",
+        stalled.cycles,
+        plain.cycles
+    );
+    let gmem = stalled.sim.top.gmem_timing.as_ref().unwrap();
+    let stalls = gmem.read().unwrap().injected_stalls();
+    assert!(stalls[0].rejected > 0, "{stalls:?}");
+}
+
+/// Every lane sums `ITERS` words, lane `l` of iteration `i` reading
+/// `base + (i * 16 + l) * stride`.
+fn stride_sweep(stride: u32) -> WorkloadRun {
+    const BASE: u32 = 0x4000_0000;
+    const OUT: u32 = 0x2e00_0000;
+    const LANES: u32 = 16;
+    const ITERS: u32 = 8;
+    let source = format!(
+        "
+            li t0, -1
+            vx_tmc t0
+            csrr t1, {THREAD_ID_CSR}
+            li t2, {stride}
+            mul t3, t1, t2
+            li t4, {BASE}
+            add t3, t3, t4          # this lane's first address
+            li t5, {step}
+            li t6, {ITERS}
+            li s0, 0
+        sweep:
+            lw s1, 0(t3)
+            add s0, s0, s1
+            add t3, t3, t5
+            addi t6, t6, -1
+            bnez t6, sweep
+            slli t1, t1, 2
+            li t2, {OUT}
+            add t2, t2, t1
+            sw s0, 0(t2)
+            li a0, 0
+            ecall
+        ",
+        step = LANES * stride,
+    );
+    let run = (0..ITERS * LANES)
+        .fold(
+            Workload::new(&format!("stride_{stride}"), source).lanes(LANES as usize),
+            |workload, idx| workload.word(BASE + idx * stride, idx),
+        )
+        .run();
+    for lane in 0..LANES {
+        let expected: u32 = (0..ITERS).map(|iter| iter * LANES + lane).sum();
+        assert_eq!(run.read_word(OUT + 4 * lane), expected, "lane {lane}");
+    }
+    run
+}
+
+#[test]
+fn stride_sweep_slows_once_lanes_leave_the_line() {
+    // Sixteen words share a line at stride 4; at stride 64 every lane
+    // touches its own line.
+    let coalesced = stride_sweep(4);
+    let scattered = stride_sweep(64);
+    assert!(
+        2 * scattered.cycles > 3 * coalesced.cycles,
+        "stride 4 took {} cycles, stride 64 {}",
+        coalesced.cycles,
+        scattered.cycles
+    );
+    assert!(
+        (1_000..20_000).contains(&scattered.cycles),
+        "stride 64 took {} cycles",
+        scattered.cycles
+    );
+}
+
+#[test]
+fn smem_transpose_round_trips_through_shared_memory() {
+    const DIM: u32 = 16;
+    const SRC: u32 = 0x2000_0000;
+    const DST: u32 = 0x2100_0000;
+    // Lane `c` copies column `c` of each source row into row `c` of a tile
+    // in shared memory, then copies the tile's rows out to the destination.
+    let source = format!(
+        "
+            li t0, -1
+            vx_tmc t0
+            csrr t1, {THREAD_ID_CSR}
+            slli t2, t1, 2          # column byte offset
+            li t3, {row_bytes}
+            mul t4, t1, t3          # tile row byte offset
+            li s0, {SRC}
+            add s0, s0, t2
+            li s1, 0
+            add s1, s1, t4
+            li t6, {DIM}
+        load_rows:
+            lw s2, 0(s0)
+            sw.shared s2, 0(s1)
+            add s0, s0, t3
+            addi s1, s1, 4
+            addi t6, t6, -1
+            bnez t6, load_rows
+            li s0, 0
+            add s0, s0, t2
+            li s1, {DST}
+            add s1, s1, t2
+            li t6, {DIM}
+        store_rows:
+            lw.shared s2, 0(s0)
+            sw s2, 0(s1)
+            add s0, s0, t3
+            add s1, s1, t3
+            addi t6, t6, -1
+            bnez t6, store_rows
+            li a0, 0
+            ecall
+        ",
+        row_bytes = DIM * 4,
+    );
+    let matrix: Vec<u32> = (0..DIM * DIM).collect();
+    let run = Workload::new("smem_transpose", source)
+        .lanes(DIM as usize)
+        .words(SRC, &matrix)
+        .run();
+
+    let out = run.read_words(DST, (DIM * DIM) as usize);
+    for row in 0..DIM {
+        for col in 0..DIM {
+            assert_eq!(
+                out[(row * DIM + col) as usize],
+                matrix[(col * DIM + row) as usize],
+                "dst[{row}][{col}]"
+            );
+        }
+    }
+    assert!(
+        (2_000..40_000).contains(&run.cycles),
+        "transpose took {} cycles",
+        run.cycles
+    );
+}
+
+/// One lane writes `WORDS` consecutive words, then sums them back.
+fn store_then_sum(store_buffer: bool) -> WorkloadRun {
+    const BUF: u32 = 0x2c00_0000;
+    const WORDS: u32 = 16;
+    let source = format!(
+        "
+            li t0, {BUF}
+            li t1, {WORDS}
+        fill:
+            sw t1, 0(t0)
+            addi t0, t0, 4
+            addi t1, t1, -1
+            bnez t1, fill
+            li t0, {BUF}
+            li t1, {WORDS}
+            li s0, 0
+        sum:
+            lw s1, 0(t0)
+            add s0, s0, s1
+            addi t0, t0, 4
+            addi t1, t1, -1
+            bnez t1, sum
+            li t2, {RESULT}
+            sw s0, 0(t2)
+            li a0, 0
+            ecall
+        "
+    );
+    let name = if store_buffer {
+        "store_buffer"
+    } else {
+        "no_store_buffer"
+    };
+    let run = Workload::new(name, source)
+        .lanes(1)
+        .timing_option("lsu.store_buffer.enabled", store_buffer)
+        // Long enough for the whole fill to merge into one entry that is
+        // still buffered when the reads start.
+        .timing_option("lsu.store_buffer.coalesce_window", 256)
+        .run();
+    assert_eq!(run.read_word(RESULT), (1..=WORDS).sum::<u32>());
+    run
+}
+
+#[test]
+fn store_buffer_merges_line_stores_and_forwards_reads() {
+    let plain = store_then_sum(false);
+    let buffered = store_then_sum(true);
+    let stats = buffered.sim.top.clusters[0].cores[0]
+        .timing_summary()
+        .store_buffer;
+    // The fill's sixteen stores share one line; the final result store
+    // takes a second entry.
+    assert_eq!(stats.stores, 17, "{stats:?}");
+    assert_eq!(stats.merged, 15, "{stats:?}");
+    assert!(stats.forwarded_loads > 0, "{stats:?}");
+    assert!(
+        buffered.cycles < plain.cycles,
+        "buffered took {} cycles, unbuffered {}",
+        buffered.cycles,
+        plain.cycles
+    );
+    assert_eq!(
+        plain.sim.top.clusters[0].cores[0]
+            .timing_summary()
+            .store_buffer
+            .stores,
+        0
+    );
+}
+
+/// Both single-warp cores store their lane's id to `RESULT` and load it
+/// back, with nothing ordering them.
+fn shared_word(atomics: &[u32]) -> WorkloadRun {
+    let source = format!(
+        "
+            csrr t1, {THREAD_ID_CSR}
+            li t0, {RESULT}
+            sw t1, 0(t0)
+            lw t2, 0(t0)
+            li a0, 0
+            ecall
+        "
+    );
+    let mut race = toml::Table::new();
+    race.insert("check".into(), "count".into());
+    let atomics: Vec<toml::Value> = atomics.iter().map(|&addr| (addr as i64).into()).collect();
+    race.insert("atomics".into(), atomics.into());
+    Workload::new("gmem_race", source)
+        .lanes(1)
+        .cores(2)
+        .sim_option("gmem_race", race)
+        .run()
+}
+
+#[test]
+fn gmem_race_check_flags_cores_sharing_a_word() {
+    let stats = shared_word(&[]).sim.gmem_race_stats().unwrap();
+    assert!(stats.races > 0, "{stats:?}");
+    let race = stats.first_races[0];
+    assert_eq!(race.address, RESULT as u64);
+    assert_ne!(race.accessor.core, race.writer.core);
+
+    let stats = shared_word(&[RESULT]).sim.gmem_race_stats().unwrap();
+    assert_eq!((stats.stores, stats.races), (2, 0));
+}
+
+#[test]
+fn co_running_kernels_are_counted_per_context() {
+    let source = format!(
+        "
+            csrr t1, {THREAD_ID_CSR}
+            slli t1, t1, 2
+            li t0, {RESULT}
+            add t0, t0, t1
+            sw t1, 0(t0)
+            lw t2, 0(t0)
+            li a0, 0
+            ecall
+        "
+    );
+    let run = Workload::new("contexts", source)
+        .lanes(4)
+        .co_kernel("second", &[1])
+        .run();
+    // Kernel 0 is the `[[sim.kernels]]` entry on cluster 1; cluster 0
+    // runs `sim.elf` as kernel 1.
+    let summaries = run.sim.timing_summaries();
+    assert_eq!(summaries.len(), 2);
+    for summary in &summaries {
+        let context = 1 - summary.cluster_id;
+        let stats = &summary.context_mem;
+        assert_eq!(stats.len(), context + 1, "{stats:?}");
+        assert_eq!(
+            stats[context].latencies.gmem_count,
+            summary.latencies.gmem_count
+        );
+        assert!(stats[context].gmem_bytes > 0);
+        assert!(stats[..context]
+            .iter()
+            .all(|other| other.latencies.gmem_count == 0));
+    }
+}
+
+const DATA: u32 = 0x2000_0000;
+
+#[test]
+fn misaligned_accesses_are_emulated_when_configured() {
+    let source = format!(
+        "
+            li t0, {DATA}
+            lw t1, 1(t0)
+            lh t2, 3(t0)
+            lh t3, 7(t0)
+            li t4, 0xaabbccdd
+            sw t4, 6(t0)
+            li t5, {RESULT}
+            sw t1, 0(t5)
+            sw t2, 4(t5)
+            sw t3, 8(t5)
+            li a0, 0
+            ecall
+        "
+    );
+    let run = Workload::new("misaligned", source)
+        .lanes(1)
+        .words(DATA, &[0x4433_2211, 0x8877_6655, 0xcc])
+        .muon_option("misaligned", "emulate")
+        .run();
+    assert_eq!(
+        run.read_words(RESULT, 3),
+        vec![0x5544_3322, 0x5544, 0xffff_cc88]
+    );
+    assert_eq!(run.read_words(DATA + 4, 2), vec![0xccdd_6655, 0xaabb]);
+}
+
+#[test]
+fn big_endian_loads_and_stores_reverse_bytes() {
+    let source = format!(
+        "
+            li t0, {DATA}
+            lw t1, 0(t0)
+            lhu t2, 2(t0)
+            li t3, 0xaabbccdd
+            sw t3, 4(t0)
+            li t4, {RESULT}
+            sw t1, 0(t4)
+            sw t2, 4(t4)
+            li a0, 0
+            ecall
+        "
+    );
+    let run = Workload::new("big_endian", source)
+        .lanes(1)
+        .word(DATA, 0x4433_2211)
+        .muon_option("endianness", "big")
+        .run();
+    // `read_word` is little-endian, so big-endian words read back swapped.
+    let word = |addr: u32| run.read_word(addr).swap_bytes();
+    assert_eq!(word(RESULT), 0x1122_3344);
+    assert_eq!(word(RESULT + 4), 0x3344);
+    assert_eq!(word(DATA + 4), 0xaabb_ccdd);
+}
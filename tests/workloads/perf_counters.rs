@@ -0,0 +1,141 @@
+use crate::harness::Workload;
+
+const OUT: u32 = 0x2d00_0000;
+const DATA: u32 = 0x3000_0000;
+const LOOP_ITERS: u32 = 10;
+
+/// Reads `cycle` twice back to back and `instret` around a countdown loop,
+/// then waits for a cold load to miss in L1 and reads the cycle and stall
+/// counters. Stores `s0` to `s6`, the last holding the L1 miss count.
+fn counters_source() -> String {
+    format!(
+        "
+            csrr s0, 0xc00
+            csrr s1, 0xc00
+            csrr s2, 0xc02
+            li t0, {LOOP_ITERS}
+        countdown:
+            addi t0, t0, -1
+            bnez t0, countdown
+            csrr s3, 0xc02
+            li t1, {DATA}
+            lw t2, 0(t1)
+            # Loads don't block the warp; poll until this one has missed
+            # in L1, giving up after a while.
+            li t3, 1000
+        wait_miss:
+            csrr s6, 0xc06
+            bnez s6, missed
+            addi t3, t3, -1
+            bnez t3, wait_miss
+        missed:
+            csrr s4, 0xc00
+            csrr s5, 0xc09
+            li t1, {OUT}
+            sw s0, 0(t1)
+            sw s1, 4(t1)
+            sw s2, 8(t1)
+            sw s3, 12(t1)
+            sw s4, 16(t1)
+            sw s5, 20(t1)
+            sw s6, 24(t1)
+            li a0, 0
+            ecall
+        "
+    )
+}
+
+fn run(name: &str, enabled: bool, mode: &str) -> Vec<u32> {
+    let run = Workload::new(name, counters_source())
+        .lanes(1)
+        .word(DATA, 7)
+        .timing_option("perf_counters.enabled", enabled)
+        .timing_option("perf_counters.mode", mode)
+        .run();
+    run.read_words(OUT, 7)
+}
+
+#[test]
+fn counters_measure_a_region() {
+    let counters = run("perf_counters", true, "continue");
+    let [first, second, instret_before, instret_after, cycle_after, stalls, l1_misses] =
+        counters[..]
+    else {
+        unreachable!()
+    };
+    assert!(second > first, "back-to-back cycle reads {first}, {second}");
+    // The second `instret` read, `li` and the loop body.
+    assert_eq!(instret_after - instret_before, 1 + 1 + 2 * LOOP_ITERS);
+    assert!(cycle_after - first >= 2 * LOOP_ITERS);
+    assert_eq!(l1_misses, 1);
+    // Cold icache misses leave the warp waiting on fetch.
+    assert!(stalls > 0);
+}
+
+#[test]
+fn freeze_mode_holds_back_to_back_reads() {
+    let counters = run("perf_counters_freeze", true, "freeze");
+    // Both cycle reads and the first `instret` read see the first read's
+    // values.
+    assert_eq!(counters[0], counters[1]);
+    assert_eq!(counters[3] - counters[2], 3 + 1 + 2 * LOOP_ITERS);
+    assert!(counters[4] > counters[0]);
+}
+
+#[test]
+fn disabled_counters_read_zero() {
+    assert_eq!(run("perf_counters_off", false, "continue"), vec![0; 7]);
+}
+
+#[test]
+fn warp_counters_split_instret_by_class() {
+    let source = format!(
+        "
+            li t0, {LOOP_ITERS}
+        countdown:
+            addi t0, t0, -1
+            bnez t0, countdown
+            li t1, {DATA}
+            lw t2, 0(t1)
+            csrr s0, 0xc02
+            csrr s1, 0xc0a
+            csrr s2, 0xc0b
+            csrr s3, 0xc0c
+            csrr s4, 0xc0d
+            csrr s5, 0xc0e
+            csrr s6, 0xc0f
+            csrr s7, 0xc10
+            li t1, {OUT}
+            sw s0, 0(t1)
+            sw s1, 4(t1)
+            sw s2, 8(t1)
+            sw s3, 12(t1)
+            sw s4, 16(t1)
+            sw s5, 20(t1)
+            sw s6, 24(t1)
+            sw s7, 28(t1)
+            li a0, 0
+            ecall
+        "
+    );
+    // Freeze so every read sees the same snapshot.
+    let run = Workload::new("perf_counters_warp", source)
+        .lanes(1)
+        .word(DATA, 7)
+        .timing_option("perf_counters.mode", "freeze")
+        .run();
+    let [instret, alu, fpu, load, store, branch, sfu, active_cycles] = run.read_words(OUT, 8)[..]
+    else {
+        unreachable!()
+    };
+    assert_eq!(alu + fpu + load + store + branch + sfu, instret);
+    assert_eq!((fpu, load, store, branch, sfu), (0, 1, 0, LOOP_ITERS, 0));
+    assert!(alu > LOOP_ITERS);
+    assert!(active_cycles >= instret);
+
+    let summary = &run.sim.timing_summaries()[0];
+    let warp = summary.warp_retire[0];
+    // The report adds the stores of the results and the exit `ecall`.
+    assert_eq!((warp.load, warp.store, warp.sfu), (1, 8, 1));
+    assert_eq!(warp.branch, LOOP_ITERS as u64);
+}
@@ -0,0 +1,104 @@
+use cyclotron::sim::top::{Event, EventSource, RunOutcome};
+
+use crate::harness::{Workload, TEXT_BASE};
+
+/// One lane counts down from `ITERS`, storing each count.
+fn countdown() -> Workload {
+    const ITERS: u32 = 4;
+    const OUT: u32 = 0x2d00_0000;
+    let source = format!(
+        "
+            li t0, {ITERS}
+            li t1, {OUT}
+        again:
+            sw t0, 0(t1)
+            addi t0, t0, -1
+            bnez t0, again
+            li a0, 0
+            ecall
+        "
+    );
+    Workload::new("countdown", source).lanes(1)
+}
+
+#[test]
+fn steps_from_one_event_to_the_next() {
+    // `again` is the third instruction.
+    let loop_pc = TEXT_BASE + 2 * 8;
+    let mut sim = countdown().build();
+
+    let mut arrivals = Vec::new();
+    loop {
+        match sim.run_until(Event::WarpAtPc(loop_pc)) {
+            RunOutcome::Fired { source, cycle, .. } => {
+                assert!(matches!(source, EventSource::Warp { warp_id: 0, .. }));
+                arrivals.push(cycle);
+            }
+            RunOutcome::Finished { .. } => break,
+            RunOutcome::TimedOut { cycle } => panic!("timed out at cycle {cycle}"),
+        }
+    }
+    assert_eq!(arrivals.len(), 4, "arrivals at cycles {arrivals:?}");
+    assert!(arrivals.windows(2).all(|pair| pair[0] < pair[1]));
+    assert!(sim.finished());
+    assert_eq!(
+        sim.run_until(Event::NextKernelBoundary),
+        RunOutcome::Finished { cycle: sim.cycle() }
+    );
+}
+
+#[test]
+fn stops_at_cycles_and_kernel_boundaries() {
+    let mut sim = countdown().build();
+    assert_eq!(
+        sim.run_until(Event::CycleReached(10)),
+        RunOutcome::Fired {
+            event: Event::CycleReached(10),
+            source: EventSource::Sim,
+            cycle: 10,
+        }
+    );
+    let RunOutcome::Fired { source, cycle, .. } = sim.run_until(Event::NextKernelBoundary) else {
+        panic!("kernel never finished");
+    };
+    assert_eq!(source, EventSource::Kernel(0));
+    assert_eq!(Some(cycle), sim.kernel_finished_at(0));
+}
+
+#[test]
+fn stops_when_a_gmem_request_completes() {
+    let mut sim = countdown().build();
+    // Request ids start at 1.
+    let RunOutcome::Fired { source, .. } = sim.run_until(Event::CompletionOfRequest(1)) else {
+        panic!("the first store never completed");
+    };
+    assert_eq!(
+        source,
+        EventSource::Core {
+            cluster_id: 0,
+            core_id: 0
+        }
+    );
+}
+
+#[test]
+fn drains_in_flight_work_within_the_budget() {
+    let mut sim = countdown().build();
+    sim.run_until(Event::CycleReached(12));
+    sim.config.drain_cycles = 0;
+    let timeout = sim.drain_all().expect_err("the kernel has work in flight");
+    assert_eq!(timeout.cores, [(0, 0)]);
+    assert_eq!(timeout.cycle, 12);
+
+    sim.config.drain_cycles = 1000;
+    let snapshot = sim.drain_all().expect("drains");
+    assert!(snapshot.drained_at > snapshot.started_at);
+    assert_eq!(snapshot.drained_at, sim.cycle());
+    assert_eq!(snapshot.summaries.len(), 1);
+    assert_eq!(sim.drain_all().unwrap().drained_at, snapshot.drained_at);
+    // The kernel picks up where it stopped.
+    assert!(matches!(
+        sim.run_until(Event::NextKernelBoundary),
+        RunOutcome::Fired { .. }
+    ));
+}
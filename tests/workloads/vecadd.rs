@@ -0,0 +1,79 @@
+use crate::harness::{Workload, THREAD_ID_CSR};
+
+pub const A: u32 = 0x2000_0000;
+pub const B: u32 = 0x2000_1000;
+pub const C: u32 = 0x2000_2000;
+pub const N: u32 = 256;
+pub const LANES: u32 = 16;
+
+pub fn vecadd_source() -> String {
+    format!(
+        "
+            li t0, -1
+            vx_tmc t0
+            csrr t1, {THREAD_ID_CSR}
+            slli t2, t1, 2          # byte offset of this lane's element
+            li s0, {A}
+            li s1, {B}
+            li s2, {C}
+            li s3, {end}
+        loop:
+            add t3, s0, t2
+            lw t4, 0(t3)
+            add t3, s1, t2
+            lw t5, 0(t3)
+            add t6, t4, t5
+            add t3, s2, t2
+            sw t6, 0(t3)
+            addi t2, t2, {stride}
+            blt t2, s3, loop
+            li a0, 0
+            ecall
+        ",
+        end = N * 4,
+        stride = LANES * 4,
+    )
+}
+
+#[test]
+fn vecadd_sums_every_element() {
+    let a: Vec<u32> = (0..N).collect();
+    let b: Vec<u32> = (0..N).map(|i| 1000 * i + 7).collect();
+    let run = Workload::new("vecadd", vecadd_source())
+        .lanes(LANES as usize)
+        .words(A, &a)
+        .words(B, &b)
+        .run();
+
+    let c = run.read_words(C, N as usize);
+    for (i, &value) in c.iter().enumerate() {
+        assert_eq!(value, a[i] + b[i], "c[{i}]");
+    }
+    // 16 iterations of two loads and a store per lane.
+    assert!(
+        (2_000..32_000).contains(&run.cycles),
+        "vecadd took {} cycles",
+        run.cycles
+    );
+}
+
+#[test]
+fn fast_mode_keeps_the_cycle_count() {
+    let a: Vec<u32> = (0..N).collect();
+    let workload = || {
+        Workload::new("vecadd_fast", vecadd_source())
+            .lanes(LANES as usize)
+            .words(A, &a)
+            .words(B, &a)
+    };
+    let full = workload().run();
+    let fast = workload().sim_option("fast", true).run();
+
+    assert_eq!(fast.cycles, full.cycles);
+    assert_eq!(
+        fast.read_words(C, N as usize),
+        full.read_words(C, N as usize)
+    );
+    let summary = &fast.sim.timing_summaries()[0];
+    assert_eq!(summary.smem_util.cycles, 0, "fast mode still sampled stats");
+}
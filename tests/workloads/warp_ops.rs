@@ -0,0 +1,102 @@
+use crate::harness::{Workload, THREAD_ID_CSR};
+
+const OUT: u32 = 0x2000_0000;
+const LANES: u32 = 8;
+/// Results of one op for every lane, then the next op's.
+const ROW: u32 = LANES * 4;
+
+/// Each op's result lands in its own row of `OUT`, one word per lane.
+fn warp_ops_source() -> String {
+    format!(
+        "
+            li t0, -1
+            vx_tmc t0
+            csrr t1, {THREAD_ID_CSR}
+            slli s0, t1, 2
+            li t2, {OUT}
+            add s0, s0, t2          # this lane's word in row 0
+            andi t3, t1, 1
+            li t4, 5
+            li t5, 1
+            li t6, 2
+            li s1, 3
+            vx_vote.all a1, t1
+            sw a1, 0(s0)
+            vx_vote.any a1, t1
+            sw a1, {r1}(s0)
+            vx_vote.uni a1, t4
+            sw a1, {r2}(s0)
+            vx_ballot a1, t3
+            sw a1, {r3}(s0)
+            vx_shfl.up a1, t1, t6
+            sw a1, {r4}(s0)
+            vx_shfl.down a1, t1, t5
+            sw a1, {r5}(s0)
+            vx_shfl.bfly a1, t1, t5
+            sw a1, {r6}(s0)
+            vx_shfl.idx a1, t1, s1
+            sw a1, {r7}(s0)
+            # Butterfly reduction of the lane ids.
+            mv a2, t1
+            li t2, 4
+        reduce:
+            vx_shfl.bfly a3, a2, t2
+            add a2, a2, a3
+            srli t2, t2, 1
+            bnez t2, reduce
+            sw a2, {r8}(s0)
+            li a0, 0
+            ecall
+        ",
+        r1 = ROW,
+        r2 = 2 * ROW,
+        r3 = 3 * ROW,
+        r4 = 4 * ROW,
+        r5 = 5 * ROW,
+        r6 = 6 * ROW,
+        r7 = 7 * ROW,
+        r8 = 8 * ROW,
+    )
+}
+
+fn run(shuffle_latency: i64) -> crate::harness::WorkloadRun {
+    Workload::new("warp_ops", warp_ops_source())
+        .lanes(LANES as usize)
+        .timing_option("execute.shuffle.base_latency", shuffle_latency)
+        .run()
+}
+
+#[test]
+fn votes_and_shuffles_cross_lanes() {
+    let run = run(2);
+    let row = |idx: u32| run.read_words(OUT + idx * ROW, LANES as usize);
+    let lanes = LANES as usize;
+
+    // Lane 0's id is zero, so not every lane votes true.
+    assert_eq!(row(0), vec![0; lanes]);
+    assert_eq!(row(1), vec![1; lanes]);
+    assert_eq!(row(2), vec![1; lanes]);
+    assert_eq!(row(3), vec![0xaa; lanes]);
+    // Lanes whose source is out of range keep their own value.
+    assert_eq!(row(4), vec![0, 1, 0, 1, 2, 3, 4, 5]);
+    assert_eq!(row(5), vec![1, 2, 3, 4, 5, 6, 7, 7]);
+    assert_eq!(row(6), vec![1, 0, 3, 2, 5, 4, 7, 6]);
+    assert_eq!(row(7), vec![3; lanes]);
+    assert_eq!(row(8), vec![28; lanes]);
+
+    if cyclotron::timeflow::STATS_COMPILED {
+        let summary = run.sim.top.clusters[0].cores[0].timing_summary();
+        assert!(summary.execute_util.shuffle_busy_sum > 0);
+    }
+}
+
+#[test]
+fn shuffle_latency_is_timed() {
+    let fast = run(2).cycles;
+    let slow = run(40).cycles;
+    // Eleven vote and shuffle ops, each waited on by the next instruction.
+    assert!(
+        slow >= fast + 11 * 30,
+        "shuffle latency 40 took {slow} cycles, latency 2 took {fast}"
+    );
+}